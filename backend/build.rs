@@ -1,20 +1,15 @@
-use std::env; // Needed for OUT_DIR
+// klyja/backend/build.rs
 use std::io::Result;
-use std::path::PathBuf; // Needed for path joining
 
 fn main() -> Result<()> {
-    println!("cargo:rerun-if-changed=../protobuf/AnimationData.proto");
+    println!("cargo:rerun-if-changed=../protobuf/AnimationService.proto");
     println!("cargo:rerun-if-changed=build.rs");
 
-    // Get the Cargo OUT_DIR environment variable
-    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR not set"));
-
-    prost_build::Config::new()
-        // Specify the output directory for generated code
-        .out_dir(&out_dir) // Use OUT_DIR
-        // Compile the .proto file
-        .compile_protos(&["../protobuf/AnimationData.proto"], // Path relative to build.rs
-                        &["../protobuf/"]) // Include path
-        ?;
+    tonic_build::configure()
+        // The server is all the backend needs; pipelines generate their own clients
+        .build_client(false)
+        // Document messages come from the shared crate, not a second copy of them
+        .extern_path(".klyja.map_animation.v1", "::klyja_proto")
+        .compile(&["../protobuf/AnimationService.proto"], &["../protobuf/"])?;
     Ok(())
 }
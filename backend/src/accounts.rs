@@ -0,0 +1,376 @@
+// klyja/backend/src/accounts.rs
+// Local accounts: email and password sign-in for deployments that can't use an OAuth
+// provider. Signing in creates an ordinary row in `sessions`, so everything behind
+// `AuthUser` works the same whichever way the user signed in.
+use crate::{
+    devices::{self, ClientInfo},
+    errors::AppError,
+    models::{
+        Account, ConfirmPasswordResetRequest, LoginRequest, PasswordResetRequest, RegisterRequest,
+    },
+    services::content_sha256,
+    DbPool,
+};
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use rand_core::{OsRng, RngCore};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Shortest password local accounts accept.
+pub const MIN_PASSWORD_CHARS: usize = 8;
+/// Longest password accepted, so hashing can't be used to tie up the server.
+pub const MAX_PASSWORD_CHARS: usize = 1024;
+/// How long a sign-in lasts.
+pub const SESSION_TTL_DAYS: i32 = 30;
+/// How long a password reset token can be used.
+pub const PASSWORD_RESET_TTL_MINUTES: i32 = 60;
+
+/// Passes password reset tokens on to their users, typically by email. Klyja doesn't
+/// send mail itself; whoever runs it installs one with `set_reset_notifier`.
+pub trait ResetNotifier: Send + Sync {
+    /// Called once the token for `email` is stored; it must not block for long.
+    fn send_reset(&self, email: &str, token: &str);
+}
+
+static RESET_NOTIFIER: RwLock<Option<Arc<dyn ResetNotifier>>> = RwLock::new(None);
+
+/// Installs the notifier password reset tokens are given to, replacing any other.
+pub fn set_reset_notifier(notifier: Arc<dyn ResetNotifier>) {
+    *RESET_NOTIFIER
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = Some(notifier);
+}
+
+/// Writes each reset to its own JSON file (`email`, `token`) in a directory, readable
+/// only by the server's user, for a mail relay to pick up and delete.
+pub struct ResetOutbox {
+    pub dir: std::path::PathBuf,
+}
+
+impl ResetNotifier for ResetOutbox {
+    fn send_reset(&self, email: &str, token: &str) {
+        use std::io::Write;
+        #[cfg(unix)]
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = self
+            .dir
+            .join(format!("reset-{}.json", uuid::Uuid::new_v4()));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let body = serde_json::json!({ "email": email, "token": token }).to_string();
+        if let Err(err) = options
+            .open(&path)
+            .and_then(|mut file| file.write_all(body.as_bytes()))
+        {
+            tracing::error!("Couldn't write {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// Hashes a password as an Argon2id PHC string with a fresh salt.
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| AppError::Internal(format!("Hashing password failed: {}", err)))
+}
+
+/// Whether `password` matches a hash made by `hash_password`.
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash).is_ok_and(|parsed| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok()
+    })
+}
+
+/// A hash no password matches, checked for unknown emails so that a failed sign-in
+/// takes as long whether or not the account exists.
+fn dummy_password_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash_password("not a real password").unwrap_or_default())
+}
+
+/// Runs password hashing or checking on a blocking thread: Argon2 is slow on
+/// purpose, too slow to run on the async workers.
+async fn run_argon2<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, AppError> {
+    tokio::task::spawn_blocking(work).await.map_err(|join_err| {
+        AppError::Internal(format!("Tokio spawn_blocking join error: {}", join_err))
+    })
+}
+
+/// 32 random bytes as hex, the form of session and reset tokens.
+pub(crate) fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Trims and lowercases an email, rejecting text that can't be one.
+pub fn normalize_email(email: &str) -> Result<String, AppError> {
+    let email = email.trim().to_lowercase();
+    let valid = email.len() <= 255
+        && email
+            .split_once('@')
+            .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'));
+    if valid {
+        Ok(email)
+    } else {
+        Err(AppError::BadRequest(format!(
+            "'{}' is not a valid email address",
+            email
+        )))
+    }
+}
+
+fn check_password(password: &str) -> Result<(), AppError> {
+    let chars = password.chars().count();
+    if chars < MIN_PASSWORD_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "Passwords need at least {} characters",
+            MIN_PASSWORD_CHARS
+        )));
+    }
+    if chars > MAX_PASSWORD_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "Passwords can be at most {} characters",
+            MAX_PASSWORD_CHARS
+        )));
+    }
+    Ok(())
+}
+
+/// Signs the user in from `client`'s device, returning the token for the session
+/// cookie. Sign-ins from a device none of the user's sessions had are flagged.
+pub async fn create_session(
+    conn: &mut AsyncPgConnection,
+    user_id: i32,
+    client: &ClientInfo,
+) -> Result<String, AppError> {
+    use crate::schema::sessions;
+
+    let ip = client.stored_ip();
+    let user_agent = client.user_agent.as_deref();
+    let new_device = devices::is_new_device(conn, user_id, ip.as_deref(), user_agent).await?;
+    if new_device {
+        tracing::warn!(
+            "SERVICE: User {} signed in from a new device ({})",
+            user_id,
+            user_agent.unwrap_or("no user agent")
+        );
+    }
+    let token = random_token();
+    diesel::insert_into(sessions::table)
+        .values((
+            sessions::token.eq(&token),
+            sessions::user_id.eq(user_id),
+            sessions::expires_at.eq(now + SESSION_TTL_DAYS.days()),
+            sessions::user_agent.eq(user_agent),
+            sessions::ip.eq(&ip),
+            sessions::new_device.eq(new_device),
+        ))
+        .execute(conn)
+        .await?;
+    Ok(token)
+}
+
+pub struct AccountService;
+
+impl AccountService {
+    /// Creates a local account and signs it in. Returns the account and session token.
+    pub async fn register_logic(
+        pool: &DbPool,
+        request: RegisterRequest,
+        client: &ClientInfo,
+    ) -> Result<(Account, String), AppError> {
+        let username = request.username.trim().to_string();
+        if username.is_empty() || username.chars().count() > 255 {
+            return Err(AppError::BadRequest(
+                "Usernames need between 1 and 255 characters".to_string(),
+            ));
+        }
+        let email = normalize_email(&request.email)?;
+        check_password(&request.password)?;
+        tracing::info!("SERVICE: Registering local account '{}'", username);
+
+        let password_hash = run_argon2(move || hash_password(&request.password)).await??;
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::users;
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            async move {
+                let taken = users::table
+                    .filter(users::username.eq(&username).or(users::email.eq(&email)))
+                    .select((users::username, users::email))
+                    .first::<(String, Option<String>)>(conn)
+                    .await
+                    .optional()?;
+                if let Some((taken_username, _)) = taken {
+                    return Err(AppError::BadRequest(if taken_username == username {
+                        format!("The username '{}' is taken", username)
+                    } else {
+                        "An account with that email already exists".to_string()
+                    }));
+                }
+
+                let account = diesel::insert_into(users::table)
+                    .values((
+                        users::username.eq(&username),
+                        users::email.eq(&email),
+                        users::password_hash.eq(&password_hash),
+                    ))
+                    .returning((users::id, users::username, users::email))
+                    .get_result::<Account>(conn)
+                    .await?;
+                let token = create_session(conn, account.id, client).await?;
+                Ok((account, token))
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    /// Signs in to a local account. Returns the account and session token.
+    pub async fn login_logic(
+        pool: &DbPool,
+        request: LoginRequest,
+        client: &ClientInfo,
+    ) -> Result<(Account, String), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::users;
+
+        let email = request.email.trim().to_lowercase();
+        let found = users::table
+            .filter(users::email.eq(&email))
+            .filter(users::password_hash.is_not_null())
+            .select((
+                (users::id, users::username, users::email),
+                users::password_hash,
+            ))
+            .first::<(Account, Option<String>)>(&mut conn)
+            .await
+            .optional()?;
+        let (account, password_hash) = match found {
+            Some((account, Some(password_hash))) => (Some(account), password_hash),
+            _ => (None, dummy_password_hash().to_string()),
+        };
+
+        // Verify even without an account, so both failures take as long
+        let password_matches =
+            run_argon2(move || verify_password(&request.password, &password_hash)).await?;
+        match account {
+            Some(account) if password_matches => {
+                tracing::info!("SERVICE: User {} signed in with a password", account.id);
+                let token = create_session(&mut conn, account.id, client).await?;
+                Ok((account, token))
+            }
+            _ => Err(AppError::Unauthorized(
+                "Invalid email or password".to_string(),
+            )),
+        }
+    }
+
+    /// Issues a password reset token for the local account with this email, if any,
+    /// and hands it to the installed `ResetNotifier`. Tokens are never logged.
+    /// Callers can't tell whether the account existed.
+    pub async fn request_password_reset_logic(
+        pool: &DbPool,
+        request: PasswordResetRequest,
+    ) -> Result<(), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::{password_resets, users};
+
+        let email = request.email.trim().to_lowercase();
+        let user_id = users::table
+            .filter(users::email.eq(&email))
+            .filter(users::password_hash.is_not_null())
+            .select(users::id)
+            .first::<i32>(&mut conn)
+            .await
+            .optional()?;
+        let Some(user_id) = user_id else {
+            return Ok(());
+        };
+
+        let token = random_token();
+        diesel::insert_into(password_resets::table)
+            .values((
+                password_resets::token_sha256.eq(content_sha256(token.as_bytes())),
+                password_resets::user_id.eq(user_id),
+                password_resets::expires_at.eq(now + PASSWORD_RESET_TTL_MINUTES.minutes()),
+            ))
+            .execute(&mut conn)
+            .await?;
+        let notifier = RESET_NOTIFIER
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+        match notifier {
+            Some(notifier) => {
+                notifier.send_reset(&email, &token);
+                tracing::info!("SERVICE: Password reset issued for user {}", user_id);
+            }
+            None => tracing::warn!(
+                "SERVICE: Password reset issued for user {}, but nothing sends it (set PASSWORD_RESET_OUTBOX_DIR)",
+                user_id
+            ),
+        }
+        Ok(())
+    }
+
+    /// Sets a new password with a reset token. The user's other reset tokens and all
+    /// their sessions end, so whoever had the old password is signed out.
+    pub async fn confirm_password_reset_logic(
+        pool: &DbPool,
+        request: ConfirmPasswordResetRequest,
+    ) -> Result<(), AppError> {
+        check_password(&request.password)?;
+        let password = request.password;
+        let password_hash = run_argon2(move || hash_password(&password)).await??;
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::{password_resets, sessions, users};
+
+        let token_sha256 = content_sha256(request.token.trim().as_bytes());
+        conn.transaction::<_, AppError, _>(|conn| {
+            async move {
+                let user_id = password_resets::table
+                    .find(token_sha256)
+                    .filter(password_resets::expires_at.gt(now))
+                    .select(password_resets::user_id)
+                    .first::<i32>(conn)
+                    .await
+                    .optional()?
+                    .ok_or_else(|| {
+                        AppError::BadRequest("Reset token is invalid or expired".to_string())
+                    })?;
+
+                diesel::update(users::table.find(user_id))
+                    .set(users::password_hash.eq(&password_hash))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(password_resets::table.filter(password_resets::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(sessions::table.filter(sessions::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                tracing::info!("SERVICE: User {} reset their password", user_id);
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
@@ -0,0 +1,313 @@
+// klyja/backend/src/admin.rs
+// Operator tasks behind the `klyja-admin` binary: looking after users and their
+// animations without poking Postgres by hand. Nothing here checks permissions; it is
+// for whoever holds DATABASE_URL, not for the API.
+use crate::{
+    cache,
+    errors::AppError,
+    presence,
+    protobuf_gen::MapAnimation,
+    services::{decompress_document, measure_document, octet_length, run_in_transaction},
+    DbPool,
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::RunQueryDsl;
+use prost::Message;
+use std::collections::HashMap;
+
+/// Animations `measure_animations_logic` loads at a time.
+const MEASURE_BATCH: i64 = 100;
+
+/// A user as operators see them in `klyja-admin users`.
+#[derive(Debug)]
+pub struct UserOverview {
+    pub id: i32,
+    pub username: String,
+    pub email: Option<String>,
+    pub is_admin: bool,
+    pub created_at: NaiveDateTime,
+    pub animation_count: i64, // Animations they own
+    pub stored_bytes: i64, // Their documents' versions as stored, which count against their quota
+}
+
+pub struct AdminService;
+
+impl AdminService {
+    /// Every user, oldest first, with how many animations they own and their size.
+    pub async fn list_users_logic(pool: &DbPool) -> Result<Vec<UserOverview>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::{animation_versions, animations, users};
+
+        let rows = users::table
+            .order(users::id.asc())
+            .select((
+                users::id,
+                users::username,
+                users::email,
+                users::is_admin,
+                users::created_at,
+            ))
+            .load::<(i32, String, Option<String>, bool, NaiveDateTime)>(&mut conn)
+            .await?;
+        let counts: HashMap<i32, i64> = animations::table
+            .filter(animations::owner_id.is_not_null())
+            .group_by(animations::owner_id)
+            .select((animations::owner_id, diesel::dsl::count_star()))
+            .load::<(Option<i32>, i64)>(&mut conn)
+            .await?
+            .into_iter()
+            .filter_map(|(owner, count)| Some((owner?, count)))
+            .collect();
+        // Every kept version counts, as it does against quotas
+        let sizes: HashMap<i32, i64> = animation_versions::table
+            .inner_join(animations::table)
+            .filter(animations::owner_id.is_not_null())
+            .group_by(animations::owner_id)
+            .select((
+                animations::owner_id,
+                diesel::dsl::sum(octet_length(animation_versions::protobuf_data)),
+            ))
+            .load::<(Option<i32>, Option<i64>)>(&mut conn)
+            .await?
+            .into_iter()
+            .filter_map(|(owner, bytes)| Some((owner?, bytes.unwrap_or(0))))
+            .collect();
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, username, email, is_admin, created_at)| {
+                let animation_count = counts.get(&id).copied().unwrap_or(0);
+                let stored_bytes = sizes.get(&id).copied().unwrap_or(0);
+                UserOverview {
+                    id,
+                    username,
+                    email,
+                    is_admin,
+                    created_at,
+                    animation_count,
+                    stored_bytes,
+                }
+            })
+            .collect())
+    }
+
+    /// Deletes a user along with the animations they own, returning how many
+    /// animations went. Their sessions, tokens, folders and the like go by cascade;
+    /// animations they only edited stay, no longer attributed to them.
+    pub async fn delete_user_logic(pool: &DbPool, user_id: i32) -> Result<usize, AppError> {
+        let deleted_ids = run_in_transaction(pool, |conn| {
+            async move {
+                use crate::schema::{animations, users};
+
+                let deleted_ids =
+                    diesel::delete(animations::table.filter(animations::owner_id.eq(user_id)))
+                        .returning(animations::id)
+                        .get_results::<i32>(conn)
+                        .await?;
+                let users_deleted = diesel::delete(users::table.find(user_id))
+                    .execute(conn)
+                    .await?;
+                if users_deleted == 0 {
+                    return Err(AppError::NotFound(format!("No user {}", user_id)));
+                }
+                Ok(deleted_ids)
+            }
+            .scope_boxed()
+        })
+        .await?;
+        for animation_id in &deleted_ids {
+            cache::invalidate(*animation_id).await;
+            presence::access_changed(*animation_id);
+        }
+        tracing::info!(
+            "ADMIN: Deleted user {} and {} animations",
+            user_id,
+            deleted_ids.len()
+        );
+        Ok(deleted_ids.len())
+    }
+
+    /// Gives animations owned by `from_user` to `to_user`: the one given, or all of
+    /// them. They leave the old owner's folders, and the old owner's embed links to
+    /// them stop working. Returns how many changed hands.
+    /// Their documents and versions are untouched; caches on running servers skip
+    /// their entries anyway, as the rows' `updated_at` has moved.
+    pub async fn reassign_animations_logic(
+        pool: &DbPool,
+        from_user: i32,
+        to_user: i32,
+        animation_id: Option<i32>,
+    ) -> Result<usize, AppError> {
+        let moved_ids = run_in_transaction(pool, |conn| {
+            async move {
+                use crate::schema::{animation_collaborators, animations, users};
+
+                let recipient_exists =
+                    diesel::select(diesel::dsl::exists(users::table.find(to_user)))
+                        .get_result::<bool>(conn)
+                        .await?;
+                if !recipient_exists {
+                    return Err(AppError::NotFound(format!("No user {}", to_user)));
+                }
+                let mut owned = animations::table
+                    .filter(animations::owner_id.eq(from_user))
+                    .into_boxed();
+                if let Some(animation_id) = animation_id {
+                    owned = owned.filter(animations::id.eq(animation_id));
+                }
+                let moved_ids = owned.select(animations::id).load::<i32>(conn).await?;
+                if let Some(animation_id) = animation_id.filter(|_| moved_ids.is_empty()) {
+                    return Err(AppError::NotFound(format!(
+                        "User {} doesn't own animation {}",
+                        from_user, animation_id
+                    )));
+                }
+
+                diesel::update(animations::table.filter(animations::id.eq_any(&moved_ids)))
+                    .set((
+                        animations::owner_id.eq(to_user),
+                        animations::folder_id.eq(None::<i32>),
+                        animations::embed_generation.eq(animations::embed_generation + 1),
+                    ))
+                    .execute(conn)
+                    .await?;
+                // An owner needn't also be a collaborator on their own animation
+                diesel::delete(
+                    animation_collaborators::table
+                        .filter(animation_collaborators::animation_id.eq_any(&moved_ids))
+                        .filter(animation_collaborators::user_id.eq(to_user)),
+                )
+                .execute(conn)
+                .await?;
+                Ok(moved_ids)
+            }
+            .scope_boxed()
+        })
+        .await?;
+        for animation_id in &moved_ids {
+            cache::invalidate(*animation_id).await;
+            presence::access_changed(*animation_id);
+        }
+        tracing::info!(
+            "ADMIN: Reassigned {} animations from user {} to user {}",
+            moved_ids.len(),
+            from_user,
+            to_user
+        );
+        Ok(moved_ids.len())
+    }
+
+    /// Offers an animation as a template, returning the template's ID. Users get
+    /// copies of the animation as it is when they use the template.
+    pub async fn add_template_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        name: &str,
+        description: Option<&str>,
+        sort_order: i32,
+    ) -> Result<i32, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::{animations, templates};
+
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 255 {
+            return Err(AppError::BadRequest(
+                "Template names must be 1 to 255 characters".to_string(),
+            ));
+        }
+        let exists = diesel::select(diesel::dsl::exists(animations::table.find(animation_id)))
+            .get_result::<bool>(&mut conn)
+            .await?;
+        if !exists {
+            return Err(AppError::NotFound(format!("No animation {}", animation_id)));
+        }
+        let template_id = diesel::insert_into(templates::table)
+            .values((
+                templates::animation_id.eq(animation_id),
+                templates::name.eq(name),
+                templates::description.eq(description),
+                templates::sort_order.eq(sort_order),
+            ))
+            .on_conflict(templates::animation_id)
+            .do_nothing()
+            .returning(templates::id)
+            .get_result::<i32>(&mut conn)
+            .await
+            .optional()?
+            .ok_or_else(|| {
+                AppError::BadRequest(format!("Animation {} is already a template", animation_id))
+            })?;
+        tracing::info!(
+            "ADMIN: Animation {} offered as template {}",
+            animation_id,
+            template_id
+        );
+        Ok(template_id)
+    }
+
+    /// Fills in the feature, point and keyframe counts of animations saved before
+    /// they were kept, which takes decoding each document. Returns how many were
+    /// measured; damaged documents are logged and left unmeasured.
+    pub async fn measure_animations_logic(pool: &DbPool) -> Result<usize, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::animations;
+
+        let mut measured = 0;
+        let mut after_id = 0;
+        loop {
+            let batch = animations::table
+                .filter(animations::feature_count.is_null())
+                .filter(animations::id.gt(after_id))
+                .order(animations::id.asc())
+                .limit(MEASURE_BATCH)
+                .select((animations::id, animations::protobuf_data))
+                .load::<(i32, Vec<u8>)>(&mut conn)
+                .await?;
+            let Some(&(last_id, _)) = batch.last() else {
+                break;
+            };
+            after_id = last_id;
+            for (animation_id, stored) in batch {
+                let stored_bytes = stored.len();
+                let decoded = decompress_document(stored).and_then(|document| {
+                    MapAnimation::decode(document.as_slice()).map_err(AppError::from)
+                });
+                let document = match decoded {
+                    Ok(document) => document,
+                    Err(err) => {
+                        tracing::warn!(
+                            "ADMIN: Animation {} couldn't be measured: {:?}",
+                            animation_id,
+                            err
+                        );
+                        continue;
+                    }
+                };
+                diesel::update(animations::table.find(animation_id))
+                    .set(measure_document(&document, stored_bytes))
+                    .execute(&mut conn)
+                    .await?;
+                measured += 1;
+            }
+        }
+        tracing::info!("ADMIN: Measured {} animations", measured);
+        Ok(measured)
+    }
+
+    /// Withdraws a template; the animation behind it and copies made from it stay.
+    pub async fn remove_template_logic(pool: &DbPool, template_id: i32) -> Result<(), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::templates;
+
+        let deleted = diesel::delete(templates::table.find(template_id))
+            .execute(&mut conn)
+            .await?;
+        if deleted == 0 {
+            return Err(AppError::NotFound(format!("No template {}", template_id)));
+        }
+        tracing::info!("ADMIN: Removed template {}", template_id);
+        Ok(())
+    }
+}
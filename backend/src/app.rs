@@ -0,0 +1,533 @@
+// klyja/backend/src/app.rs
+// The HTTP app: every route, its layers (CORS, tracing, request IDs, compression),
+// the Swagger UI and the static files, over the database pools. main.rs serves it;
+// integration tests and anything embedding the backend build the same router from here.
+use crate::{
+    auth, body_limit, db::ReadPool, handlers, i18n, maintenance, models, request_id, throttle,
+    timeouts, DbPool,
+};
+use axum::{
+    extract::{DefaultBodyLimit, FromRef},
+    middleware,
+    routing::{delete, get, post, put},
+    Router,
+};
+use std::path::PathBuf;
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{Any, CorsLayer},
+    services::ServeDir,
+    trace::TraceLayer,
+};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+// --- Define the ApiDoc struct ---
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        handlers::health_check_handler, // Add the health check handler
+        handlers::readiness_handler,
+        handlers::save_animation_handler,
+        handlers::load_animation_handler,
+        handlers::list_animations_handler,
+        handlers::update_animation_handler,
+        handlers::delete_animation_handler,
+        handlers::patch_animation_handler,
+        handlers::diff_versions_handler,
+        handlers::save_draft_handler,
+        handlers::load_draft_handler,
+        handlers::list_collaborators_handler,
+        handlers::invite_collaborator_handler,
+        handlers::remove_collaborator_handler,
+        handlers::create_invitation_handler,
+        handlers::accept_invitation_handler,
+        handlers::publish_animation_handler,
+        handlers::unpublish_animation_handler,
+        handlers::create_embed_handler,
+        handlers::revoke_embeds_handler,
+        handlers::live_handler,
+        handlers::embed_handler,
+        handlers::star_animation_handler,
+        handlers::unstar_animation_handler,
+        handlers::put_thumbnail_handler,
+        handlers::load_thumbnail_handler,
+        handlers::duplicate_animation_handler,
+        handlers::list_templates_handler,
+        handlers::instantiate_template_handler,
+        handlers::animation_metadata_handler,
+        handlers::animation_by_uuid_handler,
+        handlers::batch_metadata_handler,
+        handlers::update_details_handler,
+        handlers::animation_stats_handler,
+        handlers::import_archive_handler,
+        handlers::import_csv_handler,
+        handlers::import_gpx_handler,
+        handlers::import_status_handler,
+        handlers::create_upload_handler,
+        handlers::upload_status_handler,
+        handlers::put_upload_part_handler,
+        handlers::complete_upload_handler,
+        handlers::export_video_handler,
+        handlers::export_frames_handler,
+        handlers::export_status_handler,
+        handlers::download_export_handler,
+        handlers::job_status_handler,
+        handlers::register_handler,
+        handlers::login_handler,
+        handlers::request_password_reset_handler,
+        handlers::confirm_password_reset_handler,
+        handlers::list_sessions_handler,
+        handlers::revoke_session_handler,
+        handlers::list_tokens_handler,
+        handlers::create_token_handler,
+        handlers::revoke_token_handler,
+        handlers::list_webhooks_handler,
+        handlers::create_webhook_handler,
+        handlers::delete_webhook_handler,
+        handlers::report_animation_handler,
+        handlers::list_reports_handler,
+        handlers::hide_animation_handler,
+        handlers::unhide_animation_handler,
+        handlers::auth_throttle_stats_handler,
+        handlers::maintenance_status_handler,
+        handlers::set_maintenance_handler,
+        handlers::start_backup_handler,
+        handlers::start_restore_handler,
+        handlers::backup_status_handler,
+        handlers::download_backup_handler,
+        handlers::list_folders_handler,
+        handlers::create_folder_handler,
+        handlers::update_folder_handler,
+        handlers::delete_folder_handler,
+        handlers::move_animation_handler
+    ),
+    components(
+        schemas(
+            models::Animation,
+            models::AnimationSummary,
+            models::AnimationListItem,
+            models::AnimationMetadata,
+            models::BatchMetadataRequest,
+            models::AnimationDetailsRequest,
+            models::AnimationStats,
+            models::DailyViews,
+            models::ListSort,
+            models::StarStatus,
+            models::PublicationRequest,
+            models::PublicationStatus,
+            models::EmbedRequest,
+            models::EmbedLink,
+            models::PresenceMode,
+            models::SphereCursor,
+            models::PresenceUpdate,
+            models::PresenceEntry,
+            models::LiveMessage,
+            models::AnimationDiff,
+            models::Collaborator,
+            models::CollaboratorRole,
+            models::InviteCollaboratorRequest,
+            models::CreateInvitationRequest,
+            models::Invitation,
+            models::CreatedInvitation,
+            models::AcceptInvitationRequest,
+            models::AcceptedInvitation,
+            models::ImportedFile,
+            models::ArchiveImport,
+            models::Template,
+            models::CreateUploadRequest,
+            models::UploadStatus,
+            models::ExportFormat,
+            models::ExportVideoRequest,
+            models::ExportFramesRequest,
+            models::ExportJob,
+            models::JobInfo,
+            models::RegisterRequest,
+            models::LoginRequest,
+            models::PasswordResetRequest,
+            models::ConfirmPasswordResetRequest,
+            models::Account,
+            models::SessionInfo,
+            models::TokenScope,
+            models::CreateApiTokenRequest,
+            models::ApiToken,
+            models::CreatedApiToken,
+            models::WebhookEvent,
+            models::CreateWebhookRequest,
+            models::Webhook,
+            models::CreatedWebhook,
+            models::ReportRequest,
+            models::Report,
+            models::Folder,
+            models::FolderRequest,
+            models::MoveAnimationRequest,
+            models::BackupJob,
+            models::RestoreRequest,
+            models::PoolStats,
+            models::QueryLatency,
+            models::Readiness,
+            models::AuthThrottleStats,
+            models::MaintenanceStatus,
+            models::MaintenanceRequest,
+            crate::errors::ErrorResponsePayload,
+            crate::errors::FieldErrorPayload,
+            //crate::errors::SuccessfulSaveResponsePayload
+
+        ) // List your ToSchema-derived models here
+        // Add other schemas if you have them, e.g., error response schemas
+        // schemas(ErrorResponse, AnotherModel)
+    ),
+    tags(
+        (name = "Klyja Animations API", description = "API endpoints for managing map animations")
+        // You can define tags referenced in handlers here, or utoipa will create them.
+        // (name = "Animations", description = "Animation data operations"),
+        // (name = "System", description = "System health checks")
+    ),
+    info(
+        title = "Klyja Spherical Map Animation API",
+        version = "v0.1.0",
+        description = "This API serves the Klyja application, allowing users to save and load spherical map animation data.",
+        contact(
+            name = "Klyja Dev Team",
+            url = "http://example.com/contact",
+            email = "dev@example.com"
+        ),
+        license(
+            name = "MIT OR Apache-2.0",
+            url = "http://example.com/license"
+        )
+    )
+)]
+pub struct ApiDoc;
+
+/// Where the app serves its static files from.
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub frontend_path: PathBuf, // Served at / as the fallback
+    pub wasm_pkg_path: PathBuf, // Served under /pkg
+}
+
+impl AppConfig {
+    /// Paths within this checkout: the built frontend when `APP_ENV=production`,
+    /// its raw files otherwise, and geco's WASM package.
+    pub fn from_env() -> Self {
+        // Get the directory containing backend/Cargo.toml at compile time
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        // Get the workspace root (parent of the manifest dir)
+        let project_root = manifest_dir
+            .parent()
+            .expect("Failed to get project root directory");
+
+        // Determine frontend path based on an environment variable or build flag
+        // For example, you could use an environment variable like `APP_ENV=production`
+        let frontend_path = if std::env::var("APP_ENV").unwrap_or_default() == "production" {
+            project_root.join("frontend/dist")
+        } else {
+            project_root.join("frontend") // Development serves raw files
+        };
+        AppConfig {
+            frontend_path,
+            wasm_pkg_path: project_root.join("geco/pkg"),
+        }
+    }
+}
+
+/// What handlers extract with `State`: the primary's pool, which most use, and the
+/// pool heavy public reads go to.
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: DbPool,
+    pub read_pool: ReadPool,
+}
+
+impl FromRef<AppState> for DbPool {
+    fn from_ref(state: &AppState) -> DbPool {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for ReadPool {
+    fn from_ref(state: &AppState) -> ReadPool {
+        state.read_pool.clone()
+    }
+}
+
+/// The whole app over `pool`, ready to serve. Background work (job workers, the
+/// gRPC service) is started separately.
+pub fn build_app(config: &AppConfig, pool: DbPool) -> Router {
+    let read_pool = ReadPool(pool.clone());
+    build_app_with_read_pool(config, pool, read_pool)
+}
+
+/// The same, with heavy public reads going to `read_pool`, e.g. a read-only replica.
+pub fn build_app_with_read_pool(config: &AppConfig, pool: DbPool, read_pool: ReadPool) -> Router {
+    // Documents may be larger than axum's 2 MB default, up to MAX_UPLOAD_BYTES; bodies
+    // over it get a JSON error saying so rather than axum's plain-text one
+    let upload_limit = middleware::from_fn(body_limit::limit_upload_body);
+    // Routes taking credentials are throttled per client IP; a sign-in callback added
+    // later should be too
+    let auth_throttle = middleware::from_fn(throttle::throttle_auth);
+
+    // Metadata reads answer quickly when the database is well, so they get a short budget
+    let metadata_routes = Router::new()
+        .route("/animations", get(handlers::list_animations_handler))
+        .route(
+            "/animations/batch_meta",
+            post(handlers::batch_metadata_handler),
+        )
+        .route(
+            "/animation/:id/meta",
+            get(handlers::animation_metadata_handler),
+        )
+        .route(
+            "/animation/by-uuid/:uuid",
+            get(handlers::animation_by_uuid_handler),
+        )
+        .route(
+            "/animation/:id/stats",
+            get(handlers::animation_stats_handler),
+        )
+        .route("/templates", get(handlers::list_templates_handler))
+        .layer(middleware::from_fn(timeouts::metadata_timeout));
+
+    // Whole documents, archives and backups can take a while to send over slow links
+    let transfer_routes = Router::new()
+        .route(
+            "/save_animation",
+            post(handlers::save_animation_handler)
+                .layer(DefaultBodyLimit::disable())
+                .layer(upload_limit.clone()),
+        )
+        .route("/load_animation/:id", get(handlers::load_animation_handler))
+        .route(
+            "/animation/:id",
+            put(handlers::update_animation_handler)
+                .layer(DefaultBodyLimit::disable())
+                .layer(upload_limit.clone())
+                .delete(handlers::delete_animation_handler),
+        )
+        .route(
+            "/animation/:id/patch",
+            post(handlers::patch_animation_handler)
+                .layer(DefaultBodyLimit::disable())
+                .layer(upload_limit.clone()),
+        )
+        .route(
+            "/animation/:id/draft",
+            put(handlers::save_draft_handler)
+                .layer(DefaultBodyLimit::disable())
+                .layer(upload_limit)
+                .get(handlers::load_draft_handler),
+        )
+        .route(
+            "/animation/:id/thumbnail",
+            get(handlers::load_thumbnail_handler).put(handlers::put_thumbnail_handler),
+        )
+        .route(
+            "/import/archive",
+            post(handlers::import_archive_handler)
+                .layer(DefaultBodyLimit::disable())
+                .layer(middleware::from_fn(body_limit::limit_archive_body)),
+        )
+        .route(
+            "/import/csv",
+            post(handlers::import_csv_handler)
+                .layer(DefaultBodyLimit::disable())
+                .layer(middleware::from_fn(body_limit::limit_upload_body)),
+        )
+        .route(
+            "/import/gpx",
+            post(handlers::import_gpx_handler)
+                .layer(DefaultBodyLimit::disable())
+                .layer(middleware::from_fn(body_limit::limit_upload_body)),
+        )
+        .route(
+            "/uploads/:id/part/:n",
+            put(handlers::put_upload_part_handler),
+        )
+        .route(
+            "/uploads/:id/complete",
+            post(handlers::complete_upload_handler),
+        )
+        .route(
+            "/exports/:id/download",
+            get(handlers::download_export_handler),
+        )
+        .route(
+            "/admin/backups/:id/download",
+            get(handlers::download_backup_handler),
+        )
+        .layer(middleware::from_fn(timeouts::transfer_timeout));
+
+    // API routes (add more later in handlers.rs)
+    let api_routes = Router::new()
+        .route("/health", get(handlers::health_check_handler))
+        .route("/health/live", get(handlers::health_check_handler))
+        .route("/health/ready", get(handlers::readiness_handler))
+        .route("/animation/:id/diff", get(handlers::diff_versions_handler))
+        .route(
+            "/animation/:id/collaborators",
+            get(handlers::list_collaborators_handler).post(handlers::invite_collaborator_handler),
+        )
+        .route(
+            "/animation/:id/details",
+            put(handlers::update_details_handler),
+        )
+        .route(
+            "/animation/:id/duplicate",
+            post(handlers::duplicate_animation_handler),
+        )
+        .route(
+            "/templates/:id/instantiate",
+            post(handlers::instantiate_template_handler),
+        )
+        .route(
+            "/animation/:id/publication",
+            put(handlers::publish_animation_handler).delete(handlers::unpublish_animation_handler),
+        )
+        .route(
+            "/animation/:id/embed",
+            post(handlers::create_embed_handler).delete(handlers::revoke_embeds_handler),
+        )
+        .route("/animation/:id/live", get(handlers::live_handler))
+        .route(
+            "/animation/:id/star",
+            post(handlers::star_animation_handler).delete(handlers::unstar_animation_handler),
+        )
+        .route(
+            "/animation/:id/collaborators/:user_id",
+            delete(handlers::remove_collaborator_handler),
+        )
+        .route(
+            "/animation/:id/invitations",
+            post(handlers::create_invitation_handler),
+        )
+        .route(
+            "/invitations/accept",
+            post(handlers::accept_invitation_handler),
+        )
+        .route("/imports/:id", get(handlers::import_status_handler))
+        .route("/uploads", post(handlers::create_upload_handler))
+        .route("/uploads/:id", get(handlers::upload_status_handler))
+        .route(
+            "/animation/:id/export/video",
+            post(handlers::export_video_handler),
+        )
+        .route(
+            "/animation/:id/export/frames",
+            post(handlers::export_frames_handler),
+        )
+        .route("/exports/:id", get(handlers::export_status_handler))
+        .route("/jobs/:id", get(handlers::job_status_handler))
+        .route(
+            "/auth/register",
+            post(handlers::register_handler).layer(auth_throttle.clone()),
+        )
+        .route(
+            "/auth/login",
+            post(handlers::login_handler).layer(auth_throttle.clone()),
+        )
+        .route(
+            "/auth/password-reset",
+            post(handlers::request_password_reset_handler).layer(auth_throttle.clone()),
+        )
+        .route(
+            "/auth/password-reset/confirm",
+            post(handlers::confirm_password_reset_handler).layer(auth_throttle),
+        )
+        .route("/auth/sessions", get(handlers::list_sessions_handler))
+        .route(
+            "/auth/sessions/:id",
+            delete(handlers::revoke_session_handler),
+        )
+        .route(
+            "/tokens",
+            get(handlers::list_tokens_handler).post(handlers::create_token_handler),
+        )
+        .route("/tokens/:id", delete(handlers::revoke_token_handler))
+        .route(
+            "/webhooks",
+            get(handlers::list_webhooks_handler).post(handlers::create_webhook_handler),
+        )
+        .route("/webhooks/:id", delete(handlers::delete_webhook_handler))
+        .route(
+            "/animation/:id/report",
+            post(handlers::report_animation_handler),
+        )
+        .route("/admin/reports", get(handlers::list_reports_handler))
+        .route(
+            "/admin/animations/:id/hide",
+            post(handlers::hide_animation_handler),
+        )
+        .route(
+            "/admin/animations/:id/unhide",
+            post(handlers::unhide_animation_handler),
+        )
+        .route("/admin/backups", post(handlers::start_backup_handler))
+        .route("/admin/backups/:id", get(handlers::backup_status_handler))
+        .route("/admin/restores", post(handlers::start_restore_handler))
+        .route(
+            "/admin/auth-throttling",
+            get(handlers::auth_throttle_stats_handler),
+        )
+        .route(
+            "/folders",
+            get(handlers::list_folders_handler).post(handlers::create_folder_handler),
+        )
+        .route(
+            "/folders/:id",
+            put(handlers::update_folder_handler).delete(handlers::delete_folder_handler),
+        )
+        .route(
+            "/animation/:id/folder",
+            put(handlers::move_animation_handler),
+        )
+        .layer(middleware::from_fn(timeouts::request_timeout))
+        .merge(transfer_routes)
+        // In maintenance mode only reads get through; the routes below are left out,
+        // as metadata reads include batch_meta's POST and admins must be able to
+        // switch it off again
+        .route_layer(middleware::from_fn(maintenance::refuse_writes))
+        .merge(metadata_routes)
+        .route(
+            "/admin/maintenance",
+            get(handlers::maintenance_status_handler)
+                .put(handlers::set_maintenance_handler)
+                .layer(middleware::from_fn(timeouts::request_timeout)),
+        )
+        // Scripts authenticate with personal access tokens instead of a session cookie
+        .route_layer(middleware::from_fn_with_state(
+            pool.clone(),
+            auth::bearer_auth,
+        ));
+
+    // Service to serve WASM package files from `../geco/pkg`
+    let wasm_pkg_service =
+        ServeDir::new(&config.wasm_pkg_path).append_index_html_on_directories(false);
+
+    // Service to serve static frontend files from `../frontend`
+    let static_files_service =
+        ServeDir::new(&config.frontend_path).append_index_html_on_directories(true); // Serve index.html for directories like "/"
+
+    Router::new()
+        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .nest("/api", api_routes) // API routes under /api
+        .route("/embed/:token", get(handlers::embed_handler)) // Outside /api: the token is the only credential
+        .nest_service("/pkg", wasm_pkg_service) // WASM files under /pkg
+        .fallback_service(static_files_service) // Serve frontend static files as fallback
+        //.layer(Extension(pool)) // Add database pool state
+        .with_state(AppState { pool, read_pool })
+        //.layer(Extension(pool))
+        // Negotiated via Accept-Encoding; protobuf documents shrink a lot, images are left alone
+        .layer(CompressionLayer::new())
+        .layer(TraceLayer::new_for_http()) // Add HTTP request logging
+        .layer(middleware::from_fn(request_id::assign_request_id)) // Outside tracing, so its logs carry the ID
+        .layer(middleware::from_fn(i18n::negotiate_language)) // Error bodies are in the caller's language
+        .layer(
+            // Add CORS layer - Allow requests from any origin (adjust for production)
+            CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any) // Allows common methods
+                .allow_headers(Any), // Allows common headers
+        )
+}
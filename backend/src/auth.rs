@@ -0,0 +1,151 @@
+// klyja/backend/src/auth.rs
+use crate::{
+    devices::{self, ClientInfo},
+    errors::AppError,
+    tokens, DbPool,
+};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, MatchedPath, Request, State},
+    http::{header, request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+
+/// Name of the cookie holding the session token.
+pub const SESSION_COOKIE: &str = "klyja_session";
+
+/// `Set-Cookie` value that stores a new session token in the browser.
+pub fn session_cookie(token: &str) -> String {
+    format!(
+        "{}={}; Path=/; HttpOnly; Secure; SameSite=Lax; Max-Age={}",
+        SESSION_COOKIE,
+        token,
+        i64::from(crate::accounts::SESSION_TTL_DAYS) * 24 * 60 * 60
+    )
+}
+
+/// The signed-in user making a request.
+///
+/// Use `Option<AuthUser>` in handlers that also serve anonymous callers; a missing,
+/// unknown or expired session then yields `None` instead of a 401.
+/// Requests with an `Authorization: Bearer` token are authenticated by the
+/// `bearer_auth` layer instead, which rejects bad tokens outright.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthUser {
+    pub id: i32,
+    /// The personal access token used, if the request didn't come with a session.
+    pub token_id: Option<i32>,
+    /// The session used, if the request came with one.
+    pub session_id: Option<i32>,
+}
+
+impl AuthUser {
+    /// Fails for requests made with a personal access token, for actions that
+    /// need the user themselves, like managing tokens.
+    pub fn require_session(self) -> Result<Self, AppError> {
+        match self.token_id {
+            None => Ok(self),
+            Some(_) => Err(AppError::Forbidden(
+                "Sign in to do this; API tokens can't".to_string(),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(*user); // Already authenticated by bearer_auth
+        }
+        let token = session_token(parts)
+            .ok_or_else(|| AppError::Unauthorized("Sign in required".to_string()))?;
+        let pool = DbPool::from_ref(state);
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::sessions;
+
+        let (id, session_id) = sessions::table
+            .filter(sessions::token.eq(&token))
+            .filter(sessions::expires_at.gt(diesel::dsl::now))
+            .select((sessions::user_id, sessions::id))
+            .first::<(i32, i32)>(&mut conn)
+            .await
+            .optional()
+            .map_err(AppError::DatabaseQuery)?
+            .ok_or_else(|| AppError::Unauthorized("Session expired or invalid".to_string()))?;
+        devices::touch_session(&mut conn, &token, &ClientInfo::from_parts(parts)).await?;
+
+        Ok(AuthUser {
+            id,
+            token_id: None,
+            session_id: Some(session_id),
+        })
+    }
+}
+
+/// Reads the session token from the request's `Cookie` header.
+fn session_token(parts: &Parts) -> Option<String> {
+    parts
+        .headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Reads a personal access token from the `Authorization: Bearer` header.
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// Authenticates requests carrying a personal access token, for the `AuthUser`
+/// extractor to pick up. Unknown or expired tokens get 401, and tokens without the
+/// scope the route needs get 403, rather than being treated as anonymous.
+pub async fn bearer_auth(
+    State(pool): State<DbPool>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(token) = bearer_token(request.headers()) else {
+        return Ok(next.run(request).await);
+    };
+    let matched_path = request.extensions().get::<MatchedPath>();
+    let needed = tokens::scope_for_route(request.method(), matched_path.map(MatchedPath::as_str));
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    let resolved = tokens::resolve_token(&mut conn, &token).await?;
+    drop(conn); // Don't hold a connection while the handler runs
+
+    let (user_id, token_id, scopes) = resolved
+        .ok_or_else(|| AppError::Unauthorized("API token invalid or expired".to_string()))?;
+    if !scopes.contains(&needed) {
+        return Err(AppError::Forbidden(format!(
+            "This API token lacks the '{}' scope",
+            needed.as_str()
+        )));
+    }
+    request.extensions_mut().insert(AuthUser {
+        id: user_id,
+        token_id: Some(token_id),
+        session_id: None,
+    });
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,582 @@
+// klyja/backend/src/backups.rs
+// Backups for moving between deployments without pg_dump: an admin backup writes
+// every animation's document to a zip in BACKUP_DIR with a manifest of its details,
+// and a restore reads such a zip back in. Point BACKUP_DIR at mounted object storage
+// to share backups between deployments. Both run in the job queue.
+use crate::{
+    errors::AppError,
+    jobs::{self, Job},
+    models::{BackupJob, NewAnimation},
+    moderation,
+    services::{decompress_document, PreparedDocument},
+    DbPool,
+};
+use bytes::Bytes;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+/// Where backups go when `BACKUP_DIR` doesn't say, relative to the working directory.
+pub const DEFAULT_BACKUP_DIR: &str = "backups";
+/// Version of the manifest layout written; restores refuse newer ones.
+pub const MANIFEST_VERSION: u32 = 1;
+/// Animations read from the database at a time while backing up.
+const BACKUP_BATCH: i64 = 100;
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// The directory backups are written to and restored from.
+pub fn backup_dir() -> PathBuf {
+    std::env::var("BACKUP_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_BACKUP_DIR))
+}
+
+/// What a backup holds, stored as `manifest.json` beside the documents.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created_at: NaiveDateTime,
+    pub animations: Vec<BackupEntry>,
+}
+
+/// One animation in a backup: its details, and where its document is in the zip.
+/// Owners are kept by username, since user IDs differ between deployments.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub id: i32, // In the deployment backed up
+    pub name: String,
+    pub animation_uuid: Option<String>,
+    pub owner: Option<String>,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub source_attribution: Option<String>,
+    pub published_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub file: String, // The uncompressed protobuf document
+}
+
+/// Whether a file name can be used within BACKUP_DIR: a plain `.zip` name, with no
+/// way to reach outside the directory.
+pub fn is_valid_backup_name(file_name: &str) -> bool {
+    file_name.len() <= 255
+        && file_name.ends_with(".zip")
+        && !file_name.starts_with('.')
+        && file_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+fn io_error(err: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Backup file error: {}", err))
+}
+
+async fn require_admin(conn: &mut AsyncPgConnection, caller_id: i32) -> Result<(), AppError> {
+    if moderation::is_admin(conn, Some(caller_id)).await? {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(
+            "Only admins can back up and restore animations".to_string(),
+        ))
+    }
+}
+
+/// Adds a backup or restore and queues the job running it.
+async fn queue(
+    pool: &DbPool,
+    kind: &'static str,
+    file_name: String,
+    caller_id: i32,
+) -> Result<BackupJob, AppError> {
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    require_admin(&mut conn, caller_id).await?;
+    use crate::schema::backups;
+
+    conn.transaction::<_, AppError, _>(|conn| {
+        async move {
+            let backup_id = diesel::insert_into(backups::table)
+                .values((
+                    backups::kind.eq(kind),
+                    backups::file_name.eq(&file_name),
+                    backups::requested_by.eq(caller_id),
+                ))
+                .returning(backups::id)
+                .get_result::<i32>(conn)
+                .await?;
+            let job = match kind {
+                "backup" => Job::Backup { backup_id },
+                _ => Job::Restore { backup_id },
+            };
+            let job_id = jobs::enqueue(conn, &job, None).await?;
+            Ok(diesel::update(backups::table.find(backup_id))
+                .set(backups::job_id.eq(job_id))
+                .returning(BackupJob::as_returning())
+                .get_result::<BackupJob>(conn)
+                .await?)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+async fn set_running(pool: &DbPool, backup_id: i32) -> Result<(String, Option<i32>), AppError> {
+    use crate::schema::backups;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    Ok(diesel::update(backups::table.find(backup_id))
+        .set(backups::status.eq("running"))
+        .returning((backups::file_name, backups::requested_by))
+        .get_result::<(String, Option<i32>)>(&mut conn)
+        .await?)
+}
+
+async fn set_done(
+    pool: &DbPool,
+    backup_id: i32,
+    animation_count: i32,
+    skipped_count: Option<i32>,
+) -> Result<(), AppError> {
+    use crate::schema::backups;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    diesel::update(backups::table.find(backup_id))
+        .set((
+            backups::status.eq("done"),
+            backups::error.eq(None::<String>),
+            backups::animation_count.eq(animation_count),
+            backups::skipped_count.eq(skipped_count),
+            backups::finished_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Writes every animation to the backup's zip, a batch at a time so the documents
+/// are never all in memory at once.
+pub async fn run_backup_job(pool: &DbPool, backup_id: i32) -> Result<(), AppError> {
+    use crate::schema::{animations, users};
+
+    let (file_name, _) = set_running(pool, backup_id).await?;
+    let dir = backup_dir();
+    let path = dir.join(&file_name);
+    let mut writer = tokio::task::spawn_blocking(move || {
+        std::fs::create_dir_all(&dir)?;
+        Ok::<_, std::io::Error>(ZipWriter::new(File::create(path)?))
+    })
+    .await
+    .map_err(io_error)?
+    .map_err(io_error)?;
+
+    let mut entries = Vec::new();
+    let mut after_id = 0;
+    loop {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        let batch = animations::table
+            .left_join(users::table)
+            .filter(animations::id.gt(after_id))
+            .order(animations::id.asc())
+            .limit(BACKUP_BATCH)
+            .select((
+                (
+                    animations::id,
+                    animations::name,
+                    animations::animation_uuid,
+                    users::username.nullable(),
+                    animations::description,
+                    animations::license,
+                    animations::source_attribution,
+                    animations::published_at,
+                    animations::created_at,
+                    animations::updated_at,
+                ),
+                animations::protobuf_data,
+            ))
+            .load::<(
+                (
+                    i32,
+                    String,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                    Option<NaiveDateTime>,
+                    NaiveDateTime,
+                    NaiveDateTime,
+                ),
+                Vec<u8>,
+            )>(&mut conn)
+            .await?;
+        drop(conn);
+        let Some(((last_id, ..), _)) = batch.last() else {
+            break;
+        };
+        after_id = *last_id;
+
+        let mut documents = Vec::with_capacity(batch.len());
+        for (
+            (
+                id,
+                name,
+                animation_uuid,
+                owner,
+                description,
+                license,
+                source_attribution,
+                published_at,
+                created_at,
+                updated_at,
+            ),
+            stored,
+        ) in batch
+        {
+            let file = format!("animations/{}.pb", id);
+            documents.push((file.clone(), stored));
+            entries.push(BackupEntry {
+                id,
+                name,
+                animation_uuid,
+                owner,
+                description,
+                license,
+                source_attribution,
+                published_at,
+                created_at,
+                updated_at,
+                file,
+            });
+        }
+        writer = tokio::task::spawn_blocking(move || {
+            for (file, stored) in documents {
+                let document = decompress_document(stored)?;
+                writer.start_file(file, zip_options()).map_err(io_error)?;
+                writer.write_all(&document).map_err(io_error)?;
+            }
+            Ok::<_, AppError>(writer)
+        })
+        .await
+        .map_err(io_error)??;
+    }
+
+    let manifest = BackupManifest {
+        version: MANIFEST_VERSION,
+        created_at: chrono::Utc::now().naive_utc(),
+        animations: entries,
+    };
+    let count = manifest.animations.len() as i32;
+    tokio::task::spawn_blocking(move || {
+        writer
+            .start_file(MANIFEST_NAME, zip_options())
+            .map_err(io_error)?;
+        serde_json::to_writer_pretty(&mut writer, &manifest).map_err(io_error)?;
+        writer.finish().map_err(io_error)?;
+        Ok::<_, AppError>(())
+    })
+    .await
+    .map_err(io_error)??;
+
+    tracing::info!(
+        "JOB: Backup {} wrote {} animations to {}",
+        backup_id,
+        count,
+        file_name
+    );
+    set_done(pool, backup_id, count, None).await
+}
+
+fn zip_options() -> FileOptions {
+    FileOptions::default().compression_method(CompressionMethod::Deflated)
+}
+
+/// Opens a backup and reads its manifest; documents are read one at a time after.
+fn open_backup(path: PathBuf) -> Result<(ZipArchive<File>, BackupManifest), AppError> {
+    let mut archive = ZipArchive::new(File::open(path).map_err(io_error)?)
+        .map_err(|err| AppError::BadRequest(format!("The backup isn't a readable zip: {}", err)))?;
+    let manifest: BackupManifest = {
+        let file = archive
+            .by_name(MANIFEST_NAME)
+            .map_err(|_| AppError::BadRequest(format!("The backup has no {}", MANIFEST_NAME)))?;
+        serde_json::from_reader(file)
+            .map_err(|err| AppError::BadRequest(format!("Unreadable manifest: {}", err)))?
+    };
+    if manifest.version > MANIFEST_VERSION {
+        return Err(AppError::BadRequest(format!(
+            "The backup's manifest is version {}; this server reads up to {}",
+            manifest.version, MANIFEST_VERSION
+        )));
+    }
+    Ok((archive, manifest))
+}
+
+/// Reads one document of a backup. A missing document comes back empty, so that
+/// animation is skipped rather than failing the rest.
+fn read_document(archive: &mut ZipArchive<File>, file: &str) -> Result<Vec<u8>, AppError> {
+    let mut document = Vec::new();
+    if let Ok(mut file) = archive.by_name(file) {
+        file.read_to_end(&mut document).map_err(io_error)?;
+    }
+    Ok(document)
+}
+
+/// Whether an entry of the backup is here already: restored from this zip before, or
+/// still in the deployment under its document ID.
+async fn already_restored(
+    conn: &mut AsyncPgConnection,
+    file_name: &str,
+    entry: &BackupEntry,
+) -> Result<bool, AppError> {
+    use crate::schema::{animations, restored_animations};
+
+    let restored = diesel::select(diesel::dsl::exists(
+        restored_animations::table.find((file_name, entry.id)),
+    ))
+    .get_result::<bool>(conn)
+    .await?;
+    if restored {
+        return Ok(true);
+    }
+    let Some(animation_uuid) = &entry.animation_uuid else {
+        return Ok(false);
+    };
+    Ok(diesel::select(diesel::dsl::exists(
+        animations::table.filter(animations::animation_uuid.eq(animation_uuid)),
+    ))
+    .get_result::<bool>(conn)
+    .await?)
+}
+
+/// Adds the animations in a backup, each owned by the user with its owner's
+/// username here, else by the admin restoring it. Entries restored from the same zip
+/// before, animations whose document ID is still taken and documents that don't
+/// validate are skipped, so running a restore again adds nothing twice. Documents
+/// are read and added one at a time.
+pub async fn run_restore_job(pool: &DbPool, backup_id: i32) -> Result<(), AppError> {
+    use crate::schema::{animations, restored_animations, users};
+
+    let (file_name, requested_by) = set_running(pool, backup_id).await?;
+    let path = backup_dir().join(&file_name);
+    let (mut archive, manifest) = tokio::task::spawn_blocking(move || open_backup(path))
+        .await
+        .map_err(io_error)??;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    let owners: HashMap<String, i32> = users::table
+        .filter(
+            users::username.eq_any(
+                manifest
+                    .animations
+                    .iter()
+                    .filter_map(|entry| entry.owner.clone()),
+            ),
+        )
+        .select((users::username, users::id))
+        .load::<(String, i32)>(&mut conn)
+        .await?
+        .into_iter()
+        .collect();
+
+    let (mut restored, mut skipped) = (0, 0);
+    for entry in manifest.animations {
+        if already_restored(&mut conn, &file_name, &entry).await? {
+            skipped += 1;
+            continue;
+        }
+        let file = entry.file.clone();
+        let (returned, document) = tokio::task::spawn_blocking(move || {
+            let document = read_document(&mut archive, &file);
+            (archive, document)
+        })
+        .await
+        .map_err(io_error)?;
+        archive = returned;
+        let prepared = match PreparedDocument::from_upload(&Bytes::from(document?)) {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                tracing::warn!(
+                    "JOB: Restore {} skipped animation {}: {:?}",
+                    backup_id,
+                    entry.id,
+                    err
+                );
+                skipped += 1;
+                continue;
+            }
+        };
+        let owner_id = entry
+            .owner
+            .as_ref()
+            .and_then(|owner| owners.get(owner).copied())
+            .or(requested_by);
+        let file_name = &file_name;
+        // The animation and its record in restored_animations go in together
+        conn.transaction::<_, AppError, _>(|conn| {
+            async move {
+                let animation_id = diesel::insert_into(animations::table)
+                    .values((
+                        &NewAnimation {
+                            name: &entry.name,
+                            protobuf_data: &prepared.stored,
+                            owner_id,
+                            content_sha256: &prepared.sha256,
+                            animation_uuid: entry.animation_uuid.as_deref(),
+                            created_by: owner_id,
+                            last_edited_by: owner_id,
+                            metrics: prepared.metrics,
+                        },
+                        animations::description.eq(entry.description),
+                        animations::license.eq(entry.license),
+                        animations::source_attribution.eq(entry.source_attribution),
+                        animations::published_at.eq(entry.published_at),
+                        animations::created_at.eq(entry.created_at),
+                    ))
+                    .returning(animations::id)
+                    .get_result::<i32>(conn)
+                    .await?;
+                diesel::insert_into(restored_animations::table)
+                    .values((
+                        restored_animations::file_name.eq(file_name),
+                        restored_animations::source_id.eq(entry.id),
+                        restored_animations::animation_id.eq(animation_id),
+                    ))
+                    .execute(conn)
+                    .await?;
+                jobs::enqueue(
+                    conn,
+                    &Job::RenderThumbnail { animation_id },
+                    Some(animation_id),
+                )
+                .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+        restored += 1;
+    }
+    drop(conn);
+
+    tracing::info!(
+        "JOB: Restore {} added {} animations from {} and skipped {}",
+        backup_id,
+        restored,
+        file_name,
+        skipped
+    );
+    set_done(pool, backup_id, restored, Some(skipped)).await
+}
+
+/// Marks a backup or restore failed once its job has used up its attempts.
+pub async fn mark_backup_failed(
+    pool: &DbPool,
+    backup_id: i32,
+    error: &str,
+) -> Result<(), AppError> {
+    use crate::schema::backups;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    diesel::update(backups::table.find(backup_id))
+        .set((
+            backups::status.eq("failed"),
+            backups::error.eq(error),
+            backups::finished_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+pub struct BackupService;
+
+impl BackupService {
+    /// Starts backing up every animation to a new zip in BACKUP_DIR; admins only.
+    pub async fn start_backup_logic(pool: &DbPool, caller_id: i32) -> Result<BackupJob, AppError> {
+        let file_name = format!(
+            "klyja-backup-{}-{}.zip",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let backup = queue(pool, "backup", file_name, caller_id).await?;
+        tracing::info!(
+            "SERVICE: User {} started backup {} to {}",
+            caller_id,
+            backup.id,
+            backup.file_name
+        );
+        Ok(backup)
+    }
+
+    /// Starts restoring the animations in a zip in BACKUP_DIR; admins only.
+    pub async fn start_restore_logic(
+        pool: &DbPool,
+        caller_id: i32,
+        file_name: String,
+    ) -> Result<BackupJob, AppError> {
+        if !is_valid_backup_name(&file_name) {
+            return Err(AppError::BadRequest(
+                "Name a .zip file directly within the backup directory".to_string(),
+            ));
+        }
+        let exists = tokio::fs::try_exists(backup_dir().join(&file_name))
+            .await
+            .unwrap_or(false);
+        if !exists {
+            return Err(AppError::NotFound(format!("No backup named {}", file_name)));
+        }
+        let restore = queue(pool, "restore", file_name, caller_id).await?;
+        tracing::info!(
+            "SERVICE: User {} started restore {} from {}",
+            caller_id,
+            restore.id,
+            restore.file_name
+        );
+        Ok(restore)
+    }
+
+    /// Reports a backup's or restore's progress; admins only.
+    pub async fn backup_status_logic(
+        pool: &DbPool,
+        backup_id: i32,
+        caller_id: i32,
+    ) -> Result<BackupJob, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_admin(&mut conn, caller_id).await?;
+        use crate::schema::backups;
+
+        backups::table
+            .find(backup_id)
+            .select(BackupJob::as_select())
+            .first::<BackupJob>(&mut conn)
+            .await
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("No backup {}", backup_id)))
+    }
+
+    /// A finished backup's zip and its file name; admins only.
+    pub async fn download_backup_logic(
+        pool: &DbPool,
+        backup_id: i32,
+        caller_id: i32,
+    ) -> Result<(String, Vec<u8>), AppError> {
+        let backup = Self::backup_status_logic(pool, backup_id, caller_id).await?;
+        if backup.kind != "backup" || backup.status != "done" {
+            return Err(AppError::NotFound(format!(
+                "{} {} is {}, not a finished backup",
+                backup.kind, backup_id, backup.status
+            )));
+        }
+        let data = tokio::fs::read(backup_dir().join(&backup.file_name))
+            .await
+            .map_err(|_| {
+                AppError::NotFound(format!("{} is no longer on the server", backup.file_name))
+            })?;
+        Ok((backup.file_name, data))
+    }
+}
@@ -0,0 +1,206 @@
+// klyja/backend/src/bin/klyja-admin.rs
+// Operator CLI: runs admin tasks against the database in DATABASE_URL (read from the
+// environment or .env, as the server does), e.g. `klyja-admin users` or
+// `klyja-admin reassign --from 3 --to 8`.
+use backend::{
+    admin::AdminService,
+    db,
+    errors::AppError,
+    jobs,
+    models::{CreateApiTokenRequest, TokenScope},
+    tokens::TokenService,
+    upgrades,
+};
+use clap::{Parser, Subcommand};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "klyja-admin", about = "Administer a Klyja backend's database")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List every user with how many animations they own
+    Users,
+    /// Delete a user and the animations they own
+    DeleteUser {
+        user_id: i32,
+        /// Confirm the deletion; without it nothing is deleted
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Give one user's animations to another
+    Reassign {
+        #[arg(long)]
+        from: i32,
+        #[arg(long)]
+        to: i32,
+        /// Only this animation, rather than all of them
+        #[arg(long)]
+        animation: Option<i32>,
+    },
+    /// Create a personal access token for a user and print its secret
+    MintToken {
+        user_id: i32,
+        #[arg(long)]
+        name: String,
+        /// read or write; repeat for both
+        #[arg(long = "scope", value_parser = parse_scope, default_value = "read")]
+        scopes: Vec<TokenScope>,
+        /// Omit for a token that lasts until revoked
+        #[arg(long)]
+        expires_in_days: Option<u32>,
+    },
+    /// Offer an animation to every user as a template to start from
+    AddTemplate {
+        animation_id: i32,
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        description: Option<String>,
+        /// Templates are listed lowest first
+        #[arg(long, default_value_t = 0)]
+        sort_order: i32,
+    },
+    /// Withdraw a template, keeping its animation
+    RemoveTemplate { template_id: i32 },
+    /// Count the features, points and keyframes of animations saved before they were kept
+    Measure,
+    /// Rewrite documents stored in an older format in the current one
+    UpgradeDocuments,
+    /// Delete expired sessions, uploads, exports and finished jobs now
+    Purge,
+}
+
+fn parse_scope(scope: &str) -> Result<TokenScope, String> {
+    TokenScope::parse(scope).ok_or_else(|| format!("unknown scope '{}'", scope))
+}
+
+/// The message for a failed command; the API's for client errors, else the details.
+fn describe(err: AppError) -> String {
+    match err {
+        AppError::NotFound(msg) | AppError::BadRequest(msg) => msg,
+        other => format!("{:?}", other),
+    }
+}
+
+async fn run(command: Command, pool: &db::DbPool) -> Result<(), AppError> {
+    match command {
+        Command::Users => {
+            for user in AdminService::list_users_logic(pool).await? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{} animations\t{} bytes\t{}",
+                    user.id,
+                    user.username,
+                    user.email.as_deref().unwrap_or("-"),
+                    if user.is_admin { "admin" } else { "user" },
+                    user.animation_count,
+                    user.stored_bytes,
+                    user.created_at.format("%Y-%m-%d")
+                );
+            }
+        }
+        Command::DeleteUser { user_id, yes } => {
+            if !yes {
+                return Err(AppError::BadRequest(format!(
+                    "This deletes user {} and every animation they own; add --yes to go ahead",
+                    user_id
+                )));
+            }
+            let deleted = AdminService::delete_user_logic(pool, user_id).await?;
+            println!("Deleted user {} and {} animations", user_id, deleted);
+        }
+        Command::Reassign {
+            from,
+            to,
+            animation,
+        } => {
+            let moved = AdminService::reassign_animations_logic(pool, from, to, animation).await?;
+            println!(
+                "Gave {} animations from user {} to user {}",
+                moved, from, to
+            );
+        }
+        Command::MintToken {
+            user_id,
+            name,
+            scopes,
+            expires_in_days,
+        } => {
+            let created = TokenService::create_token_logic(
+                pool,
+                user_id,
+                CreateApiTokenRequest {
+                    name,
+                    scopes,
+                    expires_in_days,
+                },
+            )
+            .await?;
+            eprintln!(
+                "Token {} created for user {}; it won't be shown again",
+                created.details.id, user_id
+            );
+            println!("{}", created.token);
+        }
+        Command::AddTemplate {
+            animation_id,
+            name,
+            description,
+            sort_order,
+        } => {
+            let template_id = AdminService::add_template_logic(
+                pool,
+                animation_id,
+                &name,
+                description.as_deref(),
+                sort_order,
+            )
+            .await?;
+            println!("Animation {} is now template {}", animation_id, template_id);
+        }
+        Command::RemoveTemplate { template_id } => {
+            AdminService::remove_template_logic(pool, template_id).await?;
+            println!("Removed template {}", template_id);
+        }
+        Command::Measure => {
+            let measured = AdminService::measure_animations_logic(pool).await?;
+            println!("Measured {} animations", measured);
+        }
+        Command::UpgradeDocuments => {
+            let upgraded = upgrades::upgrade_documents(pool).await?;
+            println!("Upgraded {} documents", upgraded);
+        }
+        Command::Purge => {
+            jobs::purge(pool).await?;
+            println!("Purged");
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenvy::dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+    let cli = Cli::parse();
+
+    let Ok(database_url) = std::env::var("DATABASE_URL") else {
+        eprintln!("DATABASE_URL must be set");
+        return ExitCode::FAILURE;
+    };
+    let pool = db::build_pool(&database_url, 1, db::DEFAULT_CONNECTION_TIMEOUT);
+    match run(cli.command, &pool).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("klyja-admin: {}", describe(err));
+            ExitCode::FAILURE
+        }
+    }
+}
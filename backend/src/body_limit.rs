@@ -0,0 +1,53 @@
+// klyja/backend/src/body_limit.rs
+// Upload size limits: document uploads over MAX_UPLOAD_BYTES, and archive imports over
+// MAX_ARCHIVE_BYTES, are refused with the usual JSON error, saying the limit and (where
+// the client declared it) the size sent, so editors can tell their users why a save
+// failed instead of seeing a dropped request.
+use crate::{errors::AppError, imports::max_archive_bytes, services::max_upload_bytes};
+use axum::{
+    body::Body, extract::Request, http::header::CONTENT_LENGTH, middleware::Next,
+    response::Response,
+};
+use http_body_util::LengthLimitError;
+
+/// Middleware for the upload routes, in place of `DefaultBodyLimit`. Bodies declaring
+/// too large a length are refused before any of them is read; others are read up to
+/// the limit and refused once they pass it.
+pub async fn limit_upload_body(request: Request, next: Next) -> Result<Response, AppError> {
+    limit_body(request, next, max_upload_bytes()).await
+}
+
+/// The same for archive imports, which hold many documents.
+pub async fn limit_archive_body(request: Request, next: Next) -> Result<Response, AppError> {
+    limit_body(request, next, max_archive_bytes()).await
+}
+
+async fn limit_body(request: Request, next: Next, max_bytes: usize) -> Result<Response, AppError> {
+    let declared = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if let Some(received) = declared.filter(|&length| length > max_bytes) {
+        return Err(AppError::BodyTooLarge {
+            max_bytes,
+            received_bytes: Some(received),
+        });
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = axum::body::to_bytes(body, max_bytes).await.map_err(|err| {
+        let err = err.into_inner();
+        if err.downcast_ref::<LengthLimitError>().is_some() {
+            AppError::BodyTooLarge {
+                max_bytes,
+                received_bytes: None,
+            }
+        } else {
+            AppError::BadRequest(format!("Couldn't read the request body: {}", err))
+        }
+    })?;
+    Ok(next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await)
+}
@@ -0,0 +1,304 @@
+// klyja/backend/src/cache.rs
+// Hot-animation cache: loads of popular animations are answered from a cache rather
+// than fetching and decompressing the same multi-megabyte blob from Postgres each
+// time. Entries are only used if the row's `updated_at` still matches, which every
+// write to the row moves, so a save, rename, publication or hide makes older entries
+// useless on every server at once; invalidating just frees the space sooner.
+// The cache is in memory unless the `redis-cache` feature is built and REDIS_URL set,
+// in which case every server shares one in Redis.
+use crate::models::Animation;
+use chrono::NaiveDateTime;
+use lru::LruCache;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Megabytes of documents kept in memory when `ANIMATION_CACHE_MB` doesn't say.
+pub const DEFAULT_CACHE_MB: usize = 256;
+
+pub type CacheFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// What a cached copy must match to be served: when the row was last written, which
+/// the `set_timestamp` trigger moves on every update, and when it was created, which
+/// tells apart rows that reused an ID, such as in a restored database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowStamp {
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl RowStamp {
+    pub fn of(animation: &Animation) -> Self {
+        RowStamp {
+            created_at: animation.created_at,
+            updated_at: animation.updated_at,
+        }
+    }
+}
+
+/// Somewhere to keep loaded animations, documents decompressed. Caching is an
+/// optimization, so implementations log their failures rather than returning them.
+pub trait AnimationCache: Send + Sync {
+    fn get(&self, animation_id: i32) -> CacheFuture<'_, Option<Animation>>;
+    fn put(&self, animation: Animation) -> CacheFuture<'_, ()>;
+    fn invalidate(&self, animation_id: i32) -> CacheFuture<'_, ()>;
+}
+
+/// Least recently used animations in this process, up to a budget of document bytes.
+pub struct MemoryCache {
+    budget_bytes: usize,
+    state: Mutex<MemoryState>,
+}
+
+struct MemoryState {
+    entries: LruCache<i32, Arc<Animation>>,
+    bytes: usize,
+}
+
+impl MemoryCache {
+    pub fn new(budget_bytes: usize) -> Self {
+        MemoryCache {
+            budget_bytes,
+            state: Mutex::new(MemoryState {
+                entries: LruCache::unbounded(),
+                bytes: 0,
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, MemoryState> {
+        // Every change leaves the state consistent, so a panic elsewhere can't spoil it
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn get_now(&self, animation_id: i32) -> Option<Animation> {
+        let hit = self.state().entries.get(&animation_id).cloned();
+        // Copied outside the lock, so one big document doesn't hold up other loads
+        hit.map(|animation| Animation::clone(&animation))
+    }
+
+    fn put_now(&self, animation: Animation) {
+        let size = animation.protobuf_data.len();
+        if size > self.budget_bytes {
+            return;
+        }
+        let mut state = self.state();
+        if let Some(replaced) = state.entries.put(animation.id, Arc::new(animation)) {
+            state.bytes -= replaced.protobuf_data.len();
+        }
+        state.bytes += size;
+        while state.bytes > self.budget_bytes {
+            match state.entries.pop_lru() {
+                Some((_, evicted)) => state.bytes -= evicted.protobuf_data.len(),
+                None => break,
+            }
+        }
+    }
+
+    fn invalidate_now(&self, animation_id: i32) {
+        let mut state = self.state();
+        if let Some(removed) = state.entries.pop(&animation_id) {
+            state.bytes -= removed.protobuf_data.len();
+        }
+    }
+}
+
+impl AnimationCache for MemoryCache {
+    fn get(&self, animation_id: i32) -> CacheFuture<'_, Option<Animation>> {
+        Box::pin(std::future::ready(self.get_now(animation_id)))
+    }
+
+    fn put(&self, animation: Animation) -> CacheFuture<'_, ()> {
+        self.put_now(animation);
+        Box::pin(std::future::ready(()))
+    }
+
+    fn invalidate(&self, animation_id: i32) -> CacheFuture<'_, ()> {
+        self.invalidate_now(animation_id);
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// Animations kept in Redis for an hour, shared by every server using it. Values are
+/// the animation's JSON (which leaves the document out) behind its length, then the
+/// document's bytes.
+#[cfg(feature = "redis-cache")]
+pub struct RedisCache {
+    client: redis::Client,
+    connection: tokio::sync::OnceCell<redis::aio::ConnectionManager>,
+}
+
+#[cfg(feature = "redis-cache")]
+impl RedisCache {
+    const TTL_SECONDS: u64 = 60 * 60;
+
+    pub fn new(url: &str) -> redis::RedisResult<Self> {
+        Ok(RedisCache {
+            client: redis::Client::open(url)?,
+            connection: tokio::sync::OnceCell::new(),
+        })
+    }
+
+    fn key(animation_id: i32) -> String {
+        format!("klyja:animation:{}", animation_id)
+    }
+
+    async fn connection(&self) -> redis::RedisResult<redis::aio::ConnectionManager> {
+        self.connection
+            .get_or_try_init(|| self.client.get_connection_manager())
+            .await
+            .cloned()
+    }
+
+    fn encode(animation: &Animation) -> serde_json::Result<Vec<u8>> {
+        let header = serde_json::to_vec(animation)?;
+        let mut value = Vec::with_capacity(4 + header.len() + animation.protobuf_data.len());
+        value.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        value.extend_from_slice(&header);
+        value.extend_from_slice(&animation.protobuf_data);
+        Ok(value)
+    }
+
+    fn decode(value: &[u8]) -> Option<Animation> {
+        let header_len = u32::from_be_bytes(value.get(..4)?.try_into().ok()?) as usize;
+        let rest = &value[4..];
+        let mut animation: Animation = serde_json::from_slice(rest.get(..header_len)?).ok()?;
+        animation.protobuf_data = rest[header_len..].to_vec();
+        Some(animation)
+    }
+}
+
+#[cfg(feature = "redis-cache")]
+impl AnimationCache for RedisCache {
+    fn get(&self, animation_id: i32) -> CacheFuture<'_, Option<Animation>> {
+        Box::pin(async move {
+            let found = async {
+                let mut conn = self.connection().await?;
+                redis::cmd("GET")
+                    .arg(Self::key(animation_id))
+                    .query_async::<_, Option<Vec<u8>>>(&mut conn)
+                    .await
+            }
+            .await;
+            match found {
+                Ok(value) => value.and_then(|value| Self::decode(&value)),
+                Err(err) => {
+                    tracing::warn!(
+                        "CACHE: Redis get of animation {} failed: {}",
+                        animation_id,
+                        err
+                    );
+                    None
+                }
+            }
+        })
+    }
+
+    fn put(&self, animation: Animation) -> CacheFuture<'_, ()> {
+        Box::pin(async move {
+            let Ok(value) = Self::encode(&animation) else {
+                return;
+            };
+            let stored = async {
+                let mut conn = self.connection().await?;
+                redis::cmd("SET")
+                    .arg(Self::key(animation.id))
+                    .arg(value)
+                    .arg("EX")
+                    .arg(Self::TTL_SECONDS)
+                    .query_async::<_, ()>(&mut conn)
+                    .await
+            }
+            .await;
+            if let Err(err) = stored {
+                tracing::warn!(
+                    "CACHE: Redis put of animation {} failed: {}",
+                    animation.id,
+                    err
+                );
+            }
+        })
+    }
+
+    fn invalidate(&self, animation_id: i32) -> CacheFuture<'_, ()> {
+        Box::pin(async move {
+            let deleted = async {
+                let mut conn = self.connection().await?;
+                redis::cmd("DEL")
+                    .arg(Self::key(animation_id))
+                    .query_async::<_, ()>(&mut conn)
+                    .await
+            }
+            .await;
+            if let Err(err) = deleted {
+                tracing::warn!(
+                    "CACHE: Redis invalidation of animation {} failed: {}",
+                    animation_id,
+                    err
+                );
+            }
+        })
+    }
+}
+
+/// The process's cache, set up on first use from the environment: Redis at
+/// `REDIS_URL` where built with it, else `ANIMATION_CACHE_MB` megabytes in memory.
+/// `ANIMATION_CACHE_MB=0` turns caching off.
+fn animation_cache() -> Option<&'static dyn AnimationCache> {
+    static CACHE: OnceLock<Option<Box<dyn AnimationCache>>> = OnceLock::new();
+    CACHE
+        .get_or_init(|| {
+            #[cfg(feature = "redis-cache")]
+            {
+                if let Ok(url) = std::env::var("REDIS_URL") {
+                    match RedisCache::new(&url) {
+                        Ok(cache) => return Some(Box::new(cache) as Box<dyn AnimationCache>),
+                        Err(err) => tracing::warn!(
+                            "CACHE: REDIS_URL is unusable ({}), caching in memory",
+                            err
+                        ),
+                    }
+                }
+            }
+            let megabytes = std::env::var("ANIMATION_CACHE_MB")
+                .ok()
+                .and_then(|megabytes| megabytes.parse::<usize>().ok())
+                .unwrap_or(DEFAULT_CACHE_MB);
+            (megabytes > 0).then(|| {
+                Box::new(MemoryCache::new(megabytes * 1024 * 1024)) as Box<dyn AnimationCache>
+            })
+        })
+        .as_deref()
+}
+
+/// The copy of an animation `cache` holds, if it is of the row as it is now.
+pub async fn get_current(
+    cache: &dyn AnimationCache,
+    animation_id: i32,
+    current: RowStamp,
+) -> Option<Animation> {
+    let cached = cache.get(animation_id).await?;
+    (RowStamp::of(&cached) == current).then_some(cached)
+}
+
+/// The cached copy of an animation, if there is one of the row as it is now.
+pub async fn get(animation_id: i32, current: RowStamp) -> Option<Animation> {
+    get_current(animation_cache()?, animation_id, current).await
+}
+
+/// Caches a loaded animation, its document decompressed.
+pub async fn put(animation: Animation) {
+    if let Some(cache) = animation_cache() {
+        cache.put(animation).await;
+    }
+}
+
+/// Drops an animation from this process's cache, or Redis's, after a change to it.
+/// Other servers' memory caches skip the entry once its row has changed anyway.
+pub async fn invalidate(animation_id: i32) {
+    if let Some(cache) = animation_cache() {
+        cache.invalidate(animation_id).await;
+    }
+}
@@ -0,0 +1,219 @@
+// klyja/backend/src/db.rs
+// Database plumbing: the async connection pool every service queries through, the
+// embedded migrations, and the checks behind the readiness probe.
+use crate::models::{PoolStats, QueryLatency, Readiness};
+use diesel::migration::MigrationSource;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel_async::pooled_connection::{bb8, AsyncDieselConnectionManager, ManagerConfig};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// Assumes migrations directory is at ../migrations relative to backend/Cargo.toml
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../migrations");
+
+/// Pool of async Postgres connections; queries run on the runtime, not on blocking threads.
+pub type DbPool = bb8::Pool<AsyncPgConnection>;
+/// Error from checking out a pooled connection: a timeout or a failed connect.
+pub type PoolError = bb8::RunError;
+
+/// Pool for heavy public reads (the gallery, search, visitors' loads): a read-only
+/// replica at READ_DATABASE_URL when one is configured, else the primary's pool.
+/// Replicas trail the primary a little, so signed-in callers, who may have just saved,
+/// load from the primary.
+#[derive(Clone)]
+pub struct ReadPool(pub DbPool);
+
+impl ReadPool {
+    /// The pool a load by `caller_id` should read from.
+    pub fn for_caller<'a>(&'a self, primary: &'a DbPool, caller_id: Option<i32>) -> &'a DbPool {
+        match caller_id {
+            Some(_) => primary,
+            None => &self.0,
+        }
+    }
+}
+
+/// Connections the server's pool opens at most.
+pub const DEFAULT_POOL_SIZE: u32 = 10;
+/// How long a request waits for a free connection before failing.
+pub const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long Postgres lets one statement run before cancelling it, by default.
+pub const DEFAULT_STATEMENT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long readiness waits for a connection before declaring the database down.
+pub const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+/// Most recent queries the latency percentile is taken over.
+pub const LATENCY_SAMPLES: usize = 1000;
+
+fn latency_samples() -> std::sync::MutexGuard<'static, VecDeque<Duration>> {
+    static SAMPLES: OnceLock<Mutex<VecDeque<Duration>>> = OnceLock::new();
+    SAMPLES
+        .get_or_init(|| Mutex::new(VecDeque::with_capacity(LATENCY_SAMPLES)))
+        .lock()
+        // A window of timings can't be left inconsistent, so a panic elsewhere can't spoil it
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Adds a query's run time to the window readiness reports latency over.
+pub(crate) fn record_query_latency(elapsed: Duration) {
+    let mut samples = latency_samples();
+    if samples.len() == LATENCY_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(elapsed);
+}
+
+/// The `percentile` (0 to 1) of these timings, nearest rank; None without any.
+pub(crate) fn latency_percentile(samples: &[Duration], percentile: f64) -> Option<Duration> {
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let rank = (percentile * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.max(1) - 1).copied()
+}
+
+/// Runs a query, or a few back to back on one connection, adding how long it took
+/// to the latency window. Transactions and the main reads go through this, so the
+/// percentile is of the work requests wait on; failed queries aren't counted.
+pub async fn timed<T, E>(query: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+    let started = Instant::now();
+    let result = query.await;
+    if result.is_ok() {
+        record_query_latency(started.elapsed());
+    }
+    result
+}
+
+/// How long Postgres lets one statement of the pool's run, from STATEMENT_TIMEOUT_SECS;
+/// 0 lets them run as long as they like.
+pub fn statement_timeout() -> Duration {
+    std::env::var("STATEMENT_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_STATEMENT_TIMEOUT)
+}
+
+/// Opens a pool connection with the statement timeout set, so a stuck query is
+/// cancelled by Postgres and its connection freed even if nobody is waiting on it.
+fn connect(
+    database_url: &str,
+) -> Pin<Box<dyn Future<Output = ConnectionResult<AsyncPgConnection>> + Send + '_>> {
+    Box::pin(async move {
+        let mut conn = AsyncPgConnection::establish(database_url).await?;
+        diesel::sql_query(format!(
+            "SET statement_timeout = {}",
+            statement_timeout().as_millis()
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(ConnectionError::CouldntSetupConfiguration)?;
+        Ok(conn)
+    })
+}
+
+/// Makes a pool for `database_url`. Connections are opened as they are needed, so
+/// this succeeds even while the database is down.
+pub fn build_pool(database_url: &str, max_size: u32, connection_timeout: Duration) -> DbPool {
+    let mut config = ManagerConfig::default();
+    config.custom_setup = Box::new(connect);
+    let manager =
+        AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(database_url, config);
+    bb8::Pool::builder()
+        .max_size(max_size)
+        .connection_timeout(connection_timeout)
+        .build_unchecked(manager)
+}
+
+/// Runs pending migrations over a connection of their own; diesel's migration
+/// harness is synchronous, so call it from a blocking thread.
+pub fn run_migrations(database_url: &str) -> Result<usize, String> {
+    let mut conn = PgConnection::establish(database_url).map_err(|err| err.to_string())?;
+    conn.run_pending_migrations(MIGRATIONS)
+        .map(|versions| versions.len())
+        .map_err(|err| err.to_string())
+}
+
+diesel::table! {
+    __diesel_schema_migrations (version) {
+        version -> VarChar,
+        run_on -> Timestamp,
+    }
+}
+
+/// Versions of the embedded migrations the database hasn't run yet, oldest first.
+async fn pending_migrations(conn: &mut AsyncPgConnection) -> Result<Vec<String>, String> {
+    let applied = __diesel_schema_migrations::table
+        .select(__diesel_schema_migrations::version)
+        .load::<String>(conn)
+        .await
+        .map_err(|err| format!("Reading migrations failed: {}", err))?;
+    let mut pending: Vec<String> = MigrationSource::<Pg>::migrations(&MIGRATIONS)
+        .map_err(|err| format!("Reading embedded migrations failed: {}", err))?
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .filter(|version| !applied.contains(version))
+        .collect();
+    pending.sort();
+    Ok(pending)
+}
+
+/// Checks that the database answers and its schema is current. Never fails: problems
+/// are reported in the result, which is ready only if there were none.
+pub async fn check_readiness(pool: &DbPool) -> Readiness {
+    let checked = async {
+        let mut conn = tokio::time::timeout(READINESS_TIMEOUT, pool.get())
+            .await
+            .map_err(|_| "Timed out waiting for a database connection".to_string())?
+            .map_err(|err| format!("No database connection: {}", err))?;
+        diesel::sql_query("SELECT 1")
+            .execute(&mut conn)
+            .await
+            .map_err(|err| format!("Database query failed: {}", err))?;
+        let pending = pending_migrations(&mut conn).await?;
+        let latest = __diesel_schema_migrations::table
+            .select(diesel::dsl::max(__diesel_schema_migrations::version))
+            .first::<Option<String>>(&mut conn)
+            .await
+            .map_err(|err| format!("Reading migrations failed: {}", err))?;
+        Ok::<_, String>((pending.len(), latest))
+    }
+    .await;
+
+    let state = pool.state();
+    let pool_stats = PoolStats {
+        connections: state.connections,
+        idle_connections: state.idle_connections,
+        in_use_connections: state.connections - state.idle_connections,
+    };
+    let samples: Vec<Duration> = latency_samples().iter().copied().collect();
+    let query_latency = QueryLatency {
+        samples: samples.len(),
+        p95_ms: latency_percentile(&samples, 0.95).map(|p95| p95.as_secs_f64() * 1000.0),
+    };
+    match checked {
+        Ok((pending_migrations, latest_migration)) => Readiness {
+            ready: pending_migrations == 0,
+            database: true,
+            pending_migrations: Some(pending_migrations),
+            latest_migration,
+            pool: pool_stats,
+            query_latency,
+            error: (pending_migrations > 0)
+                .then(|| format!("{} migrations haven't run", pending_migrations)),
+        },
+        Err(error) => Readiness {
+            ready: false,
+            database: false,
+            pending_migrations: None,
+            latest_migration: None,
+            pool: pool_stats,
+            query_latency,
+            error: Some(error),
+        },
+    }
+}
@@ -0,0 +1,266 @@
+// klyja/backend/src/devices.rs
+// Where sessions are used from: the browser's user agent and the client's IP, kept as
+// SESSION_IP_STORAGE says (a salted hash by default, so addresses can be compared but
+// not read back), plus when each session was last seen. Users can review and end
+// their sessions, and sign-ins from a device none of their sessions had are flagged.
+use crate::{errors::AppError, models::SessionInfo, services::content_sha256, DbPool};
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts},
+};
+use chrono::NaiveDateTime;
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use std::net::{IpAddr, SocketAddr};
+
+/// Longest user agent kept; longer ones are cut.
+pub const MAX_USER_AGENT_CHARS: usize = 512;
+/// How often a session's `last_seen_at` is brought up to date, at most.
+pub const LAST_SEEN_INTERVAL_MINUTES: i32 = 1;
+
+/// How client IPs are kept on sessions, from `SESSION_IP_STORAGE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpStorage {
+    /// A salted SHA-256 prefix, salted with `SESSION_IP_SALT` (the default).
+    Hash,
+    /// The network only: the /24 of IPv4 addresses and the /48 of IPv6 ones.
+    Truncate,
+    /// The address as it is.
+    Full,
+    /// Not at all.
+    None,
+}
+
+impl IpStorage {
+    pub fn from_env() -> Self {
+        match std::env::var("SESSION_IP_STORAGE").as_deref() {
+            Ok("truncate") => IpStorage::Truncate,
+            Ok("full") => IpStorage::Full,
+            Ok("none") => IpStorage::None,
+            _ => IpStorage::Hash,
+        }
+    }
+
+    /// The form of `ip` stored on sessions.
+    pub fn apply(self, ip: IpAddr) -> Option<String> {
+        match self {
+            IpStorage::Hash => {
+                let salt = std::env::var("SESSION_IP_SALT").unwrap_or_default();
+                let hash = content_sha256(format!("{}{}", salt, ip).as_bytes());
+                Some(format!("sha256:{}", &hash[..16]))
+            }
+            IpStorage::Truncate => Some(match ip {
+                IpAddr::V4(ip) => {
+                    let [a, b, c, _] = ip.octets();
+                    format!("{}.{}.{}.0/24", a, b, c)
+                }
+                IpAddr::V6(ip) => {
+                    let [a, b, c, ..] = ip.segments();
+                    format!("{:x}:{:x}:{:x}::/48", a, b, c)
+                }
+            }),
+            IpStorage::Full => Some(ip.to_string()),
+            IpStorage::None => None,
+        }
+    }
+}
+
+/// The device a request comes from. The IP is the connection's, or with
+/// `TRUST_PROXY_HEADERS=true` the address the outermost of `TRUSTED_PROXY_HOPS`
+/// proxies (1 by default) saw connecting, for deployments behind reverse proxies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<IpAddr>,
+}
+
+impl ClientInfo {
+    pub fn from_parts(parts: &Parts) -> Self {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|agent| agent.trim().chars().take(MAX_USER_AGENT_CHARS).collect())
+            .filter(|agent: &String| !agent.is_empty());
+        let forwarded = std::env::var("TRUST_PROXY_HEADERS").is_ok_and(|trust| trust == "true");
+        let ip = forwarded
+            .then(|| {
+                parts
+                    .headers
+                    .get("x-forwarded-for")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| forwarded_client(value, trusted_proxy_hops()))
+            })
+            .flatten()
+            .or_else(|| {
+                parts
+                    .extensions
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(addr)| addr.ip())
+            });
+        ClientInfo { user_agent, ip }
+    }
+
+    /// The IP as sessions keep it.
+    pub fn stored_ip(&self) -> Option<String> {
+        self.ip.and_then(|ip| IpStorage::from_env().apply(ip))
+    }
+}
+
+/// How many reverse proxies in front of the server append to `X-Forwarded-For`,
+/// from `TRUSTED_PROXY_HOPS`.
+fn trusted_proxy_hops() -> usize {
+    std::env::var("TRUSTED_PROXY_HOPS")
+        .ok()
+        .and_then(|hops| hops.parse::<usize>().ok())
+        .filter(|&hops| hops > 0)
+        .unwrap_or(1)
+}
+
+/// The client address in an `X-Forwarded-For` value that passed through `hops`
+/// trusted proxies: the one the outermost proxy appended, `hops` entries from the
+/// right. Entries left of it come from the client and can say anything, so they
+/// are never used; a header with fewer entries didn't pass through every proxy.
+pub fn forwarded_client(header: &str, hops: usize) -> Option<IpAddr> {
+    let entries: Vec<&str> = header.split(',').collect();
+    let index = entries.len().checked_sub(hops)?;
+    entries[index].trim().parse::<IpAddr>().ok()
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ClientInfo::from_parts(parts))
+    }
+}
+
+/// Whether a user signing in from `ip` and `user_agent` has other sessions, none
+/// of them from that IP or browser.
+pub(crate) async fn is_new_device(
+    conn: &mut AsyncPgConnection,
+    user_id: i32,
+    ip: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<bool, AppError> {
+    use crate::schema::sessions;
+
+    let known = sessions::table
+        .filter(sessions::user_id.eq(user_id))
+        .select((sessions::ip, sessions::user_agent))
+        .load::<(Option<String>, Option<String>)>(conn)
+        .await?;
+    Ok(!known.is_empty()
+        && !known.iter().any(|(known_ip, known_agent)| {
+            (ip.is_some() && known_ip.as_deref() == ip)
+                || (user_agent.is_some() && known_agent.as_deref() == user_agent)
+        }))
+}
+
+/// Notes that a session was just used from `client`, unless that was noted less than
+/// LAST_SEEN_INTERVAL_MINUTES ago, so busy sessions don't write on every request.
+pub(crate) async fn touch_session(
+    conn: &mut AsyncPgConnection,
+    token: &str,
+    client: &ClientInfo,
+) -> Result<(), AppError> {
+    use crate::schema::sessions;
+
+    diesel::update(
+        sessions::table
+            .filter(sessions::token.eq(token))
+            .filter(sessions::last_seen_at.lt(now - LAST_SEEN_INTERVAL_MINUTES.minutes())),
+    )
+    .set((
+        sessions::last_seen_at.eq(now),
+        sessions::user_agent.eq(client.user_agent.as_deref()),
+        sessions::ip.eq(client.stored_ip()),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub struct SessionService;
+
+impl SessionService {
+    /// The caller's unexpired sessions, most recently seen first.
+    pub async fn list_sessions_logic(
+        pool: &DbPool,
+        user_id: i32,
+        current_session_id: Option<i32>,
+    ) -> Result<Vec<SessionInfo>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::sessions;
+
+        let rows = sessions::table
+            .filter(sessions::user_id.eq(user_id))
+            .filter(sessions::expires_at.gt(now))
+            .order(sessions::last_seen_at.desc())
+            .select((
+                sessions::id,
+                sessions::user_agent,
+                sessions::ip,
+                sessions::created_at,
+                sessions::last_seen_at,
+                sessions::expires_at,
+                sessions::new_device,
+            ))
+            .load::<(
+                i32,
+                Option<String>,
+                Option<String>,
+                NaiveDateTime,
+                NaiveDateTime,
+                NaiveDateTime,
+                bool,
+            )>(&mut conn)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(id, user_agent, ip, created_at, last_seen_at, expires_at, new_device)| {
+                    SessionInfo {
+                        id,
+                        user_agent,
+                        ip,
+                        created_at,
+                        last_seen_at,
+                        expires_at,
+                        new_device,
+                        current: current_session_id == Some(id),
+                    }
+                },
+            )
+            .collect())
+    }
+
+    /// Ends one of the caller's sessions, signing out whoever uses it.
+    pub async fn revoke_session_logic(
+        pool: &DbPool,
+        user_id: i32,
+        session_id: i32,
+    ) -> Result<(), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::sessions;
+
+        let deleted = diesel::delete(
+            sessions::table
+                .filter(sessions::id.eq(session_id))
+                .filter(sessions::user_id.eq(user_id)),
+        )
+        .execute(&mut conn)
+        .await?;
+        if deleted == 0 {
+            return Err(AppError::NotFound(format!(
+                "Session {} not found",
+                session_id
+            )));
+        }
+        tracing::info!("SERVICE: User {} ended session {}", user_id, session_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+// klyja/backend/src/drafts.rs
+// Autosave drafts: editors' clients store their unsaved work here every few seconds,
+// so it survives a crash without each autosave becoming a new version of the
+// animation. A user has at most one draft per animation; saving the animation clears it.
+use crate::{
+    errors::AppError,
+    models::AnimationDraft,
+    protobuf_gen::MapAnimation,
+    services::{
+        compress_document, decompress_document, max_upload_bytes, require_access, AnimationAccess,
+    },
+    DbPool,
+};
+use axum::body::Bytes;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use prost::Message;
+
+/// Removes the user's draft of an animation, once what it held has been saved.
+pub(crate) async fn discard_draft(
+    conn: &mut AsyncPgConnection,
+    animation_id: i32,
+    user_id: i32,
+) -> Result<(), AppError> {
+    use crate::schema::animation_drafts;
+
+    diesel::delete(animation_drafts::table.find((animation_id, user_id)))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub struct DraftService;
+
+impl DraftService {
+    /// Stores the user's draft of an animation over their previous one; needs editor
+    /// access. Drafts are work in progress, so they only have to decode, not validate.
+    /// `based_on_version` defaults to the animation's current version; it is returned.
+    pub async fn save_draft_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        animation_data_bytes: Bytes,
+        user_id: i32,
+        based_on_version: Option<i32>,
+    ) -> Result<i32, AppError> {
+        if animation_data_bytes.len() > max_upload_bytes() {
+            return Err(AppError::BodyTooLarge {
+                max_bytes: max_upload_bytes(),
+                received_bytes: Some(animation_data_bytes.len()),
+            });
+        }
+        MapAnimation::decode(animation_data_bytes.clone())?;
+        let stored = compress_document(&animation_data_bytes)?;
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(
+            &mut conn,
+            animation_id,
+            Some(user_id),
+            AnimationAccess::Edit,
+        )
+        .await?;
+        use crate::schema::{animation_drafts, animations};
+
+        let based_on_version = match based_on_version {
+            Some(version) => version,
+            None => {
+                animations::table
+                    .find(animation_id)
+                    .select(animations::lock_version)
+                    .first::<i32>(&mut conn)
+                    .await?
+            }
+        };
+        diesel::insert_into(animation_drafts::table)
+            .values((
+                animation_drafts::animation_id.eq(animation_id),
+                animation_drafts::user_id.eq(user_id),
+                animation_drafts::protobuf_data.eq(&stored),
+                animation_drafts::based_on_version.eq(based_on_version),
+            ))
+            .on_conflict((animation_drafts::animation_id, animation_drafts::user_id))
+            .do_update()
+            .set((
+                animation_drafts::protobuf_data.eq(&stored),
+                animation_drafts::based_on_version.eq(based_on_version),
+                animation_drafts::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(&mut conn)
+            .await?;
+        tracing::debug!(
+            "SERVICE: User {} autosaved a {}-byte draft of animation {}",
+            user_id,
+            animation_data_bytes.len(),
+            animation_id
+        );
+        Ok(based_on_version)
+    }
+
+    /// Fetches the user's draft of an animation with its document decompressed.
+    pub async fn load_draft_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        user_id: i32,
+    ) -> Result<AnimationDraft, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(
+            &mut conn,
+            animation_id,
+            Some(user_id),
+            AnimationAccess::View,
+        )
+        .await?;
+        use crate::schema::animation_drafts;
+
+        let mut draft = animation_drafts::table
+            .find((animation_id, user_id))
+            .select(AnimationDraft::as_select())
+            .first::<AnimationDraft>(&mut conn)
+            .await
+            .optional()?
+            .ok_or_else(|| {
+                AppError::NotFound(format!("No draft of animation {} to recover", animation_id))
+            })?;
+        drop(conn);
+        draft.protobuf_data = decompress_document(draft.protobuf_data)?;
+        Ok(draft)
+    }
+}
@@ -0,0 +1,198 @@
+// klyja/backend/src/embeds.rs
+// Embed links: owners hand out a URL that loads one animation, read-only, without an
+// account, for embedding a viewer in a blog post or article. The link carries its own
+// proof: the animation ID, its embed generation and the expiry, signed with HMAC-SHA256
+// under `EMBED_SIGNING_KEY`, so nothing is stored per link and the rest of the API stays
+// closed to whoever holds it. Bumping the animation's generation, which the owner can
+// do and reassigning it does, revokes every link made before.
+use crate::{
+    errors::AppError,
+    models::{Animation, EmbedLink, EmbedRequest},
+    services::{open_document, require_access, AnimationAccess},
+    DbPool,
+};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+/// How long an embed link works when the owner doesn't say.
+pub const DEFAULT_EMBED_TTL_HOURS: u32 = 30 * 24;
+/// Longest an embed link can work.
+pub const MAX_EMBED_TTL_HOURS: u32 = 365 * 24;
+
+/// Key embed links are signed with, from `EMBED_SIGNING_KEY`. Without one, embed
+/// links can't be made or loaded: a key of each process's own would break links on
+/// every restart and on every other server.
+fn signing_key() -> Option<&'static [u8]> {
+    static KEY: OnceLock<Option<Vec<u8>>> = OnceLock::new();
+    KEY.get_or_init(|| {
+        let key = std::env::var("EMBED_SIGNING_KEY")
+            .ok()
+            .filter(|key| !key.is_empty());
+        if key.is_none() {
+            tracing::warn!("EMBED_SIGNING_KEY is not set; embed links are turned off");
+        }
+        key.map(String::into_bytes)
+    })
+    .as_deref()
+}
+
+fn mac_for(key: &[u8], animation_id: i32, generation: i32, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(format!("{}.{}.{}", animation_id, generation, expires).as_bytes());
+    mac
+}
+
+/// The token for an embed of the animation until `expires`, as
+/// `<animation id>.<embed generation>.<expiry, unix seconds>.<hex signature>`.
+pub fn sign_embed(
+    key: &[u8],
+    animation_id: i32,
+    generation: i32,
+    expires: DateTime<Utc>,
+) -> String {
+    let expires = expires.timestamp();
+    let signature: String = mac_for(key, animation_id, generation, expires)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{}.{}.{}.{}", animation_id, generation, expires, signature)
+}
+
+/// The animation and embed generation an embed token is for, if it is well formed,
+/// correctly signed and not yet expired. Whether the generation is still current is
+/// up to the caller.
+pub fn verify_embed(key: &[u8], token: &str, at: DateTime<Utc>) -> Option<(i32, i32)> {
+    let mut parts = token.split('.');
+    let animation_id = parts.next()?.parse::<i32>().ok()?;
+    let generation = parts.next()?.parse::<i32>().ok()?;
+    let expires = parts.next()?.parse::<i64>().ok()?;
+    let signature = parts.next()?;
+    if parts.next().is_some() || signature.len() != 64 || expires < at.timestamp() {
+        return None;
+    }
+    let signature = (0..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(signature.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    mac_for(key, animation_id, generation, expires)
+        .verify_slice(&signature)
+        .ok()
+        .map(|_| (animation_id, generation))
+}
+
+fn embeds_turned_off() -> AppError {
+    AppError::Internal("Embed links are turned off: EMBED_SIGNING_KEY is not set".to_string())
+}
+
+fn invalid_embed() -> AppError {
+    AppError::Forbidden("This embed link is invalid, revoked or has expired".to_string())
+}
+
+pub struct EmbedService;
+
+impl EmbedService {
+    /// Creates an embed link for an animation; only its owner may. Fails unless
+    /// `EMBED_SIGNING_KEY` is set.
+    pub async fn create_embed_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: i32,
+        request: EmbedRequest,
+    ) -> Result<EmbedLink, AppError> {
+        let ttl_hours = request.ttl_hours.unwrap_or(DEFAULT_EMBED_TTL_HOURS);
+        if ttl_hours == 0 || ttl_hours > MAX_EMBED_TTL_HOURS {
+            return Err(AppError::BadRequest(format!(
+                "Embed links last between 1 and {} hours",
+                MAX_EMBED_TTL_HOURS
+            )));
+        }
+        let key = signing_key().ok_or_else(embeds_turned_off)?;
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(
+            &mut conn,
+            animation_id,
+            Some(caller_id),
+            AnimationAccess::Owner,
+        )
+        .await?;
+        use crate::schema::animations;
+
+        let generation = animations::table
+            .find(animation_id)
+            .select(animations::embed_generation)
+            .first::<i32>(&mut conn)
+            .await?;
+        let expires_at = Utc::now() + chrono::Duration::hours(i64::from(ttl_hours));
+        let token = sign_embed(key, animation_id, generation, expires_at);
+        tracing::info!(
+            "SERVICE: Embed link for animation {} until {}",
+            animation_id,
+            expires_at
+        );
+        Ok(EmbedLink {
+            url: format!("/embed/{}", token),
+            token,
+            expires_at: expires_at.naive_utc(),
+        })
+    }
+
+    /// Loads the animation an embed token is for. Bad, revoked and expired tokens get
+    /// 403; animations hidden by an admin get 404, as they would anywhere else.
+    pub async fn load_embed_logic(pool: &DbPool, token: &str) -> Result<Animation, AppError> {
+        let key = signing_key().ok_or_else(embeds_turned_off)?;
+        let (animation_id, generation) =
+            verify_embed(key, token, Utc::now()).ok_or_else(invalid_embed)?;
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::animations;
+
+        let (mut animation, current_generation) = crate::db::timed(
+            animations::table
+                .find(animation_id)
+                .filter(animations::hidden_at.is_null())
+                .select((Animation::as_select(), animations::embed_generation))
+                .first::<(Animation, i32)>(&mut conn),
+        )
+        .await
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Animation {} not found", animation_id)))?;
+        drop(conn);
+        if generation != current_generation {
+            return Err(invalid_embed());
+        }
+        open_document(&mut animation)?;
+        Ok(animation)
+    }
+
+    /// Revokes every embed link made for an animation so far; only its owner may.
+    pub async fn revoke_embeds_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: i32,
+    ) -> Result<(), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(
+            &mut conn,
+            animation_id,
+            Some(caller_id),
+            AnimationAccess::Owner,
+        )
+        .await?;
+        use crate::schema::animations;
+
+        diesel::update(animations::table.find(animation_id))
+            .set(animations::embed_generation.eq(animations::embed_generation + 1))
+            .execute(&mut conn)
+            .await?;
+        tracing::info!(
+            "SERVICE: Revoked embed links for animation {}",
+            animation_id
+        );
+        Ok(())
+    }
+}
@@ -1,18 +1,65 @@
 // klyja/backend/src/errors.rs
+use crate::i18n::{self, MessageArgs};
 use axum::{
-    http::StatusCode,
+    http::{
+        header::{CONTENT_LANGUAGE, ETAG, RETRY_AFTER},
+        HeaderValue, StatusCode,
+    },
     response::{IntoResponse, Response},
     Json, // For creating JSON response bodies
 };
-//use diesel::r2d2;
 use serde::Serialize; // For the error response struct for JSON
 use utoipa::ToSchema; // For OpenAPI documentation
 
 // This struct will define the shape of our JSON error responses.
 #[derive(Serialize, ToSchema)]
 pub struct ErrorResponsePayload {
+    // In the language Accept-Language asked for, when there is a translation
     #[schema(example = "Resource not found")] // Example for OpenAPI
     error: String,
+    // Stable across languages, for clients to branch on
+    #[schema(example = "not_found")]
+    code: String,
+    // The more specific English message, when `error` was translated
+    #[schema(example = "Animation with ID 99 not found")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    // One entry per problem found in an uploaded document; omitted for other errors
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    details: Vec<FieldErrorPayload>,
+    // Same as the x-request-id response header; quote it when reporting a problem
+    #[schema(example = "3f0c9a2e-1d4b-4c55-9a61-2b7d0e8f4a10")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    // The animation's version now, when an update was based on an older one
+    #[schema(example = 4)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current_version: Option<i32>,
+    // Which count limit was reached, when a save would have gone over one
+    #[schema(example = "animations")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<String>,
+    // The most that limit allows
+    #[schema(example = 1000)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit_max: Option<i64>,
+    // The most bytes the request's body may have, when it had more
+    #[schema(example = 8388608)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_bytes: Option<usize>,
+    // How many bytes the body had, when that is known
+    #[schema(example = 9437184)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    received_bytes: Option<usize>,
+}
+
+// A problem with one part of an uploaded document
+#[derive(Serialize, ToSchema)]
+pub struct FieldErrorPayload {
+    #[schema(example = "polygons[2].points[0].end_frame")]
+    pub path: String,
+    #[schema(example = "frame 300 is past the last frame")]
+    pub message: String,
 }
 
 // Our custom service error enum
@@ -21,20 +68,93 @@ pub enum AppError {
     // For errors originating from protobuf decoding
     ProtobufDecode(prost::DecodeError),
     // For errors originating from database connection pool
-    DatabasePool(::r2d2::Error),
+    DatabasePool(crate::db::PoolError),
     // For errors originating from Diesel operations (queries, inserts, etc.)
     DatabaseQuery(diesel::result::Error),
     // For when a requested resource is not found (more specific than just a general DB error)
     NotFound(String),
     // For other client-side errors, e.g. invalid input not caught by protobuf
     BadRequest(String),
+    // For documents that decode but fail semantic validation
+    InvalidDocument(Vec<klyja_validate::ValidationError>),
+    // For requests that need a signed-in user but have no valid session
+    Unauthorized(String),
+    // For signed-in users lacking permission on the resource they asked for
+    Forbidden(String),
+    // For request bodies over the configured upload limit
+    PayloadTooLarge(String),
+    // For upload bodies over MAX_UPLOAD_BYTES; `received_bytes` is unknown for bodies
+    // that were cut off rather than declaring their length
+    BodyTooLarge {
+        max_bytes: usize,
+        received_bytes: Option<usize>,
+    },
+    // For saves that would take a user past their storage quota
+    QuotaExceeded(String),
+    // For saves that would take a user past how many animations or versions they may have
+    LimitExceeded {
+        limit: CountLimit,
+        max: i64,
+    },
+    // For stored documents that no longer match their content hash
+    CorruptDocument(String),
+    // For updates that don't say which version of the animation they replace
+    PreconditionRequired(String),
+    // For updates based on a version that has since been replaced
+    VersionConflict {
+        animation_id: i32,
+        current_version: i32,
+    },
+    // For clients making too many sign-in attempts, or locked out after failing too often
+    TooManyRequests {
+        retry_after_secs: u64,
+    },
+    // For requests cancelled after running past their route's time budget
+    Timeout {
+        budget_secs: u64,
+    },
+    // For writes refused while the server is in maintenance mode
+    Maintenance(String),
     // For internal server errors that don't fit other categories
     Internal(String),
 }
 
+impl AppError {
+    /// The error's code in response bodies, e.g. `not_found`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::ProtobufDecode(_) => "invalid_data_format",
+            AppError::DatabasePool(_) | AppError::DatabaseQuery(_) => "database_error",
+            AppError::NotFound(_) => "not_found",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::InvalidDocument(_) => "invalid_document",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::BodyTooLarge { .. } => "body_too_large",
+            AppError::QuotaExceeded(_) => "quota_exceeded",
+            AppError::LimitExceeded { .. } => "limit_exceeded",
+            AppError::CorruptDocument(_) => "corrupt_document",
+            AppError::PreconditionRequired(_) => "precondition_required",
+            AppError::VersionConflict { .. } => "version_conflict",
+            AppError::TooManyRequests { .. } => "too_many_requests",
+            AppError::Timeout { .. } => "timeout",
+            AppError::Maintenance(_) => "maintenance",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+}
+
 // How AppError converts into an HTTP response for Axum
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let code = self.code();
+        let mut details = vec![];
+        let mut current_version = None;
+        let mut limit_reached = None;
+        let mut body_limit = None;
+        let mut retry_after = None;
+        let mut budget = None;
         let (status_code, message) = match self {
             AppError::ProtobufDecode(err) => {
                 tracing::error!("SERVICE ERROR - ProtobufDecode: {}", err);
@@ -65,18 +185,208 @@ impl IntoResponse for AppError {
                 tracing::warn!("SERVICE ERROR - BadRequest: {}", msg);
                 (StatusCode::BAD_REQUEST, msg)
             }
+            AppError::InvalidDocument(errors) => {
+                let msg = klyja_validate::summarize(&errors);
+                tracing::warn!("SERVICE ERROR - InvalidDocument: {}", msg);
+                details = errors
+                    .into_iter()
+                    .map(|e| FieldErrorPayload {
+                        path: e.path,
+                        message: e.message,
+                    })
+                    .collect();
+                (StatusCode::BAD_REQUEST, msg)
+            }
+            AppError::Unauthorized(msg) => {
+                tracing::warn!("SERVICE ERROR - Unauthorized: {}", msg);
+                (StatusCode::UNAUTHORIZED, msg)
+            }
+            AppError::Forbidden(msg) => {
+                tracing::warn!("SERVICE ERROR - Forbidden: {}", msg);
+                (StatusCode::FORBIDDEN, msg)
+            }
+            AppError::PayloadTooLarge(msg) => {
+                tracing::warn!("SERVICE ERROR - PayloadTooLarge: {}", msg);
+                (StatusCode::PAYLOAD_TOO_LARGE, msg)
+            }
+            AppError::BodyTooLarge {
+                max_bytes,
+                received_bytes,
+            } => {
+                let msg = body_too_large_message(max_bytes, received_bytes);
+                tracing::warn!("SERVICE ERROR - BodyTooLarge: {}", msg);
+                body_limit = Some((max_bytes, received_bytes));
+                (StatusCode::PAYLOAD_TOO_LARGE, msg)
+            }
+            AppError::QuotaExceeded(msg) => {
+                tracing::warn!("SERVICE ERROR - QuotaExceeded: {}", msg);
+                (StatusCode::PAYLOAD_TOO_LARGE, msg)
+            }
+            AppError::LimitExceeded { limit, max } => {
+                tracing::warn!(
+                    "SERVICE ERROR - LimitExceeded: {} limit of {}",
+                    limit.as_str(),
+                    max
+                );
+                limit_reached = Some((limit, max));
+                (StatusCode::FORBIDDEN, limit.describe(max))
+            }
+            AppError::CorruptDocument(msg) => {
+                tracing::error!("SERVICE ERROR - CorruptDocument: {}", msg);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "The stored animation is damaged".to_string(),
+                )
+            }
+            AppError::PreconditionRequired(msg) => {
+                tracing::warn!("SERVICE ERROR - PreconditionRequired: {}", msg);
+                (StatusCode::PRECONDITION_REQUIRED, msg)
+            }
+            AppError::VersionConflict {
+                animation_id,
+                current_version: version,
+            } => {
+                tracing::warn!(
+                    "SERVICE ERROR - VersionConflict: animation {} is at version {}",
+                    animation_id,
+                    version
+                );
+                current_version = Some(version);
+                (
+                    StatusCode::CONFLICT,
+                    format!(
+                        "Animation {} was changed since you loaded it; it is now at version {}",
+                        animation_id, version
+                    ),
+                )
+            }
+            AppError::TooManyRequests { retry_after_secs } => {
+                tracing::warn!(
+                    "SERVICE ERROR - TooManyRequests: retry after {}s",
+                    retry_after_secs
+                );
+                retry_after = Some(retry_after_secs);
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    too_many_requests_message(retry_after_secs),
+                )
+            }
+            AppError::Timeout { budget_secs } => {
+                tracing::error!("SERVICE ERROR - Timeout: cancelled after {}s", budget_secs);
+                budget = Some(budget_secs);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    timeout_message(budget_secs),
+                )
+            }
+            AppError::Maintenance(msg) => {
+                tracing::info!("SERVICE ERROR - Maintenance: write refused");
+                (StatusCode::SERVICE_UNAVAILABLE, msg)
+            }
             AppError::Internal(msg) => {
                 tracing::error!("SERVICE ERROR - Internal: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, msg)
             }
         };
 
+        // Logged in English above; the caller reads the message in their language
+        let language = i18n::current_language();
+        let args = MessageArgs {
+            count: limit_reached
+                .map(|(_, max)| max as u64)
+                .or(body_limit.map(|(max_bytes, _)| max_bytes as u64)),
+            seconds: retry_after.or(budget),
+            version: current_version,
+        };
+        let (error, detail) = match i18n::translate(language, code, args) {
+            Some(translated) => (translated, Some(message)),
+            None => (message, None),
+        };
+
         // Create a JSON response body
-        let body = Json(ErrorResponsePayload { error: message });
-        (status_code, body).into_response()
+        let body = Json(ErrorResponsePayload {
+            error,
+            code: code.to_string(),
+            detail,
+            details,
+            request_id: crate::request_id::current_request_id(),
+            current_version,
+            limit: limit_reached.map(|(limit, _)| limit.as_str().to_string()),
+            limit_max: limit_reached.map(|(_, max)| max),
+            max_bytes: body_limit.map(|(max_bytes, _)| max_bytes),
+            received_bytes: body_limit.and_then(|(_, received_bytes)| received_bytes),
+        });
+        let mut response = (status_code, body).into_response();
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_LANGUAGE, HeaderValue::from_static(language.tag()));
+        if let Some(version) = current_version {
+            if let Ok(etag) = HeaderValue::from_str(&version_etag(version)) {
+                headers.insert(ETAG, etag);
+            }
+        }
+        if let Some(secs) = retry_after {
+            headers.insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
+
+/// A per-user count that saves may not take past its maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountLimit {
+    // Animations the user owns
+    Animations,
+}
+
+impl CountLimit {
+    /// The name clients see in the error's `limit` field.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CountLimit::Animations => "animations",
+        }
+    }
+
+    pub(crate) fn describe(self, max: i64) -> String {
+        match self {
+            CountLimit::Animations => {
+                format!("You already have the most animations allowed ({})", max)
+            }
+        }
+    }
+}
+
+/// What clients are told when a body is over the upload limit.
+pub(crate) fn body_too_large_message(max_bytes: usize, received_bytes: Option<usize>) -> String {
+    match received_bytes {
+        Some(received) => format!(
+            "Request body is {} bytes, the limit is {}",
+            received, max_bytes
+        ),
+        None => format!("Request body is over the limit of {} bytes", max_bytes),
     }
 }
 
+/// What clients are told when they must wait before trying to sign in again.
+pub(crate) fn too_many_requests_message(retry_after_secs: u64) -> String {
+    format!(
+        "Too many sign-in attempts; try again in {} seconds",
+        retry_after_secs
+    )
+}
+
+/// What clients are told when their request ran out of time.
+pub(crate) fn timeout_message(budget_secs: u64) -> String {
+    format!(
+        "The request took longer than {} seconds and was cancelled; try again later",
+        budget_secs
+    )
+}
+
+/// The ETag for a version of an animation's document, e.g. `"3"`.
+pub fn version_etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
 // Helper: Convert prost::DecodeError into AppError
 // This allows us to use `?` with functions returning prost::DecodeError
 // within service functions that return Result<_, AppError>
@@ -86,9 +396,9 @@ impl From<prost::DecodeError> for AppError {
     }
 }
 
-// Helper: Convert r2d2::Error into AppError
-impl From<::r2d2::Error> for AppError {
-    fn from(err: ::r2d2::Error) -> Self {
+// Helper: Convert pool checkout errors into AppError
+impl From<crate::db::PoolError> for AppError {
+    fn from(err: crate::db::PoolError) -> Self {
         AppError::DatabasePool(err)
     }
 }
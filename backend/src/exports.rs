@@ -0,0 +1,542 @@
+// klyja/backend/src/exports.rs
+// Video exports: every frame of an animation drawn by the headless engine, encoded
+// as an animated GIF here or as MP4 by the server's ffmpeg. Frame exports write the
+// features at each frame as GeoJSON instead, one file per frame in a zip, for
+// analysis in GIS tools. Either runs in the job queue; clients poll the export and
+// download the file once it is done.
+use crate::{
+    errors::AppError,
+    jobs::{self, Job},
+    models::{ExportFormat, ExportFramesRequest, ExportJob, ExportVideoRequest},
+    protobuf_gen::MapAnimation,
+    services::{decompress_document, require_access, AnimationAccess},
+    DbPool,
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use geco_core::raster::{self, Raster};
+use prost::Message;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::process::{Command, Stdio};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+/// Width and height of exports when the request doesn't say.
+pub const DEFAULT_EXPORT_SIZE: u32 = 480;
+pub const MAX_EXPORT_SIZE: u32 = 1024;
+pub const DEFAULT_EXPORT_FPS: u32 = 24;
+/// GIF delays are in hundredths of a second and browsers slow anything under 2 down.
+pub const MAX_EXPORT_FPS: u32 = 50;
+/// Longer animations are cut off here, so one export can't run for hours.
+pub const MAX_EXPORT_FRAMES: i32 = 1000;
+
+/// Frames an export covers: `0..total_frames`, or just frame 0 for untimed documents.
+pub fn export_frame_count(animation: &MapAnimation) -> i32 {
+    animation.total_frames.clamp(1, MAX_EXPORT_FRAMES)
+}
+
+fn render_frame(animation: &MapAnimation, frame: i32, size: u32) -> Raster {
+    raster::render_orthographic(animation, frame, size, size)
+}
+
+/// Up to 256 colors for a GIF. The renderer only blends a handful of colors, so
+/// the exact ones usually fit; otherwise pixels snap to a uniform 3-3-2 RGB cube.
+pub enum Palette {
+    Exact(HashMap<[u8; 3], u8>),
+    Uniform,
+}
+
+impl Palette {
+    pub fn for_frames(frames: impl Iterator<Item = Raster>) -> Self {
+        let mut colors = HashMap::new();
+        for frame in frames {
+            for pixel in frame.pixels.chunks_exact(4) {
+                let rgb = [pixel[0], pixel[1], pixel[2]];
+                if !colors.contains_key(&rgb) {
+                    if colors.len() == 256 {
+                        return Palette::Uniform;
+                    }
+                    colors.insert(rgb, colors.len() as u8);
+                }
+            }
+        }
+        Palette::Exact(colors)
+    }
+
+    pub fn index(&self, rgb: [u8; 3]) -> u8 {
+        match self {
+            Palette::Exact(colors) => colors[&rgb],
+            Palette::Uniform => (rgb[0] & 0xe0) | ((rgb[1] & 0xe0) >> 3) | (rgb[2] >> 6),
+        }
+    }
+
+    /// The full 256-entry color table, unused entries black.
+    pub fn table(&self) -> Vec<[u8; 3]> {
+        let mut table = vec![[0; 3]; 256];
+        match self {
+            Palette::Exact(colors) => {
+                for (&rgb, &index) in colors {
+                    table[index as usize] = rgb;
+                }
+            }
+            Palette::Uniform => {
+                for (index, rgb) in table.iter_mut().enumerate() {
+                    // Spread each channel's bits over the full 0-255 range
+                    let (r, g, b) = (index >> 5, (index >> 2) & 0x07, index & 0x03);
+                    *rgb = [
+                        (r * 255 / 7) as u8,
+                        (g * 255 / 7) as u8,
+                        (b * 255 / 3) as u8,
+                    ];
+                }
+            }
+        }
+        table
+    }
+}
+
+/// Renders the animation as a looping GIF, one image per frame.
+pub fn render_gif(animation: &MapAnimation, size: u32, fps: u32) -> Result<Vec<u8>, AppError> {
+    let frames = export_frame_count(animation);
+    let palette = Palette::for_frames((0..frames).map(|f| render_frame(animation, f, size)));
+    let delay = ((100 + fps / 2) / fps).max(2) as u16;
+
+    let mut gif = gif_header(size, size, &palette);
+    for frame in 0..frames {
+        write_gif_frame(
+            &mut gif,
+            &render_frame(animation, frame, size),
+            &palette,
+            delay,
+        )?;
+    }
+    gif.push(0x3b); // Trailer
+    Ok(gif)
+}
+
+fn gif_header(width: u32, height: u32, palette: &Palette) -> Vec<u8> {
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend((width as u16).to_le_bytes());
+    gif.extend((height as u16).to_le_bytes());
+    gif.extend([0xf7, 0, 0]); // 256-color global table, background index 0, square pixels
+    gif.extend(palette.table().concat());
+    // NETSCAPE2.0 extension: loop forever
+    gif.extend([0x21, 0xff, 0x0b]);
+    gif.extend(b"NETSCAPE2.0");
+    gif.extend([0x03, 0x01, 0x00, 0x00, 0x00]);
+    gif
+}
+
+fn write_gif_frame(
+    gif: &mut Vec<u8>,
+    raster: &Raster,
+    palette: &Palette,
+    delay: u16,
+) -> Result<(), AppError> {
+    // Graphic control extension: the delay before the next frame, no transparency
+    gif.extend([0x21, 0xf9, 0x04, 0x00]);
+    gif.extend(delay.to_le_bytes());
+    gif.extend([0x00, 0x00]);
+
+    // Image descriptor covering the whole canvas, no local color table
+    gif.push(0x2c);
+    gif.extend([0, 0, 0, 0]);
+    gif.extend((raster.width as u16).to_le_bytes());
+    gif.extend((raster.height as u16).to_le_bytes());
+    gif.push(0x00);
+
+    let indices: Vec<u8> = raster
+        .pixels
+        .chunks_exact(4)
+        .map(|pixel| palette.index([pixel[0], pixel[1], pixel[2]]))
+        .collect();
+    let compressed = weezl::encode::Encoder::new(weezl::BitOrder::Lsb, 8)
+        .encode(&indices)
+        .map_err(|err| AppError::Internal(format!("GIF compression failed: {}", err)))?;
+    gif.push(8); // LZW minimum code size
+    for block in compressed.chunks(255) {
+        gif.push(block.len() as u8);
+        gif.extend(block);
+    }
+    gif.push(0x00);
+    Ok(())
+}
+
+/// Renders the animation as H.264 MP4 by piping raw frames through ffmpeg, found
+/// at `FFMPEG_PATH` or on the PATH.
+pub fn render_mp4(animation: &MapAnimation, size: u32, fps: u32) -> Result<Vec<u8>, AppError> {
+    let ffmpeg = std::env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_string());
+    let frame_size = format!("{}x{}", size, size);
+    let fps = fps.to_string();
+    let mut child = Command::new(&ffmpeg)
+        .args(["-hide_banner", "-loglevel", "error"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &frame_size, "-r", &fps, "-i", "pipe:0"])
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+        // Fragmented MP4 can be written to a pipe, which can't seek back to the header
+        .args(["-movflags", "frag_keyframe+empty_moov"])
+        .args(["-f", "mp4", "pipe:1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            AppError::Internal(format!(
+                "MP4 export needs ffmpeg on the server ({}): {}",
+                ffmpeg, err
+            ))
+        })?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let output = std::thread::scope(|scope| {
+        // Feed frames while the main thread drains ffmpeg's output, so neither pipe fills up
+        scope.spawn(move || {
+            for frame in 0..export_frame_count(animation) {
+                if stdin
+                    .write_all(&render_frame(animation, frame, size).pixels)
+                    .is_err()
+                {
+                    break; // ffmpeg exited; its stderr says why
+                }
+            }
+        });
+        child.wait_with_output()
+    })
+    .map_err(|err| AppError::Internal(format!("Running ffmpeg failed: {}", err)))?;
+
+    if !output.status.success() {
+        return Err(AppError::Internal(format!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Writes the features at every `step`-th frame as GeoJSON FeatureCollections, one
+/// file per frame named after it (`frame-00010.geojson`), into a zip.
+pub fn render_geojson_frames(animation: &MapAnimation, step: u32) -> Result<Vec<u8>, AppError> {
+    let zip_error = |err: zip::result::ZipError| {
+        AppError::Internal(format!("Writing the frames zip failed: {}", err))
+    };
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for frame in (0..export_frame_count(animation)).step_by(step.max(1) as usize) {
+        let collection = geco_core::geojson::frame_collection(&animation.polygons, frame);
+        let json = serde_json::to_vec(&collection)
+            .map_err(|err| AppError::Internal(format!("Serializing GeoJSON failed: {}", err)))?;
+        writer
+            .start_file(format!("frame-{:05}.geojson", frame), options)
+            .map_err(zip_error)?;
+        writer
+            .write_all(&json)
+            .map_err(|err| AppError::Internal(format!("Writing the frames zip failed: {}", err)))?;
+    }
+    Ok(writer.finish().map_err(zip_error)?.into_inner())
+}
+
+/// Where clients download a finished export.
+pub fn export_download_url(job_id: i32) -> String {
+    format!("/api/exports/{}/download", job_id)
+}
+
+type ExportJobRow = (
+    i32,
+    i32,
+    Option<i32>,
+    String,
+    String,
+    Option<String>,
+    NaiveDateTime,
+    Option<NaiveDateTime>,
+);
+
+/// Loads a job for a caller who can view its animation.
+async fn load_export_job(
+    conn: &mut AsyncPgConnection,
+    job_id: i32,
+    caller_id: Option<i32>,
+) -> Result<ExportJob, AppError> {
+    use crate::schema::export_jobs;
+
+    let (id, animation_id, queue_job_id, format, status, error, created_at, finished_at) =
+        export_jobs::table
+            .find(job_id)
+            .select((
+                export_jobs::id,
+                export_jobs::animation_id,
+                export_jobs::job_id,
+                export_jobs::format,
+                export_jobs::status,
+                export_jobs::error,
+                export_jobs::created_at,
+                export_jobs::finished_at,
+            ))
+            .first::<ExportJobRow>(conn)
+            .await
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Export {} not found", job_id)))?;
+    require_access(conn, animation_id, caller_id, AnimationAccess::View).await?;
+
+    Ok(ExportJob {
+        id,
+        animation_id,
+        job_id: queue_job_id,
+        download_url: (status == "done").then(|| export_download_url(id)),
+        format,
+        status,
+        error,
+        created_at,
+        finished_at,
+    })
+}
+
+/// Renders an export's file and stores it. Run by the job queue, which retries
+/// failures and calls `mark_export_failed` once it gives up.
+pub async fn run_export_job(pool: &DbPool, export_id: i32) -> Result<(), AppError> {
+    use crate::schema::{animations, export_jobs};
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    let (animation_id, format, size, fps, frame_step) = export_jobs::table
+        .find(export_id)
+        .select((
+            export_jobs::animation_id,
+            export_jobs::format,
+            export_jobs::size,
+            export_jobs::fps,
+            export_jobs::frame_step,
+        ))
+        .first::<(i32, String, i32, i32, i32)>(&mut conn)
+        .await?;
+    let format = ExportFormat::parse(&format).ok_or_else(|| {
+        AppError::Internal(format!(
+            "Export {} has unknown format {}",
+            export_id, format
+        ))
+    })?;
+    let protobuf_data = animations::table
+        .find(animation_id)
+        .select(animations::protobuf_data)
+        .first::<Vec<u8>>(&mut conn)
+        .await?;
+
+    diesel::update(export_jobs::table.find(export_id))
+        .set(export_jobs::status.eq("running"))
+        .execute(&mut conn)
+        .await?;
+    drop(conn); // Renders can take minutes; don't hold a connection meanwhile
+    let output = tokio::task::spawn_blocking(move || {
+        let animation = MapAnimation::decode(decompress_document(protobuf_data)?.as_slice())?;
+        match format {
+            ExportFormat::Gif => render_gif(&animation, size as u32, fps as u32),
+            ExportFormat::Mp4 => render_mp4(&animation, size as u32, fps as u32),
+            ExportFormat::Geojson => render_geojson_frames(&animation, frame_step as u32),
+        }
+    })
+    .await
+    .map_err(|join_err| {
+        AppError::Internal(format!("Tokio spawn_blocking join error: {}", join_err))
+    })??;
+    tracing::info!(
+        "JOB: Export {} rendered {} bytes of {}",
+        export_id,
+        output.len(),
+        format.as_str()
+    );
+    diesel::update(export_jobs::table.find(export_id))
+        .set((
+            export_jobs::status.eq("done"),
+            export_jobs::error.eq(None::<String>),
+            export_jobs::output.eq(output),
+            export_jobs::finished_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut pool.get().await.map_err(AppError::DatabasePool)?)
+        .await?;
+    Ok(())
+}
+
+/// Records that an export won't be rendered, with the reason shown to the client.
+pub async fn mark_export_failed(
+    pool: &DbPool,
+    export_id: i32,
+    error: &str,
+) -> Result<(), AppError> {
+    use crate::schema::export_jobs;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    diesel::update(export_jobs::table.find(export_id))
+        .set((
+            export_jobs::status.eq("failed"),
+            export_jobs::error.eq(error),
+            export_jobs::finished_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Records an export of an animation the caller can load and queues its job.
+async fn queue_export(
+    pool: &DbPool,
+    animation_id: i32,
+    caller_id: Option<i32>,
+    format: ExportFormat,
+    size: i32,
+    fps: i32,
+    frame_step: i32,
+) -> Result<ExportJob, AppError> {
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    require_access(&mut conn, animation_id, caller_id, AnimationAccess::View).await?;
+    use crate::schema::export_jobs;
+
+    let export_id = conn
+        .transaction::<_, AppError, _>(|conn| {
+            async move {
+                let export_id = diesel::insert_into(export_jobs::table)
+                    .values((
+                        export_jobs::animation_id.eq(animation_id),
+                        export_jobs::format.eq(format.as_str()),
+                        export_jobs::size.eq(size),
+                        export_jobs::fps.eq(fps),
+                        export_jobs::frame_step.eq(frame_step),
+                    ))
+                    .returning(export_jobs::id)
+                    .get_result::<i32>(conn)
+                    .await?;
+                let job = match format {
+                    ExportFormat::Geojson => Job::ExportFrames { export_id },
+                    ExportFormat::Gif | ExportFormat::Mp4 => Job::ExportVideo { export_id },
+                };
+                let queue_job_id = jobs::enqueue(conn, &job, Some(animation_id)).await?;
+                diesel::update(export_jobs::table.find(export_id))
+                    .set(export_jobs::job_id.eq(queue_job_id))
+                    .execute(conn)
+                    .await?;
+                Ok(export_id)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    load_export_job(&mut conn, export_id, caller_id).await
+}
+
+pub struct ExportService;
+
+impl ExportService {
+    /// Starts rendering an animation the caller can load to a video file.
+    pub async fn start_video_export_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: Option<i32>,
+        request: ExportVideoRequest,
+    ) -> Result<ExportJob, AppError> {
+        let size = request.size.unwrap_or(DEFAULT_EXPORT_SIZE);
+        let fps = request.fps.unwrap_or(DEFAULT_EXPORT_FPS);
+        if !(16..=MAX_EXPORT_SIZE).contains(&size) {
+            return Err(AppError::BadRequest(format!(
+                "Export size must be between 16 and {} pixels",
+                MAX_EXPORT_SIZE
+            )));
+        }
+        if !(1..=MAX_EXPORT_FPS).contains(&fps) {
+            return Err(AppError::BadRequest(format!(
+                "Export frame rate must be between 1 and {}",
+                MAX_EXPORT_FPS
+            )));
+        }
+        // H.264 needs even dimensions
+        let size = match request.format {
+            ExportFormat::Mp4 => size & !1,
+            ExportFormat::Gif | ExportFormat::Geojson => size,
+        };
+        tracing::info!(
+            "SERVICE: Exporting animation {} as {} ({}px, {} fps)",
+            animation_id,
+            request.format.as_str(),
+            size,
+            fps
+        );
+
+        queue_export(
+            pool,
+            animation_id,
+            caller_id,
+            request.format,
+            size as i32,
+            fps as i32,
+            1,
+        )
+        .await
+    }
+
+    /// Starts writing the features at every `step`-th frame of an animation the caller
+    /// can load to GeoJSON files.
+    pub async fn start_frames_export_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: Option<i32>,
+        request: ExportFramesRequest,
+    ) -> Result<ExportJob, AppError> {
+        let step = request.step.unwrap_or(1);
+        if !(1..=MAX_EXPORT_FRAMES as u32).contains(&step) {
+            return Err(AppError::BadRequest(format!(
+                "Frame step must be between 1 and {}",
+                MAX_EXPORT_FRAMES
+            )));
+        }
+        tracing::info!(
+            "SERVICE: Exporting every {} frames of animation {} as GeoJSON",
+            step,
+            animation_id
+        );
+        queue_export(
+            pool,
+            animation_id,
+            caller_id,
+            ExportFormat::Geojson,
+            0, // Nothing is drawn
+            0,
+            step as i32,
+        )
+        .await
+    }
+
+    /// Reports an export's progress; anyone who can load its animation may ask.
+    pub async fn export_status_logic(
+        pool: &DbPool,
+        job_id: i32,
+        caller_id: Option<i32>,
+    ) -> Result<ExportJob, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        load_export_job(&mut conn, job_id, caller_id).await
+    }
+
+    /// Fetches a finished export's file and its format.
+    pub async fn download_export_logic(
+        pool: &DbPool,
+        job_id: i32,
+        caller_id: Option<i32>,
+    ) -> Result<(ExportFormat, Vec<u8>), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        let job = load_export_job(&mut conn, job_id, caller_id).await?;
+        use crate::schema::export_jobs;
+
+        let output = export_jobs::table
+            .find(job_id)
+            .select(export_jobs::output)
+            .first::<Option<Vec<u8>>>(&mut conn)
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(format!("Export {} is {}, not done", job_id, job.status))
+            })?;
+        let format = ExportFormat::parse(&job.format).ok_or_else(|| {
+            AppError::Internal(format!(
+                "Export {} has unknown format {}",
+                job_id, job.format
+            ))
+        })?;
+        Ok((format, output))
+    }
+}
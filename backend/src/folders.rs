@@ -0,0 +1,208 @@
+// klyja/backend/src/folders.rs
+// Folders: each user can sort the animations they own into nested folders, so the
+// flat list of their saves can be organized into projects. Folders are private to
+// their owner; filing an animation changes nothing about who can load it.
+use crate::{
+    cache,
+    errors::AppError,
+    models::{Folder, FolderRequest, MoveAnimationRequest},
+    services::{require_access, run_in_transaction, AnimationAccess},
+    DbPool,
+};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use std::collections::HashMap;
+
+/// Longest folder name, in characters.
+pub const MAX_FOLDER_NAME_CHARS: usize = 255;
+
+fn folder_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_FOLDER_NAME_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "Folder names need between 1 and {} characters",
+            MAX_FOLDER_NAME_CHARS
+        )));
+    }
+    Ok(name.to_string())
+}
+
+/// Fails with 404 unless the folder exists and belongs to the user, so other
+/// users' folders can't be probed for.
+async fn require_own_folder(
+    conn: &mut AsyncPgConnection,
+    user_id: i32,
+    folder_id: i32,
+) -> Result<(), AppError> {
+    use crate::schema::folders;
+
+    let found = folders::table
+        .find(folder_id)
+        .filter(folders::owner_id.eq(user_id))
+        .select(folders::id)
+        .first::<i32>(conn)
+        .await
+        .optional()?;
+    match found {
+        Some(_) => Ok(()),
+        None => Err(AppError::NotFound(format!(
+            "Folder {} not found",
+            folder_id
+        ))),
+    }
+}
+
+pub struct FolderService;
+
+impl FolderService {
+    /// Lists all of the user's folders, by name; clients build the tree from parent_id.
+    pub async fn list_folders_logic(pool: &DbPool, user_id: i32) -> Result<Vec<Folder>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::folders;
+
+        folders::table
+            .filter(folders::owner_id.eq(user_id))
+            .order((folders::name.asc(), folders::id.asc()))
+            .select(Folder::as_select())
+            .load::<Folder>(&mut conn)
+            .await
+            .map_err(AppError::DatabaseQuery)
+    }
+
+    /// Creates a folder at the top level or inside one of the user's folders.
+    pub async fn create_folder_logic(
+        pool: &DbPool,
+        user_id: i32,
+        request: FolderRequest,
+    ) -> Result<Folder, AppError> {
+        let name = folder_name(&request.name)?;
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        if let Some(parent_id) = request.parent_id {
+            require_own_folder(&mut conn, user_id, parent_id).await?;
+        }
+        use crate::schema::folders;
+
+        let folder = diesel::insert_into(folders::table)
+            .values((
+                folders::owner_id.eq(user_id),
+                folders::parent_id.eq(request.parent_id),
+                folders::name.eq(&name),
+            ))
+            .returning(Folder::as_returning())
+            .get_result::<Folder>(&mut conn)
+            .await?;
+        tracing::info!("SERVICE: User {} created folder {}", user_id, folder.id);
+        Ok(folder)
+    }
+
+    /// Renames a folder and moves it under `parent_id`, refusing to put it inside itself.
+    pub async fn update_folder_logic(
+        pool: &DbPool,
+        user_id: i32,
+        folder_id: i32,
+        request: FolderRequest,
+    ) -> Result<Folder, AppError> {
+        let name = folder_name(&request.name)?;
+        let parent_id = request.parent_id;
+        run_in_transaction(pool, |conn| {
+            async move {
+                use crate::schema::folders;
+
+                // Locked, so two moves can't each make the other's folder its ancestor
+                let parents: HashMap<i32, Option<i32>> = folders::table
+                    .filter(folders::owner_id.eq(user_id))
+                    .select((folders::id, folders::parent_id))
+                    .for_update()
+                    .load::<(i32, Option<i32>)>(conn)
+                    .await?
+                    .into_iter()
+                    .collect();
+                for id in std::iter::once(folder_id).chain(parent_id) {
+                    if !parents.contains_key(&id) {
+                        return Err(AppError::NotFound(format!("Folder {} not found", id)));
+                    }
+                }
+                let mut ancestor = parent_id;
+                while let Some(id) = ancestor {
+                    if id == folder_id {
+                        return Err(AppError::BadRequest(
+                            "A folder can't be moved into itself or one of its subfolders"
+                                .to_string(),
+                        ));
+                    }
+                    ancestor = parents.get(&id).copied().flatten();
+                }
+
+                let folder = diesel::update(folders::table.find(folder_id))
+                    .set((folders::name.eq(&name), folders::parent_id.eq(parent_id)))
+                    .returning(Folder::as_returning())
+                    .get_result::<Folder>(conn)
+                    .await?;
+                Ok(folder)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    /// Deletes a folder and its subfolders; the animations in them go back to the top level.
+    pub async fn delete_folder_logic(
+        pool: &DbPool,
+        user_id: i32,
+        folder_id: i32,
+    ) -> Result<(), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::folders;
+
+        let deleted = diesel::delete(
+            folders::table
+                .find(folder_id)
+                .filter(folders::owner_id.eq(user_id)),
+        )
+        .execute(&mut conn)
+        .await?;
+        if deleted == 0 {
+            return Err(AppError::NotFound(format!(
+                "Folder {} not found",
+                folder_id
+            )));
+        }
+        tracing::info!("SERVICE: User {} deleted folder {}", user_id, folder_id);
+        Ok(())
+    }
+
+    /// Files one of the caller's animations in one of their folders, or back at the top level.
+    pub async fn move_animation_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: i32,
+        request: MoveAnimationRequest,
+    ) -> Result<(), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(
+            &mut conn,
+            animation_id,
+            Some(caller_id),
+            AnimationAccess::Owner,
+        )
+        .await?;
+        if let Some(folder_id) = request.folder_id {
+            require_own_folder(&mut conn, caller_id, folder_id).await?;
+        }
+        use crate::schema::animations;
+
+        // Filing isn't an edit, so the version stays as it is
+        diesel::update(animations::table.find(animation_id))
+            .set(animations::folder_id.eq(request.folder_id))
+            .execute(&mut conn)
+            .await?;
+        cache::invalidate(animation_id).await;
+        tracing::info!(
+            "SERVICE: Animation {} moved to folder {:?}",
+            animation_id,
+            request.folder_id
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+// klyja/backend/src/grpc.rs
+// gRPC service for programmatic pipelines, such as batch importers and render farms,
+// that prefer it to REST. Each call runs the same AnimationService logic as its REST
+// endpoint; the service is defined in protobuf/AnimationService.proto and served on
+// its own port (GRPC_PORT).
+use crate::{
+    errors::{body_too_large_message, timeout_message, too_many_requests_message, AppError},
+    maintenance,
+    models::{ListSort, TokenScope},
+    services::{AnimationService, IfMatch},
+    stats, tokens, DbPool,
+};
+use bytes::Bytes;
+use prost::Message;
+use tonic::{metadata::MetadataMap, Request, Response, Status};
+
+/// Port the gRPC service listens on when `GRPC_PORT` doesn't say.
+pub const DEFAULT_GRPC_PORT: u16 = 50051;
+
+// Generated from protobuf/AnimationService.proto
+pub mod proto {
+    tonic::include_proto!("klyja.animation_service.v1");
+}
+
+use proto::animations_server::{Animations, AnimationsServer};
+use proto::{
+    AnimationSummary, DeleteAnimationRequest, DeleteAnimationResponse, ListAnimationsRequest,
+    ListAnimationsResponse, LoadAnimationRequest, LoadAnimationResponse, SaveAnimationRequest,
+    SaveAnimationResponse,
+};
+
+/// The gRPC status for an error, with the message REST clients would see.
+fn status(err: AppError) -> Status {
+    match err {
+        AppError::ProtobufDecode(err) => {
+            Status::invalid_argument(format!("Invalid data format: {}", err))
+        }
+        AppError::InvalidDocument(errors) => {
+            Status::invalid_argument(klyja_validate::summarize(&errors))
+        }
+        AppError::BadRequest(msg) => Status::invalid_argument(msg),
+        AppError::NotFound(msg) => Status::not_found(msg),
+        AppError::Unauthorized(msg) => Status::unauthenticated(msg),
+        AppError::Forbidden(msg) => Status::permission_denied(msg),
+        AppError::PayloadTooLarge(msg) | AppError::QuotaExceeded(msg) => {
+            Status::resource_exhausted(msg)
+        }
+        AppError::BodyTooLarge {
+            max_bytes,
+            received_bytes,
+        } => Status::resource_exhausted(body_too_large_message(max_bytes, received_bytes)),
+        AppError::LimitExceeded { limit, max } => Status::resource_exhausted(limit.describe(max)),
+        AppError::TooManyRequests { retry_after_secs } => {
+            Status::resource_exhausted(too_many_requests_message(retry_after_secs))
+        }
+        AppError::Timeout { budget_secs } => {
+            Status::deadline_exceeded(timeout_message(budget_secs))
+        }
+        AppError::PreconditionRequired(msg) => Status::failed_precondition(msg),
+        AppError::Maintenance(msg) => Status::unavailable(msg),
+        AppError::VersionConflict {
+            animation_id,
+            current_version,
+        } => Status::aborted(format!(
+            "Animation {} was changed since you loaded it; it is now at version {}",
+            animation_id, current_version
+        )),
+        err @ (AppError::DatabasePool(_)
+        | AppError::DatabaseQuery(_)
+        | AppError::CorruptDocument(_)
+        | AppError::Internal(_)) => {
+            tracing::error!("GRPC ERROR: {:?}", err);
+            Status::internal("Internal server error")
+        }
+    }
+}
+
+pub struct AnimationsService {
+    pool: DbPool,
+}
+
+impl AnimationsService {
+    pub fn new(pool: DbPool) -> Self {
+        AnimationsService { pool }
+    }
+
+    /// The service, ready to add to a tonic server.
+    pub fn server(pool: DbPool) -> AnimationsServer<Self> {
+        AnimationsServer::new(Self::new(pool))
+    }
+
+    /// The user whose personal access token the call carries, if any. As with REST,
+    /// a bad token or one without the scope the call needs is refused outright.
+    async fn caller(
+        &self,
+        metadata: &MetadataMap,
+        needed: TokenScope,
+    ) -> Result<Option<i32>, Status> {
+        let Some(value) = metadata.get("authorization") else {
+            return Ok(None);
+        };
+        let token = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or_else(|| Status::unauthenticated("Send authorization: Bearer <API token>"))?;
+
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|err| status(AppError::DatabasePool(err)))?;
+        let (user_id, _, scopes) = tokens::resolve_token(&mut conn, token)
+            .await
+            .map_err(status)?
+            .ok_or_else(|| Status::unauthenticated("API token invalid or expired"))?;
+        if !scopes.contains(&needed) {
+            return Err(Status::permission_denied(format!(
+                "This API token lacks the '{}' scope",
+                needed.as_str()
+            )));
+        }
+        Ok(Some(user_id))
+    }
+}
+
+#[tonic::async_trait]
+impl Animations for AnimationsService {
+    async fn save_animation(
+        &self,
+        request: Request<SaveAnimationRequest>,
+    ) -> Result<Response<SaveAnimationResponse>, Status> {
+        maintenance::ensure_writable().map_err(status)?;
+        let caller_id = self.caller(request.metadata(), TokenScope::Write).await?;
+        let request = request.into_inner();
+        let document = request
+            .animation
+            .ok_or_else(|| Status::invalid_argument("animation is required"))?;
+        let body = Bytes::from(document.encode_to_vec());
+
+        let response = match request.id {
+            Some(id) => {
+                // As with If-Match over REST, an update must say what it's based on
+                let expected_version = request.expected_version.ok_or_else(|| {
+                    Status::failed_precondition(
+                        "expected_version is required to replace an animation; load it for its version",
+                    )
+                })?;
+                let version = AnimationService::update_animation_logic(
+                    &self.pool,
+                    id,
+                    body,
+                    caller_id,
+                    Some(expected_version),
+                )
+                .await
+                .map_err(status)?;
+                SaveAnimationResponse {
+                    id,
+                    version: Some(version),
+                }
+            }
+            None => {
+                // Overwriting a save of the same document needs its version
+                let if_match = request
+                    .expected_version
+                    .map_or(IfMatch::Absent, IfMatch::Version);
+                let (id, version) =
+                    AnimationService::save_animation_logic(&self.pool, body, caller_id, if_match)
+                        .await
+                        .map_err(status)?;
+                SaveAnimationResponse {
+                    id,
+                    version: Some(version),
+                }
+            }
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn load_animation(
+        &self,
+        request: Request<LoadAnimationRequest>,
+    ) -> Result<Response<LoadAnimationResponse>, Status> {
+        let caller_id = self.caller(request.metadata(), TokenScope::Read).await?;
+        let animation_id = request.into_inner().id;
+        let loaded = AnimationService::load_animation_logic(&self.pool, animation_id, caller_id)
+            .await
+            .map_err(status)?;
+        stats::record_view(&self.pool, animation_id, loaded.owner_id, caller_id).await;
+
+        let document = klyja_proto::MapAnimation::decode(loaded.protobuf_data.as_slice())
+            .map_err(|err| status(AppError::ProtobufDecode(err)))?;
+        Ok(Response::new(LoadAnimationResponse {
+            animation: Some(document),
+            version: loaded.lock_version,
+        }))
+    }
+
+    async fn list_animations(
+        &self,
+        request: Request<ListAnimationsRequest>,
+    ) -> Result<Response<ListAnimationsResponse>, Status> {
+        let caller_id = self.caller(request.metadata(), TokenScope::Read).await?;
+        let request = request.into_inner();
+        let sort = match request.sort() {
+            proto::ListSort::Recent => ListSort::Recent,
+            proto::ListSort::Stars => ListSort::Stars,
+        };
+        let query = Some(request.query).filter(|query| !query.is_empty());
+
+        let listed = AnimationService::list_animations_logic(
+            &self.pool,
+            query,
+            request.folder_id,
+            sort,
+            caller_id,
+        )
+        .await
+        .map_err(status)?;
+        let animations = listed
+            .into_iter()
+            .map(|item| AnimationSummary {
+                id: item.summary.id,
+                name: item.summary.name,
+                created_at: item.summary.created_at.and_utc().timestamp(),
+                updated_at: item.summary.updated_at.and_utc().timestamp(),
+                owner_id: item.summary.owner_id,
+                star_count: item.star_count,
+                published_at: item.summary.published_at.map(|at| at.and_utc().timestamp()),
+            })
+            .collect();
+        Ok(Response::new(ListAnimationsResponse { animations }))
+    }
+
+    async fn delete_animation(
+        &self,
+        request: Request<DeleteAnimationRequest>,
+    ) -> Result<Response<DeleteAnimationResponse>, Status> {
+        maintenance::ensure_writable().map_err(status)?;
+        let caller_id = self.caller(request.metadata(), TokenScope::Write).await?;
+        AnimationService::delete_animation_logic(&self.pool, request.into_inner().id, caller_id)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(DeleteAnimationResponse {}))
+    }
+}
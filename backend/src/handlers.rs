@@ -1,51 +1,230 @@
 // klyja/backend/src/handlers.rs
 use crate::{
-    errors::{AppError, SuccessfulSaveResponsePayload},
+    accounts::AccountService,
+    auth::{session_cookie, AuthUser},
+    backups::BackupService,
+    db::ReadPool,
+    devices::{ClientInfo, SessionService},
+    drafts::DraftService,
+    embeds::EmbedService,
+    errors::{version_etag, AppError, SuccessfulSaveResponsePayload},
     //    models::{Animation, NewAnimation},
     //    protobuf_gen::MapAnimation,
     //    schema,
-    services::AnimationService,
+    exports::ExportService,
+    folders::FolderService,
+    imports::ImportService,
+    invitations::InvitationService,
+    jobs::JobService,
+    maintenance::MaintenanceService,
+    models::{
+        AcceptInvitationRequest, AcceptedInvitation, Account, AnimationDetailsRequest,
+        AnimationDiff, AnimationListItem, AnimationMetadata, AnimationStats, ApiToken,
+        ArchiveImport, AuthThrottleStats, BackupJob, BatchMetadataRequest, Collaborator,
+        ConfirmPasswordResetRequest, CreateApiTokenRequest, CreateInvitationRequest,
+        CreateUploadRequest, CreateWebhookRequest, CreatedApiToken, CreatedInvitation,
+        CreatedWebhook, EmbedLink, EmbedRequest, ExportFramesRequest, ExportJob,
+        ExportVideoRequest, Folder, FolderRequest, InviteCollaboratorRequest, JobInfo, ListSort,
+        LoginRequest, MaintenanceRequest, MaintenanceStatus, MoveAnimationRequest,
+        PasswordResetRequest, PublicationRequest, PublicationStatus, Readiness, RegisterRequest,
+        Report, ReportRequest, RestoreRequest, SessionInfo, StarStatus, Template, UploadStatus,
+        Webhook,
+    },
+    moderation::ModerationService,
+    presence::PresenceService,
+    publishing::PublicationService,
+    services::{document_from_json, document_to_json, AnimationService, IfMatch},
+    stats::{self, StatsService},
+    templates::TemplateService,
+    throttle::ThrottleService,
+    tokens::TokenService,
+    uploads::{CompletedUpload, UploadService},
+    webhooks::WebhookService,
     DbPool,
 }; // Use crate:: for DbPool etc. defined in main.rs
 use axum::{
     body::Bytes, // Use Bytes extractor for raw body
-    extract::{Path, State},
+    extract::{ws::WebSocketUpgrade, Path, Query, State},
     http::{HeaderMap, HeaderValue, StatusCode},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json, // If you want to return JSON confirmation later
 };
 //use diesel::prelude::*;
 //use prost::Message; // For decoding protobuf
+use geco_core::patch::DocumentPatch;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+/// The version an `If-Match` header says the caller's changes are based on: a quoted
+/// version from an ETag, optionally weak. `*` (any version) gives `None`, as does a
+/// missing header unless `required`, in which case it is refused with 428.
+fn if_match_version(headers: &HeaderMap, required: bool) -> Result<Option<i32>, AppError> {
+    let Some(value) = headers.get(axum::http::header::IF_MATCH) else {
+        if required {
+            return Err(AppError::PreconditionRequired(
+                "Send If-Match with the ETag of the version you loaded, or * to overwrite"
+                    .to_string(),
+            ));
+        }
+        return Ok(None);
+    };
+    let value = value.to_str().unwrap_or_default().trim();
+    if value == "*" {
+        return Ok(None);
+    }
+    value
+        .trim_start_matches("W/")
+        .trim_matches('"')
+        .parse::<i32>()
+        .map(Some)
+        .map_err(|_| AppError::BadRequest(format!("'{}' is not an ETag of this server", value)))
+}
+
+/// `If-Match` for saves that may or may not land on an existing animation, keeping
+/// apart a missing header, which allows no overwrite, and `*`.
+fn save_if_match(headers: &HeaderMap) -> Result<IfMatch, AppError> {
+    if !headers.contains_key(axum::http::header::IF_MATCH) {
+        return Ok(IfMatch::Absent);
+    }
+    Ok(if_match_version(headers, true)?.map_or(IfMatch::Any, IfMatch::Version))
+}
+
+/// Whether `Accept` asks for the JSON form of a document rather than protobuf.
+fn accepts_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(axum::http::header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| {
+            accept.split(',').any(|media_type| {
+                let media_type = media_type.split(';').next().unwrap_or_default().trim();
+                media_type.eq_ignore_ascii_case("application/json")
+            })
+        })
+}
+
+/// An uploaded document as protobuf bytes, converted from JSON if `Content-Type` says
+/// it was sent that way.
+fn uploaded_document(headers: &HeaderMap, body: Bytes) -> Result<Bytes, AppError> {
+    let is_json = headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| {
+            let media_type = content_type.split(';').next().unwrap_or_default().trim();
+            media_type.eq_ignore_ascii_case("application/json")
+        });
+    if is_json {
+        document_from_json(&body)
+    } else {
+        Ok(body)
+    }
+}
+
+/// Query parameters for listing animations.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListAnimationsParams {
+    /// Case-insensitive substring of the animation name to match
+    pub q: Option<String>,
+    /// `recent` (the default) or `stars` for the most starred first
+    pub sort: Option<ListSort>,
+    /// Only list the animations filed in this folder
+    pub folder_id: Option<i32>,
+}
+
+/// Query parameters for loading an animation; give at most one of them.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LoadAnimationParams {
+    /// Load this version from the animation's history instead of the current one
+    pub version: Option<i32>,
+    /// Load the version that was current at this UTC time
+    pub as_of: Option<chrono::NaiveDateTime>,
+}
+
+/// Query parameters for importing a CSV point series.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportCsvParams {
+    /// Name of the new animation; defaults to "points"
+    pub name: Option<String>,
+    /// Frames to spread timestamps over; defaults to 240. Ignored when the file gives frames
+    pub total_frames: Option<i32>,
+}
+
+/// Query parameters for importing a GPX file.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportGpxParams {
+    /// Name of the new animation; defaults to "tracks"
+    pub name: Option<String>,
+    /// Frames the tracks play over; defaults to 240
+    pub total_frames: Option<i32>,
+}
+
+/// Query parameters for comparing two versions of an animation.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DiffVersionsParams {
+    /// Older version; defaults to the one before `to`
+    pub from: Option<i32>,
+    /// Newer version; defaults to the current one
+    pub to: Option<i32>,
+}
+
+/// Query parameters for an animation's view statistics.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AnimationStatsParams {
+    /// Days of daily views to return, today included; defaults to 30, at most 365
+    pub days: Option<u32>,
+}
 
 /// Save a new animation.
 ///
-/// The request body should be the raw binary Protobuf data representing the MapAnimation.
+/// The request body should be the raw binary Protobuf data representing the MapAnimation,
+/// or its JSON form sent with `Content-Type: application/json`.
+/// A signed-in caller who already saved a document with this `animation_id` gets that
+/// animation updated in place, and its ID back; this needs `If-Match` with the ETag of
+/// the version they loaded, or `*` to overwrite. Otherwise, if their latest animation
+/// already has this name and document, its ID is returned and nothing new is stored.
+/// The ETag header holds the animation's version, to send as `If-Match` next time.
 #[utoipa::path(
     post,
     path = "/api/save_animation",
     tag = "Animations", // Group this endpoint under an "Animations" tag
+    params(
+        ("If-Match" = Option<String>, Header, description = "ETag of the version an update in place is based on, or *", example = "\"3\"")
+    ),
     request_body(
         content = bytes, // Using `bytes` special type for utoipa for raw binary
-        description = "Binary Protobuf data for the MapAnimation",
+        description = "Binary Protobuf data for the MapAnimation, or its JSON form with Content-Type: application/json",
         content_type = "application/octet-stream"
     ),
     responses(
         (status = 201, description = "Animation saved successfully", body = crate::errors::SuccessfulSaveResponsePayload),
         (status = 400, description = "Invalid data format or bad request", body = crate::errors::ErrorResponsePayload),
+        (status = 409, description = "The animation saved with this document changed since the If-Match version", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "Document over the upload limit or the caller's storage quota", body = crate::errors::ErrorResponsePayload),
+        (status = 428, description = "Document already saved, and no If-Match sent to update it", body = crate::errors::ErrorResponsePayload),
         (status = 500, description = "Internal server error", body = crate::errors::ErrorResponsePayload)
     )
 )]
 
 pub async fn save_animation_handler(
     State(pool): State<DbPool>,
+    user: Option<AuthUser>,
+    request_headers: HeaderMap,
     body: Bytes,
 ) -> Result<impl IntoResponse, AppError> {
     // The suggestion used tracing_unwrap, but standard tracing is fine.
     // Ensure you have `tracing` in your Cargo.toml and `use tracing;` if not already global.
     tracing::debug!("HANDLER: Received save request with {} bytes", body.len()); // Changed to debug, info is also fine
 
-    // Call the service, which now returns Result<i32, AppError>
-    let saved_animation_id = AnimationService::save_animation_logic(&pool, body).await?;
+    let body = uploaded_document(&request_headers, body)?;
+    let if_match = save_if_match(&request_headers)?;
+    // Call the service, which now returns Result<(i32, i32), AppError>
+    let (saved_animation_id, version) =
+        AnimationService::save_animation_logic(&pool, body, user.map(|u| u.id), if_match).await?;
 
     tracing::info!(
         // Kept info level here for successful operation
@@ -61,28 +240,43 @@ pub async fn save_animation_handler(
 
     // MODIFIED: Return 201 Created status with the JSON payload
     // (StatusCode, Json(payload)) is a common way to do this in Axum.
-    Ok((StatusCode::CREATED, Json(response_payload)))
+    Ok((
+        StatusCode::CREATED,
+        [(axum::http::header::ETAG, version_etag(version))],
+        Json(response_payload),
+    ))
 }
 
 /// Load an existing animation by its ID.
 ///
-/// Returns the raw binary Protobuf data for the MapAnimation.
+/// Returns the raw binary Protobuf data for the MapAnimation, or its JSON form when
+/// `Accept` asks for `application/json`. `version` or `as_of` load an earlier revision
+/// from the animation's history instead, for diffing or previewing a rollback; the
+/// ETag is then the revision's version.
 #[utoipa::path(
     get,
     path = "/api/load_animation/{id}",
     tag = "Animations",
     params(
-        ("id" = i32, Path, description = "ID of the animation to load", example = 1)
+        ("id" = i32, Path, description = "ID of the animation to load", example = 1),
+        LoadAnimationParams
     ),
     responses(
         (status = 200, description = "Animation loaded successfully", body = bytes, content_type = "application/octet-stream"),
-        (status = 404, description = "Animation not found", body = String),
+        (status = 400, description = "Both version and as_of were given", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation or revision not found", body = String),
         (status = 500, description = "Internal server error", body = String)
     )
 )]
 pub async fn load_animation_handler(
     State(pool): State<DbPool>,
+    State(read_pool): State<ReadPool>,
     Path(animation_id): Path<i32>, // Extract ID from path
+    Query(params): Query<LoadAnimationParams>,
+    user: Option<AuthUser>,
+    request_headers: HeaderMap,
 ) -> Result<impl IntoResponse, AppError> {
     tracing::info!(
         "HANDLER: Received load request for animation ID: {}",
@@ -90,34 +284,2204 @@ pub async fn load_animation_handler(
     );
 
     // Call the business logic function from the service layer
-    let loaded_animation = AnimationService::load_animation_logic(&pool, animation_id).await?; // Propagates Err if one occurs
+    let caller_id = user.map(|u| u.id);
+    let reads = read_pool.for_caller(&pool, caller_id);
+    let (version, document) = if params.version.is_some() || params.as_of.is_some() {
+        // Looking back through the history isn't a view of the animation
+        AnimationService::load_revision_logic(
+            reads,
+            animation_id,
+            params.version,
+            params.as_of,
+            caller_id,
+        )
+        .await?
+    } else {
+        let loaded_animation =
+            AnimationService::load_animation_logic(reads, animation_id, caller_id).await?; // Propagates Err if one occurs
+        stats::record_view(&pool, animation_id, loaded_animation.owner_id, caller_id).await;
 
-    tracing::info!(
-        "HANDLER: Animation '{}' (ID: {}) loaded successfully by service.",
-        loaded_animation.name,
-        animation_id
-    );
+        tracing::info!(
+            "HANDLER: Animation '{}' (ID: {}) loaded successfully by service.",
+            loaded_animation.name,
+            animation_id
+        );
+        (
+            loaded_animation.lock_version,
+            loaded_animation.protobuf_data,
+        )
+    };
 
     // The rest of the handler is for HTTP response formatting, which stays here.
+    let json = accepts_json(&request_headers);
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        axum::http::header::CONTENT_TYPE,
+        HeaderValue::from_static(if json {
+            "application/json"
+        } else {
+            "application/octet-stream"
+        }),
+    );
+    headers.insert(axum::http::header::VARY, HeaderValue::from_static("Accept"));
+    // Sent back in If-Match when saving, so edits based on this version don't overwrite newer ones
+    if let Ok(etag) = HeaderValue::from_str(&version_etag(version)) {
+        headers.insert(axum::http::header::ETAG, etag);
+    }
+
+    let body = if json {
+        document_to_json(&document)?.into_bytes()
+    } else {
+        document
+    };
+    Ok((headers, body)) // Return headers and Vec<u8> body
+}
+/// List saved animations the caller can load, most recently updated first.
+///
+/// Returns names, timestamps and star counts only; load a document by ID to get its
+/// data. `sort=stars` puts the most starred first.
+#[utoipa::path(
+    get,
+    path = "/api/animations",
+    tag = "Animations",
+    params(ListAnimationsParams),
+    responses(
+        (status = 200, description = "Animations listed successfully", body = [AnimationListItem]),
+        (status = 500, description = "Internal server error", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn list_animations_handler(
+    State(ReadPool(read_pool)): State<ReadPool>,
+    Query(params): Query<ListAnimationsParams>,
+    user: Option<AuthUser>,
+) -> Result<Json<Vec<AnimationListItem>>, AppError> {
+    tracing::debug!("HANDLER: Received list request with query {:?}", params.q);
+
+    // Listings may trail the latest saves by a moment, so even signed-in callers get
+    // them from the read pool
+    let summaries = AnimationService::list_animations_logic(
+        &read_pool,
+        params.q,
+        params.folder_id,
+        params.sort.unwrap_or_default(),
+        user.map(|u| u.id),
+    )
+    .await?;
+    Ok(Json(summaries))
+}
+
+/// Replace the document of an existing animation.
+///
+/// Needs a session belonging to the owner or an editor collaborator, and an `If-Match`
+/// header with the ETag the document was loaded with (or `*` to overwrite whatever is
+/// there). If someone saved in between, the update is refused with 409 and the
+/// current version. Like saves, it takes the document as protobuf or JSON.
+#[utoipa::path(
+    put,
+    path = "/api/animation/{id}",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation to update", example = 1),
+        ("If-Match" = String, Header, description = "ETag of the version the changes are based on, or *", example = "\"3\"")
+    ),
+    request_body(
+        content = bytes,
+        description = "Binary Protobuf data for the MapAnimation, or its JSON form with Content-Type: application/json",
+        content_type = "application/octet-stream"
+    ),
+    responses(
+        (status = 204, description = "Animation updated successfully; the ETag header holds the new version"),
+        (status = 400, description = "Invalid data format or bad request", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller may not edit this animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload),
+        (status = 409, description = "Animation changed since the If-Match version", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "Document over the upload limit or the owner's storage quota", body = crate::errors::ErrorResponsePayload),
+        (status = 428, description = "No If-Match header", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn update_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    request_headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    tracing::debug!(
+        "HANDLER: Received update request for animation ID {} with {} bytes",
+        animation_id,
+        body.len()
+    );
+
+    let expected_version = if_match_version(&request_headers, true)?;
+    let body = uploaded_document(&request_headers, body)?;
+    let new_version = AnimationService::update_animation_logic(
+        &pool,
+        animation_id,
+        body,
+        Some(user.id),
+        expected_version,
+    )
+    .await?;
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(axum::http::header::ETAG, version_etag(new_version))],
+    ))
+}
+
+/// Delete an animation, with its history, thumbnail, stars and drafts.
+///
+/// Only the owner may. Webhooks subscribed to `deleted` are told.
+#[utoipa::path(
+    delete,
+    path = "/api/animation/{id}",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation to delete", example = 1)
+    ),
+    responses(
+        (status = 204, description = "Animation deleted"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller doesn't own this animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn delete_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+) -> Result<StatusCode, AppError> {
+    AnimationService::delete_animation_logic(&pool, animation_id, Some(user.id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Save changes to an animation as a patch instead of the whole document.
+///
+/// Takes the JSON patch geco's `export_patch_json` makes against the document last
+/// loaded or saved, whose ETag goes in `If-Match`. The server applies it and stores
+/// the result as the next version. Needs editor access.
+#[utoipa::path(
+    post,
+    path = "/api/animation/{id}/patch",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1),
+        ("If-Match" = String, Header, description = "ETag of the version the patch was made against", example = "\"3\"")
+    ),
+    request_body(
+        content = Object,
+        description = "Patch made by geco: removed_features, changed features and other changed fields",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 204, description = "Patch applied; the ETag header holds the new version"),
+        (status = 400, description = "Patch doesn't fit the document, or the result is invalid", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller may not edit this animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload),
+        (status = 409, description = "Animation changed since the If-Match version", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "Result over the upload limit or the owner's storage quota", body = crate::errors::ErrorResponsePayload),
+        (status = 428, description = "No If-Match header, or If-Match is *", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn patch_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    request_headers: HeaderMap,
+    Json(patch): Json<DocumentPatch>,
+) -> Result<impl IntoResponse, AppError> {
+    let base_version = if_match_version(&request_headers, true)?.ok_or_else(|| {
+        AppError::PreconditionRequired(
+            "Patches need If-Match with the version they were made against".to_string(),
+        )
+    })?;
+    let new_version = AnimationService::patch_animation_logic(
+        &pool,
+        animation_id,
+        patch,
+        Some(user.id),
+        base_version,
+    )
+    .await?;
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(axum::http::header::ETAG, version_etag(new_version))],
+    ))
+}
+
+/// Summarise what changed between two saved versions of an animation.
+///
+/// Both documents are decoded server-side; features and points are matched by ID.
+/// Versions are the numbers served as ETags when loading and saving.
+#[utoipa::path(
+    get,
+    path = "/api/animation/{id}/diff",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1),
+        DiffVersionsParams
+    ),
+    responses(
+        (status = 200, description = "Versions compared", body = AnimationDiff),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation or version not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn diff_versions_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    Query(params): Query<DiffVersionsParams>,
+    user: Option<AuthUser>,
+) -> Result<Json<AnimationDiff>, AppError> {
+    let diff = AnimationService::diff_versions_logic(
+        &pool,
+        animation_id,
+        params.from,
+        params.to,
+        user.map(|u| u.id),
+    )
+    .await?;
+    Ok(Json(diff))
+}
+
+/// Autosave the caller's unsaved work on an animation.
+///
+/// Replaces the caller's previous draft and leaves the animation and its version
+/// alone. `If-Match` records which version the edits started from; without it the
+/// current version is assumed. Needs editor access.
+#[utoipa::path(
+    put,
+    path = "/api/animation/{id}/draft",
+    tag = "Drafts",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1),
+        ("If-Match" = Option<String>, Header, description = "ETag of the version the edits are based on", example = "\"3\"")
+    ),
+    request_body(
+        content = bytes,
+        description = "Binary Protobuf data for the MapAnimation",
+        content_type = "application/octet-stream"
+    ),
+    responses(
+        (status = 204, description = "Draft stored; the ETag header holds the version it is based on"),
+        (status = 400, description = "Not a MapAnimation", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller may not edit this animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "Draft over the upload limit", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn save_draft_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    request_headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let based_on_version = if_match_version(&request_headers, false)?;
+    let based_on_version =
+        DraftService::save_draft_logic(&pool, animation_id, body, user.id, based_on_version)
+            .await?;
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(axum::http::header::ETAG, version_etag(based_on_version))],
+    ))
+}
+
+/// Recover the caller's autosaved draft of an animation.
+///
+/// The ETag header holds the version the draft is based on: send it as `If-Match`
+/// when saving the draft over the animation, to learn if someone saved in between.
+/// Last-Modified says when it was autosaved.
+#[utoipa::path(
+    get,
+    path = "/api/animation/{id}/draft",
+    tag = "Drafts",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 200, description = "Draft found", body = bytes, content_type = "application/octet-stream"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found, or the caller has no draft of it", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn load_draft_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let draft = DraftService::load_draft_logic(&pool, animation_id, user.id).await?;
+
     let mut headers = HeaderMap::new();
     headers.insert(
         axum::http::header::CONTENT_TYPE,
         HeaderValue::from_static("application/octet-stream"),
     );
+    if let Ok(etag) = HeaderValue::from_str(&version_etag(draft.based_on_version)) {
+        headers.insert(axum::http::header::ETAG, etag);
+    }
+    let last_modified = draft.updated_at.format("%a, %d %b %Y %H:%M:%S GMT");
+    if let Ok(last_modified) = HeaderValue::from_str(&last_modified.to_string()) {
+        headers.insert(axum::http::header::LAST_MODIFIED, last_modified);
+    }
+    // Drafts change with every autosave
+    headers.insert(
+        axum::http::header::CACHE_CONTROL,
+        HeaderValue::from_static("no-store"),
+    );
+
+    Ok((headers, draft.protobuf_data))
+}
+
+/// List the users an animation is shared with.
+#[utoipa::path(
+    get,
+    path = "/api/animation/{id}/collaborators",
+    tag = "Collaborators",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 200, description = "Collaborators listed successfully", body = [Collaborator]),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn list_collaborators_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: Option<AuthUser>,
+) -> Result<Json<Vec<Collaborator>>, AppError> {
+    let collaborators =
+        AnimationService::list_collaborators_logic(&pool, animation_id, user.map(|u| u.id)).await?;
+    Ok(Json(collaborators))
+}
+
+/// Share an animation with a user as a viewer or editor, or change their role.
+///
+/// Only the owner may share an animation.
+#[utoipa::path(
+    post,
+    path = "/api/animation/{id}/collaborators",
+    tag = "Collaborators",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    request_body = InviteCollaboratorRequest,
+    responses(
+        (status = 204, description = "Collaborator added or updated"),
+        (status = 400, description = "The owner was invited", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller doesn't own this animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation or user not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn invite_collaborator_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    Json(request): Json<InviteCollaboratorRequest>,
+) -> Result<StatusCode, AppError> {
+    AnimationService::invite_collaborator_logic(
+        &pool,
+        animation_id,
+        Some(user.id),
+        request.user_id,
+        request.role,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Stop sharing an animation with a user.
+///
+/// The owner may remove any collaborator; collaborators may remove themselves.
+#[utoipa::path(
+    delete,
+    path = "/api/animation/{id}/collaborators/{user_id}",
+    tag = "Collaborators",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1),
+        ("user_id" = i32, Path, description = "ID of the collaborator to remove", example = 12)
+    ),
+    responses(
+        (status = 204, description = "Collaborator removed"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller may not remove this collaborator", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation or collaborator not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn remove_collaborator_handler(
+    State(pool): State<DbPool>,
+    Path((animation_id, collaborator_id)): Path<(i32, i32)>,
+    user: AuthUser,
+) -> Result<StatusCode, AppError> {
+    AnimationService::remove_collaborator_logic(
+        &pool,
+        animation_id,
+        Some(user.id),
+        collaborator_id,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Invite someone to collaborate on an animation by email or username.
+///
+/// They needn't have an account yet. Klyja doesn't send mail: pass the returned token
+/// on to the invitee, who accepts it once signed in. Only the owner may invite;
+/// invitations expire after a week.
+#[utoipa::path(
+    post,
+    path = "/api/animation/{id}/invitations",
+    tag = "Collaborators",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    request_body = CreateInvitationRequest,
+    responses(
+        (status = 201, description = "Invitation created", body = CreatedInvitation),
+        (status = 400, description = "Not an email address or username", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller doesn't own this animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn create_invitation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    Json(request): Json<CreateInvitationRequest>,
+) -> Result<(StatusCode, Json<CreatedInvitation>), AppError> {
+    let created =
+        InvitationService::create_invitation_logic(&pool, animation_id, user.id, request).await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
 
-    Ok((headers, loaded_animation.protobuf_data)) // Return headers and Vec<u8> body
+/// Accept an invitation, becoming a collaborator on its animation.
+///
+/// The caller must be signed in as the user the invitation was sent to, by email
+/// or username.
+#[utoipa::path(
+    post,
+    path = "/api/invitations/accept",
+    tag = "Collaborators",
+    request_body = AcceptInvitationRequest,
+    responses(
+        (status = 200, description = "Invitation accepted", body = AcceptedInvitation),
+        (status = 400, description = "Token is invalid or expired, or the caller owns the animation", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "The invitation is for someone else", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn accept_invitation_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+    Json(request): Json<AcceptInvitationRequest>,
+) -> Result<Json<AcceptedInvitation>, AppError> {
+    let accepted = InvitationService::accept_invitation_logic(&pool, user.id, request).await?;
+    Ok(Json(accepted))
 }
-/// Health check endpoint.
+
+/// See how often an animation has been viewed.
 ///
-/// Returns a simple "Healthy!" message if the server is running.
+/// Counts loads by anyone but the owner, per day. Only the owner may ask.
 #[utoipa::path(
     get,
-    path = "/api/health",
-    tag = "System",
+    path = "/api/animation/{id}/stats",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1),
+        AnimationStatsParams
+    ),
     responses(
-        (status = 200, description = "Server is healthy", body = String, example = json!("Healthy!"))
+        (status = 200, description = "View counts and stars", body = AnimationStats),
+        (status = 400, description = "Days out of range", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller doesn't own this animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
     )
 )]
-pub async fn health_check_handler() -> (StatusCode, String) {
-    (StatusCode::OK, "Healthy!".to_string())
+pub async fn animation_stats_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    Query(params): Query<AnimationStatsParams>,
+    user: AuthUser,
+) -> Result<Json<AnimationStats>, AppError> {
+    let stats =
+        StatsService::animation_stats_logic(&pool, animation_id, params.days, Some(user.id))
+            .await?;
+    Ok(Json(stats))
+}
+
+/// Replace an animation's description, license and source attribution.
+///
+/// Fields left out or blank are cleared. Needs editor access; the document and its
+/// version are unchanged.
+#[utoipa::path(
+    put,
+    path = "/api/animation/{id}/details",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    request_body = AnimationDetailsRequest,
+    responses(
+        (status = 204, description = "Details replaced"),
+        (status = 400, description = "A field is too long or holds control characters", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller may not edit this animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn update_details_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    Json(request): Json<AnimationDetailsRequest>,
+) -> Result<StatusCode, AppError> {
+    AnimationService::update_details_logic(&pool, animation_id, request, Some(user.id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Publish an animation, making it readable by anyone, now or at a set time.
+///
+/// A `publish_at` still ahead schedules the publication, replacing any earlier
+/// schedule; without one, or with one already past, it is published now. Only the
+/// owner may publish. Webhooks subscribed to `published` are told when it happens.
+#[utoipa::path(
+    put,
+    path = "/api/animation/{id}/publication",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    request_body = PublicationRequest,
+    responses(
+        (status = 200, description = "Published or scheduled", body = PublicationStatus),
+        (status = 400, description = "Scheduling an animation that is already published", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller doesn't own the animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn publish_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    Json(request): Json<PublicationRequest>,
+) -> Result<Json<PublicationStatus>, AppError> {
+    let status = PublicationService::publish_logic(&pool, animation_id, user.id, request).await?;
+    Ok(Json(status))
+}
+
+/// Make a published animation private again, or cancel its scheduled publication.
+#[utoipa::path(
+    delete,
+    path = "/api/animation/{id}/publication",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 200, description = "Unpublished", body = PublicationStatus),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller doesn't own the animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn unpublish_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+) -> Result<Json<PublicationStatus>, AppError> {
+    let status = PublicationService::unpublish_logic(&pool, animation_id, user.id).await?;
+    Ok(Json(status))
+}
+
+/// Create a signed link that loads an animation read-only, for embedding a viewer.
+///
+/// Whoever has the link can load this one animation, without signing in, until it
+/// expires or is revoked; nothing else in the API opens up to them. Only the owner may
+/// create one, and only on servers with `EMBED_SIGNING_KEY` set.
+#[utoipa::path(
+    post,
+    path = "/api/animation/{id}/embed",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    request_body = EmbedRequest,
+    responses(
+        (status = 201, description = "Embed link created", body = EmbedLink),
+        (status = 400, description = "Lifetime out of range", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller doesn't own the animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload),
+        (status = 500, description = "Embed links are turned off on this server", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn create_embed_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    Json(request): Json<EmbedRequest>,
+) -> Result<(StatusCode, Json<EmbedLink>), AppError> {
+    let link = EmbedService::create_embed_logic(&pool, animation_id, user.id, request).await?;
+    Ok((StatusCode::CREATED, Json(link)))
+}
+
+/// Revoke every embed link made for an animation.
+///
+/// Links created before this stop working; new ones can be created as usual. Only
+/// the owner may revoke them.
+#[utoipa::path(
+    delete,
+    path = "/api/animation/{id}/embed",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 204, description = "Embed links revoked"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller doesn't own the animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn revoke_embeds_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+) -> Result<StatusCode, AppError> {
+    EmbedService::revoke_embeds_logic(&pool, animation_id, user.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Load an animation through an embed link.
+///
+/// Returns the raw binary Protobuf data, like `/api/load_animation/{id}`, with headers
+/// letting any page fetch and frame it but keeping it from running as content itself.
+#[utoipa::path(
+    get,
+    path = "/embed/{token}",
+    tag = "Animations",
+    params(
+        ("token" = String, Path, description = "Token of the embed link")
+    ),
+    responses(
+        (status = 200, description = "Animation loaded", body = bytes, content_type = "application/octet-stream"),
+        (status = 403, description = "Link is invalid, revoked or has expired", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload),
+        (status = 500, description = "Embed links are turned off on this server", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn embed_handler(
+    State(pool): State<DbPool>,
+    State(ReadPool(read_pool)): State<ReadPool>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let animation = EmbedService::load_embed_logic(&read_pool, &token).await?;
+    stats::record_view(&pool, animation.id, animation.owner_id, None).await;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        axum::http::header::CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    // Readable from any site embedding it, but never rendered or sniffed as a page
+    headers.insert(
+        "cross-origin-resource-policy",
+        HeaderValue::from_static("cross-origin"),
+    );
+    headers.insert(
+        axum::http::header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static("default-src 'none'; frame-ancestors *"),
+    );
+    headers.insert(
+        axum::http::header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert(
+        axum::http::header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    );
+    // Short, so edits show up in embeds soon after they are saved
+    headers.insert(
+        axum::http::header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=300"),
+    );
+
+    Ok((headers, animation.protobuf_data))
+}
+
+/// Join an animation's live room over a WebSocket.
+///
+/// Anyone signed in who can load the animation may join. Clients send a
+/// `PresenceUpdate` as JSON text whenever their mode, frame or cursor changes, and get
+/// a `LiveMessage` with everyone in the room each time anyone's changes, starting with
+/// their own arrival. Only the owner and editors may report themselves as editing.
+/// Each connection's updates reach the room at most ten times a second, quicker ones
+/// merged. Members who lose access to the animation are sent an error and closed.
+#[utoipa::path(
+    get,
+    path = "/api/animation/{id}/live",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 101, description = "Switched to a WebSocket carrying LiveMessage and PresenceUpdate JSON"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn live_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    socket: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    let (username, can_edit) = PresenceService::join_logic(&pool, animation_id, user.id).await?;
+    Ok(socket.on_upgrade(move |socket| {
+        PresenceService::run_session(socket, pool, animation_id, user.id, username, can_edit)
+    }))
+}
+
+/// Star an animation.
+///
+/// Anyone signed in who can load the animation may star it; starring it again
+/// changes nothing.
+#[utoipa::path(
+    post,
+    path = "/api/animation/{id}/star",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 200, description = "Animation starred", body = StarStatus),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn star_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+) -> Result<Json<StarStatus>, AppError> {
+    let status = AnimationService::set_star_logic(&pool, animation_id, user.id, true).await?;
+    Ok(Json(status))
+}
+
+/// Remove the caller's star from an animation.
+///
+/// Unstarring an animation the caller hasn't starred changes nothing.
+#[utoipa::path(
+    delete,
+    path = "/api/animation/{id}/star",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 200, description = "Star removed", body = StarStatus),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn unstar_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+) -> Result<Json<StarStatus>, AppError> {
+    let status = AnimationService::set_star_logic(&pool, animation_id, user.id, false).await?;
+    Ok(Json(status))
+}
+
+/// Upload a PNG preview for an animation, replacing any previous one.
+///
+/// Needs a session belonging to the owner or an editor collaborator.
+#[utoipa::path(
+    put,
+    path = "/api/animation/{id}/thumbnail",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    request_body(
+        content = bytes,
+        description = "PNG image",
+        content_type = "image/png"
+    ),
+    responses(
+        (status = 204, description = "Thumbnail stored"),
+        (status = 400, description = "Not a PNG, or too large", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller may not edit this animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn put_thumbnail_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    body: Bytes,
+) -> Result<StatusCode, AppError> {
+    AnimationService::put_thumbnail_logic(&pool, animation_id, body, Some(user.id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Fetch an animation's PNG preview.
+#[utoipa::path(
+    get,
+    path = "/api/animation/{id}/thumbnail",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 200, description = "Thumbnail found", body = bytes, content_type = "image/png"),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation or thumbnail not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn load_thumbnail_handler(
+    State(pool): State<DbPool>,
+    State(read_pool): State<ReadPool>,
+    Path(animation_id): Path<i32>,
+    user: Option<AuthUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let caller_id = user.map(|u| u.id);
+    let reads = read_pool.for_caller(&pool, caller_id);
+    let png_data = AnimationService::load_thumbnail_logic(reads, animation_id, caller_id).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        axum::http::header::CONTENT_TYPE,
+        HeaderValue::from_static("image/png"),
+    );
+    // Listing URLs carry a version, so a given URL always serves the same image
+    headers.insert(
+        axum::http::header::CACHE_CONTROL,
+        HeaderValue::from_static("private, max-age=31536000, immutable"),
+    );
+
+    Ok((headers, png_data))
+}
+
+/// Get an animation's metadata without downloading its data.
+///
+/// The stored document is decoded server-side to count its features and points.
+#[utoipa::path(
+    get,
+    path = "/api/animation/{id}/meta",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 200, description = "Metadata found", body = AnimationMetadata),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn animation_metadata_handler(
+    State(pool): State<DbPool>,
+    State(read_pool): State<ReadPool>,
+    Path(animation_id): Path<i32>,
+    user: Option<AuthUser>,
+) -> Result<Json<AnimationMetadata>, AppError> {
+    let caller_id = user.map(|u| u.id);
+    let reads = read_pool.for_caller(&pool, caller_id);
+    let metadata =
+        AnimationService::animation_metadata_logic(reads, animation_id, caller_id).await?;
+    Ok(Json(metadata))
+}
+
+/// Get the metadata of an animation by its document ID.
+///
+/// Documents carry a stable `animation_id` UUID of their own; this finds the saved
+/// animation holding it, so clients needn't keep track of database IDs.
+#[utoipa::path(
+    get,
+    path = "/api/animation/by-uuid/{uuid}",
+    tag = "Animations",
+    params(
+        ("uuid" = String, Path, description = "The document's animation_id", example = "5b7e1f0c-2d4a-4c8e-9f1a-3e6b8d2c7a10")
+    ),
+    responses(
+        (status = 200, description = "Metadata found", body = AnimationMetadata),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "No animation holds that document ID", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn animation_by_uuid_handler(
+    State(pool): State<DbPool>,
+    State(read_pool): State<ReadPool>,
+    Path(animation_uuid): Path<String>,
+    user: Option<AuthUser>,
+) -> Result<Json<AnimationMetadata>, AppError> {
+    let caller_id = user.map(|u| u.id);
+    let reads = read_pool.for_caller(&pool, caller_id);
+    let metadata =
+        AnimationService::animation_by_uuid_logic(reads, &animation_uuid, caller_id).await?;
+    Ok(Json(metadata))
+}
+
+/// Get the metadata of several animations in one request.
+///
+/// Results come in the order the IDs were given. Animations that don't exist or
+/// that the caller can't load are left out, so one missing entry doesn't fail a gallery.
+#[utoipa::path(
+    post,
+    path = "/api/animations/batch_meta",
+    tag = "Animations",
+    request_body = BatchMetadataRequest,
+    responses(
+        (status = 200, description = "Metadata of the animations the caller can load", body = [AnimationMetadata]),
+        (status = 400, description = "Too many IDs asked for at once", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn batch_metadata_handler(
+    State(ReadPool(read_pool)): State<ReadPool>,
+    user: Option<AuthUser>,
+    Json(request): Json<BatchMetadataRequest>,
+) -> Result<Json<Vec<AnimationMetadata>>, AppError> {
+    // Galleries of many animations, which may trail the latest saves like listings
+    let metadata =
+        AnimationService::batch_metadata_logic(&read_pool, request.ids, user.map(|u| u.id)).await?;
+    Ok(Json(metadata))
+}
+
+/// Duplicate an animation into a new one owned by the caller.
+///
+/// Works on any animation the caller can load; the copy is named "<name> (copy)".
+#[utoipa::path(
+    post,
+    path = "/api/animation/{id}/duplicate",
+    tag = "Animations",
+    params(
+        ("id" = i32, Path, description = "ID of the animation to copy", example = 1)
+    ),
+    responses(
+        (status = 201, description = "Animation duplicated successfully", body = crate::errors::SuccessfulSaveResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "Copy would exceed the caller's storage quota", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn duplicate_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let copy_id = AnimationService::duplicate_animation_logic(&pool, animation_id, user.id).await?;
+
+    let response_payload = SuccessfulSaveResponsePayload {
+        id: copy_id,
+        message: "Animation duplicated successfully".to_string(),
+    };
+    Ok((StatusCode::CREATED, Json(response_payload)))
+}
+
+/// List the templates users can start an animation from.
+#[utoipa::path(
+    get,
+    path = "/api/templates",
+    tag = "Templates",
+    responses(
+        (status = 200, description = "Every template, in their curated order", body = [Template])
+    )
+)]
+pub async fn list_templates_handler(
+    State(pool): State<DbPool>,
+) -> Result<Json<Vec<Template>>, AppError> {
+    let templates = TemplateService::list_templates_logic(&pool).await?;
+    Ok(Json(templates))
+}
+
+/// Start a new animation from a template, owned by the caller.
+///
+/// The animation is a copy of the template's, named after the template.
+#[utoipa::path(
+    post,
+    path = "/api/templates/{id}/instantiate",
+    tag = "Templates",
+    params(
+        ("id" = i32, Path, description = "ID of the template", example = 1)
+    ),
+    responses(
+        (status = 201, description = "Animation created from the template", body = crate::errors::SuccessfulSaveResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller has as many animations as allowed", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Template not found", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "Copy would exceed the caller's storage quota", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn instantiate_template_handler(
+    State(pool): State<DbPool>,
+    Path(template_id): Path<i32>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let animation_id =
+        TemplateService::instantiate_template_logic(&pool, template_id, user.id).await?;
+
+    let response_payload = SuccessfulSaveResponsePayload {
+        id: animation_id,
+        message: "Animation created from template".to_string(),
+    };
+    Ok((StatusCode::CREATED, Json(response_payload)))
+}
+
+/// Import a zip of animations, one per `.pb` document, `.geojson` file, `.gpx` track log
+/// or `.csv` point series.
+///
+/// Each file is imported by a background job of its own; poll the returned import
+/// until it is done to see the animation made from each file, or why none was.
+#[utoipa::path(
+    post,
+    path = "/api/import/archive",
+    tag = "Imports",
+    request_body(
+        content = bytes,
+        description = "A zip archive of .pb, .geojson, .gpx and .csv files",
+        content_type = "application/zip"
+    ),
+    responses(
+        (status = 202, description = "Import started", body = ArchiveImport),
+        (status = 400, description = "Not a readable zip, empty, or holding too many files", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "Archive over the archive size limit", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn import_archive_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let import = ImportService::import_archive_logic(&pool, user.id, body).await?;
+    Ok((StatusCode::ACCEPTED, Json(import)))
+}
+
+/// Import a CSV point series as a new animation of moving markers.
+///
+/// The header names the columns: an id (`id`, `name` or `track`), a `frame` or a time
+/// (`time`, `timestamp`, `date` or `datetime`), and `lat` and `lon` in degrees. Rows
+/// sharing an id become one point feature moving through them.
+#[utoipa::path(
+    post,
+    path = "/api/import/csv",
+    tag = "Imports",
+    params(ImportCsvParams),
+    request_body(
+        content = String,
+        description = "CSV with a header row",
+        content_type = "text/csv"
+    ),
+    responses(
+        (status = 201, description = "Animation created from the CSV", body = crate::errors::SuccessfulSaveResponsePayload),
+        (status = 400, description = "Missing columns or an invalid row", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller has as many animations as allowed", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "File over the upload size limit or the caller's storage quota", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn import_csv_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<ImportCsvParams>,
+    user: AuthUser,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let animation_id =
+        ImportService::import_csv_logic(&pool, user.id, params.name, params.total_frames, body)
+            .await?;
+
+    let response_payload = SuccessfulSaveResponsePayload {
+        id: animation_id,
+        message: "Animation imported from CSV".to_string(),
+    };
+    Ok((StatusCode::CREATED, Json(response_payload)))
+}
+
+/// Import a GPX file as a new animation of moving markers.
+///
+/// Each track becomes a point feature following its logged positions, all on one
+/// clock spread over the animation's frames. Every track point needs a `time`.
+#[utoipa::path(
+    post,
+    path = "/api/import/gpx",
+    tag = "Imports",
+    params(ImportGpxParams),
+    request_body(
+        content = String,
+        description = "GPX 1.0 or 1.1 track log",
+        content_type = "application/gpx+xml"
+    ),
+    responses(
+        (status = 201, description = "Animation created from the GPX file", body = crate::errors::SuccessfulSaveResponsePayload),
+        (status = 400, description = "Not GPX, or a track point without a time", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller has as many animations as allowed", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "File over the upload size limit or the caller's storage quota", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn import_gpx_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<ImportGpxParams>,
+    user: AuthUser,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let animation_id =
+        ImportService::import_gpx_logic(&pool, user.id, params.name, params.total_frames, body)
+            .await?;
+
+    let response_payload = SuccessfulSaveResponsePayload {
+        id: animation_id,
+        message: "Animation imported from GPX".to_string(),
+    };
+    Ok((StatusCode::CREATED, Json(response_payload)))
+}
+
+/// Get the progress of an archive import, file by file.
+#[utoipa::path(
+    get,
+    path = "/api/imports/{id}",
+    tag = "Imports",
+    params(
+        ("id" = i32, Path, description = "ID of the import", example = 4)
+    ),
+    responses(
+        (status = 200, description = "Import found", body = ArchiveImport),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Import belongs to another user", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Import not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn import_status_handler(
+    State(pool): State<DbPool>,
+    Path(import_id): Path<i32>,
+    user: AuthUser,
+) -> Result<Json<ArchiveImport>, AppError> {
+    let import = ImportService::import_status_logic(&pool, import_id, user.id).await?;
+    Ok(Json(import))
+}
+
+/// Start a resumable upload of a large animation.
+///
+/// Send the document's bytes in numbered parts, then complete the upload to save it.
+#[utoipa::path(
+    post,
+    path = "/api/uploads",
+    tag = "Uploads",
+    request_body = CreateUploadRequest,
+    responses(
+        (status = 201, description = "Upload started", body = UploadStatus),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller may not edit the animation to replace", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation to replace not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn create_upload_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+    Json(request): Json<CreateUploadRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let status = UploadService::create_upload_logic(&pool, user.id, request.animation_id).await?;
+    Ok((StatusCode::CREATED, Json(status)))
+}
+
+/// Get which parts of an upload have arrived.
+#[utoipa::path(
+    get,
+    path = "/api/uploads/{id}",
+    tag = "Uploads",
+    params(
+        ("id" = i32, Path, description = "ID of the upload", example = 5)
+    ),
+    responses(
+        (status = 200, description = "Upload found", body = UploadStatus),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Upload belongs to another user", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Upload not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn upload_status_handler(
+    State(pool): State<DbPool>,
+    Path(upload_id): Path<i32>,
+    user: AuthUser,
+) -> Result<Json<UploadStatus>, AppError> {
+    let status = UploadService::upload_status_logic(&pool, upload_id, user.id).await?;
+    Ok(Json(status))
+}
+
+/// Send one part of an upload, numbered from 0. Resending a part replaces it.
+#[utoipa::path(
+    put,
+    path = "/api/uploads/{id}/part/{n}",
+    tag = "Uploads",
+    params(
+        ("id" = i32, Path, description = "ID of the upload", example = 5),
+        ("n" = i32, Path, description = "Part number, from 0", example = 0)
+    ),
+    request_body(
+        content = bytes,
+        description = "The next slice of the binary Protobuf document",
+        content_type = "application/octet-stream"
+    ),
+    responses(
+        (status = 204, description = "Part stored"),
+        (status = 400, description = "Invalid part number", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Upload belongs to another user", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Upload not found", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "Part or whole upload too large", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn put_upload_part_handler(
+    State(pool): State<DbPool>,
+    Path((upload_id, part_number)): Path<(i32, i32)>,
+    user: AuthUser,
+    body: Bytes,
+) -> Result<StatusCode, AppError> {
+    UploadService::put_part_logic(&pool, upload_id, part_number, body, user.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Finish an upload: join its parts and save the animation.
+///
+/// Creates a new animation, or replaces the one named when the upload started. A
+/// replacement must send `If-Match`, and is refused if someone else saved in the meantime.
+/// A document the caller already saved updates that animation as a direct save does,
+/// and needs `If-Match` the same way.
+#[utoipa::path(
+    post,
+    path = "/api/uploads/{id}/complete",
+    tag = "Uploads",
+    params(
+        ("id" = i32, Path, description = "ID of the upload", example = 5),
+        ("If-Match" = Option<String>, Header, description = "ETag of the version a replacement is based on", example = "\"3\"")
+    ),
+    responses(
+        (status = 200, description = "Existing animation updated", body = crate::errors::SuccessfulSaveResponsePayload),
+        (status = 201, description = "Animation saved successfully", body = crate::errors::SuccessfulSaveResponsePayload),
+        (status = 400, description = "Parts missing, or the document is invalid", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Upload belongs to another user", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Upload not found", body = crate::errors::ErrorResponsePayload),
+        (status = 409, description = "Animation changed since the If-Match version", body = crate::errors::ErrorResponsePayload),
+        (status = 413, description = "Document over the storage quota", body = crate::errors::ErrorResponsePayload),
+        (status = 428, description = "Replacement, or document already saved, and no If-Match sent", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn complete_upload_handler(
+    State(pool): State<DbPool>,
+    Path(upload_id): Path<i32>,
+    user: AuthUser,
+    request_headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let if_match = save_if_match(&request_headers)?;
+    let mut headers = HeaderMap::new();
+    let (status_code, id, message) =
+        match UploadService::complete_upload_logic(&pool, upload_id, user.id, if_match).await? {
+            CompletedUpload::Created(id) => {
+                (StatusCode::CREATED, id, "Animation saved successfully")
+            }
+            CompletedUpload::Updated(id, version) => {
+                if let Ok(etag) = HeaderValue::from_str(&version_etag(version)) {
+                    headers.insert(axum::http::header::ETAG, etag);
+                }
+                (StatusCode::OK, id, "Animation updated successfully")
+            }
+        };
+
+    let response_payload = SuccessfulSaveResponsePayload {
+        id,
+        message: message.to_string(),
+    };
+    Ok((status_code, headers, Json(response_payload)))
+}
+
+/// Export an animation as an animated GIF or MP4 video.
+///
+/// Rendering happens in the background; poll the returned job until it is done,
+/// then fetch the file from its `download_url`.
+#[utoipa::path(
+    post,
+    path = "/api/animation/{id}/export/video",
+    tag = "Exports",
+    params(
+        ("id" = i32, Path, description = "ID of the animation to export", example = 1)
+    ),
+    request_body = ExportVideoRequest,
+    responses(
+        (status = 202, description = "Export started", body = ExportJob),
+        (status = 400, description = "Size or frame rate out of range", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn export_video_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: Option<AuthUser>,
+    Json(request): Json<ExportVideoRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let job =
+        ExportService::start_video_export_logic(&pool, animation_id, user.map(|u| u.id), request)
+            .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Export the features at each frame of an animation as GeoJSON.
+///
+/// The export is a zip with one FeatureCollection per frame, or per every `step`-th
+/// frame, for post-processing in GIS tools or scripts. It is written in the background;
+/// poll the returned job until it is done, then fetch the zip from its `download_url`.
+#[utoipa::path(
+    post,
+    path = "/api/animation/{id}/export/frames",
+    tag = "Exports",
+    params(
+        ("id" = i32, Path, description = "ID of the animation to export", example = 1)
+    ),
+    request_body = ExportFramesRequest,
+    responses(
+        (status = 202, description = "Export started", body = ExportJob),
+        (status = 400, description = "Frame step out of range", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn export_frames_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: Option<AuthUser>,
+    Json(request): Json<ExportFramesRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let job =
+        ExportService::start_frames_export_logic(&pool, animation_id, user.map(|u| u.id), request)
+            .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Get the progress of a video or frames export.
+#[utoipa::path(
+    get,
+    path = "/api/exports/{id}",
+    tag = "Exports",
+    params(
+        ("id" = i32, Path, description = "ID of the export job", example = 3)
+    ),
+    responses(
+        (status = 200, description = "Export found", body = ExportJob),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Export not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn export_status_handler(
+    State(pool): State<DbPool>,
+    Path(job_id): Path<i32>,
+    user: Option<AuthUser>,
+) -> Result<Json<ExportJob>, AppError> {
+    let job = ExportService::export_status_logic(&pool, job_id, user.map(|u| u.id)).await?;
+    Ok(Json(job))
+}
+
+/// Download a finished export: the video, or the zip of GeoJSON frames.
+#[utoipa::path(
+    get,
+    path = "/api/exports/{id}/download",
+    tag = "Exports",
+    params(
+        ("id" = i32, Path, description = "ID of the export job", example = 3)
+    ),
+    responses(
+        (status = 200, description = "The exported file", body = bytes, content_type = "image/gif"),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Export not found or not finished", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn download_export_handler(
+    State(pool): State<DbPool>,
+    Path(job_id): Path<i32>,
+    user: Option<AuthUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let (format, data) =
+        ExportService::download_export_logic(&pool, job_id, user.map(|u| u.id)).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        axum::http::header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    let disposition = format!(
+        "attachment; filename=\"export-{}.{}\"",
+        job_id,
+        format.extension()
+    );
+    headers.insert(
+        axum::http::header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition)
+            .map_err(|err| AppError::Internal(format!("Invalid header: {}", err)))?,
+    );
+
+    Ok((headers, data))
+}
+
+/// Health check endpoint, for liveness probes.
+///
+/// Returns a simple "Healthy!" message if the server is running, without touching
+/// the database; also served at `/api/health/live`.
+#[utoipa::path(
+    get,
+    path = "/api/health",
+    tag = "System",
+    responses(
+        (status = 200, description = "Server is healthy", body = String, example = json!("Healthy!"))
+    )
+)]
+pub async fn health_check_handler() -> (StatusCode, String) {
+    (StatusCode::OK, "Healthy!".to_string())
+}
+
+/// Readiness check, for load balancers and orchestrators.
+///
+/// Runs `SELECT 1` and checks migrations, answering 503 if this instance shouldn't
+/// get traffic. Also reports the latest migration applied, how many pooled
+/// connections are open, idle and in use, and the 95th percentile of recent
+/// database round trips, for diagnosing a struggling instance.
+#[utoipa::path(
+    get,
+    path = "/api/health/ready",
+    tag = "System",
+    responses(
+        (status = 200, description = "Database reachable and migrated", body = Readiness),
+        (status = 503, description = "Database unreachable or not migrated", body = Readiness)
+    )
+)]
+pub async fn readiness_handler(State(pool): State<DbPool>) -> (StatusCode, Json<Readiness>) {
+    let readiness = crate::db::check_readiness(&pool).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+/// Get the progress of a background job, such as a thumbnail render or video export.
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    tag = "Jobs",
+    params(
+        ("id" = i32, Path, description = "ID of the job", example = 12)
+    ),
+    responses(
+        (status = 200, description = "Job found", body = JobInfo),
+        (status = 401, description = "Animation is private and no session was given", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Job not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn job_status_handler(
+    State(pool): State<DbPool>,
+    Path(job_id): Path<i32>,
+    user: Option<AuthUser>,
+) -> Result<Json<JobInfo>, AppError> {
+    let job = JobService::job_status_logic(&pool, job_id, user.map(|u| u.id)).await?;
+    Ok(Json(job))
+}
+
+/// Response for a new sign-in: the account, with the session cookie set.
+fn signed_in(account: Account, token: &str) -> Result<impl IntoResponse, AppError> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        axum::http::header::SET_COOKIE,
+        HeaderValue::from_str(&session_cookie(token))
+            .map_err(|err| AppError::Internal(format!("Invalid header: {}", err)))?,
+    );
+    Ok((headers, Json(account)))
+}
+
+/// Create a local account with an email and password, and sign in to it.
+#[utoipa::path(
+    post,
+    path = "/api/auth/register",
+    tag = "Accounts",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "Account created; the session cookie is set", body = Account),
+        (status = 400, description = "Invalid email or password, or the username or email is taken", body = crate::errors::ErrorResponsePayload),
+        (status = 429, description = "Too many attempts from this IP; wait for Retry-After", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn register_handler(
+    State(pool): State<DbPool>,
+    client: ClientInfo,
+    Json(request): Json<RegisterRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let (account, token) = AccountService::register_logic(&pool, request, &client).await?;
+    Ok((StatusCode::CREATED, signed_in(account, &token)?))
+}
+
+/// Sign in to a local account.
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    tag = "Accounts",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Signed in; the session cookie is set", body = Account),
+        (status = 401, description = "Wrong email or password", body = crate::errors::ErrorResponsePayload),
+        (status = 429, description = "Too many attempts from this IP; wait for Retry-After", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn login_handler(
+    State(pool): State<DbPool>,
+    client: ClientInfo,
+    Json(request): Json<LoginRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let (account, token) = AccountService::login_logic(&pool, request, &client).await?;
+    signed_in(account, &token)
+}
+
+/// Ask for a password reset token for a local account.
+///
+/// Always accepted, so the response doesn't reveal whether the email has an account.
+#[utoipa::path(
+    post,
+    path = "/api/auth/password-reset",
+    tag = "Accounts",
+    request_body = PasswordResetRequest,
+    responses(
+        (status = 202, description = "A reset token is issued if the account exists"),
+        (status = 429, description = "Too many attempts from this IP; wait for Retry-After", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn request_password_reset_handler(
+    State(pool): State<DbPool>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<StatusCode, AppError> {
+    AccountService::request_password_reset_logic(&pool, request).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Set a new password with a reset token. Signs the account out everywhere.
+#[utoipa::path(
+    post,
+    path = "/api/auth/password-reset/confirm",
+    tag = "Accounts",
+    request_body = ConfirmPasswordResetRequest,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 400, description = "Token invalid or expired, or password too short", body = crate::errors::ErrorResponsePayload),
+        (status = 429, description = "Too many attempts from this IP; wait for Retry-After", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn confirm_password_reset_handler(
+    State(pool): State<DbPool>,
+    Json(request): Json<ConfirmPasswordResetRequest>,
+) -> Result<StatusCode, AppError> {
+    AccountService::confirm_password_reset_logic(&pool, request).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the caller's sessions, with the device each is used from.
+///
+/// Sessions signed in from an IP and browser none of the caller's other sessions had
+/// are marked `new_device`.
+#[utoipa::path(
+    get,
+    path = "/api/auth/sessions",
+    tag = "Accounts",
+    responses(
+        (status = 200, description = "The caller's sessions, most recently seen first", body = [SessionInfo]),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Called with an API token rather than a session", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn list_sessions_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+) -> Result<Json<Vec<SessionInfo>>, AppError> {
+    let user = user.require_session()?;
+    let sessions = SessionService::list_sessions_logic(&pool, user.id, user.session_id).await?;
+    Ok(Json(sessions))
+}
+
+/// End one of the caller's sessions, signing out the device using it.
+#[utoipa::path(
+    delete,
+    path = "/api/auth/sessions/{id}",
+    tag = "Accounts",
+    params(
+        ("id" = i32, Path, description = "ID of the session", example = 7)
+    ),
+    responses(
+        (status = 204, description = "Session ended"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Called with an API token rather than a session", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "The caller has no such session", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn revoke_session_handler(
+    State(pool): State<DbPool>,
+    Path(session_id): Path<i32>,
+    user: AuthUser,
+) -> Result<StatusCode, AppError> {
+    let user = user.require_session()?;
+    SessionService::revoke_session_logic(&pool, user.id, session_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the caller's personal access tokens.
+#[utoipa::path(
+    get,
+    path = "/api/tokens",
+    tag = "Accounts",
+    responses(
+        (status = 200, description = "The caller's tokens, newest first", body = [ApiToken]),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Called with an API token rather than a session", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn list_tokens_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+) -> Result<Json<Vec<ApiToken>>, AppError> {
+    let user = user.require_session()?;
+    let tokens = TokenService::list_tokens_logic(&pool, user.id).await?;
+    Ok(Json(tokens))
+}
+
+/// Create a personal access token, for scripts sending `Authorization: Bearer <token>`.
+///
+/// The token is only ever shown in this response.
+#[utoipa::path(
+    post,
+    path = "/api/tokens",
+    tag = "Accounts",
+    request_body = CreateApiTokenRequest,
+    responses(
+        (status = 201, description = "Token created", body = CreatedApiToken),
+        (status = 400, description = "Missing name or scopes, or an invalid lifetime", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Called with an API token rather than a session", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn create_token_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+    Json(request): Json<CreateApiTokenRequest>,
+) -> Result<(StatusCode, Json<CreatedApiToken>), AppError> {
+    let user = user.require_session()?;
+    let created = TokenService::create_token_logic(&pool, user.id, request).await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// Revoke one of the caller's personal access tokens.
+#[utoipa::path(
+    delete,
+    path = "/api/tokens/{id}",
+    tag = "Accounts",
+    params(
+        ("id" = i32, Path, description = "ID of the token", example = 4)
+    ),
+    responses(
+        (status = 204, description = "Token revoked"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Called with an API token rather than a session", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "The caller has no such token", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn revoke_token_handler(
+    State(pool): State<DbPool>,
+    Path(token_id): Path<i32>,
+    user: AuthUser,
+) -> Result<StatusCode, AppError> {
+    let user = user.require_session()?;
+    TokenService::revoke_token_logic(&pool, user.id, token_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the caller's webhooks.
+#[utoipa::path(
+    get,
+    path = "/api/webhooks",
+    tag = "Webhooks",
+    responses(
+        (status = 200, description = "The caller's webhooks, newest first", body = [Webhook]),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn list_webhooks_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+) -> Result<Json<Vec<Webhook>>, AppError> {
+    let webhooks = WebhookService::list_webhooks_logic(&pool, user.id).await?;
+    Ok(Json(webhooks))
+}
+
+/// Register a URL to be called when the caller's animations change.
+///
+/// Events are `saved`, `published` and `deleted`. Each delivery is a JSON POST naming
+/// the event, animation and version, signed in `X-Klyja-Signature` with HMAC-SHA256 of
+/// the body under the webhook's secret. The secret is only ever shown in this response.
+/// The URL must point at a public address.
+#[utoipa::path(
+    post,
+    path = "/api/webhooks",
+    tag = "Webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, description = "Webhook registered", body = CreatedWebhook),
+        (status = 400, description = "Invalid or non-public URL, invalid secret, or no events", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn create_webhook_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<CreatedWebhook>), AppError> {
+    let created = WebhookService::create_webhook_logic(&pool, user.id, request).await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// Delete one of the caller's webhooks.
+#[utoipa::path(
+    delete,
+    path = "/api/webhooks/{id}",
+    tag = "Webhooks",
+    params(
+        ("id" = i32, Path, description = "ID of the webhook", example = 3)
+    ),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "The caller has no such webhook", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn delete_webhook_handler(
+    State(pool): State<DbPool>,
+    Path(webhook_id): Path<i32>,
+    user: AuthUser,
+) -> Result<StatusCode, AppError> {
+    WebhookService::delete_webhook_logic(&pool, user.id, webhook_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Report an animation to the admins.
+///
+/// Anyone signed in who can load the animation may report it; reporting it again
+/// replaces the reason.
+#[utoipa::path(
+    post,
+    path = "/api/animation/{id}/report",
+    tag = "Moderation",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    request_body = ReportRequest,
+    responses(
+        (status = 204, description = "Report recorded"),
+        (status = 400, description = "Reason is blank or too long", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Animation is not shared with the caller", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn report_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    Json(request): Json<ReportRequest>,
+) -> Result<StatusCode, AppError> {
+    ModerationService::report_animation_logic(&pool, animation_id, user.id, request).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the reports no admin has acted on yet, oldest first. Admins only.
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports",
+    tag = "Moderation",
+    responses(
+        (status = 200, description = "Open reports", body = [Report]),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn list_reports_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+) -> Result<Json<Vec<Report>>, AppError> {
+    let reports = ModerationService::list_reports_logic(&pool, user.id).await?;
+    Ok(Json(reports))
+}
+
+/// Hide an animation from everyone but its owner. Admins only.
+///
+/// Others get 404 loading it and no longer see it listed. Resolves its open reports.
+#[utoipa::path(
+    post,
+    path = "/api/admin/animations/{id}/hide",
+    tag = "Moderation",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 204, description = "Animation hidden"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn hide_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+) -> Result<StatusCode, AppError> {
+    ModerationService::set_hidden_logic(&pool, user.id, animation_id, true).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Show a hidden animation again. Admins only.
+///
+/// Resolves its open reports.
+#[utoipa::path(
+    post,
+    path = "/api/admin/animations/{id}/unhide",
+    tag = "Moderation",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    responses(
+        (status = 204, description = "Animation shown again"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn unhide_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+) -> Result<StatusCode, AppError> {
+    ModerationService::set_hidden_logic(&pool, user.id, animation_id, false).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get counts of sign-in attempts, failures and lockouts since the server started.
+/// Admins only.
+#[utoipa::path(
+    get,
+    path = "/api/admin/auth-throttling",
+    tag = "Moderation",
+    responses(
+        (status = 200, description = "This instance's counts", body = AuthThrottleStats),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn auth_throttle_stats_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+) -> Result<Json<AuthThrottleStats>, AppError> {
+    let stats = ThrottleService::stats_logic(&pool, user.id).await?;
+    Ok(Json(stats))
+}
+
+/// Get whether the server is in maintenance mode. Admins only.
+#[utoipa::path(
+    get,
+    path = "/api/admin/maintenance",
+    tag = "Moderation",
+    responses(
+        (status = 200, description = "This instance's maintenance mode", body = MaintenanceStatus),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn maintenance_status_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+) -> Result<Json<MaintenanceStatus>, AppError> {
+    let status = MaintenanceService::status_logic(&pool, user.id).await?;
+    Ok(Json(status))
+}
+
+/// Switch maintenance mode on or off. Admins only.
+///
+/// While it is on, requests that would change anything get 503 with the code
+/// `maintenance` and the message given here; reads carry on as usual. This route
+/// keeps working, so admins can switch it off again.
+#[utoipa::path(
+    put,
+    path = "/api/admin/maintenance",
+    tag = "Moderation",
+    request_body = MaintenanceRequest,
+    responses(
+        (status = 200, description = "Maintenance mode switched", body = MaintenanceStatus),
+        (status = 400, description = "Message too long", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn set_maintenance_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+    Json(request): Json<MaintenanceRequest>,
+) -> Result<Json<MaintenanceStatus>, AppError> {
+    let status = MaintenanceService::set_logic(&pool, user.id, request).await?;
+    Ok(Json(status))
+}
+
+/// Back up every animation, with its details, to a zip on the server. Admins only.
+///
+/// Runs in the background; poll the returned backup until it is done, then download
+/// it or restore it on another deployment sharing the backup directory.
+#[utoipa::path(
+    post,
+    path = "/api/admin/backups",
+    tag = "Backups",
+    responses(
+        (status = 202, description = "Backup started", body = BackupJob),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn start_backup_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let backup = BackupService::start_backup_logic(&pool, user.id).await?;
+    Ok((StatusCode::ACCEPTED, Json(backup)))
+}
+
+/// Restore the animations in a backup zip on the server. Admins only.
+///
+/// Animations go to the user with their owner's username, else to the caller.
+/// Those whose document ID is already taken are skipped, so a restore can be rerun.
+#[utoipa::path(
+    post,
+    path = "/api/admin/restores",
+    tag = "Backups",
+    request_body = RestoreRequest,
+    responses(
+        (status = 202, description = "Restore started", body = BackupJob),
+        (status = 400, description = "Not a .zip name within the backup directory", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "No backup by that name", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn start_restore_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+    Json(request): Json<RestoreRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let restore = BackupService::start_restore_logic(&pool, user.id, request.file_name).await?;
+    Ok((StatusCode::ACCEPTED, Json(restore)))
+}
+
+/// Get the progress of a backup or restore. Admins only.
+#[utoipa::path(
+    get,
+    path = "/api/admin/backups/{id}",
+    tag = "Backups",
+    params(
+        ("id" = i32, Path, description = "ID of the backup or restore", example = 2)
+    ),
+    responses(
+        (status = 200, description = "Backup found", body = BackupJob),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Backup not found", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn backup_status_handler(
+    State(pool): State<DbPool>,
+    Path(backup_id): Path<i32>,
+    user: AuthUser,
+) -> Result<Json<BackupJob>, AppError> {
+    let backup = BackupService::backup_status_logic(&pool, backup_id, user.id).await?;
+    Ok(Json(backup))
+}
+
+/// Download a finished backup's zip. Admins only.
+#[utoipa::path(
+    get,
+    path = "/api/admin/backups/{id}/download",
+    tag = "Backups",
+    params(
+        ("id" = i32, Path, description = "ID of the backup", example = 2)
+    ),
+    responses(
+        (status = 200, description = "The backup zip", body = bytes, content_type = "application/zip"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller is not an admin", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Backup not found or not finished", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn download_backup_handler(
+    State(pool): State<DbPool>,
+    Path(backup_id): Path<i32>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let (file_name, data) = BackupService::download_backup_logic(&pool, backup_id, user.id).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        axum::http::header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    headers.insert(
+        axum::http::header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", file_name))
+            .map_err(|err| AppError::Internal(format!("Invalid header: {}", err)))?,
+    );
+
+    Ok((headers, data))
+}
+
+/// List the caller's folders, by name.
+///
+/// The list is flat; nest folders by their `parent_id`.
+#[utoipa::path(
+    get,
+    path = "/api/folders",
+    tag = "Folders",
+    responses(
+        (status = 200, description = "The caller's folders", body = [Folder]),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn list_folders_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+) -> Result<Json<Vec<Folder>>, AppError> {
+    let folders = FolderService::list_folders_logic(&pool, user.id).await?;
+    Ok(Json(folders))
+}
+
+/// Create a folder, at the top level or inside another of the caller's folders.
+#[utoipa::path(
+    post,
+    path = "/api/folders",
+    tag = "Folders",
+    request_body = FolderRequest,
+    responses(
+        (status = 201, description = "Folder created", body = Folder),
+        (status = 400, description = "Name is blank or too long", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "The caller has no such parent folder", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn create_folder_handler(
+    State(pool): State<DbPool>,
+    user: AuthUser,
+    Json(request): Json<FolderRequest>,
+) -> Result<(StatusCode, Json<Folder>), AppError> {
+    let folder = FolderService::create_folder_logic(&pool, user.id, request).await?;
+    Ok((StatusCode::CREATED, Json(folder)))
+}
+
+/// Rename a folder and set which folder it is in.
+///
+/// Leaving out `parent_id` moves it to the top level.
+#[utoipa::path(
+    put,
+    path = "/api/folders/{id}",
+    tag = "Folders",
+    params(
+        ("id" = i32, Path, description = "ID of the folder", example = 4)
+    ),
+    request_body = FolderRequest,
+    responses(
+        (status = 200, description = "Folder updated", body = Folder),
+        (status = 400, description = "Name is blank or too long, or the folder would end up inside itself", body = crate::errors::ErrorResponsePayload),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "The caller has no such folder or parent folder", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn update_folder_handler(
+    State(pool): State<DbPool>,
+    Path(folder_id): Path<i32>,
+    user: AuthUser,
+    Json(request): Json<FolderRequest>,
+) -> Result<Json<Folder>, AppError> {
+    let folder = FolderService::update_folder_logic(&pool, user.id, folder_id, request).await?;
+    Ok(Json(folder))
+}
+
+/// Delete a folder and its subfolders.
+///
+/// The animations in them are kept and go back to the top level.
+#[utoipa::path(
+    delete,
+    path = "/api/folders/{id}",
+    tag = "Folders",
+    params(
+        ("id" = i32, Path, description = "ID of the folder", example = 4)
+    ),
+    responses(
+        (status = 204, description = "Folder deleted"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "The caller has no such folder", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn delete_folder_handler(
+    State(pool): State<DbPool>,
+    Path(folder_id): Path<i32>,
+    user: AuthUser,
+) -> Result<StatusCode, AppError> {
+    FolderService::delete_folder_logic(&pool, user.id, folder_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// File one of the caller's animations in one of their folders.
+///
+/// A null `folder_id` moves it back to the top level. Only the owner may file it.
+#[utoipa::path(
+    put,
+    path = "/api/animation/{id}/folder",
+    tag = "Folders",
+    params(
+        ("id" = i32, Path, description = "ID of the animation", example = 1)
+    ),
+    request_body = MoveAnimationRequest,
+    responses(
+        (status = 204, description = "Animation moved"),
+        (status = 401, description = "No valid session", body = crate::errors::ErrorResponsePayload),
+        (status = 403, description = "Caller doesn't own the animation", body = crate::errors::ErrorResponsePayload),
+        (status = 404, description = "Animation not found, or the caller has no such folder", body = crate::errors::ErrorResponsePayload)
+    )
+)]
+pub async fn move_animation_handler(
+    State(pool): State<DbPool>,
+    Path(animation_id): Path<i32>,
+    user: AuthUser,
+    Json(request): Json<MoveAnimationRequest>,
+) -> Result<StatusCode, AppError> {
+    FolderService::move_animation_logic(&pool, animation_id, user.id, request).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,178 @@
+// klyja/backend/src/i18n.rs
+// Error messages in the caller's language: each request's Accept-Language picks one of
+// the languages below, and error bodies carry the message for their code in it, with
+// the more specific English message alongside. Logs stay in English whatever the
+// caller reads. Languages without a translation get English.
+use axum::{extract::Request, http::header::ACCEPT_LANGUAGE, middleware::Next, response::Response};
+
+/// Languages error messages are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Spanish,
+    French,
+    German,
+}
+
+impl Language {
+    /// The language's tag, as sent in `Content-Language`.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+            Language::French => "fr",
+            Language::German => "de",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        // Regional variants (es-MX, fr-CA) read the same messages
+        let primary = tag.split('-').next().unwrap_or_default();
+        match primary.to_ascii_lowercase().as_str() {
+            "en" => Some(Language::English),
+            "es" => Some(Language::Spanish),
+            "fr" => Some(Language::French),
+            "de" => Some(Language::German),
+            _ => None,
+        }
+    }
+}
+
+/// The caller's most preferred language we have, from an `Accept-Language` value
+/// such as `fr-CH, fr;q=0.9, en;q=0.8`. English if none of them.
+pub fn negotiate(accept_language: &str) -> Language {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // Stable, so equally preferred languages keep the caller's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranges
+        .into_iter()
+        .find_map(|(tag, _)| Language::from_tag(tag))
+        .unwrap_or(Language::English)
+}
+
+tokio::task_local! {
+    static LANGUAGE: Language;
+}
+
+/// The language of the request being handled; English outside `negotiate_language`.
+pub fn current_language() -> Language {
+    LANGUAGE
+        .try_with(|language| *language)
+        .unwrap_or(Language::English)
+}
+
+/// Middleware settling each request's language from its `Accept-Language`.
+pub async fn negotiate_language(request: Request, next: Next) -> Response {
+    let language = request
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(negotiate)
+        .unwrap_or(Language::English);
+    LANGUAGE.scope(language, next.run(request)).await
+}
+
+/// What a translated message may mention besides its code.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MessageArgs {
+    pub count: Option<u64>,   // A limit, in items or bytes
+    pub seconds: Option<u64>, // How long to wait, or a request was given
+    pub version: Option<i32>, // An animation's current version
+}
+
+/// The message for an error code in `language`, or `None` for English (whose
+/// messages are the errors' own) and codes without one.
+pub fn translate(language: Language, code: &str, args: MessageArgs) -> Option<String> {
+    let template = match language {
+        Language::English => return None,
+        Language::Spanish => match code {
+            "invalid_data_format" => "Los datos enviados no tienen un formato válido.",
+            "database_error" => "No se pudo acceder a la base de datos. Inténtalo de nuevo más tarde.",
+            "not_found" => "No se encontró lo que buscabas.",
+            "bad_request" => "La solicitud no es válida.",
+            "invalid_document" => "La animación contiene errores; revisa los detalles.",
+            "unauthorized" => "Inicia sesión para continuar.",
+            "forbidden" => "No tienes permiso para hacer esto.",
+            "payload_too_large" => "El archivo es demasiado grande.",
+            "body_too_large" => "El archivo es demasiado grande; el máximo es de {count} bytes.",
+            "quota_exceeded" => "Has agotado tu espacio de almacenamiento.",
+            "limit_exceeded" => "Has alcanzado el máximo permitido ({count}).",
+            "corrupt_document" => "La animación guardada está dañada.",
+            "precondition_required" => "Indica qué versión de la animación estás modificando.",
+            "version_conflict" => "Alguien cambió esta animación después de que la abrieras; ahora está en la versión {version}.",
+            "too_many_requests" => "Demasiados intentos de inicio de sesión; inténtalo de nuevo en {seconds} segundos.",
+            "timeout" => "La solicitud tardó más de {seconds} segundos y se canceló; inténtalo de nuevo más tarde.",
+            "maintenance" => "Klyja está en mantenimiento: puedes ver las animaciones, pero no guardar cambios por ahora.",
+            "internal_error" => "Algo salió mal en el servidor.",
+            _ => return None,
+        },
+        Language::French => match code {
+            "invalid_data_format" => "Les données envoyées ne sont pas dans un format valide.",
+            "database_error" => "La base de données est inaccessible. Réessayez plus tard.",
+            "not_found" => "Élément introuvable.",
+            "bad_request" => "La requête n'est pas valide.",
+            "invalid_document" => "L'animation contient des erreurs ; consultez les détails.",
+            "unauthorized" => "Connectez-vous pour continuer.",
+            "forbidden" => "Vous n'avez pas la permission de faire cela.",
+            "payload_too_large" => "Le fichier est trop volumineux.",
+            "body_too_large" => "Le fichier est trop volumineux ; la limite est de {count} octets.",
+            "quota_exceeded" => "Votre espace de stockage est plein.",
+            "limit_exceeded" => "Vous avez atteint le maximum autorisé ({count}).",
+            "corrupt_document" => "L'animation enregistrée est endommagée.",
+            "precondition_required" => "Indiquez quelle version de l'animation vous modifiez.",
+            "version_conflict" => "Quelqu'un a modifié cette animation depuis que vous l'avez ouverte ; elle est maintenant à la version {version}.",
+            "too_many_requests" => "Trop de tentatives de connexion ; réessayez dans {seconds} secondes.",
+            "timeout" => "La requête a pris plus de {seconds} secondes et a été annulée ; réessayez plus tard.",
+            "maintenance" => "Klyja est en maintenance : les animations restent consultables, mais les modifications ne peuvent pas être enregistrées pour le moment.",
+            "internal_error" => "Une erreur s'est produite sur le serveur.",
+            _ => return None,
+        },
+        Language::German => match code {
+            "invalid_data_format" => "Die gesendeten Daten haben kein gültiges Format.",
+            "database_error" => "Die Datenbank ist nicht erreichbar. Bitte später erneut versuchen.",
+            "not_found" => "Nicht gefunden.",
+            "bad_request" => "Die Anfrage ist ungültig.",
+            "invalid_document" => "Die Animation enthält Fehler; siehe Details.",
+            "unauthorized" => "Bitte melde dich an, um fortzufahren.",
+            "forbidden" => "Dazu fehlt dir die Berechtigung.",
+            "payload_too_large" => "Die Datei ist zu groß.",
+            "body_too_large" => "Die Datei ist zu groß; erlaubt sind höchstens {count} Bytes.",
+            "quota_exceeded" => "Dein Speicherplatz ist voll.",
+            "limit_exceeded" => "Du hast das erlaubte Maximum ({count}) erreicht.",
+            "corrupt_document" => "Die gespeicherte Animation ist beschädigt.",
+            "precondition_required" => "Gib an, welche Version der Animation du änderst.",
+            "version_conflict" => "Jemand hat diese Animation geändert, seit du sie geöffnet hast; sie ist jetzt bei Version {version}.",
+            "too_many_requests" => "Zu viele Anmeldeversuche; bitte in {seconds} Sekunden erneut versuchen.",
+            "timeout" => "Die Anfrage hat länger als {seconds} Sekunden gedauert und wurde abgebrochen; bitte später erneut versuchen.",
+            "maintenance" => "Klyja wird gerade gewartet: Animationen lassen sich ansehen, Änderungen aber vorerst nicht speichern.",
+            "internal_error" => "Auf dem Server ist ein Fehler aufgetreten.",
+            _ => return None,
+        },
+    };
+    let fill = |text: String, placeholder: &str, value: Option<String>| match value {
+        Some(value) => text.replace(placeholder, &value),
+        None => text,
+    };
+    let message = fill(
+        template.to_string(),
+        "{count}",
+        args.count.map(|c| c.to_string()),
+    );
+    let message = fill(message, "{seconds}", args.seconds.map(|s| s.to_string()));
+    Some(fill(
+        message,
+        "{version}",
+        args.version.map(|v| v.to_string()),
+    ))
+}
@@ -0,0 +1,504 @@
+// klyja/backend/src/imports.rs
+// Archive imports: a zip of `.pb` documents, `.geojson` files, `.gpx` tracks and `.csv`
+// point series, each made into an animation of the sender's by a queue job of its own.
+// Every file gets its own result, so one bad file doesn't sink the rest and the sender
+// can see which to fix. A single CSV file can also be imported straight away.
+use crate::{
+    errors::{body_too_large_message, AppError},
+    jobs::{self, Job},
+    models::{ArchiveImport, ImportedFile},
+    protobuf_gen::{MapAnimation, Polygon},
+    services::{insert_document, max_upload_bytes, run_in_transaction, IfMatch, PreparedDocument},
+    DbPool,
+};
+use axum::body::Bytes;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use geco_core::format::CURRENT_FORMAT_VERSION;
+use geco_core::point_series::MAX_FRAME;
+use geco_core::tracks::DEFAULT_TOTAL_FRAMES;
+use prost::Message;
+use std::io::{Cursor, Read};
+use std::sync::OnceLock;
+use zip::ZipArchive;
+
+/// Largest archive accepted when `MAX_ARCHIVE_BYTES` doesn't say.
+pub const DEFAULT_MAX_ARCHIVE_BYTES: usize = 64 * 1024 * 1024;
+/// Most files one archive may hold, directories aside.
+pub const MAX_ARCHIVE_FILES: usize = 500;
+/// Sphere radius features from GeoJSON are placed at, the viewer's default.
+pub const GEOJSON_RADIUS: f32 = 5.0;
+
+/// Largest archive import, read once from `MAX_ARCHIVE_BYTES`. Each file in it is
+/// still held to `MAX_UPLOAD_BYTES`, and all of them together, uncompressed, to this.
+pub fn max_archive_bytes() -> usize {
+    static LIMIT: OnceLock<usize> = OnceLock::new();
+    *LIMIT.get_or_init(|| {
+        std::env::var("MAX_ARCHIVE_BYTES")
+            .ok()
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(DEFAULT_MAX_ARCHIVE_BYTES)
+    })
+}
+
+/// What an archive file holds, by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Protobuf,
+    GeoJson,
+    Gpx,
+    Csv,
+}
+
+impl ImportFormat {
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let extension = file_name.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "pb" => Some(ImportFormat::Protobuf),
+            "geojson" => Some(ImportFormat::GeoJson),
+            "gpx" => Some(ImportFormat::Gpx),
+            "csv" => Some(ImportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// A file read from an archive: its path, and its data or why it can't be imported.
+type ArchiveFile = (String, Result<Vec<u8>, String>);
+
+/// Reads every file in a zip, leaving directories out. Files of other types, or over
+/// the upload limit, are kept with the reason they won't be imported. Archives that
+/// uncompress to more than the archive limit are refused whole.
+fn read_archive(archive: &Bytes) -> Result<Vec<ArchiveFile>, AppError> {
+    let mut archive = ZipArchive::new(Cursor::new(archive))
+        .map_err(|err| AppError::BadRequest(format!("Not a readable zip archive: {}", err)))?;
+    let max_bytes = max_upload_bytes();
+    let mut total_bytes = 0;
+    let mut files = vec![];
+    for index in 0..archive.len() {
+        let file = archive
+            .by_index(index)
+            .map_err(|err| AppError::BadRequest(format!("Unreadable zip entry: {}", err)))?;
+        if file.is_dir() {
+            continue;
+        }
+        if files.len() == MAX_ARCHIVE_FILES {
+            return Err(AppError::BadRequest(format!(
+                "Archives may hold at most {} files",
+                MAX_ARCHIVE_FILES
+            )));
+        }
+        let mut file_name = file.name().to_string();
+        if file_name.len() > 255 {
+            // Keep the end of long paths, where the file's own name is
+            let mut start = file_name.len() - 250;
+            while !file_name.is_char_boundary(start) {
+                start += 1;
+            }
+            file_name = format!("...{}", &file_name[start..]);
+        }
+        if ImportFormat::from_file_name(&file_name).is_none() {
+            files.push((
+                file_name,
+                Err("Only .pb, .geojson, .gpx and .csv files can be imported".to_string()),
+            ));
+            continue;
+        }
+        // The declared size can't be trusted, so read one byte past the limit to tell
+        let mut data = vec![];
+        file.take(max_bytes as u64 + 1)
+            .read_to_end(&mut data)
+            .map_err(|err| AppError::BadRequest(format!("Unreadable zip entry: {}", err)))?;
+        // Small archives can expand enormously; stop before holding it all in memory
+        total_bytes += data.len();
+        if total_bytes > max_archive_bytes() {
+            return Err(AppError::PayloadTooLarge(format!(
+                "The archive's files come to over the limit of {} bytes uncompressed",
+                max_archive_bytes()
+            )));
+        }
+        if data.len() > max_bytes {
+            files.push((file_name, Err(body_too_large_message(max_bytes, None))));
+        } else {
+            files.push((file_name, Ok(data)));
+        }
+    }
+    if files.is_empty() {
+        return Err(AppError::BadRequest(
+            "The archive holds no files".to_string(),
+        ));
+    }
+    Ok(files)
+}
+
+/// Makes an archive file into a document ready to save. GeoJSON files become
+/// untimed documents named after the file, holding their features; GPX and CSV files
+/// become documents with a marker following each track or series (see
+/// `point_series_document`).
+pub(crate) fn prepare_file(file_name: &str, data: Vec<u8>) -> Result<PreparedDocument, AppError> {
+    match ImportFormat::from_file_name(file_name) {
+        Some(ImportFormat::Protobuf) => PreparedDocument::from_upload(&Bytes::from(data)),
+        Some(ImportFormat::GeoJson) => {
+            let value: serde_json::Value = serde_json::from_slice(&data)
+                .map_err(|err| AppError::BadRequest(format!("Invalid JSON: {}", err)))?;
+            let polygons = geco_core::geojson::import_features(&value, GEOJSON_RADIUS)
+                .map_err(AppError::BadRequest)?;
+            document_from_features(file_name, 0, polygons)
+        }
+        Some(ImportFormat::Gpx) => gpx_document(file_name, &data, DEFAULT_TOTAL_FRAMES),
+        Some(ImportFormat::Csv) => point_series_document(file_name, &data, DEFAULT_TOTAL_FRAMES),
+        None => Err(AppError::BadRequest(
+            "Only .pb, .geojson, .gpx and .csv files can be imported".to_string(),
+        )),
+    }
+}
+
+/// A document with a marker following each track of a GPX file, named after the file.
+/// The tracks play over `total_frames` on one clock.
+pub(crate) fn gpx_document(
+    file_name: &str,
+    data: &[u8],
+    total_frames: i32,
+) -> Result<PreparedDocument, AppError> {
+    let xml = std::str::from_utf8(data)
+        .map_err(|_| AppError::BadRequest("GPX files must be UTF-8".to_string()))?;
+    let polygons = geco_core::gpx::import_tracks(xml, total_frames, GEOJSON_RADIUS)
+        .map_err(AppError::BadRequest)?;
+    document_from_features(file_name, total_frames, polygons)
+}
+
+/// A document with a marker moving through each series in a CSV file, named after the
+/// file. Timestamps are spread over `total_frames`; frame numbers are kept as given.
+pub(crate) fn point_series_document(
+    file_name: &str,
+    data: &[u8],
+    total_frames: i32,
+) -> Result<PreparedDocument, AppError> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| AppError::BadRequest("CSV files must be UTF-8".to_string()))?;
+    let (polygons, total_frames) =
+        geco_core::point_series::import_point_series(text, total_frames, GEOJSON_RADIUS)
+            .map_err(AppError::BadRequest)?;
+    document_from_features(file_name, total_frames, polygons)
+}
+
+/// A new document named after the file it was imported from.
+fn document_from_features(
+    file_name: &str,
+    total_frames: i32,
+    polygons: Vec<Polygon>,
+) -> Result<PreparedDocument, AppError> {
+    let stem = file_name.rsplit('/').next().unwrap_or(file_name);
+    let name = stem.rsplit_once('.').map_or(stem, |(name, _)| name);
+    let animation = MapAnimation {
+        animation_id: uuid::Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        total_frames,
+        polygons,
+        grid_layers: vec![],
+        camera_keyframes: vec![],
+        data_series: vec![],
+        scenes: vec![],
+        format_version: CURRENT_FORMAT_VERSION,
+    };
+    PreparedDocument::from_upload(&Bytes::from(animation.encode_to_vec()))
+}
+
+/// The frame count a single-file import plays over, 240 unless given.
+fn frames_to_import(total_frames: Option<i32>) -> Result<i32, AppError> {
+    let total_frames = total_frames.unwrap_or(DEFAULT_TOTAL_FRAMES);
+    if !(1..=MAX_FRAME as i32 + 1).contains(&total_frames) {
+        return Err(AppError::BadRequest(format!(
+            "total_frames must be between 1 and {}",
+            MAX_FRAME + 1
+        )));
+    }
+    Ok(total_frames)
+}
+
+/// The name a single-file import is saved under, `default` if none is given.
+fn name_to_import(name: Option<String>, default: &str) -> String {
+    name.map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| default.to_string())
+}
+
+/// Saves a single imported file as a new animation of the user's. Parsing runs on a
+/// blocking thread, before the transaction starts.
+async fn import_file<F>(pool: &DbPool, user_id: i32, prepare: F) -> Result<i32, AppError>
+where
+    F: FnOnce() -> Result<PreparedDocument, AppError> + Send + 'static,
+{
+    let prepared = tokio::task::spawn_blocking(prepare)
+        .await
+        .map_err(|err| AppError::Internal(format!("Import task failed: {}", err)))??;
+    run_in_transaction(pool, |conn| {
+        async move {
+            insert_document(conn, &prepared, Some(user_id), IfMatch::Absent)
+                .await
+                .map(|(id, _)| id)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Why a file couldn't be imported, for errors caused by the file or its sender's
+/// limits. Other errors are passed back, so the job is retried.
+fn file_error(err: AppError) -> Result<String, AppError> {
+    match err {
+        AppError::ProtobufDecode(err) => Ok(format!("Invalid data format: {}", err)),
+        AppError::InvalidDocument(errors) => Ok(klyja_validate::summarize(&errors)),
+        AppError::BadRequest(msg) | AppError::PayloadTooLarge(msg) => Ok(msg),
+        AppError::QuotaExceeded(msg) => Ok(msg),
+        AppError::BodyTooLarge {
+            max_bytes,
+            received_bytes,
+        } => Ok(body_too_large_message(max_bytes, received_bytes)),
+        AppError::LimitExceeded { limit, max } => Ok(limit.describe(max)),
+        err => Err(err),
+    }
+}
+
+/// Imports one archive file as an animation of the user who sent it, recording the
+/// result. Files that can't be imported are marked failed straight away.
+pub async fn run_import_job(pool: &DbPool, entry_id: i32) -> Result<(), AppError> {
+    use crate::schema::{import_entries, imports};
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    let Some((file_name, data, user_id)) = import_entries::table
+        .inner_join(imports::table)
+        .filter(import_entries::id.eq(entry_id))
+        .filter(import_entries::status.eq("pending"))
+        .select((
+            import_entries::file_name,
+            import_entries::data,
+            imports::user_id,
+        ))
+        .first::<(String, Option<Vec<u8>>, i32)>(&mut conn)
+        .await
+        .optional()?
+    else {
+        return Ok(()); // Already imported, or the import was deleted
+    };
+    drop(conn);
+
+    let prepared = match data {
+        Some(data) => {
+            let name = file_name.clone();
+            tokio::task::spawn_blocking(move || prepare_file(&name, data))
+                .await
+                .map_err(|err| AppError::Internal(format!("Import task failed: {}", err)))?
+        }
+        None => Err(AppError::BadRequest("The file's data is gone".to_string())),
+    };
+    let saved = match prepared {
+        Ok(prepared) => {
+            run_in_transaction(pool, |conn| {
+                async move {
+                    insert_document(conn, &prepared, Some(user_id), IfMatch::Absent)
+                        .await
+                        .map(|(id, _)| id)
+                }
+                .scope_boxed()
+            })
+            .await
+        }
+        Err(err) => Err(err),
+    };
+    let (status, animation_id, error) = match saved {
+        Ok(animation_id) => ("done", Some(animation_id), None),
+        Err(err) => ("failed", None, Some(file_error(err)?)),
+    };
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    diesel::update(import_entries::table.find(entry_id))
+        .set((
+            import_entries::status.eq(status),
+            import_entries::animation_id.eq(animation_id),
+            import_entries::error.eq(&error),
+            import_entries::data.eq(None::<Vec<u8>>),
+            import_entries::finished_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .await?;
+    tracing::info!(
+        "JOB: Imported {} for user {}: {}",
+        file_name,
+        user_id,
+        error.as_deref().unwrap_or(status)
+    );
+    Ok(())
+}
+
+/// Marks an archive file failed once its job has used up its attempts.
+pub async fn mark_import_failed(pool: &DbPool, entry_id: i32, error: &str) -> Result<(), AppError> {
+    use crate::schema::import_entries;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    diesel::update(import_entries::table.find(entry_id))
+        .set((
+            import_entries::status.eq("failed"),
+            import_entries::error.eq(error),
+            import_entries::data.eq(None::<Vec<u8>>),
+            import_entries::finished_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+pub struct ImportService;
+
+impl ImportService {
+    /// Starts importing every file in a zip as an animation of the caller's, queuing a
+    /// job per file. Files that can't be imported are reported failed from the start.
+    pub async fn import_archive_logic(
+        pool: &DbPool,
+        user_id: i32,
+        archive: Bytes,
+    ) -> Result<ArchiveImport, AppError> {
+        let files = tokio::task::spawn_blocking(move || read_archive(&archive))
+            .await
+            .map_err(|err| AppError::Internal(format!("Reading archive failed: {}", err)))??;
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        let import_id = conn
+            .transaction::<_, AppError, _>(|conn| {
+                async move {
+                    use crate::schema::{import_entries, imports};
+
+                    let import_id = diesel::insert_into(imports::table)
+                        .values(imports::user_id.eq(user_id))
+                        .returning(imports::id)
+                        .get_result::<i32>(conn)
+                        .await?;
+                    for (file_name, data) in files {
+                        let (data, error) = match data {
+                            Ok(data) => (Some(data), None),
+                            Err(error) => (None, Some(error)),
+                        };
+                        let status = if error.is_some() { "failed" } else { "pending" };
+                        let entry_id = diesel::insert_into(import_entries::table)
+                            .values((
+                                import_entries::import_id.eq(import_id),
+                                import_entries::file_name.eq(&file_name),
+                                import_entries::status.eq(status),
+                                import_entries::data.eq(&data),
+                                import_entries::error.eq(&error),
+                                import_entries::finished_at
+                                    .eq(error.is_some().then(|| chrono::Utc::now().naive_utc())),
+                            ))
+                            .returning(import_entries::id)
+                            .get_result::<i32>(conn)
+                            .await?;
+                        if data.is_some() {
+                            let job_id =
+                                jobs::enqueue(conn, &Job::ImportFile { entry_id }, None).await?;
+                            diesel::update(import_entries::table.find(entry_id))
+                                .set(import_entries::job_id.eq(job_id))
+                                .execute(conn)
+                                .await?;
+                        }
+                    }
+                    Ok(import_id)
+                }
+                .scope_boxed()
+            })
+            .await?;
+        drop(conn);
+
+        tracing::info!("SERVICE: User {} started import {}", user_id, import_id);
+        Self::import_status_logic(pool, import_id, user_id).await
+    }
+
+    /// Imports a CSV point series as a new animation of the caller's, named `name` or
+    /// "points". Timestamps are spread over `total_frames` (240 by default) frames;
+    /// frame numbers are kept as given. Returns the new animation's ID.
+    pub async fn import_csv_logic(
+        pool: &DbPool,
+        user_id: i32,
+        name: Option<String>,
+        total_frames: Option<i32>,
+        csv: Bytes,
+    ) -> Result<i32, AppError> {
+        let total_frames = frames_to_import(total_frames)?;
+        let name = name_to_import(name, "points");
+        let animation_id = import_file(pool, user_id, move || {
+            point_series_document(&format!("{}.csv", name), &csv, total_frames)
+        })
+        .await?;
+        tracing::info!(
+            "SERVICE: User {} imported a CSV point series as animation {}",
+            user_id,
+            animation_id
+        );
+        Ok(animation_id)
+    }
+
+    /// Imports the tracks of a GPX file as a new animation of the caller's, named
+    /// `name` or "tracks", playing over `total_frames` (240 by default) frames.
+    /// Returns the new animation's ID.
+    pub async fn import_gpx_logic(
+        pool: &DbPool,
+        user_id: i32,
+        name: Option<String>,
+        total_frames: Option<i32>,
+        gpx: Bytes,
+    ) -> Result<i32, AppError> {
+        let total_frames = frames_to_import(total_frames)?;
+        let name = name_to_import(name, "tracks");
+        let animation_id = import_file(pool, user_id, move || {
+            gpx_document(&format!("{}.gpx", name), &gpx, total_frames)
+        })
+        .await?;
+        tracing::info!(
+            "SERVICE: User {} imported GPX tracks as animation {}",
+            user_id,
+            animation_id
+        );
+        Ok(animation_id)
+    }
+
+    /// Reports each file of an import; only its sender may see it.
+    pub async fn import_status_logic(
+        pool: &DbPool,
+        import_id: i32,
+        user_id: i32,
+    ) -> Result<ArchiveImport, AppError> {
+        use crate::schema::{import_entries, imports};
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        let (owner, created_at) = imports::table
+            .find(import_id)
+            .select((imports::user_id, imports::created_at))
+            .first::<(i32, NaiveDateTime)>(&mut conn)
+            .await
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Import {} not found", import_id)))?;
+        if owner != user_id {
+            return Err(AppError::Forbidden(format!(
+                "Import {} belongs to another user",
+                import_id
+            )));
+        }
+        let files = import_entries::table
+            .filter(import_entries::import_id.eq(import_id))
+            .order(import_entries::id.asc())
+            .select(ImportedFile::as_select())
+            .load::<ImportedFile>(&mut conn)
+            .await?;
+
+        let count = |status: &str| files.iter().filter(|file| file.status == status).count();
+        Ok(ArchiveImport {
+            id: import_id,
+            done: count("pending") == 0,
+            imported: count("done"),
+            failed: count("failed"),
+            files,
+            created_at,
+        })
+    }
+}
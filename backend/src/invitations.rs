@@ -0,0 +1,180 @@
+// klyja/backend/src/invitations.rs
+// Invitations: owners share an animation with an email address or username, which
+// needn't belong to anyone yet. The invitee signs in (or registers with that email)
+// and accepts with the invitation's token, becoming a collaborator. Like password
+// resets, Klyja doesn't send mail: the token goes back to the owner.
+use crate::{
+    accounts::{normalize_email, random_token},
+    errors::AppError,
+    models::{
+        AcceptInvitationRequest, AcceptedInvitation, CollaboratorRole, CreateInvitationRequest,
+        CreatedInvitation, Invitation,
+    },
+    services::{content_sha256, require_access, run_in_transaction, AnimationAccess},
+    DbPool,
+};
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::RunQueryDsl;
+
+/// How long an invitation can be accepted.
+pub const INVITATION_TTL_DAYS: i32 = 7;
+
+/// Emails are matched lowercased; anything without an `@` is taken as a username.
+fn normalize_invitee(invitee: &str) -> Result<String, AppError> {
+    let invitee = invitee.trim();
+    if invitee.contains('@') {
+        return normalize_email(invitee);
+    }
+    if invitee.is_empty() || invitee.chars().count() > 255 {
+        return Err(AppError::BadRequest(
+            "Invite someone by email or by a username of at most 255 characters".to_string(),
+        ));
+    }
+    Ok(invitee.to_string())
+}
+
+pub struct InvitationService;
+
+impl InvitationService {
+    /// Invites someone to collaborate on an animation; only the owner may. Inviting
+    /// the same person again replaces their pending invitation.
+    pub async fn create_invitation_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: i32,
+        request: CreateInvitationRequest,
+    ) -> Result<CreatedInvitation, AppError> {
+        let invitee = normalize_invitee(&request.invitee)?;
+        let role = request.role;
+        let token = random_token();
+        let token_sha256 = content_sha256(token.as_bytes());
+
+        let details = run_in_transaction(pool, |conn| {
+            async move {
+                require_access(conn, animation_id, Some(caller_id), AnimationAccess::Owner).await?;
+                use crate::schema::invitations;
+
+                diesel::delete(
+                    invitations::table
+                        .filter(invitations::animation_id.eq(animation_id))
+                        .filter(invitations::invitee.eq(&invitee)),
+                )
+                .execute(conn)
+                .await?;
+                let details = diesel::insert_into(invitations::table)
+                    .values((
+                        invitations::token_sha256.eq(&token_sha256),
+                        invitations::animation_id.eq(animation_id),
+                        invitations::invited_by.eq(caller_id),
+                        invitations::invitee.eq(&invitee),
+                        invitations::role.eq(role.as_str()),
+                        invitations::expires_at.eq(now + INVITATION_TTL_DAYS.days()),
+                    ))
+                    .returning(Invitation::as_returning())
+                    .get_result::<Invitation>(conn)
+                    .await?;
+                Ok(details)
+            }
+            .scope_boxed()
+        })
+        .await?;
+        tracing::info!(
+            "SERVICE: Invitation {} to {} for animation {} as {}",
+            details.id,
+            details.invitee,
+            animation_id,
+            role.as_str()
+        );
+        Ok(CreatedInvitation { token, details })
+    }
+
+    /// Accepts an invitation for the caller, who must be the user it was sent to,
+    /// making them a collaborator. The invitation can't be used again.
+    pub async fn accept_invitation_logic(
+        pool: &DbPool,
+        caller_id: i32,
+        request: AcceptInvitationRequest,
+    ) -> Result<AcceptedInvitation, AppError> {
+        let token_sha256 = content_sha256(request.token.trim().as_bytes());
+        run_in_transaction(pool, |conn| {
+            async move {
+                use crate::schema::{animation_collaborators, animations, invitations, users};
+
+                let invitation = invitations::table
+                    .filter(invitations::token_sha256.eq(&token_sha256))
+                    .filter(invitations::expires_at.gt(now))
+                    .select(Invitation::as_select())
+                    .for_update()
+                    .first::<Invitation>(conn)
+                    .await
+                    .optional()?
+                    .ok_or_else(|| {
+                        AppError::BadRequest("Invitation is invalid or expired".to_string())
+                    })?;
+                let (username, email) = users::table
+                    .find(caller_id)
+                    .select((users::username, users::email))
+                    .first::<(String, Option<String>)>(conn)
+                    .await?;
+                let invited = if invitation.invitee.contains('@') {
+                    email.as_deref() == Some(invitation.invitee.as_str())
+                } else {
+                    username == invitation.invitee
+                };
+                if !invited {
+                    return Err(AppError::Forbidden(
+                        "This invitation is for someone else".to_string(),
+                    ));
+                }
+                let owner_id = animations::table
+                    .find(invitation.animation_id)
+                    .select(animations::owner_id)
+                    .first::<Option<i32>>(conn)
+                    .await?;
+                if owner_id == Some(caller_id) {
+                    return Err(AppError::BadRequest(
+                        "The owner can't also be a collaborator".to_string(),
+                    ));
+                }
+                let role = CollaboratorRole::parse(&invitation.role).ok_or_else(|| {
+                    AppError::Internal(format!(
+                        "Invitation {} has unknown role '{}'",
+                        invitation.id, invitation.role
+                    ))
+                })?;
+
+                diesel::insert_into(animation_collaborators::table)
+                    .values((
+                        animation_collaborators::animation_id.eq(invitation.animation_id),
+                        animation_collaborators::user_id.eq(caller_id),
+                        animation_collaborators::role.eq(role.as_str()),
+                    ))
+                    .on_conflict((
+                        animation_collaborators::animation_id,
+                        animation_collaborators::user_id,
+                    ))
+                    .do_update()
+                    .set(animation_collaborators::role.eq(role.as_str()))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(invitations::table.find(invitation.id))
+                    .execute(conn)
+                    .await?;
+                tracing::info!(
+                    "SERVICE: User {} accepted invitation {} to animation {}",
+                    caller_id,
+                    invitation.id,
+                    invitation.animation_id
+                );
+                Ok(AcceptedInvitation {
+                    animation_id: invitation.animation_id,
+                    role,
+                })
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
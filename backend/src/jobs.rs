@@ -0,0 +1,451 @@
+// klyja/backend/src/jobs.rs
+// Persistent background work: jobs are rows in `jobs`, claimed by worker loops with
+// SKIP LOCKED so several workers (or servers) can share the queue. Failed jobs are
+// retried with backoff; jobs whose worker died are picked up again once stale.
+use crate::{
+    backups,
+    errors::AppError,
+    exports, imports,
+    models::{JobInfo, WebhookEvent},
+    publishing,
+    services::{require_access, AnimationAccess},
+    thumbnails, upgrades, webhooks, DbPool,
+};
+use chrono::NaiveDateTime;
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long an idle worker waits before looking for new jobs.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Attempts a job gets before it is marked failed.
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 3;
+/// Running jobs untouched for this long are assumed abandoned and run again.
+const STALE_AFTER_MINUTES: i32 = 15;
+/// How often a running job's claim is refreshed, well within STALE_AFTER_MINUTES.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+/// Time between purges of expired sessions, uploads, exports and finished jobs.
+const PURGE_EVERY_MINUTES: i32 = 60;
+const UPLOAD_TTL_HOURS: i32 = 24;
+const FINISHED_TTL_DAYS: i32 = 7;
+
+/// A unit of background work, stored as the job's JSON payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Job {
+    /// Draws the animation's current first frame as its thumbnail.
+    RenderThumbnail { animation_id: i32 },
+    /// Renders the video file for a row of `export_jobs`.
+    ExportVideo { export_id: i32 },
+    /// Writes the GeoJSON frames zip for a row of `export_jobs`.
+    ExportFrames { export_id: i32 },
+    /// Deletes expired sessions, uploads, exports and finished jobs, then schedules the next purge.
+    Purge,
+    /// Tells one webhook about a change to an animation.
+    DeliverWebhook {
+        webhook_id: i32,
+        event: WebhookEvent,
+        animation_id: i32,
+        version: i32,
+    },
+    /// Publishes an animation at its scheduled time, if it is still scheduled for then.
+    PublishAnimation { animation_id: i32 },
+    /// Writes every animation to the zip named by a row of `backups`.
+    Backup { backup_id: i32 },
+    /// Adds the animations in the zip named by a row of `backups`.
+    Restore { backup_id: i32 },
+    /// Makes one file of an archive import into an animation.
+    ImportFile { entry_id: i32 },
+    /// Rewrites the documents stored in an older format in the current one.
+    UpgradeDocuments,
+}
+
+impl Job {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Job::RenderThumbnail { .. } => "render_thumbnail",
+            Job::ExportVideo { .. } => "export_video",
+            Job::ExportFrames { .. } => "export_frames",
+            Job::Purge => "purge",
+            Job::DeliverWebhook { .. } => "deliver_webhook",
+            Job::PublishAnimation { .. } => "publish_animation",
+            Job::Backup { .. } => "backup",
+            Job::Restore { .. } => "restore",
+            Job::ImportFile { .. } => "import_file",
+            Job::UpgradeDocuments => "upgrade_documents",
+        }
+    }
+
+    async fn run(&self, pool: &DbPool) -> Result<(), AppError> {
+        match *self {
+            Job::RenderThumbnail { animation_id } => {
+                thumbnails::render_and_store(pool, animation_id).await
+            }
+            Job::ExportVideo { export_id } | Job::ExportFrames { export_id } => {
+                exports::run_export_job(pool, export_id).await
+            }
+            Job::Purge => purge(pool).await,
+            Job::DeliverWebhook {
+                webhook_id,
+                event,
+                animation_id,
+                version,
+            } => webhooks::deliver(pool, webhook_id, event, animation_id, version).await,
+            Job::PublishAnimation { animation_id } => {
+                publishing::publish_if_due(pool, animation_id).await
+            }
+            Job::Backup { backup_id } => backups::run_backup_job(pool, backup_id).await,
+            Job::Restore { backup_id } => backups::run_restore_job(pool, backup_id).await,
+            Job::ImportFile { entry_id } => imports::run_import_job(pool, entry_id).await,
+            Job::UpgradeDocuments => upgrades::upgrade_documents(pool).await.map(|_| ()),
+        }
+    }
+
+    /// Called once the last attempt has failed.
+    async fn give_up(&self, pool: &DbPool, error: &str) -> Result<(), AppError> {
+        match *self {
+            Job::ExportVideo { export_id } | Job::ExportFrames { export_id } => {
+                exports::mark_export_failed(pool, export_id, error).await
+            }
+            Job::Backup { backup_id } | Job::Restore { backup_id } => {
+                backups::mark_backup_failed(pool, backup_id, error).await
+            }
+            Job::ImportFile { entry_id } => {
+                imports::mark_import_failed(pool, entry_id, error).await
+            }
+            Job::RenderThumbnail { .. }
+            | Job::Purge
+            | Job::DeliverWebhook { .. }
+            | Job::PublishAnimation { .. }
+            | Job::UpgradeDocuments => Ok(()),
+        }
+    }
+}
+
+/// Queues a job to run as soon as a worker is free. `animation_id` ties it to an
+/// animation, so it is dropped with it and visible to whoever can view it.
+/// Call it in the transaction that creates the work, so neither exists without the other.
+pub async fn enqueue(
+    conn: &mut AsyncPgConnection,
+    job: &Job,
+    animation_id: Option<i32>,
+) -> Result<i32, AppError> {
+    use crate::schema::jobs;
+
+    let payload = serde_json::to_value(job)
+        .map_err(|err| AppError::Internal(format!("Serializing job failed: {}", err)))?;
+    diesel::insert_into(jobs::table)
+        .values((
+            jobs::kind.eq(job.kind()),
+            jobs::payload.eq(payload),
+            jobs::animation_id.eq(animation_id),
+            jobs::max_attempts.eq(DEFAULT_MAX_ATTEMPTS),
+        ))
+        .returning(jobs::id)
+        .get_result::<i32>(conn)
+        .await
+        .map_err(AppError::DatabaseQuery)
+}
+
+/// Queues a job that waits until `run_at` (UTC) before a worker picks it up.
+/// Like `enqueue`, call it in the transaction that creates the work.
+pub async fn enqueue_at(
+    conn: &mut AsyncPgConnection,
+    job: &Job,
+    animation_id: Option<i32>,
+    run_at: NaiveDateTime,
+) -> Result<i32, AppError> {
+    use crate::schema::jobs;
+
+    let job_id = enqueue(conn, job, animation_id).await?;
+    diesel::update(jobs::table.find(job_id))
+        .set(jobs::run_at.eq(run_at))
+        .execute(conn)
+        .await?;
+    Ok(job_id)
+}
+
+/// Starts `count` worker loops, first making sure a purge is scheduled.
+pub fn spawn_workers(pool: DbPool, count: usize) {
+    let pool_clone = pool.clone();
+    tokio::spawn(async move {
+        if let Err(err) = schedule_purge(&pool_clone, 0).await {
+            tracing::error!("JOB: Scheduling purge failed: {:?}", err);
+        }
+        if let Err(err) = upgrades::schedule_upgrade(&pool_clone).await {
+            tracing::error!("JOB: Scheduling document upgrade failed: {:?}", err);
+        }
+    });
+    for _ in 0..count {
+        tokio::spawn(worker_loop(pool.clone()));
+    }
+}
+
+async fn worker_loop(pool: DbPool) {
+    loop {
+        let pool_clone = pool.clone();
+        // A task of its own, so a panicking job doesn't take the worker down with it
+        let ran_job = tokio::spawn(async move { run_next_job(&pool_clone).await }).await;
+        match ran_job {
+            Ok(Ok(true)) => continue, // There may be more waiting
+            Ok(Ok(false)) => {}
+            Ok(Err(err)) => tracing::error!("JOB: Worker error: {:?}", err),
+            Err(join_err) => tracing::error!("JOB: Worker panicked: {}", join_err),
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Claims and runs one due job. Returns whether there was one.
+pub async fn run_next_job(pool: &DbPool) -> Result<bool, AppError> {
+    use crate::schema::jobs;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    let claimed = conn
+        .transaction::<_, AppError, _>(|conn| {
+            async move {
+                let due = jobs::status
+                    .eq("pending")
+                    .and(jobs::run_at.le(now))
+                    .or(jobs::status.eq("running").and(
+                        jobs::started_at.lt((now - STALE_AFTER_MINUTES.minutes()).nullable()),
+                    ));
+                let next = jobs::table
+                    .filter(due)
+                    .order((jobs::run_at.asc(), jobs::id.asc()))
+                    .select((jobs::id, jobs::payload, jobs::attempts, jobs::max_attempts))
+                    .for_update()
+                    .skip_locked()
+                    .first::<(i32, serde_json::Value, i32, i32)>(conn)
+                    .await
+                    .optional()?;
+                if let Some((id, ..)) = next {
+                    diesel::update(jobs::table.find(id))
+                        .set((
+                            jobs::status.eq("running"),
+                            jobs::attempts.eq(jobs::attempts + 1),
+                            jobs::started_at.eq(now),
+                        ))
+                        .execute(conn)
+                        .await?;
+                }
+                Ok(next)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    // Jobs use connections of their own; take one back only to record the outcome
+    drop(conn);
+    let Some((job_id, payload, attempts, max_attempts)) = claimed else {
+        return Ok(false);
+    };
+    let attempt = attempts + 1;
+
+    let job = serde_json::from_value::<Job>(payload)
+        .map_err(|err| format!("Unreadable job payload: {}", err));
+    let result = match &job {
+        Ok(job) => {
+            tracing::debug!(
+                "JOB: Running job {} ({:?}), attempt {}",
+                job_id,
+                job,
+                attempt
+            );
+            // Keep the claim fresh while the job runs, so a long one isn't taken for
+            // abandoned and started a second time
+            tokio::select! {
+                result = job.run(pool) => result,
+                never = keep_claimed(pool, job_id) => match never {},
+            }
+        }
+        Err(msg) => Err(AppError::Internal(msg.clone())),
+    };
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+
+    match result {
+        Ok(()) => {
+            diesel::update(jobs::table.find(job_id))
+                .set((
+                    jobs::status.eq("done"),
+                    jobs::last_error.eq(None::<String>),
+                    jobs::finished_at.eq(now),
+                ))
+                .execute(&mut conn)
+                .await?;
+        }
+        Err(err) if attempt < max_attempts => {
+            let error = error_message(&err);
+            // Back off 10s, 20s, 40s, ... between attempts
+            let delay_seconds = 10 * 2_i32.pow(attempt as u32 - 1);
+            tracing::warn!(
+                "JOB: Job {} failed (attempt {}), retrying in {}s: {}",
+                job_id,
+                attempt,
+                delay_seconds,
+                error
+            );
+            diesel::update(jobs::table.find(job_id))
+                .set((
+                    jobs::status.eq("pending"),
+                    jobs::last_error.eq(&error),
+                    jobs::run_at.eq(now + delay_seconds.seconds()),
+                ))
+                .execute(&mut conn)
+                .await?;
+        }
+        Err(err) => {
+            let error = error_message(&err);
+            tracing::error!("JOB: Job {} failed for good: {}", job_id, error);
+            if let Ok(job) = &job {
+                job.give_up(pool, &error).await?;
+            }
+            diesel::update(jobs::table.find(job_id))
+                .set((
+                    jobs::status.eq("failed"),
+                    jobs::last_error.eq(&error),
+                    jobs::finished_at.eq(now),
+                ))
+                .execute(&mut conn)
+                .await?;
+        }
+    }
+    Ok(true)
+}
+
+/// Refreshes a running job's `started_at` every HEARTBEAT_INTERVAL. Never returns;
+/// it stops when dropped with the job, or with the worker if that dies.
+async fn keep_claimed(pool: &DbPool, job_id: i32) -> std::convert::Infallible {
+    use crate::schema::jobs;
+
+    loop {
+        tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+        let refreshed = match pool.get().await {
+            Ok(mut conn) => diesel::update(jobs::table.find(job_id))
+                .filter(jobs::status.eq("running"))
+                .set(jobs::started_at.eq(now))
+                .execute(&mut conn)
+                .await
+                .map_err(AppError::DatabaseQuery),
+            Err(err) => Err(AppError::DatabasePool(err)),
+        };
+        if let Err(err) = refreshed {
+            tracing::warn!("JOB: Refreshing job {} failed: {:?}", job_id, err);
+        }
+    }
+}
+
+/// The text stored as a job's error: the message of internal errors, else the debug form.
+pub fn error_message(err: &AppError) -> String {
+    match err {
+        AppError::Internal(msg) => msg.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Queues a purge `delay_minutes` from now unless one is already waiting.
+async fn schedule_purge(pool: &DbPool, delay_minutes: i32) -> Result<(), AppError> {
+    use crate::schema::jobs;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    conn.transaction::<_, AppError, _>(|conn| {
+        async move {
+            let waiting = diesel::select(diesel::dsl::exists(
+                jobs::table
+                    .filter(jobs::kind.eq(Job::Purge.kind()))
+                    .filter(jobs::status.eq("pending")),
+            ))
+            .get_result::<bool>(conn)
+            .await?;
+            if !waiting {
+                let job_id = enqueue(conn, &Job::Purge, None).await?;
+                diesel::update(jobs::table.find(job_id))
+                    .set(jobs::run_at.eq(now + delay_minutes.minutes()))
+                    .execute(conn)
+                    .await?;
+            }
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Deletes expired sessions, reset tokens, invitations and uploads, and exports and
+/// jobs finished long enough ago, then makes sure the next purge is scheduled. Runs
+/// as a job, and from `klyja-admin purge` when an operator wants it done now.
+pub async fn purge(pool: &DbPool) -> Result<(), AppError> {
+    use crate::schema::{export_jobs, invitations, jobs, password_resets, sessions, uploads};
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    // Expired sessions, reset tokens and invitations are already refused; this stops them piling up
+    let sessions_purged = diesel::delete(sessions::table.filter(sessions::expires_at.lt(now)))
+        .execute(&mut conn)
+        .await?;
+    diesel::delete(password_resets::table.filter(password_resets::expires_at.lt(now)))
+        .execute(&mut conn)
+        .await?;
+    diesel::delete(invitations::table.filter(invitations::expires_at.lt(now)))
+        .execute(&mut conn)
+        .await?;
+    let uploads_purged = diesel::delete(
+        uploads::table.filter(uploads::created_at.lt(now - UPLOAD_TTL_HOURS.hours())),
+    )
+    .execute(&mut conn)
+    .await?;
+    let exports_purged = diesel::delete(
+        export_jobs::table
+            .filter(export_jobs::finished_at.lt((now - FINISHED_TTL_DAYS.days()).nullable())),
+    )
+    .execute(&mut conn)
+    .await?;
+    let jobs_purged = diesel::delete(
+        jobs::table
+            .filter(jobs::status.eq_any(["done", "failed"]))
+            .filter(jobs::finished_at.lt((now - FINISHED_TTL_DAYS.days()).nullable())),
+    )
+    .execute(&mut conn)
+    .await?;
+    tracing::info!(
+        "JOB: Purged {} sessions, {} uploads, {} exports and {} jobs",
+        sessions_purged,
+        uploads_purged,
+        exports_purged,
+        jobs_purged
+    );
+
+    drop(conn);
+    schedule_purge(pool, PURGE_EVERY_MINUTES).await
+}
+
+pub struct JobService;
+
+impl JobService {
+    /// Reports a job's progress. Jobs about an animation are visible to anyone who
+    /// can view it; internal ones (purges) and webhook deliveries, whose errors are
+    /// the owner's business, aren't exposed.
+    pub async fn job_status_logic(
+        pool: &DbPool,
+        job_id: i32,
+        caller_id: Option<i32>,
+    ) -> Result<JobInfo, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::jobs;
+
+        let job = jobs::table
+            .find(job_id)
+            .select(JobInfo::as_select())
+            .first::<JobInfo>(&mut conn)
+            .await
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Job {} not found", job_id)))?;
+        let Some(animation_id) = job.animation_id.filter(|_| job.kind != "deliver_webhook") else {
+            return Err(AppError::NotFound(format!("Job {} not found", job_id)));
+        };
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::View).await?;
+        Ok(job)
+    }
+}
@@ -1,19 +1,48 @@
 // klyja/backend/src/lib.rs
 
-// Include Generated Protobuf Code
-pub mod protobuf_gen {
-    include!(concat!(env!("OUT_DIR"), "/klyja.map_animation.v1.rs"));
-}
+// Generated protobuf code, shared with geco
+pub use klyja_proto as protobuf_gen;
 
+pub mod accounts;
+pub mod admin; // Only for the klyja-admin binary
+pub mod app;
+pub mod auth;
+pub mod backups;
+pub mod body_limit;
+pub mod cache;
 pub mod db;
+pub mod devices;
+pub mod drafts;
+pub mod embeds;
 pub mod errors;
+pub mod exports;
+pub mod folders;
+pub mod grpc;
 pub mod handlers;
+pub mod i18n;
+pub mod imports;
+pub mod invitations;
+pub mod jobs;
+pub mod maintenance;
 pub mod models;
+pub mod moderation;
+pub mod presence;
+pub mod publishing;
+pub mod request_id;
 pub mod schema; // Will be generated by diesel print-schema
 pub mod services;
+pub mod stats;
+pub mod templates;
+pub mod throttle;
+pub mod thumbnails;
+pub mod timeouts;
+pub mod tokens;
+pub mod upgrades;
+pub mod uploads;
+pub mod webhooks;
 
 // Define a type alias for the connection pool
-pub type DbPool = r2d2::Pool<diesel::r2d2::ConnectionManager<diesel::PgConnection>>;
+pub use db::DbPool;
 
 // We'll add some basic unit tests here
 #[cfg(test)]
@@ -63,6 +92,24 @@ mod tests {
             protobuf_data: vec![1, 2, 3, 4],
             created_at: now,
             updated_at: now,
+            owner_id: None,
+            content_sha256: None,
+            lock_version: 1,
+            animation_uuid: None,
+            description: None,
+            license: None,
+            source_attribution: None,
+            hidden_at: None,
+            folder_id: None,
+            published_at: None,
+            publish_at: None,
+            created_by: None,
+            last_edited_by: None,
+            encoded_bytes: 4,
+            feature_count: None,
+            point_count: None,
+            keyframe_count: None,
+            format_version: 0,
         };
         
         let json = serde_json::to_string(&animation).expect("Failed to serialize Animation");
@@ -72,6 +119,752 @@ mod tests {
         assert!(!json.contains("protobuf_data")); // Skipped in serialization
     }
     
+    #[test]
+    fn test_escape_like_escapes_wildcards() {
+        use crate::services::escape_like;
+
+        assert_eq!(escape_like("coast"), "coast");
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+    }
+    
+    #[test]
+    fn test_animation_access_resolution() {
+        use crate::models::CollaboratorRole;
+        use crate::services::AnimationAccess;
+
+        // Unowned animations are readable by anyone, editable by no one
+        assert_eq!(
+            AnimationAccess::resolve(None, None, None),
+            AnimationAccess::View
+        );
+        assert_eq!(
+            AnimationAccess::resolve(None, Some(1), None),
+            AnimationAccess::View
+        );
+
+        assert_eq!(
+            AnimationAccess::resolve(Some(1), Some(1), None),
+            AnimationAccess::Owner
+        );
+        assert_eq!(
+            AnimationAccess::resolve(Some(1), None, None),
+            AnimationAccess::None
+        );
+        assert_eq!(
+            AnimationAccess::resolve(Some(1), Some(2), None),
+            AnimationAccess::None
+        );
+        assert_eq!(
+            AnimationAccess::resolve(Some(1), Some(2), Some(CollaboratorRole::Viewer)),
+            AnimationAccess::View
+        );
+        assert_eq!(
+            AnimationAccess::resolve(Some(1), Some(2), Some(CollaboratorRole::Editor)),
+            AnimationAccess::Edit
+        );
+
+        // Publishing lets anyone read an animation without taking anything away
+        assert_eq!(
+            AnimationAccess::None.with_publication(true),
+            AnimationAccess::View
+        );
+        assert_eq!(
+            AnimationAccess::Edit.with_publication(true),
+            AnimationAccess::Edit
+        );
+        assert_eq!(
+            AnimationAccess::None.with_publication(false),
+            AnimationAccess::None
+        );
+        assert!(AnimationAccess::Owner > AnimationAccess::Edit);
+        assert!(AnimationAccess::Edit > AnimationAccess::View);
+    }
+
+    #[test]
+    fn test_collaborator_role_round_trip() {
+        use crate::models::CollaboratorRole;
+
+        for role in [CollaboratorRole::Viewer, CollaboratorRole::Editor] {
+            assert_eq!(CollaboratorRole::parse(role.as_str()), Some(role));
+            let json = serde_json::to_string(&role).unwrap();
+            assert_eq!(json, format!("\"{}\"", role.as_str()));
+        }
+        assert_eq!(CollaboratorRole::parse("owner"), None);
+    }
+
+    #[test]
+    fn test_thumbnail_url_changes_with_upload_time() {
+        use crate::services::thumbnail_url;
+
+        let uploaded = chrono::DateTime::from_timestamp(1_715_085_300, 0)
+            .unwrap()
+            .naive_utc();
+        assert_eq!(
+            thumbnail_url(101, uploaded),
+            "/api/animation/101/thumbnail?v=1715085300"
+        );
+        assert_ne!(
+            thumbnail_url(101, uploaded),
+            thumbnail_url(101, uploaded + chrono::Duration::seconds(1))
+        );
+    }
+
+    #[test]
+    fn test_encode_png_layout() {
+        use crate::thumbnails::encode_png;
+        use geco_core::raster::Raster;
+
+        let raster = Raster::new(3, 2, [10, 20, 30, 255]);
+        let png = encode_png(&raster);
+
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..20], &3u32.to_be_bytes());
+        assert_eq!(&png[20..24], &2u32.to_be_bytes());
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+
+        // IDAT holds each row behind a filter byte
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let scanlines =
+            miniz_oxide::inflate::decompress_to_vec_zlib(&png[41..41 + idat_len]).unwrap();
+        assert_eq!(scanlines.len(), 2 * (1 + 3 * 4));
+        assert_eq!(&scanlines[..5], &[0, 10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn test_render_thumbnail_is_png() {
+        use crate::services::MAX_THUMBNAIL_BYTES;
+        use crate::thumbnails::{render_thumbnail, THUMBNAIL_SIZE};
+
+        let png = render_thumbnail(&MapAnimation::default());
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!(&png[16..20], &THUMBNAIL_SIZE.to_be_bytes());
+        // Server renders must pass the same checks as uploads
+        assert!(png.len() <= MAX_THUMBNAIL_BYTES);
+    }
+
+    #[test]
+    fn test_stored_documents_round_trip() {
+        use crate::services::{compress_document, decompress_document};
+        use prost::Message;
+
+        let document = MapAnimation {
+            name: "Pangaea".to_string(),
+            total_frames: 120,
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let stored = compress_document(&document).unwrap();
+        assert_ne!(stored, document);
+        assert_eq!(decompress_document(stored).unwrap(), document);
+
+        // Documents stored before compression come back untouched
+        assert_eq!(decompress_document(document.clone()).unwrap(), document);
+        assert!(decompress_document(Vec::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_content_sha256_is_lowercase_hex() {
+        use crate::services::content_sha256;
+
+        assert_eq!(
+            content_sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(content_sha256(&[]).len(), 64);
+    }
+
+    #[test]
+    fn test_embed_tokens_verify_only_as_signed() {
+        use crate::embeds::{sign_embed, verify_embed};
+
+        let key = b"test signing key";
+        let now = chrono::Utc::now();
+        let token = sign_embed(key, 7, 0, now + chrono::Duration::hours(1));
+        assert_eq!(verify_embed(key, &token, now), Some((7, 0)));
+        assert_eq!(
+            verify_embed(key, &token, now + chrono::Duration::hours(2)),
+            None
+        );
+        // Only the key that signed a token verifies it
+        assert_eq!(verify_embed(b"another key", &token, now), None);
+
+        let moved = token.replacen("7.", "8.", 1);
+        assert_eq!(verify_embed(key, &moved, now), None);
+        // A revoked link can't be brought back by naming the current generation
+        let renewed = token.replacen("7.0.", "7.1.", 1);
+        assert_eq!(verify_embed(key, &renewed, now), None);
+        let extended = sign_embed(key, 7, 0, now + chrono::Duration::hours(3));
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let (prefix, _) = extended.rsplit_once('.').unwrap();
+        let spliced = format!("{}.{}", prefix, signature);
+        assert_eq!(verify_embed(key, &spliced, now), None);
+        assert_eq!(verify_embed(key, "7.0.9999999999", now), None);
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_evicts_least_recently_used() {
+        use crate::cache::{AnimationCache, MemoryCache};
+
+        let animation = |id: i32, size: usize| Animation {
+            id,
+            name: format!("Animation {}", id),
+            protobuf_data: vec![0; size],
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+            owner_id: None,
+            content_sha256: None,
+            lock_version: 1,
+            animation_uuid: None,
+            description: None,
+            license: None,
+            source_attribution: None,
+            hidden_at: None,
+            folder_id: None,
+            published_at: None,
+            publish_at: None,
+            created_by: None,
+            last_edited_by: None,
+            encoded_bytes: size as i32,
+            feature_count: None,
+            point_count: None,
+            keyframe_count: None,
+            format_version: 0,
+        };
+        let cache = MemoryCache::new(100);
+        cache.put(animation(1, 40)).await;
+        cache.put(animation(2, 40)).await;
+        assert!(cache.get(1).await.is_some()); // 2 is now the least recently used
+        cache.put(animation(3, 40)).await;
+        assert!(cache.get(2).await.is_none());
+        assert_eq!(cache.get(1).await.unwrap().protobuf_data.len(), 40);
+        assert!(cache.get(3).await.is_some());
+
+        // Too big to ever fit, so not cached at all
+        cache.put(animation(4, 101)).await;
+        assert!(cache.get(4).await.is_none());
+        assert!(cache.get(1).await.is_some());
+
+        cache.invalidate(1).await;
+        assert!(cache.get(1).await.is_none());
+    }
+
+    #[test]
+    fn test_live_rooms_track_presence() {
+        use crate::models::{PresenceMode, PresenceUpdate, SphereCursor};
+        use crate::presence::{join, leave, update};
+
+        let room = |json: String| serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        let (ada, mut ada_updates, _) = join(-1, 1, "ada".to_string());
+        let (grace, _, _) = join(-1, 2, "grace".to_string());
+        let joined = room(ada_updates.try_recv().unwrap());
+        assert_eq!(joined["type"], "presence");
+        assert_eq!(joined["members"].as_array().unwrap().len(), 1);
+        let both = room(ada_updates.try_recv().unwrap());
+        assert_eq!(both["members"][1]["username"], "grace");
+
+        let moved = PresenceUpdate {
+            mode: Some(PresenceMode::Editing),
+            frame: Some(12),
+            cursor: Some(Some(SphereCursor { lat: 10.0, lon: -20.0 })),
+        };
+        assert!(update(-1, grace, moved, false).is_err()); // Viewers can't edit
+        let moved = PresenceUpdate {
+            frame: Some(12),
+            cursor: Some(Some(SphereCursor { lat: 10.0, lon: -20.0 })),
+            ..Default::default()
+        };
+        update(-1, grace, moved, false).unwrap();
+        let after = room(ada_updates.try_recv().unwrap());
+        assert_eq!(after["members"][1]["frame"], 12);
+        assert_eq!(after["members"][1]["cursor"]["lon"], -20.0);
+        let off_sphere = PresenceUpdate {
+            cursor: Some(None),
+            ..Default::default()
+        };
+        update(-1, grace, off_sphere, false).unwrap();
+        let after = room(ada_updates.try_recv().unwrap());
+        assert!(after["members"][1]["cursor"].is_null());
+        assert_eq!(after["members"][1]["frame"], 12);
+        let off_map = PresenceUpdate {
+            cursor: Some(Some(SphereCursor { lat: 91.0, lon: 0.0 })),
+            ..Default::default()
+        };
+        assert!(update(-1, grace, off_map, false).is_err());
+
+        leave(-1, grace);
+        let left = room(ada_updates.try_recv().unwrap());
+        assert_eq!(left["members"].as_array().unwrap().len(), 1);
+        assert_eq!(left["members"][0]["connection_id"], ada);
+        leave(-1, ada);
+    }
+
+    #[test]
+    fn test_live_rooms_merge_updates_and_flag_access_changes() {
+        use crate::models::{PresenceMode, PresenceUpdate, SphereCursor};
+        use crate::presence::{access_changed, join, leave, merge_update};
+
+        // Quicker updates are merged, the newest value of each field winning
+        let mut pending = PresenceUpdate {
+            mode: Some(PresenceMode::Editing),
+            frame: Some(3),
+            ..Default::default()
+        };
+        merge_update(
+            &mut pending,
+            PresenceUpdate {
+                frame: Some(4),
+                cursor: Some(Some(SphereCursor { lat: 1.0, lon: 2.0 })),
+                ..Default::default()
+            },
+        );
+        merge_update(
+            &mut pending,
+            PresenceUpdate {
+                cursor: Some(None),
+                ..Default::default()
+            },
+        );
+        assert_eq!(pending.mode, Some(PresenceMode::Editing));
+        assert_eq!(pending.frame, Some(4));
+        assert_eq!(pending.cursor, Some(None));
+
+        // Access changes reach every member, and only in the animation's room
+        let (ada, _, mut ada_access) = join(-2, 1, "ada".to_string());
+        let (other, _, mut other_access) = join(-3, 2, "grace".to_string());
+        assert!(!ada_access.has_changed().unwrap());
+        access_changed(-2);
+        assert!(ada_access.has_changed().unwrap());
+        assert!(!other_access.has_changed().unwrap());
+        ada_access.mark_unchanged();
+        other_access.mark_unchanged();
+        leave(-2, ada);
+        leave(-3, other);
+        // Leaving the last member closes the room, so nobody is left to tell
+        access_changed(-2);
+        assert!(ada_access.has_changed().is_err());
+    }
+
+    #[test]
+    fn test_query_latency_percentile() {
+        use crate::db::latency_percentile;
+        use std::time::Duration;
+
+        assert_eq!(latency_percentile(&[], 0.95), None);
+        let samples: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+        assert_eq!(latency_percentile(&samples, 0.95), Some(Duration::from_millis(95)));
+        assert_eq!(latency_percentile(&samples[..1], 0.95), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_session_ip_storage() {
+        use crate::devices::IpStorage;
+        use std::net::IpAddr;
+
+        let v4: IpAddr = "203.0.113.42".parse().unwrap();
+        let v6: IpAddr = "2001:db8:85a3:8d3:1319:8a2e:370:7348".parse().unwrap();
+        assert_eq!(
+            IpStorage::Truncate.apply(v4).as_deref(),
+            Some("203.0.113.0/24")
+        );
+        assert_eq!(
+            IpStorage::Truncate.apply(v6).as_deref(),
+            Some("2001:db8:85a3::/48")
+        );
+        assert_eq!(IpStorage::Full.apply(v4).as_deref(), Some("203.0.113.42"));
+        assert_eq!(IpStorage::None.apply(v4), None);
+
+        // Hashes can be compared, but don't hold the address
+        let hashed = IpStorage::Hash.apply(v4).unwrap();
+        assert!(hashed.starts_with("sha256:") && !hashed.contains("203"));
+        assert_eq!(IpStorage::Hash.apply(v4).unwrap(), hashed);
+        assert_ne!(IpStorage::Hash.apply(v6).unwrap(), hashed);
+    }
+
+    #[test]
+    fn test_forwarded_client_counts_trusted_hops_from_the_right() {
+        use crate::devices::forwarded_client;
+
+        let client = Some("203.0.113.77".parse().unwrap());
+        assert_eq!(forwarded_client("203.0.113.77", 1), client);
+        // Whatever the client put in front is ignored
+        assert_eq!(forwarded_client("10.0.0.1, 203.0.113.77", 1), client);
+        assert_eq!(forwarded_client("1.2.3.4,5.6.7.8, 203.0.113.77", 1), client);
+        // Behind two proxies, the inner one's address is skipped
+        assert_eq!(
+            forwarded_client("1.2.3.4, 203.0.113.77, 10.0.0.2", 2),
+            client
+        );
+        assert_eq!(forwarded_client("203.0.113.77", 2), None);
+        assert_eq!(forwarded_client("1.2.3.4, unknown", 1), None);
+    }
+
+    #[test]
+    fn test_auth_throttle_locks_out_after_failures() {
+        use crate::throttle::{Refusal, Throttle, ThrottleLimits};
+        use std::time::{Duration, Instant};
+
+        let limits = ThrottleLimits {
+            max_attempts_per_minute: 5,
+            max_failures: 3,
+            failure_window: Duration::from_secs(600),
+            lockout: Duration::from_secs(900),
+        };
+        let mut throttle = Throttle::default();
+        let ip = "203.0.113.42".parse().unwrap();
+        let other = "198.51.100.7".parse().unwrap();
+        let start = Instant::now();
+
+        // Attempts a minute are capped, until the oldest is a minute old
+        for _ in 0..5 {
+            assert_eq!(throttle.check(ip, start, &limits), Ok(()));
+        }
+        assert_eq!(
+            throttle.check(ip, start + Duration::from_secs(20), &limits),
+            Err(Refusal::TooManyAttempts {
+                retry_after: Duration::from_secs(40)
+            })
+        );
+        assert_eq!(throttle.check(other, start, &limits), Ok(()));
+        let later = start + Duration::from_secs(60);
+        assert_eq!(throttle.check(ip, later, &limits), Ok(()));
+
+        // Failures lock the client out until the lockout ends
+        assert!(!throttle.fail(ip, later, &limits));
+        assert!(!throttle.fail(ip, later, &limits));
+        assert!(throttle.fail(ip, later, &limits));
+        assert_eq!(throttle.locked_out(later, &limits), 1);
+        assert_eq!(
+            throttle.check(ip, later + Duration::from_secs(300), &limits),
+            Err(Refusal::LockedOut {
+                retry_after: Duration::from_secs(600)
+            })
+        );
+        let unlocked = later + Duration::from_secs(900);
+        assert_eq!(throttle.check(ip, unlocked, &limits), Ok(()));
+        assert_eq!(throttle.locked_out(unlocked, &limits), 0);
+    }
+
+    #[test]
+    fn test_error_messages_follow_accept_language() {
+        use crate::i18n::{negotiate, translate, Language, MessageArgs};
+
+        assert_eq!(negotiate("es-MX,es;q=0.9,en;q=0.8"), Language::Spanish);
+        assert_eq!(negotiate("ja, de;q=0.5, fr;q=0.7"), Language::French);
+        assert_eq!(negotiate("fr;q=0, de"), Language::German);
+        assert_eq!(negotiate("ja"), Language::English);
+        assert_eq!(negotiate(""), Language::English);
+
+        // English keeps the errors' own, more specific messages
+        assert_eq!(
+            translate(Language::English, "not_found", MessageArgs::default()),
+            None
+        );
+        let args = MessageArgs {
+            seconds: Some(90),
+            ..Default::default()
+        };
+        assert_eq!(
+            translate(Language::German, "too_many_requests", args).as_deref(),
+            Some("Zu viele Anmeldeversuche; bitte in 90 Sekunden erneut versuchen.")
+        );
+        assert_eq!(translate(Language::French, "no_such_code", args), None);
+    }
+
+    #[test]
+    fn test_request_id_validation() {
+        use crate::request_id::is_valid_request_id;
+
+        assert!(is_valid_request_id("3f0c9a2e-1d4b-4c55-9a61-2b7d0e8f4a10"));
+        assert!(is_valid_request_id("ci_run.42"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("has space"));
+        assert!(!is_valid_request_id("line\nbreak"));
+        assert!(!is_valid_request_id(&"a".repeat(65)));
+    }
+
+    #[test]
+    fn test_copy_name_fits_column() {
+        use crate::services::copy_name;
+
+        assert_eq!(copy_name("Pangaea"), "Pangaea (copy)");
+        assert_eq!(copy_name("Pangaea (copy)"), "Pangaea (copy) (copy)");
+
+        let long_name = "é".repeat(255);
+        let copied = copy_name(&long_name);
+        assert_eq!(copied.chars().count(), 255);
+        assert!(copied.ends_with(" (copy)"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_document_error_lists_fields() {
+        use axum::response::IntoResponse;
+        use klyja_validate::ValidationError;
+
+        let app_error = AppError::InvalidDocument(vec![
+            ValidationError {
+                path: "polygons[1]".to_string(),
+                message: "duplicate feature ID 'a'".to_string(),
+            },
+            ValidationError {
+                path: "total_frames".to_string(),
+                message: "must not be negative".to_string(),
+            },
+        ]);
+        let response = app_error.into_response();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid animation"));
+        assert_eq!(json["details"][0]["path"], "polygons[1]");
+        assert_eq!(json["details"][1]["message"], "must not be negative");
+
+        // Other errors keep the plain shape
+        let response = AppError::NotFound("gone".to_string()).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json.get("details").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_requests_over_their_budget_are_cancelled() {
+        use axum::{body::Body, http::StatusCode, middleware, routing::get, Router};
+        use std::time::Duration;
+        use tower::ServiceExt;
+
+        std::env::set_var("METADATA_TIMEOUT_SECS", "1");
+        let app = Router::new()
+            .route("/quick", get(|| async { "done" }))
+            .route(
+                "/stuck",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                    "too late"
+                }),
+            )
+            .layer(middleware::from_fn(crate::timeouts::metadata_timeout));
+
+        let request = |uri| axum::http::Request::get(uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request("/quick")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.oneshot(request("/stuck")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "timeout");
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode_refuses_writes_but_not_reads() {
+        use axum::{body::Body, http::StatusCode, middleware, routing::get, Router};
+        use tower::ServiceExt;
+
+        let app = Router::new()
+            .route(
+                "/animation",
+                get(|| async { "read" }).post(|| async { "saved" }),
+            )
+            .layer(middleware::from_fn(crate::maintenance::refuse_writes));
+        let request = |method| {
+            axum::http::Request::builder()
+                .method(method)
+                .uri("/animation")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        crate::maintenance::switch(true, Some("Moving storage".to_string()));
+        let response = app.clone().oneshot(request("POST")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "maintenance");
+        assert_eq!(json["error"], "Moving storage");
+        let response = app.clone().oneshot(request("GET")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let status = crate::maintenance::switch(false, None);
+        assert!(status.since.is_none());
+        let response = app.oneshot(request("POST")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_size_errors_are_payload_too_large() {
+        use axum::http::StatusCode;
+        use axum::response::IntoResponse;
+
+        let response = AppError::PayloadTooLarge("too big".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let response = AppError::QuotaExceeded("quota".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_limit_errors_name_the_limit() {
+        use crate::errors::CountLimit;
+        use axum::http::StatusCode;
+        use axum::response::IntoResponse;
+
+        let response = AppError::LimitExceeded {
+            limit: CountLimit::Animations,
+            max: 10,
+        }
+        .into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["limit"], "animations");
+        assert_eq!(json["limit_max"], 10);
+    }
+
+    #[test]
+    fn test_first_missing_upload_part() {
+        use crate::uploads::first_missing_part;
+
+        assert_eq!(first_missing_part(&[0, 1, 2]), None);
+        assert_eq!(first_missing_part(&[0, 2, 3]), Some(1));
+        assert_eq!(first_missing_part(&[1]), Some(0));
+    }
+
+    #[test]
+    fn test_render_gif_decodes() {
+        use crate::exports::render_gif;
+
+        let animation = MapAnimation {
+            total_frames: 3,
+            ..Default::default()
+        };
+        let gif = render_gif(&animation, 32, 25).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(gif.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (32, 32));
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 4);
+            // The corner is background, reproduced exactly by the palette
+            assert_eq!(
+                &frame.buffer[..4],
+                &geco_core::raster::BACKGROUND_COLOR[..]
+            );
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+    }
+
+    #[test]
+    fn test_uniform_palette_round_trips_its_colors() {
+        use crate::exports::Palette;
+
+        let table = Palette::Uniform.table();
+        for (index, &rgb) in table.iter().enumerate() {
+            assert_eq!(Palette::Uniform.index(rgb) as usize, index);
+        }
+    }
+
+    #[test]
+    fn test_job_payload_is_tagged_by_kind() {
+        use crate::jobs::Job;
+
+        let job = Job::RenderThumbnail { animation_id: 1 };
+        let payload = serde_json::to_value(&job).unwrap();
+        assert_eq!(
+            payload,
+            serde_json::json!({ "kind": "render_thumbnail", "animation_id": 1 })
+        );
+        assert_eq!(payload["kind"], job.kind());
+        assert_eq!(serde_json::from_value::<Job>(payload).unwrap(), job);
+        assert_eq!(
+            serde_json::to_value(Job::Purge).unwrap(),
+            serde_json::json!({ "kind": "purge" })
+        );
+    }
+
+    #[test]
+    fn test_webhooks_only_call_public_addresses() {
+        use crate::webhooks::is_public_ip;
+
+        for public in [
+            "93.184.216.34",
+            "2606:2800:220:1::1",
+            "::ffff:8.8.8.8",
+            "::8.8.8.8",
+            "64:ff9b::8.8.8.8",
+            "2002:808:808::1",
+        ] {
+            assert!(is_public_ip(public.parse().unwrap()), "{}", public);
+        }
+        for private in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            // IPv6 addresses leading to private IPv4 ones
+            "::127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::127.0.0.1",
+            "64:ff9b::169.254.169.254",
+            "2002:a00:1::1",
+            "2002:7f00:1::",
+            // Site-local and local-use NAT64
+            "fec0::1",
+            "feff::1",
+            "64:ff9b:1::8.8.8.8",
+        ] {
+            assert!(!is_public_ip(private.parse().unwrap()), "{}", private);
+        }
+    }
+
+    #[test]
+    fn test_reset_outbox_leaves_one_file_per_reset() {
+        use crate::accounts::{ResetNotifier, ResetOutbox};
+
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = ResetOutbox {
+            dir: dir.path().to_path_buf(),
+        };
+        outbox.send_reset("ada@example.com", "abc123");
+        outbox.send_reset("ada@example.com", "def456");
+
+        let mut tokens: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| {
+                let body = std::fs::read(entry.unwrap().path()).unwrap();
+                let reset: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(reset["email"], "ada@example.com");
+                reset["token"].as_str().unwrap().to_string()
+            })
+            .collect();
+        tokens.sort();
+        assert_eq!(tokens, ["abc123", "def456"]);
+    }
+
     #[test]
     fn test_map_animation_default() {
         let animation = MapAnimation::default();
@@ -1,172 +1,75 @@
 // klyja/backend/src/main.rs
-use axum::{
-    routing::{get, post},
-    Router,
-};
-use diesel::prelude::*;
-use diesel::r2d2::{self, ConnectionManager};
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use dotenvy::dotenv;
 use std::env;
 use std::net::SocketAddr;
-use std::path::PathBuf;
-use tower_http::{
-    cors::{Any, CorsLayer},
-    services::ServeDir,
-    trace::TraceLayer,
-};
 
-use utoipa::OpenApi;
-use utoipa_swagger_ui::SwaggerUi;
-
-// Include Generated Protobuf Code
-mod protobuf_gen {
-    include!(concat!(env!("OUT_DIR"), "/klyja.map_animation.v1.rs"));
-}
-
-mod db;
-mod errors;
-mod handlers;
-mod models;
-mod schema; // Will be generated by diesel print-schema
-mod services;
-
-// --- Define the ApiDoc struct ---
-#[derive(OpenApi)]
-#[openapi(
-    paths(
-        handlers::health_check_handler, // Add the health check handler
-        handlers::save_animation_handler,
-        handlers::load_animation_handler
-    ),
-    components(
-        schemas(
-            models::Animation,
-            crate::errors::ErrorResponsePayload,
-            //crate::errors::SuccessfulSaveResponsePayload
-
-        ) // List your ToSchema-derived models here
-        // Add other schemas if you have them, e.g., error response schemas
-        // schemas(ErrorResponse, AnotherModel)
-    ),
-    tags(
-        (name = "Klyja Animations API", description = "API endpoints for managing map animations")
-        // You can define tags referenced in handlers here, or utoipa will create them.
-        // (name = "Animations", description = "Animation data operations"),
-        // (name = "System", description = "System health checks")
-    ),
-    info(
-        title = "Klyja Spherical Map Animation API",
-        version = "v0.1.0",
-        description = "This API serves the Klyja application, allowing users to save and load spherical map animation data.",
-        contact(
-            name = "Klyja Dev Team",
-            url = "http://example.com/contact",
-            email = "dev@example.com"
-        ),
-        license(
-            name = "MIT OR Apache-2.0",
-            url = "http://example.com/license"
-        )
-    )
-)]
-struct ApiDoc;
-
-// Define the embedded migrations constant
-// Assumes migrations directory is at ../migrations relative to backend/Cargo.toml
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../migrations");
-// Define a type alias for the connection pool
-pub type DbPool = r2d2::Pool<ConnectionManager<PgConnection>>;
+// The router, pools and workers all live in the library crate; this only wires
+// them up from the environment and serves them
+use backend::{accounts, app, db, grpc, jobs};
 
 #[tokio::main]
 async fn main() {
     dotenv().ok(); // Load .env file
 
     // Setup logging
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    // LOG_FORMAT=json emits one JSON object per event, with span fields like request_id
+    let log_builder = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env());
+    if env::var("LOG_FORMAT").is_ok_and(|format| format == "json") {
+        log_builder.json().init();
+    } else {
+        log_builder.init();
+    }
 
     // --- Database Setup ---
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let manager = ConnectionManager::<PgConnection>::new(database_url);
-    let pool = r2d2::Pool::builder()
-        .build(manager)
-        .expect("Failed to create database connection pool.");
+    let pool = db::build_pool(
+        &database_url,
+        db::DEFAULT_POOL_SIZE,
+        db::DEFAULT_CONNECTION_TIMEOUT,
+    );
 
     // Run embedded migrations on startup
+    tracing::info!("Attempting to run database migrations...");
+    let migration_url = database_url.clone();
+    match tokio::task::spawn_blocking(move || db::run_migrations(&migration_url))
+        .await
+        .expect("Migration task panicked")
     {
-        // Scope to release connection quickly
-        tracing::info!("Attempting to run database migrations...");
-        let mut conn = pool
-            .get()
-            .expect("Failed to get DB connection for migrations");
-        match conn.run_pending_migrations(MIGRATIONS) {
-            Ok(_) => tracing::info!("Database migrations executed successfully."),
-            Err(e) => tracing::error!("Failed to run database migrations: {}", e),
-        }
+        Ok(_) => tracing::info!("Database migrations executed successfully."),
+        Err(e) => tracing::error!("Failed to run database migrations: {}", e),
     }
     // --- End Database Setup ---
 
-    // --- Calculate Paths Agnostically ---
-    // Get the directory containing backend/Cargo.toml at compile time
-    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    // Get the workspace root (parent of the manifest dir)
-    let project_root = manifest_dir
-        .parent()
-        .expect("Failed to get project root directory");
-
-    // Construct absolute paths to static asset directories
-
-    // Determine frontend path based on an environment variable or build flag
-    // For example, you could use an environment variable like `APP_ENV=production`
-    let frontend_path = if env::var("APP_ENV").unwrap_or_default() == "production" {
-        project_root.join("frontend/dist")
-    } else {
-        project_root.join("frontend") // Development serves raw files
-    };
-    let wasm_pkg_path = project_root.join("geco/pkg");
+    // Password reset tokens are left here for a mail relay; they're never logged
+    if let Ok(dir) = env::var("PASSWORD_RESET_OUTBOX_DIR") {
+        accounts::set_reset_notifier(std::sync::Arc::new(accounts::ResetOutbox {
+            dir: dir.into(),
+        }));
+    }
 
+    let config = app::AppConfig::from_env();
     tracing::info!(
         "Serving frontend static files from: {}",
-        frontend_path.display()
+        config.frontend_path.display()
     );
     tracing::info!(
         "Serving WASM package files from: {}",
-        wasm_pkg_path.display()
+        config.wasm_pkg_path.display()
     );
-    // --- End Path Calculation ---
-
-    // --- Routing Setup ---
-    // API routes (add more later in handlers.rs)
-    let api_routes = Router::new()
-        .route("/health", get(handlers::health_check_handler))
-        .route("/save_animation", post(handlers::save_animation_handler))
-        .route("/load_animation/:id", get(handlers::load_animation_handler));
-
-    // Service to serve WASM package files from `../geco/pkg`
-    let wasm_pkg_service = ServeDir::new(wasm_pkg_path).append_index_html_on_directories(false);
-
-    // Service to serve static frontend files from `../frontend`
-    let static_files_service = ServeDir::new(frontend_path).append_index_html_on_directories(true); // Serve index.html for directories like "/"
-
-    let app = Router::new()
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
-        .nest("/api", api_routes) // API routes under /api
-        .nest_service("/pkg", wasm_pkg_service) // WASM files under /pkg
-        .fallback_service(static_files_service) // Serve frontend static files as fallback
-        //.layer(Extension(pool)) // Add database pool state
-        .with_state(pool.clone())
-        //.layer(Extension(pool))
-        .layer(TraceLayer::new_for_http()) // Add HTTP request logging
-        .layer(
-            // Add CORS layer - Allow requests from any origin (adjust for production)
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any) // Allows common methods
-                .allow_headers(Any), // Allows common headers
-        );
-    // --- End Routing Setup ---
+    // Gallery, search and visitors' loads go to a read replica when one is configured
+    let app = match env::var("READ_DATABASE_URL") {
+        Ok(read_database_url) => {
+            tracing::info!("Serving heavy reads from the read replica");
+            let read_pool = db::build_pool(
+                &read_database_url,
+                db::DEFAULT_POOL_SIZE,
+                db::DEFAULT_CONNECTION_TIMEOUT,
+            );
+            app::build_app_with_read_pool(&config, pool.clone(), db::ReadPool(read_pool))
+        }
+        Err(_) => app::build_app(&config, pool.clone()),
+    };
 
     // --- Server Startup ---
     let port_str = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
@@ -177,9 +80,33 @@ async fn main() {
 
     tracing::debug!("Server listening on http://{}", addr);
 
+    // Thumbnails, video exports and purges run here, off the request path
+    jobs::spawn_workers(pool.clone(), 2);
+
+    // Pipelines that prefer gRPC get the same operations on a port of their own
+    let grpc_port = env::var("GRPC_PORT")
+        .ok()
+        .and_then(|port| port.parse::<u16>().ok())
+        .unwrap_or(grpc::DEFAULT_GRPC_PORT);
+    let grpc_addr = SocketAddr::from(([0, 0, 0, 0], grpc_port));
+    let grpc_service = grpc::AnimationsService::server(pool.clone());
+    tokio::spawn(async move {
+        tracing::info!("gRPC service listening on {}", grpc_addr);
+        if let Err(err) = tonic::transport::Server::builder()
+            .add_service(grpc_service)
+            .serve(grpc_addr)
+            .await
+        {
+            tracing::error!("gRPC service stopped: {}", err);
+        }
+    });
+
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    // Use ServiceExt::<Request>::into_make_service()
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    // The peer address is kept on sessions, as SESSION_IP_STORAGE says
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -0,0 +1,141 @@
+// klyja/backend/src/maintenance.rs
+// Maintenance mode: while it is on, requests that would change anything (REST writes
+// and gRPC saves and deletes) are answered with 503 and the code `maintenance`, while
+// reads carry on, so operators can run migrations or move the blob store without
+// writes landing halfway through. It starts on when MAINTENANCE_MODE=true (with the
+// message in MAINTENANCE_MESSAGE, if any) and admins switch it at runtime through
+// /api/admin/maintenance. The switch lives in this process's memory, so each instance
+// of a scaled-out deployment is switched on its own.
+use crate::{
+    errors::AppError,
+    models::{MaintenanceRequest, MaintenanceStatus},
+    moderation, DbPool,
+};
+use axum::{extract::Request, http::Method, middleware::Next, response::Response};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// What writers are told when the operator didn't say more.
+pub const DEFAULT_MESSAGE: &str =
+    "Klyja is down for maintenance; changes can't be saved right now, but animations can still be viewed";
+/// Longest message an admin may leave for writers.
+pub const MAX_MESSAGE_CHARS: usize = 500;
+
+fn state() -> MutexGuard<'static, MaintenanceStatus> {
+    static STATE: OnceLock<Mutex<MaintenanceStatus>> = OnceLock::new();
+    STATE
+        .get_or_init(|| {
+            let enabled = std::env::var("MAINTENANCE_MODE")
+                .is_ok_and(|value| value == "true" || value == "1");
+            Mutex::new(MaintenanceStatus {
+                enabled,
+                message: std::env::var("MAINTENANCE_MESSAGE")
+                    .ok()
+                    .filter(|message| !message.trim().is_empty()),
+                since: enabled.then(|| chrono::Utc::now().naive_utc()),
+            })
+        })
+        .lock()
+        // Each change replaces the whole status, so a panic elsewhere can't spoil it
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Whether maintenance mode is on, and since when.
+pub fn status() -> MaintenanceStatus {
+    state().clone()
+}
+
+/// Refuses with `AppError::Maintenance` while maintenance mode is on.
+pub fn ensure_writable() -> Result<(), AppError> {
+    let state = state();
+    if !state.enabled {
+        return Ok(());
+    }
+    Err(AppError::Maintenance(
+        state
+            .message
+            .clone()
+            .unwrap_or_else(|| DEFAULT_MESSAGE.to_string()),
+    ))
+}
+
+/// Middleware turning away requests that may write while maintenance mode is on.
+/// GET, HEAD and OPTIONS only read; routes that read through POST are left out of it.
+pub async fn refuse_writes(request: Request, next: Next) -> Result<Response, AppError> {
+    if !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        ensure_writable()?;
+    }
+    Ok(next.run(request).await)
+}
+
+pub struct MaintenanceService;
+
+impl MaintenanceService {
+    /// Whether maintenance mode is on. Admins only.
+    pub async fn status_logic(
+        pool: &DbPool,
+        caller_id: i32,
+    ) -> Result<MaintenanceStatus, AppError> {
+        ensure_admin(pool, caller_id).await?;
+        Ok(status())
+    }
+
+    /// Switches maintenance mode on or off. Admins only. Switching it on again
+    /// replaces the message but keeps when it started.
+    pub async fn set_logic(
+        pool: &DbPool,
+        caller_id: i32,
+        request: MaintenanceRequest,
+    ) -> Result<MaintenanceStatus, AppError> {
+        ensure_admin(pool, caller_id).await?;
+        let message = request
+            .message
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty());
+        if message
+            .as_ref()
+            .is_some_and(|message| message.chars().count() > MAX_MESSAGE_CHARS)
+        {
+            return Err(AppError::BadRequest(format!(
+                "Maintenance messages can be at most {} characters",
+                MAX_MESSAGE_CHARS
+            )));
+        }
+
+        let status = switch(request.enabled, message);
+        tracing::warn!(
+            "SERVICE: User {} switched maintenance mode {}",
+            caller_id,
+            if request.enabled { "on" } else { "off" }
+        );
+        Ok(status)
+    }
+}
+
+/// Switches maintenance mode, keeping when it started if it was already on.
+pub(crate) fn switch(enabled: bool, message: Option<String>) -> MaintenanceStatus {
+    let mut state = state();
+    let since = match (enabled, state.since) {
+        (true, Some(since)) => Some(since),
+        (true, None) => Some(chrono::Utc::now().naive_utc()),
+        (false, _) => None,
+    };
+    *state = MaintenanceStatus {
+        enabled,
+        message: message.filter(|_| enabled),
+        since,
+    };
+    state.clone()
+}
+
+async fn ensure_admin(pool: &DbPool, caller_id: i32) -> Result<(), AppError> {
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    if !moderation::is_admin(&mut conn, Some(caller_id)).await? {
+        return Err(AppError::Forbidden(
+            "Only admins can switch maintenance mode".to_string(),
+        ));
+    }
+    Ok(())
+}
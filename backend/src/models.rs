@@ -1,12 +1,12 @@
 // klyja/backend/src/models.rs
 //use crate::schema::animations; // Import the table definition
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize}; // Might need Serialize for responses later
 use utoipa::ToSchema;
 
 // Struct for reading data FROM the database (maps to the table structure)
-#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[diesel(table_name = crate::schema::animations)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 #[schema(example = json!({ // Example for the schema (requires serde_json in scope for json!)
@@ -16,17 +16,222 @@ use utoipa::ToSchema;
     "created_at": "2024-05-07T12:30:00", // Example timestamp
     "updated_at": "2024-05-07T12:35:00"
 }))]
-
 pub struct Animation {
     #[schema(example = 101)]
     pub id: i32,
     #[schema(example = "Fireball")]
     pub name: String,
-    #[serde(skip_serializing)] // Avoid sending raw bytes in typical JSON responses
+    #[serde(skip_serializing, default)] // Avoid sending raw bytes in typical JSON responses
     #[schema(hidden = true)]
     pub protobuf_data: Vec<u8>, // Matches BYTEA column
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    #[schema(example = 7)]
+    pub owner_id: Option<i32>, // None for animations saved without a session
+    // Hex SHA-256 of the document; None for old rows not yet hashed
+    #[schema(example = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")]
+    pub content_sha256: Option<String>,
+    // Starts at 1 and goes up with each update; served as the ETag
+    #[schema(example = 3)]
+    pub lock_version: i32,
+    // The document's own animation_id; saves with the same one share a row, and
+    // only one animation may hold each
+    #[schema(example = "5b7e1f0c-2d4a-4c8e-9f1a-3e6b8d2c7a10")]
+    pub animation_uuid: Option<String>,
+    #[schema(example = "The breakup of Pangaea over 200 million years")]
+    pub description: Option<String>,
+    #[schema(example = "CC-BY-4.0")]
+    pub license: Option<String>,
+    #[schema(example = "Plate motions after Scotese (2016), PALEOMAP project")]
+    pub source_attribution: Option<String>,
+    // When an admin hid it from everyone but its owner; None while visible
+    pub hidden_at: Option<NaiveDateTime>,
+    // The owner's folder it is filed in; None at the top level
+    #[schema(example = 4)]
+    pub folder_id: Option<i32>,
+    // When it was made readable by anyone; None while private
+    pub published_at: Option<NaiveDateTime>,
+    // When it is scheduled to be published, if it is
+    pub publish_at: Option<NaiveDateTime>,
+    // The user who first saved it; None for anonymous saves and deleted users
+    #[schema(example = 7)]
+    pub created_by: Option<i32>,
+    // The user whose change to it was the latest, owner or collaborator
+    #[schema(example = 12)]
+    pub last_edited_by: Option<i32>,
+    // Sizes of the document, kept up to date by saves; see DocumentMetrics
+    #[schema(example = 20480)]
+    pub encoded_bytes: i32,
+    #[schema(example = 12)]
+    pub feature_count: Option<i32>, // The counts are None for old saves not yet measured
+    #[schema(example = 480)]
+    pub point_count: Option<i32>,
+    #[schema(example = 96)]
+    pub keyframe_count: Option<i32>,
+    // The geco_core::format version the stored document is written in
+    #[schema(example = 0)]
+    pub format_version: i32,
+}
+
+// Listing row: everything but the document itself, so browsing doesn't load every save
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::animations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AnimationSummary {
+    #[schema(example = 101)]
+    pub id: i32,
+    #[schema(example = "Fireball")]
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub owner_id: Option<i32>,
+    pub folder_id: Option<i32>,
+    pub published_at: Option<NaiveDateTime>,
+    #[schema(example = 20480)]
+    pub encoded_bytes: i32, // Size of the protobuf as stored, compressed
+    #[schema(example = 12)]
+    pub feature_count: Option<i32>, // The counts are None for old saves not yet measured
+    #[schema(example = 480)]
+    pub point_count: Option<i32>,
+    #[schema(example = 96)]
+    pub keyframe_count: Option<i32>,
+}
+
+// Listing entry returned by the API: the summary plus where to fetch its preview
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnimationListItem {
+    #[serde(flatten)]
+    pub summary: AnimationSummary,
+    // None until a thumbnail has been uploaded; changes whenever it is replaced
+    #[schema(example = "/api/animation/101/thumbnail?v=1715085300")]
+    pub thumbnail_url: Option<String>,
+    #[schema(example = 4)]
+    pub star_count: i64,
+}
+
+// How listings are ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ListSort {
+    #[default]
+    Recent, // Most recently updated first
+    Stars, // Most starred first, then most recently updated
+}
+
+// An animation's stars after starring or unstarring it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StarStatus {
+    #[schema(example = 101)]
+    pub animation_id: i32,
+    pub starred: bool, // Whether the caller has starred it
+    #[schema(example = 5)]
+    pub star_count: i64,
+}
+
+// A user's autosave of an animation, as stored (the document is compressed)
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::schema::animation_drafts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AnimationDraft {
+    pub protobuf_data: Vec<u8>,
+    // The animation's version when the user started the edits the draft holds
+    pub based_on_version: i32,
+    pub updated_at: NaiveDateTime,
+}
+
+// Details of a saved animation read from its stored document, without the document
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnimationMetadata {
+    #[schema(example = 101)]
+    pub id: i32,
+    // The document's own animation_id, if no other animation already held it
+    #[schema(example = "5b7e1f0c-2d4a-4c8e-9f1a-3e6b8d2c7a10")]
+    pub animation_uuid: Option<String>,
+    #[schema(example = "Fireball")]
+    pub name: String,
+    #[schema(example = 240)]
+    pub total_frames: i32,
+    #[schema(example = 12)]
+    pub feature_count: usize,
+    #[schema(example = 480)]
+    pub point_count: usize,
+    #[schema(example = 96)]
+    pub keyframe_count: usize,
+    #[schema(example = 20480)]
+    pub byte_size: usize, // Size of the stored protobuf
+    #[schema(example = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")]
+    pub content_sha256: Option<String>,
+    #[schema(example = 3)]
+    pub lock_version: i32, // Send as If-Match when updating
+    pub owner_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    #[schema(example = "The breakup of Pangaea over 200 million years")]
+    pub description: Option<String>,
+    #[schema(example = "CC-BY-4.0")]
+    pub license: Option<String>,
+    #[schema(example = "Plate motions after Scotese (2016), PALEOMAP project")]
+    pub source_attribution: Option<String>,
+    #[schema(example = 7)]
+    pub created_by: Option<i32>,
+    #[schema(example = 12)]
+    pub last_edited_by: Option<i32>, // Who last saved it or changed its details
+}
+
+// Request body replacing an animation's description, license and attribution;
+// fields left out or blank are cleared
+#[derive(Deserialize, Debug, Default, ToSchema)]
+#[serde(default)]
+pub struct AnimationDetailsRequest {
+    #[schema(example = "The breakup of Pangaea over 200 million years")]
+    pub description: Option<String>, // At most MAX_DESCRIPTION_CHARS
+    #[schema(example = "CC-BY-4.0")]
+    pub license: Option<String>, // At most MAX_LICENSE_CHARS, e.g. an SPDX identifier
+    #[schema(example = "Plate motions after Scotese (2016), PALEOMAP project")]
+    pub source_attribution: Option<String>, // At most MAX_ATTRIBUTION_CHARS
+}
+
+// Request body for describing several animations in one request
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct BatchMetadataRequest {
+    #[schema(example = json!([101, 102, 105]))]
+    pub ids: Vec<i32>, // At most MAX_BATCH_METADATA_IDS
+}
+
+// Views of an animation on one day
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DailyViews {
+    #[schema(example = "2024-05-07")]
+    pub day: NaiveDate,
+    #[schema(example = 12)]
+    pub views: i64,
+}
+
+// How much an animation has been viewed, for its owner
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnimationStats {
+    #[schema(example = 101)]
+    pub animation_id: i32,
+    #[schema(example = 340)]
+    pub total_views: i64, // Since views were first counted
+    #[schema(example = 5)]
+    pub star_count: i64,
+    pub daily_views: Vec<DailyViews>, // Oldest first, one entry per day including empty ones
+}
+
+// What changed between two saved versions of an animation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnimationDiff {
+    #[schema(example = 101)]
+    pub animation_id: i32,
+    #[schema(example = 2)]
+    pub from_version: i32,
+    #[schema(example = 3)]
+    pub to_version: i32,
+    // Features added, removed and changed, and keyframe counts before and after
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub changes: geco_core::diff::DocumentDiff,
 }
 
 // Struct for inserting data INTO the database
@@ -37,9 +242,757 @@ pub struct NewAnimation<'a> {
     // Use lifetime for borrowed data (&str, &[u8])
     pub name: &'a str,
     pub protobuf_data: &'a [u8],
+    pub owner_id: Option<i32>,
+    pub content_sha256: &'a str, // Of the document before compression
+    pub animation_uuid: Option<&'a str>, // None when the document has no animation_id
+    pub created_by: Option<i32>, // The saving user, who is also its last editor so far
+    pub last_edited_by: Option<i32>,
+    #[diesel(embed)]
+    pub metrics: DocumentMetrics,
     // id, created_at, updated_at are handled by the database
 }
 
+// Sizes and format version of a document, stored alongside it whenever it is saved
+// so they can be queried without decoding it
+#[derive(Insertable, AsChangeset, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[diesel(table_name = crate::schema::animations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DocumentMetrics {
+    pub encoded_bytes: i32, // As stored, after compression
+    pub feature_count: i32,
+    pub point_count: i32,
+    // Point positions and values, feature transforms, grid frames, camera placements
+    // and data series values
+    pub keyframe_count: i32,
+    pub format_version: i32, // See geco_core::format
+}
+
+// What a collaborator may do with an animation they don't own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CollaboratorRole {
+    Viewer,
+    Editor,
+}
+
+impl CollaboratorRole {
+    // Matches the CHECK constraint on animation_collaborators.role
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CollaboratorRole::Viewer => "viewer",
+            CollaboratorRole::Editor => "editor",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "viewer" => Some(CollaboratorRole::Viewer),
+            "editor" => Some(CollaboratorRole::Editor),
+            _ => None,
+        }
+    }
+}
+
+// One collaborator on an animation, as returned by the collaborators listing
+#[derive(Queryable, Debug, Serialize, ToSchema)]
+pub struct Collaborator {
+    #[schema(example = 12)]
+    pub user_id: i32,
+    #[schema(example = "ada")]
+    pub username: String,
+    #[schema(example = "editor")]
+    pub role: String,
+    pub created_at: NaiveDateTime,
+}
+
+// Request body for adding a collaborator or changing their role
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct InviteCollaboratorRequest {
+    #[schema(example = 12)]
+    pub user_id: i32,
+    pub role: CollaboratorRole,
+}
+
+// Request body for inviting someone to collaborate, whether or not they have an account yet
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateInvitationRequest {
+    // An email address, or the username of an existing user
+    #[schema(example = "ada@example.com")]
+    pub invitee: String,
+    pub role: CollaboratorRole,
+}
+
+// A pending invitation to collaborate on an animation
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::invitations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Invitation {
+    #[schema(example = 8)]
+    pub id: i32,
+    #[schema(example = 101)]
+    pub animation_id: i32,
+    #[schema(example = "ada@example.com")]
+    pub invitee: String,
+    #[schema(example = "editor")]
+    pub role: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+// A newly created invitation, with the token the invitee accepts it with
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedInvitation {
+    #[schema(example = "c1d2...")]
+    pub token: String,
+    #[serde(flatten)]
+    pub details: Invitation,
+}
+
+// Request body for accepting an invitation
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
+}
+
+// What accepting an invitation gave the caller access to
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AcceptedInvitation {
+    #[schema(example = 101)]
+    pub animation_id: i32,
+    pub role: CollaboratorRole,
+}
+
+// Request body for creating a local (email and password) account
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct RegisterRequest {
+    #[schema(example = "ada")]
+    pub username: String,
+    #[schema(example = "ada@example.com")]
+    pub email: String,
+    #[schema(example = "correct horse battery staple")]
+    pub password: String,
+}
+
+// Request body for signing in to a local account
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct LoginRequest {
+    #[schema(example = "ada@example.com")]
+    pub email: String,
+    #[schema(example = "correct horse battery staple")]
+    pub password: String,
+}
+
+// Request body for asking for a password reset token
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct PasswordResetRequest {
+    #[schema(example = "ada@example.com")]
+    pub email: String,
+}
+
+// Request body for setting a new password with a reset token
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ConfirmPasswordResetRequest {
+    pub token: String,
+    #[schema(example = "correct horse battery staple")]
+    pub password: String,
+}
+
+// The signed-in user, returned after registering or signing in
+#[derive(Queryable, Debug, Serialize, ToSchema)]
+pub struct Account {
+    #[schema(example = 12)]
+    pub id: i32,
+    #[schema(example = "ada")]
+    pub username: String,
+    #[schema(example = "ada@example.com")]
+    pub email: Option<String>,
+}
+
+// What a personal access token may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    Read,  // GET and HEAD requests
+    Write, // Everything else
+}
+
+impl TokenScope {
+    // Stored in api_tokens.scopes
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Write => "write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        match scope {
+            "read" => Some(TokenScope::Read),
+            "write" => Some(TokenScope::Write),
+            _ => None,
+        }
+    }
+}
+
+// Request body for creating a personal access token
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateApiTokenRequest {
+    #[schema(example = "CI uploads")]
+    pub name: String,
+    #[schema(example = json!(["read", "write"]))]
+    pub scopes: Vec<TokenScope>,
+    // Omit for a token that lasts until revoked
+    #[schema(example = 90)]
+    pub expires_in_days: Option<u32>,
+}
+
+// A personal access token as listed to its owner; the secret itself is never shown again
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::api_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ApiToken {
+    #[schema(example = 4)]
+    pub id: i32,
+    #[schema(example = "CI uploads")]
+    pub name: String,
+    #[schema(example = json!(["read", "write"]))]
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+// One of the caller's sign-ins, with the device it is used from; the token is never shown
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionInfo {
+    #[schema(example = 7)]
+    pub id: i32,
+    #[schema(example = "Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0")]
+    pub user_agent: Option<String>,
+    // Hashed, truncated or left out, as the deployment's SESSION_IP_STORAGE says
+    #[schema(example = "203.0.113.0/24")]
+    pub ip: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime, // Updated at most once a minute
+    pub expires_at: NaiveDateTime,
+    // Signed in from an IP and browser none of the user's other sessions had
+    #[schema(example = false)]
+    pub new_device: bool,
+    // Whether this is the session making the request
+    #[schema(example = true)]
+    pub current: bool,
+}
+
+// A newly created token, with the secret to send as `Authorization: Bearer`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiToken {
+    #[schema(example = "klyja_pat_5f2c...")]
+    pub token: String,
+    #[serde(flatten)]
+    pub details: ApiToken,
+}
+
+// Animation changes a webhook can be called for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEvent {
+    Saved,     // An animation was created or its document replaced
+    Published, // An animation was made readable by anyone
+    Deleted,   // An animation was deleted; its version is the last one saved
+}
+
+impl WebhookEvent {
+    // Stored in webhooks.events
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::Saved => "saved",
+            WebhookEvent::Published => "published",
+            WebhookEvent::Deleted => "deleted",
+        }
+    }
+}
+
+// Request body for registering a webhook
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateWebhookRequest {
+    #[schema(example = "https://example.com/klyja-hook")]
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    // Key deliveries are signed with; one is generated when left out
+    pub secret: Option<String>,
+}
+
+// A webhook as listed to its owner, without its secret
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::webhooks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Webhook {
+    #[schema(example = 3)]
+    pub id: i32,
+    #[schema(example = "https://example.com/klyja-hook")]
+    pub url: String,
+    #[schema(example = json!(["saved"]))]
+    pub events: Vec<String>,
+    pub created_at: NaiveDateTime,
+}
+
+// A newly registered webhook, with the secret its deliveries are signed with
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedWebhook {
+    #[schema(example = "5f2c...")]
+    pub secret: String,
+    #[serde(flatten)]
+    pub details: Webhook,
+}
+
+// One of a user's folders; folders nest through parent_id
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::folders)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Folder {
+    #[schema(example = 4)]
+    pub id: i32,
+    #[schema(example = 1)]
+    pub parent_id: Option<i32>, // None at the top level
+    #[schema(example = "Plate tectonics")]
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+// Request body for creating a folder, or renaming and moving one
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct FolderRequest {
+    #[schema(example = "Plate tectonics")]
+    pub name: String,
+    // Folder to put it in; omit for the top level
+    #[schema(example = 1)]
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+}
+
+// Request body for filing an animation in a folder
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct MoveAnimationRequest {
+    // Folder to move it to; null moves it back to the top level
+    #[schema(example = 4)]
+    pub folder_id: Option<i32>,
+}
+
+// Request body for publishing an animation now or at a set time
+#[derive(Deserialize, Debug, Default, ToSchema)]
+#[serde(default)]
+pub struct PublicationRequest {
+    // UTC time to publish at; omitted or already past publishes it now
+    pub publish_at: Option<NaiveDateTime>,
+}
+
+// Whether an animation is published, or when it will be
+#[derive(Queryable, Debug, Serialize, ToSchema)]
+pub struct PublicationStatus {
+    #[schema(example = 101)]
+    pub animation_id: i32,
+    pub published_at: Option<NaiveDateTime>,
+    pub publish_at: Option<NaiveDateTime>,
+}
+
+// Request body for creating an embed link
+#[derive(Deserialize, Debug, Default, ToSchema)]
+#[serde(default)]
+pub struct EmbedRequest {
+    // Hours the link works for; defaults to 30 days
+    #[schema(example = 168)]
+    pub ttl_hours: Option<u32>,
+}
+
+// A signed link that loads one animation read-only, without signing in
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmbedLink {
+    pub token: String,
+    // Path of the embed endpoint for this token
+    #[schema(example = "/embed/101.1767225600.3f2a...")]
+    pub url: String,
+    pub expires_at: NaiveDateTime,
+}
+
+// What someone in an animation's live room is doing with it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceMode {
+    #[default]
+    Viewing,
+    Editing, // Only for the owner and editors
+}
+
+// A point on the sphere, in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SphereCursor {
+    #[schema(example = 48.85)]
+    pub lat: f64, // -90 to 90
+    #[schema(example = 2.35)]
+    pub lon: f64, // -180 to 180
+}
+
+// Message a client sends over the live socket when its state changes; fields left
+// out keep their last value
+#[derive(Deserialize, Debug, Default, ToSchema)]
+#[serde(default)]
+pub struct PresenceUpdate {
+    pub mode: Option<PresenceMode>,
+    #[schema(example = 24)]
+    pub frame: Option<i32>,
+    // null when the cursor leaves the sphere
+    #[serde(deserialize_with = "null_or_value")]
+    #[schema(value_type = Option<SphereCursor>)]
+    pub cursor: Option<Option<SphereCursor>>,
+}
+
+// Tells a field sent as null (Some(None)) apart from one left out (None)
+fn null_or_value<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// One connection in an animation's live room; a user with two tabs open has two
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PresenceEntry {
+    #[schema(example = 12)]
+    pub connection_id: u64,
+    #[schema(example = 7)]
+    pub user_id: i32,
+    #[schema(example = "ada")]
+    pub username: String,
+    pub mode: PresenceMode,
+    pub frame: Option<i32>,           // None until the client reports one
+    pub cursor: Option<SphereCursor>, // None while off the sphere
+}
+
+// Message the live socket sends: everyone in the room whenever it changes, or
+// why the client's last message was refused
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveMessage {
+    Presence {
+        animation_id: i32,
+        members: Vec<PresenceEntry>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+// Request body for reporting an animation to the admins
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ReportRequest {
+    #[schema(example = "Copies another user's work without credit")]
+    pub reason: String,
+}
+
+// A user's report of an animation, as listed to admins
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::reports)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Report {
+    #[schema(example = 9)]
+    pub id: i32,
+    #[schema(example = 101)]
+    pub animation_id: i32,
+    #[schema(example = 12)]
+    pub reporter_id: i32,
+    #[schema(example = "Copies another user's work without credit")]
+    pub reason: String,
+    pub created_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>, // None until an admin acts on the animation
+}
+
+// Request body for starting a resumable upload
+#[derive(Deserialize, Debug, Default, ToSchema)]
+#[serde(default)]
+pub struct CreateUploadRequest {
+    // Animation the finished document replaces; omit to save a new animation
+    #[schema(example = 101)]
+    pub animation_id: Option<i32>,
+}
+
+// Progress of a resumable upload, so a client can resend only the missing parts
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UploadStatus {
+    #[schema(example = 5)]
+    pub id: i32,
+    pub animation_id: Option<i32>,
+    #[schema(example = json!([0, 1, 3]))]
+    pub received_parts: Vec<i32>,
+    #[schema(example = 3145728)]
+    pub received_bytes: i64,
+    pub created_at: NaiveDateTime,
+}
+
+// File type of an export: a video, or a zip of GeoJSON frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Gif,
+    Mp4,
+    #[serde(skip_deserializing)] // Asked for through export/frames, not as a video
+    Geojson,
+}
+
+impl ExportFormat {
+    // Matches the CHECK constraint on export_jobs.format
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExportFormat::Gif => "gif",
+            ExportFormat::Mp4 => "mp4",
+            ExportFormat::Geojson => "geojson",
+        }
+    }
+
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "gif" => Some(ExportFormat::Gif),
+            "mp4" => Some(ExportFormat::Mp4),
+            "geojson" => Some(ExportFormat::Geojson),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Gif => "image/gif",
+            ExportFormat::Mp4 => "video/mp4",
+            ExportFormat::Geojson => "application/zip",
+        }
+    }
+
+    // Extension of the downloaded file
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Gif => "gif",
+            ExportFormat::Mp4 => "mp4",
+            ExportFormat::Geojson => "zip",
+        }
+    }
+}
+
+// Request body for exporting an animation as a video
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ExportVideoRequest {
+    pub format: ExportFormat,
+    // Width and height in pixels; defaults to 480
+    #[schema(example = 480)]
+    pub size: Option<u32>,
+    // Frames per second; defaults to 24
+    #[schema(example = 24)]
+    pub fps: Option<u32>,
+}
+
+// Request body for exporting an animation's frames as GeoJSON
+#[derive(Deserialize, Debug, Default, ToSchema)]
+#[serde(default)]
+pub struct ExportFramesRequest {
+    // Write every step-th frame, starting with the first; defaults to 1, every frame
+    #[schema(example = 10)]
+    pub step: Option<u32>,
+}
+
+// An export job as reported to clients; the file itself is fetched from download_url
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportJob {
+    #[schema(example = 3)]
+    pub id: i32,
+    #[schema(example = 101)]
+    pub animation_id: i32,
+    // The queue job rendering it, also reported at /api/jobs/{id}
+    #[schema(example = 12)]
+    pub job_id: Option<i32>,
+    #[schema(example = "gif")]
+    pub format: String,
+    #[schema(example = "done")]
+    pub status: String, // pending, running, done or failed
+    pub error: Option<String>,
+    // Set once the export is done
+    #[schema(example = "/api/exports/3/download")]
+    pub download_url: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+// A backup of every animation to a zip, or a restore from one, as reported to admins
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::backups)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BackupJob {
+    #[schema(example = 2)]
+    pub id: i32,
+    #[schema(example = "backup")]
+    pub kind: String, // backup or restore
+    // The zip within the server's BACKUP_DIR
+    #[schema(example = "klyja-backup-20261016T120000-5b7e1f0c.zip")]
+    pub file_name: String,
+    #[schema(example = "done")]
+    pub status: String, // pending, running, done or failed
+    #[schema(example = 120)]
+    pub animation_count: Option<i32>, // Written or restored, once done
+    #[schema(example = 3)]
+    pub skipped_count: Option<i32>, // Restores: already here by document ID, or unreadable
+    pub error: Option<String>,
+    // The queue job running it, also reported at /api/jobs/{id}
+    #[schema(example = 12)]
+    pub job_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+// Request body for restoring the animations in a backup
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct RestoreRequest {
+    // A zip in the server's BACKUP_DIR, made by this deployment or copied from another
+    #[schema(example = "klyja-backup-20261016T120000-5b7e1f0c.zip")]
+    pub file_name: String,
+}
+
+// One file of an archive import: the animation made from it, or why none was
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::import_entries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ImportedFile {
+    // Path within the zip
+    #[schema(example = "coastlines/europe.geojson")]
+    pub file_name: String,
+    #[schema(example = "done")]
+    pub status: String, // pending, done or failed
+    #[schema(example = 101)]
+    pub animation_id: Option<i32>,
+    pub error: Option<String>,
+    // The queue job importing it, also reported at /api/jobs/{id}
+    #[schema(example = 12)]
+    pub job_id: Option<i32>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+// An archive import as reported to the user who sent it, one file at a time
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ArchiveImport {
+    #[schema(example = 4)]
+    pub id: i32,
+    // Done once no file is still pending
+    #[schema(example = false)]
+    pub done: bool,
+    #[schema(example = 2)]
+    pub imported: usize,
+    #[schema(example = 1)]
+    pub failed: usize,
+    pub files: Vec<ImportedFile>,
+    pub created_at: NaiveDateTime,
+}
+
+// A curated starting point users can copy into their account
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::templates)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Template {
+    #[schema(example = 1)]
+    pub id: i32,
+    #[schema(example = "Tectonic starter")]
+    pub name: String,
+    #[schema(example = "Pangaea's plates, ready to drift apart")]
+    pub description: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+// A background job as reported to clients polling GET /api/jobs/{id}
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::jobs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct JobInfo {
+    #[schema(example = 12)]
+    pub id: i32,
+    #[schema(example = "render_thumbnail")]
+    pub kind: String,
+    #[schema(example = 101)]
+    pub animation_id: Option<i32>,
+    #[schema(example = "pending")]
+    pub status: String, // pending, running, done or failed
+    #[schema(example = 1)]
+    pub attempts: i32,
+    #[schema(example = 3)]
+    pub max_attempts: i32,
+    pub last_error: Option<String>, // From the latest failed attempt
+    pub created_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+// Connection pool usage, as reported by the readiness check
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolStats {
+    #[schema(example = 4)]
+    pub connections: u32, // Open, in use or idle
+    #[schema(example = 3)]
+    pub idle_connections: u32,
+    #[schema(example = 1)]
+    pub in_use_connections: u32, // Checked out by requests right now
+}
+
+// How long transactions and the main reads have been taking, as reported by the readiness check
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueryLatency {
+    #[schema(example = 1000)]
+    pub samples: usize, // Recent queries measured, up to LATENCY_SAMPLES
+    #[schema(example = 1.8)]
+    pub p95_ms: Option<f64>, // None until one has been measured
+}
+
+// Whether this instance can serve traffic: its database answers and is fully migrated
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    pub database: bool, // Whether SELECT 1 succeeded
+    #[schema(example = 0)]
+    pub pending_migrations: Option<usize>, // Unknown if the database is down
+    #[schema(example = "20261016180000")]
+    pub latest_migration: Option<String>,
+    pub pool: PoolStats,
+    pub query_latency: QueryLatency,
+    pub error: Option<String>, // Why it isn't ready
+}
+
+// Sign-in throttling since the server started, for admins
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuthThrottleStats {
+    #[schema(example = 1200)]
+    pub attempts: u64, // Requests to the credential routes from a known IP
+    #[schema(example = 85)]
+    pub failures: u64, // Of those, refused for wrong credentials or bad input
+    #[schema(example = 12)]
+    pub refused: u64, // Turned away with 429 before reaching the route
+    #[schema(example = 2)]
+    pub lockouts: u64,
+    #[schema(example = 1)]
+    pub locked_out_clients: usize, // IPs locked out right now
+}
+
+// Whether writes are refused for maintenance, for admins
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MaintenanceStatus {
+    #[schema(example = true)]
+    pub enabled: bool,
+    #[schema(example = "Moving documents to new storage; back by 14:00 UTC")]
+    pub message: Option<String>, // Shown to writers instead of the default
+    pub since: Option<NaiveDateTime>, // When it was switched on; None while off
+}
+
+// Request body for switching maintenance mode
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct MaintenanceRequest {
+    #[schema(example = true)]
+    pub enabled: bool,
+    #[schema(example = "Moving documents to new storage; back by 14:00 UTC")]
+    pub message: Option<String>, // At most MAX_MESSAGE_CHARS; ignored when switching off
+}
+
 // Optional: Struct for updating data (if needed later)
 // #[derive(AsChangeset, Debug, Deserialize)]
 // #[diesel(table_name = crate::schema::animations)]
@@ -0,0 +1,156 @@
+// klyja/backend/src/moderation.rs
+// Moderation: signed-in users report animations they can see, and admins (users with
+// `is_admin` set in the database) go through the open reports and hide animations
+// that shouldn't be public. Hidden animations stay with their owner; everyone else
+// gets 404, and they drop out of listings. Hiding or unhiding resolves the reports.
+use crate::{
+    cache,
+    errors::AppError,
+    models::{Report, ReportRequest},
+    presence,
+    services::{require_access, AnimationAccess},
+    DbPool,
+};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+/// Longest reason a report can give, in characters.
+pub const MAX_REPORT_REASON_CHARS: usize = 1000;
+
+/// Whether the caller is signed in as an admin.
+pub(crate) async fn is_admin(
+    conn: &mut AsyncPgConnection,
+    caller_id: Option<i32>,
+) -> Result<bool, AppError> {
+    let Some(caller) = caller_id else {
+        return Ok(false);
+    };
+    use crate::schema::users;
+
+    let admin = users::table
+        .find(caller)
+        .select(users::is_admin)
+        .first::<bool>(conn)
+        .await
+        .optional()?;
+    Ok(admin.unwrap_or(false))
+}
+
+async fn require_admin(conn: &mut AsyncPgConnection, caller_id: i32) -> Result<(), AppError> {
+    if is_admin(conn, Some(caller_id)).await? {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(
+            "Only admins can moderate animations".to_string(),
+        ))
+    }
+}
+
+pub struct ModerationService;
+
+impl ModerationService {
+    /// Reports an animation the caller can load. Reporting it again replaces the
+    /// earlier reason and reopens the report.
+    pub async fn report_animation_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        reporter_id: i32,
+        request: ReportRequest,
+    ) -> Result<(), AppError> {
+        let reason = request.reason.trim();
+        if reason.is_empty() || reason.chars().count() > MAX_REPORT_REASON_CHARS {
+            return Err(AppError::BadRequest(format!(
+                "Give a reason of between 1 and {} characters",
+                MAX_REPORT_REASON_CHARS
+            )));
+        }
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(
+            &mut conn,
+            animation_id,
+            Some(reporter_id),
+            AnimationAccess::View,
+        )
+        .await?;
+        use crate::schema::reports;
+
+        diesel::insert_into(reports::table)
+            .values((
+                reports::animation_id.eq(animation_id),
+                reports::reporter_id.eq(reporter_id),
+                reports::reason.eq(reason),
+            ))
+            .on_conflict((reports::animation_id, reports::reporter_id))
+            .do_update()
+            .set((
+                reports::reason.eq(reason),
+                reports::created_at.eq(diesel::dsl::now),
+                reports::resolved_at.eq(None::<chrono::NaiveDateTime>),
+            ))
+            .execute(&mut conn)
+            .await?;
+        tracing::info!(
+            "SERVICE: User {} reported animation {}",
+            reporter_id,
+            animation_id
+        );
+        Ok(())
+    }
+
+    /// Lists the reports no admin has acted on yet, oldest first.
+    pub async fn list_reports_logic(pool: &DbPool, admin_id: i32) -> Result<Vec<Report>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_admin(&mut conn, admin_id).await?;
+        use crate::schema::reports;
+
+        reports::table
+            .filter(reports::resolved_at.is_null())
+            .order((reports::created_at.asc(), reports::id.asc()))
+            .select(Report::as_select())
+            .load::<Report>(&mut conn)
+            .await
+            .map_err(AppError::DatabaseQuery)
+    }
+
+    /// Hides an animation from everyone but its owner, or shows it again, and
+    /// resolves its open reports either way.
+    pub async fn set_hidden_logic(
+        pool: &DbPool,
+        admin_id: i32,
+        animation_id: i32,
+        hidden: bool,
+    ) -> Result<(), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_admin(&mut conn, admin_id).await?;
+        use crate::schema::{animations, reports};
+
+        let hidden_at = hidden.then(|| chrono::Utc::now().naive_utc());
+        let updated = diesel::update(animations::table.find(animation_id))
+            .set(animations::hidden_at.eq(hidden_at))
+            .execute(&mut conn)
+            .await?;
+        if updated == 0 {
+            return Err(AppError::NotFound(format!(
+                "Animation {} not found",
+                animation_id
+            )));
+        }
+        cache::invalidate(animation_id).await;
+        presence::access_changed(animation_id);
+        diesel::update(
+            reports::table
+                .filter(reports::animation_id.eq(animation_id))
+                .filter(reports::resolved_at.is_null()),
+        )
+        .set(reports::resolved_at.eq(diesel::dsl::now))
+        .execute(&mut conn)
+        .await?;
+        tracing::info!(
+            "SERVICE: Admin {} {} animation {}",
+            admin_id,
+            if hidden { "hid" } else { "unhid" },
+            animation_id
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,368 @@
+// klyja/backend/src/presence.rs
+// Live rooms: everyone with an animation open in the editor joins its room over a
+// WebSocket and reports what they are doing with it (viewing or editing, the frame
+// they are on, where their cursor is on the sphere). Each change is broadcast to the
+// whole room, so collaborators can see each other working. Rooms live in this
+// process's memory and disappear when their last member leaves.
+//
+// Members' access is checked again whenever this process changes who may see the
+// animation, and every ACCESS_RECHECK_INTERVAL for changes made on other servers;
+// members who lost it are sent away. A connection's updates reach the room at most
+// once per UPDATE_INTERVAL, quicker ones merged into the next.
+use crate::{
+    errors::AppError,
+    models::{LiveMessage, PresenceEntry, PresenceMode, PresenceUpdate, SphereCursor},
+    services::{require_access, AnimationAccess},
+    DbPool,
+};
+use axum::extract::ws::{Message, WebSocket};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tokio::time::Instant;
+
+/// Room updates a slow member may fall behind by; each carries the whole room, so
+/// one that misses some only needs the latest.
+const ROOM_BACKLOG: usize = 16;
+
+/// How often members' access is checked for changes made on other servers.
+const ACCESS_RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The least time between two of a connection's updates reaching the room.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Room {
+    members: HashMap<u64, PresenceEntry>,
+    updates: broadcast::Sender<String>,
+    /// Marked changed whenever who may see the animation may have changed.
+    access: watch::Sender<()>,
+}
+
+impl Room {
+    /// Sends everyone in the room its current members, earliest to join first.
+    fn broadcast(&self, animation_id: i32) {
+        let mut members: Vec<PresenceEntry> = self.members.values().cloned().collect();
+        members.sort_by_key(|member| member.connection_id);
+        let message = LiveMessage::Presence {
+            animation_id,
+            members,
+        };
+        if let Ok(json) = serde_json::to_string(&message) {
+            // Fails only when nobody is listening, which is fine
+            let _ = self.updates.send(json);
+        }
+    }
+}
+
+fn rooms() -> MutexGuard<'static, HashMap<i32, Room>> {
+    static ROOMS: OnceLock<Mutex<HashMap<i32, Room>>> = OnceLock::new();
+    ROOMS
+        .get_or_init(Default::default)
+        .lock()
+        // Every change leaves the rooms consistent, so a panic elsewhere can't spoil them
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Adds a connection to the animation's room, returning its ID, the room's updates,
+/// starting with the one announcing it, and the room's access changes.
+pub(crate) fn join(
+    animation_id: i32,
+    user_id: i32,
+    username: String,
+) -> (u64, broadcast::Receiver<String>, watch::Receiver<()>) {
+    static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+    let connection_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+
+    let mut rooms = rooms();
+    let room = rooms.entry(animation_id).or_insert_with(|| Room {
+        members: HashMap::new(),
+        updates: broadcast::channel(ROOM_BACKLOG).0,
+        access: watch::channel(()).0,
+    });
+    let receiver = room.updates.subscribe();
+    let access = room.access.subscribe();
+    room.members.insert(
+        connection_id,
+        PresenceEntry {
+            connection_id,
+            user_id,
+            username,
+            mode: PresenceMode::Viewing,
+            frame: None,
+            cursor: None,
+        },
+    );
+    room.broadcast(animation_id);
+    (connection_id, receiver, access)
+}
+
+/// Has the animation's members' access checked again, after a change to who may
+/// see or edit it.
+pub(crate) fn access_changed(animation_id: i32) {
+    if let Some(room) = rooms().get(&animation_id) {
+        room.access.send_replace(());
+    }
+}
+
+/// Why a member's update can't be applied, if it can't. Editing needs `can_edit`.
+pub(crate) fn check_update(presence: &PresenceUpdate, can_edit: bool) -> Result<(), String> {
+    if presence.mode == Some(PresenceMode::Editing) && !can_edit {
+        return Err("You can view this animation but not edit it".to_string());
+    }
+    if presence.frame.is_some_and(|frame| frame < 0) {
+        return Err("Frames start at 0".to_string());
+    }
+    if let Some(Some(SphereCursor { lat, lon })) = presence.cursor {
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(
+                "Cursors need a latitude within ±90 and a longitude within ±180".to_string(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Folds a newer update into one not yet applied; the newer one's fields win.
+pub(crate) fn merge_update(pending: &mut PresenceUpdate, newer: PresenceUpdate) {
+    if newer.mode.is_some() {
+        pending.mode = newer.mode;
+    }
+    if newer.frame.is_some() {
+        pending.frame = newer.frame;
+    }
+    if newer.cursor.is_some() {
+        pending.cursor = newer.cursor;
+    }
+}
+
+/// Applies a member's update and tells the room. Editing needs `can_edit`.
+pub(crate) fn update(
+    animation_id: i32,
+    connection_id: u64,
+    presence: PresenceUpdate,
+    can_edit: bool,
+) -> Result<(), String> {
+    check_update(&presence, can_edit)?;
+
+    let mut rooms = rooms();
+    let Some(room) = rooms.get_mut(&animation_id) else {
+        return Ok(());
+    };
+    if let Some(member) = room.members.get_mut(&connection_id) {
+        if let Some(mode) = presence.mode {
+            member.mode = mode;
+        }
+        if let Some(frame) = presence.frame {
+            member.frame = Some(frame);
+        }
+        if let Some(cursor) = presence.cursor {
+            member.cursor = cursor;
+        }
+    }
+    room.broadcast(animation_id);
+    Ok(())
+}
+
+/// Removes a connection from its room, closing the room if it was the last one.
+pub(crate) fn leave(animation_id: i32, connection_id: u64) {
+    let mut rooms = rooms();
+    let Some(room) = rooms.get_mut(&animation_id) else {
+        return;
+    };
+    room.members.remove(&connection_id);
+    if room.members.is_empty() {
+        rooms.remove(&animation_id);
+    } else {
+        room.broadcast(animation_id);
+    }
+}
+
+pub struct PresenceService;
+
+impl PresenceService {
+    /// Who the caller is to the room: their username, and whether they may edit.
+    /// Joining needs view access to the animation.
+    pub async fn join_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: i32,
+    ) -> Result<(String, bool), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        let access = require_access(
+            &mut conn,
+            animation_id,
+            Some(caller_id),
+            AnimationAccess::View,
+        )
+        .await?;
+        use crate::schema::users;
+
+        let username = users::table
+            .find(caller_id)
+            .select(users::username)
+            .first::<String>(&mut conn)
+            .await?;
+        Ok((username, access >= AnimationAccess::Edit))
+    }
+
+    /// Whether a member may still edit the animation, or `None` once they may no
+    /// longer see it at all.
+    pub async fn recheck_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: i32,
+    ) -> Result<Option<bool>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        match require_access(
+            &mut conn,
+            animation_id,
+            Some(caller_id),
+            AnimationAccess::View,
+        )
+        .await
+        {
+            Ok(access) => Ok(Some(access >= AnimationAccess::Edit)),
+            Err(AppError::NotFound(_) | AppError::Forbidden(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Runs one member's connection: relays the room's updates to them and their
+    /// updates to the room, until either side closes it or the member loses access.
+    pub async fn run_session(
+        mut socket: WebSocket,
+        pool: DbPool,
+        animation_id: i32,
+        user_id: i32,
+        username: String,
+        mut can_edit: bool,
+    ) {
+        let (connection_id, mut updates, mut access) = join(animation_id, user_id, username);
+        tracing::info!(
+            "LIVE: User {} joined animation {} as connection {}",
+            user_id,
+            animation_id,
+            connection_id
+        );
+        let mut recheck = tokio::time::interval_at(
+            Instant::now() + ACCESS_RECHECK_INTERVAL,
+            ACCESS_RECHECK_INTERVAL,
+        );
+        // Updates received since the last one reached the room, and when the next may
+        let mut pending: Option<PresenceUpdate> = None;
+        let mut next_update = Instant::now();
+        loop {
+            let mut refused = None;
+            let mut check_access = false;
+            tokio::select! {
+                incoming = socket.recv() => match incoming {
+                    Some(Ok(Message::Text(text))) => {
+                        refused = match serde_json::from_str::<PresenceUpdate>(&text) {
+                            Ok(presence) => check_update(&presence, can_edit)
+                                .map(|()| {
+                                    let merged = pending.get_or_insert_with(Default::default);
+                                    merge_update(merged, presence);
+                                })
+                                .err(),
+                            Err(err) => Some(format!("Unreadable presence update: {}", err)),
+                        };
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {} // Pings are answered for us; nothing else means anything
+                },
+                _ = tokio::time::sleep_until(next_update), if pending.is_some() => {
+                    if let Some(presence) = pending.take() {
+                        // Access may have narrowed since it was checked on arrival
+                        refused = update(animation_id, connection_id, presence, can_edit).err();
+                    }
+                    next_update = Instant::now() + UPDATE_INTERVAL;
+                }
+                outgoing = updates.recv() => match outgoing {
+                    Ok(json) => {
+                        if socket.send(Message::Text(json)).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Missed updates are superseded by the ones still queued
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                changed = access.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    check_access = true;
+                }
+                _ = recheck.tick() => check_access = true,
+            }
+            if check_access {
+                let Some(editor) = Self::recheck(&pool, animation_id, user_id, can_edit).await
+                else {
+                    Self::send_away(&mut socket).await;
+                    break;
+                };
+                Self::lose_edit(animation_id, connection_id, can_edit, editor);
+                can_edit = editor;
+            }
+            if let Some(message) = refused {
+                let reply =
+                    serde_json::to_string(&LiveMessage::Error { message }).unwrap_or_default();
+                if socket.send(Message::Text(reply)).await.is_err() {
+                    break;
+                }
+            }
+        }
+        leave(animation_id, connection_id);
+        tracing::info!(
+            "LIVE: Connection {} left animation {}",
+            connection_id,
+            animation_id
+        );
+    }
+
+    /// Whether the member may still edit, or `None` once they may not see the
+    /// animation. A failed check leaves their access as it was.
+    async fn recheck(
+        pool: &DbPool,
+        animation_id: i32,
+        user_id: i32,
+        can_edit: bool,
+    ) -> Option<bool> {
+        match Self::recheck_logic(pool, animation_id, user_id).await {
+            Ok(access) => access,
+            Err(err) => {
+                tracing::warn!(
+                    "LIVE: Couldn't recheck user {} on animation {}: {:?}",
+                    user_id,
+                    animation_id,
+                    err
+                );
+                Some(can_edit)
+            }
+        }
+    }
+
+    /// Shows a member who could edit and no longer may as viewing.
+    fn lose_edit(animation_id: i32, connection_id: u64, could_edit: bool, can_edit: bool) {
+        if could_edit && !can_edit {
+            let viewing = PresenceUpdate {
+                mode: Some(PresenceMode::Viewing),
+                ..Default::default()
+            };
+            let _ = update(animation_id, connection_id, viewing, false);
+        }
+    }
+
+    /// Tells a member who may no longer see the animation why they are leaving, and
+    /// closes their socket.
+    async fn send_away(socket: &mut WebSocket) {
+        let message = "You no longer have access to this animation".to_string();
+        let reply = serde_json::to_string(&LiveMessage::Error { message }).unwrap_or_default();
+        let _ = socket.send(Message::Text(reply)).await;
+        let _ = socket.send(Message::Close(None)).await;
+    }
+}
@@ -0,0 +1,192 @@
+// klyja/backend/src/publishing.rs
+// Publication: owners make their animations readable by anyone, either now or at a
+// time they pick, so a series can be queued up and released on schedule. A scheduled
+// publication is a job queued to run at that time; it publishes the animation only if
+// the schedule still says so, so rescheduling or cancelling needs no job bookkeeping.
+use crate::{
+    cache,
+    errors::AppError,
+    jobs::{self, Job},
+    models::{PublicationRequest, PublicationStatus, WebhookEvent},
+    presence,
+    services::{require_access, run_in_transaction, AnimationAccess},
+    webhooks, DbPool,
+};
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+async fn publication_status(
+    conn: &mut AsyncPgConnection,
+    animation_id: i32,
+) -> Result<PublicationStatus, AppError> {
+    use crate::schema::animations;
+
+    animations::table
+        .find(animation_id)
+        .select((
+            animations::id,
+            animations::published_at,
+            animations::publish_at,
+        ))
+        .first::<PublicationStatus>(conn)
+        .await
+        .map_err(AppError::from)
+}
+
+/// Publishes the animation now, clearing any schedule, and tells webhooks about it.
+/// An animation that is already published keeps its original time.
+async fn publish_now(conn: &mut AsyncPgConnection, animation_id: i32) -> Result<(), AppError> {
+    use crate::schema::animations;
+
+    let (already_published, version) = animations::table
+        .find(animation_id)
+        .select((
+            animations::published_at.is_not_null(),
+            animations::lock_version,
+        ))
+        .first::<(bool, i32)>(conn)
+        .await?;
+    if already_published {
+        diesel::update(animations::table.find(animation_id))
+            .set(animations::publish_at.eq(None::<NaiveDateTime>))
+            .execute(conn)
+            .await?;
+        return Ok(());
+    }
+    diesel::update(animations::table.find(animation_id))
+        .set((
+            animations::published_at.eq(now.nullable()),
+            animations::publish_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(conn)
+        .await?;
+    webhooks::enqueue_event(conn, animation_id, WebhookEvent::Published, version).await?;
+    tracing::info!("SERVICE: Published animation {}", animation_id);
+    Ok(())
+}
+
+/// Runs a scheduled publication, unless it was cancelled or moved later since it was queued.
+pub async fn publish_if_due(pool: &DbPool, animation_id: i32) -> Result<(), AppError> {
+    let outcome = run_in_transaction(pool, |conn| {
+        async move {
+            use crate::schema::animations;
+
+            let due = animations::table
+                .find(animation_id)
+                .filter(animations::publish_at.le(now.nullable()))
+                .select(animations::id)
+                .for_update()
+                .first::<i32>(conn)
+                .await
+                .optional()?;
+            match due {
+                Some(_) => publish_now(conn, animation_id).await,
+                None => {
+                    tracing::info!(
+                        "JOB: Animation {} isn't due for publication, skipping",
+                        animation_id
+                    );
+                    Ok(())
+                }
+            }
+        }
+        .scope_boxed()
+    })
+    .await;
+    cache::invalidate(animation_id).await;
+    outcome
+}
+
+pub struct PublicationService;
+
+impl PublicationService {
+    /// Publishes an animation now, or schedules it for `publish_at` if that is still
+    /// ahead. Scheduling again replaces the earlier time. Only the owner may publish.
+    pub async fn publish_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: i32,
+        request: PublicationRequest,
+    ) -> Result<PublicationStatus, AppError> {
+        let publish_at = request
+            .publish_at
+            .filter(|at| *at > chrono::Utc::now().naive_utc());
+        let status = run_in_transaction(pool, |conn| {
+            async move {
+                require_access(conn, animation_id, Some(caller_id), AnimationAccess::Owner).await?;
+                use crate::schema::animations;
+
+                match publish_at {
+                    Some(at) => {
+                        let published = animations::table
+                            .find(animation_id)
+                            .select(animations::published_at.is_not_null())
+                            .for_update()
+                            .first::<bool>(conn)
+                            .await?;
+                        if published {
+                            return Err(AppError::BadRequest(format!(
+                                "Animation {} is already published",
+                                animation_id
+                            )));
+                        }
+                        diesel::update(animations::table.find(animation_id))
+                            .set(animations::publish_at.eq(at))
+                            .execute(conn)
+                            .await?;
+                        jobs::enqueue_at(
+                            conn,
+                            &Job::PublishAnimation { animation_id },
+                            Some(animation_id),
+                            at,
+                        )
+                        .await?;
+                        tracing::info!(
+                            "SERVICE: Animation {} scheduled for publication at {}",
+                            animation_id,
+                            at
+                        );
+                    }
+                    None => publish_now(conn, animation_id).await?,
+                }
+                publication_status(conn, animation_id).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+        cache::invalidate(animation_id).await;
+        Ok(status)
+    }
+
+    /// Makes a published animation private again and cancels any scheduled publication.
+    pub async fn unpublish_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: i32,
+    ) -> Result<PublicationStatus, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(
+            &mut conn,
+            animation_id,
+            Some(caller_id),
+            AnimationAccess::Owner,
+        )
+        .await?;
+        use crate::schema::animations;
+
+        diesel::update(animations::table.find(animation_id))
+            .set((
+                animations::published_at.eq(None::<NaiveDateTime>),
+                animations::publish_at.eq(None::<NaiveDateTime>),
+            ))
+            .execute(&mut conn)
+            .await?;
+        cache::invalidate(animation_id).await;
+        presence::access_changed(animation_id);
+        tracing::info!("SERVICE: Unpublished animation {}", animation_id);
+        publication_status(&mut conn, animation_id).await
+    }
+}
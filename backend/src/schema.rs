@@ -1,5 +1,51 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    animation_collaborators (animation_id, user_id) {
+        animation_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 16]
+        role -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    animation_daily_views (animation_id, day) {
+        animation_id -> Int4,
+        day -> Date,
+        views -> Int8,
+    }
+}
+
+diesel::table! {
+    animation_drafts (animation_id, user_id) {
+        animation_id -> Int4,
+        user_id -> Int4,
+        protobuf_data -> Bytea,
+        based_on_version -> Int4,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    animation_thumbnails (animation_id) {
+        animation_id -> Int4,
+        png_data -> Bytea,
+        updated_at -> Timestamp,
+        rendered_by_server -> Bool,
+    }
+}
+
+diesel::table! {
+    animation_versions (animation_id, version) {
+        animation_id -> Int4,
+        version -> Int4,
+        protobuf_data -> Bytea,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     animations (id) {
         id -> Int4,
@@ -8,5 +54,328 @@ diesel::table! {
         protobuf_data -> Bytea,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        owner_id -> Nullable<Int4>,
+        #[max_length = 64]
+        content_sha256 -> Nullable<Varchar>,
+        lock_version -> Int4,
+        #[max_length = 255]
+        animation_uuid -> Nullable<Varchar>,
+        description -> Nullable<Text>,
+        #[max_length = 100]
+        license -> Nullable<Varchar>,
+        #[max_length = 1000]
+        source_attribution -> Nullable<Varchar>,
+        hidden_at -> Nullable<Timestamp>,
+        folder_id -> Nullable<Int4>,
+        published_at -> Nullable<Timestamp>,
+        publish_at -> Nullable<Timestamp>,
+        created_by -> Nullable<Int4>,
+        last_edited_by -> Nullable<Int4>,
+        encoded_bytes -> Int4,
+        feature_count -> Nullable<Int4>,
+        point_count -> Nullable<Int4>,
+        keyframe_count -> Nullable<Int4>,
+        format_version -> Int4,
+        embed_generation -> Int4,
+    }
+}
+
+diesel::table! {
+    api_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 255]
+        name -> Varchar,
+        #[max_length = 64]
+        token_sha256 -> Varchar,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    backups (id) {
+        id -> Int4,
+        #[max_length = 8]
+        kind -> Varchar,
+        #[max_length = 255]
+        file_name -> Varchar,
+        #[max_length = 16]
+        status -> Varchar,
+        animation_count -> Nullable<Int4>,
+        skipped_count -> Nullable<Int4>,
+        error -> Nullable<Text>,
+        requested_by -> Nullable<Int4>,
+        job_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    export_jobs (id) {
+        id -> Int4,
+        animation_id -> Int4,
+        #[max_length = 8]
+        format -> Varchar,
+        size -> Int4,
+        fps -> Int4,
+        #[max_length = 16]
+        status -> Varchar,
+        error -> Nullable<Text>,
+        output -> Nullable<Bytea>,
+        created_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+        job_id -> Nullable<Int4>,
+        frame_step -> Int4,
+    }
+}
+
+diesel::table! {
+    folders (id) {
+        id -> Int4,
+        owner_id -> Int4,
+        parent_id -> Nullable<Int4>,
+        #[max_length = 255]
+        name -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    import_entries (id) {
+        id -> Int4,
+        import_id -> Int4,
+        #[max_length = 255]
+        file_name -> Varchar,
+        #[max_length = 16]
+        status -> Varchar,
+        data -> Nullable<Bytea>,
+        animation_id -> Nullable<Int4>,
+        error -> Nullable<Text>,
+        job_id -> Nullable<Int4>,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    imports (id) {
+        id -> Int4,
+        user_id -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    invitations (id) {
+        id -> Int4,
+        #[max_length = 64]
+        token_sha256 -> Varchar,
+        animation_id -> Int4,
+        invited_by -> Int4,
+        #[max_length = 255]
+        invitee -> Varchar,
+        #[max_length = 16]
+        role -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    jobs (id) {
+        id -> Int4,
+        #[max_length = 32]
+        kind -> Varchar,
+        payload -> Jsonb,
+        animation_id -> Nullable<Int4>,
+        #[max_length = 16]
+        status -> Varchar,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        run_at -> Timestamp,
+        started_at -> Nullable<Timestamp>,
+        finished_at -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    password_resets (token_sha256) {
+        #[max_length = 64]
+        token_sha256 -> Varchar,
+        user_id -> Int4,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    reports (id) {
+        id -> Int4,
+        animation_id -> Int4,
+        reporter_id -> Int4,
+        #[max_length = 1000]
+        reason -> Varchar,
+        created_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    restored_animations (file_name, source_id) {
+        #[max_length = 255]
+        file_name -> Varchar,
+        source_id -> Int4,
+        animation_id -> Int4,
+    }
+}
+
+diesel::table! {
+    sessions (token) {
+        #[max_length = 64]
+        token -> Varchar,
+        user_id -> Int4,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        id -> Int4,
+        #[max_length = 512]
+        user_agent -> Nullable<Varchar>,
+        #[max_length = 64]
+        ip -> Nullable<Varchar>,
+        last_seen_at -> Timestamp,
+        new_device -> Bool,
+    }
+}
+
+diesel::table! {
+    stars (animation_id, user_id) {
+        animation_id -> Int4,
+        user_id -> Int4,
+        created_at -> Timestamp,
     }
 }
+
+diesel::table! {
+    templates (id) {
+        id -> Int4,
+        animation_id -> Int4,
+        #[max_length = 255]
+        name -> Varchar,
+        description -> Nullable<Text>,
+        sort_order -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    upload_parts (upload_id, part_number) {
+        upload_id -> Int4,
+        part_number -> Int4,
+        data -> Bytea,
+    }
+}
+
+diesel::table! {
+    uploads (id) {
+        id -> Int4,
+        user_id -> Int4,
+        animation_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    users (id) {
+        id -> Int4,
+        #[max_length = 255]
+        username -> Varchar,
+        created_at -> Timestamp,
+        storage_quota_bytes -> Int8,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        #[max_length = 255]
+        password_hash -> Nullable<Varchar>,
+        is_admin -> Bool,
+        max_animations -> Int4,
+        max_versions_per_animation -> Int4,
+    }
+}
+
+diesel::table! {
+    webhooks (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 2048]
+        url -> Varchar,
+        #[max_length = 255]
+        secret -> Varchar,
+        events -> Array<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(animation_collaborators -> animations (animation_id));
+diesel::joinable!(animation_collaborators -> users (user_id));
+diesel::joinable!(animation_daily_views -> animations (animation_id));
+diesel::joinable!(animation_drafts -> animations (animation_id));
+diesel::joinable!(animation_drafts -> users (user_id));
+diesel::joinable!(animation_thumbnails -> animations (animation_id));
+diesel::joinable!(animation_versions -> animations (animation_id));
+diesel::joinable!(animations -> folders (folder_id));
+diesel::joinable!(animations -> users (owner_id));
+diesel::joinable!(api_tokens -> users (user_id));
+diesel::joinable!(backups -> jobs (job_id));
+diesel::joinable!(backups -> users (requested_by));
+diesel::joinable!(export_jobs -> animations (animation_id));
+diesel::joinable!(export_jobs -> jobs (job_id));
+diesel::joinable!(folders -> users (owner_id));
+diesel::joinable!(import_entries -> animations (animation_id));
+diesel::joinable!(import_entries -> imports (import_id));
+diesel::joinable!(import_entries -> jobs (job_id));
+diesel::joinable!(imports -> users (user_id));
+diesel::joinable!(invitations -> animations (animation_id));
+diesel::joinable!(invitations -> users (invited_by));
+diesel::joinable!(jobs -> animations (animation_id));
+diesel::joinable!(password_resets -> users (user_id));
+diesel::joinable!(reports -> animations (animation_id));
+diesel::joinable!(reports -> users (reporter_id));
+diesel::joinable!(restored_animations -> animations (animation_id));
+diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(stars -> animations (animation_id));
+diesel::joinable!(stars -> users (user_id));
+diesel::joinable!(templates -> animations (animation_id));
+diesel::joinable!(upload_parts -> uploads (upload_id));
+diesel::joinable!(uploads -> animations (animation_id));
+diesel::joinable!(uploads -> users (user_id));
+diesel::joinable!(webhooks -> users (user_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    animation_collaborators,
+    animation_daily_views,
+    animation_drafts,
+    animation_thumbnails,
+    animation_versions,
+    animations,
+    api_tokens,
+    backups,
+    export_jobs,
+    folders,
+    import_entries,
+    imports,
+    invitations,
+    jobs,
+    password_resets,
+    reports,
+    restored_animations,
+    sessions,
+    stars,
+    templates,
+    upload_parts,
+    uploads,
+    users,
+    webhooks,
+);
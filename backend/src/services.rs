@@ -1,86 +1,788 @@
 // backend/src/services.rs
 use crate::{
-    errors::AppError,
-    models::{Animation, NewAnimation},
+    cache, db, drafts,
+    errors::{AppError, CountLimit},
+    jobs::{self, Job},
+    models::{
+        Animation, AnimationDetailsRequest, AnimationDiff, AnimationListItem, AnimationMetadata,
+        AnimationSummary, Collaborator, CollaboratorRole, DocumentMetrics, ListSort, NewAnimation,
+        StarStatus, WebhookEvent,
+    },
+    moderation, presence,
     protobuf_gen::MapAnimation,
-    schema, DbPool,
+    schema, webhooks, DbPool,
 };
 use axum::body::Bytes;
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
+use diesel_async::scoped_futures::{ScopedBoxFuture, ScopedFutureExt};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use geco_core::document_json;
+use geco_core::format;
+use geco_core::patch::{apply_patch, DocumentPatch};
+use geco_core::stats::document_stats;
 use prost::Message;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Largest document accepted when `MAX_UPLOAD_BYTES` isn't set.
+pub const DEFAULT_MAX_UPLOAD_BYTES: usize = 8 * 1024 * 1024;
+
+/// Largest thumbnail accepted; previews are small, so anything bigger is a mistake.
+pub const MAX_THUMBNAIL_BYTES: usize = 512 * 1024;
+
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// First byte of stored documents that are zstd-compressed. Field number 0 is
+/// invalid in protobuf, so no encoded document (stored before compression) starts with it.
+pub const COMPRESSED_DOCUMENT_MARKER: u8 = 0x00;
+const DOCUMENT_ZSTD_LEVEL: i32 = 3;
+
+/// Most animations one batch metadata request may ask about.
+pub const MAX_BATCH_METADATA_IDS: usize = 100;
+
+/// Longest description an animation can have, in characters.
+pub const MAX_DESCRIPTION_CHARS: usize = 5000;
+/// Longest license, in characters; room for any SPDX expression in practice.
+pub const MAX_LICENSE_CHARS: usize = 100;
+/// Longest source attribution, in characters.
+pub const MAX_ATTRIBUTION_CHARS: usize = 1000;
+
+/// What a caller may do with one animation, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AnimationAccess {
+    None,
+    View,
+    Edit,
+    Owner,
+}
+
+impl AnimationAccess {
+    /// Unowned animations (saved without a session) stay readable by anyone but
+    /// can't be changed; owned ones are private to the owner and collaborators.
+    pub fn resolve(
+        owner_id: Option<i32>,
+        caller_id: Option<i32>,
+        collaborator_role: Option<CollaboratorRole>,
+    ) -> Self {
+        match (owner_id, caller_id) {
+            (None, _) => AnimationAccess::View,
+            (Some(owner), Some(caller)) if owner == caller => AnimationAccess::Owner,
+            _ => match collaborator_role {
+                Some(CollaboratorRole::Editor) => AnimationAccess::Edit,
+                Some(CollaboratorRole::Viewer) => AnimationAccess::View,
+                None => AnimationAccess::None,
+            },
+        }
+    }
+
+    /// Published animations are readable by anyone, on top of whatever else the caller may do.
+    pub fn with_publication(self, published: bool) -> Self {
+        if published {
+            self.max(AnimationAccess::View)
+        } else {
+            self
+        }
+    }
+}
+
+/// Looks up the caller's access to an animation and fails unless it is at least `needed`.
+/// Anonymous callers get 401 so the client knows signing in may help; signed-in
+/// callers without access get 403. Animations hidden by an admin are 404 to
+/// everyone but their owner and the admins.
+pub(crate) async fn require_access(
+    conn: &mut AsyncPgConnection,
+    animation_id: i32,
+    caller_id: Option<i32>,
+    needed: AnimationAccess,
+) -> Result<AnimationAccess, AppError> {
+    let (owner_id, hidden_at, published_at) = schema::animations::table
+        .find(animation_id)
+        .select((
+            schema::animations::owner_id,
+            schema::animations::hidden_at,
+            schema::animations::published_at,
+        ))
+        .first::<(Option<i32>, Option<NaiveDateTime>, Option<NaiveDateTime>)>(conn)
+        .await?;
+    let is_owner = owner_id.is_some() && owner_id == caller_id;
+    if hidden_at.is_some() && !is_owner && !moderation::is_admin(conn, caller_id).await? {
+        return Err(AppError::NotFound(format!(
+            "Animation {} not found",
+            animation_id
+        )));
+    }
+
+    let collaborator_role = match caller_id {
+        Some(caller) => schema::animation_collaborators::table
+            .find((animation_id, caller))
+            .select(schema::animation_collaborators::role)
+            .first::<String>(conn)
+            .await
+            .optional()?
+            .and_then(|role| CollaboratorRole::parse(&role)),
+        None => None,
+    };
+
+    let access = AnimationAccess::resolve(owner_id, caller_id, collaborator_role)
+        .with_publication(published_at.is_some());
+    if access >= needed {
+        Ok(access)
+    } else if caller_id.is_none() {
+        Err(AppError::Unauthorized(
+            "Sign in to access this animation".to_string(),
+        ))
+    } else {
+        Err(AppError::Forbidden(format!(
+            "You don't have permission to do that with animation {}",
+            animation_id
+        )))
+    }
+}
+
+/// Largest document save, update, patch and draft uploads accept, read once from
+/// `MAX_UPLOAD_BYTES` so each deployment can set its own.
+pub fn max_upload_bytes() -> usize {
+    static LIMIT: OnceLock<usize> = OnceLock::new();
+    *LIMIT.get_or_init(|| {
+        std::env::var("MAX_UPLOAD_BYTES")
+            .ok()
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES)
+    })
+}
+
+diesel::sql_function!(fn octet_length(data: diesel::sql_types::Bytea) -> diesel::sql_types::Integer);
+
+/// Fails if storing `new_bytes` more would take the user past their storage quota.
+/// Every saved version of their animations counts, the current ones included, since
+/// each is kept in `animation_versions`. Locks the user's row, so call it in the
+/// transaction that writes the document.
+async fn require_storage(
+    conn: &mut AsyncPgConnection,
+    user_id: i32,
+    new_bytes: usize,
+) -> Result<(), AppError> {
+    use crate::schema::users;
+
+    let quota = users::table
+        .find(user_id)
+        .select(users::storage_quota_bytes)
+        .for_update()
+        .first::<i64>(conn)
+        .await?;
+
+    let used = stored_bytes(conn, user_id).await?;
+
+    if used + new_bytes as i64 > quota {
+        return Err(AppError::QuotaExceeded(format!(
+            "Saving {} bytes would exceed your storage quota ({} of {} bytes used)",
+            new_bytes, used, quota
+        )));
+    }
+    Ok(())
+}
+
+/// Bytes the user's animations take up: every version kept of each, as stored.
+/// Reads only the sizes, not the documents.
+async fn stored_bytes(conn: &mut AsyncPgConnection, user_id: i32) -> Result<i64, AppError> {
+    use crate::schema::{animation_versions, animations};
+
+    Ok(animation_versions::table
+        .inner_join(animations::table)
+        .filter(animations::owner_id.eq(user_id))
+        .select(diesel::dsl::sum(octet_length(
+            animation_versions::protobuf_data,
+        )))
+        .first::<Option<i64>>(conn)
+        .await?
+        .unwrap_or(0))
+}
+
+/// Fails if the user already owns as many animations as they may. Call it after
+/// `require_storage`, whose lock on the user's row keeps concurrent saves counting in turn.
+async fn require_animation_slot(
+    conn: &mut AsyncPgConnection,
+    user_id: i32,
+) -> Result<(), AppError> {
+    use crate::schema::{animations, users};
+
+    let max = users::table
+        .find(user_id)
+        .select(users::max_animations)
+        .first::<i32>(conn)
+        .await?;
+    let owned = animations::table
+        .filter(animations::owner_id.eq(user_id))
+        .count()
+        .get_result::<i64>(conn)
+        .await?;
+    if owned >= i64::from(max) {
+        return Err(AppError::LimitExceeded {
+            limit: CountLimit::Animations,
+            max: i64::from(max),
+        });
+    }
+    Ok(())
+}
+
+/// Makes room for another save of the animation by deleting its oldest versions
+/// beyond the most its owner's animations may keep, current version included.
+/// Returns how many went. The animation's row must already be locked.
+async fn prune_versions(
+    conn: &mut AsyncPgConnection,
+    owner_id: i32,
+    animation_id: i32,
+) -> Result<usize, AppError> {
+    use crate::schema::{animation_versions, users};
+
+    let max = users::table
+        .find(owner_id)
+        .select(users::max_versions_per_animation)
+        .first::<i32>(conn)
+        .await?;
+    // The save about to happen takes one of the slots
+    let kept = i64::from(max.max(1) - 1);
+    let newest_pruned = animation_versions::table
+        .filter(animation_versions::animation_id.eq(animation_id))
+        .order(animation_versions::version.desc())
+        .offset(kept)
+        .select(animation_versions::version)
+        .first::<i32>(conn)
+        .await
+        .optional()?;
+    let Some(newest_pruned) = newest_pruned else {
+        return Ok(0);
+    };
+    Ok(diesel::delete(
+        animation_versions::table
+            .filter(animation_versions::animation_id.eq(animation_id))
+            .filter(animation_versions::version.le(newest_pruned)),
+    )
+    .execute(conn)
+    .await?)
+}
+
+/// Encodes a document's protobuf bytes the way they are stored in `protobuf_data`.
+pub fn compress_document(protobuf_data: &[u8]) -> Result<Vec<u8>, AppError> {
+    let mut stored = vec![COMPRESSED_DOCUMENT_MARKER];
+    zstd::stream::copy_encode(protobuf_data, &mut stored, DOCUMENT_ZSTD_LEVEL)
+        .map_err(|err| AppError::Internal(format!("Compressing document failed: {}", err)))?;
+    Ok(stored)
+}
+
+/// Turns stored `protobuf_data` back into protobuf bytes, passing through
+/// documents saved before compression was introduced.
+pub fn decompress_document(stored: Vec<u8>) -> Result<Vec<u8>, AppError> {
+    match stored.split_first() {
+        Some((&COMPRESSED_DOCUMENT_MARKER, compressed)) => zstd::stream::decode_all(compressed)
+            .map_err(|err| AppError::Internal(format!("Decompressing document failed: {}", err))),
+        _ => Ok(stored),
+    }
+}
+
+/// Hex SHA-256 of a document's protobuf bytes, as stored in `content_sha256`.
+pub fn content_sha256(protobuf_data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    use std::fmt::Write;
+
+    Sha256::digest(protobuf_data)
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+/// Protobuf bytes of a document sent in the JSON form geco exports, which is also
+/// what `Accept: application/json` loads return.
+pub fn document_from_json(json: &[u8]) -> Result<Bytes, AppError> {
+    let json = std::str::from_utf8(json)
+        .map_err(|_| AppError::BadRequest("Animation JSON must be UTF-8".to_string()))?;
+    let map_animation = document_json::from_json(json).map_err(AppError::BadRequest)?;
+    Ok(Bytes::from(map_animation.encode_to_vec()))
+}
+
+/// A document's JSON form, from its protobuf bytes.
+pub fn document_to_json(protobuf_data: &[u8]) -> Result<String, AppError> {
+    let map_animation = MapAnimation::decode(protobuf_data)?;
+    document_json::to_json(&map_animation).map_err(AppError::Internal)
+}
+
+/// Decodes an uploaded document, converting it if it is in an older format, and
+/// applies the same checks geco runs before saving. Returns it with whether it was
+/// converted.
+fn decode_valid_document(animation_data_bytes: &Bytes) -> Result<(MapAnimation, bool), AppError> {
+    // The router limits bodies too; this covers routers built without that layer
+    if animation_data_bytes.len() > max_upload_bytes() {
+        return Err(AppError::BodyTooLarge {
+            max_bytes: max_upload_bytes(),
+            received_bytes: Some(animation_data_bytes.len()),
+        });
+    }
+    let mut map_animation = MapAnimation::decode(animation_data_bytes.clone())?;
+    let converted = format::upgrade(&mut map_animation).map_err(AppError::BadRequest)?;
+    // Reject documents geco would refuse to save, whatever client sent them
+    klyja_validate::validate(&map_animation).map_err(AppError::InvalidDocument)?;
+    Ok((map_animation, converted))
+}
+
+/// The sizes stored with a document that takes `stored_bytes` once compressed.
+pub fn measure_document(document: &MapAnimation, stored_bytes: usize) -> DocumentMetrics {
+    let count = |n: usize| i32::try_from(n).unwrap_or(i32::MAX);
+    DocumentMetrics {
+        encoded_bytes: count(stored_bytes),
+        feature_count: count(document.polygons.len()),
+        point_count: count(document.polygons.iter().map(|p| p.points.len()).sum()),
+        keyframe_count: count(document_stats(document).keyframe_count),
+        format_version: count(document.format_version as usize),
+    }
+}
+
+/// The user's most recently changed animation, if it already holds exactly this
+/// document under this name. Autosave resends unchanged documents, and storing
+/// each one again would only pile up identical rows. Returns its ID and version.
+async fn identical_latest_save(
+    conn: &mut AsyncPgConnection,
+    owner: i32,
+    name: &str,
+    hash: &str,
+) -> Result<Option<(i32, i32)>, AppError> {
+    use crate::schema::animations;
+
+    let latest = animations::table
+        .filter(animations::owner_id.eq(owner))
+        .order((animations::updated_at.desc(), animations::id.desc()))
+        .select((
+            animations::id,
+            animations::name,
+            animations::content_sha256,
+            animations::lock_version,
+        ))
+        .first::<(i32, String, Option<String>, i32)>(conn)
+        .await
+        .optional()?;
+    Ok(latest.and_then(|(id, latest_name, latest_hash, version)| {
+        (latest_name == name && latest_hash.as_deref() == Some(hash)).then_some((id, version))
+    }))
+}
+
+/// Runs `work` in a transaction on a pooled connection, committing if it returns
+/// `Ok` and rolling back otherwise. Use it for any change spanning several rows or
+/// tables, so a failure partway through can't leave half of it stored.
+pub async fn run_in_transaction<'a, T, F>(pool: &DbPool, work: F) -> Result<T, AppError>
+where
+    F: for<'r> FnOnce(&'r mut AsyncPgConnection) -> ScopedBoxFuture<'a, 'r, Result<T, AppError>>
+        + Send
+        + 'a,
+    T: Send + 'a,
+{
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    db::timed(AsyncConnection::transaction(&mut *conn, work)).await
+}
+
+/// An uploaded document, validated and in the form it is stored. Prepared before
+/// any transaction starts, so none is held open while decoding and compressing.
+pub(crate) struct PreparedDocument {
+    pub name: String,
+    pub animation_uuid: Option<String>,
+    pub stored: Vec<u8>,
+    pub sha256: String,
+    pub metrics: DocumentMetrics,
+}
+
+impl PreparedDocument {
+    pub(crate) fn from_upload(animation_data_bytes: &Bytes) -> Result<Self, AppError> {
+        let (map_animation, converted) = decode_valid_document(animation_data_bytes)?;
+        // Documents sent in an older format are stored as converted
+        let protobuf_data = if converted {
+            Bytes::from(map_animation.encode_to_vec())
+        } else {
+            animation_data_bytes.clone()
+        };
+        let stored = compress_document(&protobuf_data)?;
+        let metrics = measure_document(&map_animation, stored.len());
+        Ok(PreparedDocument {
+            name: map_animation.name,
+            animation_uuid: Some(map_animation.animation_id).filter(|id| !id.is_empty()),
+            stored,
+            sha256: content_sha256(&protobuf_data),
+            metrics,
+        })
+    }
+}
+
+/// The animation saved under this document ID, with its owner, name, hash and
+/// version. Only one row may hold a document ID, so it can be looked up by it.
+async fn saved_under_uuid(
+    conn: &mut AsyncPgConnection,
+    animation_uuid: &str,
+) -> Result<Option<(i32, Option<i32>, String, Option<String>, i32)>, AppError> {
+    use crate::schema::animations;
+
+    Ok(animations::table
+        .filter(animations::animation_uuid.eq(animation_uuid))
+        .select((
+            animations::id,
+            animations::owner_id,
+            animations::name,
+            animations::content_sha256,
+            animations::lock_version,
+        ))
+        .first::<(i32, Option<i32>, String, Option<String>, i32)>(conn)
+        .await
+        .optional()?)
+}
+
+/// What a save that can land on an existing animation expects of it, from `If-Match`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfMatch {
+    // Nothing sent: the save may not overwrite anything
+    Absent,
+    // `*`: overwrite whatever version is there
+    Any,
+    // The version the caller's changes are based on
+    Version(i32),
+}
+
+impl IfMatch {
+    /// The version to check an update against; `None` skips the check.
+    pub fn expected_version(self) -> Option<i32> {
+        match self {
+            IfMatch::Version(version) => Some(version),
+            IfMatch::Absent | IfMatch::Any => None,
+        }
+    }
+}
+
+/// Stores a document as a new animation and queues its thumbnail and webhook
+/// deliveries, returning its ID and version.
+/// An owner's document already saved under its animation_id replaces that save in
+/// place instead, if `if_match` allows it, and an identical latest save is reused.
+/// Run it in a transaction.
+pub(crate) async fn insert_document(
+    conn: &mut AsyncPgConnection,
+    document: &PreparedDocument,
+    owner_id: Option<i32>,
+    if_match: IfMatch,
+) -> Result<(i32, i32), AppError> {
+    let mut animation_uuid = document.animation_uuid.as_deref();
+    if let Some(uuid) = animation_uuid {
+        match saved_under_uuid(conn, uuid).await? {
+            Some((existing_id, existing_owner, name, hash, version))
+                if owner_id.is_some() && existing_owner == owner_id =>
+            {
+                if name == document.name && hash.as_deref() == Some(document.sha256.as_str()) {
+                    return Ok((existing_id, version));
+                }
+                if if_match == IfMatch::Absent {
+                    return Err(AppError::PreconditionRequired(format!(
+                            "Animation {} already holds this document; send If-Match with the ETag of the version you loaded, or * to overwrite",
+                            existing_id
+                        )));
+                }
+                let new_version = replace_document(
+                    conn,
+                    existing_id,
+                    document,
+                    owner_id,
+                    if_match.expected_version(),
+                )
+                .await?;
+                tracing::info!(
+                    "SERVICE: Save updates animation {} in place to version {}",
+                    existing_id,
+                    new_version
+                );
+                return Ok((existing_id, new_version));
+            }
+            // Someone else's save holds this document ID, so this one is stored without it
+            Some(_) => animation_uuid = None,
+            None => {}
+        }
+    }
+    if let Some(owner) = owner_id {
+        if let Some((existing_id, version)) =
+            identical_latest_save(conn, owner, &document.name, &document.sha256).await?
+        {
+            tracing::info!(
+                "SERVICE: Save is identical to animation {}, not stored again",
+                existing_id
+            );
+            return Ok((existing_id, version));
+        }
+        require_storage(conn, owner, document.stored.len()).await?;
+        require_animation_slot(conn, owner).await?;
+    }
+    let saved = diesel::insert_into(schema::animations::table)
+        .values(&NewAnimation {
+            name: &document.name,
+            protobuf_data: &document.stored,
+            owner_id,
+            content_sha256: &document.sha256,
+            animation_uuid,
+            created_by: owner_id,
+            last_edited_by: owner_id,
+            metrics: document.metrics,
+        })
+        .returning(Animation::as_returning())
+        .get_result::<Animation>(conn)
+        .await
+        .map_err(AppError::DatabaseQuery)?;
+    jobs::enqueue(
+        conn,
+        &Job::RenderThumbnail {
+            animation_id: saved.id,
+        },
+        Some(saved.id),
+    )
+    .await?;
+    webhooks::enqueue_event(conn, saved.id, WebhookEvent::Saved, saved.lock_version).await?;
+    Ok((saved.id, saved.lock_version))
+}
+
+/// Replaces an animation's document, which needs editor access, and queues a new
+/// thumbnail and webhook deliveries. With `expected_version`, the save is refused unless the animation is
+/// still at that version. The caller's draft is dropped, now that its work is saved.
+/// Returns the new version. Run it in a transaction.
+pub(crate) async fn replace_document(
+    conn: &mut AsyncPgConnection,
+    animation_id: i32,
+    document: &PreparedDocument,
+    caller_id: Option<i32>,
+    expected_version: Option<i32>,
+) -> Result<i32, AppError> {
+    use crate::schema::animations;
+
+    require_access(conn, animation_id, caller_id, AnimationAccess::Edit).await?;
+    // Locked until commit, so two saves based on the same version can't both pass
+    let (owner, current_version) = animations::table
+        .find(animation_id)
+        .select((animations::owner_id, animations::lock_version))
+        .for_update()
+        .first::<(Option<i32>, i32)>(conn)
+        .await?;
+    if expected_version.is_some_and(|expected| expected != current_version) {
+        return Err(AppError::VersionConflict {
+            animation_id,
+            current_version,
+        });
+    }
+    // Editors' saves count against the owner's quota, not their own
+    if let Some(owner) = owner {
+        // Pruned first, so the versions going free their bytes for this one
+        prune_versions(conn, owner, animation_id).await?;
+        require_storage(conn, owner, document.stored.len()).await?;
+    }
+    let mut animation_uuid = document.animation_uuid.as_deref();
+    if let Some(uuid) = animation_uuid {
+        match saved_under_uuid(conn, uuid).await? {
+            Some((other_id, other_owner, _, _, _)) if other_id != animation_id => {
+                if owner.is_some() && other_owner == owner {
+                    return Err(AppError::BadRequest(format!(
+                        "Animation {} already holds the document with ID '{}'",
+                        other_id, uuid
+                    )));
+                }
+                // Someone else's save holds this document ID, so this one goes without it
+                animation_uuid = None;
+            }
+            _ => {}
+        }
+    }
+    let new_version = diesel::update(animations::table.find(animation_id))
+        .set((
+            animations::name.eq(&document.name),
+            animations::animation_uuid.eq(animation_uuid),
+            animations::protobuf_data.eq(&document.stored),
+            animations::content_sha256.eq(&document.sha256),
+            animations::lock_version.eq(animations::lock_version + 1),
+            animations::last_edited_by.eq(caller_id),
+            document.metrics,
+        ))
+        .returning(animations::lock_version)
+        .get_result::<i32>(conn)
+        .await
+        .map_err(AppError::DatabaseQuery)?;
+    jobs::enqueue(
+        conn,
+        &Job::RenderThumbnail { animation_id },
+        Some(animation_id),
+    )
+    .await?;
+    webhooks::enqueue_event(conn, animation_id, WebhookEvent::Saved, new_version).await?;
+    if let Some(caller) = caller_id {
+        drafts::discard_draft(conn, animation_id, caller).await?;
+    }
+    Ok(new_version)
+}
 
 pub struct AnimationService;
 
 impl AnimationService {
+    /// Stores a new animation and returns its ID. A signed-in caller resending the
+    /// document and name of their latest animation gets that animation's ID instead.
+    /// Resending a changed document they already saved updates it, which `if_match`
+    /// must allow. Returns the ID with the animation's version.
     pub async fn save_animation_logic(
         pool: &DbPool, // Keep as reference
         animation_data_bytes: Bytes,
-    ) -> Result<i32, AppError> {
+        owner_id: Option<i32>, // The signed-in caller, if any, becomes the owner
+        if_match: IfMatch,
+    ) -> Result<(i32, i32), AppError> {
         tracing::info!(
             "SERVICE: Processing save_animation_logic with {} bytes",
             animation_data_bytes.len()
         );
 
-        let map_animation = MapAnimation::decode(animation_data_bytes.clone())?;
-
-        // Clone the pool and other necessary data to move into the blocking task
-        let pool_clone = pool.clone();
-        let name_for_blocking_task = map_animation.name.clone(); // Renamed for clarity
-        let data_for_blocking = animation_data_bytes.clone();
-
-        let saved_animation_id = tokio::task::spawn_blocking(move || {
-            let mut conn = pool_clone.get().map_err(AppError::DatabasePool)?;
-            let new_animation_payload = NewAnimation {
-                name: &name_for_blocking_task, // Use the string cloned for the task
-                protobuf_data: &data_for_blocking,
-            };
-
-            diesel::insert_into(schema::animations::table)
-                .values(&new_animation_payload)
-                .get_result::<Animation>(&mut conn)
-                .map_err(AppError::DatabaseQuery)
-                .map(|anim| anim.id)
+        let document = PreparedDocument::from_upload(&animation_data_bytes)?;
+        let (saved_animation_id, version) = run_in_transaction(pool, |conn| {
+            insert_document(conn, &document, owner_id, if_match).scope_boxed()
         })
-        .await
-        .map_err(|join_err| {
-            AppError::Internal(format!("Tokio spawn_blocking join error: {}", join_err))
-        })??;
+        .await?;
 
         tracing::info!(
             "SERVICE: Animation '{}' saved successfully with ID {}.",
-            map_animation.name, // Use the original map_animation.name for logging here
+            document.name,
             saved_animation_id
         );
-        Ok(saved_animation_id)
+        Ok((saved_animation_id, version))
+    }
+
+    /// Replaces the document of an existing animation; needs editor access. Fails with a
+    /// conflict if the animation is no longer at `expected_version` (`None` skips the
+    /// check). Returns the new version.
+    pub async fn update_animation_logic(
+        pool: &DbPool,
+        animation_id_to_update: i32,
+        animation_data_bytes: Bytes,
+        caller_id: Option<i32>,
+        expected_version: Option<i32>,
+    ) -> Result<i32, AppError> {
+        tracing::info!(
+            "SERVICE: Processing update_animation_logic for ID {} with {} bytes",
+            animation_id_to_update,
+            animation_data_bytes.len()
+        );
+
+        let document = PreparedDocument::from_upload(&animation_data_bytes)?;
+        let new_version = run_in_transaction(pool, |conn| {
+            replace_document(
+                conn,
+                animation_id_to_update,
+                &document,
+                caller_id,
+                expected_version,
+            )
+            .scope_boxed()
+        })
+        .await?;
+
+        tracing::info!(
+            "SERVICE: Animation '{}' (ID: {}) updated successfully to version {}.",
+            document.name,
+            animation_id_to_update,
+            new_version
+        );
+        Ok(new_version)
+    }
+
+    /// Applies a patch made by geco against `base_version` and saves the result as
+    /// the next version; needs editor access. Patches only make sense against the
+    /// version they were made from, so any other version is a conflict.
+    pub async fn patch_animation_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        patch: DocumentPatch,
+        caller_id: Option<i32>,
+        base_version: i32,
+    ) -> Result<i32, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::Edit).await?;
+        use crate::schema::animations;
+
+        let (stored, current_version) = animations::table
+            .find(animation_id)
+            .select((animations::protobuf_data, animations::lock_version))
+            .first::<(Vec<u8>, i32)>(&mut conn)
+            .await?;
+        drop(conn);
+        // Checked again under the row lock when saving; this saves applying a doomed patch
+        if current_version != base_version {
+            return Err(AppError::VersionConflict {
+                animation_id,
+                current_version,
+            });
+        }
+        let mut document = MapAnimation::decode(decompress_document(stored)?.as_slice())?;
+        apply_patch(&mut document, &patch).map_err(AppError::BadRequest)?;
+        let document = PreparedDocument::from_upload(&Bytes::from(document.encode_to_vec()))?;
+
+        let new_version = run_in_transaction(pool, |conn| {
+            replace_document(conn, animation_id, &document, caller_id, Some(base_version))
+                .scope_boxed()
+        })
+        .await?;
+        tracing::info!(
+            "SERVICE: Patched animation {} from version {} to {}",
+            animation_id,
+            base_version,
+            new_version
+        );
+        Ok(new_version)
     }
 
     pub async fn load_animation_logic(
         pool: &DbPool,
         animation_id_to_load: i32,
+        caller_id: Option<i32>,
     ) -> Result<Animation, AppError> {
         tracing::info!(
             "SERVICE: Processing load_animation_logic for ID: {}",
             animation_id_to_load
         );
 
-        let pool_clone = pool.clone();
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(
+            &mut conn,
+            animation_id_to_load,
+            caller_id,
+            AnimationAccess::View,
+        )
+        .await?;
+        use crate::schema::animations::dsl::*;
 
-        let loaded_animation = tokio::task::spawn_blocking(move || {
-            let mut conn = pool_clone.get().map_err(AppError::DatabasePool)?; // Get conn and map r2d2 error
-            use crate::schema::animations::dsl::*;
+        // Every write to the row moves updated_at, so a cached copy stamped alike is current
+        let (created, updated) = db::timed(
+            animations
+                .find(animation_id_to_load)
+                .select((created_at, updated_at))
+                .first::<(NaiveDateTime, NaiveDateTime)>(&mut conn),
+        )
+        .await?;
+        let current = cache::RowStamp {
+            created_at: created,
+            updated_at: updated,
+        };
+        if let Some(cached) = cache::get(animation_id_to_load, current).await {
+            tracing::info!(
+                "SERVICE: Animation {} version {} served from cache.",
+                animation_id_to_load,
+                cached.lock_version
+            );
+            return Ok(cached);
+        }
 
-            let query_result: Result<Animation, diesel::result::Error> = animations
+        let mut loaded_animation = db::timed(
+            animations
                 .find(animation_id_to_load)
                 .select(Animation::as_select())
-                .first::<Animation>(&mut conn);
-
-            // Explicitly convert diesel::result::Error to AppError using your From trait impl
-            query_result.map_err(AppError::from)
-        })
-        .await // Wait for the blocking task
-        .map_err(|join_err| {
-            AppError::Internal(format!("Tokio spawn_blocking join error: {}", join_err))
-        })??; // First ? for JoinError, second ? for AppError from the closure
+                .first::<Animation>(&mut conn),
+        )
+        .await?;
+        drop(conn); // Decompressing and hashing don't need the connection
+        open_document(&mut loaded_animation)?;
+        cache::put(loaded_animation.clone()).await;
 
         tracing::info!(
             "SERVICE: Animation '{}' (ID: {}) loaded successfully.",
@@ -89,4 +791,877 @@ impl AnimationService {
         );
         Ok(loaded_animation)
     }
+
+    /// Lists the animations the caller can load: unowned and published ones, their own
+    /// and those shared with them, with their star counts. `folder` narrows it to one folder.
+    pub async fn list_animations_logic(
+        pool: &DbPool,
+        name_query: Option<String>,
+        folder: Option<i32>,
+        sort: ListSort,
+        caller_id: Option<i32>,
+    ) -> Result<Vec<AnimationListItem>, AppError> {
+        tracing::info!(
+            "SERVICE: Processing list_animations_logic with query: {:?}",
+            name_query
+        );
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::animations::dsl::*;
+        use crate::schema::{animation_collaborators, animation_thumbnails};
+
+        let mut query = animations
+            .left_join(animation_thumbnails::table)
+            .select((
+                AnimationSummary::as_select(),
+                animation_thumbnails::updated_at.nullable(),
+            ))
+            .order(updated_at.desc())
+            .into_boxed();
+        query = match caller_id {
+            Some(caller) => {
+                let shared_with_caller = animation_collaborators::table
+                    .filter(animation_collaborators::user_id.eq(caller))
+                    .select(animation_collaborators::animation_id);
+                query.filter(
+                    owner_id
+                        .is_null()
+                        .or(published_at.is_not_null())
+                        .or(owner_id.eq(caller))
+                        .or(id.eq_any(shared_with_caller)),
+                )
+            }
+            None => query.filter(owner_id.is_null().or(published_at.is_not_null())),
+        };
+        query = match caller_id {
+            Some(caller) => query.filter(hidden_at.is_null().or(owner_id.eq(caller))),
+            None => query.filter(hidden_at.is_null()),
+        };
+        if let Some(q) = name_query
+            .as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+        {
+            query = query.filter(name.ilike(format!("%{}%", escape_like(q))));
+        }
+        if let Some(folder) = folder {
+            query = query.filter(folder_id.eq(folder));
+        }
+
+        let rows =
+            db::timed(query.load::<(AnimationSummary, Option<NaiveDateTime>)>(&mut conn)).await?;
+        let listed_ids: Vec<i32> = rows.iter().map(|(summary, _)| summary.id).collect();
+        let star_counts = star_counts(&mut conn, &listed_ids).await?;
+        let mut summaries = rows
+            .into_iter()
+            .map(|(summary, thumbnail_updated_at)| AnimationListItem {
+                thumbnail_url: thumbnail_updated_at.map(|at| thumbnail_url(summary.id, at)),
+                star_count: star_counts.get(&summary.id).copied().unwrap_or(0),
+                summary,
+            })
+            .collect::<Vec<_>>();
+        if sort == ListSort::Stars {
+            // Stable, so equally starred animations stay most recent first
+            summaries.sort_by_key(|item| std::cmp::Reverse(item.star_count));
+        }
+
+        tracing::info!("SERVICE: Listed {} animations.", summaries.len());
+        Ok(summaries)
+    }
+
+    /// Lists who an animation is shared with; anyone who can load it may ask.
+    pub async fn list_collaborators_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: Option<i32>,
+    ) -> Result<Vec<Collaborator>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::View).await?;
+        use crate::schema::{animation_collaborators, users};
+
+        animation_collaborators::table
+            .inner_join(users::table)
+            .filter(animation_collaborators::animation_id.eq(animation_id))
+            .order(animation_collaborators::created_at.asc())
+            .select((
+                animation_collaborators::user_id,
+                users::username,
+                animation_collaborators::role,
+                animation_collaborators::created_at,
+            ))
+            .load::<Collaborator>(&mut conn)
+            .await
+            .map_err(AppError::from)
+    }
+
+    /// Shares an animation with an existing user, or changes their role if it is
+    /// already shared with them. Only the owner may do this.
+    pub async fn invite_collaborator_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: Option<i32>,
+        invitee_id: i32,
+        role: CollaboratorRole,
+    ) -> Result<(), AppError> {
+        tracing::info!(
+            "SERVICE: Sharing animation {} with user {} as {}",
+            animation_id,
+            invitee_id,
+            role.as_str()
+        );
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::Owner).await?;
+        use crate::schema::{animation_collaborators, users};
+
+        if caller_id == Some(invitee_id) {
+            return Err(AppError::BadRequest(
+                "The owner can't also be a collaborator".to_string(),
+            ));
+        }
+        let invitee_exists = diesel::select(diesel::dsl::exists(users::table.find(invitee_id)))
+            .get_result::<bool>(&mut conn)
+            .await?;
+        if !invitee_exists {
+            return Err(AppError::NotFound(format!("User {} not found", invitee_id)));
+        }
+
+        diesel::insert_into(animation_collaborators::table)
+            .values((
+                animation_collaborators::animation_id.eq(animation_id),
+                animation_collaborators::user_id.eq(invitee_id),
+                animation_collaborators::role.eq(role.as_str()),
+            ))
+            .on_conflict((
+                animation_collaborators::animation_id,
+                animation_collaborators::user_id,
+            ))
+            .do_update()
+            .set(animation_collaborators::role.eq(role.as_str()))
+            .execute(&mut conn)
+            .await?;
+        // A role change may take editing away from someone in the live room
+        presence::access_changed(animation_id);
+        Ok(())
+    }
+
+    /// Stops sharing an animation with a user. The owner may remove anyone;
+    /// collaborators may remove themselves.
+    pub async fn remove_collaborator_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: Option<i32>,
+        collaborator_id: i32,
+    ) -> Result<(), AppError> {
+        tracing::info!(
+            "SERVICE: Removing user {} from animation {}",
+            collaborator_id,
+            animation_id
+        );
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        let needed = if caller_id == Some(collaborator_id) {
+            AnimationAccess::View
+        } else {
+            AnimationAccess::Owner
+        };
+        require_access(&mut conn, animation_id, caller_id, needed).await?;
+        use crate::schema::animation_collaborators;
+
+        let removed =
+            diesel::delete(animation_collaborators::table.find((animation_id, collaborator_id)))
+                .execute(&mut conn)
+                .await?;
+        if removed == 0 {
+            return Err(AppError::NotFound(format!(
+                "User {} is not a collaborator on animation {}",
+                collaborator_id, animation_id
+            )));
+        }
+        presence::access_changed(animation_id);
+        Ok(())
+    }
+
+    /// Replaces an animation's description, license and source attribution; needs
+    /// editor access. Blank fields are cleared. The document and its version are
+    /// left alone.
+    pub async fn update_details_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        request: AnimationDetailsRequest,
+        caller_id: Option<i32>,
+    ) -> Result<(), AppError> {
+        let description = detail_field(
+            "description",
+            request.description,
+            MAX_DESCRIPTION_CHARS,
+            true,
+        )?;
+        let license = detail_field("license", request.license, MAX_LICENSE_CHARS, false)?;
+        let source_attribution = detail_field(
+            "source_attribution",
+            request.source_attribution,
+            MAX_ATTRIBUTION_CHARS,
+            false,
+        )?;
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::Edit).await?;
+        use crate::schema::animations;
+
+        diesel::update(animations::table.find(animation_id))
+            .set((
+                animations::description.eq(description),
+                animations::license.eq(license),
+                animations::source_attribution.eq(source_attribution),
+                animations::last_edited_by.eq(caller_id),
+            ))
+            .execute(&mut conn)
+            .await?;
+        cache::invalidate(animation_id).await;
+        tracing::info!("SERVICE: Updated details of animation {}", animation_id);
+        Ok(())
+    }
+
+    /// Stars or unstars an animation for the caller; anyone who can load it may.
+    /// Doing either twice is the same as doing it once.
+    pub async fn set_star_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: i32,
+        starred: bool,
+    ) -> Result<StarStatus, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(
+            &mut conn,
+            animation_id,
+            Some(caller_id),
+            AnimationAccess::View,
+        )
+        .await?;
+        use crate::schema::stars;
+
+        if starred {
+            diesel::insert_into(stars::table)
+                .values((
+                    stars::animation_id.eq(animation_id),
+                    stars::user_id.eq(caller_id),
+                ))
+                .on_conflict_do_nothing()
+                .execute(&mut conn)
+                .await?;
+        } else {
+            diesel::delete(stars::table.find((animation_id, caller_id)))
+                .execute(&mut conn)
+                .await?;
+        }
+        let star_count = star_counts(&mut conn, &[animation_id])
+            .await?
+            .remove(&animation_id)
+            .unwrap_or(0);
+
+        tracing::info!(
+            "SERVICE: User {} {} animation {}",
+            caller_id,
+            if starred { "starred" } else { "unstarred" },
+            animation_id
+        );
+        Ok(StarStatus {
+            animation_id,
+            starred,
+            star_count,
+        })
+    }
+
+    /// Stores a PNG preview for an animation, replacing any previous one; needs
+    /// editor access.
+    pub async fn put_thumbnail_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        png_data: Bytes,
+        caller_id: Option<i32>,
+    ) -> Result<(), AppError> {
+        tracing::info!(
+            "SERVICE: Storing {}-byte thumbnail for animation {}",
+            png_data.len(),
+            animation_id
+        );
+
+        if !png_data.starts_with(PNG_SIGNATURE) {
+            return Err(AppError::BadRequest(
+                "Thumbnail must be a PNG image".to_string(),
+            ));
+        }
+        if png_data.len() > MAX_THUMBNAIL_BYTES {
+            return Err(AppError::BadRequest(format!(
+                "Thumbnail is {} bytes, the limit is {}",
+                png_data.len(),
+                MAX_THUMBNAIL_BYTES
+            )));
+        }
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::Edit).await?;
+        use crate::schema::animation_thumbnails;
+
+        diesel::insert_into(animation_thumbnails::table)
+            .values((
+                animation_thumbnails::animation_id.eq(animation_id),
+                animation_thumbnails::png_data.eq(png_data.as_ref()),
+            ))
+            .on_conflict(animation_thumbnails::animation_id)
+            .do_update()
+            .set((
+                animation_thumbnails::png_data.eq(png_data.as_ref()),
+                animation_thumbnails::updated_at.eq(diesel::dsl::now),
+                animation_thumbnails::rendered_by_server.eq(false),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(AppError::DatabaseQuery)
+            .map(|_| ())
+    }
+
+    /// Fetches an animation's PNG preview; anyone who can load the animation may.
+    pub async fn load_thumbnail_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: Option<i32>,
+    ) -> Result<Vec<u8>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::View).await?;
+        use crate::schema::animation_thumbnails;
+
+        db::timed(
+            animation_thumbnails::table
+                .find(animation_id)
+                .select(animation_thumbnails::png_data)
+                .first::<Vec<u8>>(&mut conn),
+        )
+        .await
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Animation {} has no thumbnail", animation_id)))
+    }
+
+    /// Deletes an animation, with everything kept for it: versions, thumbnail, stars,
+    /// drafts and queued jobs. Only the owner may. Webhooks subscribed to `deleted`
+    /// are told, with the last version saved.
+    pub async fn delete_animation_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: Option<i32>,
+    ) -> Result<(), AppError> {
+        run_in_transaction(pool, |conn| {
+            async move {
+                require_access(conn, animation_id, caller_id, AnimationAccess::Owner).await?;
+                use crate::schema::animations;
+
+                let version = animations::table
+                    .find(animation_id)
+                    .select(animations::lock_version)
+                    .first::<i32>(conn)
+                    .await?;
+                // Queued while the animation still names its owner
+                webhooks::enqueue_event(conn, animation_id, WebhookEvent::Deleted, version).await?;
+                // The rows kept for it go with it, by ON DELETE CASCADE
+                diesel::delete(animations::table.find(animation_id))
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+        cache::invalidate(animation_id).await;
+        presence::access_changed(animation_id);
+        tracing::info!("SERVICE: Deleted animation {}", animation_id);
+        Ok(())
+    }
+
+    /// Copies an animation the caller can load into a new one they own, named
+    /// "<name> (copy)". Its thumbnail is copied along with it.
+    pub async fn duplicate_animation_logic(
+        pool: &DbPool,
+        source_id: i32,
+        caller_id: i32,
+    ) -> Result<i32, AppError> {
+        tracing::info!(
+            "SERVICE: Duplicating animation {} for user {}",
+            source_id,
+            caller_id
+        );
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, source_id, Some(caller_id), AnimationAccess::View).await?;
+        drop(conn);
+        let copy_id = copy_animation(pool, source_id, caller_id, None).await?;
+
+        tracing::info!(
+            "SERVICE: Animation {} duplicated as {}.",
+            source_id,
+            copy_id
+        );
+        Ok(copy_id)
+    }
+
+    /// Compares two saved versions of an animation; anyone who can load it may. `to`
+    /// defaults to the current version and `from` to the one before `to`.
+    pub async fn diff_versions_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        from: Option<i32>,
+        to: Option<i32>,
+        caller_id: Option<i32>,
+    ) -> Result<AnimationDiff, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::View).await?;
+        use crate::schema::animations;
+
+        let to_version = match to {
+            Some(version) => version,
+            None => {
+                animations::table
+                    .find(animation_id)
+                    .select(animations::lock_version)
+                    .first::<i32>(&mut conn)
+                    .await?
+            }
+        };
+        let from_version = from.unwrap_or(to_version - 1);
+        let before = load_version(&mut conn, animation_id, from_version).await?;
+        let after = load_version(&mut conn, animation_id, to_version).await?;
+        drop(conn);
+
+        Ok(AnimationDiff {
+            animation_id,
+            from_version,
+            to_version,
+            changes: geco_core::diff::diff_documents(&before, &after),
+        })
+    }
+
+    /// Loads a saved revision of an animation's document from its version history:
+    /// `version` picks one by number, `as_of` the one that was current at that (UTC)
+    /// time. Returns the revision's version and its decompressed document. Needs the
+    /// same access as loading the animation.
+    pub async fn load_revision_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        version: Option<i32>,
+        as_of: Option<NaiveDateTime>,
+        caller_id: Option<i32>,
+    ) -> Result<(i32, Vec<u8>), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::View).await?;
+        use crate::schema::animation_versions;
+
+        let revisions = animation_versions::table
+            .filter(animation_versions::animation_id.eq(animation_id))
+            .select((
+                animation_versions::version,
+                animation_versions::protobuf_data,
+            ));
+        let (found_version, stored) = match (version, as_of) {
+            (Some(number), None) => db::timed(
+                revisions
+                    .filter(animation_versions::version.eq(number))
+                    .first::<(i32, Vec<u8>)>(&mut conn),
+            )
+            .await
+            .optional()?
+            .ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "Animation {} has no version {}",
+                    animation_id, number
+                ))
+            })?,
+            (None, Some(time)) => db::timed(
+                revisions
+                    .filter(animation_versions::created_at.le(time))
+                    .order(animation_versions::version.desc())
+                    .first::<(i32, Vec<u8>)>(&mut conn),
+            )
+            .await
+            .optional()?
+            .ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "Animation {} has no version saved by {}",
+                    animation_id, time
+                ))
+            })?,
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Give exactly one of version and as_of".to_string(),
+                ))
+            }
+        };
+        drop(conn);
+
+        tracing::info!(
+            "SERVICE: Animation {} version {} loaded from its history.",
+            animation_id,
+            found_version
+        );
+        // Old revisions stay in the format they were saved in
+        let document = decompress_document(stored)?;
+        let document = upgrade_document(&document)?.unwrap_or(document);
+        Ok((found_version, document))
+    }
+
+    /// Describes a stored animation from its document, for UIs that don't need the
+    /// document itself. Needs the same access as loading it.
+    pub async fn animation_metadata_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        caller_id: Option<i32>,
+    ) -> Result<AnimationMetadata, AppError> {
+        let animation = Self::load_animation_logic(pool, animation_id, caller_id).await?;
+        describe_animation(animation)
+    }
+
+    /// Describes the animation holding the document with this ID (the document's own
+    /// `animation_id`), so clients can refer to animations by it. Needs the same
+    /// access as loading it.
+    pub async fn animation_by_uuid_logic(
+        pool: &DbPool,
+        animation_uuid: &str,
+        caller_id: Option<i32>,
+    ) -> Result<AnimationMetadata, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        let (animation_id, _, _, _, _) = saved_under_uuid(&mut conn, animation_uuid)
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "No animation holds the document with ID '{}'",
+                    animation_uuid
+                ))
+            })?;
+        drop(conn);
+        Self::animation_metadata_logic(pool, animation_id, caller_id).await
+    }
+
+    /// Describes several animations at once, in the order asked for. IDs that don't
+    /// exist or that the caller can't load are left out rather than failing the batch.
+    pub async fn batch_metadata_logic(
+        pool: &DbPool,
+        animation_ids: Vec<i32>,
+        caller_id: Option<i32>,
+    ) -> Result<Vec<AnimationMetadata>, AppError> {
+        if animation_ids.len() > MAX_BATCH_METADATA_IDS {
+            return Err(AppError::BadRequest(format!(
+                "Ask for at most {} animations at once, not {}",
+                MAX_BATCH_METADATA_IDS,
+                animation_ids.len()
+            )));
+        }
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::{animation_collaborators, animations};
+
+        let found = db::timed(
+            animations::table
+                .filter(animations::id.eq_any(&animation_ids))
+                .select(Animation::as_select())
+                .load::<Animation>(&mut conn),
+        )
+        .await?;
+        let roles: HashMap<i32, String> = match caller_id {
+            Some(caller) => animation_collaborators::table
+                .filter(animation_collaborators::user_id.eq(caller))
+                .filter(animation_collaborators::animation_id.eq_any(&animation_ids))
+                .select((
+                    animation_collaborators::animation_id,
+                    animation_collaborators::role,
+                ))
+                .load::<(i32, String)>(&mut conn)
+                .await?
+                .into_iter()
+                .collect(),
+            None => HashMap::new(),
+        };
+        drop(conn);
+
+        let mut by_id: HashMap<i32, Animation> = found
+            .into_iter()
+            .filter(|animation| {
+                let role = roles
+                    .get(&animation.id)
+                    .and_then(|role| CollaboratorRole::parse(role));
+                let access = AnimationAccess::resolve(animation.owner_id, caller_id, role)
+                    .with_publication(animation.published_at.is_some());
+                // Hidden ones are left out as if they didn't exist, as loading them would be
+                access >= AnimationAccess::View
+                    && (animation.hidden_at.is_none() || access == AnimationAccess::Owner)
+            })
+            .map(|animation| (animation.id, animation))
+            .collect();
+        let mut described = Vec::with_capacity(by_id.len());
+        for animation_id in animation_ids {
+            // Repeated IDs are only described the first time
+            if let Some(mut animation) = by_id.remove(&animation_id) {
+                open_document(&mut animation)?;
+                described.push(describe_animation(animation)?);
+            }
+        }
+        tracing::info!("SERVICE: Described {} animations.", described.len());
+        Ok(described)
+    }
+}
+
+/// Decompresses a loaded animation's document in place and checks it against its
+/// hash, catching damaged blobs rather than handing clients a broken document.
+/// Documents stored in an older format are handed out converted; the stored bytes
+/// are left for `upgrades` to rewrite.
+pub(crate) fn open_document(animation: &mut Animation) -> Result<(), AppError> {
+    animation.protobuf_data = decompress_document(std::mem::take(&mut animation.protobuf_data))?;
+    if let Some(expected) = &animation.content_sha256 {
+        let actual = content_sha256(&animation.protobuf_data);
+        if &actual != expected {
+            return Err(AppError::CorruptDocument(format!(
+                "Animation {} hashes to {}, expected {}",
+                animation.id, actual, expected
+            )));
+        }
+    }
+    if animation.format_version < format::CURRENT_FORMAT_VERSION as i32 {
+        if let Some(converted) = upgrade_document(&animation.protobuf_data)? {
+            animation.protobuf_data = converted;
+        }
+    }
+    Ok(())
+}
+
+/// A document's protobuf bytes converted to the current format, or `None` if it is
+/// in it already.
+pub(crate) fn upgrade_document(protobuf_data: &[u8]) -> Result<Option<Vec<u8>>, AppError> {
+    let mut document = MapAnimation::decode(protobuf_data)?;
+    let converted = format::upgrade(&mut document).map_err(AppError::Internal)?;
+    Ok(converted.then(|| document.encode_to_vec()))
+}
+
+/// Trims a free-text detail, turning blank text into `None`, and refuses text that
+/// is too long or holds control characters (line breaks only where `multiline`).
+fn detail_field(
+    field: &str,
+    value: Option<String>,
+    max_chars: usize,
+    multiline: bool,
+) -> Result<Option<String>, AppError> {
+    let Some(value) = value.map(|value| value.trim().to_string()) else {
+        return Ok(None);
+    };
+    if value.is_empty() {
+        return Ok(None);
+    }
+    if value.chars().count() > max_chars {
+        return Err(AppError::BadRequest(format!(
+            "{} can be at most {} characters",
+            field, max_chars
+        )));
+    }
+    let allowed = |c: char| !c.is_control() || (multiline && matches!(c, '\n' | '\r' | '\t'));
+    if !value.chars().all(allowed) {
+        return Err(AppError::BadRequest(format!(
+            "{} holds characters it can't contain",
+            field
+        )));
+    }
+    Ok(Some(value))
+}
+
+/// Metadata for an animation whose document has been opened.
+fn describe_animation(animation: Animation) -> Result<AnimationMetadata, AppError> {
+    let document = MapAnimation::decode(animation.protobuf_data.as_slice())?;
+    Ok(AnimationMetadata {
+        id: animation.id,
+        animation_uuid: animation.animation_uuid,
+        name: animation.name,
+        total_frames: document.total_frames,
+        feature_count: document.polygons.len(),
+        point_count: document.polygons.iter().map(|p| p.points.len()).sum(),
+        keyframe_count: document_stats(&document).keyframe_count,
+        byte_size: animation.protobuf_data.len(),
+        content_sha256: animation.content_sha256,
+        lock_version: animation.lock_version,
+        owner_id: animation.owner_id,
+        created_at: animation.created_at,
+        updated_at: animation.updated_at,
+        description: animation.description,
+        license: animation.license,
+        source_attribution: animation.source_attribution,
+        created_by: animation.created_by,
+        last_edited_by: animation.last_edited_by,
+    })
+}
+
+/// How many stars each of these animations has; unstarred ones are left out.
+pub(crate) async fn star_counts(
+    conn: &mut AsyncPgConnection,
+    animation_ids: &[i32],
+) -> Result<HashMap<i32, i64>, AppError> {
+    use crate::schema::stars;
+
+    let counts = stars::table
+        .filter(stars::animation_id.eq_any(animation_ids))
+        .group_by(stars::animation_id)
+        .select((stars::animation_id, diesel::dsl::count_star()))
+        .load::<(i32, i64)>(conn)
+        .await?;
+    Ok(counts.into_iter().collect())
+}
+
+/// A saved version's document, decompressed and decoded.
+async fn load_version(
+    conn: &mut AsyncPgConnection,
+    animation_id: i32,
+    version: i32,
+) -> Result<MapAnimation, AppError> {
+    use crate::schema::animation_versions;
+
+    let stored = animation_versions::table
+        .find((animation_id, version))
+        .select(animation_versions::protobuf_data)
+        .first::<Vec<u8>>(conn)
+        .await
+        .optional()?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Animation {} has no version {}",
+                animation_id, version
+            ))
+        })?;
+    Ok(MapAnimation::decode(
+        decompress_document(stored)?.as_slice(),
+    )?)
+}
+
+/// Copies an animation into a new one owned by `owner_id`, without checking they may
+/// see it: named `name`, or "<name> (copy)" if none is given. Its thumbnail is copied
+/// along with it, or rendered for it if the source has none, and the copy counts against the owner's quota and limits.
+pub(crate) async fn copy_animation(
+    pool: &DbPool,
+    source_id: i32,
+    owner_id: i32,
+    name: Option<String>,
+) -> Result<i32, AppError> {
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    use crate::schema::{animation_thumbnails, animations};
+
+    let source = animations::table
+        .find(source_id)
+        .select(Animation::as_select())
+        .first::<Animation>(&mut conn)
+        .await?;
+    // The document carries its name too, so rename it to match the new row
+    let mut document = MapAnimation::decode(decompress_document(source.protobuf_data)?.as_slice())?;
+    let copy_name = name.unwrap_or_else(|| copy_name(&source.name));
+    let (description, license, source_attribution) = (
+        source.description,
+        source.license,
+        source.source_attribution,
+    );
+    document.name = copy_name.clone();
+    // A copy is a document of its own, so later saves of either don't replace the other
+    let copy_uuid = uuid::Uuid::new_v4().to_string();
+    document.animation_id = copy_uuid.clone();
+    let encoded = document.encode_to_vec();
+    let hash = content_sha256(&encoded);
+    let protobuf_data = compress_document(&encoded)?;
+    let metrics = measure_document(&document, protobuf_data.len());
+    drop(conn);
+
+    let copy_id = run_in_transaction(pool, |conn| {
+        async move {
+            require_storage(conn, owner_id, protobuf_data.len()).await?;
+            require_animation_slot(conn, owner_id).await?;
+            let copy = diesel::insert_into(animations::table)
+                .values((
+                    &NewAnimation {
+                        name: &copy_name,
+                        protobuf_data: &protobuf_data,
+                        owner_id: Some(owner_id),
+                        content_sha256: &hash,
+                        animation_uuid: Some(&copy_uuid),
+                        created_by: Some(owner_id),
+                        last_edited_by: Some(owner_id),
+                        metrics,
+                    },
+                    // Copies keep the description, and above all the license and credits
+                    animations::description.eq(description),
+                    animations::license.eq(license),
+                    animations::source_attribution.eq(source_attribution),
+                ))
+                .returning(Animation::as_returning())
+                .get_result::<Animation>(conn)
+                .await?;
+
+            let thumbnail = animation_thumbnails::table
+                .find(source_id)
+                .select((
+                    animation_thumbnails::png_data,
+                    animation_thumbnails::rendered_by_server,
+                ))
+                .first::<(Vec<u8>, bool)>(conn)
+                .await
+                .optional()?;
+            webhooks::enqueue_event(conn, copy.id, WebhookEvent::Saved, copy.lock_version).await?;
+            if let Some((png_data, rendered_by_server)) = thumbnail {
+                diesel::insert_into(animation_thumbnails::table)
+                    .values((
+                        animation_thumbnails::animation_id.eq(copy.id),
+                        animation_thumbnails::png_data.eq(png_data),
+                        animation_thumbnails::rendered_by_server.eq(rendered_by_server),
+                    ))
+                    .execute(conn)
+                    .await?;
+            } else {
+                jobs::enqueue(
+                    conn,
+                    &Job::RenderThumbnail {
+                        animation_id: copy.id,
+                    },
+                    Some(copy.id),
+                )
+                .await?;
+            }
+            Ok(copy.id)
+        }
+        .scope_boxed()
+    })
+    .await?;
+    Ok(copy_id)
+}
+
+/// Name for a duplicate: the original with " (copy)" appended, shortening the
+/// original if needed to stay within the 255 characters the column allows.
+pub fn copy_name(name: &str) -> String {
+    const SUFFIX: &str = " (copy)";
+    const MAX_NAME_CHARS: usize = 255;
+    let kept: String = name
+        .chars()
+        .take(MAX_NAME_CHARS - SUFFIX.chars().count())
+        .collect();
+    format!("{}{}", kept, SUFFIX)
+}
+
+/// Escapes LIKE wildcards so a search for "50%" matches the text literally.
+/// Postgres treats backslash as the default LIKE escape character.
+pub fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Where clients fetch an animation's thumbnail. The timestamp changes the URL
+/// whenever the image is replaced, so it can be cached indefinitely.
+pub fn thumbnail_url(animation_id: i32, updated_at: NaiveDateTime) -> String {
+    format!(
+        "/api/animation/{}/thumbnail?v={}",
+        animation_id,
+        updated_at.and_utc().timestamp()
+    )
 }
@@ -0,0 +1,121 @@
+// klyja/backend/src/stats.rs
+// View counts: each load of an animation by someone other than its owner bumps a
+// counter for that day, so owners can see whether the links they share get used.
+// Counting never gets in the way of loading; a failed count is only logged.
+use crate::{
+    errors::AppError,
+    models::{AnimationStats, DailyViews},
+    services::{require_access, star_counts, AnimationAccess},
+    DbPool,
+};
+use chrono::{Days, NaiveDate};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use std::collections::HashMap;
+
+/// Days of views returned when the caller doesn't say.
+pub const DEFAULT_STATS_DAYS: u32 = 30;
+/// Most days of views one request can ask for.
+pub const MAX_STATS_DAYS: u32 = 365;
+
+/// Counts a load of the animation today, unless the viewer is its owner.
+pub async fn record_view(
+    pool: &DbPool,
+    animation_id: i32,
+    owner_id: Option<i32>,
+    viewer_id: Option<i32>,
+) {
+    if owner_id.is_some() && owner_id == viewer_id {
+        return;
+    }
+    if let Err(err) = count_view(pool, animation_id).await {
+        tracing::warn!(
+            "SERVICE: Counting a view of animation {} failed: {:?}",
+            animation_id,
+            err
+        );
+    }
+}
+
+async fn count_view(pool: &DbPool, animation_id: i32) -> Result<(), AppError> {
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    use crate::schema::animation_daily_views;
+
+    diesel::insert_into(animation_daily_views::table)
+        .values((
+            animation_daily_views::animation_id.eq(animation_id),
+            animation_daily_views::day.eq(chrono::Utc::now().date_naive()),
+            animation_daily_views::views.eq(1),
+        ))
+        .on_conflict((
+            animation_daily_views::animation_id,
+            animation_daily_views::day,
+        ))
+        .do_update()
+        .set(animation_daily_views::views.eq(animation_daily_views::views + 1))
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+pub struct StatsService;
+
+impl StatsService {
+    /// Views of an animation over the last `days` days (today included) and in
+    /// total, with its star count; only the owner may see them.
+    pub async fn animation_stats_logic(
+        pool: &DbPool,
+        animation_id: i32,
+        days: Option<u32>,
+        caller_id: Option<i32>,
+    ) -> Result<AnimationStats, AppError> {
+        let days = days.unwrap_or(DEFAULT_STATS_DAYS);
+        if days == 0 || days > MAX_STATS_DAYS {
+            return Err(AppError::BadRequest(format!(
+                "Ask for between 1 and {} days of views",
+                MAX_STATS_DAYS
+            )));
+        }
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_access(&mut conn, animation_id, caller_id, AnimationAccess::Owner).await?;
+        use crate::schema::animation_daily_views;
+
+        let today = chrono::Utc::now().date_naive();
+        let first_day = today - Days::new(u64::from(days - 1));
+        let counted: HashMap<NaiveDate, i64> = animation_daily_views::table
+            .filter(animation_daily_views::animation_id.eq(animation_id))
+            .filter(animation_daily_views::day.ge(first_day))
+            .select((animation_daily_views::day, animation_daily_views::views))
+            .load::<(NaiveDate, i64)>(&mut conn)
+            .await?
+            .into_iter()
+            .collect();
+        // At most one row a day, so adding them up here is cheap (and SUM would be NUMERIC)
+        let total_views: i64 = animation_daily_views::table
+            .filter(animation_daily_views::animation_id.eq(animation_id))
+            .select(animation_daily_views::views)
+            .load::<i64>(&mut conn)
+            .await?
+            .into_iter()
+            .sum();
+        let star_count = star_counts(&mut conn, &[animation_id])
+            .await?
+            .remove(&animation_id)
+            .unwrap_or(0);
+
+        let daily_views = first_day
+            .iter_days()
+            .take_while(|day| *day <= today)
+            .map(|day| DailyViews {
+                day,
+                views: counted.get(&day).copied().unwrap_or(0),
+            })
+            .collect();
+        Ok(AnimationStats {
+            animation_id,
+            total_views,
+            star_count,
+            daily_views,
+        })
+    }
+}
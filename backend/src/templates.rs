@@ -0,0 +1,53 @@
+// klyja/backend/src/templates.rs
+// The template library: curated animations (a world basemap, a tectonic starter,
+// blank timelines) that anyone can browse and signed-in users can copy into their
+// account as a starting point. Operators curate it with `klyja-admin add-template`;
+// the animations behind templates needn't be published themselves.
+use crate::{errors::AppError, models::Template, services::copy_animation, DbPool};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+
+pub struct TemplateService;
+
+impl TemplateService {
+    /// Every template, in their curated order.
+    pub async fn list_templates_logic(pool: &DbPool) -> Result<Vec<Template>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::templates;
+
+        Ok(templates::table
+            .order((templates::sort_order.asc(), templates::id.asc()))
+            .select(Template::as_select())
+            .load::<Template>(&mut conn)
+            .await?)
+    }
+
+    /// Copies a template's animation into a new one the caller owns, named after the
+    /// template. Returns the new animation's ID.
+    pub async fn instantiate_template_logic(
+        pool: &DbPool,
+        template_id: i32,
+        caller_id: i32,
+    ) -> Result<i32, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::templates;
+
+        let (animation_id, name) = templates::table
+            .find(template_id)
+            .select((templates::animation_id, templates::name))
+            .first::<(i32, String)>(&mut conn)
+            .await
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Template {} not found", template_id)))?;
+        drop(conn);
+
+        let copy_id = copy_animation(pool, animation_id, caller_id, Some(name)).await?;
+        tracing::info!(
+            "SERVICE: User {} started animation {} from template {}",
+            caller_id,
+            copy_id,
+            template_id
+        );
+        Ok(copy_id)
+    }
+}
@@ -0,0 +1,266 @@
+// klyja/backend/src/throttle.rs
+// Brute-force protection for the routes that take credentials (sign-up, password
+// login, password resets, and any sign-in callback added later): each client IP may
+// make AUTH_MAX_ATTEMPTS_PER_MINUTE attempts a minute, and one that fails
+// AUTH_MAX_FAILURES times within AUTH_FAILURE_WINDOW_MINUTES is locked out for
+// AUTH_LOCKOUT_MINUTES. Refused requests get 429 with a Retry-After. Every failure,
+// lockout and refusal is logged as an audit event (target `klyja::audit`), and the
+// totals are counted for admins. The counts live in this process's memory, so they
+// start over on restart and each instance of a scaled-out deployment keeps its own.
+use crate::{devices::ClientInfo, errors::AppError, models::AuthThrottleStats, moderation, DbPool};
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Tracing target of the audit events.
+pub const AUDIT_TARGET: &str = "klyja::audit";
+/// Clients tracked before records with nothing left in them are dropped.
+const PRUNE_AT_CLIENTS: usize = 10_000;
+
+/// Limits, from the environment each time so they can be tuned without a rebuild.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottleLimits {
+    pub max_attempts_per_minute: usize,
+    pub max_failures: usize,
+    pub failure_window: Duration,
+    pub lockout: Duration,
+}
+
+impl ThrottleLimits {
+    pub fn from_env() -> Self {
+        fn var(name: &str, default: u64) -> u64 {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|&value| value > 0)
+                .unwrap_or(default)
+        }
+        ThrottleLimits {
+            max_attempts_per_minute: var("AUTH_MAX_ATTEMPTS_PER_MINUTE", 20) as usize,
+            max_failures: var("AUTH_MAX_FAILURES", 10) as usize,
+            failure_window: Duration::from_secs(var("AUTH_FAILURE_WINDOW_MINUTES", 15) * 60),
+            lockout: Duration::from_secs(var("AUTH_LOCKOUT_MINUTES", 15) * 60),
+        }
+    }
+}
+
+/// One client's recent attempts and failures, oldest first.
+#[derive(Debug, Default)]
+struct ClientRecord {
+    attempts: VecDeque<Instant>,
+    failures: VecDeque<Instant>,
+    locked_until: Option<Instant>,
+}
+
+impl ClientRecord {
+    fn forget_before(&mut self, now: Instant, limits: &ThrottleLimits) {
+        let minute = Duration::from_secs(60);
+        while self
+            .attempts
+            .front()
+            .is_some_and(|&at| now.duration_since(at) >= minute)
+        {
+            self.attempts.pop_front();
+        }
+        while self
+            .failures
+            .front()
+            .is_some_and(|&at| now.duration_since(at) >= limits.failure_window)
+        {
+            self.failures.pop_front();
+        }
+        if self.locked_until.is_some_and(|until| until <= now) {
+            self.locked_until = None;
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.attempts.is_empty() && self.failures.is_empty() && self.locked_until.is_none()
+    }
+}
+
+/// Why an attempt was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    TooManyAttempts { retry_after: Duration },
+    LockedOut { retry_after: Duration },
+}
+
+/// Every client's record, keyed by IP.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    clients: HashMap<IpAddr, ClientRecord>,
+}
+
+impl Throttle {
+    /// Counts an attempt by `ip`, unless it is refused.
+    pub fn check(
+        &mut self,
+        ip: IpAddr,
+        now: Instant,
+        limits: &ThrottleLimits,
+    ) -> Result<(), Refusal> {
+        if self.clients.len() >= PRUNE_AT_CLIENTS {
+            self.prune(now, limits);
+        }
+        let record = self.clients.entry(ip).or_default();
+        record.forget_before(now, limits);
+        if let Some(until) = record.locked_until {
+            return Err(Refusal::LockedOut {
+                retry_after: until - now,
+            });
+        }
+        if record.attempts.len() >= limits.max_attempts_per_minute {
+            let oldest = record.attempts[0];
+            return Err(Refusal::TooManyAttempts {
+                retry_after: Duration::from_secs(60) - now.duration_since(oldest),
+            });
+        }
+        record.attempts.push_back(now);
+        Ok(())
+    }
+
+    /// Counts a failed attempt by `ip`; true when it locks the client out.
+    pub fn fail(&mut self, ip: IpAddr, now: Instant, limits: &ThrottleLimits) -> bool {
+        let record = self.clients.entry(ip).or_default();
+        record.forget_before(now, limits);
+        record.failures.push_back(now);
+        if record.locked_until.is_none() && record.failures.len() >= limits.max_failures {
+            record.locked_until = Some(now + limits.lockout);
+            record.failures.clear();
+            return true;
+        }
+        false
+    }
+
+    /// How many clients are locked out at `now`.
+    pub fn locked_out(&mut self, now: Instant, limits: &ThrottleLimits) -> usize {
+        self.prune(now, limits);
+        self.clients
+            .values()
+            .filter(|record| record.locked_until.is_some())
+            .count()
+    }
+
+    /// Drops records with nothing left in them.
+    fn prune(&mut self, now: Instant, limits: &ThrottleLimits) {
+        self.clients.retain(|_, record| {
+            record.forget_before(now, limits);
+            !record.is_idle()
+        });
+    }
+}
+
+fn throttle() -> MutexGuard<'static, Throttle> {
+    static THROTTLE: OnceLock<Mutex<Throttle>> = OnceLock::new();
+    THROTTLE
+        .get_or_init(Default::default)
+        .lock()
+        // Each change leaves the records consistent, so a panic elsewhere can't spoil them
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+static ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+static FAILURES: AtomicU64 = AtomicU64::new(0);
+static REFUSED: AtomicU64 = AtomicU64::new(0);
+static LOCKOUTS: AtomicU64 = AtomicU64::new(0);
+
+/// Whether a response to a credential route means the credentials were wrong.
+fn is_failure(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+    )
+}
+
+/// Middleware throttling a credential route by client IP. Requests whose IP isn't
+/// known (no connection info, as in tests) pass untouched.
+pub async fn throttle_auth(request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let client = ClientInfo::from_parts(&parts);
+    let Some(ip) = client.ip else {
+        return next.run(Request::from_parts(parts, body)).await;
+    };
+    let limits = ThrottleLimits::from_env();
+    let path = parts.uri.path().to_string();
+
+    ATTEMPTS.fetch_add(1, Ordering::Relaxed);
+    let checked = throttle().check(ip, Instant::now(), &limits);
+    if let Err(refusal) = checked {
+        REFUSED.fetch_add(1, Ordering::Relaxed);
+        return refuse(&client, &path, refusal);
+    }
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+    if is_failure(response.status()) {
+        FAILURES.fetch_add(1, Ordering::Relaxed);
+        let locked = throttle().fail(ip, Instant::now(), &limits);
+        tracing::info!(
+            target: AUDIT_TARGET,
+            event = "auth_failed",
+            path = %path,
+            status = response.status().as_u16(),
+            ip = client.stored_ip().as_deref().unwrap_or("-"),
+        );
+        if locked {
+            LOCKOUTS.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                target: AUDIT_TARGET,
+                event = "auth_locked_out",
+                path = %path,
+                minutes = limits.lockout.as_secs() / 60,
+                ip = client.stored_ip().as_deref().unwrap_or("-"),
+            );
+        }
+    }
+    response
+}
+
+fn refuse(client: &ClientInfo, path: &str, refusal: Refusal) -> Response {
+    let (reason, retry_after) = match refusal {
+        Refusal::TooManyAttempts { retry_after } => ("too_many_attempts", retry_after),
+        Refusal::LockedOut { retry_after } => ("locked_out", retry_after),
+    };
+    tracing::warn!(
+        target: AUDIT_TARGET,
+        event = "auth_refused",
+        reason,
+        path = %path,
+        ip = client.stored_ip().as_deref().unwrap_or("-"),
+    );
+    AppError::TooManyRequests {
+        // Round up, so a client waiting that long isn't refused again
+        retry_after_secs: retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0),
+    }
+    .into_response()
+}
+
+pub struct ThrottleService;
+
+impl ThrottleService {
+    /// Counts of credential attempts since the server started. Admins only.
+    pub async fn stats_logic(pool: &DbPool, caller_id: i32) -> Result<AuthThrottleStats, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        if !moderation::is_admin(&mut conn, Some(caller_id)).await? {
+            return Err(AppError::Forbidden(
+                "Only admins can see sign-in throttling".to_string(),
+            ));
+        }
+        let locked_out_clients = throttle().locked_out(Instant::now(), &ThrottleLimits::from_env());
+        Ok(AuthThrottleStats {
+            attempts: ATTEMPTS.load(Ordering::Relaxed),
+            failures: FAILURES.load(Ordering::Relaxed),
+            refused: REFUSED.load(Ordering::Relaxed),
+            lockouts: LOCKOUTS.load(Ordering::Relaxed),
+            locked_out_clients,
+        })
+    }
+}
@@ -0,0 +1,116 @@
+// klyja/backend/src/thumbnails.rs
+// Server-rendered previews: frame 0 of an animation drawn by the headless engine
+// and encoded as PNG, so listings have a thumbnail even if the client never uploads one.
+use crate::{errors::AppError, protobuf_gen::MapAnimation, DbPool};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use geco_core::raster::{self, Raster};
+use prost::Message;
+
+/// Width and height of rendered thumbnails in pixels.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Renders the animation's first frame as a PNG thumbnail.
+pub fn render_thumbnail(animation: &MapAnimation) -> Vec<u8> {
+    encode_png(&raster::render_orthographic(
+        animation,
+        0,
+        THUMBNAIL_SIZE,
+        THUMBNAIL_SIZE,
+    ))
+}
+
+/// Encodes RGBA pixels as an 8-bit truecolor-with-alpha PNG.
+pub fn encode_png(raster: &Raster) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(raster.width.to_be_bytes());
+    header.extend(raster.height.to_be_bytes());
+    header.extend([8, 6, 0, 0, 0]); // 8 bits per channel, RGBA, deflate, no filter, no interlace
+    write_chunk(&mut png, b"IHDR", &header);
+
+    // Every scanline starts with its filter type; 0 leaves the row as is
+    let row_len = raster.width as usize * 4;
+    let mut scanlines = Vec::with_capacity(raster.pixels.len() + raster.height as usize);
+    for row in raster.pixels.chunks_exact(row_len.max(1)) {
+        scanlines.push(0);
+        scanlines.extend(row);
+    }
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&scanlines, 6);
+    write_chunk(&mut png, b"IDAT", &compressed);
+
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    png.extend(crc.finalize().to_be_bytes());
+}
+
+/// Renders and stores a thumbnail from an animation's current document; run by the
+/// job queue after each save so the save response isn't held up. Thumbnails
+/// uploaded by a client are left alone.
+pub async fn render_and_store(pool: &DbPool, animation_id: i32) -> Result<(), AppError> {
+    use crate::schema::animation_thumbnails;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    let has_uploaded_thumbnail = animation_thumbnails::table
+        .find(animation_id)
+        .select(animation_thumbnails::rendered_by_server)
+        .first::<bool>(&mut conn)
+        .await
+        .optional()?
+        .is_some_and(|rendered_by_server| !rendered_by_server);
+    if has_uploaded_thumbnail {
+        return Ok(());
+    }
+
+    // Render whatever is saved now; later saves queue a render of their own
+    let protobuf_data = crate::schema::animations::table
+        .find(animation_id)
+        .select(crate::schema::animations::protobuf_data)
+        .first::<Vec<u8>>(&mut conn)
+        .await?;
+    drop(conn); // Don't hold a connection while rendering
+    let png_data = tokio::task::spawn_blocking(move || {
+        let protobuf_data = crate::services::decompress_document(protobuf_data)?;
+        Ok::<_, AppError>(render_thumbnail(&MapAnimation::decode(
+            protobuf_data.as_slice(),
+        )?))
+    })
+    .await
+    .map_err(|join_err| {
+        AppError::Internal(format!("Tokio spawn_blocking join error: {}", join_err))
+    })??;
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    diesel::insert_into(animation_thumbnails::table)
+        .values((
+            animation_thumbnails::animation_id.eq(animation_id),
+            animation_thumbnails::png_data.eq(&png_data),
+            animation_thumbnails::rendered_by_server.eq(true),
+        ))
+        .on_conflict(animation_thumbnails::animation_id)
+        .do_update()
+        .set((
+            animation_thumbnails::png_data.eq(&png_data),
+            animation_thumbnails::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(AppError::DatabaseQuery)?;
+
+    tracing::info!(
+        "JOB: Rendered {}-byte thumbnail for animation {}",
+        png_data.len(),
+        animation_id
+    );
+    Ok(())
+}
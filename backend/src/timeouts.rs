@@ -0,0 +1,57 @@
+// klyja/backend/src/timeouts.rs
+// Time budgets for API requests: metadata reads get METADATA_TIMEOUT_SECS, routes
+// sending or receiving whole documents, archives and backups TRANSFER_TIMEOUT_SECS,
+// and everything else REQUEST_TIMEOUT_SECS. A request over its budget is cancelled
+// (its handler is dropped, returning any connection it held to the pool) and answered
+// with 503, rather than leaving the client waiting on a stuck query forever. Postgres
+// cancels statements that run past STATEMENT_TIMEOUT_SECS on its side (see db.rs).
+use crate::errors::AppError;
+use axum::{extract::Request, middleware::Next, response::Response};
+use std::time::Duration;
+
+/// Default budget of metadata reads: listings, details, stats and the like.
+pub const DEFAULT_METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default budget of most requests.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default budget of uploads and downloads of documents, archives and backups.
+pub const DEFAULT_TRANSFER_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A budget from the environment, in whole seconds.
+fn budget(var: &str, default: Duration) -> Duration {
+    std::env::var(var)
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(default)
+}
+
+/// Middleware for metadata reads, which answer quickly when the database is well.
+pub async fn metadata_timeout(request: Request, next: Next) -> Result<Response, AppError> {
+    let limit = budget("METADATA_TIMEOUT_SECS", DEFAULT_METADATA_TIMEOUT);
+    limit_time(request, next, limit).await
+}
+
+/// Middleware for routes without a budget of their own.
+pub async fn request_timeout(request: Request, next: Next) -> Result<Response, AppError> {
+    let limit = budget("REQUEST_TIMEOUT_SECS", DEFAULT_REQUEST_TIMEOUT);
+    limit_time(request, next, limit).await
+}
+
+/// Middleware for routes moving large bodies, which slow links take a while to send.
+pub async fn transfer_timeout(request: Request, next: Next) -> Result<Response, AppError> {
+    let limit = budget("TRANSFER_TIMEOUT_SECS", DEFAULT_TRANSFER_TIMEOUT);
+    limit_time(request, next, limit).await
+}
+
+async fn limit_time(request: Request, next: Next, limit: Duration) -> Result<Response, AppError> {
+    let path = request.uri().path().to_string();
+    tokio::time::timeout(limit, next.run(request))
+        .await
+        .map_err(|_| {
+            tracing::warn!("Request to {} cancelled after {:?}", path, limit);
+            AppError::Timeout {
+                budget_secs: limit.as_secs(),
+            }
+        })
+}
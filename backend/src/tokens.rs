@@ -0,0 +1,168 @@
+// klyja/backend/src/tokens.rs
+// Personal access tokens: long-lived secrets that let scripts and CI act as a user
+// through `Authorization: Bearer`. Only their hashes are stored, and each carries
+// scopes limiting it to reads, writes or both.
+use crate::{
+    accounts::random_token,
+    errors::AppError,
+    models::{ApiToken, CreateApiTokenRequest, CreatedApiToken, TokenScope},
+    services::content_sha256,
+    DbPool,
+};
+use diesel::dsl::now;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+/// Prefix of every token, so leaked ones are easy to recognise and scan for.
+pub const TOKEN_PREFIX: &str = "klyja_pat_";
+/// Longest lifetime a token can be given, in days.
+pub const MAX_TOKEN_DAYS: u32 = 365;
+
+/// Routes that take a POST body but only read, so read-only tokens may call them.
+const READ_ONLY_POSTS: &[&str] = &["/api/animations/batch_meta"];
+
+/// The scope a request to the route at `matched_path` needs: reads for GET and HEAD
+/// and the read-only POSTs, writes for the rest.
+pub fn scope_for_route(method: &axum::http::Method, matched_path: Option<&str>) -> TokenScope {
+    let reads = method == axum::http::Method::GET
+        || method == axum::http::Method::HEAD
+        || (method == axum::http::Method::POST
+            && matched_path.is_some_and(|path| READ_ONLY_POSTS.contains(&path)));
+    if reads {
+        TokenScope::Read
+    } else {
+        TokenScope::Write
+    }
+}
+
+/// Looks up an unexpired token, returning its owner, its ID and its scopes, and
+/// records that it was used.
+pub async fn resolve_token(
+    conn: &mut AsyncPgConnection,
+    token: &str,
+) -> Result<Option<(i32, i32, Vec<TokenScope>)>, AppError> {
+    use crate::schema::api_tokens;
+
+    let found = api_tokens::table
+        .filter(api_tokens::token_sha256.eq(content_sha256(token.as_bytes())))
+        .filter(
+            api_tokens::expires_at
+                .is_null()
+                .or(api_tokens::expires_at.gt(now)),
+        )
+        .select((api_tokens::id, api_tokens::user_id, api_tokens::scopes))
+        .first::<(i32, i32, Vec<String>)>(conn)
+        .await
+        .optional()?;
+    let Some((token_id, user_id, scopes)) = found else {
+        return Ok(None);
+    };
+
+    diesel::update(api_tokens::table.find(token_id))
+        .set(api_tokens::last_used_at.eq(now))
+        .execute(conn)
+        .await?;
+    let scopes = scopes
+        .iter()
+        .filter_map(|scope| TokenScope::parse(scope))
+        .collect();
+    Ok(Some((user_id, token_id, scopes)))
+}
+
+pub struct TokenService;
+
+impl TokenService {
+    /// Lists the user's tokens, newest first.
+    pub async fn list_tokens_logic(pool: &DbPool, user_id: i32) -> Result<Vec<ApiToken>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::api_tokens;
+
+        api_tokens::table
+            .filter(api_tokens::user_id.eq(user_id))
+            .order((api_tokens::created_at.desc(), api_tokens::id.desc()))
+            .select(ApiToken::as_select())
+            .load::<ApiToken>(&mut conn)
+            .await
+            .map_err(AppError::DatabaseQuery)
+    }
+
+    /// Creates a token for the user. The secret is returned here and never again.
+    pub async fn create_token_logic(
+        pool: &DbPool,
+        user_id: i32,
+        request: CreateApiTokenRequest,
+    ) -> Result<CreatedApiToken, AppError> {
+        let name = request.name.trim().to_string();
+        if name.is_empty() || name.chars().count() > 255 {
+            return Err(AppError::BadRequest(
+                "Token names need between 1 and 255 characters".to_string(),
+            ));
+        }
+        if request.scopes.is_empty() {
+            return Err(AppError::BadRequest(
+                "Give the token at least one scope".to_string(),
+            ));
+        }
+        if let Some(days) = request.expires_in_days {
+            if days == 0 || days > MAX_TOKEN_DAYS {
+                return Err(AppError::BadRequest(format!(
+                    "Tokens can last between 1 and {} days",
+                    MAX_TOKEN_DAYS
+                )));
+            }
+        }
+        let mut scopes: Vec<&str> = request.scopes.iter().map(|scope| scope.as_str()).collect();
+        scopes.sort_unstable();
+        scopes.dedup();
+        let scopes: Vec<String> = scopes.into_iter().map(str::to_string).collect();
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::api_tokens;
+
+        let token = format!("{}{}", TOKEN_PREFIX, random_token());
+        let expires_at = request
+            .expires_in_days
+            .map(|days| chrono::Utc::now().naive_utc() + chrono::Duration::days(days.into()));
+        let details = diesel::insert_into(api_tokens::table)
+            .values((
+                api_tokens::user_id.eq(user_id),
+                api_tokens::name.eq(&name),
+                api_tokens::token_sha256.eq(content_sha256(token.as_bytes())),
+                api_tokens::scopes.eq(&scopes),
+                api_tokens::expires_at.eq(expires_at),
+            ))
+            .returning(ApiToken::as_returning())
+            .get_result::<ApiToken>(&mut conn)
+            .await?;
+        tracing::info!(
+            "SERVICE: User {} created API token {} ({:?})",
+            user_id,
+            details.id,
+            details.scopes
+        );
+        Ok(CreatedApiToken { token, details })
+    }
+
+    /// Revokes one of the user's tokens; scripts using it get 401 from then on.
+    pub async fn revoke_token_logic(
+        pool: &DbPool,
+        user_id: i32,
+        token_id: i32,
+    ) -> Result<(), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::api_tokens;
+
+        let deleted = diesel::delete(
+            api_tokens::table
+                .find(token_id)
+                .filter(api_tokens::user_id.eq(user_id)),
+        )
+        .execute(&mut conn)
+        .await?;
+        if deleted == 0 {
+            return Err(AppError::NotFound(format!("Token {} not found", token_id)));
+        }
+        tracing::info!("SERVICE: User {} revoked API token {}", user_id, token_id);
+        Ok(())
+    }
+}
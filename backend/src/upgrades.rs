@@ -0,0 +1,125 @@
+// klyja/backend/src/upgrades.rs
+// Rewrites documents stored in an older geco_core::format version, so saves made
+// before a schema change keep working after it. Loads already hand such documents
+// out converted; this brings the stored bytes up to date, in batches. It runs as a
+// job the servers queue at startup, and from `klyja-admin upgrade-documents`.
+use crate::{
+    errors::AppError,
+    jobs::{enqueue, Job},
+    models::DocumentMetrics,
+    protobuf_gen::MapAnimation,
+    services::{compress_document, content_sha256, decompress_document, measure_document},
+    DbPool,
+};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use geco_core::format::{self, CURRENT_FORMAT_VERSION};
+use prost::Message;
+
+/// Animations `upgrade_documents` loads at a time.
+const UPGRADE_BATCH: i64 = 100;
+
+/// Rewrites every animation stored in an older format, returning how many were
+/// rewritten. Damaged documents are logged and left as they are. The version isn't
+/// bumped: the document means the same, and edits based on it still apply.
+pub async fn upgrade_documents(pool: &DbPool) -> Result<usize, AppError> {
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    use crate::schema::animations;
+
+    let mut upgraded = 0;
+    let mut after_id = 0;
+    loop {
+        let batch = animations::table
+            .filter(animations::format_version.lt(CURRENT_FORMAT_VERSION as i32))
+            .filter(animations::id.gt(after_id))
+            .order(animations::id.asc())
+            .limit(UPGRADE_BATCH)
+            .select((
+                animations::id,
+                animations::protobuf_data,
+                animations::lock_version,
+            ))
+            .load::<(i32, Vec<u8>, i32)>(&mut conn)
+            .await?;
+        let Some(&(last_id, _, _)) = batch.last() else {
+            break;
+        };
+        after_id = last_id;
+        for (animation_id, stored, version) in batch {
+            let (protobuf_data, stored, metrics) = match upgrade_stored(stored) {
+                Ok(rewritten) => rewritten,
+                Err(err) => {
+                    tracing::warn!(
+                        "UPGRADE: Animation {} couldn't be upgraded: {:?}",
+                        animation_id,
+                        err
+                    );
+                    continue;
+                }
+            };
+            // A save since it was read stored the current format already
+            let rewritten = diesel::update(
+                animations::table
+                    .find(animation_id)
+                    .filter(animations::lock_version.eq(version)),
+            )
+            .set((
+                animations::protobuf_data.eq(stored),
+                animations::content_sha256.eq(content_sha256(&protobuf_data)),
+                metrics,
+            ))
+            .execute(&mut conn)
+            .await?;
+            upgraded += rewritten;
+        }
+    }
+    tracing::info!(
+        "UPGRADE: Rewrote {} documents in format version {}",
+        upgraded,
+        CURRENT_FORMAT_VERSION
+    );
+    Ok(upgraded)
+}
+
+/// A stored document converted to the current format: its protobuf bytes, the
+/// bytes to store and its metrics.
+fn upgrade_stored(stored: Vec<u8>) -> Result<(Vec<u8>, Vec<u8>, DocumentMetrics), AppError> {
+    let mut document = MapAnimation::decode(decompress_document(stored)?.as_slice())?;
+    format::upgrade(&mut document).map_err(AppError::Internal)?;
+    let protobuf_data = document.encode_to_vec();
+    let stored = compress_document(&protobuf_data)?;
+    let metrics = measure_document(&document, stored.len());
+    Ok((protobuf_data, stored, metrics))
+}
+
+/// Queues a rewrite if any document is stored in an older format and no rewrite is
+/// waiting or running already.
+pub(crate) async fn schedule_upgrade(pool: &DbPool) -> Result<(), AppError> {
+    use crate::schema::{animations, jobs};
+
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    conn.transaction::<_, AppError, _>(|conn| {
+        async move {
+            let outdated = diesel::select(diesel::dsl::exists(
+                animations::table
+                    .filter(animations::format_version.lt(CURRENT_FORMAT_VERSION as i32)),
+            ))
+            .get_result::<bool>(conn)
+            .await?;
+            let queued = diesel::select(diesel::dsl::exists(
+                jobs::table
+                    .filter(jobs::kind.eq(Job::UpgradeDocuments.kind()))
+                    .filter(jobs::status.eq_any(["pending", "running"])),
+            ))
+            .get_result::<bool>(conn)
+            .await?;
+            if outdated && !queued {
+                enqueue(conn, &Job::UpgradeDocuments, None).await?;
+            }
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+}
@@ -0,0 +1,294 @@
+// klyja/backend/src/uploads.rs
+// Resumable uploads: large documents arrive in numbered parts that can be resent
+// individually, and are saved through the normal save/update path once complete.
+use crate::{
+    errors::AppError,
+    models::UploadStatus,
+    services::{
+        insert_document, max_upload_bytes, replace_document, require_access, run_in_transaction,
+        AnimationAccess, IfMatch, PreparedDocument,
+    },
+    DbPool,
+};
+use axum::body::Bytes;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+
+/// Largest single part; clients split documents into parts no bigger than this.
+pub const MAX_UPLOAD_PART_BYTES: usize = 1024 * 1024;
+
+/// What completing an upload did with the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletedUpload {
+    Created(i32),
+    /// The animation's ID and its new version.
+    Updated(i32, i32),
+}
+
+/// Looks up an upload, failing unless it belongs to `user_id`. Returns the
+/// animation it will replace, if any.
+async fn require_upload(
+    conn: &mut AsyncPgConnection,
+    upload_id: i32,
+    user_id: i32,
+) -> Result<Option<i32>, AppError> {
+    use crate::schema::uploads;
+
+    let (owner, animation_id) = uploads::table
+        .find(upload_id)
+        .select((uploads::user_id, uploads::animation_id))
+        .first::<(i32, Option<i32>)>(conn)
+        .await
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Upload {} not found", upload_id)))?;
+    if owner != user_id {
+        return Err(AppError::Forbidden(format!(
+            "Upload {} belongs to another user",
+            upload_id
+        )));
+    }
+    Ok(animation_id)
+}
+
+/// Checks that parts `0..n` are all present, returning the first one missing.
+pub fn first_missing_part(received_parts: &[i32]) -> Option<i32> {
+    (0..)
+        .zip(received_parts)
+        .find_map(|(expected, &part)| (part != expected).then_some(expected))
+}
+
+async fn upload_status(
+    conn: &mut AsyncPgConnection,
+    upload_id: i32,
+) -> Result<UploadStatus, AppError> {
+    use crate::schema::{upload_parts, uploads};
+
+    let (animation_id, created_at) = uploads::table
+        .find(upload_id)
+        .select((uploads::animation_id, uploads::created_at))
+        .first::<(Option<i32>, NaiveDateTime)>(conn)
+        .await?;
+    let parts = upload_parts::table
+        .filter(upload_parts::upload_id.eq(upload_id))
+        .order(upload_parts::part_number.asc())
+        .select((
+            upload_parts::part_number,
+            crate::services::octet_length(upload_parts::data),
+        ))
+        .load::<(i32, i32)>(conn)
+        .await?;
+
+    Ok(UploadStatus {
+        id: upload_id,
+        animation_id,
+        received_bytes: parts.iter().map(|&(_, len)| len as i64).sum(),
+        received_parts: parts.into_iter().map(|(part, _)| part).collect(),
+        created_at,
+    })
+}
+
+pub struct UploadService;
+
+impl UploadService {
+    /// Starts an upload. Replacing an existing animation needs editor access to it.
+    pub async fn create_upload_logic(
+        pool: &DbPool,
+        user_id: i32,
+        animation_id: Option<i32>,
+    ) -> Result<UploadStatus, AppError> {
+        tracing::info!(
+            "SERVICE: Starting upload for user {} (animation {:?})",
+            user_id,
+            animation_id
+        );
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        if let Some(animation_id) = animation_id {
+            require_access(
+                &mut conn,
+                animation_id,
+                Some(user_id),
+                AnimationAccess::Edit,
+            )
+            .await?;
+        }
+        use crate::schema::uploads;
+
+        let upload_id = diesel::insert_into(uploads::table)
+            .values((
+                uploads::user_id.eq(user_id),
+                uploads::animation_id.eq(animation_id),
+            ))
+            .returning(uploads::id)
+            .get_result::<i32>(&mut conn)
+            .await?;
+        upload_status(&mut conn, upload_id).await
+    }
+
+    /// Reports which parts have arrived, so an interrupted client knows where to resume.
+    pub async fn upload_status_logic(
+        pool: &DbPool,
+        upload_id: i32,
+        user_id: i32,
+    ) -> Result<UploadStatus, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_upload(&mut conn, upload_id, user_id).await?;
+        upload_status(&mut conn, upload_id).await
+    }
+
+    /// Stores one part, replacing it if it was sent before.
+    pub async fn put_part_logic(
+        pool: &DbPool,
+        upload_id: i32,
+        part_number: i32,
+        data: Bytes,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        tracing::debug!(
+            "SERVICE: Storing {}-byte part {} of upload {}",
+            data.len(),
+            part_number,
+            upload_id
+        );
+
+        if part_number < 0 {
+            return Err(AppError::BadRequest("Part numbers start at 0".to_string()));
+        }
+        if data.len() > MAX_UPLOAD_PART_BYTES {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Part is {} bytes, the limit is {}",
+                data.len(),
+                MAX_UPLOAD_PART_BYTES
+            )));
+        }
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        require_upload(&mut conn, upload_id, user_id).await?;
+        use crate::schema::upload_parts;
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            async move {
+                // The finished document has to fit the same limit as a single save
+                let other_parts_bytes = upload_parts::table
+                    .filter(upload_parts::upload_id.eq(upload_id))
+                    .filter(upload_parts::part_number.ne(part_number))
+                    .select(diesel::dsl::sum(crate::services::octet_length(
+                        upload_parts::data,
+                    )))
+                    .first::<Option<i64>>(conn)
+                    .await?
+                    .unwrap_or(0);
+                if other_parts_bytes + data.len() as i64 > max_upload_bytes() as i64 {
+                    return Err(AppError::PayloadTooLarge(format!(
+                        "Upload would exceed the {}-byte limit for an animation",
+                        max_upload_bytes()
+                    )));
+                }
+
+                diesel::insert_into(upload_parts::table)
+                    .values((
+                        upload_parts::upload_id.eq(upload_id),
+                        upload_parts::part_number.eq(part_number),
+                        upload_parts::data.eq(data.as_ref()),
+                    ))
+                    .on_conflict((upload_parts::upload_id, upload_parts::part_number))
+                    .do_update()
+                    .set(upload_parts::data.eq(data.as_ref()))
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    /// Joins the parts in order and saves the document, as a new animation or
+    /// over the one named when the upload started. The upload is removed in the same
+    /// transaction; if the document is rejected, parts can be resent and completion retried.
+    /// `if_match` guards an update the way it does for a direct save, and a replacement
+    /// needs one.
+    pub async fn complete_upload_logic(
+        pool: &DbPool,
+        upload_id: i32,
+        user_id: i32,
+        if_match: IfMatch,
+    ) -> Result<CompletedUpload, AppError> {
+        tracing::info!("SERVICE: Completing upload {}", upload_id);
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        let animation_id = require_upload(&mut conn, upload_id, user_id).await?;
+        // Replacing a document is an update, and updates must say what they're based on
+        if let (Some(animation_id), IfMatch::Absent) = (animation_id, if_match) {
+            return Err(AppError::PreconditionRequired(format!(
+                "Upload {} replaces animation {}; send If-Match with the ETag of the version you loaded, or * to overwrite",
+                upload_id, animation_id
+            )));
+        }
+        use crate::schema::{upload_parts, uploads};
+
+        let parts = upload_parts::table
+            .filter(upload_parts::upload_id.eq(upload_id))
+            .order(upload_parts::part_number.asc())
+            .select((upload_parts::part_number, upload_parts::data))
+            .load::<(i32, Vec<u8>)>(&mut conn)
+            .await?;
+        if parts.is_empty() {
+            return Err(AppError::BadRequest(format!(
+                "Upload {} has no parts",
+                upload_id
+            )));
+        }
+        let part_numbers: Vec<i32> = parts.iter().map(|(part, _)| *part).collect();
+        if let Some(missing) = first_missing_part(&part_numbers) {
+            return Err(AppError::BadRequest(format!(
+                "Upload {} is missing part {}",
+                upload_id, missing
+            )));
+        }
+        let document: Vec<u8> = parts.into_iter().flat_map(|(_, data)| data).collect();
+        drop(conn);
+        let document = PreparedDocument::from_upload(&Bytes::from(document))?;
+
+        // Saving and removing the upload happen together: a failed save keeps the
+        // upload for a retry, and of two concurrent completions only one saves
+        let completed = run_in_transaction(pool, |conn| {
+            async move {
+                let removed = diesel::delete(uploads::table.find(upload_id))
+                    .execute(conn)
+                    .await?;
+                if removed == 0 {
+                    return Err(AppError::NotFound(format!(
+                        "Upload {} was already completed",
+                        upload_id
+                    )));
+                }
+                match animation_id {
+                    Some(animation_id) => {
+                        let version = replace_document(
+                            conn,
+                            animation_id,
+                            &document,
+                            Some(user_id),
+                            if_match.expected_version(),
+                        )
+                        .await?;
+                        Ok(CompletedUpload::Updated(animation_id, version))
+                    }
+                    None => Ok(CompletedUpload::Created(
+                        insert_document(conn, &document, Some(user_id), if_match)
+                            .await?
+                            .0,
+                    )),
+                }
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+        tracing::info!("SERVICE: Upload {} completed: {:?}", upload_id, completed);
+        Ok(completed)
+    }
+}
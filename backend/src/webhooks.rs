@@ -0,0 +1,375 @@
+// klyja/backend/src/webhooks.rs
+// Outgoing webhooks: users register URLs to be called when their animations change.
+// Each change queues one delivery job per interested webhook, so a slow or failing
+// receiver never holds up a save and gets the job queue's retries. Bodies are signed
+// with the webhook's secret (HMAC-SHA256) so receivers can tell they came from us.
+// Webhook URLs must point at public addresses, checked when a webhook is registered and
+// again on every delivery, so users can't have the server call into its own network.
+use crate::{
+    accounts::random_token,
+    errors::AppError,
+    jobs::{self, Job},
+    models::{CreateWebhookRequest, CreatedWebhook, Webhook, WebhookEvent},
+    DbPool,
+};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Header carrying `sha256=` and the hex HMAC-SHA256 of the body under the webhook's secret.
+pub const SIGNATURE_HEADER: &str = "X-Klyja-Signature";
+/// Header naming the event a delivery is for, e.g. `saved`.
+pub const EVENT_HEADER: &str = "X-Klyja-Event";
+/// Longest URL a webhook can have.
+pub const MAX_URL_CHARS: usize = 2048;
+/// How long a receiver has to answer before the delivery counts as failed.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether webhooks may call loopback and private addresses, for local development
+/// and tests: set `WEBHOOK_ALLOW_PRIVATE_HOSTS=true`.
+fn private_hosts_allowed() -> bool {
+    std::env::var("WEBHOOK_ALLOW_PRIVATE_HOSTS").is_ok_and(|allow| allow == "true")
+}
+
+/// Whether `ip` is reachable from the internet at large: not loopback, private,
+/// link-local (which includes cloud metadata services), shared, unspecified,
+/// broadcast, documentation or multicast. IPv6 addresses that carry an IPv4 one are
+/// judged by the IPv4 address they lead to.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0 // "This network"
+                || (a == 100 && (64..128).contains(&b))) // Carrier-grade NAT
+        }
+        IpAddr::V6(ip) => {
+            if ip.is_loopback() || ip.is_unspecified() {
+                return false;
+            }
+            if let Some(v4) = embedded_ipv4(ip) {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let [first, second, third, ..] = ip.segments();
+            !(ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00 // Unique local
+                || (first & 0xffc0) == 0xfe80 // Link-local
+                || (first & 0xffc0) == 0xfec0 // Site-local, deprecated but still routable
+                || (first, second, third) == (0x64, 0xff9b, 1)) // Local-use NAT64
+        }
+    }
+}
+
+/// The IPv4 address an IPv6 one reaches through: IPv4-mapped (`::ffff:a.b.c.d`),
+/// IPv4-compatible (`::a.b.c.d`), NAT64 (`64:ff9b::a.b.c.d`) or 6to4
+/// (`2002:aabb:ccdd::`).
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let [.., a, b, c, d] = ip.octets();
+    match ip.segments() {
+        [0, 0, 0, 0, 0, 0xffff | 0, ..] | [0x64, 0xff9b, 0, 0, 0, 0, ..] => {
+            Some(Ipv4Addr::new(a, b, c, d))
+        }
+        [0x2002, high, low, ..] => Some(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low))),
+        _ => None,
+    }
+}
+
+/// Checks that every address `url`'s host has is public, saying why not otherwise.
+async fn check_destination(url: &reqwest::Url) -> Result<(), String> {
+    if private_hosts_allowed() {
+        return Ok(());
+    }
+    let host = url.host_str().unwrap_or_default();
+    // IPv6 literals keep their brackets in URLs
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<IpAddr> = match literal.parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => {
+            let port = url.port_or_known_default().unwrap_or(80);
+            tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|err| format!("Webhook host '{}' can't be found: {}", host, err))?
+                .map(|address| address.ip())
+                .collect()
+        }
+    };
+    if addresses.is_empty() || !addresses.into_iter().all(is_public_ip) {
+        return Err(format!("Webhook host '{}' is not a public address", host));
+    }
+    Ok(())
+}
+
+/// Resolves host names for deliveries, leaving out non-public addresses, so a name
+/// can't be pointed at a private address between the check and the request.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let allow_private = private_hosts_allowed();
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|address| allow_private || is_public_ip(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("'{}' has no public address", host).into());
+            }
+            Ok(Box::new(addresses.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// The `X-Klyja-Signature` value for a body, for receivers to recompute and compare.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    use hmac::{Hmac, KeyInit, Mac};
+    use sha2::Sha256;
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// Queues a delivery to each of the animation owner's webhooks that asked for
+/// `event`. Unowned animations have nobody to tell. Call it in the transaction
+/// making the change, so deliveries go out only for changes that were stored.
+pub(crate) async fn enqueue_event(
+    conn: &mut AsyncPgConnection,
+    animation_id: i32,
+    event: WebhookEvent,
+    version: i32,
+) -> Result<(), AppError> {
+    use crate::schema::{animations, webhooks};
+
+    let owner = animations::table
+        .find(animation_id)
+        .select(animations::owner_id)
+        .first::<Option<i32>>(conn)
+        .await?;
+    let Some(owner) = owner else {
+        return Ok(());
+    };
+    let webhook_ids = webhooks::table
+        .filter(webhooks::user_id.eq(owner))
+        .filter(webhooks::events.contains(vec![event.as_str().to_string()]))
+        .select(webhooks::id)
+        .load::<i32>(conn)
+        .await?;
+    for webhook_id in webhook_ids {
+        let job = Job::DeliverWebhook {
+            webhook_id,
+            event,
+            animation_id,
+            version,
+        };
+        // Not tied to the animation: its viewers could see the job, and deliveries
+        // of its deletion must outlive it
+        jobs::enqueue(conn, &job, None).await?;
+    }
+    Ok(())
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            // A redirect could lead anywhere, past the address checks
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()
+            .expect("Building the webhook HTTP client failed")
+    })
+}
+
+/// Sends one signed delivery. Failures, including non-2xx answers, are returned so
+/// the job is retried; webhooks deleted since the event was queued are skipped.
+pub async fn deliver(
+    pool: &DbPool,
+    webhook_id: i32,
+    event: WebhookEvent,
+    animation_id: i32,
+    version: i32,
+) -> Result<(), AppError> {
+    let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+    use crate::schema::webhooks;
+
+    let webhook = webhooks::table
+        .find(webhook_id)
+        .select((webhooks::url, webhooks::secret))
+        .first::<(String, String)>(&mut conn)
+        .await
+        .optional()?;
+    drop(conn); // Not held while waiting on the receiver
+    let Some((url, secret)) = webhook else {
+        tracing::info!("JOB: Webhook {} was deleted, not delivering", webhook_id);
+        return Ok(());
+    };
+
+    // Host names are checked again as they are resolved, by the client's resolver
+    let parsed = reqwest::Url::parse(&url).map_err(|err| {
+        AppError::Internal(format!("Webhook {} URL is invalid: {}", webhook_id, err))
+    })?;
+    check_destination(&parsed).await.map_err(|msg| {
+        AppError::Internal(format!("Webhook {} not delivered: {}", webhook_id, msg))
+    })?;
+
+    let body = serde_json::to_vec(&serde_json::json!({
+        "event": event.as_str(),
+        "animation_id": animation_id,
+        "version": version,
+    }))
+    .map_err(|err| AppError::Internal(format!("Serializing webhook body failed: {}", err)))?;
+    let response = client()
+        .post(&url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, event.as_str())
+        .header(SIGNATURE_HEADER, sign(&secret, &body))
+        .body(body)
+        .send()
+        .await
+        // Errors are kept with the job; URLs often carry tokens, so leave them out
+        .map_err(|err| {
+            AppError::Internal(format!(
+                "Webhook {} failed: {}",
+                webhook_id,
+                err.without_url()
+            ))
+        })?;
+    if !response.status().is_success() {
+        return Err(AppError::Internal(format!(
+            "Webhook {} answered {}",
+            webhook_id,
+            response.status()
+        )));
+    }
+    tracing::info!(
+        "JOB: Delivered {} of animation {} to webhook {}",
+        event.as_str(),
+        animation_id,
+        webhook_id
+    );
+    Ok(())
+}
+
+pub struct WebhookService;
+
+impl WebhookService {
+    /// Lists the user's webhooks, newest first.
+    pub async fn list_webhooks_logic(
+        pool: &DbPool,
+        user_id: i32,
+    ) -> Result<Vec<Webhook>, AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::webhooks;
+
+        webhooks::table
+            .filter(webhooks::user_id.eq(user_id))
+            .order((webhooks::created_at.desc(), webhooks::id.desc()))
+            .select(Webhook::as_select())
+            .load::<Webhook>(&mut conn)
+            .await
+            .map_err(AppError::DatabaseQuery)
+    }
+
+    /// Registers a webhook for the user's animations. The secret is returned here
+    /// and never again.
+    pub async fn create_webhook_logic(
+        pool: &DbPool,
+        user_id: i32,
+        request: CreateWebhookRequest,
+    ) -> Result<CreatedWebhook, AppError> {
+        let url = request.url.trim().to_string();
+        let parsed = reqwest::Url::parse(&url)
+            .map_err(|err| AppError::BadRequest(format!("'{}' is not a URL: {}", url, err)))?;
+        if !matches!(parsed.scheme(), "http" | "https") || url.chars().count() > MAX_URL_CHARS {
+            return Err(AppError::BadRequest(format!(
+                "Webhook URLs must be http or https and at most {} characters",
+                MAX_URL_CHARS
+            )));
+        }
+        check_destination(&parsed)
+            .await
+            .map_err(AppError::BadRequest)?;
+        if request.events.is_empty() {
+            return Err(AppError::BadRequest(
+                "Give the webhook at least one event".to_string(),
+            ));
+        }
+        let secret = match request.secret {
+            Some(secret) if secret.is_empty() || secret.chars().count() > 255 => {
+                return Err(AppError::BadRequest(
+                    "Webhook secrets need between 1 and 255 characters".to_string(),
+                ));
+            }
+            Some(secret) => secret,
+            None => random_token(),
+        };
+        let mut events: Vec<&str> = request.events.iter().map(|event| event.as_str()).collect();
+        events.sort_unstable();
+        events.dedup();
+        let events: Vec<String> = events.into_iter().map(str::to_string).collect();
+
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::webhooks;
+
+        let details = diesel::insert_into(webhooks::table)
+            .values((
+                webhooks::user_id.eq(user_id),
+                webhooks::url.eq(&url),
+                webhooks::secret.eq(&secret),
+                webhooks::events.eq(&events),
+            ))
+            .returning(Webhook::as_returning())
+            .get_result::<Webhook>(&mut conn)
+            .await?;
+        tracing::info!(
+            "SERVICE: User {} registered webhook {} for {:?}",
+            user_id,
+            details.id,
+            details.events
+        );
+        Ok(CreatedWebhook { secret, details })
+    }
+
+    /// Deletes one of the user's webhooks; deliveries still queued for it are dropped.
+    pub async fn delete_webhook_logic(
+        pool: &DbPool,
+        user_id: i32,
+        webhook_id: i32,
+    ) -> Result<(), AppError> {
+        let mut conn = pool.get().await.map_err(AppError::DatabasePool)?;
+        use crate::schema::webhooks;
+
+        let deleted = diesel::delete(
+            webhooks::table
+                .find(webhook_id)
+                .filter(webhooks::user_id.eq(user_id)),
+        )
+        .execute(&mut conn)
+        .await?;
+        if deleted == 0 {
+            return Err(AppError::NotFound(format!(
+                "Webhook {} not found",
+                webhook_id
+            )));
+        }
+        tracing::info!("SERVICE: User {} deleted webhook {}", user_id, webhook_id);
+        Ok(())
+    }
+}
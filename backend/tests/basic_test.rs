@@ -13,6 +13,11 @@ fn test_map_animation_creation() {
         animation_id: "test-id".to_string(),
         total_frames: 10,
         polygons: vec![],
+        grid_layers: vec![],
+        camera_keyframes: vec![],
+        data_series: vec![],
+        scenes: vec![],
+        format_version: 0,
     };
     
     // Test that fields are set correctly
@@ -45,6 +50,24 @@ fn test_animation_struct() {
         protobuf_data: vec![1, 2, 3],
         created_at: now,
         updated_at: now,
+        owner_id: None,
+        content_sha256: None,
+        lock_version: 1,
+        animation_uuid: None,
+        description: None,
+        license: None,
+        source_attribution: None,
+        hidden_at: None,
+        folder_id: None,
+        published_at: None,
+        publish_at: None,
+        created_by: None,
+        last_edited_by: None,
+        encoded_bytes: 3,
+        feature_count: None,
+        point_count: None,
+        keyframe_count: None,
+        format_version: 0,
     };
     
     assert_eq!(animation.id, 123);
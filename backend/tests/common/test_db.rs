@@ -1,20 +1,15 @@
 // backend/tests/common/test_db.rs
+use backend::DbPool;
 use diesel::prelude::*;
-use diesel::r2d2::{self, ConnectionManager, Pool};
 use diesel::sql_query;
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use std::env;
 
-// Import the same migrations from your main app
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../migrations");
-
-pub type TestDbPool = Pool<ConnectionManager<PgConnection>>;
-
 /// Test database configuration
 pub struct TestDb {
-    pub pool: TestDbPool,
+    pub pool: DbPool, // Async pool for the app under test
     db_name: String,
-    _base_pool: TestDbPool, // Keep connection to postgres db for cleanup
+    db_url: String,
+    base_url: String, // The postgres database, for cleanup
 }
 
 impl TestDb {
@@ -31,47 +26,34 @@ impl TestDb {
             rand::random::<u32>()
         );
 
-        // Create connection to postgres database for administrative tasks
-        let manager = ConnectionManager::<PgConnection>::new(&base_url);
-        let base_pool = r2d2::Pool::builder()
-            .max_size(1)
-            .build(manager)
-            .expect("Failed to create base pool");
-
-        // Create the test database
+        // Create the test database, connected to the postgres database
         {
-            let mut conn = base_pool.get().expect("Failed to get base connection");
+            let mut conn =
+                PgConnection::establish(&base_url).expect("Failed to get base connection");
             let query = format!("CREATE DATABASE \"{}\"", db_name);
             sql_query(query)
                 .execute(&mut conn)
                 .expect("Failed to create test database");
         }
 
-        // Create connection pool for the test database
-        let test_db_url = base_url.rsplit_once('/').unwrap().0.to_string() + "/" + &db_name;
-        let manager = ConnectionManager::<PgConnection>::new(&test_db_url);
-        let pool = r2d2::Pool::builder()
-            .max_size(5)
-            .build(manager)
-            .expect("Failed to create test pool");
-
         // Run migrations
-        {
-            let mut conn = pool.get().expect("Failed to get test connection");
-            conn.run_pending_migrations(MIGRATIONS)
-                .expect("Failed to run migrations");
-        }
+        let db_url = base_url.rsplit_once('/').unwrap().0.to_string() + "/" + &db_name;
+        backend::db::run_migrations(&db_url).expect("Failed to run migrations");
+
+        // Job workers may still be polling when the database is dropped; fail fast then
+        let pool = backend::db::build_pool(&db_url, 5, std::time::Duration::from_secs(2));
 
         TestDb {
             pool,
             db_name,
-            _base_pool: base_pool,
+            db_url,
+            base_url,
         }
     }
 
-    /// Get a connection from the test pool
-    pub fn conn(&self) -> r2d2::PooledConnection<ConnectionManager<PgConnection>> {
-        self.pool.get().expect("Failed to get test connection")
+    /// Opens a synchronous connection to the test database, for fixtures and checks
+    pub fn conn(&self) -> PgConnection {
+        PgConnection::establish(&self.db_url).expect("Failed to get test connection")
     }
 }
 
@@ -81,7 +63,7 @@ impl Drop for TestDb {
         // Note: In a real scenario, you might need to force disconnect active connections
 
         // Drop the test database
-        if let Ok(mut conn) = self._base_pool.get() {
+        if let Ok(mut conn) = PgConnection::establish(&self.base_url) {
             // Terminate existing connections to the test database
             let terminate_query = format!(
                 "SELECT pg_terminate_backend(pid) FROM pg_stat_activity 
@@ -100,7 +82,8 @@ impl Drop for TestDb {
 /// Creates test data for integration tests
 pub mod fixtures {
     use backend::models::{Animation, NewAnimation};
-    use backend::protobuf_gen::{AnimatedPoint, MapAnimation, Point, Polygon};
+    use backend::protobuf_gen::{AnimatedPoint, FeatureType, MapAnimation, Point, Polygon};
+    use backend::services::{content_sha256, measure_document};
     use diesel::prelude::*;
     use prost::Message;
 
@@ -115,12 +98,21 @@ pub mod fixtures {
             point_id: "test-point".to_string(),
             initial_position: Some(point),
             movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
         };
 
         let polygon = Polygon {
             polygon_id: "test-polygon".to_string(),
             points: vec![animated_point],
             properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
         };
 
         let animation = MapAnimation {
@@ -128,22 +120,95 @@ pub mod fixtures {
             name: name.to_string(),
             total_frames: 30,
             polygons: vec![polygon],
+            grid_layers: vec![],
+            camera_keyframes: vec![],
+            data_series: vec![],
+            scenes: vec![],
+            format_version: geco_core::format::CURRENT_FORMAT_VERSION,
         };
 
         animation.encode_to_vec()
     }
 
+    /// The sizes stored with a fixture document, which is stored uncompressed.
+    fn metrics(protobuf_data: &[u8]) -> backend::models::DocumentMetrics {
+        let document = MapAnimation::decode(protobuf_data).expect("Fixture document decodes");
+        measure_document(&document, protobuf_data.len())
+    }
+
     pub fn insert_test_animation(conn: &mut PgConnection, name: &str) -> Animation {
         use backend::schema::animations;
 
+        let protobuf_data = create_test_animation_proto(name);
+        let new_animation = NewAnimation {
+            name,
+            protobuf_data: &protobuf_data,
+            owner_id: None,
+            content_sha256: &content_sha256(&protobuf_data),
+            animation_uuid: None,
+            created_by: None,
+            last_edited_by: None,
+            metrics: metrics(&protobuf_data),
+        };
+
+        diesel::insert_into(animations::table)
+            .values(&new_animation)
+            .returning(Animation::as_returning())
+            .get_result::<Animation>(conn)
+            .expect("Failed to insert test animation")
+    }
+
+    pub fn insert_owned_test_animation(
+        conn: &mut PgConnection,
+        name: &str,
+        owner_id: i32,
+    ) -> Animation {
+        use backend::schema::animations;
+
+        let protobuf_data = create_test_animation_proto(name);
         let new_animation = NewAnimation {
             name,
-            protobuf_data: &create_test_animation_proto(name),
+            protobuf_data: &protobuf_data,
+            owner_id: Some(owner_id),
+            content_sha256: &content_sha256(&protobuf_data),
+            animation_uuid: None,
+            created_by: Some(owner_id),
+            last_edited_by: Some(owner_id),
+            metrics: metrics(&protobuf_data),
         };
 
         diesel::insert_into(animations::table)
             .values(&new_animation)
+            .returning(Animation::as_returning())
             .get_result::<Animation>(conn)
             .expect("Failed to insert test animation")
     }
+
+    pub fn insert_test_user(conn: &mut PgConnection, username: &str) -> i32 {
+        use backend::schema::users;
+
+        diesel::insert_into(users::table)
+            .values(users::username.eq(username))
+            .returning(users::id)
+            .get_result(conn)
+            .expect("Failed to insert test user")
+    }
+
+    /// Starts a session for the user, returning the `Cookie` header value to send.
+    pub fn insert_test_session(conn: &mut PgConnection, user_id: i32) -> String {
+        use backend::auth::SESSION_COOKIE;
+        use backend::schema::sessions;
+
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let expires_at = chrono::Utc::now().naive_utc() + chrono::Duration::hours(1);
+        diesel::insert_into(sessions::table)
+            .values((
+                sessions::token.eq(&token),
+                sessions::user_id.eq(user_id),
+                sessions::expires_at.eq(expires_at),
+            ))
+            .execute(conn)
+            .expect("Failed to insert test session");
+        format!("{}={}", SESSION_COOKIE, token)
+    }
 }
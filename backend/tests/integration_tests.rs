@@ -1,10 +1,16 @@
 // backend/tests/integration_tests.rs
 mod common;
 
-use axum::http::StatusCode; // Removed Request and body::Body
+use axum::http::{
+    header::{
+        ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE,
+        COOKIE, ETAG, IF_MATCH, USER_AGENT,
+    },
+    HeaderValue, StatusCode,
+}; // Removed Request and body::Body
 use axum_test::TestServer;
 use backend::protobuf_gen::MapAnimation;
-use backend::{handlers, DbPool};
+use backend::DbPool;
 use bytes::Bytes; // Import Bytes
 use common::{fixtures, TestDb};
 use prost::Message;
@@ -14,23 +20,12 @@ use rstest::*;
 // For now, let's comment it out as TestServer abstracts its usage.
 // use tower::ServiceExt; // for oneshot
 
-/// Creates a test server with the test database
+/// Creates a test server with the test database, running the same app main.rs serves
 async fn create_test_app(pool: DbPool) -> TestServer {
-    let app = axum::Router::new()
-        .route(
-            "/api/health",
-            axum::routing::get(handlers::health_check_handler),
-        )
-        .route(
-            "/api/save_animation",
-            axum::routing::post(handlers::save_animation_handler),
-        )
-        .route(
-            "/api/load_animation/:id",
-            axum::routing::get(handlers::load_animation_handler),
-        )
-        .with_state(pool);
+    // Thumbnails and exports are rendered by the job queue
+    backend::jobs::spawn_workers(pool.clone(), 1);
 
+    let app = backend::app::build_app(&backend::app::AppConfig::from_env(), pool);
     TestServer::new(app).unwrap()
 }
 
@@ -45,6 +40,60 @@ async fn test_health_check() {
     assert_eq!(response.text(), "Healthy!");
 }
 
+#[tokio::test]
+async fn test_app_has_the_served_layers() {
+    let test_db = TestDb::new();
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    let response = server
+        .get("/api/health")
+        .add_header(
+            axum::http::header::ORIGIN,
+            HeaderValue::from_static("https://example.org"),
+        )
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "*"
+    );
+    assert!(response.headers().contains_key("x-request-id"));
+    assert_eq!(
+        server.get("/api-docs/openapi.json").await.status_code(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_readiness_reports_database_and_pool() {
+    let test_db = TestDb::new();
+    let server = create_test_app(test_db.pool.clone()).await;
+    // Saves and loads are what latency is measured on
+    let response = server
+        .post("/api/save_animation")
+        .bytes(Bytes::from(fixtures::create_test_animation_proto("Timed")))
+        .await;
+    let animation_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+    server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .await;
+
+    let response = server.get("/api/health/ready").await;
+
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let body = response.json::<serde_json::Value>();
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["database"], true);
+    assert_eq!(body["pending_migrations"], 0);
+    assert!(body["latest_migration"].is_string());
+    assert!(body["pool"]["connections"].as_u64().unwrap() > 0);
+    assert!(body["pool"]["in_use_connections"].is_u64());
+    assert!(body["query_latency"]["samples"].as_u64().unwrap() > 0);
+    assert!(body["query_latency"]["p95_ms"].as_f64().is_some());
+    assert!(body["error"].is_null());
+}
+
 #[tokio::test]
 async fn test_save_animation_success() {
     let test_db = TestDb::new();
@@ -87,6 +136,77 @@ async fn test_save_animation_invalid_protobuf() {
         .contains("Invalid data format"));
 }
 
+#[tokio::test]
+async fn test_save_animation_rejects_invalid_document() {
+    let test_db = TestDb::new();
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    // Decodes fine, but both features share an ID
+    let mut animation =
+        MapAnimation::decode(&fixtures::create_test_animation_proto("Duplicate IDs")[..]).unwrap();
+    animation.polygons.push(animation.polygons[0].clone());
+
+    let response = server
+        .post("/api/save_animation")
+        .bytes(Bytes::from(animation.encode_to_vec()))
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let json: serde_json::Value = response.json();
+    assert!(json["error"]
+        .as_str()
+        .unwrap()
+        .contains("duplicate feature ID 'test-polygon'"));
+    assert_eq!(json["details"][0]["path"], "polygons[1]");
+    assert_eq!(
+        json["details"][0]["message"],
+        "duplicate feature ID 'test-polygon'"
+    );
+}
+
+#[tokio::test]
+async fn test_documents_from_newer_formats_are_refused_and_current_ones_kept() {
+    use geco_core::format::CURRENT_FORMAT_VERSION;
+
+    let test_db = TestDb::new();
+    let stored = {
+        let mut conn = test_db.conn();
+        fixtures::insert_test_animation(&mut conn, "Current")
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    let mut animation =
+        MapAnimation::decode(&fixtures::create_test_animation_proto("From the future")[..])
+            .unwrap();
+    animation.format_version = CURRENT_FORMAT_VERSION + 1;
+    let response = server
+        .post("/api/save_animation")
+        .bytes(Bytes::from(animation.encode_to_vec()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    let json: serde_json::Value = response.json();
+    let error = json["error"].as_str().unwrap();
+    assert!(
+        error.contains(&format!("format version {}", CURRENT_FORMAT_VERSION + 1)),
+        "{}",
+        error
+    );
+
+    // Nothing stored is behind, so the rewrite leaves every document as it was
+    assert_eq!(
+        backend::upgrades::upgrade_documents(&test_db.pool)
+            .await
+            .unwrap(),
+        0
+    );
+    let response = server
+        .get(&format!("/api/load_animation/{}", stored.id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.as_bytes().to_vec(), stored.protobuf_data);
+}
+
 #[tokio::test]
 async fn test_load_animation_success() {
     let test_db = TestDb::new();
@@ -149,6 +269,157 @@ async fn test_save_and_load_flow() {
     assert_eq!(loaded_bytes.to_vec(), animation_data_vec); // Compare Vec<u8> with Vec<u8>
 }
 
+#[tokio::test]
+async fn test_saved_documents_are_stored_compressed() {
+    let test_db = TestDb::new();
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    let mut animation =
+        MapAnimation::decode(fixtures::create_test_animation_proto("Compressed").as_slice())
+            .unwrap();
+    let polygon = animation.polygons[0].clone();
+    animation.polygons = (0..200)
+        .map(|i| backend::protobuf_gen::Polygon {
+            polygon_id: format!("poly-{}", i),
+            ..polygon.clone()
+        })
+        .collect();
+    let document = animation.encode_to_vec();
+
+    let save_response = server
+        .post("/api/save_animation")
+        .bytes(Bytes::from(document.clone()))
+        .await;
+    assert_eq!(save_response.status_code(), StatusCode::CREATED);
+    let animation_id = save_response.json::<serde_json::Value>()["id"]
+        .as_i64()
+        .unwrap() as i32;
+
+    let stored = {
+        use backend::schema::animations;
+        use diesel::prelude::*;
+        animations::table
+            .find(animation_id)
+            .select(animations::protobuf_data)
+            .first::<Vec<u8>>(&mut test_db.conn())
+            .unwrap()
+    };
+    assert_eq!(stored[0], backend::services::COMPRESSED_DOCUMENT_MARKER);
+    assert!(stored.len() < document.len() / 2);
+
+    // Clients still get the document exactly as sent
+    let load_response = server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .await;
+    assert_eq!(load_response.status_code(), StatusCode::OK);
+    assert_eq!(load_response.into_bytes().to_vec(), document);
+}
+
+#[tokio::test]
+async fn test_identical_saves_reuse_latest_animation() {
+    let test_db = TestDb::new();
+    let cookie = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "autosaver");
+        fixtures::insert_test_session(&mut conn, owner)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let save = |document: Vec<u8>, cookie: Option<HeaderValue>| {
+        let mut request = server
+            .post("/api/save_animation")
+            .bytes(Bytes::from(document));
+        if let Some(cookie) = cookie {
+            request = request.add_header(COOKIE, cookie);
+        }
+        async move {
+            let response = request.await;
+            assert_eq!(response.status_code(), StatusCode::CREATED);
+            response.json::<serde_json::Value>()["id"].as_i64().unwrap()
+        }
+    };
+    let first = fixtures::create_test_animation_proto("Autosaved");
+    let renamed = fixtures::create_test_animation_proto("Renamed");
+
+    let first_id = save(first.clone(), Some(cookie.clone())).await;
+    assert_eq!(save(first.clone(), Some(cookie.clone())).await, first_id);
+
+    // Another document gets its own row, and the first is still found by its ID
+    let renamed_id = save(renamed, Some(cookie.clone())).await;
+    assert_ne!(renamed_id, first_id);
+    assert_eq!(save(first.clone(), Some(cookie)).await, first_id);
+
+    // Anonymous saves have no history to compare against
+    let anonymous_id = save(first.clone(), None).await;
+    assert_ne!(save(first, None).await, anonymous_id);
+}
+
+#[tokio::test]
+async fn test_saves_of_the_same_document_update_in_place() {
+    let test_db = TestDb::new();
+    let (owner_cookie, other_cookie) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let other = fixtures::insert_test_user(&mut conn, "other");
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_session(&mut conn, other),
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let owner_cookie = HeaderValue::from_str(&owner_cookie).unwrap();
+    let other_cookie = HeaderValue::from_str(&other_cookie).unwrap();
+
+    let save = |document: &MapAnimation, cookie: HeaderValue| {
+        let request = server
+            .post("/api/save_animation")
+            .add_header(COOKIE, cookie)
+            .add_header(IF_MATCH, HeaderValue::from_static("\"1\""))
+            .bytes(Bytes::from(document.encode_to_vec()));
+        async move {
+            let response = request.await;
+            assert_eq!(response.status_code(), StatusCode::CREATED);
+            response.json::<serde_json::Value>()["id"].as_i64().unwrap()
+        }
+    };
+    let mut document =
+        MapAnimation::decode(&fixtures::create_test_animation_proto("Draft")[..]).unwrap();
+    let first_id = save(&document, owner_cookie.clone()).await;
+
+    // Updating in place needs the version the changes are based on, as a PUT does
+    document.name = "Edited".to_string();
+    document.total_frames = 60;
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, owner_cookie.clone())
+        .bytes(Bytes::from(document.encode_to_vec()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PRECONDITION_REQUIRED);
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, owner_cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("\"7\""))
+        .bytes(Bytes::from(document.encode_to_vec()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CONFLICT);
+    assert_eq!(save(&document, owner_cookie.clone()).await, first_id);
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", first_id))
+        .add_header(COOKIE, owner_cookie.clone())
+        .await;
+    assert_eq!(response.headers()[ETAG], "\"2\"");
+    let saved = MapAnimation::decode(response.as_bytes().as_ref()).unwrap();
+    assert_eq!(saved, document);
+
+    // IDs are only matched among one user's animations
+    assert_ne!(save(&document, other_cookie).await, first_id);
+
+    document.animation_id = format!("test-{}", uuid::Uuid::new_v4());
+    assert_ne!(save(&document, owner_cookie).await, first_id);
+}
+
 #[rstest]
 #[case::small(10)]
 #[case::medium(100)]
@@ -163,6 +434,11 @@ async fn test_save_animation_various_sizes(#[case] polygon_count: usize) {
         name: format!("Size Test {}", polygon_count),
         total_frames: 30,
         polygons: Vec::with_capacity(polygon_count),
+        grid_layers: vec![],
+        camera_keyframes: vec![],
+        data_series: vec![],
+        scenes: vec![],
+        format_version: geco_core::format::CURRENT_FORMAT_VERSION,
     };
 
     for i in 0..polygon_count {
@@ -170,6 +446,11 @@ async fn test_save_animation_various_sizes(#[case] polygon_count: usize) {
             polygon_id: format!("poly-{}", i),
             points: vec![],
             properties: Default::default(),
+            draw_order: 0,
+            feature_type: backend::protobuf_gen::FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
         };
         animation.polygons.push(polygon);
     }
@@ -184,3 +465,4067 @@ async fn test_save_animation_various_sizes(#[case] polygon_count: usize) {
 
     assert_eq!(response.status_code(), StatusCode::CREATED);
 }
+
+#[tokio::test]
+async fn test_list_animations_filters_by_name() {
+    let test_db = TestDb::new();
+    {
+        let mut conn = test_db.conn();
+        fixtures::insert_test_animation(&mut conn, "Pangaea Breakup");
+        fixtures::insert_test_animation(&mut conn, "Ice Age");
+        fixtures::insert_test_animation(&mut conn, "100% pangaea");
+    }
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    let response = server.get("/api/animations").await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json.as_array().unwrap().len(), 3);
+    assert!(json[0].get("protobuf_data").is_none());
+
+    // Matching is case-insensitive
+    let response = server
+        .get("/api/animations")
+        .add_query_param("q", "PANGAEA")
+        .await;
+    let json: serde_json::Value = response.json();
+    let mut names: Vec<&str> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["name"].as_str().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["100% pangaea", "Pangaea Breakup"]);
+
+    // Wildcards in the query are matched literally
+    let response = server
+        .get("/api/animations")
+        .add_query_param("q", "%")
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert_eq!(json[0]["name"], "100% pangaea");
+}
+
+#[tokio::test]
+async fn test_stars_are_counted_and_sorted_on() {
+    let test_db = TestDb::new();
+    let (first_cookie, second_cookie, starred_id, private_id) = {
+        let mut conn = test_db.conn();
+        let first = fixtures::insert_test_user(&mut conn, "first");
+        let second = fixtures::insert_test_user(&mut conn, "second");
+        let starred_id = fixtures::insert_test_animation(&mut conn, "Starred").id;
+        fixtures::insert_test_animation(&mut conn, "Newer");
+        let private_id = fixtures::insert_owned_test_animation(&mut conn, "Private", second).id;
+        (
+            fixtures::insert_test_session(&mut conn, first),
+            fixtures::insert_test_session(&mut conn, second),
+            starred_id,
+            private_id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let first_cookie = HeaderValue::from_str(&first_cookie).unwrap();
+    let second_cookie = HeaderValue::from_str(&second_cookie).unwrap();
+    let star_url = format!("/api/animation/{}/star", starred_id);
+
+    let response = server.post(&star_url).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+
+    // Starring twice counts once
+    for _ in 0..2 {
+        let response = server
+            .post(&star_url)
+            .add_header(COOKIE, first_cookie.clone())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.json::<serde_json::Value>()["star_count"], 1);
+    }
+    let response = server
+        .post(&star_url)
+        .add_header(COOKIE, second_cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["starred"], true);
+    assert_eq!(json["star_count"], 2);
+
+    let response = server
+        .post(&format!("/api/animation/{}/star", private_id))
+        .add_header(COOKIE, first_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let names_and_stars = |json: serde_json::Value| -> Vec<(String, i64)> {
+        json.as_array()
+            .unwrap()
+            .iter()
+            .map(|a| {
+                (
+                    a["name"].as_str().unwrap().to_string(),
+                    a["star_count"].as_i64().unwrap(),
+                )
+            })
+            .collect()
+    };
+    let response = server
+        .get("/api/animations")
+        .add_query_param("sort", "stars")
+        .await;
+    assert_eq!(
+        names_and_stars(response.json()),
+        vec![("Starred".to_string(), 2), ("Newer".to_string(), 0)]
+    );
+
+    let response = server
+        .delete(&star_url)
+        .add_header(COOKIE, second_cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["starred"], false);
+    assert_eq!(json["star_count"], 1);
+
+    let response = server
+        .get("/api/animations")
+        .add_query_param("sort", "recent")
+        .await;
+    assert_eq!(
+        names_and_stars(response.json()),
+        vec![("Newer".to_string(), 0), ("Starred".to_string(), 1)]
+    );
+}
+
+#[tokio::test]
+async fn test_owned_animation_is_private_to_owner() {
+    let test_db = TestDb::new();
+    let (owner_cookie, stranger_cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let stranger = fixtures::insert_test_user(&mut conn, "stranger");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Private", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_session(&mut conn, stranger),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let url = format!("/api/load_animation/{}", animation_id);
+
+    let response = server.get(&url).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+
+    let response = server
+        .get(&url)
+        .add_header(COOKIE, HeaderValue::from_str(&stranger_cookie).unwrap())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .get(&url)
+        .add_header(COOKIE, HeaderValue::from_str(&owner_cookie).unwrap())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    // Listings only show it to the owner
+    let response = server.get("/api/animations").await;
+    let json: serde_json::Value = response.json();
+    assert!(json.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_collaborator_roles_control_load_and_save() {
+    let test_db = TestDb::new();
+    let (owner_cookie, collaborator, collaborator_cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let collaborator = fixtures::insert_test_user(&mut conn, "collaborator");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Shared", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            collaborator,
+            fixtures::insert_test_session(&mut conn, collaborator),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let owner_cookie = HeaderValue::from_str(&owner_cookie).unwrap();
+    let collaborator_cookie = HeaderValue::from_str(&collaborator_cookie).unwrap();
+    let collaborators_url = format!("/api/animation/{}/collaborators", animation_id);
+    let update_url = format!("/api/animation/{}", animation_id);
+    let document = Bytes::from(fixtures::create_test_animation_proto("Shared v2"));
+
+    // Viewers can load but not save
+    let response = server
+        .post(&collaborators_url)
+        .add_header(COOKIE, owner_cookie.clone())
+        .json(&serde_json::json!({ "user_id": collaborator, "role": "viewer" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .add_header(COOKIE, collaborator_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let response = server
+        .put(&update_url)
+        .add_header(COOKIE, collaborator_cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("*"))
+        .bytes(document.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    // Collaborators can't share the animation further
+    let response = server
+        .post(&collaborators_url)
+        .add_header(COOKIE, collaborator_cookie.clone())
+        .json(&serde_json::json!({ "user_id": collaborator, "role": "editor" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    // Promoted to editor, they can save
+    server
+        .post(&collaborators_url)
+        .add_header(COOKIE, owner_cookie.clone())
+        .json(&serde_json::json!({ "user_id": collaborator, "role": "editor" }))
+        .await;
+    let response = server
+        .put(&update_url)
+        .add_header(COOKIE, collaborator_cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("*"))
+        .bytes(document)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    let response = server
+        .get(&collaborators_url)
+        .add_header(COOKIE, collaborator_cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(json[0]["username"], "collaborator");
+    assert_eq!(json[0]["role"], "editor");
+
+    // Once removed, they lose access
+    let response = server
+        .delete(&format!("{}/{}", collaborators_url, collaborator))
+        .add_header(COOKIE, owner_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .add_header(COOKIE, collaborator_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_updates_refuse_stale_versions() {
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Versioned", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let url = format!("/api/animation/{}", animation_id);
+    let document = Bytes::from(fixtures::create_test_animation_proto("Versioned v2"));
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let loaded_etag = response.headers()[ETAG].clone();
+    assert_eq!(loaded_etag, "\"1\"");
+
+    // Saves must say which version they replace
+    let response = server
+        .put(&url)
+        .add_header(COOKIE, cookie.clone())
+        .bytes(document.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PRECONDITION_REQUIRED);
+
+    let response = server
+        .put(&url)
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, loaded_etag.clone())
+        .bytes(document.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()[ETAG], "\"2\"");
+
+    // A second save based on the same load would overwrite the first
+    let response = server
+        .put(&url)
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, loaded_etag)
+        .bytes(document.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CONFLICT);
+    assert_eq!(response.headers()[ETAG], "\"2\"");
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["current_version"], 2);
+
+    let response = server
+        .put(&url)
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("W/\"2\""))
+        .bytes(document)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    let response = server
+        .get(&format!("/api/animation/{}/meta", animation_id))
+        .add_header(COOKIE, cookie)
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["lock_version"], 3);
+}
+
+#[tokio::test]
+async fn test_patch_saves_apply_to_stored_document() {
+    use geco_core::patch::{make_patch, DocumentPatch};
+
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Patched", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let load_url = format!("/api/load_animation/{}", animation_id);
+    let patch_url = format!("/api/animation/{}/patch", animation_id);
+
+    let response = server
+        .get(&load_url)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let base = MapAnimation::decode(response.as_bytes().as_ref()).unwrap();
+    let mut edited = base.clone();
+    let mut added = edited.polygons[0].clone();
+    added.polygon_id = "added-polygon".to_string();
+    edited.polygons.push(added);
+    let patch = make_patch(&base, &edited);
+    assert_eq!(patch.features.len(), 1);
+
+    let response = server
+        .post(&patch_url)
+        .add_header(COOKIE, cookie.clone())
+        .json(&patch)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PRECONDITION_REQUIRED);
+
+    let response = server
+        .post(&patch_url)
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("\"1\""))
+        .json(&patch)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()[ETAG], "\"2\"");
+
+    let response = server
+        .get(&load_url)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let saved = MapAnimation::decode(response.as_bytes().as_ref()).unwrap();
+    assert_eq!(saved, edited);
+
+    // The same patch again would be applied to a version it wasn't made from
+    let response = server
+        .post(&patch_url)
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("\"1\""))
+        .json(&patch)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CONFLICT);
+
+    let stray = DocumentPatch {
+        removed_features: vec!["no-such-feature".to_string()],
+        ..Default::default()
+    };
+    let response = server
+        .post(&patch_url)
+        .add_header(COOKIE, cookie)
+        .add_header(IF_MATCH, HeaderValue::from_static("\"2\""))
+        .json(&stray)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_diff_between_versions() {
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Diffed", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let diff_url = format!("/api/animation/{}/diff", animation_id);
+
+    let mut document =
+        MapAnimation::decode(fixtures::create_test_animation_proto("Diffed v2").as_slice())
+            .unwrap();
+    let mut added = document.polygons[0].clone();
+    added.polygon_id = "added-polygon".to_string();
+    document.polygons.push(added);
+    let response = server
+        .put(&format!("/api/animation/{}", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("\"1\""))
+        .bytes(Bytes::from(document.encode_to_vec()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    // Without versions, the latest save is compared with the one before it
+    let response = server
+        .get(&diff_url)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["from_version"], 1);
+    assert_eq!(json["to_version"], 2);
+    assert_eq!(json["name_changed"], true);
+    assert_eq!(json["added_features"], serde_json::json!(["added-polygon"]));
+    assert_eq!(json["removed_features"], serde_json::json!([]));
+    assert_eq!(json["changed_features"], serde_json::json!([]));
+
+    let response = server
+        .get(&diff_url)
+        .add_query_param("from", 2)
+        .add_query_param("to", 1)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(
+        json["removed_features"],
+        serde_json::json!(["added-polygon"])
+    );
+
+    let response = server
+        .get(&diff_url)
+        .add_query_param("from", 1)
+        .add_query_param("to", 9)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    // Private animations can't be compared by strangers
+    let response = server.get(&diff_url).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_drafts_are_kept_apart_from_saves() {
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Saved", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let draft_url = format!("/api/animation/{}/draft", animation_id);
+    let draft = fixtures::create_test_animation_proto("Work in progress");
+
+    let response = server
+        .get(&draft_url)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = server
+        .put(&draft_url)
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from_static(b"not a document"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    for _ in 0..3 {
+        let response = server
+            .put(&draft_url)
+            .add_header(COOKIE, cookie.clone())
+            .bytes(Bytes::from(draft.clone()))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[ETAG], "\"1\"");
+    }
+
+    // Autosaves don't touch the saved animation or its version
+    let response = server
+        .get(&format!("/api/animation/{}/meta", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["name"], "Saved");
+    assert_eq!(json["lock_version"], 1);
+
+    let response = server
+        .get(&draft_url)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.as_bytes().as_ref(), draft.as_slice());
+    let based_on = response.headers()[ETAG].clone();
+
+    // Saving the draft over the animation clears it
+    let response = server
+        .put(&format!("/api/animation/{}", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, based_on)
+        .bytes(Bytes::from(draft))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    let response = server.get(&draft_url).add_header(COOKIE, cookie).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_thumbnail_upload_and_listing() {
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Preview", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let url = format!("/api/animation/{}/thumbnail", animation_id);
+
+    let response = server
+        .get("/api/animations")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    assert!(json[0]["thumbnail_url"].is_null());
+
+    let response = server
+        .put(&url)
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from_static(b"GIF89a"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let png = Bytes::from_static(b"\x89PNG\r\n\x1a\nrest-of-image");
+    let response = server
+        .put(&url)
+        .add_header(COOKIE, cookie.clone())
+        .bytes(png.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    let response = server
+        .get("/api/animations")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    let thumbnail_url = json[0]["thumbnail_url"].as_str().unwrap().to_string();
+    assert!(thumbnail_url.starts_with(&url));
+
+    // TestServer would percent-encode a `?` left in the path
+    let (path, version) = thumbnail_url.split_once('?').unwrap();
+    let response = server
+        .get(path)
+        .add_raw_query_param(version)
+        .add_header(COOKIE, cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.header("content-type"), "image/png");
+    assert_eq!(response.as_bytes(), &png);
+
+    // Private animations keep their previews private too
+    let response = server.get(&url).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_saved_animation_gets_rendered_thumbnail() {
+    let test_db = TestDb::new();
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    let response = server
+        .post("/api/save_animation")
+        .bytes(Bytes::from(fixtures::create_test_animation_proto(
+            "Rendered",
+        )))
+        .await;
+    let animation_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+
+    // The render job runs after the response, so wait for it
+    let mut thumbnail_url = None;
+    for _ in 0..50 {
+        let json: serde_json::Value = server.get("/api/animations").await.json();
+        if let Some(url) = json[0]["thumbnail_url"].as_str() {
+            thumbnail_url = Some(url.to_string());
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let thumbnail_url = thumbnail_url.expect("Thumbnail was never rendered");
+    assert!(thumbnail_url.starts_with(&format!("/api/animation/{}/thumbnail", animation_id)));
+
+    let (path, version) = thumbnail_url.split_once('?').unwrap();
+    let response = server.get(path).add_raw_query_param(version).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert!(response.as_bytes().starts_with(b"\x89PNG"));
+}
+
+#[tokio::test]
+async fn test_duplicate_animation_creates_owned_copy() {
+    let test_db = TestDb::new();
+    let (owner_cookie, other_cookie, source_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let other = fixtures::insert_test_user(&mut conn, "other");
+        let source = fixtures::insert_owned_test_animation(&mut conn, "Experiment", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_session(&mut conn, other),
+            source.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let owner_cookie = HeaderValue::from_str(&owner_cookie).unwrap();
+    let url = format!("/api/animation/{}/duplicate", source_id);
+
+    // Needs a session, and access to the original
+    assert_eq!(
+        server.post(&url).await.status_code(),
+        StatusCode::UNAUTHORIZED
+    );
+    let response = server
+        .post(&url)
+        .add_header(COOKIE, HeaderValue::from_str(&other_cookie).unwrap())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .post(&url)
+        .add_header(COOKIE, owner_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let copy_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+    assert_ne!(copy_id, source_id as i64);
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", copy_id))
+        .add_header(COOKIE, owner_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let document = MapAnimation::decode(response.as_bytes().clone()).unwrap();
+    assert_eq!(document.name, "Experiment (copy)");
+    assert_eq!(document.polygons.len(), 1);
+
+    // The original had no thumbnail to copy, so one is rendered for the copy
+    let render_jobs = {
+        use backend::schema::jobs;
+        use diesel::prelude::*;
+
+        jobs::table
+            .filter(jobs::kind.eq("render_thumbnail"))
+            .filter(jobs::animation_id.eq(copy_id as i32))
+            .count()
+            .get_result::<i64>(&mut test_db.conn())
+            .unwrap()
+    };
+    assert_eq!(render_jobs, 1);
+}
+
+#[tokio::test]
+async fn test_animation_metadata() {
+    let test_db = TestDb::new();
+    let animation = {
+        let mut conn = test_db.conn();
+        fixtures::insert_test_animation(&mut conn, "Described")
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    let response = server
+        .get(&format!("/api/animation/{}/meta", animation.id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["id"], animation.id);
+    assert_eq!(json["name"], "Described");
+    assert_eq!(json["total_frames"], 30);
+    assert_eq!(json["feature_count"], 1);
+    assert_eq!(json["point_count"], 1);
+    assert_eq!(json["byte_size"], animation.protobuf_data.len());
+    assert_eq!(
+        json["content_sha256"],
+        backend::services::content_sha256(&animation.protobuf_data)
+    );
+    assert!(json["created_at"].is_string());
+    assert!(json.get("protobuf_data").is_none());
+
+    let response = server.get("/api/animation/999999/meta").await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_details_are_validated_and_shown_in_metadata() {
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Described", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let details_url = format!("/api/animation/{}/details", animation_id);
+    let meta_url = format!("/api/animation/{}/meta", animation_id);
+
+    let response = server
+        .put(&details_url)
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({
+            "description": "  Plates drifting apart.\nSecond paragraph.  ",
+            "license": "CC-BY-4.0",
+            "source_attribution": "PALEOMAP project",
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    let json: serde_json::Value = server
+        .get(&meta_url)
+        .add_header(COOKIE, cookie.clone())
+        .await
+        .json();
+    assert_eq!(
+        json["description"],
+        "Plates drifting apart.\nSecond paragraph."
+    );
+    assert_eq!(json["license"], "CC-BY-4.0");
+    assert_eq!(json["source_attribution"], "PALEOMAP project");
+    // Details aren't part of the document
+    assert_eq!(json["lock_version"], 1);
+
+    for invalid in [
+        serde_json::json!({ "license": "CC-BY\n4.0" }),
+        serde_json::json!({ "license": "x".repeat(backend::services::MAX_LICENSE_CHARS + 1) }),
+    ] {
+        let response = server
+            .put(&details_url)
+            .add_header(COOKIE, cookie.clone())
+            .json(&invalid)
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    // Left out or blank fields are cleared
+    let response = server
+        .put(&details_url)
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "license": "MIT", "description": "   " }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    let json: serde_json::Value = server
+        .get(&meta_url)
+        .add_header(COOKIE, cookie)
+        .await
+        .json();
+    assert_eq!(json["license"], "MIT");
+    assert!(json["description"].is_null() && json["source_attribution"].is_null());
+
+    let response = server
+        .put(&details_url)
+        .json(&serde_json::json!({ "license": "MIT" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_views_are_counted_for_the_owner() {
+    let test_db = TestDb::new();
+    let (owner_cookie, viewer_cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let viewer = fixtures::insert_test_user(&mut conn, "viewer");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Watched", owner);
+        {
+            use backend::schema::animation_collaborators;
+            use diesel::prelude::*;
+            diesel::insert_into(animation_collaborators::table)
+                .values((
+                    animation_collaborators::animation_id.eq(animation.id),
+                    animation_collaborators::user_id.eq(viewer),
+                    animation_collaborators::role.eq("viewer"),
+                ))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_session(&mut conn, viewer),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let owner_cookie = HeaderValue::from_str(&owner_cookie).unwrap();
+    let viewer_cookie = HeaderValue::from_str(&viewer_cookie).unwrap();
+    let load_url = format!("/api/load_animation/{}", animation_id);
+    let stats_url = format!("/api/animation/{}/stats", animation_id);
+
+    // The owner's own loads aren't counted
+    for cookie in [&viewer_cookie, &viewer_cookie, &owner_cookie] {
+        let response = server
+            .get(&load_url)
+            .add_header(COOKIE, cookie.clone())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+    }
+
+    let response = server
+        .get(&stats_url)
+        .add_query_param("days", 7)
+        .add_header(COOKIE, owner_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["total_views"], 2);
+    let daily = json["daily_views"].as_array().unwrap();
+    assert_eq!(daily.len(), 7);
+    assert_eq!(daily[6]["views"], 2);
+    assert_eq!(daily[6]["day"], chrono::Utc::now().date_naive().to_string());
+    assert_eq!(daily[0]["views"], 0);
+
+    let response = server
+        .get(&stats_url)
+        .add_header(COOKIE, viewer_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .get(&stats_url)
+        .add_query_param("days", 0)
+        .add_header(COOKIE, owner_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_reported_animations_can_be_hidden_by_admins() {
+    let test_db = TestDb::new();
+    let (owner_cookie, reporter_cookie, admin_cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let reporter = fixtures::insert_test_user(&mut conn, "reporter");
+        let admin = fixtures::insert_test_user(&mut conn, "admin");
+        {
+            use backend::schema::users;
+            use diesel::prelude::*;
+            diesel::update(users::table.find(admin))
+                .set(users::is_admin.eq(true))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Reported", owner);
+        {
+            use backend::schema::animation_collaborators;
+            use diesel::prelude::*;
+            diesel::insert_into(animation_collaborators::table)
+                .values((
+                    animation_collaborators::animation_id.eq(animation.id),
+                    animation_collaborators::user_id.eq(reporter),
+                    animation_collaborators::role.eq("viewer"),
+                ))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_session(&mut conn, reporter),
+            fixtures::insert_test_session(&mut conn, admin),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let owner_cookie = HeaderValue::from_str(&owner_cookie).unwrap();
+    let reporter_cookie = HeaderValue::from_str(&reporter_cookie).unwrap();
+    let admin_cookie = HeaderValue::from_str(&admin_cookie).unwrap();
+    let load_url = format!("/api/load_animation/{}", animation_id);
+
+    let response = server
+        .post(&format!("/api/animation/{}/report", animation_id))
+        .add_header(COOKIE, reporter_cookie.clone())
+        .json(&serde_json::json!({ "reason": "  " }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    let response = server
+        .post(&format!("/api/animation/{}/report", animation_id))
+        .add_header(COOKIE, reporter_cookie.clone())
+        .json(&serde_json::json!({ "reason": "Not mine to share" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    // Only admins see reports and hide animations
+    let response = server
+        .get("/api/admin/reports")
+        .add_header(COOKIE, reporter_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    let response = server
+        .post(&format!("/api/admin/animations/{}/hide", animation_id))
+        .add_header(COOKIE, owner_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .get("/api/admin/reports")
+        .add_header(COOKIE, admin_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert_eq!(json[0]["animation_id"], animation_id);
+    assert_eq!(json[0]["reason"], "Not mine to share");
+
+    let response = server
+        .post(&format!("/api/admin/animations/{}/hide", animation_id))
+        .add_header(COOKIE, admin_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    // Hidden from everyone but the owner, and the report is resolved
+    let response = server
+        .get(&load_url)
+        .add_header(COOKIE, reporter_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    let response = server
+        .get("/api/animations")
+        .add_header(COOKIE, reporter_cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    assert!(json.as_array().unwrap().is_empty());
+    let response = server
+        .get(&load_url)
+        .add_header(COOKIE, owner_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let response = server
+        .get("/api/admin/reports")
+        .add_header(COOKIE, admin_cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    assert!(json.as_array().unwrap().is_empty());
+
+    let response = server
+        .post(&format!("/api/admin/animations/{}/unhide", animation_id))
+        .add_header(COOKIE, admin_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    let response = server
+        .get(&load_url)
+        .add_header(COOKIE, reporter_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_folders_organize_animations() {
+    let test_db = TestDb::new();
+    let (cookie, other_cookie, animation_id, other_animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let other = fixtures::insert_test_user(&mut conn, "other");
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_session(&mut conn, other),
+            fixtures::insert_owned_test_animation(&mut conn, "Filed", owner).id,
+            fixtures::insert_owned_test_animation(&mut conn, "Loose", owner).id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let other_cookie = HeaderValue::from_str(&other_cookie).unwrap();
+
+    let response = server
+        .post("/api/folders")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "name": "Tectonics" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let project_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+    let response = server
+        .post("/api/folders")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "name": "Drafts", "parent_id": project_id }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let json: serde_json::Value = response.json();
+    let drafts_id = json["id"].as_i64().unwrap();
+    assert_eq!(json["parent_id"], project_id);
+
+    // A folder can't end up inside its own subfolder
+    let response = server
+        .put(&format!("/api/folders/{}", project_id))
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "name": "Tectonics", "parent_id": drafts_id }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    // Folders are private to their owner
+    let response = server
+        .get("/api/folders")
+        .add_header(COOKIE, other_cookie.clone())
+        .await;
+    assert!(response
+        .json::<serde_json::Value>()
+        .as_array()
+        .unwrap()
+        .is_empty());
+    let response = server
+        .post("/api/folders")
+        .add_header(COOKIE, other_cookie)
+        .json(&serde_json::json!({ "name": "Mine", "parent_id": project_id }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = server
+        .put(&format!("/api/animation/{}/folder", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "folder_id": drafts_id }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    let response = server
+        .get("/api/animations")
+        .add_query_param("folder_id", drafts_id)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert_eq!(json[0]["id"], animation_id);
+    assert_eq!(json[0]["folder_id"], drafts_id);
+
+    // Deleting a folder deletes its subfolders and keeps their animations
+    let response = server
+        .delete(&format!("/api/folders/{}", project_id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    let response = server
+        .get("/api/folders")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert!(response
+        .json::<serde_json::Value>()
+        .as_array()
+        .unwrap()
+        .is_empty());
+    let response = server
+        .get("/api/animations")
+        .add_header(COOKIE, cookie)
+        .await;
+    let json: serde_json::Value = response.json();
+    let listed: Vec<i64> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| {
+            assert!(item["folder_id"].is_null());
+            item["id"].as_i64().unwrap()
+        })
+        .collect();
+    assert!(listed.contains(&(animation_id as i64)));
+    assert!(listed.contains(&(other_animation_id as i64)));
+}
+
+#[tokio::test]
+async fn test_invitations_make_collaborators() {
+    let test_db = TestDb::new();
+    let (owner_cookie, ada_cookie, other_cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let ada = fixtures::insert_test_user(&mut conn, "ada");
+        let other = fixtures::insert_test_user(&mut conn, "other");
+        {
+            use backend::schema::users;
+            use diesel::prelude::*;
+            diesel::update(users::table.find(ada))
+                .set(users::email.eq("ada@example.com"))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_session(&mut conn, ada),
+            fixtures::insert_test_session(&mut conn, other),
+            fixtures::insert_owned_test_animation(&mut conn, "Shared", owner).id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let owner_cookie = HeaderValue::from_str(&owner_cookie).unwrap();
+    let ada_cookie = HeaderValue::from_str(&ada_cookie).unwrap();
+    let other_cookie = HeaderValue::from_str(&other_cookie).unwrap();
+    let invitations_url = format!("/api/animation/{}/invitations", animation_id);
+
+    // Only the owner invites
+    let response = server
+        .post(&invitations_url)
+        .add_header(COOKIE, other_cookie.clone())
+        .json(&serde_json::json!({ "invitee": "other", "role": "editor" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .post(&invitations_url)
+        .add_header(COOKIE, owner_cookie.clone())
+        .json(&serde_json::json!({ "invitee": " Ada@Example.com ", "role": "editor" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["invitee"], "ada@example.com");
+    let token = json["token"].as_str().unwrap().to_string();
+
+    let response = server
+        .post("/api/invitations/accept")
+        .add_header(COOKIE, other_cookie)
+        .json(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .post("/api/invitations/accept")
+        .add_header(COOKIE, ada_cookie.clone())
+        .json(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["animation_id"], animation_id);
+    assert_eq!(json["role"], "editor");
+
+    let response = server
+        .get(&format!("/api/animation/{}/collaborators", animation_id))
+        .add_header(COOKIE, owner_cookie)
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(json[0]["username"], "ada");
+    assert_eq!(json[0]["role"], "editor");
+
+    // Each invitation works once
+    let response = server
+        .post("/api/invitations/accept")
+        .add_header(COOKIE, ada_cookie)
+        .json(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_scheduled_publication_makes_animations_public() {
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_owned_test_animation(&mut conn, "Weekly", owner).id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let load_url = format!("/api/load_animation/{}", animation_id);
+    let publication_url = format!("/api/animation/{}/publication", animation_id);
+
+    let publish_at = chrono::Utc::now().naive_utc() + chrono::Duration::seconds(2);
+    let response = server
+        .put(&publication_url)
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "publish_at": publish_at }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    assert!(json["published_at"].is_null());
+    assert!(json["publish_at"].is_string());
+    assert_eq!(
+        server.get(&load_url).await.status_code(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // The queued job publishes it once the time comes
+    let mut published = false;
+    for _ in 0..100 {
+        if server.get(&load_url).await.status_code() == StatusCode::OK {
+            published = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(published, "animation was not published on schedule");
+    let json: serde_json::Value = server.get("/api/animations").await.json();
+    assert_eq!(json[0]["id"], animation_id);
+    assert!(json[0]["published_at"].is_string());
+
+    let response = server
+        .delete(&publication_url)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(
+        server.get(&load_url).await.status_code(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Without a time it is published straight away
+    let response = server
+        .put(&publication_url)
+        .add_header(COOKIE, cookie)
+        .json(&serde_json::json!({}))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(server.get(&load_url).await.status_code(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_embed_links_load_private_animations_read_only() {
+    use backend::admin::AdminService;
+
+    // Servers without a key refuse to make links at all
+    std::env::set_var("EMBED_SIGNING_KEY", "integration test embed key");
+    let test_db = TestDb::new();
+    let (owner, cookie, new_owner, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        (
+            owner,
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_user(&mut conn, "new owner"),
+            fixtures::insert_owned_test_animation(&mut conn, "Embedded", owner).id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let embed_url = format!("/api/animation/{}/embed", animation_id);
+
+    let response = server.post(&embed_url).json(&serde_json::json!({})).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+    let response = server
+        .post(&embed_url)
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "ttl_hours": 0 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let response = server
+        .post(&embed_url)
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "ttl_hours": 24 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let json: serde_json::Value = response.json();
+    let url = json["url"].as_str().unwrap().to_string();
+    assert!(json["expires_at"].is_string());
+
+    // Anyone with the link can load it, though the animation itself stays private
+    let response = server.get(&url).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(
+        response.header("cross-origin-resource-policy"),
+        "cross-origin"
+    );
+    assert_eq!(response.header("x-content-type-options"), "nosniff");
+    let loaded = MapAnimation::decode(response.into_bytes()).unwrap();
+    assert_eq!(loaded.name, "Embedded");
+    assert_eq!(
+        server
+            .get(&format!("/api/load_animation/{}", animation_id))
+            .await
+            .status_code(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // A link can't be pointed at another animation
+    let forged = url.replacen(
+        &format!("/embed/{}.", animation_id),
+        &format!("/embed/{}.", animation_id + 1),
+        1,
+    );
+    assert_eq!(
+        server.get(&forged).await.status_code(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        server.get("/embed/not-a-token").await.status_code(),
+        StatusCode::FORBIDDEN
+    );
+
+    // Revoking stops every link made so far, but not ones made after
+    let response = server
+        .delete(&embed_url)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    assert_eq!(server.get(&url).await.status_code(), StatusCode::FORBIDDEN);
+    let response = server
+        .post(&embed_url)
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({}))
+        .await;
+    let renewed = response.json::<serde_json::Value>()["url"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(server.get(&renewed).await.status_code(), StatusCode::OK);
+
+    // Links the old owner made stop working once the animation changes hands
+    AdminService::reassign_animations_logic(&test_db.pool, owner, new_owner, Some(animation_id))
+        .await
+        .unwrap();
+    assert_eq!(
+        server.get(&renewed).await.status_code(),
+        StatusCode::FORBIDDEN
+    );
+    let response = server.delete(&embed_url).add_header(COOKIE, cookie).await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_grpc_service_saves_loads_lists_and_deletes() {
+    use backend::grpc::proto::{
+        animations_server::Animations, DeleteAnimationRequest, ListAnimationsRequest,
+        LoadAnimationRequest, SaveAnimationRequest,
+    };
+    use tonic::{Code, Request};
+
+    fn with_token<T>(message: T, token: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert(
+            "authorization",
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        request
+    }
+
+    let test_db = TestDb::new();
+    let cookie = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "pipeline");
+        fixtures::insert_test_session(&mut conn, owner)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let token: serde_json::Value = server
+        .post("/api/tokens")
+        .add_header(COOKIE, HeaderValue::from_str(&cookie).unwrap())
+        .json(&serde_json::json!({ "name": "Importer", "scopes": ["read", "write"] }))
+        .await
+        .json();
+    let token = token["token"].as_str().unwrap();
+    let grpc = backend::grpc::AnimationsService::new(test_db.pool.clone());
+
+    let document =
+        MapAnimation::decode(&fixtures::create_test_animation_proto("Imported")[..]).unwrap();
+    let saved = grpc
+        .save_animation(with_token(
+            SaveAnimationRequest {
+                animation: Some(document.clone()),
+                id: None,
+                expected_version: None,
+            },
+            token,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let loaded = grpc
+        .load_animation(with_token(LoadAnimationRequest { id: saved.id }, token))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(loaded.animation.unwrap().name, "Imported");
+    let err = grpc
+        .load_animation(Request::new(LoadAnimationRequest { id: saved.id }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    let renamed = MapAnimation {
+        name: "Reimported".to_string(),
+        ..document
+    };
+    let replace = |expected_version| SaveAnimationRequest {
+        animation: Some(renamed.clone()),
+        id: Some(saved.id),
+        expected_version,
+    };
+    // Replacing blind could overwrite someone else's edit
+    let err = grpc
+        .save_animation(with_token(replace(None), token))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+    let updated = grpc
+        .save_animation(with_token(replace(Some(loaded.version)), token))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(updated.version, Some(loaded.version + 1));
+    let err = grpc
+        .save_animation(with_token(replace(Some(loaded.version)), token))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Aborted);
+
+    let listed = grpc
+        .list_animations(with_token(
+            ListAnimationsRequest {
+                query: "reimp".to_string(),
+                ..Default::default()
+            },
+            token,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(listed.animations.len(), 1);
+    assert_eq!(listed.animations[0].name, "Reimported");
+
+    grpc.delete_animation(with_token(DeleteAnimationRequest { id: saved.id }, token))
+        .await
+        .unwrap();
+    let err = grpc
+        .load_animation(with_token(LoadAnimationRequest { id: saved.id }, token))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_oversized_uploads_get_json_413() {
+    let test_db = TestDb::new();
+    let cookie = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "bigsaver");
+        fixtures::insert_test_session(&mut conn, owner)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let max_bytes = backend::services::max_upload_bytes();
+    // The test transport doesn't declare the length itself, as HTTP clients do
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, cookie)
+        .add_header(
+            axum::http::header::CONTENT_LENGTH,
+            HeaderValue::from(max_bytes + 1),
+        )
+        .bytes(Bytes::from(vec![0u8; max_bytes + 1]))
+        .await;
+
+    assert_eq!(response.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["max_bytes"], max_bytes);
+    assert_eq!(json["received_bytes"], max_bytes + 1);
+    assert!(json["error"].as_str().unwrap().contains("limit"));
+}
+
+#[tokio::test]
+async fn test_metadata_shows_who_last_edited() {
+    let test_db = TestDb::new();
+    let (owner, editor, editor_cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let editor = fixtures::insert_test_user(&mut conn, "editor");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Shared", owner);
+        {
+            use backend::schema::animation_collaborators;
+            use diesel::prelude::*;
+            diesel::insert_into(animation_collaborators::table)
+                .values((
+                    animation_collaborators::animation_id.eq(animation.id),
+                    animation_collaborators::user_id.eq(editor),
+                    animation_collaborators::role.eq("editor"),
+                ))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        (
+            owner,
+            editor,
+            fixtures::insert_test_session(&mut conn, editor),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let editor_cookie = HeaderValue::from_str(&editor_cookie).unwrap();
+    let meta_url = format!("/api/animation/{}/meta", animation_id);
+
+    let json: serde_json::Value = server
+        .get(&meta_url)
+        .add_header(COOKIE, editor_cookie.clone())
+        .await
+        .json();
+    assert_eq!(json["created_by"], owner);
+    assert_eq!(json["last_edited_by"], owner);
+
+    let response = server
+        .put(&format!("/api/animation/{}/details", animation_id))
+        .add_header(COOKIE, editor_cookie.clone())
+        .json(&serde_json::json!({ "description": "Edited by a collaborator" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    let json: serde_json::Value = server
+        .get(&meta_url)
+        .add_header(COOKIE, editor_cookie)
+        .await
+        .json();
+    assert_eq!(json["created_by"], owner);
+    assert_eq!(json["last_edited_by"], editor);
+}
+
+#[tokio::test]
+async fn test_animations_can_be_found_by_document_id() {
+    let test_db = TestDb::new();
+    let (owner_cookie, other_cookie) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let other = fixtures::insert_test_user(&mut conn, "other");
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_session(&mut conn, other),
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let owner_cookie = HeaderValue::from_str(&owner_cookie).unwrap();
+    let other_cookie = HeaderValue::from_str(&other_cookie).unwrap();
+
+    let document = fixtures::create_test_animation_proto("Referenced");
+    let uuid = MapAnimation::decode(document.as_slice())
+        .unwrap()
+        .animation_id;
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, owner_cookie.clone())
+        .bytes(Bytes::from(document.clone()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let animation_id = response.json::<serde_json::Value>()["id"].clone();
+
+    let by_uuid_url = format!("/api/animation/by-uuid/{}", uuid);
+    let response = server
+        .get(&by_uuid_url)
+        .add_header(COOKIE, owner_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["id"], animation_id);
+    assert_eq!(json["animation_uuid"], uuid);
+
+    // Someone else saving the same document gets an animation of their own, which
+    // doesn't take the document ID over
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, other_cookie.clone())
+        .bytes(Bytes::from(document))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    assert_ne!(response.json::<serde_json::Value>()["id"], animation_id);
+    let response = server
+        .get(&by_uuid_url)
+        .add_header(COOKIE, owner_cookie)
+        .await;
+    assert_eq!(response.json::<serde_json::Value>()["id"], animation_id);
+
+    // The owner's animation is private
+    let response = server
+        .get(&by_uuid_url)
+        .add_header(COOKIE, other_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = server.get("/api/animation/by-uuid/no-such-document").await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_other_servers_caches_skip_animations_changed_since() {
+    use backend::cache::{get_current, AnimationCache, MemoryCache, RowStamp};
+    use backend::moderation::ModerationService;
+    use backend::services::AnimationService;
+
+    let test_db = TestDb::new();
+    let (admin, owner, animation_id) = {
+        let mut conn = test_db.conn();
+        let admin = fixtures::insert_test_user(&mut conn, "admin");
+        {
+            use backend::schema::users;
+            use diesel::prelude::*;
+            diesel::update(users::table.find(admin))
+                .set(users::is_admin.eq(true))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Cached", owner);
+        (admin, owner, animation.id)
+    };
+    let pool = &test_db.pool;
+    let current = || {
+        use backend::schema::animations;
+        use diesel::prelude::*;
+
+        let (created_at, updated_at) = animations::table
+            .find(animation_id)
+            .select((animations::created_at, animations::updated_at))
+            .first(&mut test_db.conn())
+            .unwrap();
+        RowStamp {
+            created_at,
+            updated_at,
+        }
+    };
+
+    // Two servers, each with the animation in its own memory cache
+    let loaded = AnimationService::load_animation_logic(pool, animation_id, Some(owner))
+        .await
+        .unwrap();
+    let (server_a, server_b) = (MemoryCache::new(1 << 20), MemoryCache::new(1 << 20));
+    server_a.put(loaded.clone()).await;
+    server_b.put(loaded).await;
+    assert!(get_current(&server_b, animation_id, current())
+        .await
+        .is_some());
+
+    // Hidden through server A, which drops its own entry; B's entry no longer matches
+    ModerationService::set_hidden_logic(pool, admin, animation_id, true)
+        .await
+        .unwrap();
+    server_a.invalidate(animation_id).await;
+    assert!(server_b.get(animation_id).await.is_some());
+    assert!(get_current(&server_b, animation_id, current())
+        .await
+        .is_none());
+
+    let reloaded = AnimationService::load_animation_logic(pool, animation_id, Some(owner))
+        .await
+        .unwrap();
+    assert!(reloaded.hidden_at.is_some());
+    server_b.put(reloaded).await;
+    let cached = get_current(&server_b, animation_id, current())
+        .await
+        .unwrap();
+    assert!(cached.hidden_at.is_some());
+}
+
+#[tokio::test]
+async fn test_admin_reassigns_animations_and_deletes_users() {
+    use backend::admin::AdminService;
+
+    let test_db = TestDb::new();
+    let (leaver, heir, kept, given) = {
+        let mut conn = test_db.conn();
+        let leaver = fixtures::insert_test_user(&mut conn, "leaver");
+        let heir = fixtures::insert_test_user(&mut conn, "heir");
+        let kept = fixtures::insert_owned_test_animation(&mut conn, "Kept", leaver);
+        let given = fixtures::insert_owned_test_animation(&mut conn, "Given", leaver);
+        (leaver, heir, kept.id, given.id)
+    };
+    let pool = &test_db.pool;
+    let loaded = |animation_id: i32, caller: i32| {
+        backend::services::AnimationService::load_animation_logic(pool, animation_id, Some(caller))
+    };
+    let before = loaded(given, leaver).await.unwrap();
+
+    let moved = AdminService::reassign_animations_logic(pool, leaver, heir, Some(given))
+        .await
+        .unwrap();
+    assert_eq!(moved, 1);
+    // A server's cache still holding the animation doesn't keep serving the old owner
+    backend::cache::put(before.clone()).await;
+    let after = loaded(given, heir).await.unwrap();
+    assert_eq!(after.owner_id, Some(heir));
+    // The document didn't change, so neither do its version and history
+    assert_eq!(after.lock_version, before.lock_version);
+    let history = {
+        use backend::schema::animation_versions;
+        use diesel::prelude::*;
+
+        animation_versions::table
+            .filter(animation_versions::animation_id.eq(given))
+            .count()
+            .get_result::<i64>(&mut test_db.conn())
+            .unwrap()
+    };
+    assert_eq!(history, 1);
+    let users = AdminService::list_users_logic(pool).await.unwrap();
+    let count = |id: i32| {
+        users
+            .iter()
+            .find(|user| user.id == id)
+            .unwrap()
+            .animation_count
+    };
+    assert_eq!((count(leaver), count(heir)), (1, 1));
+
+    let deleted = AdminService::delete_user_logic(pool, leaver).await.unwrap();
+    assert_eq!(deleted, 1);
+    let users = AdminService::list_users_logic(pool).await.unwrap();
+    assert!(users.iter().all(|user| user.id != leaver));
+    assert!(
+        backend::services::AnimationService::load_animation_logic(pool, kept, Some(heir))
+            .await
+            .is_err()
+    );
+    backend::services::AnimationService::load_animation_logic(pool, given, Some(heir))
+        .await
+        .unwrap();
+
+    assert!(AdminService::delete_user_logic(pool, leaver).await.is_err());
+    assert!(
+        AdminService::reassign_animations_logic(pool, heir, leaver, None)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_admins_back_up_and_restore_every_animation() {
+    let backup_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("BACKUP_DIR", backup_dir.path());
+    let test_db = TestDb::new();
+    let (owner, owner_cookie, admin_cookie) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let admin = fixtures::insert_test_user(&mut conn, "admin");
+        {
+            use backend::schema::users;
+            use diesel::prelude::*;
+            diesel::update(users::table.find(admin))
+                .set(users::is_admin.eq(true))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        (
+            owner,
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_test_session(&mut conn, admin),
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let owner_cookie = HeaderValue::from_str(&owner_cookie).unwrap();
+    let admin_cookie = HeaderValue::from_str(&admin_cookie).unwrap();
+
+    let mut first_id = 0;
+    let mut second_document = Vec::new();
+    for name in ["First", "Second"] {
+        let document = fixtures::create_test_animation_proto(name);
+        let response = server
+            .post("/api/save_animation")
+            .add_header(COOKIE, owner_cookie.clone())
+            .bytes(Bytes::from(document.clone()))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::CREATED);
+        if name == "First" {
+            first_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap() as i32;
+        } else {
+            second_document = document;
+        }
+    }
+
+    let response = server
+        .post("/api/admin/backups")
+        .add_header(COOKIE, owner_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    // Waits for a backup or restore to finish, returning how it went
+    async fn finished(
+        server: &TestServer,
+        cookie: &HeaderValue,
+        backup: serde_json::Value,
+    ) -> serde_json::Value {
+        let status_url = format!("/api/admin/backups/{}", backup["id"]);
+        for _ in 0..100 {
+            let backup: serde_json::Value = server
+                .get(&status_url)
+                .add_header(COOKIE, cookie.clone())
+                .await
+                .json();
+            assert_ne!(backup["status"], "failed", "{}", backup["error"]);
+            if backup["status"] == "done" {
+                return backup;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("{} never finished", backup["kind"]);
+    }
+
+    let response = server
+        .post("/api/admin/backups")
+        .add_header(COOKIE, admin_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::ACCEPTED);
+    let backup = finished(&server, &admin_cookie, response.json()).await;
+    assert_eq!(backup["kind"], "backup");
+    assert_eq!(backup["animation_count"], 2);
+    let file_name = backup["file_name"].as_str().unwrap().to_string();
+    assert!(backup_dir.path().join(&file_name).exists());
+
+    let response = server
+        .get(&format!("/api/admin/backups/{}/download", backup["id"]))
+        .add_header(COOKIE, admin_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.header("content-type"), "application/zip");
+    assert!(response.into_bytes().starts_with(b"PK"));
+
+    // Restoring into the same deployment adds nothing twice
+    let response = server
+        .post("/api/admin/restores")
+        .add_header(COOKIE, admin_cookie.clone())
+        .json(&serde_json::json!({ "file_name": file_name }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::ACCEPTED);
+    let restore = finished(&server, &admin_cookie, response.json()).await;
+    assert_eq!(restore["animation_count"], 0);
+    assert_eq!(restore["skipped_count"], 2);
+
+    // A deleted animation comes back, still owned by its owner
+    {
+        use backend::schema::animations;
+        use diesel::prelude::*;
+        diesel::delete(animations::table.find(first_id))
+            .execute(&mut test_db.conn())
+            .unwrap();
+    }
+    let response = server
+        .post("/api/admin/restores")
+        .add_header(COOKIE, admin_cookie.clone())
+        .json(&serde_json::json!({ "file_name": file_name }))
+        .await;
+    let restore = finished(&server, &admin_cookie, response.json()).await;
+    assert_eq!(restore["animation_count"], 1);
+    assert_eq!(restore["skipped_count"], 1);
+    let restored = {
+        use backend::schema::animations;
+        use diesel::prelude::*;
+        animations::table
+            .filter(animations::name.eq("First"))
+            .select((animations::owner_id, animations::created_by))
+            .load::<(Option<i32>, Option<i32>)>(&mut test_db.conn())
+            .unwrap()
+    };
+    assert_eq!(restored, [(Some(owner), Some(owner))]);
+
+    // Another user's save of the same document is stored without its document ID, and
+    // restores into a fresh deployment alongside the owner's, once
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, admin_cookie.clone())
+        .bytes(Bytes::from(second_document))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let response = server
+        .post("/api/admin/backups")
+        .add_header(COOKIE, admin_cookie.clone())
+        .await;
+    let backup = finished(&server, &admin_cookie, response.json()).await;
+    assert_eq!(backup["animation_count"], 3);
+    {
+        use backend::schema::animations;
+        use diesel::prelude::*;
+        diesel::delete(animations::table)
+            .execute(&mut test_db.conn())
+            .unwrap();
+    }
+    for (animation_count, skipped_count) in [(3, 0), (0, 3)] {
+        let response = server
+            .post("/api/admin/restores")
+            .add_header(COOKIE, admin_cookie.clone())
+            .json(&serde_json::json!({ "file_name": backup["file_name"] }))
+            .await;
+        let restore = finished(&server, &admin_cookie, response.json()).await;
+        assert_eq!(restore["animation_count"], animation_count);
+        assert_eq!(restore["skipped_count"], skipped_count);
+    }
+    let second_copies = {
+        use backend::schema::animations;
+        use diesel::prelude::*;
+        animations::table
+            .filter(animations::name.eq("Second"))
+            .count()
+            .get_result::<i64>(&mut test_db.conn())
+            .unwrap()
+    };
+    assert_eq!(second_copies, 2);
+
+    for file_name in ["../secrets.zip", "missing.zip", "notes.txt"] {
+        let response = server
+            .post("/api/admin/restores")
+            .add_header(COOKIE, admin_cookie.clone())
+            .json(&serde_json::json!({ "file_name": file_name }))
+            .await;
+        assert!(
+            response.status_code() == StatusCode::BAD_REQUEST
+                || response.status_code() == StatusCode::NOT_FOUND,
+            "{} was accepted",
+            file_name
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_archive_import_reports_each_file() {
+    let test_db = TestDb::new();
+    let (cookie, other_cookie) = {
+        let mut conn = test_db.conn();
+        let user = fixtures::insert_test_user(&mut conn, "importer");
+        let other = fixtures::insert_test_user(&mut conn, "other");
+        (
+            fixtures::insert_test_session(&mut conn, user),
+            fixtures::insert_test_session(&mut conn, other),
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let other_cookie = HeaderValue::from_str(&other_cookie).unwrap();
+
+    let islands = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": [{
+            "type": "Feature",
+            "geometry": { "type": "Polygon", "coordinates": [[[0, 0], [10, 0], [10, 10], [0, 0]]] },
+            "properties": { "name": "Isle" }
+        }]
+    });
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (file_name, data) in [
+        (
+            "saved/first.pb",
+            fixtures::create_test_animation_proto("From protobuf"),
+        ),
+        ("maps/islands.geojson", islands.to_string().into_bytes()),
+        ("maps/broken.geojson", b"{ not json".to_vec()),
+        ("notes.txt", b"hello".to_vec()),
+        (
+            "voyage.gpx",
+            br#"<gpx version="1.1"><trk><name>Voyage</name><trkseg>
+                <trkpt lat="0" lon="0"><time>2024-01-01T00:00:00Z</time></trkpt>
+                <trkpt lat="0" lon="20"><time>2024-01-02T00:00:00Z</time></trkpt>
+            </trkseg></trk></gpx>"#
+                .to_vec(),
+        ),
+    ] {
+        use std::io::Write;
+        archive
+            .start_file(file_name, zip::write::FileOptions::default())
+            .unwrap();
+        archive.write_all(&data).unwrap();
+    }
+    let archive = archive.finish().unwrap().into_inner();
+
+    let response = server
+        .post("/api/import/archive")
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from(archive.clone()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::ACCEPTED);
+    let import: serde_json::Value = response.json();
+    assert_eq!(import["files"].as_array().unwrap().len(), 5);
+    assert_eq!(import["files"][3]["file_name"], "notes.txt");
+    assert_eq!(import["files"][3]["status"], "failed");
+    let status_url = format!("/api/imports/{}", import["id"]);
+
+    // Each file is imported by its own job
+    let mut import = import;
+    for _ in 0..100 {
+        if import["done"] == true {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        import = server
+            .get(&status_url)
+            .add_header(COOKIE, cookie.clone())
+            .await
+            .json();
+    }
+    assert_eq!(import["done"], true, "import never finished");
+    assert_eq!(import["imported"], 3);
+    assert_eq!(import["failed"], 2);
+    let files = import["files"].as_array().unwrap();
+    assert_eq!(files[0]["status"], "done");
+    assert_eq!(files[2]["status"], "failed");
+    assert!(files[2]["error"].as_str().unwrap().contains("Invalid JSON"));
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", files[1]["animation_id"]))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let animation = MapAnimation::decode(response.into_bytes()).unwrap();
+    assert_eq!(animation.name, "islands");
+    assert_eq!(animation.polygons.len(), 1);
+    assert_eq!(animation.polygons[0].points.len(), 3);
+    assert_eq!(animation.polygons[0].properties["name"], "Isle");
+
+    // GPX tracks become markers moving over the whole timeline
+    let response = server
+        .get(&format!("/api/load_animation/{}", files[4]["animation_id"]))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let animation = MapAnimation::decode(response.into_bytes()).unwrap();
+    assert_eq!(animation.name, "voyage");
+    assert_eq!(animation.total_frames, 240);
+    assert_eq!(animation.polygons[0].polygon_id, "voyage");
+    assert_eq!(animation.polygons[0].points[0].movements.len(), 239);
+
+    let response = server
+        .get(&status_url)
+        .add_header(COOKIE, other_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    let response = server
+        .post("/api/import/archive")
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from_static(b"not a zip"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    // Files within the upload limit that together uncompress past the archive limit
+    let mut bomb = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let zeros = vec![0u8; backend::services::max_upload_bytes()];
+    let file_count = backend::imports::max_archive_bytes() / zeros.len() + 1;
+    for n in 0..file_count {
+        use std::io::Write;
+        bomb.start_file(format!("{}.pb", n), zip::write::FileOptions::default())
+            .unwrap();
+        bomb.write_all(&zeros).unwrap();
+    }
+    let bomb = bomb.finish().unwrap().into_inner();
+    assert!(bomb.len() < backend::imports::max_archive_bytes());
+    let response = server
+        .post("/api/import/archive")
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from(bomb))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let response = server
+        .post("/api/import/archive")
+        .bytes(Bytes::from(archive))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_csv_import_makes_moving_markers() {
+    let test_db = TestDb::new();
+    let cookie = {
+        let mut conn = test_db.conn();
+        let user = fixtures::insert_test_user(&mut conn, "importer");
+        fixtures::insert_test_session(&mut conn, user)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let csv = "id,time,lat,lon\n\
+               ferry,2024-05-01T10:00:00Z,59.3,18.0\n\
+               bus,2024-05-01 10:30:00,59.4,18.1\n\
+               ferry,2024-05-01T11:00:00Z,60.1,19.9\n";
+    let response = server
+        .post("/api/import/csv")
+        .add_query_param("name", "Commute")
+        .add_query_param("total_frames", 61)
+        .add_header(COOKIE, cookie.clone())
+        .text(csv)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let animation = MapAnimation::decode(response.into_bytes()).unwrap();
+    assert_eq!(animation.name, "Commute");
+    assert_eq!(animation.total_frames, 61);
+    let ids: Vec<_> = animation
+        .polygons
+        .iter()
+        .map(|p| p.polygon_id.as_str())
+        .collect();
+    assert_eq!(ids, ["ferry", "bus"]);
+    assert_eq!(animation.polygons[0].points[0].movements.len(), 60);
+    // The bus is first seen halfway through the hour
+    assert_eq!(animation.polygons[1].points[0].start_frame, Some(30));
+
+    // Frame numbers are kept as given
+    let response = server
+        .post("/api/import/csv")
+        .add_header(COOKIE, cookie.clone())
+        .text("Frame,Name,Latitude,Longitude\n0,gull,10,20\n12,gull,11,21\n")
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+    let response = server
+        .get(&format!("/api/load_animation/{}", id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let animation = MapAnimation::decode(response.into_bytes()).unwrap();
+    assert_eq!(animation.name, "points");
+    assert_eq!(animation.total_frames, 13);
+
+    let response = server
+        .post("/api/import/csv")
+        .add_header(COOKIE, cookie.clone())
+        .text("id,lat,lon\nferry,59.3,18.0\n")
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    let response = server
+        .post("/api/import/csv")
+        .add_header(COOKIE, cookie.clone())
+        .text("id,frame,lat,lon\nferry,3,95,18.0\n")
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    let response = server.post("/api/import/csv").text(csv).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_gpx_import_makes_moving_markers() {
+    let test_db = TestDb::new();
+    let cookie = {
+        let mut conn = test_db.conn();
+        let user = fixtures::insert_test_user(&mut conn, "hiker");
+        fixtures::insert_test_session(&mut conn, user)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let gpx = r#"<gpx version="1.1"><trk><name>Voyage</name><trkseg>
+        <trkpt lat="0" lon="0"><time>2024-01-01T00:00:00Z</time></trkpt>
+        <trkpt lat="0" lon="20"><time>2024-01-02T00:00:00Z</time></trkpt>
+    </trkseg></trk></gpx>"#;
+    let response = server
+        .post("/api/import/gpx")
+        .add_query_param("name", "Crossing")
+        .add_query_param("total_frames", 25)
+        .add_header(COOKIE, cookie.clone())
+        .text(gpx)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let animation = MapAnimation::decode(response.into_bytes()).unwrap();
+    assert_eq!(animation.name, "Crossing");
+    assert_eq!(animation.total_frames, 25);
+    assert_eq!(animation.polygons.len(), 1);
+    assert!(!animation.polygons[0].points[0].movements.is_empty());
+
+    let response = server
+        .post("/api/import/gpx")
+        .add_header(COOKIE, cookie.clone())
+        .text("<gpx><trk><trkseg><trkpt lat=\"0\" lon=\"0\"/></trkseg></trk></gpx>")
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    // A small file can't ask for a marker per track moving over the longest timeline
+    let tracks = gpx
+        .replace("<gpx version=\"1.1\">", "")
+        .replace("</gpx>", "");
+    let many_tracks = format!("<gpx>{}</gpx>", tracks.repeat(20));
+    let response = server
+        .post("/api/import/gpx")
+        .add_query_param("total_frames", geco_core::point_series::MAX_FRAME + 1)
+        .add_header(COOKIE, cookie.clone())
+        .text(many_tracks)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    assert!(response.json::<serde_json::Value>()["error"]
+        .as_str()
+        .unwrap()
+        .contains("movements"));
+    let response = server.post("/api/import/gpx").text(gpx).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_sessions_record_their_device_and_can_be_ended() {
+    let test_db = TestDb::new();
+    let server = create_test_app(test_db.pool.clone()).await;
+    let session_of = |response: &axum_test::TestResponse| {
+        let set_cookie = response.header("set-cookie");
+        let cookie = set_cookie.to_str().unwrap().split(';').next().unwrap();
+        HeaderValue::from_str(cookie).unwrap()
+    };
+
+    let response = server
+        .post("/api/auth/register")
+        .add_header(USER_AGENT, HeaderValue::from_static("Laptop browser"))
+        .json(&serde_json::json!({
+            "username": "grace",
+            "email": "grace@example.com",
+            "password": "compiler pioneer"
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let laptop = session_of(&response);
+    let response = server
+        .post("/api/auth/login")
+        .add_header(USER_AGENT, HeaderValue::from_static("Phone browser"))
+        .json(&serde_json::json!({
+            "email": "grace@example.com",
+            "password": "compiler pioneer"
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let phone = session_of(&response);
+
+    let sessions: serde_json::Value = server
+        .get("/api/auth/sessions")
+        .add_header(COOKIE, laptop.clone())
+        .await
+        .json();
+    let sessions = sessions.as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    let by_agent = |agent: &str| {
+        sessions
+            .iter()
+            .find(|session| session["user_agent"] == agent)
+            .unwrap()
+            .clone()
+    };
+    let (laptop_session, phone_session) = (by_agent("Laptop browser"), by_agent("Phone browser"));
+    assert_eq!(laptop_session["current"], true);
+    assert_eq!(laptop_session["new_device"], false);
+    // Signed in from a browser none of the user's sessions had
+    assert_eq!(phone_session["current"], false);
+    assert_eq!(phone_session["new_device"], true);
+    assert!(phone_session["last_seen_at"].is_string());
+    assert!(sessions
+        .iter()
+        .all(|session| session.get("token").is_none()));
+
+    let response = server
+        .delete(&format!("/api/auth/sessions/{}", phone_session["id"]))
+        .add_header(COOKIE, laptop.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    let response = server
+        .get("/api/auth/sessions")
+        .add_header(COOKIE, phone)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+    let response = server
+        .delete(&format!("/api/auth/sessions/{}", phone_session["id"]))
+        .add_header(COOKIE, laptop)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_repeated_failed_logins_lock_out_the_client_ip() {
+    // Only addresses from X-Forwarded-For are known in tests; other tests send none
+    std::env::set_var("TRUST_PROXY_HEADERS", "true");
+    let test_db = TestDb::new();
+    let admin_cookie = {
+        let mut conn = test_db.conn();
+        let admin = fixtures::insert_test_user(&mut conn, "admin");
+        {
+            use backend::schema::users;
+            use diesel::prelude::*;
+            diesel::update(users::table.find(admin))
+                .set(users::is_admin.eq(true))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        fixtures::insert_test_session(&mut conn, admin)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let response = server
+        .post("/api/auth/register")
+        .json(&serde_json::json!({
+            "username": "ada",
+            "email": "ada@example.com",
+            "password": "analytical engine"
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+
+    let login = |forwarded_for: &str, password: &str| {
+        server
+            .post("/api/auth/login")
+            .add_header(
+                axum::http::HeaderName::from_static("x-forwarded-for"),
+                HeaderValue::from_str(forwarded_for).unwrap(),
+            )
+            .json(&serde_json::json!({
+                "email": "ada@example.com",
+                "password": password
+            }))
+    };
+    // The tenth failure in a row locks the address out, whatever the client puts
+    // in front of the address the proxy appended
+    for attempt in 0..10 {
+        let spoofed = format!("192.0.2.{}, 203.0.113.77", attempt);
+        let response = login(&spoofed, "guess").await;
+        assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+    }
+    let response = login("192.0.2.200, 203.0.113.77", "analytical engine").await;
+    assert_eq!(response.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response
+        .header("retry-after")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 15 * 60);
+    let body: serde_json::Value = response.json();
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("Too many sign-in attempts"));
+
+    // Other addresses can still sign in
+    let response = login("198.51.100.8", "analytical engine").await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let response = server
+        .get("/api/admin/auth-throttling")
+        .add_header(COOKIE, HeaderValue::from_str(&admin_cookie).unwrap())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let stats: serde_json::Value = response.json();
+    assert!(stats["failures"].as_u64().unwrap() >= 10);
+    assert!(stats["refused"].as_u64().unwrap() >= 1);
+    assert!(stats["lockouts"].as_u64().unwrap() >= 1);
+    assert!(stats["locked_out_clients"].as_u64().unwrap() >= 1);
+}
+
+#[tokio::test]
+async fn test_templates_are_copied_into_the_callers_account() {
+    use backend::admin::AdminService;
+
+    let test_db = TestDb::new();
+    let (curator, newcomer, source, newcomer_cookie) = {
+        let mut conn = test_db.conn();
+        let curator = fixtures::insert_test_user(&mut conn, "curator");
+        let newcomer = fixtures::insert_test_user(&mut conn, "newcomer");
+        let source = fixtures::insert_owned_test_animation(&mut conn, "Plates draft", curator);
+        let cookie = fixtures::insert_test_session(&mut conn, newcomer);
+        (curator, newcomer, source.id, cookie)
+    };
+    let pool = &test_db.pool;
+    let blank = fixtures::insert_owned_test_animation(&mut test_db.conn(), "Blank", curator);
+    let tectonic =
+        AdminService::add_template_logic(pool, source, "Tectonic starter", Some("Plates"), 1)
+            .await
+            .unwrap();
+    AdminService::add_template_logic(pool, blank.id, "Blank timeline", None, 0)
+        .await
+        .unwrap();
+    // One template per animation
+    assert!(
+        AdminService::add_template_logic(pool, source, "Again", None, 2)
+            .await
+            .is_err()
+    );
+    let server = create_test_app(test_db.pool.clone()).await;
+    let newcomer_cookie = HeaderValue::from_str(&newcomer_cookie).unwrap();
+
+    let templates: serde_json::Value = server.get("/api/templates").await.json();
+    let names: Vec<&str> = templates
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|template| template["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["Blank timeline", "Tectonic starter"]);
+
+    let path = format!("/api/templates/{}/instantiate", tectonic);
+    let response = server.post(&path).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+    let response = server
+        .post(&path)
+        .add_header(COOKIE, newcomer_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let copy_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap() as i32;
+    assert_ne!(copy_id, source);
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", copy_id))
+        .add_header(COOKIE, newcomer_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let document = MapAnimation::decode(response.into_bytes()).unwrap();
+    assert_eq!(document.name, "Tectonic starter");
+    {
+        use backend::schema::animations;
+        use diesel::prelude::*;
+        let owner = animations::table
+            .find(copy_id)
+            .select(animations::owner_id)
+            .first::<Option<i32>>(&mut test_db.conn())
+            .unwrap();
+        assert_eq!(owner, Some(newcomer));
+    }
+
+    // Withdrawn templates can't be used, but copies made from them stay
+    AdminService::remove_template_logic(pool, tectonic)
+        .await
+        .unwrap();
+    let response = server
+        .post(&path)
+        .add_header(COOKIE, newcomer_cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    let response = server
+        .get(&format!("/api/load_animation/{}", copy_id))
+        .add_header(COOKIE, newcomer_cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_error_messages_are_in_the_callers_language() {
+    let test_db = TestDb::new();
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    let response = server
+        .get("/api/load_animation/99999")
+        .add_header(
+            ACCEPT_LANGUAGE,
+            HeaderValue::from_static("es-ES,es;q=0.9,en;q=0.5"),
+        )
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("content-language"), "es");
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["code"], "not_found");
+    assert_eq!(json["error"], "No se encontró lo que buscabas.");
+    // The specific English message comes along, for bug reports
+    assert!(json["detail"].as_str().unwrap().contains("not found"));
+
+    // Languages without translations get English, without a separate detail
+    let response = server
+        .get("/api/load_animation/99999")
+        .add_header(ACCEPT_LANGUAGE, HeaderValue::from_static("ja"))
+        .await;
+    assert_eq!(response.header("content-language"), "en");
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["code"], "not_found");
+    assert!(json["error"].as_str().unwrap().contains("not found"));
+    assert!(json.get("detail").is_none());
+}
+
+#[tokio::test]
+async fn test_heavy_reads_go_to_the_read_pool() {
+    // A second database stands in for the replica, so each side's rows tell the
+    // pools apart
+    let primary = TestDb::new();
+    let replica = TestDb::new();
+    let (cookie, own_id) = {
+        let mut conn = primary.conn();
+        let caller = fixtures::insert_test_user(&mut conn, "caller");
+        (
+            fixtures::insert_test_session(&mut conn, caller),
+            fixtures::insert_owned_test_animation(&mut conn, "Just saved", caller).id,
+        )
+    };
+    let replica_id = fixtures::insert_test_animation(&mut replica.conn(), "Replicated").id;
+    let app = backend::app::build_app_with_read_pool(
+        &backend::app::AppConfig::from_env(),
+        primary.pool.clone(),
+        backend::db::ReadPool(replica.pool.clone()),
+    );
+    let server = TestServer::new(app).unwrap();
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    // The gallery is read from the replica, even for signed-in callers
+    let response = server
+        .get("/api/animations")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    let names: Vec<&str> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["Replicated"]);
+
+    // Visitors load from the replica too
+    let response = server
+        .get(&format!("/api/animation/{}/meta", replica_id))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    // Signed-in callers load from the primary, so they see what they just saved
+    let response = server
+        .get(&format!("/api/load_animation/{}", own_id))
+        .add_header(COOKIE, cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let document = MapAnimation::decode(response.into_bytes()).unwrap();
+    assert_eq!(document.name, "Just saved");
+}
+
+#[tokio::test]
+async fn test_only_admins_switch_maintenance_mode() {
+    // Switching it on here would refuse other tests' writes, as the switch is shared
+    // by the whole process; the unit tests cover what it refuses
+    let test_db = TestDb::new();
+    let (member_cookie, admin_cookie) = {
+        let mut conn = test_db.conn();
+        let member = fixtures::insert_test_user(&mut conn, "member");
+        let admin = fixtures::insert_test_user(&mut conn, "admin");
+        {
+            use backend::schema::users;
+            use diesel::prelude::*;
+            diesel::update(users::table.find(admin))
+                .set(users::is_admin.eq(true))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        (
+            fixtures::insert_test_session(&mut conn, member),
+            fixtures::insert_test_session(&mut conn, admin),
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    let response = server
+        .put("/api/admin/maintenance")
+        .add_header(COOKIE, HeaderValue::from_str(&member_cookie).unwrap())
+        .json(&serde_json::json!({ "enabled": true }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .get("/api/admin/maintenance")
+        .add_header(COOKIE, HeaderValue::from_str(&admin_cookie).unwrap())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["enabled"], false);
+    assert!(json["since"].is_null());
+}
+
+#[tokio::test]
+async fn test_saves_record_document_sizes() {
+    use backend::admin::AdminService;
+    use backend::schema::animations;
+    use diesel::prelude::*;
+
+    let test_db = TestDb::new();
+    let (cookie, owner) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "measured");
+        (fixtures::insert_test_session(&mut conn, owner), owner)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let mut animation =
+        MapAnimation::decode(fixtures::create_test_animation_proto("Measured").as_slice()).unwrap();
+    let polygon = &mut animation.polygons[0];
+    polygon.points.push(backend::protobuf_gen::AnimatedPoint {
+        point_id: "second-point".to_string(),
+        ..polygon.points[0].clone()
+    });
+    polygon.points[0].scalar_keyframes = vec![
+        backend::protobuf_gen::ScalarKeyframe {
+            frame: 0,
+            value: 1.0,
+        },
+        backend::protobuf_gen::ScalarKeyframe {
+            frame: 10,
+            value: 2.0,
+        },
+    ];
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from(animation.encode_to_vec()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let animation_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap() as i32;
+
+    let response = server
+        .get("/api/animations")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let listed: serde_json::Value = response.json();
+    assert_eq!(listed[0]["feature_count"], 1);
+    assert_eq!(listed[0]["point_count"], 2);
+    // Each point's starting position plus the two values
+    assert_eq!(listed[0]["keyframe_count"], 4);
+    let response = server
+        .get(&format!("/api/animation/{}/meta", animation_id))
+        .add_header(COOKIE, cookie)
+        .await;
+    let metadata: serde_json::Value = response.json();
+    assert_eq!(metadata["keyframe_count"], 4);
+
+    // The stored size is what counts against quotas: the document once compressed
+    let stored_bytes = animations::table
+        .find(animation_id)
+        .select(animations::protobuf_data)
+        .first::<Vec<u8>>(&mut test_db.conn())
+        .unwrap()
+        .len();
+    assert_eq!(listed[0]["encoded_bytes"], stored_bytes);
+    let users = AdminService::list_users_logic(&test_db.pool).await.unwrap();
+    let user = users.iter().find(|user| user.id == owner).unwrap();
+    assert_eq!(user.stored_bytes, stored_bytes as i64);
+
+    // Saves from before the counts were kept are measured on demand
+    diesel::update(animations::table.find(animation_id))
+        .set(animations::feature_count.eq(None::<i32>))
+        .execute(&mut test_db.conn())
+        .unwrap();
+    let measured = AdminService::measure_animations_logic(&test_db.pool)
+        .await
+        .unwrap();
+    assert_eq!(measured, 1);
+    let counts = animations::table
+        .find(animation_id)
+        .select((animations::feature_count, animations::keyframe_count))
+        .first::<(Option<i32>, Option<i32>)>(&mut test_db.conn())
+        .unwrap();
+    assert_eq!(counts, (Some(1), Some(4)));
+}
+
+#[tokio::test]
+async fn test_keyframe_counts_include_moving_points() {
+    use backend::protobuf_gen::Vector;
+
+    let test_db = TestDb::new();
+    let cookie = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "mover");
+        fixtures::insert_test_session(&mut conn, owner)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    // The point moves on frames 1 and 3 and holds still on frame 2
+    let mut animation =
+        MapAnimation::decode(fixtures::create_test_animation_proto("Moving").as_slice()).unwrap();
+    let step = |dx: f32| Vector {
+        dx,
+        dy: 0.0,
+        dz: None,
+    };
+    animation.polygons[0].points[0].movements = vec![step(1.0), step(0.0), step(1.0)];
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from(animation.encode_to_vec()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let animation_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+
+    let response = server
+        .get("/api/animations")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    let listed: serde_json::Value = response.json();
+    assert_eq!(listed[0]["keyframe_count"], 3);
+    let response = server
+        .get(&format!("/api/animation/{}/meta", animation_id))
+        .add_header(COOKIE, cookie)
+        .await;
+    let metadata: serde_json::Value = response.json();
+    assert_eq!(metadata["keyframe_count"], 3);
+}
+
+#[tokio::test]
+async fn test_loading_earlier_revisions() {
+    use backend::schema::animation_versions;
+    use diesel::prelude::*;
+
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "First draft", owner);
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let load_url = format!("/api/load_animation/{}", animation_id);
+
+    let response = server
+        .put(&format!("/api/animation/{}", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("\"1\""))
+        .bytes(Bytes::from(fixtures::create_test_animation_proto(
+            "Second draft",
+        )))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    // Date the first version back, so as_of can tell the two apart
+    let first_saved = chrono::NaiveDate::from_ymd_opt(2024, 5, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    {
+        let mut conn = test_db.conn();
+        diesel::update(animation_versions::table.find((animation_id, 1)))
+            .set(animation_versions::created_at.eq(first_saved))
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    let response = server
+        .get(&load_url)
+        .add_query_param("version", 1)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.headers()[ETAG], "\"1\"");
+    let document = MapAnimation::decode(response.as_bytes().as_ref()).unwrap();
+    assert_eq!(document.name, "First draft");
+
+    let response = server
+        .get(&load_url)
+        .add_query_param("as_of", "2024-05-02T00:00:00")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.headers()[ETAG], "\"1\"");
+
+    let response = server
+        .get(&load_url)
+        .add_query_param("as_of", "2999-01-01T00:00:00")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.headers()[ETAG], "\"2\"");
+    let document = MapAnimation::decode(response.as_bytes().as_ref()).unwrap();
+    assert_eq!(document.name, "Second draft");
+
+    // Nothing was saved yet, or no such version
+    let response = server
+        .get(&load_url)
+        .add_query_param("as_of", "2024-04-30T00:00:00")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    let response = server
+        .get(&load_url)
+        .add_query_param("version", 7)
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    let response = server
+        .get(&load_url)
+        .add_query_param("version", 1)
+        .add_query_param("as_of", "2024-05-02T00:00:00")
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    // The history is as private as the animation
+    let response = server.get(&load_url).add_query_param("version", 1).await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_frames_export_zips_geojson_per_frame() {
+    use std::io::Read;
+
+    let test_db = TestDb::new();
+    let animation = {
+        let mut conn = test_db.conn();
+        fixtures::insert_test_animation(&mut conn, "Frames")
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let url = format!("/api/animation/{}/export/frames", animation.id);
+
+    let response = server
+        .post(&url)
+        .json(&serde_json::json!({ "step": 0 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    // Frame exports aren't videos
+    let response = server
+        .post(&format!("/api/animation/{}/export/video", animation.id))
+        .json(&serde_json::json!({ "format": "geojson" }))
+        .await;
+    assert!(response.status_code().is_client_error());
+
+    let response = server
+        .post(&url)
+        .json(&serde_json::json!({ "step": 10 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::ACCEPTED);
+    let job: serde_json::Value = response.json();
+    assert_eq!(job["format"], "geojson");
+    let status_url = format!("/api/exports/{}", job["id"]);
+
+    let mut download_url = None;
+    for _ in 0..100 {
+        let job: serde_json::Value = server.get(&status_url).await.json();
+        assert_ne!(job["status"], "failed", "{}", job["error"]);
+        if let Some(url) = job["download_url"].as_str() {
+            download_url = Some(url.to_string());
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let download_url = download_url.expect("Export never finished");
+    let queue_job: serde_json::Value = server
+        .get(&format!("/api/jobs/{}", job["job_id"]))
+        .await
+        .json();
+    assert_eq!(queue_job["kind"], "export_frames");
+
+    let response = server.get(&download_url).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.header("content-type"), "application/zip");
+    assert!(response
+        .header("content-disposition")
+        .to_str()
+        .unwrap()
+        .ends_with(".zip\""));
+
+    // The fixture runs for 30 frames, so every 10th is 0, 10 and 20
+    let mut archive =
+        zip::ZipArchive::new(std::io::Cursor::new(response.as_bytes().to_vec())).unwrap();
+    let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "frame-00000.geojson",
+            "frame-00010.geojson",
+            "frame-00020.geojson"
+        ]
+    );
+    let mut json = String::new();
+    archive
+        .by_name("frame-00010.geojson")
+        .unwrap()
+        .read_to_string(&mut json)
+        .unwrap();
+    let collection: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(collection["type"], "FeatureCollection");
+}
+
+#[tokio::test]
+async fn test_batch_metadata() {
+    let test_db = TestDb::new();
+    let (cookie, public_id, own_id, private_id) = {
+        let mut conn = test_db.conn();
+        let caller = fixtures::insert_test_user(&mut conn, "caller");
+        let stranger = fixtures::insert_test_user(&mut conn, "stranger");
+        (
+            fixtures::insert_test_session(&mut conn, caller),
+            fixtures::insert_test_animation(&mut conn, "Public").id,
+            fixtures::insert_owned_test_animation(&mut conn, "Own", caller).id,
+            fixtures::insert_owned_test_animation(&mut conn, "Private", stranger).id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let response = server
+        .post("/api/animations/batch_meta")
+        .add_header(COOKIE, cookie)
+        .json(&serde_json::json!({ "ids": [own_id, 999999, private_id, public_id, own_id] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let json: serde_json::Value = response.json();
+    let described: Vec<(i64, &str)> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|meta| (meta["id"].as_i64().unwrap(), meta["name"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        described,
+        vec![(own_id as i64, "Own"), (public_id as i64, "Public")]
+    );
+    assert_eq!(json[0]["feature_count"], 1);
+
+    // Without a session only unowned animations are described
+    let response = server
+        .post("/api/animations/batch_meta")
+        .json(&serde_json::json!({ "ids": [own_id, public_id] }))
+        .await;
+    let json: serde_json::Value = response.json();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert_eq!(json[0]["id"], public_id);
+
+    let too_many: Vec<i32> = (1..=backend::services::MAX_BATCH_METADATA_IDS as i32 + 1).collect();
+    let response = server
+        .post("/api/animations/batch_meta")
+        .json(&serde_json::json!({ "ids": too_many }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_load_detects_corrupted_documents() {
+    let test_db = TestDb::new();
+    let animation = {
+        let mut conn = test_db.conn();
+        fixtures::insert_test_animation(&mut conn, "Damaged")
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let url = format!("/api/load_animation/{}", animation.id);
+    assert_eq!(server.get(&url).await.status_code(), StatusCode::OK);
+
+    // Flip a bit of the stored document behind the service's back, moving the
+    // version on as a save would so the cached copy isn't used
+    {
+        use backend::schema::animations;
+        use diesel::prelude::*;
+        let mut damaged = animation.protobuf_data.clone();
+        *damaged.last_mut().unwrap() ^= 1;
+        diesel::update(animations::table.find(animation.id))
+            .set((
+                animations::protobuf_data.eq(damaged),
+                animations::lock_version.eq(animations::lock_version + 1),
+            ))
+            .execute(&mut test_db.conn())
+            .unwrap();
+    }
+
+    let response = server.get(&url).await;
+    assert_eq!(response.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    let json: serde_json::Value = response.json();
+    assert!(json["error"].as_str().unwrap().contains("damaged"));
+}
+
+#[tokio::test]
+async fn test_storage_quota_limits_saves() {
+    let test_db = TestDb::new();
+    let document = fixtures::create_test_animation_proto("Quota");
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Quota", owner);
+        // Room for the existing animation and not much more
+        let quota = (animation.protobuf_data.len() + document.len() / 2) as i64;
+        {
+            use backend::schema::users;
+            use diesel::prelude::*;
+            diesel::update(users::table.find(owner))
+                .set(users::storage_quota_bytes.eq(quota))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from(document.clone()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    let json: serde_json::Value = response.json();
+    assert!(json["error"].as_str().unwrap().contains("storage quota"));
+
+    let response = server
+        .post(&format!("/api/animation/{}/duplicate", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // The replaced document is kept as an earlier version, so it still counts
+    let response = server
+        .put(&format!("/api/animation/{}", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("*"))
+        .bytes(Bytes::from(document.clone()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Keeping a single version, the replaced document goes and frees its bytes
+    {
+        use backend::schema::users;
+        use diesel::prelude::*;
+        diesel::update(users::table)
+            .set(users::max_versions_per_animation.eq(1))
+            .execute(&mut test_db.conn())
+            .unwrap();
+    }
+    let response = server
+        .put(&format!("/api/animation/{}", animation_id))
+        .add_header(COOKIE, cookie)
+        .add_header(IF_MATCH, HeaderValue::from_static("*"))
+        .bytes(Bytes::from(document))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+
+    // Anonymous saves have no owner to charge
+    let response = server
+        .post("/api/save_animation")
+        .bytes(Bytes::from(fixtures::create_test_animation_proto("Quota")))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn test_count_limits_stop_new_animations_and_prune_versions() {
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "owner");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Limited", owner);
+        {
+            use backend::schema::users;
+            use diesel::prelude::*;
+            diesel::update(users::table.find(owner))
+                .set((
+                    users::max_animations.eq(1),
+                    users::max_versions_per_animation.eq(2),
+                ))
+                .execute(&mut conn)
+                .unwrap();
+        }
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            animation.id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from(fixtures::create_test_animation_proto(
+            "Another",
+        )))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["limit"], "animations");
+    assert_eq!(json["limit_max"], 1);
+
+    let response = server
+        .post(&format!("/api/animation/{}/duplicate", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    // Saves past the version limit go through, dropping the oldest versions
+    for name in ["Second", "Third"] {
+        let response = server
+            .put(&format!("/api/animation/{}", animation_id))
+            .add_header(COOKIE, cookie.clone())
+            .add_header(IF_MATCH, HeaderValue::from_static("*"))
+            .bytes(Bytes::from(fixtures::create_test_animation_proto(name)))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    }
+    let versions = {
+        use backend::schema::animation_versions;
+        use diesel::prelude::*;
+        animation_versions::table
+            .filter(animation_versions::animation_id.eq(animation_id))
+            .order(animation_versions::version.asc())
+            .select(animation_versions::version)
+            .load::<i32>(&mut test_db.conn())
+            .unwrap()
+    };
+    assert_eq!(versions, [2, 3]);
+    let response = server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .add_query_param("version", 1)
+        .add_header(COOKIE, cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    // Anonymous saves have no owner to count against
+    let response = server
+        .post("/api/save_animation")
+        .bytes(Bytes::from(fixtures::create_test_animation_proto(
+            "Another",
+        )))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn test_failed_transactions_leave_nothing_behind() {
+    use backend::{errors::AppError, schema::users, services::run_in_transaction};
+    use diesel::{ExpressionMethods, QueryDsl};
+    use diesel_async::scoped_futures::ScopedFutureExt;
+
+    let test_db = TestDb::new();
+
+    let result = run_in_transaction(&test_db.pool, |conn| {
+        async move {
+            use diesel_async::RunQueryDsl;
+            diesel::insert_into(users::table)
+                .values(users::username.eq("half-saved"))
+                .execute(conn)
+                .await?;
+            Err::<(), _>(AppError::Internal("second step failed".to_string()))
+        }
+        .scope_boxed()
+    })
+    .await;
+    assert!(matches!(result, Err(AppError::Internal(_))));
+
+    let remaining = {
+        use diesel::RunQueryDsl;
+        users::table
+            .filter(users::username.eq("half-saved"))
+            .count()
+            .get_result::<i64>(&mut test_db.conn())
+    };
+    assert_eq!(remaining, Ok(0));
+}
+
+#[tokio::test]
+async fn test_animations_load_and_save_as_json() {
+    let test_db = TestDb::new();
+    let (cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "scripter");
+        (
+            fixtures::insert_test_session(&mut conn, owner),
+            fixtures::insert_owned_test_animation(&mut conn, "Scripted", owner).id,
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let url = format!("/api/load_animation/{}", animation_id);
+
+    let response = server
+        .get(&url)
+        .add_header(COOKIE, cookie.clone())
+        .add_header(ACCEPT, HeaderValue::from_static("application/json"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.header(CONTENT_TYPE), "application/json");
+    let mut document: serde_json::Value = response.json();
+    assert_eq!(document["name"], "Scripted");
+
+    // Edited and sent back as JSON, it is stored as protobuf
+    document["name"] = serde_json::json!("Scripted again");
+    let response = server
+        .put(&format!("/api/animation/{}", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("*"))
+        .json(&document)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    let response = server.get(&url).add_header(COOKIE, cookie).await;
+    assert_eq!(response.header(CONTENT_TYPE), "application/octet-stream");
+    let loaded = MapAnimation::decode(response.into_bytes()).unwrap();
+    assert_eq!(loaded.name, "Scripted again");
+
+    let response = server.post("/api/save_animation").json(&document).await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let response = server
+        .post("/api/save_animation")
+        .json(&serde_json::json!({ "name": 5 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_load_animation_is_compressed_when_accepted() {
+    let test_db = TestDb::new();
+    let animation = {
+        let mut conn = test_db.conn();
+        fixtures::insert_test_animation(&mut conn, "Compressible")
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let url = format!("/api/load_animation/{}", animation.id);
+
+    let response = server
+        .get(&url)
+        .add_header(ACCEPT_ENCODING, HeaderValue::from_static("gzip"))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.header(CONTENT_ENCODING), "gzip");
+
+    // Clients that don't ask get the raw document
+    let response = server.get(&url).await;
+    assert!(response.maybe_header(CONTENT_ENCODING).is_none());
+    assert_eq!(
+        response.as_bytes().as_ref(),
+        animation.protobuf_data.as_slice()
+    );
+}
+
+#[tokio::test]
+async fn test_resumable_upload_saves_once_complete() {
+    let test_db = TestDb::new();
+    let (cookie, other_cookie) = {
+        let mut conn = test_db.conn();
+        let uploader = fixtures::insert_test_user(&mut conn, "uploader");
+        let other = fixtures::insert_test_user(&mut conn, "other");
+        (
+            fixtures::insert_test_session(&mut conn, uploader),
+            fixtures::insert_test_session(&mut conn, other),
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let document = fixtures::create_test_animation_proto("Uploaded in parts");
+    let parts: Vec<&[u8]> = document.chunks(document.len() / 3 + 1).collect();
+    assert_eq!(parts.len(), 3);
+
+    let response = server
+        .post("/api/uploads")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({}))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let upload_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+    let url = format!("/api/uploads/{}", upload_id);
+
+    // Parts may arrive out of order; completing with a gap fails
+    for n in [2, 0] {
+        let response = server
+            .put(&format!("{}/part/{}", url, n))
+            .add_header(COOKIE, cookie.clone())
+            .bytes(Bytes::copy_from_slice(parts[n]))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    }
+    let response = server
+        .post(&format!("{}/complete", url))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    assert!(response.json::<serde_json::Value>()["error"]
+        .as_str()
+        .unwrap()
+        .contains("missing part 1"));
+
+    // The status says what to resend; only the owner may see it
+    let json: serde_json::Value = server
+        .get(&url)
+        .add_header(COOKIE, cookie.clone())
+        .await
+        .json();
+    assert_eq!(json["received_parts"], serde_json::json!([0, 2]));
+    let response = server
+        .get(&url)
+        .add_header(COOKIE, HeaderValue::from_str(&other_cookie).unwrap())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+    server
+        .put(&format!("{}/part/1", url))
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::copy_from_slice(parts[1]))
+        .await;
+    let response = server
+        .post(&format!("{}/complete", url))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let animation_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+
+    let response = server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.as_bytes().as_ref(), document.as_slice());
+
+    // Completed uploads are gone
+    let response = server.get(&url).add_header(COOKIE, cookie).await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_replacement_upload_requires_if_match() {
+    let test_db = TestDb::new();
+    let (animation, cookie) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "replacer");
+        (
+            fixtures::insert_owned_test_animation(&mut conn, "Replaced", owner),
+            fixtures::insert_test_session(&mut conn, owner),
+        )
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let response = server
+        .post("/api/uploads")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "animation_id": animation.id }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let upload_id = response.json::<serde_json::Value>()["id"].as_i64().unwrap();
+    let url = format!("/api/uploads/{}", upload_id);
+    server
+        .put(&format!("{}/part/0", url))
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from(fixtures::create_test_animation_proto(
+            "Replacement",
+        )))
+        .await;
+
+    // Without If-Match the replacement could overwrite an edit made meanwhile
+    let response = server
+        .post(&format!("{}/complete", url))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::PRECONDITION_REQUIRED);
+
+    // The upload is kept, so completing again with If-Match saves it
+    let response = server
+        .post(&format!("{}/complete", url))
+        .add_header(COOKIE, cookie.clone())
+        .add_header(IF_MATCH, HeaderValue::from_static("\"1\""))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let response = server
+        .get(&format!("/api/animation/{}/meta", animation.id))
+        .add_header(COOKIE, cookie)
+        .await;
+    assert_eq!(response.json::<serde_json::Value>()["name"], "Replacement");
+}
+
+#[tokio::test]
+async fn test_video_export_renders_gif() {
+    let test_db = TestDb::new();
+    let animation = {
+        let mut conn = test_db.conn();
+        fixtures::insert_test_animation(&mut conn, "Exported")
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let url = format!("/api/animation/{}/export/video", animation.id);
+
+    let response = server
+        .post(&url)
+        .json(&serde_json::json!({ "format": "gif", "size": 4096 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let response = server
+        .post(&url)
+        .json(&serde_json::json!({ "format": "gif", "size": 64, "fps": 10 }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::ACCEPTED);
+    let job: serde_json::Value = response.json();
+    let status_url = format!("/api/exports/{}", job["id"]);
+
+    // Rendering runs after the response, so wait for it
+    let mut download_url = None;
+    for _ in 0..100 {
+        let job: serde_json::Value = server.get(&status_url).await.json();
+        assert_ne!(job["status"], "failed", "{}", job["error"]);
+        if let Some(url) = job["download_url"].as_str() {
+            download_url = Some(url.to_string());
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let download_url = download_url.expect("Export never finished");
+
+    // The queue job that rendered it is reported too, and is marked done just after the export
+    let job_url = format!("/api/jobs/{}", job["job_id"]);
+    let mut queue_job: serde_json::Value = server.get(&job_url).await.json();
+    for _ in 0..50 {
+        if queue_job["status"] == "done" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        queue_job = server.get(&job_url).await.json();
+    }
+    assert_eq!(queue_job["kind"], "export_video");
+    assert_eq!(queue_job["status"], "done");
+    assert_eq!(queue_job["attempts"], 1);
+
+    let response = server.get(&download_url).await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.header("content-type"), "image/gif");
+    assert!(response.as_bytes().starts_with(b"GIF89a"));
+}
+
+#[tokio::test]
+async fn test_local_account_register_login_and_reset() {
+    let test_db = TestDb::new();
+    let server = create_test_app(test_db.pool.clone()).await;
+    let session_of = |response: &axum_test::TestResponse| {
+        let set_cookie = response.header("set-cookie");
+        let cookie = set_cookie.to_str().unwrap().split(';').next().unwrap();
+        HeaderValue::from_str(cookie).unwrap()
+    };
+
+    let response = server
+        .post("/api/auth/register")
+        .json(&serde_json::json!({
+            "username": "ada",
+            "email": " Ada@Example.com ",
+            "password": "analytical engine"
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let account: serde_json::Value = response.json();
+    assert_eq!(account["email"], "ada@example.com");
+    let user_id = account["id"].as_i64().unwrap();
+    let session = session_of(&response);
+
+    // The new session works like any other
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, session.clone())
+        .bytes(Bytes::from(fixtures::create_test_animation_proto("Mine")))
+        .await;
+    let animation_id = response.json::<serde_json::Value>()["id"].clone();
+    let meta: serde_json::Value = server
+        .get(&format!("/api/animation/{}/meta", animation_id))
+        .add_header(COOKIE, session)
+        .await
+        .json();
+    assert_eq!(meta["owner_id"], user_id);
+
+    let response = server
+        .post("/api/auth/register")
+        .json(&serde_json::json!({
+            "username": "countess",
+            "email": "ada@example.com",
+            "password": "analytical engine"
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    let response = server
+        .post("/api/auth/register")
+        .json(&serde_json::json!({
+            "username": "babbage",
+            "email": "charles@example.com",
+            "password": "short"
+        }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let login = |password: &'static str| {
+        server.post("/api/auth/login").json(&serde_json::json!({
+            "email": "ada@example.com",
+            "password": password
+        }))
+    };
+    assert_eq!(
+        login("difference engine").await.status_code(),
+        StatusCode::UNAUTHORIZED
+    );
+    let response = login("analytical engine").await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let old_session = session_of(&response);
+
+    // Tokens go to the installed notifier, such as a mailer
+    use std::sync::{Arc, Mutex};
+    struct Outbox(Arc<Mutex<Vec<(String, String)>>>);
+    impl backend::accounts::ResetNotifier for Outbox {
+        fn send_reset(&self, email: &str, token: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((email.to_string(), token.to_string()));
+        }
+    }
+    let sent: Arc<Mutex<Vec<(String, String)>>> = Default::default();
+    backend::accounts::set_reset_notifier(Arc::new(Outbox(sent.clone())));
+
+    // Unknown emails are accepted too, so accounts can't be discovered this way
+    let response = server
+        .post("/api/auth/password-reset")
+        .json(&serde_json::json!({ "email": "nobody@example.com" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::ACCEPTED);
+    let response = server
+        .post("/api/auth/password-reset")
+        .json(&serde_json::json!({ "email": "ada@example.com" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::ACCEPTED);
+
+    let token = sent
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(email, _)| email == "ada@example.com")
+        .map(|(_, token)| token.clone())
+        .expect("The reset was never sent");
+    let confirm = |token: String| {
+        server
+            .post("/api/auth/password-reset/confirm")
+            .json(&serde_json::json!({ "token": token, "password": "difference engine" }))
+    };
+    assert_eq!(
+        confirm("b".repeat(64)).await.status_code(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        confirm(token.clone()).await.status_code(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(confirm(token).await.status_code(), StatusCode::BAD_REQUEST);
+
+    assert_eq!(
+        login("analytical engine").await.status_code(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        login("difference engine").await.status_code(),
+        StatusCode::OK
+    );
+    // Resetting signed out the old sessions
+    let response = server
+        .post(&format!("/api/animation/{}/duplicate", animation_id))
+        .add_header(COOKIE, old_session)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_tokens_authenticate_scripts() {
+    let test_db = TestDb::new();
+    let (owner, cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "scripter");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Private", owner);
+        let cookie = fixtures::insert_test_session(&mut conn, owner);
+        (owner, cookie, animation.id)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+    let bearer = |token: &serde_json::Value| {
+        HeaderValue::from_str(&format!("Bearer {}", token["token"].as_str().unwrap())).unwrap()
+    };
+
+    let response = server
+        .post("/api/tokens")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "name": "Dashboard", "scopes": ["read"] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let read_token: serde_json::Value = response.json();
+    assert!(read_token["token"]
+        .as_str()
+        .unwrap()
+        .starts_with(backend::tokens::TOKEN_PREFIX));
+    let response = server
+        .post("/api/tokens")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({
+            "name": "CI",
+            "scopes": ["read", "write"],
+            "expires_in_days": 30
+        }))
+        .await;
+    let ci_token: serde_json::Value = response.json();
+    assert!(ci_token["expires_at"].is_string());
+
+    let tokens: serde_json::Value = server
+        .get("/api/tokens")
+        .add_header(COOKIE, cookie.clone())
+        .await
+        .json();
+    assert_eq!(tokens.as_array().unwrap().len(), 2);
+    assert!(tokens[0].get("token").is_none());
+
+    // Read-only tokens can load private animations but not save
+    let response = server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .add_header(AUTHORIZATION, bearer(&read_token))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let document = fixtures::create_test_animation_proto("From CI");
+    let response = server
+        .post("/api/save_animation")
+        .add_header(AUTHORIZATION, bearer(&read_token))
+        .bytes(Bytes::from(document.clone()))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    // Batch metadata is a POST, but only reads
+    let response = server
+        .post("/api/animations/batch_meta")
+        .add_header(AUTHORIZATION, bearer(&read_token))
+        .json(&serde_json::json!({ "ids": [animation_id] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+
+    let response = server
+        .post("/api/save_animation")
+        .add_header(AUTHORIZATION, bearer(&ci_token))
+        .bytes(Bytes::from(document))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let saved_id = response.json::<serde_json::Value>()["id"].clone();
+    let meta: serde_json::Value = server
+        .get(&format!("/api/animation/{}/meta", saved_id))
+        .add_header(AUTHORIZATION, bearer(&ci_token))
+        .await
+        .json();
+    assert_eq!(meta["owner_id"], owner);
+
+    // Tokens can't mint more tokens, and bad tokens aren't treated as anonymous
+    let response = server
+        .get("/api/tokens")
+        .add_header(AUTHORIZATION, bearer(&ci_token))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    let response = server
+        .get("/api/health")
+        .add_header(
+            AUTHORIZATION,
+            HeaderValue::from_static("Bearer klyja_pat_nonsense"),
+        )
+        .await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+
+    let response = server
+        .delete(&format!("/api/tokens/{}", ci_token["id"]))
+        .add_header(COOKIE, cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    let response = server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .add_header(AUTHORIZATION, bearer(&ci_token))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_webhooks_receive_signed_save_events() {
+    use axum::http::HeaderMap;
+    use backend::webhooks;
+    use std::sync::{Arc, Mutex};
+
+    // A receiver recording what it is sent
+    let received: Arc<Mutex<Vec<(HeaderMap, Bytes)>>> = Default::default();
+    let receiver = axum::Router::new().route(
+        "/hook",
+        axum::routing::post({
+            let received = received.clone();
+            move |headers: HeaderMap, body: Bytes| async move {
+                received.lock().unwrap().push((headers, body));
+                StatusCode::OK
+            }
+        }),
+    );
+    // The receiver is on loopback, which webhooks may only call when allowed
+    std::env::set_var("WEBHOOK_ALLOW_PRIVATE_HOSTS", "true");
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hook_url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+    let test_db = TestDb::new();
+    let cookie = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "hooked");
+        fixtures::insert_test_session(&mut conn, owner)
+    };
+    let server = create_test_app(test_db.pool.clone()).await;
+    let cookie = HeaderValue::from_str(&cookie).unwrap();
+
+    let response = server
+        .post("/api/webhooks")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "url": "ftp://example.com", "events": ["saved"] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+    let response = server
+        .post("/api/webhooks")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "url": hook_url, "events": ["saved"], "secret": "s3cret" }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    assert_eq!(response.json::<serde_json::Value>()["secret"], "s3cret");
+
+    let listed: serde_json::Value = server
+        .get("/api/webhooks")
+        .add_header(COOKIE, cookie.clone())
+        .await
+        .json();
+    assert_eq!(listed[0]["url"], hook_url.as_str());
+    assert!(listed[0].get("secret").is_none());
+
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from(fixtures::create_test_animation_proto("Hooked")))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let animation_id = response.json::<serde_json::Value>()["id"].clone();
+
+    let mut waited = 0;
+    while received.lock().unwrap().is_empty() {
+        assert!(waited < 100, "Webhook was never delivered");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        waited += 1;
+    }
+    let (headers, body) = received.lock().unwrap()[0].clone();
+    assert_eq!(headers[webhooks::EVENT_HEADER], "saved");
+    assert_eq!(
+        headers[webhooks::SIGNATURE_HEADER],
+        webhooks::sign("s3cret", &body).as_str()
+    );
+    let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["animation_id"], animation_id);
+    assert_eq!(payload["version"], 1);
+
+    // Failed deliveries keep their error without the URL, which may hold a token,
+    // and aren't shown to the animation's viewers
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let secret_url = format!("http://{}/hook?token=hush", closed.local_addr().unwrap());
+    drop(closed);
+    let response = server
+        .post("/api/webhooks")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "url": secret_url, "events": ["saved"] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let response = server
+        .post("/api/save_animation")
+        .add_header(COOKIE, cookie.clone())
+        .bytes(Bytes::from(fixtures::create_test_animation_proto(
+            "Hooked again",
+        )))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let (job_id, job_animation, error) = {
+        use backend::schema::jobs;
+        use diesel::prelude::*;
+
+        let mut waited = 0;
+        loop {
+            let failed = jobs::table
+                .filter(jobs::kind.eq("deliver_webhook"))
+                .filter(jobs::last_error.is_not_null())
+                .select((jobs::id, jobs::animation_id, jobs::last_error))
+                .first::<(i32, Option<i32>, Option<String>)>(&mut test_db.conn())
+                .optional()
+                .unwrap();
+            if let Some((id, animation, error)) = failed {
+                break (id, animation, error.unwrap());
+            }
+            assert!(waited < 100, "Delivery never failed");
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            waited += 1;
+        }
+    };
+    assert!(!error.contains("hush"), "{}", error);
+    assert_eq!(job_animation, None);
+    let response = server
+        .get(&format!("/api/jobs/{}", job_id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+    // Deleting an animation is delivered after it is gone
+    let response = server
+        .post("/api/webhooks")
+        .add_header(COOKIE, cookie.clone())
+        .json(&serde_json::json!({ "url": hook_url, "events": ["deleted"] }))
+        .await;
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    let response = server
+        .delete(&format!("/api/animation/{}", animation_id))
+        .add_header(COOKIE, cookie.clone())
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+    let deleted = || {
+        received
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(headers, _)| headers[webhooks::EVENT_HEADER] == "deleted")
+            .map(|(_, body)| body.clone())
+    };
+    let mut waited = 0;
+    while deleted().is_none() {
+        assert!(waited < 100, "Deletion was never delivered");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        waited += 1;
+    }
+    let payload: serde_json::Value = serde_json::from_slice(&deleted().unwrap()).unwrap();
+    assert_eq!(payload["animation_id"], animation_id);
+    let response = server
+        .get(&format!("/api/load_animation/{}", animation_id))
+        .add_header(COOKIE, cookie)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_purge_deletes_expired_sessions() {
+    use backend::schema::sessions;
+    use diesel::prelude::*;
+
+    let test_db = TestDb::new();
+    let live_cookie = {
+        let mut conn = test_db.conn();
+        let user = fixtures::insert_test_user(&mut conn, "sleepy");
+        diesel::insert_into(sessions::table)
+            .values((
+                sessions::token.eq("expired"),
+                sessions::user_id.eq(user),
+                sessions::expires_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::days(1)),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        fixtures::insert_test_session(&mut conn, user)
+    };
+
+    // Workers purge once as soon as they start
+    let _server = create_test_app(test_db.pool.clone()).await;
+    let mut tokens = vec![];
+    for _ in 0..50 {
+        tokens = sessions::table
+            .select(sessions::token)
+            .load::<String>(&mut test_db.conn())
+            .unwrap();
+        if tokens.len() == 1 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(tokens.len(), 1);
+    assert!(live_cookie.ends_with(&tokens[0]));
+}
+
+#[tokio::test]
+async fn test_request_ids_are_echoed_and_reported_in_errors() {
+    let test_db = TestDb::new();
+    let server = create_test_app(test_db.pool.clone()).await;
+
+    let response = server.get("/api/load_animation/999999").await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    let request_id = response.header("x-request-id");
+    assert_eq!(request_id.len(), 36);
+    let json: serde_json::Value = response.json();
+    assert_eq!(json["request_id"], request_id.to_str().unwrap());
+
+    // Well-formed IDs from clients or proxies are kept, so traces line up
+    let response = server
+        .get("/api/health")
+        .add_header(
+            axum::http::HeaderName::from_static("x-request-id"),
+            HeaderValue::from_static("ci-run-42"),
+        )
+        .await;
+    assert_eq!(response.header("x-request-id"), "ci-run-42");
+    let response = server
+        .get("/api/health")
+        .add_header(
+            axum::http::HeaderName::from_static("x-request-id"),
+            HeaderValue::from_static("not valid!"),
+        )
+        .await;
+    assert_ne!(response.header("x-request-id"), "not valid!");
+}
+
+#[tokio::test]
+async fn test_live_rooms_throttle_updates_and_send_away_revoked_members() {
+    use backend::models::CollaboratorRole;
+    use backend::services::AnimationService;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest};
+
+    let test_db = TestDb::new();
+    let (owner, owner_cookie, collaborator, collaborator_cookie, stranger_cookie, animation_id) = {
+        let mut conn = test_db.conn();
+        let owner = fixtures::insert_test_user(&mut conn, "live owner");
+        let collaborator = fixtures::insert_test_user(&mut conn, "live editor");
+        let stranger = fixtures::insert_test_user(&mut conn, "live stranger");
+        let animation = fixtures::insert_owned_test_animation(&mut conn, "Live", owner);
+        (
+            owner,
+            fixtures::insert_test_session(&mut conn, owner),
+            collaborator,
+            fixtures::insert_test_session(&mut conn, collaborator),
+            fixtures::insert_test_session(&mut conn, stranger),
+            animation.id,
+        )
+    };
+    AnimationService::invite_collaborator_logic(
+        &test_db.pool,
+        animation_id,
+        Some(owner),
+        collaborator,
+        CollaboratorRole::Editor,
+    )
+    .await
+    .unwrap();
+
+    // Sockets need a real server to upgrade
+    let app = backend::app::build_app(&backend::app::AppConfig::from_env(), test_db.pool.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let live_url = format!(
+        "ws://{}/api/animation/{}/live",
+        listener.local_addr().unwrap(),
+        animation_id
+    );
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        )
+        .await
+        .unwrap()
+    });
+    let connect = |cookie: Option<&str>| {
+        let mut request = live_url.as_str().into_client_request().unwrap();
+        if let Some(cookie) = cookie {
+            request
+                .headers_mut()
+                .insert(COOKIE, HeaderValue::from_str(cookie).unwrap());
+        }
+        tokio_tungstenite::connect_async(request)
+    };
+    let refused_with = |result: Result<_, tungstenite::Error>| match result {
+        Err(tungstenite::Error::Http(response)) => response.status(),
+        _ => panic!("Socket wasn't refused"),
+    };
+    let next_json = |text: tungstenite::Message| -> serde_json::Value {
+        serde_json::from_str(text.to_text().unwrap()).unwrap()
+    };
+
+    // Joining needs a session with access to the animation
+    assert_eq!(
+        refused_with(connect(None).await.map(|_| ())),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        refused_with(connect(Some(&stranger_cookie)).await.map(|_| ())),
+        StatusCode::FORBIDDEN
+    );
+
+    let (mut editor, _) = connect(Some(&collaborator_cookie)).await.unwrap();
+    let joined = next_json(editor.next().await.unwrap().unwrap());
+    assert_eq!(joined["type"], "presence");
+    assert_eq!(joined["members"].as_array().unwrap().len(), 1);
+    let (mut watcher, _) = connect(Some(&owner_cookie)).await.unwrap();
+    let both = next_json(watcher.next().await.unwrap().unwrap());
+    assert_eq!(both["members"].as_array().unwrap().len(), 2);
+    assert_eq!(both["members"][0]["username"], "live editor");
+
+    // A burst of updates reaches the room merged, ending on the latest
+    for frame in 0..20 {
+        let update = serde_json::json!({ "mode": "editing", "frame": frame });
+        editor
+            .send(tungstenite::Message::Text(update.to_string()))
+            .await
+            .unwrap();
+    }
+    let mut broadcasts = 0;
+    loop {
+        let room = next_json(watcher.next().await.unwrap().unwrap());
+        broadcasts += 1;
+        if room["members"][0]["frame"] == 19 {
+            assert_eq!(room["members"][0]["mode"], "editing");
+            break;
+        }
+    }
+    assert!(broadcasts < 20, "{} broadcasts for 20 updates", broadcasts);
+
+    // Made a viewer, they are shown viewing and can't edit again
+    AnimationService::invite_collaborator_logic(
+        &test_db.pool,
+        animation_id,
+        Some(owner),
+        collaborator,
+        CollaboratorRole::Viewer,
+    )
+    .await
+    .unwrap();
+    loop {
+        let room = next_json(watcher.next().await.unwrap().unwrap());
+        if room["members"][0]["mode"] == "viewing" {
+            break;
+        }
+    }
+    editor
+        .send(tungstenite::Message::Text(
+            serde_json::json!({ "mode": "editing" }).to_string(),
+        ))
+        .await
+        .unwrap();
+    loop {
+        let message = next_json(editor.next().await.unwrap().unwrap());
+        if message["type"] == "error" {
+            break;
+        }
+    }
+
+    // Removed, they are told why and closed, and leave the room
+    AnimationService::remove_collaborator_logic(
+        &test_db.pool,
+        animation_id,
+        Some(owner),
+        collaborator,
+    )
+    .await
+    .unwrap();
+    loop {
+        match editor.next().await {
+            Some(Ok(tungstenite::Message::Text(text))) => {
+                let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+                if message["type"] == "error" {
+                    assert_eq!(
+                        message["message"],
+                        "You no longer have access to this animation"
+                    );
+                }
+            }
+            Some(Ok(tungstenite::Message::Close(_))) | None | Some(Err(_)) => break,
+            Some(Ok(_)) => {}
+        }
+    }
+    loop {
+        let room = next_json(watcher.next().await.unwrap().unwrap());
+        if room["members"].as_array().unwrap().len() == 1 {
+            assert_eq!(room["members"][0]["username"], "live owner");
+            break;
+        }
+    }
+}
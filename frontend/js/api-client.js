@@ -3,14 +3,21 @@
 export class ApiClient {
   constructor(baseUrl = '') {
     this.baseUrl = baseUrl;
+    // ETag of the last animation loaded or saved; saving the same document again
+    // sends it as If-Match, so the server can refuse to overwrite newer changes
+    this.lastEtag = null;
   }
 
   async saveAnimation(protobufData) {
+    const headers = {
+      'Content-Type': 'application/octet-stream'
+    };
+    if (this.lastEtag) {
+      headers['If-Match'] = this.lastEtag;
+    }
     const response = await fetch(`${this.baseUrl}/api/save_animation`, {
       method: 'POST',
-      headers: {
-        'Content-Type': 'application/octet-stream'
-      },
+      headers,
       body: protobufData
     });
 
@@ -22,6 +29,7 @@ export class ApiClient {
     if (response.status !== 201) {
       console.warn('Save request returned unexpected status:', response.status);
     }
+    this.lastEtag = response.headers?.get('ETag') ?? null;
     
     return response.json();
   }
@@ -37,8 +45,36 @@ export class ApiClient {
       const errorText = await response.text().catch(() => 'Failed to get error details');
       throw new Error(`Load failed (${response.status}): ${errorText || response.statusText}`);
     }
+    this.lastEtag = response.headers?.get('ETag') ?? null;
 
     const arrayBuffer = await response.arrayBuffer();
     return new Uint8Array(arrayBuffer);
   }
+
+  async listAnimations(query = '') {
+    const params = query ? `?${new URLSearchParams({ q: query })}` : '';
+    const response = await fetch(`${this.baseUrl}/api/animations${params}`);
+
+    if (!response.ok) {
+      const errorText = await response.text().catch(() => 'Failed to get error details');
+      throw new Error(`Listing failed (${response.status}): ${errorText || response.statusText}`);
+    }
+
+    return response.json();
+  }
+
+  async uploadThumbnail(id, pngData) {
+    const response = await fetch(`${this.baseUrl}/api/animation/${id}/thumbnail`, {
+      method: 'PUT',
+      headers: {
+        'Content-Type': 'image/png'
+      },
+      body: pngData
+    });
+
+    if (!response.ok) {
+      const errorText = await response.text().catch(() => 'Failed to get error details');
+      throw new Error(`Thumbnail upload failed (${response.status}): ${errorText || response.statusText}`);
+    }
+  }
 }
@@ -49,6 +49,21 @@ describe('ApiClient', () => {
       expect(result).toEqual({ id: 123, message: 'Success' });
     });
 
+    it('should send the last ETag as If-Match', async () => {
+      fetchMock.mockResolvedValue({
+        ok: true,
+        status: 201,
+        headers: new Headers({ ETag: '"4"' }),
+        json: vi.fn().mockResolvedValue({ id: 123 })
+      });
+
+      await client.saveAnimation(new Uint8Array([1]));
+      await client.saveAnimation(new Uint8Array([2]));
+
+      expect(fetchMock.mock.calls[0][1].headers['If-Match']).toBeUndefined();
+      expect(fetchMock.mock.calls[1][1].headers['If-Match']).toBe('"4"');
+    });
+
     it('should handle non-201 success status', async () => {
       const mockResponse = {
         ok: true,
@@ -146,4 +161,73 @@ describe('ApiClient', () => {
       expect(fetchMock).toHaveBeenCalledWith('http://api.example.com/api/load_animation/123');
     });
   });
+
+  describe('listAnimations', () => {
+    it('should list all animations without a query', async () => {
+      const summaries = [{ id: 1, name: 'Pangaea' }];
+      fetchMock.mockResolvedValue({
+        ok: true,
+        status: 200,
+        json: vi.fn().mockResolvedValue(summaries)
+      });
+
+      const result = await client.listAnimations();
+
+      expect(fetchMock).toHaveBeenCalledWith('/api/animations');
+      expect(result).toEqual(summaries);
+    });
+
+    it('should encode the name query', async () => {
+      fetchMock.mockResolvedValue({
+        ok: true,
+        status: 200,
+        json: vi.fn().mockResolvedValue([])
+      });
+
+      await client.listAnimations('ice & fire');
+
+      expect(fetchMock).toHaveBeenCalledWith('/api/animations?q=ice+%26+fire');
+    });
+
+    it('should throw error on failed request', async () => {
+      fetchMock.mockResolvedValue({
+        ok: false,
+        status: 500,
+        statusText: 'Internal Server Error',
+        text: vi.fn().mockResolvedValue('Database error')
+      });
+
+      await expect(client.listAnimations())
+        .rejects.toThrow('Listing failed (500): Database error');
+    });
+  });
+
+  describe('uploadThumbnail', () => {
+    it('should PUT the PNG bytes for the animation', async () => {
+      fetchMock.mockResolvedValue({ ok: true, status: 204 });
+
+      const pngData = new Uint8Array([0x89, 0x50, 0x4e, 0x47]);
+      await client.uploadThumbnail(7, pngData);
+
+      expect(fetchMock).toHaveBeenCalledWith('/api/animation/7/thumbnail', {
+        method: 'PUT',
+        headers: {
+          'Content-Type': 'image/png'
+        },
+        body: pngData
+      });
+    });
+
+    it('should throw error on failed request', async () => {
+      fetchMock.mockResolvedValue({
+        ok: false,
+        status: 400,
+        statusText: 'Bad Request',
+        text: vi.fn().mockResolvedValue('Thumbnail must be a PNG image')
+      });
+
+      await expect(client.uploadThumbnail(7, new Uint8Array([1])))
+        .rejects.toThrow('Thumbnail upload failed (400): Thumbnail must be a PNG image');
+    });
+  });
 });
@@ -0,0 +1,65 @@
+// klyja/geco-core/src/basemap.rs
+// Decoding of packed basemap line data (e.g. pre-simplified Natural Earth coastlines).
+//
+// Packed format, all values little-endian:
+//   b"KBM1"                      magic
+//   u32 ring_count
+//   ring_count times:
+//     u32 point_count
+//     point_count times: f32 latitude, f32 longitude (degrees)
+
+const MAGIC: &[u8; 4] = b"KBM1";
+
+/// Decodes packed basemap data into rings of (latitude, longitude) pairs.
+pub fn decode_rings(data: &[u8]) -> Result<Vec<Vec<(f32, f32)>>, String> {
+    let mut reader = Reader { data, offset: 0 };
+    if reader.take(4)? != MAGIC {
+        return Err("Not a packed basemap (bad magic)".to_string());
+    }
+    let ring_count = reader.u32()?;
+    let mut rings = Vec::new();
+    for _ in 0..ring_count {
+        let point_count = reader.u32()? as usize;
+        // Guard against bogus counts before allocating
+        if point_count > data.len() / 8 {
+            return Err(format!(
+                "Ring claims {} points, more than the data holds",
+                point_count
+            ));
+        }
+        let mut ring = Vec::with_capacity(point_count);
+        for _ in 0..point_count {
+            ring.push((reader.f32()?, reader.f32()?));
+        }
+        rings.push(ring);
+    }
+    Ok(rings)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| "Packed basemap is truncated".to_string())?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+}
@@ -0,0 +1,32 @@
+// klyja/geco-core/src/bounds.rs
+// Bounding spherical caps: the smallest-ish circle on the globe around a feature,
+// cheap to test against for culling, hit-test pre-filtering and camera framing.
+use crate::geometry::{dot, normalize, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingCap {
+    pub center: Vec3,       // Unit direction from the globe center
+    pub angle_degrees: f32, // Angular radius around `center`
+}
+
+/// Cap centered on the mean direction of `points`, just wide enough to hold them all.
+/// Not always the minimal cap, but close for compact shapes and cheap to compute.
+/// Great-circle edges between the points stay inside too while the cap is smaller
+/// than a hemisphere.
+/// None without usable points.
+pub fn bounding_cap(points: &[Vec3]) -> Option<BoundingCap> {
+    let units: Vec<Vec3> = points.iter().filter_map(|p| normalize(*p)).collect();
+    let sum = units.iter().fold([0.0; 3], |acc, u| {
+        [acc[0] + u[0], acc[1] + u[1], acc[2] + u[2]]
+    });
+    // Points spread evenly around the globe cancel out; any direction then works
+    let center = normalize(sum).or_else(|| units.first().copied())?;
+    let min_cos = units
+        .iter()
+        .map(|u| dot(*u, center).clamp(-1.0, 1.0))
+        .fold(1.0f32, f32::min);
+    Some(BoundingCap {
+        center,
+        angle_degrees: min_cos.acos().to_degrees(),
+    })
+}
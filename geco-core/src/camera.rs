@@ -0,0 +1,81 @@
+// klyja/geco-core/src/camera.rs
+// Camera path keyframes: positions and targets are slerped around the globe center,
+// zoom is interpolated linearly.
+use crate::geometry::{dot, length, normalize, Vec3};
+use crate::protobuf_gen::{CameraKeyframe, Point};
+
+/// Inserts a keyframe, replacing any existing keyframe at the same frame.
+pub fn upsert_keyframe(keyframes: &mut Vec<CameraKeyframe>, keyframe: CameraKeyframe) {
+    match keyframes.binary_search_by_key(&keyframe.frame, |k| k.frame) {
+        Ok(index) => keyframes[index] = keyframe,
+        Err(index) => keyframes.insert(index, keyframe),
+    }
+}
+
+/// Camera state at `frame`, held constant before the first and after the last keyframe.
+/// Returns None when there are no keyframes.
+pub fn camera_at_frame(keyframes: &[CameraKeyframe], frame: i32) -> Option<CameraKeyframe> {
+    let next_index = keyframes.partition_point(|k| k.frame <= frame);
+    match (
+        next_index.checked_sub(1).map(|i| &keyframes[i]),
+        keyframes.get(next_index),
+    ) {
+        (None, None) => None,
+        (Some(only), None) | (None, Some(only)) => Some(CameraKeyframe {
+            frame,
+            ..only.clone()
+        }),
+        (Some(previous), Some(next)) => {
+            let t = (frame - previous.frame) as f32 / (next.frame - previous.frame) as f32;
+            Some(CameraKeyframe {
+                frame,
+                position: slerp_points(previous.position.as_ref(), next.position.as_ref(), t),
+                target: slerp_points(previous.target.as_ref(), next.target.as_ref(), t),
+                zoom: previous.zoom + (next.zoom - previous.zoom) * t,
+            })
+        }
+    }
+}
+
+fn slerp_points(a: Option<&Point>, b: Option<&Point>, t: f32) -> Option<Point> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let [x, y, z] = slerp(to_vec3(a), to_vec3(b), t);
+            Some(Point { x, y, z: Some(z) })
+        }
+        (a, b) => a.or(b).cloned(),
+    }
+}
+
+fn to_vec3(p: &Point) -> Vec3 {
+    [p.x, p.y, p.z.unwrap_or(0.0)]
+}
+
+/// Spherical interpolation of direction combined with linear interpolation of distance
+/// from the center, so the camera orbits the globe instead of cutting through it.
+pub fn slerp(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+    let lerp = |a: Vec3, b: Vec3| {
+        [
+            a[0] + (b[0] - a[0]) * t,
+            a[1] + (b[1] - a[1]) * t,
+            a[2] + (b[2] - a[2]) * t,
+        ]
+    };
+    let (Some(unit_a), Some(unit_b)) = (normalize(a), normalize(b)) else {
+        return lerp(a, b);
+    };
+    let omega = dot(unit_a, unit_b).clamp(-1.0, 1.0).acos();
+    if omega.abs() < 1e-4 || (std::f32::consts::PI - omega).abs() < 1e-4 {
+        // Nearly parallel (or opposite, where the arc is ambiguous): fall back to lerp
+        return lerp(a, b);
+    }
+    let sin_omega = omega.sin();
+    let weight_a = ((1.0 - t) * omega).sin() / sin_omega;
+    let weight_b = (t * omega).sin() / sin_omega;
+    let radius = length(a) + (length(b) - length(a)) * t;
+    [
+        (unit_a[0] * weight_a + unit_b[0] * weight_b) * radius,
+        (unit_a[1] * weight_a + unit_b[1] * weight_b) * radius,
+        (unit_a[2] * weight_a + unit_b[2] * weight_b) * radius,
+    ]
+}
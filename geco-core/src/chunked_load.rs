@@ -0,0 +1,86 @@
+// klyja/geco-core/src/chunked_load.rs
+// Incremental protobuf decoding for large documents arriving as a stream of chunks.
+//
+// A `MapAnimation` is a sequence of top-level fields, and decoding fields one at a time
+// into the same message is equivalent to decoding them all at once (repeated fields
+// append). So only the top-level field currently being received (e.g. one polygon) has
+// to be buffered, never the whole document.
+use crate::protobuf_gen::MapAnimation;
+use prost::Message;
+
+#[derive(Default)]
+pub struct ChunkedDecoder {
+    animation: MapAnimation,
+    pending: Vec<u8>, // Bytes of the incomplete top-level field, if any
+}
+
+impl ChunkedDecoder {
+    /// Decodes every top-level field completed by `chunk`, keeping the remainder.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), String> {
+        self.pending.extend_from_slice(chunk);
+        let mut consumed = 0;
+        while let Some(field_len) = complete_field_len(&self.pending[consumed..])? {
+            let field = &self.pending[consumed..consumed + field_len];
+            self.animation
+                .merge(field)
+                .map_err(|e| format!("Failed to decode Protobuf: {}", e))?;
+            consumed += field_len;
+        }
+        self.pending.drain(..consumed);
+        Ok(())
+    }
+
+    /// Returns the decoded document, failing if the stream stopped mid-field.
+    pub fn finish(self) -> Result<MapAnimation, String> {
+        if !self.pending.is_empty() {
+            return Err(format!(
+                "Protobuf stream ended with {} bytes of an incomplete field",
+                self.pending.len()
+            ));
+        }
+        Ok(self.animation)
+    }
+}
+
+/// Length in bytes (key included) of the field at the start of `buf`, or None if
+/// `buf` doesn't hold all of it yet.
+pub fn complete_field_len(buf: &[u8]) -> Result<Option<usize>, String> {
+    let Some((key, key_len)) = read_varint(buf)? else {
+        return Ok(None);
+    };
+    let rest = &buf[key_len..];
+    let value_len = match key & 0x7 {
+        0 => read_varint(rest)?.map(|(_, len)| len),
+        1 => (rest.len() >= 8).then_some(8),
+        2 => match read_varint(rest)? {
+            Some((len, prefix_len)) => {
+                let total = usize::try_from(len)
+                    .ok()
+                    .and_then(|len| len.checked_add(prefix_len))
+                    .ok_or("Protobuf field length overflows")?;
+                (rest.len() >= total).then_some(total)
+            }
+            None => None,
+        },
+        5 => (rest.len() >= 4).then_some(4),
+        wire_type => return Err(format!("Unsupported protobuf wire type {}", wire_type)),
+    };
+    Ok(value_len.map(|len| key_len + len))
+}
+
+/// Decodes a base-128 varint, returning it with its encoded length, or None if
+/// `buf` ends before the varint does.
+fn read_varint(buf: &[u8]) -> Result<Option<(u64, usize)>, String> {
+    let mut value = 0u64;
+    for (index, byte) in buf.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7F) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, index + 1)));
+        }
+    }
+    if buf.len() >= 10 {
+        Err("Invalid protobuf varint".to_string())
+    } else {
+        Ok(None)
+    }
+}
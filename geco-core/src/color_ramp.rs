@@ -0,0 +1,55 @@
+// klyja/geco-core/src/color_ramp.rs
+// Maps scalar point data to colors for data-driven styling.
+use serde::Deserialize;
+
+/// Color used for vertices without data or when no ramp is configured.
+pub const DEFAULT_VERTEX_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ColorStop {
+    pub value: f32,
+    pub color: [f32; 3], // RGB, each channel in 0..=1
+}
+
+/// A piecewise-linear color ramp. Values outside the stops are clamped to the end colors.
+#[derive(Clone, Debug)]
+pub struct ColorRamp {
+    stops: Vec<ColorStop>, // Sorted by value, never empty
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<ColorStop>) -> Result<Self, String> {
+        if stops.is_empty() {
+            return Err("A color ramp needs at least one stop".to_string());
+        }
+        if stops.iter().any(|s| !s.value.is_finite()) {
+            return Err("Color ramp stop values must be finite".to_string());
+        }
+        stops.sort_by(|a, b| a.value.total_cmp(&b.value));
+        Ok(ColorRamp { stops })
+    }
+
+    /// Parses stops from JSON like `[{"value": 0, "color": [0, 0, 1]}, ...]`.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let stops: Vec<ColorStop> =
+            serde_json::from_str(json).map_err(|e| format!("Invalid color ramp JSON: {}", e))?;
+        Self::new(stops)
+    }
+
+    pub fn color_at(&self, value: f32) -> [f32; 3] {
+        let next_index = self.stops.partition_point(|s| s.value <= value);
+        if next_index == 0 {
+            return self.stops[0].color;
+        }
+        let previous = &self.stops[next_index - 1];
+        let Some(next) = self.stops.get(next_index) else {
+            return previous.color;
+        };
+        let t = (value - previous.value) / (next.value - previous.value);
+        let mut color = [0.0; 3];
+        for (channel, out) in color.iter_mut().enumerate() {
+            *out = previous.color[channel] + (next.color[channel] - previous.color[channel]) * t;
+        }
+        color
+    }
+}
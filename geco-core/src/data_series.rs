@@ -0,0 +1,122 @@
+// klyja/geco-core/src/data_series.rs
+// Data series bound to the timeline: external values such as a CO2 level or the year
+// being shown, kept with the document and read out per frame for on-screen display.
+// Values are interpolated linearly between keyframes; labels are text and are held
+// from their keyframe until the next one with a label.
+use crate::protobuf_gen::{DataKeyframe, DataSeries};
+use serde::Serialize;
+
+/// Inserts a keyframe, replacing any existing keyframe at the same frame.
+pub fn upsert_keyframe(series: &mut DataSeries, keyframe: DataKeyframe) {
+    match series
+        .keyframes
+        .binary_search_by_key(&keyframe.frame, |k| k.frame)
+    {
+        Ok(index) => series.keyframes[index] = keyframe,
+        Err(index) => series.keyframes.insert(index, keyframe),
+    }
+}
+
+/// Replaces the series' keyframes with samples taken at external times (e.g. years),
+/// placing `start_time` on frame 0 and `end_time` on the last of `total_frames`.
+/// Samples outside that span are dropped; of samples landing on the same frame the
+/// last is kept. Returns the number of keyframes set.
+pub fn bind_samples(
+    series: &mut DataSeries,
+    times: &[f64],
+    values: &[f32],
+    start_time: f64,
+    end_time: f64,
+    total_frames: i32,
+) -> Result<usize, String> {
+    if times.len() != values.len() {
+        return Err(format!(
+            "Series '{}' got {} times but {} values",
+            series.series_id,
+            times.len(),
+            values.len()
+        ));
+    }
+    if !(start_time.is_finite() && end_time.is_finite() && start_time < end_time) {
+        return Err("The start time must come before the end time".to_string());
+    }
+    let last_frame = (total_frames - 1).max(0);
+    let per_frame = (end_time - start_time) / last_frame.max(1) as f64;
+
+    series.keyframes.clear();
+    for (&time, &value) in times.iter().zip(values) {
+        if !(start_time..=end_time).contains(&time) || !value.is_finite() {
+            continue;
+        }
+        let frame = (((time - start_time) / per_frame).round() as i32).min(last_frame);
+        upsert_keyframe(
+            series,
+            DataKeyframe {
+                frame,
+                value,
+                label: None,
+            },
+        );
+    }
+    Ok(series.keyframes.len())
+}
+
+/// Returns the series value at `frame`, linearly interpolated between the surrounding
+/// keyframes and held constant before the first and after the last one.
+/// Returns None if the series has no keyframes.
+pub fn value_at_frame(series: &DataSeries, frame: i32) -> Option<f32> {
+    let keyframes = &series.keyframes;
+    let next_index = keyframes.partition_point(|k| k.frame <= frame);
+    match (
+        next_index.checked_sub(1).map(|i| &keyframes[i]),
+        keyframes.get(next_index),
+    ) {
+        (None, None) => None,
+        (Some(only), None) | (None, Some(only)) => Some(only.value),
+        (Some(previous), Some(next)) => {
+            let t = (frame - previous.frame) as f32 / (next.frame - previous.frame) as f32;
+            Some(previous.value + (next.value - previous.value) * t)
+        }
+    }
+}
+
+/// Returns the label shown at `frame`: that of the last labelled keyframe at or before
+/// it, or of the first labelled one when `frame` comes earlier. None without labels.
+pub fn label_at_frame(series: &DataSeries, frame: i32) -> Option<&str> {
+    let mut labelled = series
+        .keyframes
+        .iter()
+        .filter_map(|k| k.label.as_deref().map(|label| (k.frame, label)));
+    let first = labelled.next()?;
+    let current = labelled
+        .take_while(|(at, _)| *at <= frame)
+        .last()
+        .unwrap_or(first);
+    Some(current.1)
+}
+
+/// A series as shown at one frame.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SeriesReading {
+    pub series_id: String,
+    pub name: String,
+    pub unit: String,
+    pub value: f32,
+    pub label: Option<String>,
+}
+
+/// Reads every series with keyframes at `frame`, in document order.
+pub fn readings_at_frame(series: &[DataSeries], frame: i32) -> Vec<SeriesReading> {
+    series
+        .iter()
+        .filter_map(|series| {
+            Some(SeriesReading {
+                series_id: series.series_id.clone(),
+                name: series.name.clone(),
+                unit: series.unit.clone(),
+                value: value_at_frame(series, frame)?,
+                label: label_at_frame(series, frame).map(str::to_string),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,195 @@
+// klyja/geco-core/src/delaunay.rs
+// Spherical Delaunay triangulation and Voronoi cells of a point set, e.g. for
+// generating plate boundaries or cell-based maps from scattered points.
+// On the sphere the Delaunay triangles are exactly the faces of the points' convex
+// hull, so this builds the hull incrementally (in f64, since nearby points give
+// nearly flat faces).
+use crate::geometry::{length, normalize, Vec3};
+
+type DVec3 = [f64; 3];
+
+/// Below this, a point is treated as lying on a face's plane (unit-sphere scale).
+const PLANE_EPSILON: f64 = 1e-10;
+
+fn sub(a: DVec3, b: DVec3) -> DVec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: DVec3, b: DVec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: DVec3, b: DVec3) -> DVec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn face_normal(units: &[DVec3], [a, b, c]: [usize; 3]) -> DVec3 {
+    cross(sub(units[b], units[a]), sub(units[c], units[a]))
+}
+
+/// Delaunay triangles of the points on the globe, as index triples into `points`
+/// wound counter-clockwise seen from outside. Points at the center and repeats of
+/// earlier points are left out. Empty unless at least four points don't share a great circle.
+/// Points confined to a hemisphere only get triangles over their own spherical hull.
+pub fn delaunay_triangles(points: &[Vec3]) -> Vec<[usize; 3]> {
+    let units: Vec<DVec3> = points
+        .iter()
+        .map(|p| match normalize(*p) {
+            Some(u) => u.map(f64::from),
+            None => [0.0; 3],
+        })
+        .collect();
+    let mut usable: Vec<usize> = Vec::with_capacity(units.len());
+    for i in 0..units.len() {
+        let duplicate = |&j: &usize| {
+            let d = sub(units[i], units[j]);
+            dot(d, d) < PLANE_EPSILON
+        };
+        if dot(units[i], units[i]) > 0.5 && !usable.iter().any(duplicate) {
+            usable.push(i);
+        }
+    }
+    let Some(mut faces) = initial_tetrahedron(&units, &usable) else {
+        return vec![];
+    };
+    let used: Vec<usize> = faces.iter().flatten().copied().collect();
+    for &p in usable.iter().filter(|i| !used.contains(i)) {
+        add_point(&units, &mut faces, p);
+    }
+    // Faces with the globe center in front of them close off the hull of a
+    // hemisphere-bound point set; they aren't part of the triangulation
+    faces.retain(|&face| dot(face_normal(&units, face), units[face[0]]) > 0.0);
+    faces
+}
+
+/// Four points spanning a volume, as outward-facing triangles, or None if every
+/// usable point lies on one plane.
+fn initial_tetrahedron(units: &[DVec3], usable: &[usize]) -> Option<Vec<[usize; 3]>> {
+    let farthest = |score: &dyn Fn(DVec3) -> f64| {
+        usable
+            .iter()
+            .copied()
+            .max_by(|&a, &b| score(units[a]).total_cmp(&score(units[b])))
+    };
+    let a = *usable.first()?;
+    let b = farthest(&|p| dot(sub(p, units[a]), sub(p, units[a])))?;
+    let ab = sub(units[b], units[a]);
+    let c = farthest(&|p| {
+        let n = cross(ab, sub(p, units[a]));
+        dot(n, n)
+    })?;
+    let normal = cross(ab, sub(units[c], units[a]));
+    let d = farthest(&|p| dot(normal, sub(p, units[a])).abs())?;
+    if dot(normal, sub(units[d], units[a])).abs() < PLANE_EPSILON {
+        return None;
+    }
+    // Wind every face so its normal points away from the opposite vertex
+    let faces = [
+        ([a, b, c], d),
+        ([a, b, d], c),
+        ([a, c, d], b),
+        ([b, c, d], a),
+    ];
+    Some(
+        faces
+            .into_iter()
+            .map(|(face, opposite)| {
+                let toward = dot(
+                    face_normal(units, face),
+                    sub(units[opposite], units[face[0]]),
+                );
+                if toward > 0.0 {
+                    [face[0], face[2], face[1]]
+                } else {
+                    face
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Grows the hull to include point `p`: faces it can see are replaced by a fan
+/// from `p` to the horizon around them.
+fn add_point(units: &[DVec3], faces: &mut Vec<[usize; 3]>, p: usize) {
+    let (visible, hidden): (Vec<[usize; 3]>, Vec<[usize; 3]>) = faces.iter().partition(|&&face| {
+        dot(face_normal(units, face), sub(units[p], units[face[0]])) > PLANE_EPSILON
+    });
+    if visible.is_empty() {
+        return; // Already on the hull (a near-duplicate)
+    }
+    let edges = |face: [usize; 3]| [(face[0], face[1]), (face[1], face[2]), (face[2], face[0])];
+    let visible_edges: Vec<(usize, usize)> = visible.iter().flat_map(|&f| edges(f)).collect();
+    let horizon = visible_edges
+        .iter()
+        .filter(|&&(from, to)| !visible_edges.contains(&(to, from)));
+    let mut grown = hidden;
+    grown.extend(horizon.map(|&(from, to)| [from, to, p]));
+    *faces = grown;
+}
+
+/// Voronoi cell of every point surrounded by triangles: the circumcenters of the
+/// triangles around it, in counter-clockwise order seen from outside, at the point's
+/// distance from the center. Points on the edge of a hemisphere-bound set have
+/// unbounded cells and are left out, as are points missing from the triangulation.
+pub fn voronoi_cells(points: &[Vec3], triangles: &[[usize; 3]]) -> Vec<(usize, Vec<Vec3>)> {
+    let units: Vec<DVec3> = points
+        .iter()
+        .map(|p| normalize(*p).unwrap_or([0.0; 3]).map(f64::from))
+        .collect();
+    let circumcenters: Vec<DVec3> = triangles
+        .iter()
+        .map(|&t| {
+            let n = face_normal(&units, t);
+            let len = dot(n, n).sqrt();
+            n.map(|c| c / len)
+        })
+        .collect();
+
+    (0..points.len())
+        .filter_map(|i| {
+            let around: Vec<usize> = (0..triangles.len())
+                .filter(|&t| triangles[t].contains(&i))
+                .collect();
+            // A closed fan has as many distinct neighbours as triangles
+            let mut neighbours: Vec<usize> = around
+                .iter()
+                .flat_map(|&t| triangles[t])
+                .filter(|&j| j != i)
+                .collect();
+            neighbours.sort_unstable();
+            neighbours.dedup();
+            if around.len() < 3 || neighbours.len() != around.len() {
+                return None;
+            }
+
+            // Order the circumcenters by angle in the tangent plane at the point
+            let normal = units[i];
+            let reference = if normal[0].abs() < 0.9 {
+                [1.0, 0.0, 0.0]
+            } else {
+                [0.0, 1.0, 0.0]
+            };
+            let e1 = cross(reference, normal);
+            let e2 = cross(normal, e1);
+            let mut corners: Vec<(f64, DVec3)> = around
+                .iter()
+                .map(|&t| {
+                    let c = circumcenters[t];
+                    (dot(c, e2).atan2(dot(c, e1)), c)
+                })
+                .collect();
+            corners.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let radius = length(points[i]) as f64;
+            let cell = corners
+                .into_iter()
+                .map(|(_, c)| c.map(|v| (v * radius) as f32))
+                .collect();
+            Some((i, cell))
+        })
+        .collect()
+}
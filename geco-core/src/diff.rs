@@ -0,0 +1,128 @@
+// klyja/geco-core/src/diff.rs
+// What changed between two versions of a document, feature by feature. Features and
+// points are matched by ID, so renumbered or reordered items aren't reported as changed.
+use crate::interpolation::point_keyframes;
+use crate::protobuf_gen::{AnimatedPoint, MapAnimation, Polygon};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct DocumentDiff {
+    pub name_changed: bool,
+    pub total_frames_before: i32,
+    pub total_frames_after: i32,
+    pub added_features: Vec<String>,
+    pub removed_features: Vec<String>,
+    pub changed_features: Vec<FeatureChange>,
+    // Point, data, transform, grid and camera keyframes in the whole document
+    pub keyframes_before: usize,
+    pub keyframes_after: usize,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct FeatureChange {
+    pub feature_id: String,
+    pub points_added: usize,
+    pub points_removed: usize,
+    pub points_changed: usize, // Kept points that moved, got keyframes or changed timing
+    pub properties_changed: bool,
+    pub keyframes_before: usize,
+    pub keyframes_after: usize,
+}
+
+/// Compares two documents. Added and changed features are listed in `after`'s order,
+/// removed ones in `before`'s.
+pub fn diff_documents(before: &MapAnimation, after: &MapAnimation) -> DocumentDiff {
+    let before_features: HashMap<&str, &Polygon> = before
+        .polygons
+        .iter()
+        .map(|polygon| (polygon.polygon_id.as_str(), polygon))
+        .collect();
+    let after_features: HashMap<&str, &Polygon> = after
+        .polygons
+        .iter()
+        .map(|polygon| (polygon.polygon_id.as_str(), polygon))
+        .collect();
+
+    let mut diff = DocumentDiff {
+        name_changed: before.name != after.name,
+        total_frames_before: before.total_frames,
+        total_frames_after: after.total_frames,
+        keyframes_before: document_keyframes(before),
+        keyframes_after: document_keyframes(after),
+        ..Default::default()
+    };
+    for polygon in &after.polygons {
+        match before_features.get(polygon.polygon_id.as_str()) {
+            None => diff.added_features.push(polygon.polygon_id.clone()),
+            Some(&old) if old != polygon => {
+                diff.changed_features.push(feature_change(old, polygon))
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed_features = before
+        .polygons
+        .iter()
+        .filter(|polygon| !after_features.contains_key(polygon.polygon_id.as_str()))
+        .map(|polygon| polygon.polygon_id.clone())
+        .collect();
+    diff
+}
+
+fn feature_change(before: &Polygon, after: &Polygon) -> FeatureChange {
+    let before_points: HashMap<&str, &AnimatedPoint> = before
+        .points
+        .iter()
+        .map(|point| (point.point_id.as_str(), point))
+        .collect();
+    let mut change = FeatureChange {
+        feature_id: after.polygon_id.clone(),
+        properties_changed: before.properties != after.properties,
+        keyframes_before: feature_keyframes(before),
+        keyframes_after: feature_keyframes(after),
+        ..Default::default()
+    };
+    let mut kept = 0;
+    for point in &after.points {
+        match before_points.get(point.point_id.as_str()) {
+            None => change.points_added += 1,
+            Some(&old) => {
+                kept += 1;
+                if old != point {
+                    change.points_changed += 1;
+                }
+            }
+        }
+    }
+    change.points_removed = before.points.len() - kept;
+    change
+}
+
+fn feature_keyframes(polygon: &Polygon) -> usize {
+    polygon
+        .points
+        .iter()
+        .map(|point| point_keyframes(point).len() + point.scalar_keyframes.len())
+        .sum::<usize>()
+        + polygon.transform_keyframes.len()
+}
+
+fn document_keyframes(animation: &MapAnimation) -> usize {
+    animation
+        .polygons
+        .iter()
+        .map(feature_keyframes)
+        .sum::<usize>()
+        + animation
+            .grid_layers
+            .iter()
+            .map(|layer| layer.keyframes.len())
+            .sum::<usize>()
+        + animation.camera_keyframes.len()
+        + animation
+            .data_series
+            .iter()
+            .map(|series| series.keyframes.len())
+            .sum::<usize>()
+}
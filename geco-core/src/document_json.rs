@@ -0,0 +1,15 @@
+// klyja/geco-core/src/document_json.rs
+// Human-readable JSON form of the protobuf document, for hand-editing and keeping
+// animations under version control. Field names match the .proto file; fields left
+// out of the JSON take their protobuf defaults. The serde derives themselves are
+// generated in klyja-proto.
+use crate::protobuf_gen::MapAnimation;
+
+pub fn to_json(animation: &MapAnimation) -> Result<String, String> {
+    serde_json::to_string_pretty(animation)
+        .map_err(|e| format!("Failed to serialize animation to JSON: {}", e))
+}
+
+pub fn from_json(json: &str) -> Result<MapAnimation, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid animation JSON: {}", e))
+}
@@ -0,0 +1,166 @@
+// klyja/geco-core/src/editing.rs
+// Pure helpers used by the editing APIs on `Geco`.
+use crate::geometry::snap_to_grid;
+use crate::interpolation::is_present_at_frame;
+pub use crate::protobuf_gen::{feature_type_name, parse_feature_type};
+use crate::protobuf_gen::{AnimatedPoint, FeatureType, Point, Polygon, ScalarKeyframe, Vector};
+
+/// Adds a movement vector to a position in place.
+pub fn apply_movement(position: &mut Point, movement: &Vector) {
+    position.x += movement.dx;
+    position.y += movement.dy;
+    position.z = Some(position.z.unwrap_or(0.0) + movement.dz.unwrap_or(0.0));
+}
+
+/// Translates a point's initial position; its movements are relative, so they follow along.
+pub fn translate_point(point: &mut AnimatedPoint, dx: f32, dy: f32, dz: f32) {
+    if let Some(position) = point.initial_position.as_mut() {
+        apply_movement(
+            position,
+            &Vector {
+                dx,
+                dy,
+                dz: Some(dz),
+            },
+        );
+    }
+}
+
+/// Snaps a point's initial position to a lat/lon grid of `degrees` spacing.
+pub fn snap_point(point: &mut AnimatedPoint, degrees: f32) {
+    if let Some(position) = point.initial_position.as_mut() {
+        let [x, y, z] = snap_to_grid([position.x, position.y, position.z.unwrap_or(0.0)], degrees);
+        *position = Point { x, y, z: Some(z) };
+    }
+}
+
+/// Shifts a point's keyframes along the timeline by `offset` frames.
+///
+/// A positive offset delays the motion by padding the start with empty movements.
+/// A negative offset starts the motion earlier: the skipped movements are folded
+/// into the initial position so the point lands where it would have been.
+pub fn shift_point_keyframes(point: &mut AnimatedPoint, offset: i32) {
+    if offset > 0 {
+        let padding = std::iter::repeat_n(Vector::default(), offset as usize);
+        point.movements.splice(0..0, padding);
+    } else if offset < 0 {
+        let skipped = (offset.unsigned_abs() as usize).min(point.movements.len());
+        let folded: Vec<Vector> = point.movements.drain(..skipped).collect();
+        if let Some(position) = point.initial_position.as_mut() {
+            for movement in &folded {
+                apply_movement(position, movement);
+            }
+        }
+    }
+}
+
+/// Generates a point ID unique within the polygon, following the `<polygon>-pt<n>` scheme.
+/// Points can be removed, so the point count alone may collide with an existing ID.
+pub fn next_point_id(polygon: &Polygon) -> String {
+    let mut index = polygon.points.len();
+    loop {
+        let candidate = format!("{}-pt{}", polygon.polygon_id, index);
+        if !polygon.points.iter().any(|p| p.point_id == candidate) {
+            return candidate;
+        }
+        index += 1;
+    }
+}
+
+/// Generates a feature ID not yet used in `polygons`: `base` itself if free,
+/// otherwise `base` with the first free numeric suffix (`<base>-2`, `<base>-3`, ...).
+pub fn unique_feature_id(polygons: &[Polygon], base: &str) -> String {
+    let taken = |id: &str| polygons.iter().any(|p| p.polygon_id == id);
+    if !taken(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|candidate| !taken(candidate))
+        .expect("unbounded suffixes")
+}
+
+/// Maps an index into the shape's point list at `frame` (only points present then)
+/// to an index into the polygon's stored point list. `index` may equal the number of
+/// present points, meaning "after the last one". Returns None if out of range.
+pub fn storage_index_at_frame(polygon: &Polygon, frame: i32, index: usize) -> Option<usize> {
+    let present: Vec<usize> = polygon
+        .points
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| is_present_at_frame(p, frame))
+        .map(|(i, _)| i)
+        .collect();
+    match present.get(index) {
+        Some(storage_index) => Some(*storage_index),
+        None if index == present.len() => Some(present.last().map_or(0, |last| last + 1)),
+        None => None,
+    }
+}
+
+/// Converts a feature to another type, adjusting its points so the drawn shape is kept.
+///
+/// A polygon becoming a polyline gets an explicit closing point (a copy of the first
+/// point); a polyline becoming a polygon drops such a closing point if it has one,
+/// since polygons close implicitly.
+pub fn convert_feature_type(
+    polygon: &mut Polygon,
+    new_type: FeatureType,
+) -> Result<(), String> {
+    let current = polygon.feature_type();
+    if current == new_type {
+        return Ok(());
+    }
+
+    let mut points = polygon.points.clone();
+    match (current, new_type) {
+        (FeatureType::Polygon, FeatureType::Polyline) => {
+            if let Some(first) = points.first() {
+                let mut closing = first.clone();
+                closing.point_id = next_point_id(polygon);
+                points.push(closing);
+            }
+        }
+        (FeatureType::Polyline, FeatureType::Polygon)
+            if points.len() > 3 && is_closing_point(&points[0], &points[points.len() - 1]) =>
+        {
+            points.pop();
+        }
+        _ => {}
+    }
+
+    let (minimum, maximum) = match new_type {
+        FeatureType::Polygon => (3, usize::MAX),
+        FeatureType::Polyline => (2, usize::MAX),
+        FeatureType::Point => (1, 1),
+    };
+    if points.len() < minimum || points.len() > maximum {
+        return Err(format!(
+            "Cannot convert feature '{}' with {} points to a {}",
+            polygon.polygon_id,
+            points.len(),
+            feature_type_name(new_type)
+        ));
+    }
+
+    polygon.points = points;
+    polygon.set_feature_type(new_type);
+    Ok(())
+}
+
+/// Whether `last` retraces `first` for the whole animation, i.e. closes the line.
+fn is_closing_point(first: &AnimatedPoint, last: &AnimatedPoint) -> bool {
+    first.initial_position == last.initial_position
+        && first.movements == last.movements
+        && first.start_frame == last.start_frame
+        && first.end_frame == last.end_frame
+}
+
+/// Sets a point's data value at `frame`, replacing an existing keyframe at that frame.
+pub fn upsert_scalar_keyframe(point: &mut AnimatedPoint, frame: i32, value: f32) {
+    let keyframes = &mut point.scalar_keyframes;
+    match keyframes.binary_search_by_key(&frame, |k| k.frame) {
+        Ok(index) => keyframes[index].value = value,
+        Err(index) => keyframes.insert(index, ScalarKeyframe { frame, value }),
+    }
+}
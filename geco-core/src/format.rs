@@ -0,0 +1,48 @@
+// klyja/geco-core/src/format.rs
+// Document format versions. A change to the schema that old documents can't simply
+// decode into adds a step here, converting documents from the version before it, so
+// saves made before the change keep loading. The backend runs the same steps when
+// loading and when rewriting stored documents, and geco when opening files.
+use crate::protobuf_gen::MapAnimation;
+
+/// Converts a document from the version it is registered at to the next one.
+pub(crate) type Upgrade = fn(&mut MapAnimation) -> Result<(), String>;
+
+/// Steps from each version to the next: `UPGRADES[0]` takes version 0 documents to
+/// version 1, and so on. Version 0 is every document written before versions were
+/// recorded; no change has needed converting since.
+const UPGRADES: &[Upgrade] = &[];
+
+/// The version documents are written in today.
+pub const CURRENT_FORMAT_VERSION: u32 = UPGRADES.len() as u32;
+
+/// Brings a document up to the current version, returning whether it changed.
+/// Documents from a newer version than this build knows are refused.
+pub fn upgrade(animation: &mut MapAnimation) -> Result<bool, String> {
+    upgrade_with(animation, UPGRADES)
+}
+
+/// `upgrade` with the steps given, so they can be tried out before registering them.
+pub(crate) fn upgrade_with(
+    animation: &mut MapAnimation,
+    steps: &[Upgrade],
+) -> Result<bool, String> {
+    let from = animation.format_version;
+    let Some(pending) = steps.get(from as usize..) else {
+        return Err(format!(
+            "The document is in format version {}, newer than the {} this version of Klyja reads",
+            from,
+            steps.len()
+        ));
+    };
+    for (step, version) in pending.iter().zip(from..) {
+        step(animation).map_err(|err| {
+            format!(
+                "Upgrading the document from format version {} failed: {}",
+                version, err
+            )
+        })?;
+        animation.format_version = version + 1;
+    }
+    Ok(!pending.is_empty())
+}
@@ -0,0 +1,496 @@
+// klyja/geco-core/src/geojson.rs
+// GeoJSON (RFC 7946) export of features at one frame, and import of static features.
+//
+// Spherical shapes can't be written as naive lat/lon lists: an edge crossing the
+// antimeridian would be drawn the long way round the map, and a polygon around a
+// pole has no closed lat/lon outline at all. Lines are therefore split where they
+// cross the antimeridian, and polygon rings are cut into pieces that each stay within
+// [-180, 180] longitude, closed along the antimeridian and, for polar polygons,
+// along the pole.
+use crate::geometry::{from_lat_lon, to_lat_lon, Vec3};
+use crate::protobuf_gen::{AnimatedPoint, FeatureType, Point, Polygon};
+use crate::transform;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+
+type LonLat = [f32; 2];
+
+/// Length of the outline of the [-180, 180] x [-90, 90] map rectangle, measured in
+/// degrees counter-clockwise from its south-east corner (see `perimeter_position`).
+const PERIMETER: f32 = 1080.0;
+
+/// Map rectangle corners by perimeter position: north-east, north-west, south-west,
+/// south-east.
+const CORNERS: [(f32, LonLat); 4] = [
+    (180.0, [180.0, 90.0]),
+    (540.0, [-180.0, 90.0]),
+    (720.0, [-180.0, -90.0]),
+    (1080.0, [180.0, -90.0]),
+];
+
+/// GeoJSON geometry for a feature given its resolved points, or None if it has
+/// too few points for its type.
+pub fn geometry(feature_type: FeatureType, points: &[Vec3]) -> Option<Value> {
+    let lon_lats: Vec<LonLat> = points
+        .iter()
+        .filter_map(|p| to_lat_lon(*p).map(|(lat, lon)| [lon, lat]))
+        .collect();
+    match feature_type {
+        FeatureType::Point => {
+            let [point] = lon_lats.as_slice() else {
+                return None;
+            };
+            Some(json!({ "type": "Point", "coordinates": point }))
+        }
+        FeatureType::Polyline => {
+            if lon_lats.len() < 2 {
+                return None;
+            }
+            let parts = split_line(points);
+            Some(match parts.as_slice() {
+                [line] => json!({ "type": "LineString", "coordinates": line }),
+                _ => json!({ "type": "MultiLineString", "coordinates": parts }),
+            })
+        }
+        FeatureType::Polygon => {
+            if lon_lats.len() < 3 {
+                return None;
+            }
+            let pieces: Vec<Vec<Vec<LonLat>>> = split_ring(points)
+                .into_iter()
+                .map(|ring| vec![ring])
+                .collect();
+            match pieces.as_slice() {
+                [] => None,
+                [polygon] => Some(json!({ "type": "Polygon", "coordinates": polygon })),
+                _ => Some(json!({ "type": "MultiPolygon", "coordinates": pieces })),
+            }
+        }
+    }
+}
+
+/// Wraps a geometry and its properties into a GeoJSON feature.
+pub fn feature(id: &str, geometry: Value, properties: Map<String, Value>) -> Value {
+    json!({
+        "type": "Feature",
+        "id": id,
+        "geometry": geometry,
+        "properties": properties,
+    })
+}
+
+pub fn feature_collection(features: Vec<Value>) -> Value {
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+/// The features at `frame` as a FeatureCollection, in draw order, with their
+/// properties. Features without enough points at that frame are left out.
+pub fn frame_collection(polygons: &[Polygon], frame: i32) -> Value {
+    let mut in_draw_order: Vec<&Polygon> = polygons.iter().collect();
+    in_draw_order.sort_by_key(|p| p.draw_order);
+    let features = in_draw_order
+        .into_iter()
+        .filter_map(|polygon| {
+            let points: Vec<Vec3> = transform::world_points_at_frame(polygons, polygon, frame)
+                .into_iter()
+                .map(|(_, xyz)| xyz)
+                .collect();
+            let geometry = geometry(polygon.feature_type(), &points)?;
+            let properties = polygon
+                .properties
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone().into()))
+                .collect();
+            Some(feature(&polygon.polygon_id, geometry, properties))
+        })
+        .collect();
+    feature_collection(features)
+}
+
+/// Reads features from GeoJSON: a FeatureCollection, a single Feature or a bare
+/// geometry. Points, lines and polygon outer rings become point, polyline and polygon
+/// features with their points at `radius`, in file order; multi-part geometries
+/// become one feature per part, and polygon holes are dropped. Feature IDs are kept
+/// where given and unique. String properties are kept as they are, others as JSON.
+pub fn import_features(value: &Value, radius: f32) -> Result<Vec<Polygon>, String> {
+    let wrapped;
+    let features: Vec<&Value> = match value["type"].as_str() {
+        Some("FeatureCollection") => value["features"]
+            .as_array()
+            .ok_or("FeatureCollection has no features array")?
+            .iter()
+            .collect(),
+        Some("Feature") => vec![value],
+        Some(_) => {
+            wrapped = json!({ "type": "Feature", "geometry": value });
+            vec![&wrapped]
+        }
+        None => return Err("Not GeoJSON: no type given".to_string()),
+    };
+
+    let mut polygons = vec![];
+    let mut used_ids = HashSet::new();
+    for (index, feature) in features.into_iter().enumerate() {
+        let mut parts = vec![];
+        if !feature["geometry"].is_null() {
+            geometry_parts(&feature["geometry"], &mut parts)
+                .map_err(|err| format!("Feature {}: {}", index, err))?;
+        }
+        let base_id = match &feature["id"] {
+            Value::String(id) if !id.is_empty() => id.clone(),
+            Value::Number(id) => id.to_string(),
+            _ => format!("geojson-{}", index),
+        };
+        let properties: HashMap<String, String> = feature["properties"]
+            .as_object()
+            .map(|properties| {
+                properties
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            Value::String(text) => text.clone(),
+                            other => other.to_string(),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let part_count = parts.len();
+        for (part_index, (feature_type, lon_lats)) in parts.into_iter().enumerate() {
+            let mut polygon_id = if part_count > 1 {
+                format!("{}-{}", base_id, part_index)
+            } else {
+                base_id.clone()
+            };
+            while !used_ids.insert(polygon_id.clone()) {
+                polygon_id.push_str("-dup");
+            }
+            let points = lon_lats
+                .iter()
+                .enumerate()
+                .map(|(point_index, &[lon, lat])| {
+                    let [x, y, z] = from_lat_lon(lat, lon, radius);
+                    AnimatedPoint {
+                        point_id: format!("{}-pt{}", polygon_id, point_index),
+                        initial_position: Some(Point { x, y, z: Some(z) }),
+                        movements: vec![],
+                        end_frame: None,
+                        start_frame: None,
+                        scalar_keyframes: vec![],
+                        pinned_feature_id: None,
+                    }
+                })
+                .collect();
+            polygons.push(Polygon {
+                draw_order: polygons.len() as i32,
+                polygon_id,
+                points,
+                properties: properties.clone(),
+                feature_type: feature_type as i32,
+                locked: false,
+                transform_keyframes: vec![],
+                parent_id: None,
+            });
+        }
+    }
+    Ok(polygons)
+}
+
+/// Appends the parts of a GeoJSON geometry, each a feature type and its points.
+fn geometry_parts(
+    geometry: &Value,
+    parts: &mut Vec<(FeatureType, Vec<LonLat>)>,
+) -> Result<(), String> {
+    let coordinates = &geometry["coordinates"];
+    let list = |value: &Value| -> Result<Vec<Value>, String> {
+        value
+            .as_array()
+            .cloned()
+            .ok_or_else(|| "coordinates are not an array".to_string())
+    };
+    match geometry["type"].as_str() {
+        Some("Point") => parts.push((FeatureType::Point, vec![position(coordinates)?])),
+        Some("MultiPoint") => {
+            for point in list(coordinates)? {
+                parts.push((FeatureType::Point, vec![position(&point)?]));
+            }
+        }
+        Some("LineString") => parts.push((FeatureType::Polyline, line(coordinates, 2)?)),
+        Some("MultiLineString") => {
+            for coordinates in list(coordinates)? {
+                parts.push((FeatureType::Polyline, line(&coordinates, 2)?));
+            }
+        }
+        Some("Polygon") => parts.push((FeatureType::Polygon, outer_ring(coordinates)?)),
+        Some("MultiPolygon") => {
+            for coordinates in list(coordinates)? {
+                parts.push((FeatureType::Polygon, outer_ring(&coordinates)?));
+            }
+        }
+        Some("GeometryCollection") => {
+            for geometry in list(&geometry["geometries"])? {
+                geometry_parts(&geometry, parts)?;
+            }
+        }
+        Some(other) => return Err(format!("unsupported geometry type '{}'", other)),
+        None => return Err("geometry has no type".to_string()),
+    }
+    Ok(())
+}
+
+/// A GeoJSON position as lon/lat, ignoring any altitude.
+fn position(value: &Value) -> Result<LonLat, String> {
+    let (Some(lon), Some(lat)) = (value[0].as_f64(), value[1].as_f64()) else {
+        return Err(format!("{} is not a [longitude, latitude] position", value));
+    };
+    if !(-180.0..=180.0).contains(&lon) || !(-90.0..=90.0).contains(&lat) {
+        return Err(format!("position {} is off the globe", value));
+    }
+    Ok([lon as f32, lat as f32])
+}
+
+fn line(value: &Value, min_points: usize) -> Result<Vec<LonLat>, String> {
+    let points = value
+        .as_array()
+        .ok_or_else(|| "coordinates are not an array".to_string())?
+        .iter()
+        .map(position)
+        .collect::<Result<Vec<_>, _>>()?;
+    if points.len() < min_points {
+        return Err(format!("needs at least {} positions", min_points));
+    }
+    Ok(points)
+}
+
+/// A polygon's outer ring without its closing position, which features leave implicit.
+fn outer_ring(value: &Value) -> Result<Vec<LonLat>, String> {
+    let mut ring = line(&value[0], 3)?;
+    if ring.len() > 3 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    Ok(ring)
+}
+
+/// Where the great-circle edge from `a` to `b` crosses the antimeridian, as the
+/// latitude of the crossing, or None if it doesn't cross.
+fn antimeridian_crossing(a: Vec3, b: Vec3) -> Option<f32> {
+    let ((_, lon_a), (_, lon_b)) = (to_lat_lon(a)?, to_lat_lon(b)?);
+    if (lon_b - lon_a).abs() <= 180.0 || a[2] == b[2] {
+        return None;
+    }
+    // The antimeridian lies in the z = 0 plane, so the chord meets it where z vanishes;
+    // projecting that point onto the sphere gives the point on the arc.
+    let t = a[2] / (a[2] - b[2]);
+    let crossing = [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+    to_lat_lon(crossing).map(|(lat, _)| lat)
+}
+
+/// Splits an open line into parts that don't cross the antimeridian.
+fn split_line(points: &[Vec3]) -> Vec<Vec<LonLat>> {
+    let mut parts = vec![];
+    let mut current: Vec<LonLat> = vec![];
+    for (index, point) in points.iter().enumerate() {
+        let Some((lat, lon)) = to_lat_lon(*point) else {
+            continue;
+        };
+        if let Some(previous) = index.checked_sub(1).map(|i| points[i]) {
+            if let Some(crossing_lat) = antimeridian_crossing(previous, *point) {
+                // Leave through the side we are on, re-enter from the other
+                let exit_lon = if lon < 0.0 { 180.0 } else { -180.0 };
+                current.push([exit_lon, crossing_lat]);
+                parts.push(std::mem::take(&mut current));
+                current.push([-exit_lon, crossing_lat]);
+            }
+        }
+        current.push([lon, lat]);
+    }
+    if current.len() > 1 {
+        parts.push(current);
+    }
+    parts.retain(|part| part.len() > 1);
+    parts
+}
+
+/// A stretch of ring between two antimeridian crossings.
+struct Segment {
+    points: Vec<LonLat>,
+    start: f32, // Perimeter positions of the first and last point
+    end: f32,
+}
+
+/// Splits a closed ring into closed, counter-clockwise lon/lat rings that stay within
+/// [-180, 180] longitude. Rings around a pole are closed through the pole on the side
+/// the ring's vertices lean towards.
+fn split_ring(points: &[Vec3]) -> Vec<Vec<LonLat>> {
+    let mut ring: Vec<Vec3> = points.to_vec();
+    let lon_lats: Vec<LonLat> = ring
+        .iter()
+        .filter_map(|p| to_lat_lon(*p).map(|(lat, lon)| [lon, lat]))
+        .collect();
+    if lon_lats.len() != ring.len() {
+        return vec![]; // A point at the globe center has no lat/lon
+    }
+
+    // Orient so the inside is on the left when walking the ring in lon/lat
+    let drift = longitude_drift(&lon_lats);
+    let should_reverse = if drift.abs() > 180.0 {
+        // Polar: heading east keeps the north pole on the left
+        let north_inside = ring.iter().map(|p| p[1]).sum::<f32>() >= 0.0;
+        (drift > 0.0) != north_inside
+    } else {
+        unwrapped_signed_area(&lon_lats) < 0.0
+    };
+    if should_reverse {
+        ring.reverse();
+    }
+
+    let segments = ring_segments(&ring);
+    if segments.is_empty() {
+        let mut closed: Vec<LonLat> = ring
+            .iter()
+            .filter_map(|p| to_lat_lon(*p).map(|(lat, lon)| [lon, lat]))
+            .collect();
+        closed.push(closed[0]);
+        return vec![closed];
+    }
+    join_segments(segments)
+}
+
+/// Total longitude travelled around the ring, taking the short way along each edge:
+/// 0 for ordinary rings, +-360 for rings around a pole.
+fn longitude_drift(lon_lats: &[LonLat]) -> f32 {
+    (0..lon_lats.len())
+        .map(|i| {
+            let delta = lon_lats[(i + 1) % lon_lats.len()][0] - lon_lats[i][0];
+            if delta > 180.0 {
+                delta - 360.0
+            } else if delta < -180.0 {
+                delta + 360.0
+            } else {
+                delta
+            }
+        })
+        .sum()
+}
+
+/// Signed lon/lat area after unwrapping longitudes, positive for counter-clockwise.
+fn unwrapped_signed_area(lon_lats: &[LonLat]) -> f32 {
+    let mut unwrapped = Vec::with_capacity(lon_lats.len());
+    let mut offset = 0.0;
+    for (index, [lon, lat]) in lon_lats.iter().enumerate() {
+        if let Some([previous_lon, _]) = index.checked_sub(1).map(|i| lon_lats[i]) {
+            if lon - previous_lon > 180.0 {
+                offset -= 360.0;
+            } else if lon - previous_lon < -180.0 {
+                offset += 360.0;
+            }
+        }
+        unwrapped.push([lon + offset, *lat]);
+    }
+    (0..unwrapped.len())
+        .map(|i| {
+            let (p, q) = (unwrapped[i], unwrapped[(i + 1) % unwrapped.len()]);
+            p[0] * q[1] - q[0] * p[1]
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Cuts the ring at every antimeridian crossing. Empty if it never crosses.
+fn ring_segments(ring: &[Vec3]) -> Vec<Segment> {
+    let n = ring.len();
+    let Some(first_crossing) =
+        (0..n).find(|&i| antimeridian_crossing(ring[i], ring[(i + 1) % n]).is_some())
+    else {
+        return vec![];
+    };
+    // Side of the antimeridian an edge leaves from, given its start point
+    let exit_lon = |from: Vec3| match to_lat_lon(from) {
+        Some((_, lon)) if lon > 0.0 => 180.0,
+        _ => -180.0,
+    };
+
+    // Walk once around the ring, starting with the first crossing edge
+    let mut segments = vec![];
+    let mut current: Vec<LonLat> = vec![];
+    for step in 0..n {
+        let from = ring[(first_crossing + step) % n];
+        let to = ring[(first_crossing + step + 1) % n];
+        if let Some(crossing_lat) = antimeridian_crossing(from, to) {
+            if !current.is_empty() {
+                current.push([exit_lon(from), crossing_lat]);
+                segments.push(std::mem::take(&mut current));
+            }
+            current.push([-exit_lon(from), crossing_lat]);
+        }
+        if let Some((lat, lon)) = to_lat_lon(to) {
+            current.push([lon, lat]);
+        }
+    }
+    // The last segment ends on the first crossing edge, where the walk began
+    let from = ring[first_crossing];
+    let crossing_lat = antimeridian_crossing(from, ring[(first_crossing + 1) % n]).unwrap_or(0.0);
+    current.push([exit_lon(from), crossing_lat]);
+    segments.push(current);
+
+    segments
+        .into_iter()
+        .map(|points| Segment {
+            start: perimeter_position(points[0]),
+            end: perimeter_position(points[points.len() - 1]),
+            points,
+        })
+        .collect()
+}
+
+/// Position of a point on the antimeridian along the map outline, walked
+/// counter-clockwise: up the east edge (0..180), then down the west edge (540..720).
+fn perimeter_position([lon, lat]: LonLat) -> f32 {
+    if lon > 0.0 {
+        lat + 90.0
+    } else {
+        540.0 + (90.0 - lat)
+    }
+}
+
+/// Joins segments into closed rings: from each segment's end, follow the map outline
+/// counter-clockwise (keeping the inside on the left) to the next segment start.
+fn join_segments(segments: Vec<Segment>) -> Vec<Vec<LonLat>> {
+    let mut used = vec![false; segments.len()];
+    let mut rings = vec![];
+    while let Some(first) = used.iter().position(|u| !u) {
+        let mut ring: Vec<LonLat> = vec![];
+        let mut current = first;
+        loop {
+            used[current] = true;
+            ring.extend(&segments[current].points);
+            let end = segments[current].end;
+            let distance = |to: f32| (to - end).rem_euclid(PERIMETER);
+            // Only the first segment of this ring or unused ones can come next
+            let Some(next) = (0..segments.len())
+                .filter(|&i| i == first || !used[i])
+                .min_by(|&a, &b| {
+                    distance(segments[a].start).total_cmp(&distance(segments[b].start))
+                })
+            else {
+                break;
+            };
+            // Map corners passed on the way, in walking order
+            let mut corners: Vec<(f32, LonLat)> = CORNERS
+                .iter()
+                .map(|&(position, corner)| (distance(position), corner))
+                .filter(|&(d, _)| d > 0.0 && d < distance(segments[next].start))
+                .collect();
+            corners.sort_by(|a, b| a.0.total_cmp(&b.0));
+            ring.extend(corners.into_iter().map(|(_, corner)| corner));
+            if next == first {
+                break;
+            }
+            current = next;
+        }
+        ring.push(ring[0]);
+        rings.push(ring);
+    }
+    rings
+}
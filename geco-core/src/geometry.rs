@@ -0,0 +1,303 @@
+// klyja/geco-core/src/geometry.rs
+// Spherical geometry helpers. Positions are Cartesian points on (or near) the globe,
+// with +y pointing to the north pole, matching the frontend's three.js scene.
+
+pub type Vec3 = [f32; 3];
+
+pub fn dot(a: Vec3, b: Vec3) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn length(a: Vec3) -> f32 {
+    dot(a, a).sqrt()
+}
+
+/// Unit vector in the direction of `a`, or None for the zero vector.
+pub fn normalize(a: Vec3) -> Option<Vec3> {
+    let len = length(a);
+    if len > f32::EPSILON {
+        Some([a[0] / len, a[1] / len, a[2] / len])
+    } else {
+        None
+    }
+}
+
+/// Latitude and longitude in degrees. Longitude -180 lies on the -x axis and grows
+/// eastwards through +z, the same layout three.js uses for SphereGeometry UVs.
+pub fn to_lat_lon(p: Vec3) -> Option<(f32, f32)> {
+    let [x, y, z] = normalize(p)?;
+    let lat = y.clamp(-1.0, 1.0).asin().to_degrees();
+    let lon = z.atan2(-x).to_degrees() - 180.0;
+    let lon = if lon < -180.0 { lon + 360.0 } else { lon };
+    Some((lat, lon))
+}
+
+/// Inverse of `to_lat_lon`, placing the point at `radius` from the center.
+pub fn from_lat_lon(lat: f32, lon: f32, radius: f32) -> Vec3 {
+    let theta = (90.0 - lat).to_radians(); // Polar angle from the north pole
+    let phi = (lon + 180.0).to_radians();
+    [
+        -radius * phi.cos() * theta.sin(),
+        radius * theta.cos(),
+        radius * phi.sin() * theta.sin(),
+    ]
+}
+
+/// Rounds a point's latitude and longitude to the nearest multiple of `degrees`,
+/// keeping its distance from the center. Points at the center are left alone.
+pub fn snap_to_grid(p: Vec3, degrees: f32) -> Vec3 {
+    let Some((lat, lon)) = to_lat_lon(p) else {
+        return p;
+    };
+    let snap = |angle: f32| (angle / degrees).round() * degrees;
+    from_lat_lon(snap(lat).clamp(-90.0, 90.0), snap(lon), length(p))
+}
+
+/// Equirectangular texture coordinates: u runs west to east from -180 longitude,
+/// v runs from the south pole (0) to the north pole (1).
+pub fn equirectangular_uv(p: Vec3) -> [f32; 2] {
+    match to_lat_lon(p) {
+        Some((lat, lon)) => [(lon + 180.0) / 360.0, (lat + 90.0) / 180.0],
+        None => [0.0, 0.0],
+    }
+}
+
+/// Triangulates a simple spherical polygon by ear clipping in the tangent plane at
+/// its centroid. Returns triangles as indices into `points`, wound counter-clockwise
+/// when seen from outside the sphere. Polygons spanning more than a hemisphere
+/// cannot be projected this way and yield no triangles.
+pub fn triangulate(points: &[Vec3]) -> Vec<u32> {
+    if points.len() < 3 {
+        return vec![];
+    }
+    let Some(plane) = TangentPlane::for_polygon(points) else {
+        return vec![];
+    };
+    let projected: Vec<[f32; 2]> = points.iter().filter_map(|p| plane.project(*p)).collect();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if signed_area(&projected, &remaining) < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::with_capacity((points.len() - 2) * 3);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            is_convex(projected[a], projected[b], projected[c])
+                && !remaining.iter().any(|&other| {
+                    other != a
+                        && other != b
+                        && other != c
+                        && in_triangle(projected[other], projected[a], projected[b], projected[c])
+                })
+        });
+        let Some(i) = ear else {
+            // Self-intersecting or degenerate input; return what was clipped so far
+            return triangles;
+        };
+        let (a, b, c) = (
+            remaining[(i + n - 1) % n],
+            remaining[i],
+            remaining[(i + 1) % n],
+        );
+        triangles.extend([a as u32, b as u32, c as u32]);
+        remaining.remove(i);
+    }
+    triangles.extend(remaining.iter().map(|&i| i as u32));
+    triangles
+}
+
+/// Whether `p` lies inside the spherical polygon (even-odd rule), using the same
+/// tangent-plane projection as `triangulate`. Always false for polygons spanning
+/// more than a hemisphere.
+pub fn contains_point(polygon: &[Vec3], p: Vec3) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+    let Some(plane) = TangentPlane::for_polygon(polygon) else {
+        return false;
+    };
+    let Some([x, y]) = plane.project(p) else {
+        return false;
+    };
+    let projected: Vec<[f32; 2]> = polygon.iter().filter_map(|v| plane.project(*v)).collect();
+    let mut inside = false;
+    for i in 0..projected.len() {
+        let (a, b) = (projected[i], projected[(i + 1) % projected.len()]);
+        if (a[1] > y) != (b[1] > y) && x < a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// The point on an outline (a ring if `closed`) nearest to `p`, pushed out to the
+/// same distance from the center as `p` so it stays on the globe surface.
+/// None for an empty outline.
+pub fn nearest_point_on_outline(outline: &[Vec3], closed: bool, p: Vec3) -> Option<Vec3> {
+    let sub = |a: Vec3, b: Vec3| [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    let edge_count = match outline.len() {
+        0 => return None,
+        1 => 0,
+        n if closed => n,
+        n => n - 1,
+    };
+    let mut nearest = outline[0];
+    for i in 0..edge_count {
+        let (a, b) = (outline[i], outline[(i + 1) % outline.len()]);
+        let ab = sub(b, a);
+        let len_sq = dot(ab, ab);
+        let t = if len_sq > f32::EPSILON {
+            (dot(sub(p, a), ab) / len_sq).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let candidate = [a[0] + ab[0] * t, a[1] + ab[1] * t, a[2] + ab[2] * t];
+        if length(sub(candidate, p)) < length(sub(nearest, p)) {
+            nearest = candidate;
+        }
+    }
+    Some(normalize(nearest).map_or(nearest, |direction| direction.map(|c| c * length(p))))
+}
+
+/// Plane touching the sphere at a polygon's centroid direction. Gnomonic projection
+/// onto it keeps great-circle edges straight.
+struct TangentPlane {
+    normal: Vec3,
+    e1: Vec3,
+    e2: Vec3,
+}
+
+impl TangentPlane {
+    /// None if the polygon doesn't fit in the hemisphere around its centroid.
+    fn for_polygon(points: &[Vec3]) -> Option<Self> {
+        let normal = normalize(points.iter().fold([0.0; 3], |acc, p| {
+            let unit = normalize(*p).unwrap_or([0.0; 3]);
+            [acc[0] + unit[0], acc[1] + unit[1], acc[2] + unit[2]]
+        }))?;
+        if points.iter().any(|p| dot(*p, normal) <= 0.0) {
+            return None;
+        }
+        let helper = if normal[1].abs() < 0.9 {
+            [0.0, 1.0, 0.0]
+        } else {
+            [1.0, 0.0, 0.0]
+        };
+        let e1 = normalize(cross(helper, normal))?;
+        let e2 = cross(normal, e1);
+        Some(TangentPlane { normal, e1, e2 })
+    }
+
+    /// None for points on the far hemisphere, which have no projection.
+    fn project(&self, p: Vec3) -> Option<[f32; 2]> {
+        let height = dot(p, self.normal);
+        if height <= 0.0 {
+            return None;
+        }
+        Some([dot(p, self.e1) / height, dot(p, self.e2) / height])
+    }
+}
+
+fn signed_area(points: &[[f32; 2]], order: &[usize]) -> f32 {
+    let n = order.len();
+    (0..n)
+        .map(|i| {
+            let (p, q) = (points[order[i]], points[order[(i + 1) % n]]);
+            p[0] * q[1] - q[0] * p[1]
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+fn cross_2d(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+fn is_convex(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    cross_2d(a, b, c) > 0.0
+}
+
+fn in_triangle(p: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    cross_2d(a, b, p) >= 0.0 && cross_2d(b, c, p) >= 0.0 && cross_2d(c, a, p) >= 0.0
+}
+
+/// Rotation quaternion stored as [w, x, y, z].
+pub type Quat = [f32; 4];
+
+pub const IDENTITY: Quat = [1.0, 0.0, 0.0, 0.0];
+
+/// Rotation by `angle_degrees` counter-clockwise about `axis` (right-hand rule).
+pub fn quat_from_axis_angle(axis: Vec3, angle_degrees: f32) -> Quat {
+    let Some([x, y, z]) = normalize(axis) else {
+        return IDENTITY;
+    };
+    let half = angle_degrees.to_radians() / 2.0;
+    let s = half.sin();
+    [half.cos(), x * s, y * s, z * s]
+}
+
+/// Composition `a * b`: rotating by the result applies `b` first, then `a`.
+pub fn quat_mul(a: Quat, b: Quat) -> Quat {
+    [
+        a[0] * b[0] - a[1] * b[1] - a[2] * b[2] - a[3] * b[3],
+        a[0] * b[1] + a[1] * b[0] + a[2] * b[3] - a[3] * b[2],
+        a[0] * b[2] - a[1] * b[3] + a[2] * b[0] + a[3] * b[1],
+        a[0] * b[3] + a[1] * b[2] - a[2] * b[1] + a[3] * b[0],
+    ]
+}
+
+pub fn quat_rotate(q: Quat, v: Vec3) -> Vec3 {
+    let u = [q[1], q[2], q[3]];
+    let w = q[0];
+    // v' = v + 2w(u x v) + 2(u x (u x v))
+    let uv = cross(u, v);
+    let uuv = cross(u, uv);
+    [
+        v[0] + 2.0 * (w * uv[0] + uuv[0]),
+        v[1] + 2.0 * (w * uv[1] + uuv[1]),
+        v[2] + 2.0 * (w * uv[2] + uuv[2]),
+    ]
+}
+
+/// Shortest-path spherical interpolation between two rotations.
+pub fn quat_slerp(a: Quat, b: Quat, t: f32) -> Quat {
+    let mut cos_omega = a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3];
+    let b = if cos_omega < 0.0 {
+        cos_omega = -cos_omega;
+        [-b[0], -b[1], -b[2], -b[3]]
+    } else {
+        b
+    };
+    let (weight_a, weight_b) = if cos_omega > 0.9995 {
+        (1.0 - t, t) // Nearly identical: lerp avoids dividing by ~0
+    } else {
+        let omega = cos_omega.acos();
+        let sin_omega = omega.sin();
+        (
+            ((1.0 - t) * omega).sin() / sin_omega,
+            (t * omega).sin() / sin_omega,
+        )
+    };
+    let q = [
+        a[0] * weight_a + b[0] * weight_b,
+        a[1] * weight_a + b[1] * weight_b,
+        a[2] * weight_a + b[2] * weight_b,
+        a[3] * weight_a + b[3] * weight_b,
+    ];
+    let len = (q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3]).sqrt();
+    [q[0] / len, q[1] / len, q[2] / len, q[3] / len]
+}
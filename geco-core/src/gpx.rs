@@ -0,0 +1,93 @@
+// klyja/geco-core/src/gpx.rs
+// GPX import: each track (`<trk>`, its segments joined) becomes a marker feature
+// moving along the track (see tracks.rs). All tracks share one clock: the earliest
+// timestamp in the file lands on frame 0 and the latest on the last frame, so
+// markers keep their relative timing. Files are held to the track and movement
+// limits of tracks.rs.
+use crate::protobuf_gen::Polygon;
+use crate::tracks::{self, Timing, Track, TrackPoint, MAX_TRACKS};
+use chrono::DateTime;
+
+/// Reads the tracks of a GPX file. Every track point needs a `time`; tracks without
+/// points are skipped. Routes and waypoints carry no timing and are ignored.
+pub fn parse_tracks(xml: &str) -> Result<Vec<Track>, String> {
+    let document =
+        roxmltree::Document::parse(xml).map_err(|err| format!("Invalid GPX: {}", err))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "gpx" {
+        return Err("Not GPX: the root element isn't <gpx>".to_string());
+    }
+
+    let mut tracks = vec![];
+    for (track_index, track) in root
+        .children()
+        .filter(|node| node.has_tag_name("trk"))
+        .enumerate()
+    {
+        let name = child_text(track, "name").map(str::to_string);
+        let mut points = vec![];
+        for point in track
+            .descendants()
+            .filter(|node| node.has_tag_name("trkpt"))
+        {
+            let coordinate = |attribute: &str, limit: f32| {
+                point
+                    .attribute(attribute)
+                    .and_then(|value| value.trim().parse::<f32>().ok())
+                    .filter(|value| value.abs() <= limit)
+                    .ok_or_else(|| {
+                        format!(
+                            "Track {} point {}: missing or invalid {}",
+                            track_index,
+                            points.len(),
+                            attribute
+                        )
+                    })
+            };
+            let lat = coordinate("lat", 90.0)?;
+            let lon = coordinate("lon", 180.0)?;
+            let time = child_text(point, "time")
+                .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                .ok_or_else(|| {
+                    format!(
+                        "Track {} point {}: missing or invalid time",
+                        track_index,
+                        points.len()
+                    )
+                })?;
+            let time = time.timestamp() as f64 + time.timestamp_subsec_nanos() as f64 / 1e9;
+            points.push(TrackPoint { lat, lon, time });
+        }
+        if points.is_empty() {
+            continue;
+        }
+        if tracks.len() == MAX_TRACKS {
+            return Err(format!(
+                "Track {}: one import may hold at most {} tracks",
+                track_index, MAX_TRACKS
+            ));
+        }
+        // Stable, so points logged at the same second keep their order
+        points.sort_by(|a, b| a.time.total_cmp(&b.time));
+        tracks.push(Track { name, points });
+    }
+    if tracks.is_empty() {
+        return Err("The GPX file has no track points".to_string());
+    }
+    Ok(tracks)
+}
+
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, tag: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.has_tag_name(tag))
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+/// Makes each track of a GPX file into a marker feature moving over `total_frames`
+/// frames, with its points at `radius`. Markers are named after their tracks.
+pub fn import_tracks(xml: &str, total_frames: i32, radius: f32) -> Result<Vec<Polygon>, String> {
+    let tracks = parse_tracks(xml)?;
+    tracks::markers(&tracks, Timing::Spread { total_frames }, radius).map(|(markers, _)| markers)
+}
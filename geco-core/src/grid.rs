@@ -0,0 +1,61 @@
+// klyja/geco-core/src/grid.rs
+// Keyframe handling for animated lat/lon grid layers.
+use crate::protobuf_gen::{GridKeyframe, GridLayer};
+
+/// Number of values a keyframe of this layer must hold.
+pub fn cell_count(layer: &GridLayer) -> usize {
+    (layer.lat_count.max(0) as usize) * (layer.lon_count.max(0) as usize)
+}
+
+/// Inserts a keyframe, replacing any existing keyframe at the same frame,
+/// and keeps the keyframes sorted by frame.
+pub fn upsert_keyframe(
+    layer: &mut GridLayer,
+    frame: i32,
+    values: Vec<f32>,
+) -> Result<(), String> {
+    let expected = cell_count(layer);
+    if values.len() != expected {
+        return Err(format!(
+            "Grid '{}' expects {} values ({}x{}), got {}",
+            layer.grid_id,
+            expected,
+            layer.lat_count,
+            layer.lon_count,
+            values.len()
+        ));
+    }
+    match layer.keyframes.binary_search_by_key(&frame, |k| k.frame) {
+        Ok(index) => layer.keyframes[index].values = values,
+        Err(index) => layer
+            .keyframes
+            .insert(index, GridKeyframe { frame, values }),
+    }
+    Ok(())
+}
+
+/// Returns the grid values at `frame`, linearly interpolated between the surrounding
+/// keyframes and held constant before the first and after the last one.
+/// Returns None if the layer has no keyframes.
+pub fn values_at_frame(layer: &GridLayer, frame: i32) -> Option<Vec<f32>> {
+    let keyframes = &layer.keyframes;
+    let next_index = keyframes.partition_point(|k| k.frame <= frame);
+    match (
+        next_index.checked_sub(1).map(|i| &keyframes[i]),
+        keyframes.get(next_index),
+    ) {
+        (None, None) => None,
+        (Some(only), None) | (None, Some(only)) => Some(only.values.clone()),
+        (Some(previous), Some(next)) => {
+            let t = (frame - previous.frame) as f32 / (next.frame - previous.frame) as f32;
+            Some(
+                previous
+                    .values
+                    .iter()
+                    .zip(&next.values)
+                    .map(|(a, b)| a + (b - a) * t)
+                    .collect(),
+            )
+        }
+    }
+}
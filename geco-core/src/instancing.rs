@@ -0,0 +1,107 @@
+// klyja/geco-core/src/instancing.rs
+// Scatters copies of a marker symbol along a feature's path (army arrows, migration flows).
+// Instances are spaced evenly by arc length along the straight segments between points,
+// the same segments the renderer draws.
+use crate::geometry::{cross, dot, length, normalize, Vec3};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathInstance {
+    pub position: Vec3,
+    pub direction: Vec3, // Unit tangent, pointing along the path
+}
+
+/// Places `count` instances along the path through `points`, closing it back to the
+/// first point when `closed`. Instance `i` sits at arc length `(i + phase) / count` of
+/// the total, so animating `phase` from 0 to 1 moves every instance one slot forward
+/// and the flow loops seamlessly. Empty for degenerate (zero-length) paths.
+pub fn instances_along_path(
+    points: &[Vec3],
+    closed: bool,
+    count: usize,
+    phase: f32,
+) -> Vec<PathInstance> {
+    let mut segments: Vec<(Vec3, Vec3)> = points.windows(2).map(|w| (w[0], w[1])).collect();
+    if closed && points.len() > 2 {
+        segments.push((points[points.len() - 1], points[0]));
+    }
+    let segments: Vec<(Vec3, Vec3, f32)> = segments
+        .into_iter()
+        .map(|(a, b)| (a, b, length(sub(b, a))))
+        .filter(|(_, _, segment_length)| *segment_length > f32::EPSILON)
+        .collect();
+    let total: f32 = segments.iter().map(|(_, _, l)| l).sum();
+    if count == 0 || segments.is_empty() {
+        return vec![];
+    }
+
+    let phase = phase.rem_euclid(1.0);
+    let mut instances = Vec::with_capacity(count);
+    let mut segment_index = 0;
+    let mut segment_start = 0.0;
+    for i in 0..count {
+        let distance = (i as f32 + phase) / count as f32 * total;
+        // Distances only grow, so walk the segments forward instead of searching
+        while segment_index + 1 < segments.len()
+            && segment_start + segments[segment_index].2 < distance
+        {
+            segment_start += segments[segment_index].2;
+            segment_index += 1;
+        }
+        let (a, b, segment_length) = segments[segment_index];
+        let t = ((distance - segment_start) / segment_length).clamp(0.0, 1.0);
+        let delta = sub(b, a);
+        instances.push(PathInstance {
+            position: [
+                a[0] + delta[0] * t,
+                a[1] + delta[1] * t,
+                a[2] + delta[2] * t,
+            ],
+            direction: [
+                delta[0] / segment_length,
+                delta[1] / segment_length,
+                delta[2] / segment_length,
+            ],
+        });
+    }
+    instances
+}
+
+/// Column-major 4x4 transform (three.js `Matrix4.fromArray` layout) placing a symbol
+/// at the instance: local +x along the path, +y away from the globe center, uniformly
+/// scaled by `scale`.
+pub fn instance_matrix(instance: &PathInstance, scale: f32) -> [f32; 16] {
+    let x_axis = instance.direction;
+    let outward = normalize(instance.position).unwrap_or([0.0, 1.0, 0.0]);
+    // Remove the tangent's share so the axes stay orthogonal on sloped segments
+    let along = dot(outward, x_axis);
+    let y_axis = normalize([
+        outward[0] - x_axis[0] * along,
+        outward[1] - x_axis[1] * along,
+        outward[2] - x_axis[2] * along,
+    ])
+    .unwrap_or(outward);
+    let z_axis = cross(x_axis, y_axis);
+    let [px, py, pz] = instance.position;
+    [
+        x_axis[0] * scale,
+        x_axis[1] * scale,
+        x_axis[2] * scale,
+        0.0,
+        y_axis[0] * scale,
+        y_axis[1] * scale,
+        y_axis[2] * scale,
+        0.0,
+        z_axis[0] * scale,
+        z_axis[1] * scale,
+        z_axis[2] * scale,
+        0.0,
+        px,
+        py,
+        pz,
+        1.0,
+    ]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
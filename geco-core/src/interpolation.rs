@@ -0,0 +1,72 @@
+// klyja/geco-core/src/interpolation.rs
+// Resolves animated points at a given frame.
+//
+// A point's `movements` are per-frame steps: movement `i` is applied when going
+// from frame `start + i` to frame `start + i + 1`, where `start` is the point's
+// `start_frame` (0 unless the point was inserted mid-animation) and
+// `initial_position` is its position at `start`. Past the last movement the point
+// holds still. A point is part of the shape from `start_frame` until `end_frame`.
+use crate::editing::apply_movement;
+use crate::protobuf_gen::{AnimatedPoint, Point, ScalarKeyframe};
+
+/// Whether the point is part of its shape at `frame`.
+pub fn is_present_at_frame(point: &AnimatedPoint, frame: i32) -> bool {
+    frame >= start_frame(point) && point.end_frame.is_none_or(|end| frame < end)
+}
+
+/// Frame at which the point joins the shape.
+pub fn start_frame(point: &AnimatedPoint) -> i32 {
+    point.start_frame.unwrap_or(0)
+}
+
+/// Returns the position of a point at `frame`, or None if it has no initial position
+/// or is not part of the shape at that frame.
+pub fn position_at_frame(point: &AnimatedPoint, frame: i32) -> Option<Point> {
+    if !is_present_at_frame(point, frame) {
+        return None;
+    }
+    let mut position = point.initial_position.clone()?;
+    let steps = (frame - start_frame(point)) as usize;
+    for movement in point.movements.iter().take(steps) {
+        apply_movement(&mut position, movement);
+    }
+    Some(position)
+}
+
+/// Lists the frames where a point's position is defined explicitly: its start frame and
+/// every frame reached by a non-zero movement. Empty padding steps are skipped,
+/// as are frames after the point has been removed.
+pub fn point_keyframes(point: &AnimatedPoint) -> Vec<(i32, Point)> {
+    let Some(mut position) = point.initial_position.clone() else {
+        return vec![];
+    };
+    let start = start_frame(point);
+    let mut keyframes = vec![(start, position.clone())];
+    for (index, movement) in point.movements.iter().enumerate() {
+        apply_movement(&mut position, movement);
+        let frame = start + index as i32 + 1;
+        let is_still =
+            movement.dx == 0.0 && movement.dy == 0.0 && movement.dz.unwrap_or(0.0) == 0.0;
+        if !is_still && is_present_at_frame(point, frame) {
+            keyframes.push((frame, position.clone()));
+        }
+    }
+    keyframes
+}
+
+/// Returns a point's data value at `frame`, linearly interpolated between the
+/// surrounding keyframes and held constant outside them. None without keyframes.
+pub fn scalar_at_frame(keyframes: &[ScalarKeyframe], frame: i32) -> Option<f32> {
+    let next_index = keyframes.partition_point(|k| k.frame <= frame);
+    match (
+        next_index.checked_sub(1).map(|i| &keyframes[i]),
+        keyframes.get(next_index),
+    ) {
+        (None, None) => None,
+        (Some(only), None) | (None, Some(only)) => Some(only.value),
+        (Some(previous), Some(next)) => {
+            let t = (frame - previous.frame) as f32 / (next.frame - previous.frame) as f32;
+            Some(previous.value + (next.value - previous.value) * t)
+        }
+    }
+}
@@ -0,0 +1,108 @@
+// klyja/geco-core/src/lazy_load.rs
+// Header-only decoding for browsing large documents: every feature is decoded except
+// its points, whose raw bytes are kept aside and only decoded when first needed.
+// Points dominate a document's size, so this makes opening one near-instant.
+use crate::chunked_load::complete_field_len;
+use crate::protobuf_gen::{MapAnimation, Polygon};
+use prost::Message;
+
+/// Field numbers from AnimationData.proto.
+const POLYGONS_FIELD: u64 = 4; // MapAnimation.polygons
+const POINTS_FIELD: u64 = 2; // Polygon.points
+const LENGTH_DELIMITED: u64 = 2;
+
+pub struct LazyDocument {
+    header: MapAnimation,           // Polygons have no points until materialized
+    deferred: Vec<Option<Vec<u8>>>, // Encoded point fields per polygon, None once decoded
+}
+
+impl LazyDocument {
+    /// Decodes everything but the features' points.
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut header = MapAnimation::default();
+        let mut deferred = vec![];
+        for (key, field) in fields(data)? {
+            if key == (POLYGONS_FIELD << 3 | LENGTH_DELIMITED) {
+                let (polygon, points) = split_polygon(length_delimited_body(field)?)?;
+                header.polygons.push(polygon);
+                deferred.push(Some(points));
+            } else {
+                header.merge(field).map_err(decode_error)?;
+            }
+        }
+        Ok(LazyDocument { header, deferred })
+    }
+
+    /// The document with only the features materialized so far holding their points.
+    pub fn header(&self) -> &MapAnimation {
+        &self.header
+    }
+
+    /// Number of points of the feature at `index`, counted without decoding them.
+    pub fn point_count(&self, index: usize) -> usize {
+        match &self.deferred[index] {
+            Some(points) => fields(points).map_or(0, |fields| fields.len()),
+            None => self.header.polygons[index].points.len(),
+        }
+    }
+
+    /// Decodes the points of the feature at `index` if that hasn't happened yet.
+    pub fn materialize(&mut self, index: usize) -> Result<&Polygon, String> {
+        if let Some(points) = self.deferred[index].take() {
+            // The point fields on their own are a valid encoding of a Polygon
+            self.header.polygons[index].points = Polygon::decode(points.as_slice())
+                .map_err(decode_error)?
+                .points;
+        }
+        Ok(&self.header.polygons[index])
+    }
+
+    /// The whole document, decoding every remaining feature's points.
+    pub fn into_animation(mut self) -> Result<MapAnimation, String> {
+        for index in 0..self.deferred.len() {
+            self.materialize(index)?;
+        }
+        Ok(self.header)
+    }
+}
+
+/// Splits an encoded Polygon into the decoded polygon without points and the
+/// encoded point fields.
+fn split_polygon(data: &[u8]) -> Result<(Polygon, Vec<u8>), String> {
+    let mut polygon = Polygon::default();
+    let mut points = vec![];
+    for (key, field) in fields(data)? {
+        if key == (POINTS_FIELD << 3 | LENGTH_DELIMITED) {
+            points.extend_from_slice(field);
+        } else {
+            polygon.merge(field).map_err(decode_error)?;
+        }
+    }
+    Ok((polygon, points))
+}
+
+/// Splits an encoded message into its fields, each with its key.
+fn fields(data: &[u8]) -> Result<Vec<(u64, &[u8])>, String> {
+    let mut fields = vec![];
+    let mut rest = data;
+    while !rest.is_empty() {
+        let len = complete_field_len(rest)?.ok_or("Protobuf data ends mid-field")?;
+        let (field, tail) = rest.split_at(len);
+        let key = prost::encoding::decode_varint(&mut &field[..]).map_err(decode_error)?;
+        fields.push((key, field));
+        rest = tail;
+    }
+    Ok(fields)
+}
+
+/// The payload of a complete length-delimited field (key and length stripped).
+fn length_delimited_body(field: &[u8]) -> Result<&[u8], String> {
+    let mut buf = field;
+    prost::encoding::decode_varint(&mut buf).map_err(decode_error)?; // Key
+    let len = prost::encoding::decode_varint(&mut buf).map_err(decode_error)?;
+    Ok(&buf[..len as usize])
+}
+
+fn decode_error(e: prost::DecodeError) -> String {
+    format!("Failed to decode Protobuf: {}", e)
+}
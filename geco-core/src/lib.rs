@@ -0,0 +1,43 @@
+// klyja/geco-core/src/lib.rs
+// The animation engine behind `geco`: document model, interpolation, editing helpers,
+// geometry and render data. Free of wasm-bindgen so it also builds natively.
+
+// --- Protobuf types, shared with the backend ---
+pub use klyja_proto as protobuf_gen;
+
+pub mod basemap;
+pub mod bounds;
+pub mod camera;
+pub mod chunked_load;
+pub mod color_ramp;
+pub mod data_series;
+pub mod delaunay;
+pub mod diff;
+pub mod document_json;
+pub mod editing;
+pub mod format;
+pub mod geojson;
+pub mod geometry;
+pub mod gpx;
+pub mod grid;
+pub mod instancing;
+pub mod interpolation;
+pub mod lazy_load;
+pub mod mirror;
+pub mod patch;
+pub mod point_series;
+pub mod raster;
+pub mod render;
+pub mod scatter;
+pub mod scenes;
+pub mod search;
+pub mod smoothing;
+pub mod stats;
+pub mod tracks;
+pub mod transform;
+
+// Include the test module
+#[cfg(test)]
+#[path = "lib_test.rs"]
+#[allow(clippy::module_inception)] // lib_test.rs wraps its tests in its own `mod tests`
+mod tests;
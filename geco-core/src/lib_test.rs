@@ -0,0 +1,1835 @@
+#[cfg(test)]
+mod tests {
+    use crate::protobuf_gen::{AnimatedPoint, FeatureType, MapAnimation, Point, Polygon};
+    use prost::Message;
+
+    #[test]
+    fn test_map_animation_serialization() {
+        let point = Point {
+            x: 1.0,
+            y: 2.0,
+            z: Some(3.0),
+        };
+        
+        let animated_point = AnimatedPoint {
+            point_id: "test-point".to_string(),
+            initial_position: Some(point),
+            movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+        
+        let polygon = Polygon {
+            polygon_id: "test-polygon".to_string(),
+            points: vec![animated_point],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+        
+        let animation = MapAnimation {
+            animation_id: "test-animation".to_string(),
+            name: "Test Animation".to_string(),
+            total_frames: 10,
+            polygons: vec![polygon],
+            grid_layers: vec![],
+            camera_keyframes: vec![],
+            data_series: vec![],
+            scenes: vec![],
+            format_version: 0,
+        };
+        
+        // Serialize to protobuf
+        let bytes = animation.encode_to_vec();
+        
+        // Deserialize
+        let decoded = MapAnimation::decode(&bytes[..]).unwrap();
+        
+        // Verify the data
+        assert_eq!(decoded.animation_id, "test-animation");
+        assert_eq!(decoded.name, "Test Animation");
+        assert_eq!(decoded.total_frames, 10);
+        assert_eq!(decoded.polygons.len(), 1);
+        assert_eq!(decoded.polygons[0].polygon_id, "test-polygon");
+        assert_eq!(decoded.polygons[0].points.len(), 1);
+        assert_eq!(decoded.polygons[0].points[0].point_id, "test-point");
+        let pos = decoded.polygons[0].points[0].initial_position.as_ref().unwrap();
+        assert_eq!(pos.x, 1.0);
+        assert_eq!(pos.y, 2.0);
+        assert_eq!(pos.z, Some(3.0));
+    }
+
+    #[test]
+    fn test_shift_point_keyframes_delays_and_advances_motion() {
+        use crate::editing::shift_point_keyframes;
+        use crate::protobuf_gen::Vector;
+
+        let mut point = AnimatedPoint {
+            point_id: "p".to_string(),
+            initial_position: Some(Point {
+                x: 0.0,
+                y: 0.0,
+                z: Some(0.0),
+            }),
+            movements: vec![
+                Vector {
+                    dx: 1.0,
+                    dy: 0.0,
+                    dz: None,
+                },
+                Vector {
+                    dx: 0.0,
+                    dy: 2.0,
+                    dz: None,
+                },
+            ],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+
+        shift_point_keyframes(&mut point, 2);
+        assert_eq!(point.movements.len(), 4);
+        assert_eq!(point.movements[0], Vector::default());
+        assert_eq!(point.movements[2].dx, 1.0);
+
+        // Advancing past the padding and the first real movement folds it into the start
+        shift_point_keyframes(&mut point, -3);
+        assert_eq!(point.movements.len(), 1);
+        let pos = point.initial_position.as_ref().unwrap();
+        assert_eq!(pos.x, 1.0);
+        assert_eq!(pos.y, 0.0);
+        assert_eq!(point.movements[0].dy, 2.0);
+    }
+
+    #[test]
+    fn test_translate_point_moves_initial_position() {
+        use crate::editing::translate_point;
+
+        let mut point = AnimatedPoint {
+            point_id: "p".to_string(),
+            initial_position: Some(Point {
+                x: 1.0,
+                y: 1.0,
+                z: None,
+            }),
+            movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+
+        translate_point(&mut point, 0.5, -1.0, 2.0);
+        let pos = point.initial_position.unwrap();
+        assert_eq!(pos.x, 1.5);
+        assert_eq!(pos.y, 0.0);
+        assert_eq!(pos.z, Some(2.0));
+    }
+
+    #[test]
+    fn test_position_at_frame_and_keyframes() {
+        use crate::interpolation::{point_keyframes, position_at_frame};
+        use crate::protobuf_gen::Vector;
+
+        let point = AnimatedPoint {
+            point_id: "p".to_string(),
+            initial_position: Some(Point {
+                x: 0.0,
+                y: 0.0,
+                z: Some(0.0),
+            }),
+            movements: vec![
+                Vector {
+                    dx: 1.0,
+                    dy: 0.0,
+                    dz: None,
+                },
+                Vector::default(),
+                Vector {
+                    dx: 0.0,
+                    dy: 1.0,
+                    dz: None,
+                },
+            ],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+
+        assert_eq!(position_at_frame(&point, 0).unwrap().x, 0.0);
+        assert_eq!(position_at_frame(&point, 2).unwrap().x, 1.0);
+        // Frames past the last movement hold the final position
+        let end = position_at_frame(&point, 10).unwrap();
+        assert_eq!((end.x, end.y), (1.0, 1.0));
+
+        let frames: Vec<i32> = point_keyframes(&point).iter().map(|(f, _)| *f).collect();
+        assert_eq!(frames, vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn test_next_point_id_skips_taken_ids() {
+        use crate::editing::next_point_id;
+
+        let point = |id: &str| AnimatedPoint {
+            point_id: id.to_string(),
+            initial_position: None,
+            movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+        // "poly-pt0" was deleted, so the count (2) collides with the surviving "poly-pt2"
+        let polygon = Polygon {
+            polygon_id: "poly".to_string(),
+            points: vec![point("poly-pt1"), point("poly-pt2")],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+
+        assert_eq!(next_point_id(&polygon), "poly-pt3");
+    }
+
+    #[test]
+    fn test_storage_index_at_frame_skips_absent_points() {
+        use crate::editing::storage_index_at_frame;
+
+        let point = |id: &str, end_frame: Option<i32>| AnimatedPoint {
+            point_id: id.to_string(),
+            initial_position: None,
+            movements: vec![],
+            end_frame,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+        let polygon = Polygon {
+            polygon_id: "poly".to_string(),
+            points: vec![point("a", None), point("gone", Some(2)), point("b", None)],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+
+        // At frame 5 the shape is [a, b]: inserting at 1 goes before "b"
+        assert_eq!(storage_index_at_frame(&polygon, 5, 1), Some(2));
+        assert_eq!(storage_index_at_frame(&polygon, 5, 2), Some(3));
+        assert_eq!(storage_index_at_frame(&polygon, 5, 3), None);
+        // At frame 0 all three points are present
+        assert_eq!(storage_index_at_frame(&polygon, 0, 1), Some(1));
+    }
+
+    #[test]
+    fn test_convert_feature_type_round_trip() {
+        use crate::editing::convert_feature_type;
+
+        let point = |id: &str, x: f32| AnimatedPoint {
+            point_id: id.to_string(),
+            initial_position: Some(Point { x, y: 0.0, z: None }),
+            movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+        let mut polygon = Polygon {
+            polygon_id: "poly".to_string(),
+            points: vec![
+                point("poly-pt0", 0.0),
+                point("poly-pt1", 1.0),
+                point("poly-pt2", 2.0),
+            ],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+
+        // Opening the polygon adds the explicit closing segment
+        convert_feature_type(&mut polygon, FeatureType::Polyline).unwrap();
+        assert_eq!(polygon.feature_type(), FeatureType::Polyline);
+        assert_eq!(polygon.points.len(), 4);
+        assert_eq!(
+            polygon.points[3].initial_position,
+            polygon.points[0].initial_position
+        );
+
+        // Closing it again drops the now redundant closing point
+        convert_feature_type(&mut polygon, FeatureType::Polygon).unwrap();
+        assert_eq!(polygon.points.len(), 3);
+
+        assert!(convert_feature_type(&mut polygon, FeatureType::Point).is_err());
+        assert_eq!(polygon.feature_type(), FeatureType::Polygon);
+    }
+
+    #[test]
+    fn test_grid_values_interpolate_between_keyframes() {
+        use crate::grid::{upsert_keyframe, values_at_frame};
+        use crate::protobuf_gen::GridLayer;
+
+        let mut layer = GridLayer {
+            grid_id: "temperature".to_string(),
+            lat_count: 1,
+            lon_count: 2,
+            keyframes: vec![],
+            properties: Default::default(),
+        };
+        assert!(values_at_frame(&layer, 0).is_none());
+
+        upsert_keyframe(&mut layer, 10, vec![10.0, 20.0]).unwrap();
+        upsert_keyframe(&mut layer, 0, vec![0.0, 0.0]).unwrap();
+        assert!(upsert_keyframe(&mut layer, 5, vec![1.0]).is_err());
+        assert_eq!(layer.keyframes[0].frame, 0);
+
+        assert_eq!(values_at_frame(&layer, 5).unwrap(), vec![5.0, 10.0]);
+        assert_eq!(values_at_frame(&layer, 10).unwrap(), vec![10.0, 20.0]);
+        // Held constant outside the keyframed range
+        assert_eq!(values_at_frame(&layer, 50).unwrap(), vec![10.0, 20.0]);
+        assert_eq!(values_at_frame(&layer, -1).unwrap(), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_color_ramp_maps_values() {
+        use crate::color_ramp::ColorRamp;
+
+        let ramp = ColorRamp::from_json(
+            r#"[{"value": 100, "color": [1, 0, 0]}, {"value": 0, "color": [0, 0, 1]}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(ramp.color_at(50.0), [0.5, 0.0, 0.5]);
+        assert_eq!(ramp.color_at(-10.0), [0.0, 0.0, 1.0]);
+        assert_eq!(ramp.color_at(1000.0), [1.0, 0.0, 0.0]);
+        assert!(ColorRamp::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn test_vertex_buffers_use_point_scalars() {
+        use crate::color_ramp::{ColorRamp, DEFAULT_VERTEX_COLOR};
+        use crate::editing::upsert_scalar_keyframe;
+        use crate::render::build_vertex_buffers;
+
+        let mut with_data = AnimatedPoint {
+            point_id: "a".to_string(),
+            initial_position: Some(Point {
+                x: 1.0,
+                y: 2.0,
+                z: Some(3.0),
+            }),
+            movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+        upsert_scalar_keyframe(&mut with_data, 0, 0.0);
+        upsert_scalar_keyframe(&mut with_data, 10, 1.0);
+        let mut without_data = with_data.clone();
+        without_data.point_id = "b".to_string();
+        without_data.scalar_keyframes.clear();
+
+        let polygon = Polygon {
+            polygon_id: "poly".to_string(),
+            points: vec![with_data, without_data],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+        let ramp = ColorRamp::from_json(
+            r#"[{"value": 0, "color": [0, 0, 0]}, {"value": 1, "color": [1, 1, 1]}]"#,
+        )
+        .unwrap();
+
+        let buffers =
+            build_vertex_buffers(std::slice::from_ref(&polygon), &[&polygon], 5, Some(&ramp));
+        assert_eq!(buffers.vertex_counts, vec![2]);
+        assert_eq!(&buffers.positions[..3], &[1.0, 2.0, 3.0]);
+        assert_eq!(&buffers.colors[..3], &[0.5, 0.5, 0.5]);
+        assert_eq!(&buffers.colors[3..], &DEFAULT_VERTEX_COLOR);
+    }
+
+    /// Places a lat/lon (degrees) on a sphere of radius 5, laid out like the frontend globe.
+    fn globe_point(lat: f32, lon: f32) -> [f32; 3] {
+        let theta = (90.0 - lat).to_radians();
+        let phi = (lon + 180.0).to_radians();
+        [
+            -5.0 * phi.cos() * theta.sin(),
+            5.0 * theta.cos(),
+            5.0 * phi.sin() * theta.sin(),
+        ]
+    }
+
+    #[test]
+    fn test_to_lat_lon_and_uv() {
+        use crate::geometry::{equirectangular_uv, to_lat_lon};
+
+        for (lat, lon) in [(0.0, 0.0), (45.0, 90.0), (-30.0, -120.0), (10.0, 179.0)] {
+            let (lat2, lon2) = to_lat_lon(globe_point(lat, lon)).unwrap();
+            assert!((lat - lat2).abs() < 1e-3, "lat {} -> {}", lat, lat2);
+            assert!((lon - lon2).abs() < 1e-3, "lon {} -> {}", lon, lon2);
+        }
+
+        let [u, v] = equirectangular_uv([5.0, 0.0, 0.0]);
+        assert!((u - 0.5).abs() < 1e-5 && (v - 0.5).abs() < 1e-5);
+        assert!(to_lat_lon([0.0, 0.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn test_triangulate_concave_polygon() {
+        use crate::geometry::triangulate;
+
+        // An L-shaped (concave) hexagon near the equator
+        let points: Vec<[f32; 3]> = [
+            (0.0, 0.0),
+            (0.0, 20.0),
+            (10.0, 20.0),
+            (10.0, 10.0),
+            (20.0, 10.0),
+            (20.0, 0.0),
+        ]
+        .iter()
+        .map(|&(lat, lon)| globe_point(lat, lon))
+        .collect();
+
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), (points.len() - 2) * 3);
+        assert!(triangles.iter().all(|&i| (i as usize) < points.len()));
+        assert!(triangulate(&points[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_decode_packed_basemap() {
+        use crate::basemap::decode_rings;
+
+        let mut data = b"KBM1".to_vec();
+        data.extend(1u32.to_le_bytes());
+        data.extend(2u32.to_le_bytes());
+        for value in [10.0f32, 20.0, 11.0, 21.5] {
+            data.extend(value.to_le_bytes());
+        }
+
+        let rings = decode_rings(&data).unwrap();
+        assert_eq!(rings, vec![vec![(10.0, 20.0), (11.0, 21.5)]]);
+
+        assert!(decode_rings(&data[..data.len() - 1]).is_err());
+        assert!(decode_rings(b"NOPE").is_err());
+    }
+
+    #[test]
+    fn test_camera_slerps_between_keyframes() {
+        use crate::camera::{camera_at_frame, upsert_keyframe};
+        use crate::protobuf_gen::CameraKeyframe;
+
+        let keyframe = |frame: i32, x: f32, z: f32, zoom: f32| CameraKeyframe {
+            frame,
+            position: Some(Point {
+                x,
+                y: 0.0,
+                z: Some(z),
+            }),
+            target: None,
+            zoom,
+        };
+        let mut keyframes = vec![];
+        upsert_keyframe(&mut keyframes, keyframe(10, 0.0, 10.0, 2.0));
+        upsert_keyframe(&mut keyframes, keyframe(0, 10.0, 0.0, 1.0));
+        assert!(camera_at_frame(&[], 0).is_none());
+
+        let halfway = camera_at_frame(&keyframes, 5).unwrap();
+        let position = halfway.position.unwrap();
+        // Slerp keeps the camera at orbit distance rather than cutting the corner
+        let distance = (position.x.powi(2) + position.z.unwrap().powi(2)).sqrt();
+        assert!((distance - 10.0).abs() < 1e-3);
+        assert!((position.x - position.z.unwrap()).abs() < 1e-3);
+        assert!((halfway.zoom - 1.5).abs() < 1e-6);
+
+        assert_eq!(camera_at_frame(&keyframes, 99).unwrap().zoom, 2.0);
+    }
+
+    #[test]
+    fn test_children_inherit_parent_rotation() {
+        use crate::geometry::quat_rotate;
+        use crate::protobuf_gen::TransformKeyframe;
+        use crate::transform::{upsert_keyframe, world_rotation_at_frame, would_create_cycle};
+
+        let feature = |id: &str, parent_id: Option<&str>| Polygon {
+            polygon_id: id.to_string(),
+            points: vec![],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: parent_id.map(str::to_string),
+        };
+        let spin = |frame: i32, angle_degrees: f32| TransformKeyframe {
+            frame,
+            axis: Some(Point {
+                x: 0.0,
+                y: 1.0,
+                z: Some(0.0),
+            }),
+            angle_degrees,
+        };
+        let mut plate = feature("plate", None);
+        upsert_keyframe(&mut plate, spin(0, 0.0));
+        upsert_keyframe(&mut plate, spin(10, 90.0));
+        let mut island = feature("island", Some("plate"));
+        upsert_keyframe(&mut island, spin(0, 90.0));
+        let features = vec![plate, island];
+
+        // Halfway, the plate has turned 45 degrees and the island 90 more on top
+        let rotation = world_rotation_at_frame(&features, &features[1], 5);
+        let rotated = quat_rotate(rotation, [1.0, 0.0, 0.0]);
+        let angle = 135f32.to_radians();
+        let expected = [angle.cos(), 0.0, -angle.sin()];
+        for (actual, expected) in rotated.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-5, "{:?}", rotated);
+        }
+
+        assert!(would_create_cycle(&features, "plate", "island"));
+        assert!(would_create_cycle(&features, "plate", "plate"));
+        assert!(!would_create_cycle(&features, "island", "plate"));
+    }
+
+    #[test]
+    fn test_instances_spaced_by_arc_length() {
+        use crate::instancing::{instance_matrix, instances_along_path};
+
+        // An L-shaped path, 3 units then 1 unit long
+        let path = [[0.0, 5.0, 0.0], [3.0, 5.0, 0.0], [3.0, 5.0, 1.0]];
+        let instances = instances_along_path(&path, false, 4, 0.0);
+        let xs: Vec<f32> = instances.iter().map(|i| i.position[0]).collect();
+        assert_eq!(xs, vec![0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(instances[1].direction, [1.0, 0.0, 0.0]);
+
+        // Half a step further along, the last instance turns the corner
+        let shifted = instances_along_path(&path, false, 4, 0.5);
+        assert_eq!(shifted[3].position, [3.0, 5.0, 0.5]);
+        assert_eq!(shifted[3].direction, [0.0, 0.0, 1.0]);
+
+        let matrix = instance_matrix(&instances[1], 2.0);
+        assert_eq!(&matrix[0..3], &[2.0, 0.0, 0.0]); // Along the path
+        assert_eq!(&matrix[4..7], &[0.0, 2.0, 0.0]); // Away from the globe
+        assert_eq!(&matrix[12..16], &[1.0, 5.0, 0.0, 1.0]);
+
+        assert!(instances_along_path(&[[1.0, 0.0, 0.0]], false, 4, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_scatter_is_deterministic_and_inside() {
+        use crate::geometry::{contains_point, length};
+        use crate::scatter::scatter_in_polygon;
+
+        let square = [
+            globe_point(-10.0, -10.0),
+            globe_point(-10.0, 10.0),
+            globe_point(10.0, 10.0),
+            globe_point(10.0, -10.0),
+        ];
+        let points = scatter_in_polygon(&square, 50, 42);
+        assert_eq!(points.len(), 50);
+        assert!(points.iter().all(|p| contains_point(&square, *p)));
+        assert!(points.iter().all(|p| (length(*p) - 5.0).abs() < 1e-3));
+
+        assert_eq!(scatter_in_polygon(&square, 50, 42), points);
+        assert_ne!(scatter_in_polygon(&square, 50, 7), points);
+    }
+
+    #[test]
+    fn test_bounding_cap_holds_all_points() {
+        use crate::bounds::bounding_cap;
+
+        let cap = bounding_cap(&[globe_point(0.0, -10.0), globe_point(0.0, 10.0)]).unwrap();
+        let [x, y, z] = cap.center;
+        let expected = globe_point(0.0, 0.0);
+        assert!((x - expected[0] / 5.0).abs() < 1e-5);
+        assert!(y.abs() < 1e-5);
+        assert!((z - expected[2] / 5.0).abs() < 1e-5);
+        assert!((cap.angle_degrees - 10.0).abs() < 1e-3);
+
+        let single = bounding_cap(&[globe_point(20.0, 30.0)]).unwrap();
+        assert!(single.angle_degrees < 0.1);
+        assert!(bounding_cap(&[]).is_none());
+    }
+
+    #[test]
+    fn test_feature_query_matches_name_and_properties() {
+        use crate::search::FeatureQuery;
+
+        let mut river = Polygon {
+            polygon_id: "feature-1".to_string(),
+            points: vec![],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polyline as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+        river
+            .properties
+            .insert("name".to_string(), "Great River".to_string());
+        river
+            .properties
+            .insert("layer".to_string(), "hydro".to_string());
+
+        let query = |json: &str| FeatureQuery::from_json(json).unwrap();
+        assert!(query("{}").matches(&river));
+        assert!(query(r#"{"name": "river"}"#).matches(&river));
+        assert!(query(r#"{"name": "FEATURE-1"}"#).matches(&river));
+        assert!(query(r#"{"name": "river", "properties": {"layer": "hydro"}}"#).matches(&river));
+        assert!(!query(r#"{"properties": {"layer": "roads"}}"#).matches(&river));
+        assert!(!query(r#"{"properties": {"color": "blue"}}"#).matches(&river));
+        assert!(FeatureQuery::from_json(r#"{"nmae": "river"}"#).is_err());
+    }
+
+    #[test]
+    fn test_document_json_round_trip() {
+        use crate::document_json::{from_json, to_json};
+
+        let animation = MapAnimation {
+            animation_id: "anim-1".to_string(),
+            name: "Rivers".to_string(),
+            total_frames: 10,
+            polygons: vec![Polygon {
+                polygon_id: "river".to_string(),
+                points: vec![AnimatedPoint {
+                    point_id: "river-pt0".to_string(),
+                    initial_position: Some(Point {
+                        x: 0.1,
+                        y: 4.9,
+                        z: None,
+                    }),
+                    movements: vec![],
+                    end_frame: Some(8),
+                    start_frame: None,
+                    scalar_keyframes: vec![],
+                    pinned_feature_id: None,
+                }],
+                properties: Default::default(),
+                draw_order: 2,
+                feature_type: FeatureType::Polyline as i32,
+                locked: false,
+                transform_keyframes: vec![],
+                parent_id: None,
+            }],
+            grid_layers: vec![],
+            camera_keyframes: vec![],
+            data_series: vec![],
+            scenes: vec![],
+            format_version: 0,
+        };
+
+        let json = to_json(&animation).unwrap();
+        assert!(json.contains(r#""feature_type": "polyline""#));
+        assert_eq!(from_json(&json).unwrap(), animation);
+
+        // Hand-written documents may leave fields out
+        let sparse = from_json(r#"{"name": "Sketch", "polygons": [{"polygon_id": "a"}]}"#).unwrap();
+        assert_eq!(sparse.name, "Sketch");
+        assert_eq!(sparse.polygons[0].feature_type(), FeatureType::Polygon);
+        assert!(from_json(r#"{"polygons": [{"feature_type": "blob"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_chunked_decoder_matches_whole_decode() {
+        use crate::chunked_load::ChunkedDecoder;
+
+        let polygon = |id: &str| Polygon {
+            polygon_id: id.to_string(),
+            points: vec![AnimatedPoint {
+                point_id: format!("{}-pt0", id),
+                initial_position: Some(Point {
+                    x: 1.0,
+                    y: 2.0,
+                    z: Some(3.0),
+                }),
+                movements: vec![],
+                end_frame: None,
+                start_frame: None,
+                scalar_keyframes: vec![],
+                pinned_feature_id: None,
+            }],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+        let animation = MapAnimation {
+            animation_id: "anim-1".to_string(),
+            name: "Streamed".to_string(),
+            total_frames: 300,
+            polygons: vec![polygon("a"), polygon("b"), polygon("c")],
+            grid_layers: vec![],
+            camera_keyframes: vec![],
+            data_series: vec![],
+            scenes: vec![],
+            format_version: 0,
+        };
+        let bytes = animation.encode_to_vec();
+
+        let mut decoder = ChunkedDecoder::default();
+        for chunk in bytes.chunks(7) {
+            decoder.feed(chunk).unwrap();
+        }
+        assert_eq!(decoder.finish().unwrap(), animation);
+
+        let mut truncated = ChunkedDecoder::default();
+        truncated.feed(&bytes[..bytes.len() - 3]).unwrap();
+        assert!(truncated.finish().is_err());
+    }
+
+    #[test]
+    fn test_document_stats_counts() {
+        use crate::protobuf_gen::Vector;
+        use crate::stats::document_stats;
+
+        let moving = |dx: f32| Vector {
+            dx,
+            dy: 0.0,
+            dz: None,
+        };
+        let polygon = Polygon {
+            polygon_id: "poly".to_string(),
+            points: vec![AnimatedPoint {
+                point_id: "poly-pt0".to_string(),
+                initial_position: Some(Point {
+                    x: 0.0,
+                    y: 5.0,
+                    z: Some(0.0),
+                }),
+                // Only the non-zero step adds a keyframe after the start
+                movements: vec![moving(0.0), moving(1.0)],
+                end_frame: None,
+                start_frame: None,
+                scalar_keyframes: vec![],
+                pinned_feature_id: None,
+            }],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+        let animation = MapAnimation {
+            animation_id: "anim".to_string(),
+            name: "Stats".to_string(),
+            total_frames: 2,
+            polygons: vec![polygon],
+            grid_layers: vec![],
+            camera_keyframes: vec![],
+            data_series: vec![],
+            scenes: vec![],
+            format_version: 0,
+        };
+
+        let stats = document_stats(&animation);
+        assert_eq!(stats.feature_count, 1);
+        assert_eq!(stats.point_count, 1);
+        assert_eq!(stats.movement_count, 2);
+        assert_eq!(stats.keyframe_count, 2);
+        assert_eq!(stats.encoded_bytes, animation.encode_to_vec().len());
+        assert!(stats.estimated_heap_bytes > 0);
+    }
+
+    #[test]
+    fn test_diff_documents_matches_by_id() {
+        use crate::diff::diff_documents;
+        use crate::protobuf_gen::Vector;
+
+        let point = |id: &str, x: f32| AnimatedPoint {
+            point_id: id.to_string(),
+            initial_position: Some(Point {
+                x,
+                y: 5.0,
+                z: Some(0.0),
+            }),
+            movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+        let polygon = |id: &str, points: Vec<AnimatedPoint>| Polygon {
+            polygon_id: id.to_string(),
+            points,
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+        let document = |polygons: Vec<Polygon>| MapAnimation {
+            animation_id: "anim".to_string(),
+            name: "Diff".to_string(),
+            total_frames: 10,
+            polygons,
+            grid_layers: vec![],
+            camera_keyframes: vec![],
+            data_series: vec![],
+            scenes: vec![],
+            format_version: 0,
+        };
+        let before = document(vec![
+            polygon("kept", vec![point("kept-pt0", 0.0)]),
+            polygon("edited", vec![point("a", 0.0), point("b", 0.0)]),
+            polygon("gone", vec![point("gone-pt0", 0.0)]),
+        ]);
+        let mut moved = point("a", 0.0);
+        moved.movements = vec![Vector {
+            dx: 1.0,
+            dy: 0.0,
+            dz: None,
+        }];
+        // Reordering features is not a change
+        let after = document(vec![
+            polygon("new", vec![point("new-pt0", 0.0)]),
+            polygon("edited", vec![moved, point("c", 0.0)]),
+            polygon("kept", vec![point("kept-pt0", 0.0)]),
+        ]);
+
+        let diff = diff_documents(&before, &after);
+        assert!(!diff.name_changed);
+        assert_eq!(diff.added_features, vec!["new"]);
+        assert_eq!(diff.removed_features, vec!["gone"]);
+        assert_eq!(diff.changed_features.len(), 1);
+        let change = &diff.changed_features[0];
+        assert_eq!(change.feature_id, "edited");
+        assert_eq!(
+            (change.points_added, change.points_removed, change.points_changed),
+            (1, 1, 1)
+        );
+        assert_eq!((change.keyframes_before, change.keyframes_after), (2, 3));
+        assert_eq!((diff.keyframes_before, diff.keyframes_after), (4, 5));
+        let unchanged = diff_documents(&after, &after);
+        assert!(unchanged.added_features.is_empty() && unchanged.changed_features.is_empty());
+    }
+
+    #[test]
+    fn test_patch_round_trips_edits() {
+        use crate::patch::{apply_patch, make_patch};
+
+        let polygon = |id: &str, draw_order: i32| Polygon {
+            polygon_id: id.to_string(),
+            points: vec![],
+            properties: Default::default(),
+            draw_order,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+        let before = MapAnimation {
+            animation_id: "anim".to_string(),
+            name: "Patched".to_string(),
+            total_frames: 10,
+            polygons: vec![polygon("a", 0), polygon("b", 0), polygon("c", 0)],
+            grid_layers: vec![],
+            camera_keyframes: vec![],
+            data_series: vec![],
+            scenes: vec![],
+            format_version: 0,
+        };
+        let mut after = before.clone();
+        after.total_frames = 20;
+        after.polygons = vec![polygon("c", 0), polygon("a", 5), polygon("d", 0)];
+
+        let patch = make_patch(&before, &after);
+        assert_eq!(patch.removed_features, vec!["b"]);
+        let sent: Vec<&str> = patch.features.iter().map(|p| p.polygon_id.as_str()).collect();
+        assert_eq!(sent, vec!["a", "d"]);
+        assert!(patch.name.is_none() && patch.feature_order.is_some());
+
+        let mut patched = before.clone();
+        apply_patch(&mut patched, &patch).unwrap();
+        assert_eq!(patched, after);
+        assert!(make_patch(&after, &patched).is_empty());
+
+        // Patches made against another version are refused without changing anything
+        let mut other = after.clone();
+        assert!(apply_patch(&mut other, &patch).is_err());
+        assert_eq!(other, after);
+    }
+
+    #[test]
+    fn test_frame_range_buffers_pack_each_frame() {
+        use crate::protobuf_gen::Vector;
+        use crate::render::{build_frame_range_buffers, build_vertex_buffers};
+
+        // A point that appears at frame 1, next to one that is always there
+        let point = |id: &str, start_frame: Option<i32>| AnimatedPoint {
+            point_id: id.to_string(),
+            initial_position: Some(Point {
+                x: 0.0,
+                y: 5.0,
+                z: Some(0.0),
+            }),
+            movements: vec![Vector {
+                dx: 1.0,
+                dy: 0.0,
+                dz: None,
+            }],
+            end_frame: None,
+            start_frame,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
+        };
+        let polygon = Polygon {
+            polygon_id: "line".to_string(),
+            points: vec![point("a", None), point("b", Some(1))],
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: FeatureType::Polyline as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+        let all = std::slice::from_ref(&polygon);
+
+        let packed = build_frame_range_buffers(all, &[&polygon], (0..3).step_by(2), None);
+        assert_eq!(packed.frames, vec![0, 2]);
+        assert_eq!(packed.vertex_starts, vec![0, 1]);
+        assert_eq!(packed.feature_starts, vec![0, 1]);
+        assert_eq!(packed.buffers.vertex_counts, vec![1, 2]);
+        let frame_2 = build_vertex_buffers(all, &[&polygon], 2, None);
+        assert_eq!(&packed.buffers.positions[3..], &frame_2.positions[..]);
+    }
+
+    #[test]
+    fn test_geojson_simple_polygon_is_closed_and_ccw() {
+        use crate::geojson::geometry;
+
+        // Listed clockwise; the writer flips it to the counter-clockwise RFC 7946 order
+        let square = [
+            globe_point(-10.0, -10.0),
+            globe_point(10.0, -10.0),
+            globe_point(10.0, 10.0),
+            globe_point(-10.0, 10.0),
+        ];
+        let geojson = geometry(FeatureType::Polygon, &square).unwrap();
+        assert_eq!(geojson["type"], "Polygon");
+        let ring = geojson["coordinates"][0].as_array().unwrap();
+        assert_eq!(ring.len(), 5);
+        assert_eq!(ring[0], ring[4]);
+        let lon_lat = |i: usize| (ring[i][0].as_f64().unwrap(), ring[i][1].as_f64().unwrap());
+        let area: f64 = (0..4)
+            .map(|i| {
+                let ((x0, y0), (x1, y1)) = (lon_lat(i), lon_lat(i + 1));
+                x0 * y1 - x1 * y0
+            })
+            .sum();
+        assert!(area > 0.0);
+    }
+
+    #[test]
+    fn test_geojson_splits_at_antimeridian() {
+        use crate::geojson::geometry;
+
+        let square = [
+            globe_point(-10.0, 170.0),
+            globe_point(-10.0, -170.0),
+            globe_point(10.0, -170.0),
+            globe_point(10.0, 170.0),
+        ];
+        let geojson = geometry(FeatureType::Polygon, &square).unwrap();
+        assert_eq!(geojson["type"], "MultiPolygon");
+        let pieces = geojson["coordinates"].as_array().unwrap();
+        assert_eq!(pieces.len(), 2);
+        for piece in pieces {
+            let ring = piece[0].as_array().unwrap();
+            assert_eq!(ring.first(), ring.last());
+            let lons: Vec<f64> = ring.iter().map(|p| p[0].as_f64().unwrap()).collect();
+            // Each piece stays on one side and touches the antimeridian
+            assert!(lons.iter().all(|lon| *lon > 169.9) || lons.iter().all(|lon| *lon < -169.9));
+            assert!(lons.iter().any(|lon| lon.abs() == 180.0));
+        }
+
+        let line = [globe_point(0.0, 175.0), globe_point(5.0, -175.0)];
+        let geojson = geometry(FeatureType::Polyline, &line).unwrap();
+        assert_eq!(geojson["type"], "MultiLineString");
+        let parts = geojson["coordinates"].as_array().unwrap();
+        assert_eq!(parts[0][1][0], 180.0);
+        assert_eq!(parts[1][0][0], -180.0);
+        assert_eq!(parts[0][1][1], parts[1][0][1]);
+    }
+
+    #[test]
+    fn test_geojson_closes_polar_polygon_through_pole() {
+        use crate::geojson::geometry;
+
+        let cap: Vec<[f32; 3]> = [-135.0, -45.0, 45.0, 135.0]
+            .iter()
+            .map(|lon| globe_point(80.0, *lon))
+            .collect();
+        let geojson = geometry(FeatureType::Polygon, &cap).unwrap();
+        assert_eq!(geojson["type"], "Polygon");
+        let ring = geojson["coordinates"][0].as_array().unwrap();
+        assert_eq!(ring.first(), ring.last());
+        let has = |lon: f64, lat: f64| {
+            ring.iter()
+                .any(|p| p[0].as_f64() == Some(lon) && p[1].as_f64() == Some(lat))
+        };
+        assert!(has(180.0, 90.0) && has(-180.0, 90.0));
+        assert!(ring.iter().all(|p| p[1].as_f64().unwrap() > 0.0));
+
+        // The same ring around the south pole closes through the south pole
+        let cap: Vec<[f32; 3]> = [-135.0, -45.0, 45.0, 135.0]
+            .iter()
+            .map(|lon| globe_point(-80.0, *lon))
+            .collect();
+        let geojson = geometry(FeatureType::Polygon, &cap).unwrap();
+        let ring = geojson["coordinates"][0].as_array().unwrap();
+        assert!(ring.iter().any(|p| p[1].as_f64() == Some(-90.0)));
+    }
+
+    #[test]
+    fn test_geojson_frame_collection_follows_points_in_draw_order() {
+        use crate::geojson::frame_collection;
+
+        let point_feature = |id: &str, draw_order: i32, start_frame: Option<i32>| {
+            let [x, y, z] = globe_point(10.0, 20.0);
+            Polygon {
+                polygon_id: id.to_string(),
+                points: vec![AnimatedPoint {
+                    point_id: format!("{}-pt", id),
+                    initial_position: Some(Point { x, y, z: Some(z) }),
+                    start_frame,
+                    ..Default::default()
+                }],
+                draw_order,
+                feature_type: FeatureType::Point as i32,
+                ..Default::default()
+            }
+        };
+        let polygons = vec![
+            point_feature("late", 0, Some(5)),
+            point_feature("top", 2, None),
+            point_feature("bottom", 1, None),
+        ];
+
+        let ids = |frame: i32| -> Vec<String> {
+            frame_collection(&polygons, frame)["features"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|feature| feature["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        // The late feature has no point until frame 5
+        assert_eq!(ids(0), vec!["bottom", "top"]);
+        assert_eq!(ids(5), vec!["late", "bottom", "top"]);
+        let collection = frame_collection(&polygons, 5);
+        assert_eq!(collection["type"], "FeatureCollection");
+        assert_eq!(collection["features"][0]["geometry"]["type"], "Point");
+    }
+
+    #[test]
+    fn test_geojson_import_reads_each_geometry_part() {
+        use crate::geojson::import_features;
+        use crate::geometry::to_lat_lon;
+
+        let collection = serde_json::json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "id": "island",
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [[[0, 0], [10, 0], [10, 10], [0, 0]], [[2, 2], [3, 2], [3, 3], [2, 2]]]
+                    },
+                    "properties": { "name": "Isle", "height": 12 }
+                },
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "MultiPoint", "coordinates": [[20, 5], [30, -5, 100]] },
+                    "properties": null
+                },
+                { "type": "Feature", "id": "island", "geometry": { "type": "LineString", "coordinates": [[0, 0], [1, 1]] } }
+            ]
+        });
+        let features = import_features(&collection, 5.0).unwrap();
+        let ids: Vec<&str> = features.iter().map(|f| f.polygon_id.as_str()).collect();
+        assert_eq!(ids, ["island", "geojson-1-0", "geojson-1-1", "island-dup"]);
+
+        // The closing position and the hole are dropped
+        let island = &features[0];
+        assert_eq!(island.feature_type(), FeatureType::Polygon);
+        assert_eq!(island.points.len(), 3);
+        assert_eq!(island.properties["name"], "Isle");
+        assert_eq!(island.properties["height"], "12");
+        let corner = island.points[1].initial_position.as_ref().unwrap();
+        let (lat, lon) = to_lat_lon([corner.x, corner.y, corner.z.unwrap()]).unwrap();
+        assert!(lat.abs() < 1e-4 && (lon - 10.0).abs() < 1e-4);
+
+        assert_eq!(features[2].feature_type(), FeatureType::Point);
+        assert_eq!(features[3].feature_type(), FeatureType::Polyline);
+
+        let geometry = serde_json::json!({ "type": "Point", "coordinates": [0, 95] });
+        assert!(import_features(&geometry, 5.0).is_err());
+        let geometry = serde_json::json!({ "type": "LineString", "coordinates": [[0, 0]] });
+        assert!(import_features(&geometry, 5.0).is_err());
+        assert!(import_features(&serde_json::json!({ "features": [] }), 5.0).is_err());
+    }
+
+    #[test]
+    fn test_gpx_tracks_become_markers_on_one_clock() {
+        use crate::geometry::to_lat_lon;
+        use crate::gpx::import_tracks;
+        use crate::interpolation::position_at_frame;
+
+        // The ship sails 0..100 s, the boat sets off at 50 s; 11 frames make 10 s each
+        let gpx = r#"<?xml version="1.0"?>
+            <gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+              <wpt lat="1" lon="1"><name>Ignored</name></wpt>
+              <trk><name>HMS Beagle</name>
+                <trkseg>
+                  <trkpt lat="0" lon="0"><time>2024-01-01T00:00:00Z</time></trkpt>
+                  <trkpt lat="0" lon="10"><time>2024-01-01T00:00:50Z</time></trkpt>
+                </trkseg>
+                <trkseg>
+                  <trkpt lat="10" lon="10"><time>2024-01-01T00:01:40Z</time></trkpt>
+                </trkseg>
+              </trk>
+              <trk>
+                <trkpt lat="-5" lon="20"><time>2024-01-01T01:00:50+01:00</time></trkpt>
+                <trkpt lat="-5" lon="30"><time>2024-01-01T00:01:00Z</time></trkpt>
+              </trk>
+            </gpx>"#;
+        let markers = import_tracks(gpx, 11, 5.0).unwrap();
+        assert_eq!(markers.len(), 2);
+        let (ship, boat) = (&markers[0], &markers[1]);
+        assert_eq!(ship.polygon_id, "hms-beagle");
+        assert_eq!(ship.properties["name"], "HMS Beagle");
+        assert_eq!(ship.feature_type(), FeatureType::Point);
+        assert_eq!(boat.polygon_id, "track-1");
+
+        let lat_lon = |polygon: &Polygon, frame: i32| {
+            let p = position_at_frame(&polygon.points[0], frame)?;
+            to_lat_lon([p.x, p.y, p.z.unwrap()])
+        };
+        let close = |(lat, lon): (f32, f32), expected: (f32, f32)| {
+            (lat - expected.0).abs() < 0.01 && (lon - expected.1).abs() < 0.01
+        };
+        assert!(close(lat_lon(ship, 0).unwrap(), (0.0, 0.0)));
+        assert!(close(lat_lon(ship, 5).unwrap(), (0.0, 10.0)));
+        assert!(close(lat_lon(ship, 10).unwrap(), (10.0, 10.0)));
+        // Two fifths of the way from the first logged point to the second
+        assert!(close(lat_lon(ship, 2).unwrap(), (0.0, 4.0)));
+
+        // The boat appears at its first timestamp and stays once it arrives
+        assert_eq!(boat.points[0].start_frame, Some(5));
+        assert!(lat_lon(boat, 4).is_none());
+        assert!(close(lat_lon(boat, 5).unwrap(), (-5.0, 20.0)));
+        assert!(close(lat_lon(boat, 10).unwrap(), (-5.0, 30.0)));
+
+        assert!(import_tracks("<kml/>", 10, 5.0).is_err());
+        let untimed = r#"<gpx><trk><trkpt lat="0" lon="0"/></trk></gpx>"#;
+        assert!(import_tracks(untimed, 10, 5.0).is_err());
+        assert!(import_tracks("<gpx></gpx>", 10, 5.0).is_err());
+    }
+
+    #[test]
+    fn test_gpx_tracks_are_bounded() {
+        use crate::gpx::import_tracks;
+        use crate::point_series::MAX_FRAME;
+        use crate::tracks::{MAX_MOVEMENTS, MAX_TRACKS};
+
+        let track =
+            r#"<trk><trkpt lat="0" lon="0"><time>2024-01-01T00:00:00Z</time></trkpt></trk>"#;
+        let gpx = |count: usize| format!("<gpx>{}</gpx>", track.repeat(count));
+
+        // Each track moves on every frame, however short it is
+        let frames = MAX_FRAME as i32 + 1;
+        let count = MAX_MOVEMENTS / MAX_FRAME as usize + 1;
+        let error = import_tracks(&gpx(count), frames, 5.0).unwrap_err();
+        assert!(error.contains("movements"), "{}", error);
+        assert_eq!(import_tracks(&gpx(count), 10, 5.0).unwrap().len(), count);
+
+        let error = import_tracks(&gpx(MAX_TRACKS + 1), 10, 5.0).unwrap_err();
+        assert!(error.contains("tracks"), "{}", error);
+    }
+
+    #[test]
+    fn test_csv_point_series_by_frame_and_by_time() {
+        use crate::geometry::to_lat_lon;
+        use crate::interpolation::position_at_frame;
+        use crate::point_series::import_point_series;
+
+        let lat_lon = |polygon: &Polygon, frame: i32| {
+            let p = position_at_frame(&polygon.points[0], frame)?;
+            to_lat_lon([p.x, p.y, p.z.unwrap()])
+        };
+        let close = |(lat, lon): (f32, f32), expected: (f32, f32)| {
+            (lat - expected.0).abs() < 0.01 && (lon - expected.1).abs() < 0.01
+        };
+
+        // Columns in any order and case, extra ones ignored, rows in any order
+        let csv = "Longitude,Frame,note,LAT,ID\n\
+                   10,10,arrives,0,Buoy A\n\
+                   0,0,,0,Buoy A\n\
+                   \n\
+                   -20,4,,5,buoy-b\n";
+        let (markers, frames) = import_point_series(csv, 100, 5.0).unwrap();
+        assert_eq!(frames, 11);
+        let ids: Vec<&str> = markers.iter().map(|m| m.polygon_id.as_str()).collect();
+        assert_eq!(ids, ["buoy-a", "buoy-b"]);
+        assert_eq!(markers[0].properties["name"], "Buoy A");
+        assert!(close(lat_lon(&markers[0], 5).unwrap(), (0.0, 5.0)));
+        assert!(close(lat_lon(&markers[0], 10).unwrap(), (0.0, 10.0)));
+        assert!(lat_lon(&markers[1], 3).is_none());
+        assert!(close(lat_lon(&markers[1], 10).unwrap(), (5.0, -20.0)));
+
+        // Times are spread over the frames asked for, whatever their format
+        let csv = "id,time,lat,lon\n\
+                   a,2024-01-01T00:00:00Z,0,0\n\
+                   a,2024-01-01 00:01:40,0,20\n";
+        let (markers, frames) = import_point_series(csv, 11, 5.0).unwrap();
+        assert_eq!(frames, 11);
+        assert!(close(lat_lon(&markers[0], 5).unwrap(), (0.0, 10.0)));
+
+        let error = import_point_series("id,lat,lon\na,0,0\n", 10, 5.0).unwrap_err();
+        assert!(error.contains("frame or time"), "{}", error);
+        let error = import_point_series("id,frame,lat,lon\na,0,0,0\na,1,95,0\n", 10, 5.0)
+            .unwrap_err();
+        assert!(error.starts_with("Line 3"), "{}", error);
+        assert!(import_point_series("id,frame,lat,lon\na,-1,0,0\n", 10, 5.0).is_err());
+        assert!(import_point_series("id,frame,lat,lon\n", 10, 5.0).is_err());
+    }
+
+    #[test]
+    fn test_csv_point_series_are_bounded() {
+        use crate::point_series::{import_point_series, MAX_FRAME};
+        use crate::tracks::{MAX_MOVEMENTS, MAX_TRACKS};
+
+        // Every id starting at frame 0 moves on each frame up to the one late row
+        let mut csv = format!("id,frame,lat,lon\nlate,{},0,0\n", MAX_FRAME);
+        let ids = MAX_MOVEMENTS / MAX_FRAME as usize + 1;
+        for id in 0..ids {
+            csv.push_str(&format!("{},0,0,0\n", id));
+        }
+        let error = import_point_series(&csv, 10, 5.0).unwrap_err();
+        assert!(error.contains("movements"), "{}", error);
+
+        let mut csv = "id,frame,lat,lon\n".to_string();
+        for id in 0..=MAX_TRACKS {
+            csv.push_str(&format!("{},0,0,0\n", id));
+        }
+        let error = import_point_series(&csv, 10, 5.0).unwrap_err();
+        assert!(error.contains("ids"), "{}", error);
+
+        // Many ids on a short timeline are fine
+        let mut csv = "id,frame,lat,lon\n".to_string();
+        for id in 0..MAX_TRACKS {
+            csv.push_str(&format!("{},0,0,0\n{},9,0,1\n", id, id));
+        }
+        let (markers, frames) = import_point_series(&csv, 10, 5.0).unwrap();
+        assert_eq!((markers.len(), frames), (MAX_TRACKS, 10));
+    }
+
+    #[test]
+    fn test_data_series_values_and_labels_per_frame() {
+        use crate::data_series::{bind_samples, readings_at_frame, upsert_keyframe};
+        use crate::patch::{apply_patch, make_patch};
+        use crate::protobuf_gen::{DataKeyframe, DataSeries};
+
+        let keyframe = |frame: i32, value: f32, label: Option<&str>| DataKeyframe {
+            frame,
+            value,
+            label: label.map(str::to_string),
+        };
+        let mut years = DataSeries {
+            series_id: "year".to_string(),
+            name: "Year".to_string(),
+            ..Default::default()
+        };
+        upsert_keyframe(&mut years, keyframe(10, 1900.0, Some("1900s")));
+        upsert_keyframe(&mut years, keyframe(0, 1850.0, Some("1850s")));
+        upsert_keyframe(&mut years, keyframe(20, 1950.0, None));
+        let mut co2 = DataSeries {
+            series_id: "co2".to_string(),
+            name: "CO2".to_string(),
+            unit: "ppm".to_string(),
+            ..Default::default()
+        };
+
+        // Bound by year: 1850 on frame 0, 1950 on frame 20; 2000 is past the end
+        let count = bind_samples(
+            &mut co2,
+            &[1850.0, 1900.0, 1950.0, 2000.0],
+            &[285.0, 296.0, 311.0, 369.0],
+            1850.0,
+            1950.0,
+            21,
+        )
+        .unwrap();
+        assert_eq!(count, 3);
+        assert!(bind_samples(&mut co2.clone(), &[1850.0], &[], 1850.0, 1950.0, 21).is_err());
+        assert!(bind_samples(&mut co2.clone(), &[], &[], 1950.0, 1850.0, 21).is_err());
+
+        let series = vec![years, co2, DataSeries::default()];
+        let readings = readings_at_frame(&series, 5);
+        assert_eq!(readings.len(), 2, "series without values are left out");
+        assert_eq!(readings[0].value, 1875.0);
+        assert_eq!(readings[0].label.as_deref(), Some("1850s"));
+        assert_eq!(readings[1].value, 290.5);
+        assert_eq!(readings[1].unit, "ppm");
+        assert_eq!(readings[1].label, None);
+        // Labels hold until the next one; values hold past the last keyframe
+        let readings = readings_at_frame(&series, 30);
+        assert_eq!(readings[0].label.as_deref(), Some("1900s"));
+        assert_eq!(readings[0].value, 1950.0);
+        assert_eq!(readings[1].value, 311.0);
+
+        // Series travel in patches like the rest of the document
+        let before = MapAnimation::default();
+        let after = MapAnimation {
+            data_series: series,
+            ..Default::default()
+        };
+        let mut patched = before.clone();
+        apply_patch(&mut patched, &make_patch(&before, &after)).unwrap();
+        assert_eq!(patched, after);
+    }
+
+    #[test]
+    fn test_scenes_step_like_slides() {
+        use crate::protobuf_gen::Scene;
+        use crate::scenes::{next_scene, previous_scene, scene_at_frame, upsert_scene};
+
+        let scene = |id: &str, start_frame: i32, end_frame: i32| Scene {
+            scene_id: id.to_string(),
+            title: id.to_uppercase(),
+            start_frame,
+            end_frame,
+            ..Default::default()
+        };
+        let mut scenes = vec![];
+        upsert_scene(&mut scenes, scene("late", 50, 80)).unwrap();
+        upsert_scene(&mut scenes, scene("early", 0, 20)).unwrap();
+        upsert_scene(&mut scenes, scene("middle", 20, 40)).unwrap();
+        let ids = |scenes: &[Scene]| -> Vec<String> {
+            scenes.iter().map(|s| s.scene_id.clone()).collect()
+        };
+        assert_eq!(ids(&scenes), ["early", "middle", "late"]);
+
+        assert!(upsert_scene(&mut scenes, scene("overlap", 30, 60)).is_err());
+        assert!(upsert_scene(&mut scenes, scene("empty", 45, 45)).is_err());
+        // Moving a scene may overlap where it used to be
+        upsert_scene(&mut scenes, scene("middle", 25, 45)).unwrap();
+        assert_eq!(ids(&scenes), ["early", "middle", "late"]);
+
+        let id = |scene: Option<&Scene>| scene.map(|s| s.scene_id.clone());
+        assert_eq!(id(scene_at_frame(&scenes, 19)).as_deref(), Some("early"));
+        assert_eq!(id(scene_at_frame(&scenes, 22)), None);
+        assert_eq!(id(scene_at_frame(&scenes, 79)).as_deref(), Some("late"));
+        assert_eq!(id(scene_at_frame(&scenes, 80)), None);
+
+        assert_eq!(id(next_scene(&scenes, 0)).as_deref(), Some("middle"));
+        assert_eq!(id(next_scene(&scenes, 47)).as_deref(), Some("late"));
+        assert_eq!(id(next_scene(&scenes, 50)), None);
+        // Back from a scene goes to the one before it; from a gap, to the last one started
+        assert_eq!(id(previous_scene(&scenes, 60)).as_deref(), Some("middle"));
+        assert_eq!(id(previous_scene(&scenes, 47)).as_deref(), Some("middle"));
+        assert_eq!(id(previous_scene(&scenes, 10)), None);
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        use crate::editing::snap_point;
+        use crate::geometry::{length, snap_to_grid, to_lat_lon};
+
+        let snapped = snap_to_grid(globe_point(12.3, -47.6), 5.0);
+        let (lat, lon) = to_lat_lon(snapped).unwrap();
+        assert!((lat - 10.0).abs() < 1e-3 && (lon + 50.0).abs() < 1e-3);
+        assert!((length(snapped) - 5.0).abs() < 1e-4);
+        // Latitudes never snap past the poles
+        let (lat, _) = to_lat_lon(snap_to_grid(globe_point(88.0, 10.0), 7.0)).unwrap();
+        assert!((lat - 90.0).abs() < 1e-3);
+        assert_eq!(snap_to_grid([0.0, 0.0, 0.0], 5.0), [0.0, 0.0, 0.0]);
+
+        let [x, y, z] = globe_point(31.0, 59.0);
+        let mut point = AnimatedPoint {
+            initial_position: Some(Point { x, y, z: Some(z) }),
+            ..Default::default()
+        };
+        snap_point(&mut point, 10.0);
+        let position = point.initial_position.unwrap();
+        let (lat, lon) = to_lat_lon([position.x, position.y, position.z.unwrap()]).unwrap();
+        assert!((lat - 30.0).abs() < 1e-3 && (lon - 60.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_pinned_point_follows_target_outline() {
+        use crate::geometry::{length, to_lat_lon};
+        use crate::protobuf_gen::TransformKeyframe;
+        use crate::transform::{upsert_keyframe, world_points_at_frame};
+
+        let feature = |id: &str, feature_type: FeatureType, points: Vec<AnimatedPoint>| Polygon {
+            polygon_id: id.to_string(),
+            points,
+            properties: Default::default(),
+            draw_order: 0,
+            feature_type: feature_type as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
+        };
+        let point = |lat: f32, lon: f32, pinned_feature_id: Option<&str>| {
+            let [x, y, z] = globe_point(lat, lon);
+            AnimatedPoint {
+                initial_position: Some(Point { x, y, z: Some(z) }),
+                pinned_feature_id: pinned_feature_id.map(str::to_string),
+                ..Default::default()
+            }
+        };
+        // A coastline along the equator, turning 90 degrees over ten frames
+        let mut coast = feature(
+            "coast",
+            FeatureType::Polyline,
+            vec![point(0.0, 0.0, None), point(0.0, 20.0, None)],
+        );
+        for (frame, angle_degrees) in [(0, 0.0), (10, 90.0)] {
+            let axis = Some(Point {
+                x: 0.0,
+                y: 1.0,
+                z: Some(0.0),
+            });
+            upsert_keyframe(
+                &mut coast,
+                TransformKeyframe {
+                    frame,
+                    axis,
+                    angle_degrees,
+                },
+            );
+        }
+        let river = feature(
+            "river",
+            FeatureType::Polyline,
+            vec![point(3.0, 10.0, Some("coast")), point(20.0, 10.0, None)],
+        );
+        let features = vec![coast, river];
+
+        let mouth = world_points_at_frame(&features, &features[1], 0)[0].1;
+        let (lat, lon) = to_lat_lon(mouth).unwrap();
+        assert!(lat.abs() < 1e-3 && (lon - 10.0).abs() < 1e-3);
+        assert!((length(mouth) - 5.0).abs() < 1e-4);
+
+        // Once the coast has turned away, the mouth sits on its nearest end
+        let mouth = world_points_at_frame(&features, &features[1], 10)[0].1;
+        let coast_end = world_points_at_frame(&features, &features[0], 10)[0].1;
+        for (actual, expected) in mouth.iter().zip(coast_end) {
+            assert!((actual - expected).abs() < 1e-4, "{:?}", mouth);
+        }
+
+        // Pins to missing features leave the point where it is
+        let mut orphan = features[1].clone();
+        orphan.points[0].pinned_feature_id = Some("missing".to_string());
+        let (lat, _) = to_lat_lon(world_points_at_frame(&features, &orphan, 0)[0].1).unwrap();
+        assert!((lat - 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_mirror_polygon_across_equator() {
+        use crate::editing::unique_feature_id;
+        use crate::geometry::to_lat_lon;
+        use crate::mirror::mirror_polygon;
+        use crate::protobuf_gen::{TransformKeyframe, Vector};
+
+        let [x, y, z] = globe_point(30.0, 40.0);
+        let polygon = Polygon {
+            polygon_id: "island".to_string(),
+            points: vec![AnimatedPoint {
+                point_id: "island-pt0".to_string(),
+                initial_position: Some(Point { x, y, z: Some(z) }),
+                movements: vec![Vector {
+                    dx: 0.5,
+                    dy: 1.0,
+                    dz: None,
+                }],
+                ..Default::default()
+            }],
+            feature_type: FeatureType::Polygon as i32,
+            transform_keyframes: vec![TransformKeyframe {
+                frame: 0,
+                axis: Some(Point {
+                    x: 1.0,
+                    y: 0.0,
+                    z: Some(0.0),
+                }),
+                angle_degrees: 30.0,
+            }],
+            parent_id: Some("plate".to_string()),
+            ..Default::default()
+        };
+
+        let mirrored = mirror_polygon(&polygon, [0.0, 1.0, 0.0], "island-mirror");
+        assert_eq!(mirrored.polygon_id, "island-mirror");
+        assert_eq!(mirrored.parent_id, None);
+        let point = &mirrored.points[0];
+        assert_eq!(point.point_id, "island-mirror-pt0");
+        let position = point.initial_position.as_ref().unwrap();
+        let (lat, lon) = to_lat_lon([position.x, position.y, position.z.unwrap()]).unwrap();
+        assert!((lat + 30.0).abs() < 1e-3 && (lon - 40.0).abs() < 1e-3);
+        assert_eq!((point.movements[0].dx, point.movements[0].dy), (0.5, -1.0));
+        // The axis lies in the mirror plane, so it stays put and only the angle flips
+        let keyframe = &mirrored.transform_keyframes[0];
+        assert_eq!(keyframe.axis.as_ref().unwrap().x, 1.0);
+        assert_eq!(keyframe.angle_degrees, -30.0);
+
+        let polygons = vec![polygon, mirrored];
+        assert_eq!(unique_feature_id(&polygons, "lake"), "lake");
+        assert_eq!(
+            unique_feature_id(&polygons, "island-mirror"),
+            "island-mirror-2"
+        );
+    }
+
+    #[test]
+    fn test_delaunay_and_voronoi_of_octahedron() {
+        use crate::delaunay::{delaunay_triangles, voronoi_cells};
+        use crate::geometry::{cross, dot, length};
+
+        let points = [
+            [5.0, 0.0, 0.0],
+            [-5.0, 0.0, 0.0],
+            [0.0, 5.0, 0.0],
+            [0.0, -5.0, 0.0],
+            [0.0, 0.0, 5.0],
+            [0.0, 0.0, -5.0],
+            [0.0, 0.0, 5.0], // Duplicate, left out
+        ];
+        let triangles = delaunay_triangles(&points);
+        assert_eq!(triangles.len(), 8);
+        assert!(triangles.iter().all(|t| !t.contains(&6)));
+        for [a, b, c] in &triangles {
+            // Wound counter-clockwise seen from outside
+            let (pa, pb, pc) = (points[*a], points[*b], points[*c]);
+            let ab = [pb[0] - pa[0], pb[1] - pa[1], pb[2] - pa[2]];
+            let ac = [pc[0] - pa[0], pc[1] - pa[1], pc[2] - pa[2]];
+            assert!(dot(cross(ab, ac), pa) > 0.0);
+        }
+
+        // Each vertex's cell is the square of the four face centers around it
+        let cells = voronoi_cells(&points, &triangles);
+        assert_eq!(cells.len(), 6);
+        let (_, north) = cells.iter().find(|(i, _)| *i == 2).unwrap();
+        assert_eq!(north.len(), 4);
+        for corner in north {
+            assert!((length(*corner) - 5.0).abs() < 1e-4);
+            assert!((corner[1] - 5.0 / 3f32.sqrt()).abs() < 1e-4);
+        }
+
+        // Points all over the globe: a triangulated sphere has 2n - 4 faces
+        let spiral: Vec<[f32; 3]> = (0..200)
+            .map(|i| {
+                let lat = (1.0 - (2 * i + 1) as f32 / 200.0).asin().to_degrees();
+                globe_point(lat, (i as f32 * 137.508) % 360.0 - 180.0)
+            })
+            .collect();
+        let triangles = delaunay_triangles(&spiral);
+        assert_eq!(triangles.len(), 2 * spiral.len() - 4);
+        assert_eq!(voronoi_cells(&spiral, &triangles).len(), spiral.len());
+
+        // Points on one great circle can't be triangulated
+        let ring: Vec<[f32; 3]> = (0..6).map(|i| globe_point(0.0, i as f32 * 60.0)).collect();
+        assert!(delaunay_triangles(&ring).is_empty());
+
+        // Within a hemisphere, only the cap's own triangles remain and rim cells are open
+        let cap: Vec<[f32; 3]> = [
+            (90.0, 0.0),
+            (60.0, 0.0),
+            (60.0, 90.0),
+            (60.0, 180.0),
+            (60.0, -90.0),
+        ]
+        .iter()
+        .map(|(lat, lon)| globe_point(*lat, *lon))
+        .collect();
+        let triangles = delaunay_triangles(&cap);
+        assert_eq!(triangles.len(), 4);
+        let cells = voronoi_cells(&cap, &triangles);
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0].0, 0);
+    }
+
+    #[test]
+    fn test_input_smoother_damps_jitter() {
+        use crate::geometry::{length, to_lat_lon};
+        use crate::smoothing::InputSmoother;
+
+        let mut smoother = InputSmoother::new(0.25);
+        assert_eq!(
+            smoother.filter(globe_point(0.0, 0.0)),
+            globe_point(0.0, 0.0)
+        );
+        // A sample jumping 4 degrees north only moves the line about a quarter of the way
+        let filtered = smoother.filter(globe_point(4.0, 1.0));
+        let (lat, lon) = to_lat_lon(filtered).unwrap();
+        assert!((lat - 1.0).abs() < 0.01 && (lon - 0.25).abs() < 0.01);
+        assert!((length(filtered) - 5.0).abs() < 1e-4);
+
+        smoother.start_stroke();
+        assert_eq!(
+            smoother.filter(globe_point(30.0, 30.0)),
+            globe_point(30.0, 30.0)
+        );
+    }
+
+    #[test]
+    fn test_timeline_density_per_frame() {
+        use crate::protobuf_gen::{CameraKeyframe, Vector};
+        use crate::stats::timeline_density;
+
+        let point = |start_frame: Option<i32>, end_frame: Option<i32>, movements: Vec<Vector>| {
+            AnimatedPoint {
+                initial_position: Some(Point::default()),
+                start_frame,
+                end_frame,
+                movements,
+                ..Default::default()
+            }
+        };
+        let step = Vector {
+            dx: 1.0,
+            dy: 0.0,
+            dz: None,
+        };
+        let animation = MapAnimation {
+            total_frames: 3,
+            polygons: vec![
+                Polygon {
+                    points: vec![point(None, None, vec![step.clone()])],
+                    ..Default::default()
+                },
+                // Joins at frame 1; only leaves once both points are gone
+                Polygon {
+                    points: vec![
+                        point(Some(1), Some(2), vec![]),
+                        point(Some(2), Some(4), vec![]),
+                    ],
+                    ..Default::default()
+                },
+            ],
+            camera_keyframes: vec![CameraKeyframe {
+                frame: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let density = timeline_density(&animation);
+        assert_eq!(density.frame_count, 5);
+        assert_eq!(density.keyframes, vec![1, 3, 1, 0, 0]);
+        assert_eq!(density.appearing, vec![1, 1, 0, 0, 0]);
+        assert_eq!(density.disappearing, vec![0, 0, 0, 0, 1]);
+
+        // Frames anywhere in i32's range neither overflow nor size the counts
+        let far = MapAnimation {
+            total_frames: 0,
+            camera_keyframes: vec![
+                CameraKeyframe {
+                    frame: i32::MAX,
+                    ..Default::default()
+                },
+                CameraKeyframe {
+                    frame: 2,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let density = timeline_density(&far);
+        assert_eq!(density.frame_count, i32::MAX as usize);
+        assert_eq!(density.keyframes.len(), crate::stats::MAX_DENSITY_FRAMES);
+        assert_eq!(density.keyframes.iter().sum::<u32>(), 1);
+        assert_eq!(density.keyframes[2], 1);
+    }
+
+    #[test]
+    fn test_lazy_document_defers_points() {
+        use crate::lazy_load::LazyDocument;
+
+        let polygon = |id: &str, point_count: usize| Polygon {
+            polygon_id: id.to_string(),
+            points: (0..point_count)
+                .map(|i| AnimatedPoint {
+                    point_id: format!("{}-pt{}", id, i),
+                    initial_position: Some(Point {
+                        x: i as f32,
+                        y: 2.0,
+                        z: Some(3.0),
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+            draw_order: 4,
+            parent_id: Some("plate".to_string()),
+            ..Default::default()
+        };
+        let animation = MapAnimation {
+            animation_id: "anim-1".to_string(),
+            name: "Browsed".to_string(),
+            total_frames: 120,
+            polygons: vec![polygon("a", 3), polygon("b", 0), polygon("c", 2)],
+            ..Default::default()
+        };
+        let bytes = animation.encode_to_vec();
+
+        let mut lazy = LazyDocument::decode(&bytes).unwrap();
+        assert_eq!(lazy.header().name, "Browsed");
+        assert_eq!(lazy.header().polygons.len(), 3);
+        assert!(lazy.header().polygons.iter().all(|p| p.points.is_empty()));
+        assert_eq!(
+            lazy.header().polygons[2].parent_id.as_deref(),
+            Some("plate")
+        );
+        assert_eq!(lazy.point_count(0), 3);
+        assert_eq!(lazy.point_count(1), 0);
+
+        assert_eq!(lazy.materialize(2).unwrap(), &animation.polygons[2]);
+        assert_eq!(lazy.point_count(2), 2);
+        assert_eq!(lazy.into_animation().unwrap(), animation);
+
+        assert!(LazyDocument::decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+    #[test]
+    fn test_render_orthographic_draws_visible_side() {
+        use crate::protobuf_gen::{
+            AnimatedPoint, CameraKeyframe, FeatureType, MapAnimation, Point, Polygon,
+        };
+        use crate::raster::{render_orthographic, BACKGROUND_COLOR, GLOBE_COLOR, MARKER_COLOR};
+
+        let feature = |id: &str, feature_type: FeatureType, corners: &[(f32, f32)]| Polygon {
+            polygon_id: id.to_string(),
+            feature_type: feature_type as i32,
+            points: corners
+                .iter()
+                .enumerate()
+                .map(|(i, &(lat, lon))| {
+                    let [x, y, z] = globe_point(lat, lon);
+                    AnimatedPoint {
+                        point_id: format!("{}-{}", id, i),
+                        initial_position: Some(Point { x, y, z: Some(z) }),
+                        ..Default::default()
+                    }
+                })
+                .collect(),
+            ..Default::default()
+        };
+        // The default view looks from +z, at longitude -90
+        let square = |lon: f32| {
+            vec![
+                (-20.0, lon - 20.0),
+                (-20.0, lon + 20.0),
+                (20.0, lon + 20.0),
+                (20.0, lon - 20.0),
+            ]
+        };
+        let mut animation = MapAnimation {
+            polygons: vec![
+                feature("front", FeatureType::Polygon, &square(-90.0)),
+                feature("back", FeatureType::Polygon, &square(90.0)),
+                feature("marker", FeatureType::Point, &[(45.0, -90.0)]),
+            ],
+            ..Default::default()
+        };
+
+        let raster = render_orthographic(&animation, 0, 64, 64);
+        assert_eq!(raster.pixels.len(), 64 * 64 * 4);
+        assert_eq!(raster.pixel(0, 0), BACKGROUND_COLOR);
+        assert_eq!(raster.pixel(32, 58), GLOBE_COLOR);
+        let center = raster.pixel(32, 32);
+        assert!(center[0] > GLOBE_COLOR[0] && center[2] == 0xff); // Translucent white fill
+        assert_eq!(raster.pixel(32, 10), MARKER_COLOR);
+
+        // From the other side, the back square shows and the marker is hidden
+        animation.camera_keyframes = vec![CameraKeyframe {
+            frame: 0,
+            position: Some(Point {
+                x: 0.0,
+                y: 0.0,
+                z: Some(-15.0),
+            }),
+            target: None,
+            zoom: 1.0,
+        }];
+        let raster = render_orthographic(&animation, 0, 64, 64);
+        assert_eq!(raster.pixel(32, 32), center);
+        assert_eq!(raster.pixel(32, 10), GLOBE_COLOR);
+    }
+
+    #[test]
+    fn test_format_upgrades_run_from_the_documents_version() {
+        use crate::format::{upgrade, upgrade_with, Upgrade, CURRENT_FORMAT_VERSION};
+
+        fn rename(animation: &mut MapAnimation) -> Result<(), String> {
+            animation.name.push_str(" v1");
+            Ok(())
+        }
+        fn stretch(animation: &mut MapAnimation) -> Result<(), String> {
+            animation.total_frames *= 2;
+            Ok(())
+        }
+        fn refuse(_: &mut MapAnimation) -> Result<(), String> {
+            Err("no polygons left".to_string())
+        }
+        let steps: &[Upgrade] = &[rename, stretch];
+        let document = |format_version| MapAnimation {
+            name: "Old".to_string(),
+            total_frames: 10,
+            format_version,
+            ..Default::default()
+        };
+
+        // Each step runs once, from the document's own version on
+        let mut oldest = document(0);
+        assert_eq!(upgrade_with(&mut oldest, steps), Ok(true));
+        assert_eq!((oldest.name.as_str(), oldest.total_frames), ("Old v1", 20));
+        assert_eq!(oldest.format_version, 2);
+        let mut newer = document(1);
+        assert_eq!(upgrade_with(&mut newer, steps), Ok(true));
+        assert_eq!((newer.name.as_str(), newer.total_frames), ("Old", 20));
+        let mut current = document(2);
+        assert_eq!(upgrade_with(&mut current, steps), Ok(false));
+        assert_eq!(current, document(2));
+
+        // Versions this build doesn't know, and failing steps, are reported
+        let error = upgrade_with(&mut document(3), steps).unwrap_err();
+        assert!(error.contains("format version 3"), "{}", error);
+        let error = upgrade_with(&mut document(1), &[rename, refuse]).unwrap_err();
+        assert!(error.contains("from format version 1"), "{}", error);
+        assert!(error.contains("no polygons left"), "{}", error);
+
+        // Documents in today's format are left alone
+        let mut today = document(CURRENT_FORMAT_VERSION);
+        assert_eq!(upgrade(&mut today), Ok(false));
+        assert_eq!(today, document(CURRENT_FORMAT_VERSION));
+    }
+}
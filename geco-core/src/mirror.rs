@@ -0,0 +1,60 @@
+// klyja/geco-core/src/mirror.rs
+// Reflection of features across a great circle (a plane through the globe center),
+// for symmetric stylized or fictional maps.
+use crate::geometry::{dot, Vec3};
+use crate::protobuf_gen::{Point, Polygon, Vector};
+
+/// Reflects `v` across the plane through the origin with unit normal `normal`.
+/// Linear, so it applies to positions and movement vectors alike.
+pub fn reflect(v: Vec3, normal: Vec3) -> Vec3 {
+    let d = 2.0 * dot(v, normal);
+    [
+        v[0] - d * normal[0],
+        v[1] - d * normal[1],
+        v[2] - d * normal[2],
+    ]
+}
+
+/// Reflects a point in place.
+pub fn reflect_point(point: &mut Point, normal: Vec3) {
+    let [x, y, z] = reflect([point.x, point.y, point.z.unwrap_or(0.0)], normal);
+    *point = Point { x, y, z: Some(z) };
+}
+
+/// A mirrored copy of `polygon` named `new_id`: positions, movements and rotation
+/// keyframes are all reflected, so the copy animates as the mirror image.
+/// Point IDs are rebased onto `new_id`. The copy is detached from any parent, since
+/// the parent's own rotation isn't mirrored. `normal` must be a unit vector.
+pub fn mirror_polygon(polygon: &Polygon, normal: Vec3, new_id: &str) -> Polygon {
+    let mut mirrored = polygon.clone();
+    mirrored.polygon_id = new_id.to_string();
+    mirrored.parent_id = None;
+    for point in &mut mirrored.points {
+        point.point_id = match point.point_id.strip_prefix(&polygon.polygon_id) {
+            Some(suffix) => format!("{}{}", new_id, suffix),
+            None => format!("{}-{}", new_id, point.point_id),
+        };
+        if let Some(position) = point.initial_position.as_mut() {
+            reflect_point(position, normal);
+        }
+        for movement in &mut point.movements {
+            let [dx, dy, dz] = reflect(
+                [movement.dx, movement.dy, movement.dz.unwrap_or(0.0)],
+                normal,
+            );
+            *movement = Vector {
+                dx,
+                dy,
+                dz: Some(dz),
+            };
+        }
+    }
+    // A reflected rotation turns the other way about the reflected axis
+    for keyframe in &mut mirrored.transform_keyframes {
+        if let Some(axis) = keyframe.axis.as_mut() {
+            reflect_point(axis, normal);
+        }
+        keyframe.angle_degrees = -keyframe.angle_degrees;
+    }
+    mirrored
+}
@@ -0,0 +1,147 @@
+// klyja/geco-core/src/patch.rs
+// Compact deltas between two versions of a document, so autosaves can send what
+// changed instead of the whole document. Features are the unit of change: an edited
+// feature is sent whole, untouched ones not at all. The backend applies patches with
+// the same code the editor makes them with.
+use crate::protobuf_gen::{CameraKeyframe, DataSeries, GridLayer, MapAnimation, Polygon, Scene};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct DocumentPatch {
+    pub animation_id: Option<String>,
+    pub name: Option<String>,
+    pub total_frames: Option<i32>,
+    pub removed_features: Vec<String>,
+    pub features: Vec<Polygon>, // Each replaces the feature with its ID, or is appended
+    pub feature_order: Option<Vec<String>>, // Only when features were reordered
+    pub grid_layers: Option<Vec<GridLayer>>, // Sent whole when any changed
+    pub camera_keyframes: Option<Vec<CameraKeyframe>>,
+    pub data_series: Option<Vec<DataSeries>>, // Sent whole when any changed
+    pub scenes: Option<Vec<Scene>>,
+}
+
+impl DocumentPatch {
+    pub fn is_empty(&self) -> bool {
+        *self == DocumentPatch::default()
+    }
+}
+
+/// The patch that turns `before` into `after`.
+pub fn make_patch(before: &MapAnimation, after: &MapAnimation) -> DocumentPatch {
+    let before_features: HashMap<&str, &Polygon> = before
+        .polygons
+        .iter()
+        .map(|polygon| (polygon.polygon_id.as_str(), polygon))
+        .collect();
+    let after_ids: HashSet<&str> = after
+        .polygons
+        .iter()
+        .map(|polygon| polygon.polygon_id.as_str())
+        .collect();
+    let changed = |old: &str, new: &str| (old != new).then(|| new.to_string());
+
+    let mut patch = DocumentPatch {
+        animation_id: changed(&before.animation_id, &after.animation_id),
+        name: changed(&before.name, &after.name),
+        total_frames: (before.total_frames != after.total_frames).then_some(after.total_frames),
+        removed_features: before
+            .polygons
+            .iter()
+            .filter(|polygon| !after_ids.contains(polygon.polygon_id.as_str()))
+            .map(|polygon| polygon.polygon_id.clone())
+            .collect(),
+        features: after
+            .polygons
+            .iter()
+            .filter(|polygon| before_features.get(polygon.polygon_id.as_str()) != Some(polygon))
+            .cloned()
+            .collect(),
+        feature_order: None,
+        grid_layers: (before.grid_layers != after.grid_layers).then(|| after.grid_layers.clone()),
+        camera_keyframes: (before.camera_keyframes != after.camera_keyframes)
+            .then(|| after.camera_keyframes.clone()),
+        data_series: (before.data_series != after.data_series).then(|| after.data_series.clone()),
+        scenes: (before.scenes != after.scenes).then(|| after.scenes.clone()),
+    };
+
+    // Upserts keep existing features in place and append new ones; say so if that isn't the order
+    let mut applied = before.clone();
+    if apply_patch(&mut applied, &patch).is_ok() && applied.polygons != after.polygons {
+        patch.feature_order = Some(
+            after
+                .polygons
+                .iter()
+                .map(|polygon| polygon.polygon_id.clone())
+                .collect(),
+        );
+    }
+    patch
+}
+
+/// Applies a patch made against this document. Fails, leaving the document as it
+/// was, if the patch names features the document doesn't have, which means it was
+/// made against another version.
+pub fn apply_patch(animation: &mut MapAnimation, patch: &DocumentPatch) -> Result<(), String> {
+    let mut polygons = animation.polygons.clone();
+    for feature_id in &patch.removed_features {
+        let index = polygons
+            .iter()
+            .position(|polygon| &polygon.polygon_id == feature_id)
+            .ok_or_else(|| format!("Patch removes unknown feature '{}'", feature_id))?;
+        polygons.remove(index);
+    }
+    for feature in &patch.features {
+        match polygons
+            .iter_mut()
+            .find(|polygon| polygon.polygon_id == feature.polygon_id)
+        {
+            Some(existing) => *existing = feature.clone(),
+            None => polygons.push(feature.clone()),
+        }
+    }
+    if let Some(order) = &patch.feature_order {
+        let mut by_id: HashMap<&str, &Polygon> = polygons
+            .iter()
+            .map(|polygon| (polygon.polygon_id.as_str(), polygon))
+            .collect();
+        let reordered = order
+            .iter()
+            .map(|feature_id| {
+                by_id
+                    .remove(feature_id.as_str())
+                    .cloned()
+                    .ok_or_else(|| format!("Patch orders unknown feature '{}'", feature_id))
+            })
+            .collect::<Result<Vec<Polygon>, String>>()?;
+        if !by_id.is_empty() {
+            return Err("Patch feature order leaves features out".to_string());
+        }
+        polygons = reordered;
+    }
+
+    animation.polygons = polygons;
+    if let Some(animation_id) = &patch.animation_id {
+        animation.animation_id = animation_id.clone();
+    }
+    if let Some(name) = &patch.name {
+        animation.name = name.clone();
+    }
+    if let Some(total_frames) = patch.total_frames {
+        animation.total_frames = total_frames;
+    }
+    if let Some(grid_layers) = &patch.grid_layers {
+        animation.grid_layers = grid_layers.clone();
+    }
+    if let Some(camera_keyframes) = &patch.camera_keyframes {
+        animation.camera_keyframes = camera_keyframes.clone();
+    }
+    if let Some(data_series) = &patch.data_series {
+        animation.data_series = data_series.clone();
+    }
+    if let Some(scenes) = &patch.scenes {
+        animation.scenes = scenes.clone();
+    }
+    Ok(())
+}
@@ -0,0 +1,159 @@
+// klyja/geco-core/src/point_series.rs
+// CSV point-series import: one row per position of a thing, with columns for its id,
+// when (a frame number or a time) and where (lat and lon in degrees). Rows sharing an
+// id become one marker feature moving through them (see tracks.rs); rows may come in
+// any order. Columns are found by their header, in any order and case, and others
+// are ignored:
+//   id:    id, name or track
+//   frame: frame
+//   time:  time, timestamp, date or datetime; RFC 3339, `YYYY-MM-DD HH:MM:SS` (UTC)
+//          or seconds
+//   lat:   lat or latitude
+//   lon:   lon, lng, long or longitude
+// With a frame column, frames are taken as given; otherwise times are spread over
+// the frames asked for.
+use crate::protobuf_gen::Polygon;
+use crate::tracks::{self, Timing, Track, TrackPoint, MAX_TRACKS};
+use chrono::{DateTime, NaiveDateTime};
+use std::collections::HashMap;
+
+/// Highest frame number a file may give, so a typo can't ask for a timeline of
+/// billions of frames.
+pub const MAX_FRAME: u32 = 100_000;
+
+/// Column positions found in the header.
+struct Columns {
+    id: usize,
+    when: usize,
+    lat: usize,
+    lon: usize,
+    frames: bool, // `when` holds frame numbers, not times
+}
+
+impl Columns {
+    fn find(header: &csv::StringRecord) -> Result<Self, String> {
+        let find = |names: &[&str]| {
+            header
+                .iter()
+                .position(|column| names.contains(&column.trim().to_ascii_lowercase().as_str()))
+        };
+        let missing = |what: &str| format!("The CSV header has no {} column", what);
+        let frame = find(&["frame"]);
+        let time = find(&["time", "timestamp", "date", "datetime"]);
+        Ok(Columns {
+            id: find(&["id", "name", "track"]).ok_or_else(|| missing("id"))?,
+            when: frame.or(time).ok_or_else(|| missing("frame or time"))?,
+            lat: find(&["lat", "latitude"]).ok_or_else(|| missing("lat"))?,
+            lon: find(&["lon", "lng", "long", "longitude"]).ok_or_else(|| missing("lon"))?,
+            frames: frame.is_some(),
+        })
+    }
+}
+
+/// Reads the point series in a CSV file, in the order their ids first appear, and
+/// how their times map onto frames; times are spread over `total_frames` unless
+/// the file gives frames.
+pub fn parse_series(text: &str, total_frames: i32) -> Result<(Vec<Track>, Timing), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+    let header = reader
+        .headers()
+        .map_err(|err| format!("Invalid CSV: {}", err))?
+        .clone();
+    let columns = Columns::find(&header)?;
+
+    let mut tracks: Vec<Track> = vec![];
+    let mut track_indices: HashMap<String, usize> = HashMap::new();
+    for record in reader.records() {
+        let record = record.map_err(|err| format!("Invalid CSV: {}", err))?;
+        let line = record.position().map_or(0, |position| position.line());
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        let field = |column: usize| record.get(column).unwrap_or_default();
+        let id = field(columns.id);
+        if id.is_empty() {
+            return Err(format!("Line {}: no id", line));
+        }
+        let degrees = |column: usize, limit: f32, what: &str| {
+            field(column)
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.abs() <= limit)
+                .ok_or_else(|| format!("Line {}: invalid {} '{}'", line, what, field(column)))
+        };
+        let lat = degrees(columns.lat, 90.0, "lat")?;
+        let lon = degrees(columns.lon, 180.0, "lon")?;
+        let when = field(columns.when);
+        let time = if columns.frames {
+            when.parse::<u32>()
+                .ok()
+                .filter(|frame| *frame <= MAX_FRAME)
+                .map(f64::from)
+                .ok_or_else(|| format!("Line {}: invalid frame '{}'", line, when))?
+        } else {
+            parse_time(when).ok_or_else(|| format!("Line {}: invalid time '{}'", line, when))?
+        };
+
+        let point = TrackPoint { lat, lon, time };
+        match track_indices.get(id) {
+            Some(&index) => tracks[index].points.push(point),
+            None => {
+                if tracks.len() == MAX_TRACKS {
+                    return Err(format!(
+                        "Line {}: more than {} ids; one import may hold at most that many series",
+                        line, MAX_TRACKS
+                    ));
+                }
+                track_indices.insert(id.to_string(), tracks.len());
+                tracks.push(Track {
+                    name: Some(id.to_string()),
+                    points: vec![point],
+                });
+            }
+        }
+    }
+    if tracks.is_empty() {
+        return Err("The CSV file has no rows".to_string());
+    }
+    for track in &mut tracks {
+        // Stable, so rows at the same time keep their order
+        track.points.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+    let timing = if columns.frames {
+        Timing::Frames
+    } else {
+        Timing::Spread { total_frames }
+    };
+    Ok((tracks, timing))
+}
+
+/// Seconds since the Unix epoch of an RFC 3339 time, a UTC date and time, or a
+/// plain number of seconds.
+fn parse_time(text: &str) -> Option<f64> {
+    if let Ok(seconds) = text.parse::<f64>() {
+        return seconds.is_finite().then_some(seconds);
+    }
+    let utc = DateTime::parse_from_rfc3339(text)
+        .map(|time| time.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f"))
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()?
+        .and_utc();
+    Some(utc.timestamp() as f64 + utc.timestamp_subsec_nanos() as f64 / 1e9)
+}
+
+/// Makes each series in a CSV file into a marker feature with its points at `radius`,
+/// named after its id. Returns the markers and the number of frames they play over:
+/// `total_frames` when the file gives times, or up to its last frame when it gives frames.
+/// Series that would take more movements than tracks.rs allows are refused.
+pub fn import_point_series(
+    text: &str,
+    total_frames: i32,
+    radius: f32,
+) -> Result<(Vec<Polygon>, i32), String> {
+    let (series, timing) = parse_series(text, total_frames)?;
+    tracks::markers(&series, timing, radius)
+}
@@ -0,0 +1,249 @@
+// klyja/geco-core/src/raster.rs
+// Software rendering of the globe to RGBA pixels, for previews made without a GPU
+// (e.g. server-side thumbnails). Orthographic and unlit, in the editor's colors.
+use crate::camera;
+use crate::geometry::{self, cross, dot, normalize, Vec3};
+use crate::protobuf_gen::{FeatureType, MapAnimation, Polygon};
+use crate::transform;
+
+pub const BACKGROUND_COLOR: [u8; 4] = [0x28, 0x2c, 0x34, 0xff]; // Scene background
+pub const GLOBE_COLOR: [u8; 4] = [0x00, 0x77, 0xff, 0xff];
+pub const FEATURE_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+pub const MARKER_COLOR: [u8; 4] = [0xff, 0x00, 0x00, 0xff]; // Point features
+
+const FILL_OPACITY: f32 = 0.5;
+const GLOBE_MARGIN: f32 = 0.95; // Fraction of half the image the globe spans at zoom 1
+
+/// RGBA pixels, row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Raster {
+    pub fn new(width: u32, height: u32, color: [u8; 4]) -> Self {
+        Raster {
+            width,
+            height,
+            pixels: color
+                .iter()
+                .copied()
+                .cycle()
+                .take(width as usize * height as usize * 4)
+                .collect(),
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    fn blend(&mut self, x: i64, y: i64, color: [u8; 4], opacity: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        for (under, &over) in self.pixels[i..i + 3].iter_mut().zip(&color) {
+            *under = (*under as f32 + (over as f32 - *under as f32) * opacity).round() as u8;
+        }
+        self.pixels[i + 3] = self.pixels[i + 3].max(color[3]);
+    }
+
+    /// Fills the pixels whose centers lie inside the triangle.
+    fn fill_triangle(&mut self, [a, b, c]: [[f32; 2]; 3], color: [u8; 4], opacity: f32) {
+        let edge = |p: [f32; 2], q: [f32; 2], r: [f32; 2]| {
+            (q[0] - p[0]) * (r[1] - p[1]) - (q[1] - p[1]) * (r[0] - p[0])
+        };
+        let area = edge(a, b, c);
+        if area.abs() < f32::EPSILON {
+            return;
+        }
+        let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as i64;
+        let max_x = a[0].max(b[0]).max(c[0]).ceil().min(self.width as f32) as i64;
+        let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as i64;
+        let max_y = a[1].max(b[1]).max(c[1]).ceil().min(self.height as f32) as i64;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+                // Same sign as the whole triangle on all three edges, whatever the winding
+                let inside = [edge(a, b, p), edge(b, c, p), edge(c, a, p)]
+                    .iter()
+                    .all(|w| w * area >= 0.0);
+                if inside {
+                    self.blend(x, y, color, opacity);
+                }
+            }
+        }
+    }
+
+    fn fill_disc(&mut self, center: [f32; 2], radius: f32, color: [u8; 4]) {
+        let min_x = (center[0] - radius).floor() as i64;
+        let max_x = (center[0] + radius).ceil() as i64;
+        let min_y = (center[1] - radius).floor() as i64;
+        let max_y = (center[1] + radius).ceil() as i64;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let dx = x as f32 + 0.5 - center[0];
+                let dy = y as f32 + 0.5 - center[1];
+                if dx * dx + dy * dy <= radius * radius {
+                    self.blend(x, y, color, 1.0);
+                }
+            }
+        }
+    }
+
+    /// Draws a line `width` pixels wide by stamping discs along it.
+    fn draw_line(&mut self, from: [f32; 2], to: [f32; 2], width: f32, color: [u8; 4]) {
+        let steps = (to[0] - from[0]).abs().max((to[1] - from[1]).abs()).ceil() as usize;
+        for step in 0..=steps {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                step as f32 / steps as f32
+            };
+            let p = [
+                from[0] + (to[0] - from[0]) * t,
+                from[1] + (to[1] - from[1]) * t,
+            ];
+            self.fill_disc(p, width / 2.0, color);
+        }
+    }
+}
+
+/// An orthographic view of the globe from far away along `eye`, with north up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrthographicView {
+    eye: Vec3,
+    right: Vec3,
+    up: Vec3,
+    center: [f32; 2],
+    globe_radius_px: f32, // Size of the globe on screen; positions are normalized to it
+}
+
+impl OrthographicView {
+    /// Looks at the globe center from `eye` (any length). `zoom` scales the globe as
+    /// camera keyframes do, 1.0 fitting it to the smaller image dimension.
+    pub fn new(eye: Vec3, zoom: f32, width: u32, height: u32) -> Self {
+        let eye = normalize(eye).unwrap_or([0.0, 0.0, 1.0]);
+        // Looking straight down a pole, "north up" is undefined; use -z as up instead
+        let right = normalize(cross([0.0, 1.0, 0.0], eye))
+            .unwrap_or_else(|| normalize(cross([0.0, 0.0, -1.0], eye)).unwrap_or([1.0, 0.0, 0.0]));
+        let up = cross(eye, right);
+        let zoom = if zoom.is_finite() && zoom > 0.0 {
+            zoom
+        } else {
+            1.0
+        };
+        OrthographicView {
+            eye,
+            right,
+            up,
+            center: [width as f32 / 2.0, height as f32 / 2.0],
+            globe_radius_px: width.min(height) as f32 / 2.0 * GLOBE_MARGIN * zoom,
+        }
+    }
+
+    /// The view the document's camera path has at `frame`, or the editor's default
+    /// view (from +z) when it has none.
+    pub fn at_frame(animation: &MapAnimation, frame: i32, width: u32, height: u32) -> Self {
+        match camera::camera_at_frame(&animation.camera_keyframes, frame) {
+            Some(keyframe) => {
+                let to_vec3 = |p: &crate::protobuf_gen::Point| [p.x, p.y, p.z.unwrap_or(0.0)];
+                let position = keyframe
+                    .position
+                    .as_ref()
+                    .map(to_vec3)
+                    .unwrap_or([0.0, 0.0, 1.0]);
+                let target = keyframe.target.as_ref().map(to_vec3).unwrap_or([0.0; 3]);
+                let eye = [
+                    position[0] - target[0],
+                    position[1] - target[1],
+                    position[2] - target[2],
+                ];
+                OrthographicView::new(eye, keyframe.zoom, width, height)
+            }
+            None => OrthographicView::new([0.0, 0.0, 1.0], 1.0, width, height),
+        }
+    }
+
+    /// Screen position of the point on the globe in direction `p`, or None when
+    /// it is on the far side.
+    pub fn project(&self, p: Vec3) -> Option<[f32; 2]> {
+        let direction = normalize(p)?;
+        if dot(direction, self.eye) < 0.0 {
+            return None;
+        }
+        Some([
+            self.center[0] + dot(direction, self.right) * self.globe_radius_px,
+            self.center[1] - dot(direction, self.up) * self.globe_radius_px,
+        ])
+    }
+}
+
+/// Renders the document at `frame` as seen by its camera: the globe, then each
+/// feature in draw order. Shapes crossing the horizon are clipped at their first
+/// hidden vertex rather than exactly at the limb, which is plenty for previews.
+pub fn render_orthographic(
+    animation: &MapAnimation,
+    frame: i32,
+    width: u32,
+    height: u32,
+) -> Raster {
+    let view = OrthographicView::at_frame(animation, frame, width, height);
+    let mut raster = Raster::new(width, height, BACKGROUND_COLOR);
+    raster.fill_disc(view.center, view.globe_radius_px, GLOBE_COLOR);
+
+    let line_width = (width.min(height) as f32 / 128.0).max(1.0);
+    let mut ordered: Vec<&Polygon> = animation.polygons.iter().collect();
+    ordered.sort_by_key(|p| p.draw_order);
+    for polygon in ordered {
+        let points: Vec<Vec3> =
+            transform::world_points_at_frame(&animation.polygons, polygon, frame)
+                .into_iter()
+                .map(|(_, xyz)| xyz)
+                .collect();
+        let projected: Vec<Option<[f32; 2]>> = points.iter().map(|&p| view.project(p)).collect();
+        match polygon.feature_type() {
+            FeatureType::Point => {
+                for screen in projected.iter().flatten() {
+                    raster.fill_disc(*screen, line_width * 2.0, MARKER_COLOR);
+                }
+            }
+            feature_type => {
+                let closed = feature_type == FeatureType::Polygon;
+                if closed {
+                    for triangle in geometry::triangulate(&points).chunks_exact(3) {
+                        let corners = [
+                            projected[triangle[0] as usize],
+                            projected[triangle[1] as usize],
+                            projected[triangle[2] as usize],
+                        ];
+                        if let [Some(a), Some(b), Some(c)] = corners {
+                            raster.fill_triangle([a, b, c], FEATURE_COLOR, FILL_OPACITY);
+                        }
+                    }
+                }
+                let segment_count = match (closed, projected.len()) {
+                    (_, 0 | 1) => 0,
+                    (true, n) => n,
+                    (false, n) => n - 1,
+                };
+                for i in 0..segment_count {
+                    let next = (i + 1) % projected.len();
+                    if let (Some(from), Some(to)) = (projected[i], projected[next]) {
+                        raster.draw_line(from, to, line_width, FEATURE_COLOR);
+                    }
+                }
+            }
+        }
+    }
+    raster
+}
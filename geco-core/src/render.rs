@@ -0,0 +1,136 @@
+// klyja/geco-core/src/render.rs
+// Flat vertex buffers for the renderer, built from the document at a given frame.
+use crate::color_ramp::{ColorRamp, DEFAULT_VERTEX_COLOR};
+use crate::geometry;
+use crate::interpolation;
+use crate::protobuf_gen::{FeatureType, Polygon};
+use crate::transform;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct VertexBuffers {
+    pub positions: Vec<f32>,     // x, y, z per vertex
+    pub colors: Vec<f32>,        // r, g, b per vertex
+    pub uvs: Vec<f32>,           // u, v per vertex (equirectangular)
+    pub indices: Vec<u32>,       // Fill triangles for polygon features, indexing the vertices
+    pub vertex_counts: Vec<u32>, // Vertices per feature, in the order the features were given
+}
+
+/// Builds buffers for `polygons` (expected in draw order) at `frame`.
+/// Only points that are part of their shape at `frame` are emitted. Polygon features
+/// are also triangulated so their fills can be drawn (and textured via the UVs).
+/// `all` is the whole document, used to resolve transforms inherited from parents.
+pub fn build_vertex_buffers(
+    all: &[Polygon],
+    polygons: &[&Polygon],
+    frame: i32,
+    color_ramp: Option<&ColorRamp>,
+) -> VertexBuffers {
+    concat_feature_buffers(
+        polygons
+            .iter()
+            .map(|polygon| build_feature_buffers(all, polygon, frame, color_ramp)),
+    )
+}
+
+/// Builds one feature's buffers at `frame`, its fill indices counting from its own
+/// first vertex. Features can be built independently (even on separate threads)
+/// and joined with `concat_feature_buffers`.
+pub fn build_feature_buffers(
+    all: &[Polygon],
+    polygon: &Polygon,
+    frame: i32,
+    color_ramp: Option<&ColorRamp>,
+) -> VertexBuffers {
+    let mut buffers = VertexBuffers::default();
+    let mut fill_points = vec![];
+    for (point, xyz) in transform::world_points_at_frame(all, polygon, frame) {
+        buffers.positions.extend(xyz);
+        buffers.uvs.extend(geometry::equirectangular_uv(xyz));
+        let color = match (
+            color_ramp,
+            interpolation::scalar_at_frame(&point.scalar_keyframes, frame),
+        ) {
+            (Some(ramp), Some(value)) => ramp.color_at(value),
+            _ => DEFAULT_VERTEX_COLOR,
+        };
+        buffers.colors.extend(color);
+        fill_points.push(xyz);
+    }
+    if polygon.feature_type() == FeatureType::Polygon {
+        buffers.indices = geometry::triangulate(&fill_points);
+    }
+    buffers.vertex_counts.push(fill_points.len() as u32);
+    buffers
+}
+
+/// Joins buffers built by `build_feature_buffers`, in order, shifting each one's
+/// indices past the vertices before it.
+pub fn concat_feature_buffers(features: impl IntoIterator<Item = VertexBuffers>) -> VertexBuffers {
+    let mut buffers = VertexBuffers::default();
+    for feature in features {
+        let first_vertex = (buffers.positions.len() / 3) as u32;
+        buffers.positions.extend(feature.positions);
+        buffers.colors.extend(feature.colors);
+        buffers.uvs.extend(feature.uvs);
+        buffers
+            .indices
+            .extend(feature.indices.into_iter().map(|i| first_vertex + i));
+        buffers.vertex_counts.extend(feature.vertex_counts);
+    }
+    buffers
+}
+
+/// Buffers for several frames packed back to back. Frame `i` owns vertices
+/// `vertex_starts[i]..vertex_starts[i + 1]` (the last one runs to the end), and likewise
+/// for indices and per-feature vertex counts. Indices are relative to their frame's
+/// first vertex, so each frame's slices can be uploaded as-is.
+#[derive(Debug, Default, PartialEq)]
+pub struct FrameRangeBuffers {
+    pub frames: Vec<i32>,
+    pub buffers: VertexBuffers,
+    pub vertex_starts: Vec<u32>,
+    pub index_starts: Vec<u32>,
+    pub feature_starts: Vec<u32>,
+}
+
+pub fn build_frame_range_buffers(
+    all: &[Polygon],
+    polygons: &[&Polygon],
+    frames: impl Iterator<Item = i32>,
+    color_ramp: Option<&ColorRamp>,
+) -> FrameRangeBuffers {
+    pack_frame_buffers(frames.map(|frame| {
+        (
+            frame,
+            build_vertex_buffers(all, polygons, frame, color_ramp),
+        )
+    }))
+}
+
+/// Packs each frame's buffers back to back, in the order given.
+pub fn pack_frame_buffers(
+    frames: impl IntoIterator<Item = (i32, VertexBuffers)>,
+) -> FrameRangeBuffers {
+    let mut packed = FrameRangeBuffers::default();
+    for (frame, frame_buffers) in frames {
+        let packed_buffers = &mut packed.buffers;
+        packed.frames.push(frame);
+        packed
+            .vertex_starts
+            .push((packed_buffers.positions.len() / 3) as u32);
+        packed
+            .index_starts
+            .push(packed_buffers.indices.len() as u32);
+        packed
+            .feature_starts
+            .push(packed_buffers.vertex_counts.len() as u32);
+        packed_buffers.positions.extend(frame_buffers.positions);
+        packed_buffers.colors.extend(frame_buffers.colors);
+        packed_buffers.uvs.extend(frame_buffers.uvs);
+        packed_buffers.indices.extend(frame_buffers.indices);
+        packed_buffers
+            .vertex_counts
+            .extend(frame_buffers.vertex_counts);
+    }
+    packed
+}
@@ -0,0 +1,78 @@
+// klyja/geco-core/src/scatter.rs
+// Deterministic random points inside a spherical polygon, for population-dot or
+// vegetation effects. The same seed always yields the same points.
+use crate::geometry::{contains_point, cross, dot, length, normalize, Vec3};
+use std::f32::consts::PI;
+
+/// Rejected samples allowed per requested point before giving up on thin shapes.
+const MAX_ATTEMPTS_PER_POINT: usize = 200;
+
+/// SplitMix64: tiny, seedable and good enough for visual scatter.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Returns up to `count` points uniformly distributed over the area of the spherical
+/// polygon, at the polygon's mean radius. Samples are drawn uniformly from the
+/// smallest cap around the polygon's centroid that holds all its vertices, and kept
+/// if they fall inside. Fewer points come back only for degenerate or extremely
+/// thin polygons.
+pub fn scatter_in_polygon(polygon: &[Vec3], count: usize, seed: u32) -> Vec<Vec3> {
+    if polygon.len() < 3 || count == 0 {
+        return vec![];
+    }
+    let Some(center) = normalize(polygon.iter().fold([0.0; 3], |acc, p| {
+        let unit = normalize(*p).unwrap_or([0.0; 3]);
+        [acc[0] + unit[0], acc[1] + unit[1], acc[2] + unit[2]]
+    })) else {
+        return vec![];
+    };
+    let radius = polygon.iter().map(|p| length(*p)).sum::<f32>() / polygon.len() as f32;
+    let cos_max = polygon
+        .iter()
+        .filter_map(|p| normalize(*p))
+        .map(|unit| dot(unit, center))
+        .fold(1.0f32, f32::min);
+    let helper = if center[1].abs() < 0.9 {
+        [0.0, 1.0, 0.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    let Some(e1) = normalize(cross(helper, center)) else {
+        return vec![];
+    };
+    let e2 = cross(center, e1);
+
+    let mut rng = SplitMix64(seed as u64);
+    let mut points = Vec::with_capacity(count);
+    for _ in 0..count * MAX_ATTEMPTS_PER_POINT {
+        if points.len() == count {
+            break;
+        }
+        // Uniform on the cap: the height along `center` is uniform (Archimedes)
+        let cos_theta = 1.0 - rng.next_f32() * (1.0 - cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = rng.next_f32() * 2.0 * PI;
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let candidate = [0, 1, 2].map(|i| {
+            radius * (center[i] * cos_theta + (e1[i] * cos_phi + e2[i] * sin_phi) * sin_theta)
+        });
+        if contains_point(polygon, candidate) {
+            points.push(candidate);
+        }
+    }
+    points
+}
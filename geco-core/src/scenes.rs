@@ -0,0 +1,57 @@
+// klyja/geco-core/src/scenes.rs
+// Scenes: titled frame ranges that split a long animation into chapters. Scenes are
+// kept sorted by start frame and never overlap, so a frame belongs to at most one;
+// frames between scenes belong to none. Playback steps through them like the slides
+// of a presentation.
+use crate::protobuf_gen::Scene;
+
+/// Adds a scene, or replaces the one with the same ID, keeping scenes sorted by start
+/// frame. Fails if its range is empty or negative, or overlaps another scene.
+pub fn upsert_scene(scenes: &mut Vec<Scene>, scene: Scene) -> Result<(), String> {
+    if scene.start_frame < 0 || scene.end_frame <= scene.start_frame {
+        return Err(format!(
+            "Scene '{}' needs 0 <= start_frame < end_frame, got {}..{}",
+            scene.scene_id, scene.start_frame, scene.end_frame
+        ));
+    }
+    if let Some(other) = scenes.iter().find(|other| {
+        other.scene_id != scene.scene_id
+            && other.start_frame < scene.end_frame
+            && scene.start_frame < other.end_frame
+    }) {
+        return Err(format!(
+            "Scene '{}' overlaps scene '{}' ({}..{})",
+            scene.scene_id, other.scene_id, other.start_frame, other.end_frame
+        ));
+    }
+    scenes.retain(|other| other.scene_id != scene.scene_id);
+    let index = scenes.partition_point(|other| other.start_frame < scene.start_frame);
+    scenes.insert(index, scene);
+    Ok(())
+}
+
+/// The scene playing at `frame`, if any.
+pub fn scene_at_frame(scenes: &[Scene], frame: i32) -> Option<&Scene> {
+    let index = scenes
+        .partition_point(|scene| scene.start_frame <= frame)
+        .checked_sub(1)?;
+    let scene = &scenes[index];
+    (frame < scene.end_frame).then_some(scene)
+}
+
+/// The first scene starting after `frame`, to skip forward to.
+pub fn next_scene(scenes: &[Scene], frame: i32) -> Option<&Scene> {
+    let index = scenes.partition_point(|scene| scene.start_frame <= frame);
+    scenes.get(index)
+}
+
+/// The scene to skip back to from `frame`: the one before the scene playing, or,
+/// between scenes, the last one that has started.
+pub fn previous_scene(scenes: &[Scene], frame: i32) -> Option<&Scene> {
+    let started = scenes.partition_point(|scene| scene.start_frame <= frame);
+    let index = match scene_at_frame(scenes, frame) {
+        Some(_) => started.checked_sub(2)?,
+        None => started.checked_sub(1)?,
+    };
+    Some(&scenes[index])
+}
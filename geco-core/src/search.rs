@@ -0,0 +1,43 @@
+// klyja/geco-core/src/search.rs
+// Feature queries for navigating large documents from the UI.
+use crate::protobuf_gen::Polygon;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Property holding a feature's display name.
+pub const NAME_PROPERTY: &str = "name";
+
+/// Filters are combined with AND; an empty query matches every feature.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureQuery {
+    /// Case-insensitive substring of the feature's name or ID.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Properties that must be present with exactly these values.
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+impl FeatureQuery {
+    /// Parses a query like `{"name": "river", "properties": {"layer": "hydro"}}`.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid feature query JSON: {}", e))
+    }
+
+    pub fn matches(&self, polygon: &Polygon) -> bool {
+        let name_matches = self.name.as_deref().is_none_or(|needle| {
+            let needle = needle.to_lowercase();
+            polygon.polygon_id.to_lowercase().contains(&needle)
+                || polygon
+                    .properties
+                    .get(NAME_PROPERTY)
+                    .is_some_and(|name| name.to_lowercase().contains(&needle))
+        });
+        name_matches
+            && self
+                .properties
+                .iter()
+                .all(|(key, value)| polygon.properties.get(key) == Some(value))
+    }
+}
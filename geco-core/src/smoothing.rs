@@ -0,0 +1,38 @@
+// klyja/geco-core/src/smoothing.rs
+// Low-pass filtering of pointer samples while drawing, since hand-held mouse input
+// projected onto the globe makes visibly shaky lines.
+use crate::geometry::{length, normalize, Vec3};
+
+/// Exponential moving average over the samples of one stroke.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputSmoother {
+    alpha: f32,         // Weight of the newest sample, in (0, 1]; 1 disables smoothing
+    last: Option<Vec3>, // Previous filtered sample of the current stroke
+}
+
+impl InputSmoother {
+    pub fn new(alpha: f32) -> Self {
+        InputSmoother { alpha, last: None }
+    }
+
+    /// Forgets the previous stroke so the next sample is taken as is.
+    pub fn start_stroke(&mut self) {
+        self.last = None;
+    }
+
+    /// Blends `sample` with the previous filtered sample. The result keeps the
+    /// sample's distance from the globe center so it doesn't sink into the globe
+    /// on tight curves.
+    pub fn filter(&mut self, sample: Vec3) -> Vec3 {
+        let filtered = match self.last {
+            Some(last) => {
+                let blended: Vec3 =
+                    std::array::from_fn(|i| self.alpha * sample[i] + (1.0 - self.alpha) * last[i]);
+                normalize(blended).map_or(sample, |d| d.map(|c| c * length(sample)))
+            }
+            None => sample,
+        };
+        self.last = Some(filtered);
+        filtered
+    }
+}
@@ -0,0 +1,206 @@
+// klyja/geco-core/src/stats.rs
+// Document size statistics, to explain why a document got slow or too big to save,
+// and per-frame activity for the timeline.
+use crate::interpolation::{point_keyframes, start_frame};
+use crate::protobuf_gen::{AnimatedPoint, MapAnimation, Polygon};
+use prost::Message;
+use serde::Serialize;
+use std::mem::size_of;
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct DocumentStats {
+    pub feature_count: usize,
+    pub point_count: usize,
+    pub movement_count: usize, // Per-frame steps stored across all points
+    pub keyframe_count: usize, // Point, data, transform, grid and camera keyframes
+    pub grid_layer_count: usize,
+    pub estimated_heap_bytes: usize,
+    pub encoded_bytes: usize, // Size of the protobuf a save would upload
+    // Filled in by the editor, which owns the undo history
+    pub undo_snapshots: usize,
+    pub undo_heap_bytes: usize,
+}
+
+pub fn document_stats(animation: &MapAnimation) -> DocumentStats {
+    let points = || animation.polygons.iter().flat_map(|p| &p.points);
+    let point_keyframe_count: usize = points()
+        .map(|point| point_keyframes(point).len() + point.scalar_keyframes.len())
+        .sum();
+    let transform_keyframe_count: usize = animation
+        .polygons
+        .iter()
+        .map(|p| p.transform_keyframes.len())
+        .sum();
+    let grid_keyframe_count: usize = animation
+        .grid_layers
+        .iter()
+        .map(|layer| layer.keyframes.len())
+        .sum();
+    let data_keyframe_count: usize = animation
+        .data_series
+        .iter()
+        .map(|series| series.keyframes.len())
+        .sum();
+    DocumentStats {
+        feature_count: animation.polygons.len(),
+        point_count: points().count(),
+        movement_count: points().map(|point| point.movements.len()).sum(),
+        keyframe_count: point_keyframe_count
+            + transform_keyframe_count
+            + grid_keyframe_count
+            + animation.camera_keyframes.len()
+            + data_keyframe_count,
+        grid_layer_count: animation.grid_layers.len(),
+        estimated_heap_bytes: estimated_heap_bytes(animation),
+        encoded_bytes: animation.encoded_len(),
+        ..Default::default()
+    }
+}
+
+/// Most frames a timeline density counts. Documents may name any frame, and a heat
+/// strip has no use for millions of them.
+pub const MAX_DENSITY_FRAMES: usize = 100_000;
+
+/// Per-frame activity, indexed by frame, for drawing a timeline heat strip.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct TimelineDensity {
+    /// Frames the timeline covers; the counts stop at MAX_DENSITY_FRAMES.
+    pub frame_count: usize,
+    pub keyframes: Vec<u32>,    // Keyframes of every kind set at each frame
+    pub appearing: Vec<u32>,    // Features whose first point joins at each frame
+    pub disappearing: Vec<u32>, // Features whose last point leaves at each frame
+}
+
+/// Counts keyframes and feature entrances/exits per frame. Covers the document's
+/// `total_frames` and any later frame with activity, up to MAX_DENSITY_FRAMES;
+/// negative frames and those past the limit are ignored.
+pub fn timeline_density(animation: &MapAnimation) -> TimelineDensity {
+    let mut keyframes = vec![];
+    let mut appearing = vec![];
+    let mut disappearing = vec![];
+    for polygon in &animation.polygons {
+        for point in &polygon.points {
+            keyframes.extend(point_keyframes(point).iter().map(|(frame, _)| *frame));
+            keyframes.extend(point.scalar_keyframes.iter().map(|k| k.frame));
+        }
+        keyframes.extend(polygon.transform_keyframes.iter().map(|k| k.frame));
+        if let Some(first) = polygon.points.iter().map(start_frame).min() {
+            appearing.push(first);
+        }
+        // A feature only leaves once every one of its points has
+        let ends: Option<Vec<i32>> = polygon.points.iter().map(|p| p.end_frame).collect();
+        if let Some(last) = ends.and_then(|ends| ends.into_iter().max()) {
+            disappearing.push(last);
+        }
+    }
+    for layer in &animation.grid_layers {
+        keyframes.extend(layer.keyframes.iter().map(|k| k.frame));
+    }
+    keyframes.extend(animation.camera_keyframes.iter().map(|k| k.frame));
+    for series in &animation.data_series {
+        keyframes.extend(series.keyframes.iter().map(|k| k.frame));
+    }
+
+    let last_active = keyframes
+        .iter()
+        .chain(&appearing)
+        .chain(&disappearing)
+        .max()
+        .map_or(0, |&frame| frame.saturating_add(1));
+    let frame_count = animation.total_frames.max(last_active).max(0) as usize;
+    let histogram = |frames: &[i32]| {
+        let mut counts = vec![0; frame_count.min(MAX_DENSITY_FRAMES)];
+        for &frame in frames {
+            if let Some(count) = usize::try_from(frame).ok().and_then(|f| counts.get_mut(f)) {
+                *count += 1;
+            }
+        }
+        counts
+    };
+    TimelineDensity {
+        frame_count,
+        keyframes: histogram(&keyframes),
+        appearing: histogram(&appearing),
+        disappearing: histogram(&disappearing),
+    }
+}
+
+/// Rough heap footprint: allocated capacity of every vector and string in the
+/// document. Allocator overhead and hash map internals are not counted.
+pub fn estimated_heap_bytes(animation: &MapAnimation) -> usize {
+    animation.animation_id.capacity()
+        + animation.name.capacity()
+        + vec_bytes(&animation.polygons)
+        + animation
+            .polygons
+            .iter()
+            .map(polygon_heap_bytes)
+            .sum::<usize>()
+        + vec_bytes(&animation.grid_layers)
+        + animation
+            .grid_layers
+            .iter()
+            .map(|layer| {
+                layer.grid_id.capacity()
+                    + vec_bytes(&layer.keyframes)
+                    + layer
+                        .keyframes
+                        .iter()
+                        .map(|k| vec_bytes(&k.values))
+                        .sum::<usize>()
+                    + properties_bytes(&layer.properties)
+            })
+            .sum::<usize>()
+        + vec_bytes(&animation.camera_keyframes)
+        + vec_bytes(&animation.data_series)
+        + animation
+            .data_series
+            .iter()
+            .map(|series| {
+                series.series_id.capacity()
+                    + series.name.capacity()
+                    + series.unit.capacity()
+                    + vec_bytes(&series.keyframes)
+                    + series
+                        .keyframes
+                        .iter()
+                        .map(|k| k.label.as_ref().map_or(0, String::capacity))
+                        .sum::<usize>()
+                    + properties_bytes(&series.properties)
+            })
+            .sum::<usize>()
+        + vec_bytes(&animation.scenes)
+        + animation
+            .scenes
+            .iter()
+            .map(|scene| {
+                scene.scene_id.capacity()
+                    + scene.title.capacity()
+                    + properties_bytes(&scene.properties)
+            })
+            .sum::<usize>()
+}
+
+fn polygon_heap_bytes(polygon: &Polygon) -> usize {
+    polygon.polygon_id.capacity()
+        + vec_bytes(&polygon.points)
+        + polygon.points.iter().map(point_heap_bytes).sum::<usize>()
+        + properties_bytes(&polygon.properties)
+        + vec_bytes(&polygon.transform_keyframes)
+        + polygon.parent_id.as_ref().map_or(0, String::capacity)
+}
+
+fn point_heap_bytes(point: &AnimatedPoint) -> usize {
+    point.point_id.capacity() + vec_bytes(&point.movements) + vec_bytes(&point.scalar_keyframes)
+}
+
+fn properties_bytes(properties: &std::collections::HashMap<String, String>) -> usize {
+    properties
+        .iter()
+        .map(|(key, value)| size_of::<(String, String)>() + key.capacity() + value.capacity())
+        .sum()
+}
+
+fn vec_bytes<T>(items: &Vec<T>) -> usize {
+    items.capacity() * size_of::<T>()
+}
@@ -0,0 +1,200 @@
+// klyja/geco-core/src/tracks.rs
+// Timed positions, from GPS logs or spreadsheets, made into marker features: one point
+// feature per track, moving along it over the timeline. A marker appears at its first
+// position's frame and stays at its last position once its track ends. Between
+// positions it moves along the great circle joining them, one movement per frame.
+use crate::editing::unique_feature_id;
+use crate::geometry::{from_lat_lon, normalize, Vec3};
+use crate::protobuf_gen::{AnimatedPoint, FeatureType, Point, Polygon, Vector};
+use std::collections::HashMap;
+
+/// Frames timestamped tracks are spread over when nothing else says: ten seconds at
+/// 24 frames per second.
+pub const DEFAULT_TOTAL_FRAMES: i32 = 240;
+/// Most tracks one import may make into markers.
+pub const MAX_TRACKS: usize = 1_000;
+/// Most movements the markers of one import may have between them. Each track gets
+/// one per frame from its start to the end of the timeline, so a short file could
+/// otherwise ask for billions.
+pub const MAX_MOVEMENTS: usize = 1_000_000;
+
+/// A series of positions of one thing: its name, if any, and its timed positions in
+/// time order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub name: Option<String>,
+    pub points: Vec<TrackPoint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackPoint {
+    pub lat: f32,
+    pub lon: f32,
+    /// Seconds since the Unix epoch, fractions included, or a frame number.
+    pub time: f64,
+}
+
+/// How track times map onto frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// Times are timestamps, shared by all tracks: the earliest lands on frame 0 and
+    /// the latest on the last of `total_frames`, so tracks keep their relative timing.
+    Spread { total_frames: i32 },
+    /// Times are frame numbers already; the timeline runs to the last of them.
+    Frames,
+}
+
+/// Makes each track into a marker feature with its points at `radius`, IDs from the
+/// tracks' names and the names kept as a `name` property. Returns the markers and
+/// the number of frames they play over, or an error if there are more than
+/// MAX_TRACKS tracks or they'd take more than MAX_MOVEMENTS movements.
+pub fn markers(
+    tracks: &[Track],
+    timing: Timing,
+    radius: f32,
+) -> Result<(Vec<Polygon>, i32), String> {
+    if tracks.len() > MAX_TRACKS {
+        return Err(format!(
+            "{} tracks is more than the {} one import may hold",
+            tracks.len(),
+            MAX_TRACKS
+        ));
+    }
+    let first = tracks
+        .iter()
+        .filter_map(|track| track.points.first())
+        .map(|point| point.time)
+        .fold(f64::INFINITY, f64::min);
+    let last = tracks
+        .iter()
+        .filter_map(|track| track.points.last())
+        .map(|point| point.time)
+        .fold(f64::NEG_INFINITY, f64::max);
+    // The track time at frame 0, and how much of it each frame covers
+    let (origin, per_frame, last_frame) = match timing {
+        Timing::Spread { total_frames } => {
+            let last_frame = (total_frames - 1).max(0);
+            // Tracks logged all at once play on frame 0
+            let per_frame = if last > first && last_frame > 0 {
+                (last - first) / last_frame as f64
+            } else {
+                0.0
+            };
+            (first, per_frame, last_frame)
+        }
+        Timing::Frames => (0.0, 1.0, last.max(0.0).ceil() as i32),
+    };
+    let start_frame = |first_point: &TrackPoint| {
+        if per_frame > 0.0 {
+            (((first_point.time - origin) / per_frame).round() as i32).clamp(0, last_frame)
+        } else {
+            0
+        }
+    };
+    // Count before building anything, as a marker holds one movement per frame
+    let movement_count: usize = tracks
+        .iter()
+        .filter_map(|track| track.points.first())
+        .map(|first_point| (last_frame - start_frame(first_point)) as usize)
+        .sum();
+    if movement_count > MAX_MOVEMENTS {
+        return Err(format!(
+            "The tracks would take {} movements over {} frames, more than the {} allowed; \
+             import fewer tracks or frames",
+            movement_count,
+            last_frame + 1,
+            MAX_MOVEMENTS
+        ));
+    }
+
+    let mut polygons: Vec<Polygon> = vec![];
+    for (index, track) in tracks.iter().enumerate() {
+        let Some(first_point) = track.points.first() else {
+            continue;
+        };
+        let base = track
+            .name
+            .as_deref()
+            .and_then(slug)
+            .unwrap_or_else(|| format!("track-{}", index));
+        let polygon_id = unique_feature_id(&polygons, &base);
+        let start_frame = start_frame(first_point);
+
+        let mut positions = (start_frame..=last_frame)
+            .map(|frame| position_at(&track.points, origin + frame as f64 * per_frame, radius));
+        let initial = positions.next().expect("start_frame is at most last_frame");
+        let mut previous = initial;
+        let movements = positions
+            .map(|position| {
+                let movement = Vector {
+                    dx: position[0] - previous[0],
+                    dy: position[1] - previous[1],
+                    dz: Some(position[2] - previous[2]),
+                };
+                previous = position;
+                movement
+            })
+            .collect();
+
+        let mut properties = HashMap::new();
+        if let Some(name) = &track.name {
+            properties.insert("name".to_string(), name.clone());
+        }
+        polygons.push(Polygon {
+            polygon_id: polygon_id.clone(),
+            points: vec![AnimatedPoint {
+                point_id: format!("{}-pt0", polygon_id),
+                initial_position: Some(Point {
+                    x: initial[0],
+                    y: initial[1],
+                    z: Some(initial[2]),
+                }),
+                movements,
+                start_frame: (start_frame > 0).then_some(start_frame),
+                ..Default::default()
+            }],
+            properties,
+            draw_order: index as i32,
+            feature_type: FeatureType::Point as i32,
+            ..Default::default()
+        });
+    }
+    Ok((polygons, last_frame + 1))
+}
+
+/// Where a track is at `time`: its first point before it starts, its last after it
+/// ends, and in between along the great circle joining the two points around it.
+fn position_at(points: &[TrackPoint], time: f64, radius: f32) -> Vec3 {
+    let on_sphere = |point: &TrackPoint| from_lat_lon(point.lat, point.lon, radius);
+    let after = points.partition_point(|point| point.time <= time);
+    if after == 0 {
+        return on_sphere(&points[0]);
+    }
+    if after == points.len() {
+        return on_sphere(&points[after - 1]);
+    }
+    let (from, to) = (&points[after - 1], &points[after]);
+    let t = ((time - from.time) / (to.time - from.time)) as f32;
+    let (a, b) = (on_sphere(from), on_sphere(to));
+    let chord = [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ];
+    // Antipodal points have no one great circle between them; stay put until the next
+    match normalize(chord) {
+        Some([x, y, z]) => [x * radius, y * radius, z * radius],
+        None => a,
+    }
+}
+
+/// A feature ID from a track name: its ASCII letters and digits, lowercased, in
+/// words joined by dashes. None if it has none.
+fn slug(name: &str) -> Option<String> {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    (!words.is_empty()).then(|| words.join("-"))
+}
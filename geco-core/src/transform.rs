@@ -0,0 +1,144 @@
+// klyja/geco-core/src/transform.rs
+// Whole-feature rigid rotations (e.g. a tectonic plate turning about its Euler pole)
+// and their inheritance from parent features, plus points pinned to other features.
+use crate::geometry::{
+    nearest_point_on_outline, quat_from_axis_angle, quat_mul, quat_rotate, quat_slerp, Quat, Vec3,
+    IDENTITY,
+};
+use crate::interpolation;
+use crate::protobuf_gen::{AnimatedPoint, FeatureType, Polygon, TransformKeyframe};
+
+/// Inserts a keyframe, replacing any existing keyframe at the same frame.
+pub fn upsert_keyframe(polygon: &mut Polygon, keyframe: TransformKeyframe) {
+    let keyframes = &mut polygon.transform_keyframes;
+    match keyframes.binary_search_by_key(&keyframe.frame, |k| k.frame) {
+        Ok(index) => keyframes[index] = keyframe,
+        Err(index) => keyframes.insert(index, keyframe),
+    }
+}
+
+fn keyframe_rotation(keyframe: &TransformKeyframe) -> Quat {
+    match &keyframe.axis {
+        Some(axis) => quat_from_axis_angle(
+            [axis.x, axis.y, axis.z.unwrap_or(0.0)],
+            keyframe.angle_degrees,
+        ),
+        None => IDENTITY,
+    }
+}
+
+/// The feature's own rotation at `frame`, slerped between keyframes and held
+/// constant outside them. Identity for features without transform keyframes.
+pub fn local_rotation_at_frame(polygon: &Polygon, frame: i32) -> Quat {
+    let keyframes = &polygon.transform_keyframes;
+    let next_index = keyframes.partition_point(|k| k.frame <= frame);
+    match (
+        next_index.checked_sub(1).map(|i| &keyframes[i]),
+        keyframes.get(next_index),
+    ) {
+        (None, None) => IDENTITY,
+        (Some(only), None) | (None, Some(only)) => keyframe_rotation(only),
+        (Some(previous), Some(next)) => {
+            let t = (frame - previous.frame) as f32 / (next.frame - previous.frame) as f32;
+            quat_slerp(keyframe_rotation(previous), keyframe_rotation(next), t)
+        }
+    }
+}
+
+/// The feature's rotation at `frame` including everything inherited from its parents.
+/// A parent chain that loops back on itself (only possible in hand-edited documents)
+/// is cut where it repeats.
+pub fn world_rotation_at_frame(all: &[Polygon], polygon: &Polygon, frame: i32) -> Quat {
+    let mut rotation = local_rotation_at_frame(polygon, frame);
+    let mut visited = vec![polygon.polygon_id.as_str()];
+    let mut parent_id = polygon.parent_id.as_deref();
+    while let Some(id) = parent_id {
+        if visited.contains(&id) {
+            break;
+        }
+        let Some(parent) = all.iter().find(|p| p.polygon_id == id) else {
+            break;
+        };
+        rotation = quat_mul(local_rotation_at_frame(parent, frame), rotation);
+        visited.push(id);
+        parent_id = parent.parent_id.as_deref();
+    }
+    rotation
+}
+
+/// Whether making `parent_id` the parent of `child_id` would create a cycle,
+/// i.e. `child_id` is `parent_id` itself or one of its ancestors.
+pub fn would_create_cycle(all: &[Polygon], child_id: &str, parent_id: &str) -> bool {
+    let mut current = Some(parent_id);
+    let mut steps = 0;
+    while let Some(id) = current {
+        if id == child_id || steps > all.len() {
+            return true;
+        }
+        current = all
+            .iter()
+            .find(|p| p.polygon_id == id)
+            .and_then(|p| p.parent_id.as_deref());
+        steps += 1;
+    }
+    false
+}
+
+/// The feature's points present at `frame`, paired with their final positions
+/// (own movements plus the feature's world rotation). Points pinned to another
+/// feature are then moved onto the nearest spot of that feature's outline;
+/// pins to missing features are ignored.
+pub fn world_points_at_frame<'a>(
+    all: &[Polygon],
+    polygon: &'a Polygon,
+    frame: i32,
+) -> Vec<(&'a AnimatedPoint, Vec3)> {
+    let mut points = rotated_points_at_frame(all, polygon, frame);
+    for (point, xyz) in &mut points {
+        let Some(target_id) = point.pinned_feature_id.as_deref() else {
+            continue;
+        };
+        if let Some(pinned) = pinned_position(all, polygon, target_id, frame, *xyz) {
+            *xyz = pinned;
+        }
+    }
+    points
+}
+
+/// Points before pins are resolved. Pin targets are read through this, so a chain
+/// of pins only follows one level and can't loop.
+fn rotated_points_at_frame<'a>(
+    all: &[Polygon],
+    polygon: &'a Polygon,
+    frame: i32,
+) -> Vec<(&'a AnimatedPoint, Vec3)> {
+    let rotation = world_rotation_at_frame(all, polygon, frame);
+    polygon
+        .points
+        .iter()
+        .filter_map(|point| {
+            let position = interpolation::position_at_frame(point, frame)?;
+            let xyz = [position.x, position.y, position.z.unwrap_or(0.0)];
+            Some((point, quat_rotate(rotation, xyz)))
+        })
+        .collect()
+}
+
+fn pinned_position(
+    all: &[Polygon],
+    polygon: &Polygon,
+    target_id: &str,
+    frame: i32,
+    xyz: Vec3,
+) -> Option<Vec3> {
+    if target_id == polygon.polygon_id {
+        return None;
+    }
+    let target = all.iter().find(|p| p.polygon_id == target_id)?;
+    let outline: Vec<Vec3> = rotated_points_at_frame(all, target, frame)
+        .into_iter()
+        .map(|(_, xyz)| xyz)
+        .collect();
+    let closed = target.feature_type() == FeatureType::Polygon;
+    nearest_point_on_outline(&outline, closed, xyz)
+}
@@ -4,18 +4,18 @@ use std::io::Result;
 use std::path::PathBuf; // Needed for path joining
 
 fn main() -> Result<()> {
-    println!("cargo:rerun-if-changed=../protobuf/AnimationData.proto");
     println!("cargo:rerun-if-changed=build.rs");
 
     // Get the Cargo OUT_DIR environment variable
     let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR not set"));
 
-    prost_build::Config::new()
-        // Specify the output directory for generated code
-        .out_dir(&out_dir) // Use OUT_DIR
-        // Compile the .proto file
-        .compile_protos(&["../protobuf/AnimationData.proto"], // Path relative to build.rs
-                        &["../protobuf/"]) // Include path
-        ?;
+    // With the `basemap` feature, bundle the packed coastline file into the build
+    println!("cargo:rerun-if-env-changed=KLYJA_BASEMAP_FILE");
+    if env::var_os("CARGO_FEATURE_BASEMAP").is_some() {
+        let basemap_file = env::var("KLYJA_BASEMAP_FILE")
+            .expect("KLYJA_BASEMAP_FILE must point to a packed basemap when the `basemap` feature is enabled");
+        println!("cargo:rerun-if-changed={}", basemap_file);
+        std::fs::copy(&basemap_file, out_dir.join("basemap.bin"))?;
+    }
     Ok(())
 }
@@ -0,0 +1,8 @@
+// klyja/geco/src/basemap.rs
+// The coastline dataset optionally bundled into the build. Decoding of the packed
+// format lives in `geco_core::basemap`.
+
+/// The coastline dataset packed into the build, if the `basemap` feature is enabled.
+/// build.rs copies the file named by `KLYJA_BASEMAP_FILE` into OUT_DIR.
+#[cfg(feature = "basemap")]
+pub(crate) const BUNDLED_BASEMAP: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/basemap.bin"));
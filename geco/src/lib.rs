@@ -3,20 +3,32 @@ use prost::Message;
 use wasm_bindgen::prelude::*;
 // --- Add serde for JSON serialization ---
 use serde::Serialize; // Needed for get_polygons_json
+use std::cell::RefCell;
+use std::collections::HashMap;
 
-// --- Protobuf Includes ---
-pub mod protobuf_gen {
-    // Use include! with OUT_DIR
-    include!(concat!(env!("OUT_DIR"), "/klyja.map_animation.v1.rs"));
+// --- Protobuf types, generated in geco-core ---
+pub use geco_core::protobuf_gen;
+use protobuf_gen::{
+    AnimatedPoint, CameraKeyframe, DataKeyframe, DataSeries, FeatureType, GridLayer, MapAnimation,
+    Point, Polygon, Scene, TransformKeyframe,
+};
 
-    // --- Add Serialize derive for relevant structs ---
-    // Need to add #[derive(serde::Serialize)] to the structs in the generated code
-    // This requires modifying the build script slightly or using feature flags in prost-build
-    // Let's try adding it via build script attributes first.
-    // If that fails, we might need prost-serde feature or manual JSON construction.
-    // Update: Let's create *separate* serializable structs within Geco to avoid build script complexity for now.
-}
-use protobuf_gen::{AnimatedPoint, MapAnimation, Point, Polygon};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(feature = "parallel")]
+pub use wasm_bindgen_rayon::init_thread_pool; // Must be awaited from JS before rendering
+
+mod basemap;
+mod profiling;
+// The engine itself lives in geco-core; this crate is its wasm-bindgen wrapper
+use geco_core::{
+    bounds, camera, chunked_load, color_ramp, data_series, delaunay, document_json, editing,
+    format, geojson, geometry, gpx, grid, instancing, interpolation, lazy_load, mirror, patch,
+    point_series, render, scatter, scenes, search, smoothing, stats, tracks, transform,
+};
+
+/// Maximum number of document snapshots kept for undo.
+const MAX_UNDO_DEPTH: usize = 100;
 
 // --- Simple Structs for JSON Serialization ---
 // Define simplified structs matching protobuf structure but with Serialize
@@ -56,6 +68,9 @@ struct SimplePolygon {
     polygon_id: String,
     points: Vec<SimpleAnimatedPoint>,
     properties: std::collections::HashMap<String, String>, // Protobuf map -> HashMap
+    draw_order: i32,
+    feature_type: &'static str,
+    parent_id: Option<String>,
 }
 impl From<&Polygon> for SimplePolygon {
     fn from(poly: &Polygon) -> Self {
@@ -63,11 +78,322 @@ impl From<&Polygon> for SimplePolygon {
             polygon_id: poly.polygon_id.clone(),
             points: poly.points.iter().map(SimpleAnimatedPoint::from).collect(),
             properties: poly.properties.clone(), // Clone the map
+            draw_order: poly.draw_order,
+            feature_type: editing::feature_type_name(poly.feature_type()),
+            parent_id: poly.parent_id.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SimpleKeyframe {
+    frame: i32,
+    position: SimplePoint,
+    easing: &'static str, // Movements are per-frame linear steps, so always "linear" for now
+}
+
+#[derive(Serialize)]
+struct SimplePointKeyframes {
+    point_id: String,
+    keyframes: Vec<SimpleKeyframe>,
+}
+impl From<&AnimatedPoint> for SimplePointKeyframes {
+    fn from(ap: &AnimatedPoint) -> Self {
+        SimplePointKeyframes {
+            point_id: ap.point_id.clone(),
+            keyframes: interpolation::point_keyframes(ap)
+                .iter()
+                .map(|(frame, position)| SimpleKeyframe {
+                    frame: *frame,
+                    position: SimplePoint::from(position),
+                    easing: "linear",
+                })
+                .collect(),
         }
     }
 }
+
+/// A feature's geometry resolved at a single frame, ready for rendering.
+#[derive(Serialize)]
+struct RenderableFeature {
+    feature_id: String,
+    feature_type: &'static str,
+    draw_order: i32,
+    points: Vec<RenderablePoint>,
+    properties: std::collections::HashMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct RenderablePoint {
+    point_id: String,
+    position: SimplePoint,
+}
+
+impl RenderableFeature {
+    /// `all` is the whole document, used to resolve transforms inherited from parents.
+    fn at_frame(all: &[Polygon], poly: &Polygon, frame: i32) -> Self {
+        RenderableFeature {
+            feature_id: poly.polygon_id.clone(),
+            feature_type: editing::feature_type_name(poly.feature_type()),
+            draw_order: poly.draw_order,
+            points: transform::world_points_at_frame(all, poly, frame)
+                .into_iter()
+                .map(|(ap, [x, y, z])| RenderablePoint {
+                    point_id: ap.point_id.clone(),
+                    position: SimplePoint { x, y, z: Some(z) },
+                })
+                .collect(),
+            properties: poly.properties.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SimpleCamera {
+    frame: i32,
+    position: Option<SimplePoint>,
+    target: Option<SimplePoint>,
+    zoom: f32,
+}
+impl From<&CameraKeyframe> for SimpleCamera {
+    fn from(keyframe: &CameraKeyframe) -> Self {
+        SimpleCamera {
+            frame: keyframe.frame,
+            position: keyframe.position.as_ref().map(SimplePoint::from),
+            target: keyframe.target.as_ref().map(SimplePoint::from),
+            zoom: keyframe.zoom,
+        }
+    }
+}
+#[derive(Serialize)]
+struct SimpleScene {
+    scene_id: String,
+    title: String,
+    start_frame: i32,
+    end_frame: i32,
+    camera: Option<SimpleCamera>, // Preset to cut to when jumping to the scene
+}
+impl From<&Scene> for SimpleScene {
+    fn from(scene: &Scene) -> Self {
+        SimpleScene {
+            scene_id: scene.scene_id.clone(),
+            title: scene.title.clone(),
+            start_frame: scene.start_frame,
+            end_frame: scene.end_frame,
+            camera: scene.camera.as_ref().map(SimpleCamera::from),
+        }
+    }
+}
+#[derive(Serialize)]
+struct SimpleBounds {
+    center: SimplePoint, // Unit direction from the globe center
+    angle_degrees: f32,
+}
+impl From<&bounds::BoundingCap> for SimpleBounds {
+    fn from(cap: &bounds::BoundingCap) -> Self {
+        let [x, y, z] = cap.center;
+        SimpleBounds {
+            center: SimplePoint { x, y, z: Some(z) },
+            angle_degrees: cap.angle_degrees,
+        }
+    }
+}
+#[derive(Serialize)]
+struct SimpleFeatureHeader {
+    polygon_id: String,
+    point_count: usize,
+    properties: std::collections::HashMap<String, String>,
+    draw_order: i32,
+    feature_type: &'static str,
+    parent_id: Option<String>,
+}
 // --- End Simple Structs ---
 
+/// Flat vertex buffers for one frame, features in draw order.
+/// Each getter returns a fresh typed array (Float32Array / Uint32Array) backed by its
+/// own ArrayBuffer rather than a view into wasm memory, so it stays valid after this
+/// object is freed and can be listed as a transferable when posting it from a Worker.
+#[wasm_bindgen]
+pub struct RenderBuffers {
+    positions: Vec<f32>,
+    colors: Vec<f32>,
+    uvs: Vec<f32>,
+    indices: Vec<u32>,
+    vertex_counts: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl RenderBuffers {
+    /// x, y, z per vertex.
+    #[wasm_bindgen(getter)]
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    /// r, g, b per vertex, from the color ramp when the point carries data.
+    #[wasm_bindgen(getter)]
+    pub fn colors(&self) -> Vec<f32> {
+        self.colors.clone()
+    }
+
+    /// u, v per vertex, equirectangular so fills can be textured with world imagery.
+    #[wasm_bindgen(getter)]
+    pub fn uvs(&self) -> Vec<f32> {
+        self.uvs.clone()
+    }
+
+    /// Fill triangles for polygon features, as indices into the vertex arrays.
+    #[wasm_bindgen(getter)]
+    pub fn indices(&self) -> Vec<u32> {
+        self.indices.clone()
+    }
+
+    /// Number of vertices belonging to each feature.
+    #[wasm_bindgen(getter)]
+    pub fn vertex_counts(&self) -> Vec<u32> {
+        self.vertex_counts.clone()
+    }
+}
+
+impl From<render::VertexBuffers> for RenderBuffers {
+    fn from(buffers: render::VertexBuffers) -> Self {
+        RenderBuffers {
+            positions: buffers.positions,
+            colors: buffers.colors,
+            uvs: buffers.uvs,
+            indices: buffers.indices,
+            vertex_counts: buffers.vertex_counts,
+        }
+    }
+}
+
+/// Render buffers for a range of frames packed into one set of arrays, so exporters
+/// and scrub previews cross the wasm boundary once. The per-frame `*_starts` arrays
+/// locate each frame's slice; indices are relative to their frame's first vertex.
+/// Getters return transferable typed arrays, like `RenderBuffers`.
+#[wasm_bindgen]
+pub struct FrameRangeRenderBuffers {
+    frames: Vec<i32>,
+    positions: Vec<f32>,
+    colors: Vec<f32>,
+    uvs: Vec<f32>,
+    indices: Vec<u32>,
+    vertex_counts: Vec<u32>,
+    vertex_starts: Vec<u32>,
+    index_starts: Vec<u32>,
+    feature_starts: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl FrameRangeRenderBuffers {
+    /// The frames included, in order.
+    #[wasm_bindgen(getter)]
+    pub fn frames(&self) -> Vec<i32> {
+        self.frames.clone()
+    }
+
+    /// x, y, z per vertex, all frames back to back.
+    #[wasm_bindgen(getter)]
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    /// r, g, b per vertex.
+    #[wasm_bindgen(getter)]
+    pub fn colors(&self) -> Vec<f32> {
+        self.colors.clone()
+    }
+
+    /// u, v per vertex.
+    #[wasm_bindgen(getter)]
+    pub fn uvs(&self) -> Vec<f32> {
+        self.uvs.clone()
+    }
+
+    /// Fill triangles, indexing vertices from the start of their own frame.
+    #[wasm_bindgen(getter)]
+    pub fn indices(&self) -> Vec<u32> {
+        self.indices.clone()
+    }
+
+    /// Number of vertices belonging to each feature, frame after frame.
+    #[wasm_bindgen(getter)]
+    pub fn vertex_counts(&self) -> Vec<u32> {
+        self.vertex_counts.clone()
+    }
+
+    /// First vertex of each frame.
+    #[wasm_bindgen(getter)]
+    pub fn vertex_starts(&self) -> Vec<u32> {
+        self.vertex_starts.clone()
+    }
+
+    /// First entry in `indices` for each frame.
+    #[wasm_bindgen(getter)]
+    pub fn index_starts(&self) -> Vec<u32> {
+        self.index_starts.clone()
+    }
+
+    /// First entry in `vertex_counts` for each frame.
+    #[wasm_bindgen(getter)]
+    pub fn feature_starts(&self) -> Vec<u32> {
+        self.feature_starts.clone()
+    }
+}
+
+impl From<render::FrameRangeBuffers> for FrameRangeRenderBuffers {
+    fn from(packed: render::FrameRangeBuffers) -> Self {
+        FrameRangeRenderBuffers {
+            frames: packed.frames,
+            positions: packed.buffers.positions,
+            colors: packed.buffers.colors,
+            uvs: packed.buffers.uvs,
+            indices: packed.buffers.indices,
+            vertex_counts: packed.buffers.vertex_counts,
+            vertex_starts: packed.vertex_starts,
+            index_starts: packed.index_starts,
+            feature_starts: packed.feature_starts,
+        }
+    }
+}
+
+/// Per-instance transforms for drawing a symbol many times along a path
+/// (e.g. with a three.js InstancedMesh). Getters return transferable typed arrays,
+/// like `RenderBuffers`.
+#[wasm_bindgen]
+pub struct InstanceBuffers {
+    positions: Vec<f32>,
+    directions: Vec<f32>,
+    matrices: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl InstanceBuffers {
+    /// x, y, z per instance.
+    #[wasm_bindgen(getter)]
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    /// Unit tangent x, y, z per instance, pointing along the path.
+    #[wasm_bindgen(getter)]
+    pub fn directions(&self) -> Vec<f32> {
+        self.directions.clone()
+    }
+
+    /// 16 floats per instance, column-major like three.js `Matrix4.fromArray`.
+    #[wasm_bindgen(getter)]
+    pub fn matrices(&self) -> Vec<f32> {
+        self.matrices.clone()
+    }
+
+    /// Number of instances.
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.positions.len() / 3
+    }
+}
+
 // Optional logging setup...
 #[wasm_bindgen]
 extern "C" {
@@ -82,6 +408,23 @@ pub struct Geco {
     animation_state: MapAnimation,
     // --- Track the currently active polygon for adding points ---
     active_polygon_id: Option<String>,
+    // --- Feature (polygon) IDs targeted by batch operations ---
+    selection: Vec<String>,
+    // --- Document snapshots taken before each edit, most recent last ---
+    undo_stack: Vec<MapAnimation>,
+    // --- Maps point data values to vertex colors (view setting, not saved) ---
+    color_ramp: Option<color_ramp::ColorRamp>,
+    // --- Bounding caps by (feature ID, frame), cleared whenever the document changes ---
+    bounds_cache: RefCell<HashMap<(String, i32), Option<bounds::BoundingCap>>>,
+    // --- Document being streamed in by begin_load / feed_chunk / finish_load ---
+    pending_load: Option<chunked_load::ChunkedDecoder>,
+    // --- Lat/lon spacing new and moved points snap to (editor setting, not saved) ---
+    snap_grid_degrees: Option<f32>,
+    // --- Low-pass filter for points drawn into the active polygon (editor setting) ---
+    input_smoother: Option<smoothing::InputSmoother>,
+    // --- Live symmetry plane normal and the mirror of the active polygon being drawn ---
+    symmetry_normal: Option<geometry::Vec3>,
+    symmetry_partner: Option<String>,
 }
 
 #[wasm_bindgen]
@@ -95,9 +438,39 @@ impl Geco {
                 name: "Untitled Animation".to_string(),
                 total_frames: 0,
                 polygons: vec![],
+                grid_layers: vec![],
+                camera_keyframes: vec![],
+                data_series: vec![],
+                scenes: vec![],
+                format_version: format::CURRENT_FORMAT_VERSION,
             },
             active_polygon_id: None, // No active polygon initially
+            selection: vec![],
+            undo_stack: vec![],
+            color_ramp: None,
+            bounds_cache: RefCell::new(HashMap::new()),
+            pending_load: None,
+            snap_grid_degrees: None,
+            input_smoother: None,
+            symmetry_normal: None,
+            symmetry_partner: None,
+        }
+    }
+
+    /// Creates an instance from state shared by another instance, typically to run
+    /// interpolation in a Web Worker: `document` is the output of `get_animation_protobuf`
+    /// (transfer its buffer to the worker to avoid a copy) and `color_ramp_json` the
+    /// stops passed to `set_color_ramp`, if any, since view settings aren't saved.
+    pub fn from_shared_state(
+        document: &[u8],
+        color_ramp_json: Option<String>,
+    ) -> Result<Geco, JsValue> {
+        let mut geco = Geco::new();
+        geco.load_animation_protobuf(document)?;
+        if let Some(stops_json) = color_ramp_json {
+            geco.set_color_ramp(&stops_json)?;
         }
+        Ok(geco)
     }
 
     // --- Name Management ---
@@ -112,21 +485,40 @@ impl Geco {
     // --- Geometry Management ---
     pub fn add_static_polygon(&mut self, polygon_id: String, point_x: f32, point_y: f32) {
         console_log!("Adding static polygon: {}", polygon_id);
-        let point = Point {
-            x: point_x,
-            y: point_y,
-            z: Some(0.0),
-        }; // Add default Z
+        self.record_undo();
+        if let Some(smoother) = self.input_smoother.as_mut() {
+            smoother.start_stroke();
+        }
+        let point = self.drawn_point(point_x, point_y, 0.0); // Add default Z
         let animated_point = AnimatedPoint {
             point_id: format!("{}-pt0", polygon_id),
             initial_position: Some(point),
             movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
         };
         let polygon = Polygon {
             polygon_id: polygon_id.clone(),
             points: vec![animated_point],
             properties: Default::default(),
+            draw_order: self.top_draw_order() + 1, // New features go on top
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
         };
+        // --- With live symmetry on, draw the mirror image alongside ---
+        self.symmetry_partner = self.symmetry_normal.map(|normal| {
+            let mirror_id = editing::unique_feature_id(
+                &self.animation_state.polygons,
+                &format!("{}-mirror", polygon_id),
+            );
+            let mirrored = mirror::mirror_polygon(&polygon, normal, &mirror_id);
+            self.animation_state.polygons.push(mirrored);
+            mirror_id
+        });
         self.animation_state.polygons.push(polygon);
         // --- Set the newly added polygon as active ---
         self.active_polygon_id = Some(polygon_id.clone());
@@ -137,9 +529,60 @@ impl Geco {
         );
     }
 
+    /// Makes new and moved points snap to a lat/lon grid with `degrees` spacing,
+    /// for schematic maps with regular vertices. Zero turns snapping off.
+    pub fn set_snap_grid(&mut self, degrees: f32) -> Result<(), JsValue> {
+        if !degrees.is_finite() || degrees < 0.0 {
+            let error_msg = format!("Invalid snap grid spacing: {}", degrees);
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        }
+        self.snap_grid_degrees = (degrees > 0.0).then_some(degrees);
+        Ok(())
+    }
+
+    /// Turns on live symmetry: polygons started with `add_static_polygon` get a mirror
+    /// image across the great circle with normal (normal_x, normal_y, normal_z), and
+    /// points added to the active polygon are mirrored into it as they are drawn.
+    pub fn set_live_symmetry(
+        &mut self,
+        normal_x: f32,
+        normal_y: f32,
+        normal_z: f32,
+    ) -> Result<(), JsValue> {
+        self.symmetry_normal = Some(mirror_normal(normal_x, normal_y, normal_z)?);
+        Ok(())
+    }
+
+    pub fn clear_live_symmetry(&mut self) {
+        self.symmetry_normal = None;
+        self.symmetry_partner = None;
+    }
+
+    /// Smooths points drawn with `add_static_polygon` / `add_point_to_active_polygon`:
+    /// each point is blended with the previous one, `alpha` being the weight of the new
+    /// sample. Lower values give steadier but laggier lines; 1 turns smoothing off.
+    pub fn set_input_smoothing(&mut self, alpha: f32) -> Result<(), JsValue> {
+        if !(alpha > 0.0 && alpha <= 1.0) {
+            let error_msg = format!("Input smoothing alpha must be in (0, 1], got {}", alpha);
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        }
+        self.input_smoother = (alpha < 1.0).then(|| smoothing::InputSmoother::new(alpha));
+        Ok(())
+    }
+
     /// Adds a point to the currently active polygon.
     pub fn add_point_to_active_polygon(&mut self, x: f32, y: f32, z: f32) {
         console_log!("Attempting to add point ({}, {}, {})", x, y, z);
+        if self
+            .active_polygon_id
+            .as_ref()
+            .is_some_and(|id| self.find_polygon(id).is_some())
+        {
+            self.record_undo();
+        }
+        let point = self.drawn_point(x, y, z);
         if let Some(active_id) = &self.active_polygon_id {
             console_log!("Active polygon ID: {}", active_id);
             // Find the active polygon by ID
@@ -149,15 +592,17 @@ impl Geco {
                 .iter_mut()
                 .find(|p| p.polygon_id == *active_id)
             {
-                let point_index = polygon.points.len();
-                let point_id = format!("{}-pt{}", active_id, point_index);
+                let point_id = editing::next_point_id(polygon);
                 console_log!("New point ID: {}", point_id);
 
-                let point = Point { x, y, z: Some(z) };
                 let animated_point = AnimatedPoint {
                     point_id: point_id.clone(),
-                    initial_position: Some(point),
+                    initial_position: Some(point.clone()),
                     movements: vec![], // Static point initially
+                    end_frame: None,
+                    start_frame: None,
+                    scalar_keyframes: vec![],
+                    pinned_feature_id: None,
                 };
                 polygon.points.push(animated_point);
                 console_log!(
@@ -176,18 +621,877 @@ impl Geco {
         } else {
             console_log!("Warning: No active polygon set. Cannot add point.");
         }
+        if self.active_polygon_id.is_some() {
+            self.mirror_into_partner(point);
+        }
+    }
+
+    /// Inserts a new point into a feature's shape at `frame`, before the point currently at
+    /// `index` in the shape's ordered point list. The point exists from `frame` onwards.
+    pub fn insert_point_at_index(
+        &mut self,
+        feature_id: String,
+        frame: i32,
+        index: usize,
+        x: f32,
+        y: f32,
+        z: f32,
+    ) -> Result<String, JsValue> {
+        let polygon = self.find_editable_polygon(&feature_id)?;
+        let Some(storage_index) = editing::storage_index_at_frame(polygon, frame, index) else {
+            let error_msg = format!(
+                "Index {} is out of range for feature '{}' at frame {}",
+                index, feature_id, frame
+            );
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        };
+        let point_id = editing::next_point_id(polygon);
+        let point = self.snapped_point(x, y, z);
+
+        self.record_undo();
+        let polygon = self.find_polygon_mut(&feature_id).expect("checked above");
+        polygon.points.insert(
+            storage_index,
+            AnimatedPoint {
+                point_id: point_id.clone(),
+                initial_position: Some(point),
+                movements: vec![],
+                end_frame: None,
+                start_frame: (frame > 0).then_some(frame),
+                scalar_keyframes: vec![],
+                pinned_feature_id: None,
+            },
+        );
+        console_log!(
+            "Inserted point {} into feature '{}' at index {} from frame {}",
+            point_id,
+            feature_id,
+            index,
+            frame
+        );
+        Ok(point_id)
+    }
+
+    /// Removes a point from a feature's shape starting at `frame`.
+    /// Earlier frames keep the point; removing at frame 0 deletes it outright.
+    pub fn remove_point_at_frame(
+        &mut self,
+        feature_id: String,
+        point_id: String,
+        frame: i32,
+    ) -> Result<(), JsValue> {
+        let polygon = self.find_editable_polygon(&feature_id)?;
+        if !polygon.points.iter().any(|p| p.point_id == point_id) {
+            return Err(point_not_found(&feature_id, &point_id));
+        }
+
+        self.record_undo();
+        let polygon = self.find_polygon_mut(&feature_id).expect("checked above");
+        if frame <= 0 {
+            polygon.points.retain(|p| p.point_id != point_id);
+        } else if let Some(point) = polygon.points.iter_mut().find(|p| p.point_id == point_id) {
+            // Keep the earliest removal if the point was already cut off before `frame`
+            point.end_frame = Some(point.end_frame.map_or(frame, |end| end.min(frame)));
+        }
+        console_log!(
+            "Point '{}' removed from feature '{}' from frame {}",
+            point_id,
+            feature_id,
+            frame
+        );
+        Ok(())
+    }
+
+    /// Adds a mirrored copy of a feature, reflected across the great circle whose plane
+    /// has normal (normal_x, normal_y, normal_z). The copy animates as the mirror image
+    /// and goes on top; returns its ID.
+    pub fn mirror_feature(
+        &mut self,
+        feature_id: String,
+        normal_x: f32,
+        normal_y: f32,
+        normal_z: f32,
+    ) -> Result<String, JsValue> {
+        let normal = mirror_normal(normal_x, normal_y, normal_z)?;
+        let polygon = self.find_editable_polygon(&feature_id)?;
+        let mirror_id = editing::unique_feature_id(
+            &self.animation_state.polygons,
+            &format!("{}-mirror", feature_id),
+        );
+        let mut mirrored = mirror::mirror_polygon(polygon, normal, &mirror_id);
+        mirrored.draw_order = self.top_draw_order() + 1;
+
+        self.record_undo();
+        self.animation_state.polygons.push(mirrored);
+        console_log!("Feature '{}' mirrored as '{}'", feature_id, mirror_id);
+        Ok(mirror_id)
+    }
+
+    /// Converts a feature to another type ("polygon", "polyline" or "point"),
+    /// e.g. closing a polyline into a polygon. Fails if the feature's points
+    /// don't fit the new type.
+    pub fn convert_feature_type(
+        &mut self,
+        feature_id: String,
+        new_type: String,
+    ) -> Result<(), JsValue> {
+        let Some(feature_type) = editing::parse_feature_type(&new_type) else {
+            let error_msg = format!("Unknown feature type '{}'", new_type);
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        };
+        let mut converted = self.find_editable_polygon(&feature_id)?.clone();
+        editing::convert_feature_type(&mut converted, feature_type).map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+
+        self.record_undo();
+        if let Some(polygon) = self.find_polygon_mut(&feature_id) {
+            *polygon = converted;
+        }
+        console_log!("Feature '{}' converted to {}", feature_id, new_type);
+        Ok(())
+    }
+
+    // --- Hierarchy ---
+    /// Makes `parent_id` the parent of a feature, so the feature follows the
+    /// parent's transform on top of its own. Pass None to detach it.
+    /// Fails if the parent doesn't exist or would end up its own ancestor.
+    pub fn set_feature_parent(
+        &mut self,
+        feature_id: String,
+        parent_id: Option<String>,
+    ) -> Result<(), JsValue> {
+        self.find_editable_polygon(&feature_id)?;
+        if let Some(parent_id) = parent_id.as_deref() {
+            self.find_polygon(parent_id)
+                .ok_or_else(|| feature_not_found(parent_id))?;
+            if transform::would_create_cycle(&self.animation_state.polygons, &feature_id, parent_id)
+            {
+                let error_msg = format!(
+                    "Cannot make '{}' the parent of '{}': it would create a cycle",
+                    parent_id, feature_id
+                );
+                console_log!("Error: {}", error_msg);
+                return Err(JsValue::from_str(&error_msg));
+            }
+        }
+
+        self.record_undo();
+        if let Some(polygon) = self.find_polygon_mut(&feature_id) {
+            polygon.parent_id = parent_id;
+        }
+        Ok(())
+    }
+
+    /// Pins a point to another feature's outline: at every frame the point is moved
+    /// to the nearest spot on that feature (e.g. a river mouth on a moving coastline).
+    /// Pass None to unpin it. Fails if the target doesn't exist or is the point's own feature.
+    pub fn pin_point_to_feature(
+        &mut self,
+        feature_id: String,
+        point_id: String,
+        target_id: Option<String>,
+    ) -> Result<(), JsValue> {
+        let polygon = self.find_editable_polygon(&feature_id)?;
+        if !polygon.points.iter().any(|p| p.point_id == point_id) {
+            return Err(point_not_found(&feature_id, &point_id));
+        }
+        if let Some(target_id) = target_id.as_deref() {
+            self.find_polygon(target_id)
+                .ok_or_else(|| feature_not_found(target_id))?;
+            if target_id == feature_id {
+                let error_msg = format!("Cannot pin a point of '{}' to itself", feature_id);
+                console_log!("Error: {}", error_msg);
+                return Err(JsValue::from_str(&error_msg));
+            }
+        }
+
+        self.record_undo();
+        if let Some(point) = self
+            .find_polygon_mut(&feature_id)
+            .and_then(|p| p.points.iter_mut().find(|p| p.point_id == point_id))
+        {
+            point.pinned_feature_id = target_id;
+        }
+        Ok(())
+    }
+
+    /// Sets a feature's rotation at `frame`: `angle_degrees` about the axis through
+    /// the globe center and (axis_x, axis_y, axis_z). Replaces any keyframe at that frame.
+    /// The rotation also applies to the feature's children.
+    pub fn set_feature_transform_keyframe(
+        &mut self,
+        feature_id: String,
+        frame: i32,
+        axis_x: f32,
+        axis_y: f32,
+        axis_z: f32,
+        angle_degrees: f32,
+    ) -> Result<(), JsValue> {
+        if geometry::normalize([axis_x, axis_y, axis_z]).is_none() {
+            let error_msg = "Rotation axis must be non-zero".to_string();
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        }
+        self.find_editable_polygon(&feature_id)?;
+
+        self.record_undo();
+        if let Some(polygon) = self.find_polygon_mut(&feature_id) {
+            transform::upsert_keyframe(
+                polygon,
+                TransformKeyframe {
+                    frame,
+                    axis: Some(Point {
+                        x: axis_x,
+                        y: axis_y,
+                        z: Some(axis_z),
+                    }),
+                    angle_degrees,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    // --- Basemap ---
+    /// Adds the coastline dataset bundled at build time as locked background
+    /// polylines at `radius`. Returns the number of features added.
+    #[cfg(feature = "basemap")]
+    pub fn add_bundled_basemap(&mut self, radius: f32) -> Result<usize, JsValue> {
+        self.add_basemap_from_bytes(basemap::BUNDLED_BASEMAP, radius)
+    }
+
+    /// Adds coastline rings from packed basemap data (see basemap.rs for the format)
+    /// as locked background polylines at `radius`. Returns the number of features added.
+    pub fn add_basemap_from_bytes(&mut self, data: &[u8], radius: f32) -> Result<usize, JsValue> {
+        let rings = geco_core::basemap::decode_rings(data).map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+
+        self.record_undo();
+        // Basemap features sit below everything drawn so far
+        let draw_order = self
+            .animation_state
+            .polygons
+            .iter()
+            .map(|p| p.draw_order)
+            .min()
+            .unwrap_or(0)
+            - 1;
+        let mut added = 0;
+        for (ring_index, ring) in rings.iter().enumerate() {
+            if ring.len() < 2 {
+                continue;
+            }
+            let polygon_id = format!("basemap-coast-{}", ring_index);
+            if self.find_polygon(&polygon_id).is_some() {
+                continue; // Already imported
+            }
+            let points = ring
+                .iter()
+                .enumerate()
+                .map(|(point_index, &(lat, lon))| {
+                    let [x, y, z] = geometry::from_lat_lon(lat, lon, radius);
+                    AnimatedPoint {
+                        point_id: format!("{}-pt{}", polygon_id, point_index),
+                        initial_position: Some(Point { x, y, z: Some(z) }),
+                        movements: vec![],
+                        end_frame: None,
+                        start_frame: None,
+                        scalar_keyframes: vec![],
+                        pinned_feature_id: None,
+                    }
+                })
+                .collect();
+            self.animation_state.polygons.push(Polygon {
+                polygon_id,
+                points,
+                properties: [("layer".to_string(), "basemap".to_string())].into(),
+                draw_order,
+                feature_type: FeatureType::Polyline as i32,
+                locked: true,
+                transform_keyframes: vec![],
+                parent_id: None,
+            });
+            added += 1;
+        }
+        console_log!("Added {} basemap features", added);
+        Ok(added)
+    }
+
+    // --- Point Data ---
+    /// Sets a point's data value (e.g. elevation) at `frame`. Values are
+    /// interpolated between frames and mapped to colors by the color ramp.
+    pub fn set_point_scalar(
+        &mut self,
+        feature_id: String,
+        point_id: String,
+        frame: i32,
+        value: f32,
+    ) -> Result<(), JsValue> {
+        let polygon = self.find_editable_polygon(&feature_id)?;
+        if !polygon.points.iter().any(|p| p.point_id == point_id) {
+            return Err(point_not_found(&feature_id, &point_id));
+        }
+        self.record_undo();
+        let polygon = self.find_polygon_mut(&feature_id).expect("checked above");
+        if let Some(point) = polygon.points.iter_mut().find(|p| p.point_id == point_id) {
+            editing::upsert_scalar_keyframe(point, frame, value);
+        }
+        Ok(())
+    }
+
+    /// Sets the color ramp from JSON stops: `[{"value": 0, "color": [r, g, b]}, ...]`.
+    pub fn set_color_ramp(&mut self, stops_json: &str) -> Result<(), JsValue> {
+        let ramp = color_ramp::ColorRamp::from_json(stops_json).map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+        self.color_ramp = Some(ramp);
+        Ok(())
+    }
+
+    pub fn clear_color_ramp(&mut self) {
+        self.color_ramp = None;
+    }
+
+    // --- Grid Layers ---
+    /// Adds an empty raster layer with `lat_count` rows and `lon_count` columns.
+    pub fn add_grid_layer(
+        &mut self,
+        grid_id: String,
+        lat_count: u32,
+        lon_count: u32,
+    ) -> Result<(), JsValue> {
+        if lat_count == 0 || lon_count == 0 {
+            return Err(JsValue::from_str("Grid dimensions must be non-zero"));
+        }
+        if self.find_grid_layer(&grid_id).is_some() {
+            let error_msg = format!("Grid layer '{}' already exists", grid_id);
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        }
+        self.record_undo();
+        self.animation_state.grid_layers.push(GridLayer {
+            grid_id: grid_id.clone(),
+            lat_count: lat_count as i32,
+            lon_count: lon_count as i32,
+            keyframes: vec![],
+            properties: Default::default(),
+        });
+        console_log!(
+            "Grid layer '{}' added ({}x{})",
+            grid_id,
+            lat_count,
+            lon_count
+        );
+        Ok(())
+    }
+
+    /// Sets the grid's values at `frame` (row-major, lat_count * lon_count values).
+    pub fn set_grid_keyframe(
+        &mut self,
+        grid_id: String,
+        frame: i32,
+        values: Vec<f32>,
+    ) -> Result<(), JsValue> {
+        let mut layer = self
+            .find_grid_layer(&grid_id)
+            .ok_or_else(|| grid_layer_not_found(&grid_id))?
+            .clone();
+        grid::upsert_keyframe(&mut layer, frame, values).map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+
+        self.record_undo();
+        if let Some(existing) = self
+            .animation_state
+            .grid_layers
+            .iter_mut()
+            .find(|g| g.grid_id == grid_id)
+        {
+            *existing = layer;
+        }
+        Ok(())
+    }
+
+    /// Returns the grid's values interpolated at `frame` as a Float32Array.
+    /// A layer without keyframes yields an empty array.
+    pub fn get_grid_values(&self, grid_id: String, frame: i32) -> Result<Vec<f32>, JsValue> {
+        let layer = self
+            .find_grid_layer(&grid_id)
+            .ok_or_else(|| grid_layer_not_found(&grid_id))?;
+        Ok(grid::values_at_frame(layer, frame).unwrap_or_default())
+    }
+
+    // --- Camera Path ---
+    /// Sets the camera position, look-at target and zoom at `frame`.
+    #[allow(clippy::too_many_arguments)] // Flat numbers keep the JS call cheap
+    pub fn set_camera_keyframe(
+        &mut self,
+        frame: i32,
+        position_x: f32,
+        position_y: f32,
+        position_z: f32,
+        target_x: f32,
+        target_y: f32,
+        target_z: f32,
+        zoom: f32,
+    ) {
+        self.record_undo();
+        camera::upsert_keyframe(
+            &mut self.animation_state.camera_keyframes,
+            CameraKeyframe {
+                frame,
+                position: Some(Point {
+                    x: position_x,
+                    y: position_y,
+                    z: Some(position_z),
+                }),
+                target: Some(Point {
+                    x: target_x,
+                    y: target_y,
+                    z: Some(target_z),
+                }),
+                zoom,
+            },
+        );
+        console_log!("Camera keyframe set at frame {}", frame);
+    }
+
+    /// Removes the camera keyframe at `frame`. Returns false if there was none.
+    pub fn remove_camera_keyframe(&mut self, frame: i32) -> bool {
+        let keyframes = &self.animation_state.camera_keyframes;
+        let Some(index) = keyframes.iter().position(|k| k.frame == frame) else {
+            return false;
+        };
+        self.record_undo();
+        self.animation_state.camera_keyframes.remove(index);
+        true
+    }
+
+    /// Returns the camera (position, target, zoom) at `frame` as JSON, or undefined
+    /// when the animation has no camera keyframes.
+    pub fn get_camera_at_frame(&self, frame: i32) -> Option<String> {
+        let camera = camera::camera_at_frame(&self.animation_state.camera_keyframes, frame)?;
+        serde_json::to_string(&SimpleCamera::from(&camera)).ok()
+    }
+
+    // --- Data Series ---
+    /// Adds a time series to show alongside the map (e.g. "CO2" in "ppm"), or renames
+    /// an existing one, keeping its values.
+    pub fn set_data_series(&mut self, series_id: String, name: String, unit: String) {
+        self.record_undo();
+        let all_series = &mut self.animation_state.data_series;
+        match all_series.iter_mut().find(|s| s.series_id == series_id) {
+            Some(series) => {
+                series.name = name;
+                series.unit = unit;
+            }
+            None => all_series.push(DataSeries {
+                series_id: series_id.clone(),
+                name,
+                unit,
+                ..Default::default()
+            }),
+        }
+        console_log!("Data series '{}' set", series_id);
+    }
+
+    /// Sets the series value at `frame`, with an optional label (e.g. a year) shown
+    /// from this frame until the next label.
+    pub fn set_data_keyframe(
+        &mut self,
+        series_id: String,
+        frame: i32,
+        value: f32,
+        label: Option<String>,
+    ) -> Result<(), JsValue> {
+        if !value.is_finite() {
+            return Err(JsValue::from_str("Series values must be finite numbers"));
+        }
+        self.find_data_series(&series_id)
+            .ok_or_else(|| data_series_not_found(&series_id))?;
+        self.record_undo();
+        let series = self
+            .find_data_series_mut(&series_id)
+            .expect("checked above");
+        data_series::upsert_keyframe(
+            series,
+            DataKeyframe {
+                frame,
+                value,
+                label,
+            },
+        );
+        Ok(())
+    }
+
+    /// Replaces the series values with samples taken at external times (e.g. CO2 by
+    /// year), binding `start_time` to frame 0 and `end_time` to the last frame.
+    /// Samples outside that span are dropped. Returns the number of keyframes set.
+    pub fn bind_data_series(
+        &mut self,
+        series_id: String,
+        times: Vec<f64>,
+        values: Vec<f32>,
+        start_time: f64,
+        end_time: f64,
+    ) -> Result<u32, JsValue> {
+        let mut series = self
+            .find_data_series(&series_id)
+            .ok_or_else(|| data_series_not_found(&series_id))?
+            .clone();
+        let total_frames = self.animation_state.total_frames;
+        let count = data_series::bind_samples(
+            &mut series,
+            &times,
+            &values,
+            start_time,
+            end_time,
+            total_frames,
+        )
+        .map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+
+        self.record_undo();
+        *self
+            .find_data_series_mut(&series_id)
+            .expect("checked above") = series;
+        console_log!("Bound {} samples to data series '{}'", count, series_id);
+        Ok(count as u32)
+    }
+
+    /// Removes a data series. Returns false if there was none with this ID.
+    pub fn remove_data_series(&mut self, series_id: String) -> bool {
+        let Some(index) = self
+            .animation_state
+            .data_series
+            .iter()
+            .position(|s| s.series_id == series_id)
+        else {
+            return false;
+        };
+        self.record_undo();
+        self.animation_state.data_series.remove(index);
+        true
+    }
+
+    /// Returns the value and label of every data series at `frame` as JSON, for
+    /// on-screen display: `[{"series_id", "name", "unit", "value", "label"}, ...]`.
+    /// Series without values are left out.
+    pub fn get_data_at_frame(&self, frame: i32) -> String {
+        let readings = data_series::readings_at_frame(&self.animation_state.data_series, frame);
+        serde_json::to_string(&readings).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    // --- Scenes ---
+    /// Adds a titled scene covering frames `start_frame..end_frame`, or moves and
+    /// retitles an existing one, keeping its camera preset. Scenes may not overlap.
+    pub fn set_scene(
+        &mut self,
+        scene_id: String,
+        title: String,
+        start_frame: i32,
+        end_frame: i32,
+    ) -> Result<(), JsValue> {
+        let mut all_scenes = self.animation_state.scenes.clone();
+        let existing = all_scenes.iter().find(|s| s.scene_id == scene_id);
+        let scene = Scene {
+            scene_id: scene_id.clone(),
+            title,
+            start_frame,
+            end_frame,
+            ..existing.cloned().unwrap_or_default()
+        };
+        scenes::upsert_scene(&mut all_scenes, scene).map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+
+        self.record_undo();
+        self.animation_state.scenes = all_scenes;
+        console_log!(
+            "Scene '{}' set to frames {}..{}",
+            scene_id,
+            start_frame,
+            end_frame
+        );
+        Ok(())
+    }
+
+    /// Sets the view a player cuts to when jumping to the scene.
+    #[allow(clippy::too_many_arguments)] // Flat numbers keep the JS call cheap
+    pub fn set_scene_camera(
+        &mut self,
+        scene_id: String,
+        position_x: f32,
+        position_y: f32,
+        position_z: f32,
+        target_x: f32,
+        target_y: f32,
+        target_z: f32,
+        zoom: f32,
+    ) -> Result<(), JsValue> {
+        let start_frame = self
+            .find_scene(&scene_id)
+            .ok_or_else(|| scene_not_found(&scene_id))?
+            .start_frame;
+        self.record_undo();
+        let scene = self.find_scene_mut(&scene_id).expect("checked above");
+        scene.camera = Some(CameraKeyframe {
+            frame: start_frame,
+            position: Some(Point {
+                x: position_x,
+                y: position_y,
+                z: Some(position_z),
+            }),
+            target: Some(Point {
+                x: target_x,
+                y: target_y,
+                z: Some(target_z),
+            }),
+            zoom,
+        });
+        Ok(())
+    }
+
+    /// Removes the scene's camera preset, so jumping to it keeps the camera path.
+    pub fn clear_scene_camera(&mut self, scene_id: String) -> Result<(), JsValue> {
+        self.find_scene(&scene_id)
+            .ok_or_else(|| scene_not_found(&scene_id))?;
+        self.record_undo();
+        self.find_scene_mut(&scene_id)
+            .expect("checked above")
+            .camera = None;
+        Ok(())
+    }
+
+    /// Removes a scene. Returns false if there was none with this ID.
+    pub fn remove_scene(&mut self, scene_id: String) -> bool {
+        let Some(index) = self
+            .animation_state
+            .scenes
+            .iter()
+            .position(|s| s.scene_id == scene_id)
+        else {
+            return false;
+        };
+        self.record_undo();
+        self.animation_state.scenes.remove(index);
+        true
+    }
+
+    /// Returns the scenes in playback order as JSON:
+    /// `[{"scene_id", "title", "start_frame", "end_frame", "camera"}, ...]`.
+    pub fn get_scenes_json(&self) -> String {
+        let scenes: Vec<SimpleScene> = self
+            .animation_state
+            .scenes
+            .iter()
+            .map(SimpleScene::from)
+            .collect();
+        serde_json::to_string(&scenes).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Returns the scene playing at `frame` as JSON, or undefined between scenes.
+    pub fn get_scene_at_frame(&self, frame: i32) -> Option<String> {
+        let scene = scenes::scene_at_frame(&self.animation_state.scenes, frame)?;
+        serde_json::to_string(&SimpleScene::from(scene)).ok()
+    }
+
+    /// Returns the scene to skip forward to from `frame` as JSON; playback jumps to its
+    /// `start_frame` and cuts to its `camera`, if any. Undefined after the last scene.
+    pub fn get_next_scene(&self, frame: i32) -> Option<String> {
+        let scene = scenes::next_scene(&self.animation_state.scenes, frame)?;
+        serde_json::to_string(&SimpleScene::from(scene)).ok()
+    }
+
+    /// Returns the scene to skip back to from `frame` as JSON, like `get_next_scene`.
+    /// Undefined from the first scene.
+    pub fn get_previous_scene(&self, frame: i32) -> Option<String> {
+        let scene = scenes::previous_scene(&self.animation_state.scenes, frame)?;
+        serde_json::to_string(&SimpleScene::from(scene)).ok()
+    }
+
+    // --- Undo ---
+    /// Reverts the most recent edit. Returns false when there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop() {
+            Some(previous_state) => {
+                self.animation_state = previous_state;
+                self.bounds_cache.borrow_mut().clear();
+                // Drop selected IDs that no longer exist in the restored document
+                let state = &self.animation_state;
+                self.selection
+                    .retain(|id| state.polygons.iter().any(|p| p.polygon_id == *id));
+                console_log!("Undo applied. {} snapshots left.", self.undo_stack.len());
+                true
+            }
+            None => {
+                console_log!("Warning: Nothing to undo.");
+                false
+            }
+        }
+    }
+
+    // --- Search ---
+    /// Returns the IDs of features matching `query_json`, in draw order, e.g.
+    /// `{"name": "river", "properties": {"layer": "hydro"}}`. `name` is a
+    /// case-insensitive substring of the "name" property or the feature ID;
+    /// `properties` must all match exactly. Omitted filters match everything.
+    pub fn find_features(&self, query_json: &str) -> Result<Vec<String>, JsValue> {
+        let query = search::FeatureQuery::from_json(query_json).map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+        Ok(self
+            .polygons_in_draw_order()
+            .into_iter()
+            .filter(|p| query.matches(p))
+            .map(|p| p.polygon_id.clone())
+            .collect())
+    }
+
+    // --- Selection ---
+    /// Replaces the selection with the given feature IDs. Unknown and locked IDs are ignored.
+    /// Returns the number of features selected.
+    pub fn select_features(&mut self, ids: Vec<String>) -> usize {
+        self.selection.clear();
+        for id in ids {
+            let selectable = self.find_polygon(&id).is_some_and(|p| !p.locked);
+            if selectable && !self.selection.contains(&id) {
+                self.selection.push(id);
+            } else {
+                console_log!(
+                    "Warning: Skipping unknown, locked or duplicate feature ID '{}'",
+                    id
+                );
+            }
+        }
+        console_log!("Selected {} features", self.selection.len());
+        self.selection.len()
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+    }
+
+    pub fn get_selected_feature_ids(&self) -> Vec<String> {
+        self.selection.clone()
+    }
+
+    // --- Batch Operations (applied to the whole selection as one undo step) ---
+    /// Deletes every selected feature. Returns the number of features removed.
+    pub fn delete_selected_features(&mut self) -> usize {
+        if self.selection.is_empty() {
+            return 0;
+        }
+        self.record_undo();
+        let selection = std::mem::take(&mut self.selection);
+        let before = self.animation_state.polygons.len();
+        self.animation_state
+            .polygons
+            .retain(|p| !selection.contains(&p.polygon_id));
+        if self
+            .active_polygon_id
+            .as_ref()
+            .is_some_and(|id| selection.contains(id))
+        {
+            self.active_polygon_id = None;
+        }
+        let removed = before - self.animation_state.polygons.len();
+        console_log!("Deleted {} selected features", removed);
+        removed
+    }
+
+    /// Translates every point of the selected features by (dx, dy, dz).
+    pub fn move_selected_features(&mut self, dx: f32, dy: f32, dz: f32) {
+        let snap_degrees = self.snap_grid_degrees;
+        self.edit_selection(|polygon| {
+            for point in &mut polygon.points {
+                editing::translate_point(point, dx, dy, dz);
+                if let Some(degrees) = snap_degrees {
+                    editing::snap_point(point, degrees);
+                }
+            }
+        });
+    }
+
+    /// Shifts the keyframes of every selected feature by `frame_offset` frames.
+    pub fn shift_selected_keyframes(&mut self, frame_offset: i32) {
+        if frame_offset == 0 {
+            return;
+        }
+        self.edit_selection(|polygon| {
+            for point in &mut polygon.points {
+                editing::shift_point_keyframes(point, frame_offset);
+            }
+        });
+    }
+
+    /// Sets a style property (e.g. "color") on every selected feature.
+    pub fn set_selected_style(&mut self, key: String, value: String) {
+        self.edit_selection(|polygon| {
+            polygon.properties.insert(key.clone(), value.clone());
+        });
+    }
+
+    // --- Draw Order ---
+    /// Moves a feature above every other feature.
+    pub fn bring_to_front(&mut self, feature_id: String) -> Result<(), JsValue> {
+        let order = self.top_draw_order() + 1;
+        self.set_feature_order(feature_id, order)
+    }
+
+    /// Moves a feature below every other feature.
+    pub fn send_to_back(&mut self, feature_id: String) -> Result<(), JsValue> {
+        let order = self
+            .animation_state
+            .polygons
+            .iter()
+            .map(|p| p.draw_order)
+            .min()
+            .unwrap_or(0)
+            - 1;
+        self.set_feature_order(feature_id, order)
+    }
+
+    /// Sets a feature's draw order explicitly. Higher values are drawn on top;
+    /// features sharing a value keep their creation order.
+    pub fn set_feature_order(&mut self, feature_id: String, order: i32) -> Result<(), JsValue> {
+        if self.find_polygon(&feature_id).is_none() {
+            return Err(feature_not_found(&feature_id));
+        }
+        self.record_undo();
+        if let Some(polygon) = self
+            .animation_state
+            .polygons
+            .iter_mut()
+            .find(|p| p.polygon_id == feature_id)
+        {
+            polygon.draw_order = order;
+        }
+        console_log!("Feature '{}' draw order set to {}", feature_id, order);
+        Ok(())
     }
 
     // --- Getter for JS Rendering ---
-    /// Returns the current polygon state as a JSON string.
+    /// Returns the current polygon state as a JSON string, in draw order (back to front).
     #[wasm_bindgen]
     pub fn get_polygons_json(&self) -> String {
         console_log!("Serializing polygon state to JSON...");
         // Convert internal Protobuf structs to simple serializable structs
         let simple_polygons: Vec<SimplePolygon> = self
-            .animation_state
-            .polygons
-            .iter()
+            .polygons_in_draw_order()
+            .into_iter()
             .map(SimplePolygon::from)
             .collect();
 
@@ -198,24 +1502,283 @@ impl Geco {
         })
     }
 
+    /// Returns every point's keyframes (frame, position, easing) for one feature as JSON.
+    pub fn get_keyframes_json(&self, feature_id: String) -> Result<String, JsValue> {
+        let polygon = self
+            .find_polygon(&feature_id)
+            .ok_or_else(|| feature_not_found(&feature_id))?;
+        let point_keyframes: Vec<SimplePointKeyframes> = polygon
+            .points
+            .iter()
+            .map(SimplePointKeyframes::from)
+            .collect();
+        serde_json::to_string(&point_keyframes)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize keyframes: {}", e)))
+    }
+
+    /// Returns one feature's geometry interpolated at `frame` as JSON.
+    pub fn get_renderable_feature_json(
+        &self,
+        feature_id: String,
+        frame: i32,
+    ) -> Result<String, JsValue> {
+        let polygon = self
+            .find_polygon(&feature_id)
+            .ok_or_else(|| feature_not_found(&feature_id))?;
+        let feature = profiling::timed("interpolate_feature", || {
+            RenderableFeature::at_frame(&self.animation_state.polygons, polygon, frame)
+        });
+        serde_json::to_string(&feature)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize feature: {}", e)))
+    }
+
+    /// Returns every feature's geometry interpolated at `frame` as a JSON array, in
+    /// draw order. With the `parallel` feature the features are interpolated on the
+    /// rayon thread pool.
+    pub fn get_renderable_features_json(&self, frame: i32) -> Result<String, JsValue> {
+        let all = &self.animation_state.polygons;
+        let polygons = self.polygons_in_draw_order();
+        let features: Vec<RenderableFeature> = profiling::timed("interpolate_features", || {
+            #[cfg(feature = "parallel")]
+            let polygons = polygons.into_par_iter();
+            #[cfg(not(feature = "parallel"))]
+            let polygons = polygons.into_iter();
+            polygons
+                .map(|polygon| RenderableFeature::at_frame(all, polygon, frame))
+                .collect()
+        });
+        serde_json::to_string(&features)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize features: {}", e)))
+    }
+
+    /// Returns flat render buffers (positions, colors, UVs, fill triangles and
+    /// per-feature vertex counts) at `frame`. With the `parallel` feature the features
+    /// are interpolated on the rayon thread pool.
+    pub fn get_render_buffers(&self, frame: i32) -> RenderBuffers {
+        let polygons = self.polygons_in_draw_order();
+        profiling::timed("build_render_buffers", || {
+            vertex_buffers_at(
+                &self.animation_state.polygons,
+                &polygons,
+                frame,
+                self.color_ramp.as_ref(),
+            )
+        })
+        .into()
+    }
+
+    /// Returns render buffers for frames `start`, `start + step`, ... up to but not
+    /// including `end`, packed into one object. `step` must be positive. With the
+    /// `parallel` feature each frame's features are interpolated on the rayon thread
+    /// pool.
+    pub fn get_render_data_for_frames(
+        &self,
+        start: i32,
+        end: i32,
+        step: u32,
+    ) -> Result<FrameRangeRenderBuffers, JsValue> {
+        if step == 0 {
+            let error_msg = "Frame step must be positive".to_string();
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        }
+        let polygons = self.polygons_in_draw_order();
+        let packed = profiling::timed("build_frame_range_buffers", || {
+            frame_range_buffers(
+                &self.animation_state.polygons,
+                &polygons,
+                (start..end).step_by(step as usize),
+                self.color_ramp.as_ref(),
+            )
+        });
+        Ok(packed.into())
+    }
+
+    /// Scatters `count` instances of a symbol along a feature's path at `frame`,
+    /// evenly spaced by arc length. Polygons are treated as closed loops. Animate
+    /// `phase` from 0 to 1 to move the instances along one spacing; symbols are
+    /// oriented along the path and scaled by `scale`.
+    pub fn get_path_instances(
+        &self,
+        feature_id: String,
+        frame: i32,
+        count: u32,
+        phase: f32,
+        scale: f32,
+    ) -> Result<InstanceBuffers, JsValue> {
+        let polygon = self
+            .find_polygon(&feature_id)
+            .ok_or_else(|| feature_not_found(&feature_id))?;
+        if polygon.feature_type() == FeatureType::Point {
+            let error_msg = format!("Feature '{}' is a point and has no path", feature_id);
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        }
+        let points: Vec<_> =
+            transform::world_points_at_frame(&self.animation_state.polygons, polygon, frame)
+                .into_iter()
+                .map(|(_, xyz)| xyz)
+                .collect();
+        let closed = polygon.feature_type() == FeatureType::Polygon;
+        let instances = instancing::instances_along_path(&points, closed, count as usize, phase);
+        Ok(InstanceBuffers {
+            positions: instances.iter().flat_map(|i| i.position).collect(),
+            directions: instances.iter().flat_map(|i| i.direction).collect(),
+            matrices: instances
+                .iter()
+                .flat_map(|i| instancing::instance_matrix(i, scale))
+                .collect(),
+        })
+    }
+
+    /// Generates `count` points uniformly distributed inside a polygon feature's shape
+    /// at `frame`, as flat x, y, z triples. The same seed always gives the same points.
+    pub fn scatter_points_in_polygon(
+        &self,
+        feature_id: String,
+        count: u32,
+        seed: u32,
+        frame: i32,
+    ) -> Result<Vec<f32>, JsValue> {
+        let polygon = self
+            .find_polygon(&feature_id)
+            .ok_or_else(|| feature_not_found(&feature_id))?;
+        if polygon.feature_type() != FeatureType::Polygon {
+            let error_msg = format!("Feature '{}' is not a polygon", feature_id);
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        }
+        let points: Vec<_> =
+            transform::world_points_at_frame(&self.animation_state.polygons, polygon, frame)
+                .into_iter()
+                .map(|(_, xyz)| xyz)
+                .collect();
+        Ok(scatter::scatter_in_polygon(&points, count as usize, seed)
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// Adds the spherical Delaunay triangulation of `points` (flat x, y, z triples, e.g.
+    /// from `scatter_points_in_polygon`) as one polygon feature per triangle, named
+    /// `<id_prefix>-<n>`. Returns the new feature IDs.
+    pub fn add_delaunay_features(
+        &mut self,
+        points: Vec<f32>,
+        id_prefix: String,
+    ) -> Result<Vec<String>, JsValue> {
+        let points = flat_points(&points)?;
+        let triangles = delaunay::delaunay_triangles(&points);
+        if triangles.is_empty() {
+            return Err(too_few_points_to_triangulate());
+        }
+        let shapes = triangles
+            .iter()
+            .map(|triangle| triangle.iter().map(|&i| points[i]).collect())
+            .collect();
+        Ok(self.add_generated_features(&id_prefix, shapes))
+    }
+
+    /// Adds the spherical Voronoi cells of `points` (flat x, y, z triples) as one polygon
+    /// feature per cell, named `<id_prefix>-<n>`, e.g. for plate boundaries or cell-based
+    /// maps. Points on the rim of a set confined to a hemisphere have unbounded cells and
+    /// get none. Returns the new feature IDs.
+    pub fn add_voronoi_features(
+        &mut self,
+        points: Vec<f32>,
+        id_prefix: String,
+    ) -> Result<Vec<String>, JsValue> {
+        let points = flat_points(&points)?;
+        let triangles = delaunay::delaunay_triangles(&points);
+        if triangles.is_empty() {
+            return Err(too_few_points_to_triangulate());
+        }
+        let shapes = delaunay::voronoi_cells(&points, &triangles)
+            .into_iter()
+            .map(|(_, cell)| cell)
+            .collect();
+        Ok(self.add_generated_features(&id_prefix, shapes))
+    }
+
+    /// Returns the bounding spherical cap of a feature at `frame` as JSON
+    /// (`{"center": {x, y, z}, "angle_degrees": ...}`), or None if none of its
+    /// points exist at that frame. Results are cached until the next edit.
+    pub fn get_feature_bounds(
+        &self,
+        feature_id: String,
+        frame: i32,
+    ) -> Result<Option<String>, JsValue> {
+        let polygon = self
+            .find_polygon(&feature_id)
+            .ok_or_else(|| feature_not_found(&feature_id))?;
+        let cap = *self
+            .bounds_cache
+            .borrow_mut()
+            .entry((feature_id, frame))
+            .or_insert_with(|| {
+                let points: Vec<_> = transform::world_points_at_frame(
+                    &self.animation_state.polygons,
+                    polygon,
+                    frame,
+                )
+                .into_iter()
+                .map(|(_, xyz)| xyz)
+                .collect();
+                bounds::bounding_cap(&points)
+            });
+        cap.map(|cap| serde_json::to_string(&SimpleBounds::from(&cap)))
+            .transpose()
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize bounds: {}", e)))
+    }
+
+    /// Returns document statistics as JSON: feature, point, movement and keyframe
+    /// counts, estimated heap usage (document and undo history) and encoded size.
+    pub fn get_stats_json(&self) -> Result<String, JsValue> {
+        let mut stats = stats::document_stats(&self.animation_state);
+        stats.undo_snapshots = self.undo_stack.len();
+        stats.undo_heap_bytes = self
+            .undo_stack
+            .iter()
+            .map(stats::estimated_heap_bytes)
+            .sum();
+        serde_json::to_string(&stats)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize stats: {}", e)))
+    }
+
+    /// Returns per-frame activity as JSON for the timeline: `keyframes` (keyframes of
+    /// every kind set at each frame), `appearing` and `disappearing` (features entering
+    /// or leaving at each frame), each `frame_count` long up to `MAX_DENSITY_FRAMES`.
+    pub fn get_timeline_density_json(&self) -> Result<String, JsValue> {
+        let density = stats::timeline_density(&self.animation_state);
+        serde_json::to_string(&density)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize timeline density: {}", e)))
+    }
+
     // --- Serialization / Deserialization ---
-    pub fn get_animation_protobuf(&self) -> Vec<u8> {
-        // ... (keep implementation from previous step)
+    /// Encodes the document for saving, after checking it with the same rules the
+    /// backend applies on upload so a document that can't be saved fails here first.
+    pub fn get_animation_protobuf(&self) -> Result<Vec<u8>, JsValue> {
         console_log!("Serializing animation state to Protobuf...");
-        self.animation_state.encode_to_vec()
+        klyja_validate::validate(&self.animation_state).map_err(|errors| {
+            let error_msg = klyja_validate::summarize(&errors);
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+        Ok(profiling::timed("encode_protobuf", || {
+            self.animation_state.encode_to_vec()
+        }))
     }
     pub fn load_animation_protobuf(&mut self, data: &[u8]) -> Result<(), JsValue> {
         // ... (keep implementation from previous step)
         console_log!("Deserializing Protobuf data ({} bytes)...", data.len());
-        match MapAnimation::decode(data) {
-            Ok(decoded_state) => {
-                self.animation_state = decoded_state;
-                // Reset active polygon on load
-                self.active_polygon_id = self
-                    .animation_state
-                    .polygons
-                    .last()
-                    .map(|p| p.polygon_id.clone());
+        match profiling::timed("decode_protobuf", || MapAnimation::decode(data)) {
+            Ok(mut decoded_state) => {
+                // Saves from before a schema change are converted as they open
+                format::upgrade(&mut decoded_state).map_err(|error_msg| {
+                    console_log!("Error: {}", error_msg);
+                    JsValue::from_str(&error_msg)
+                })?;
+                self.replace_document(decoded_state);
                 console_log!(
                     "Protobuf deserialized successfully. Name: {}. Active polygon: {:?}",
                     self.animation_state.name,
@@ -230,6 +1793,549 @@ impl Geco {
             }
         }
     }
+
+    /// Starts decoding a document delivered in chunks (e.g. from a fetch stream),
+    /// discarding any unfinished streamed load. The current document stays in place
+    /// until `finish_load` succeeds.
+    pub fn begin_load(&mut self) {
+        self.pending_load = Some(chunked_load::ChunkedDecoder::default());
+    }
+
+    /// Decodes the next chunk of protobuf data. Only an incomplete trailing field is
+    /// buffered between calls. On error the streamed load is abandoned.
+    pub fn feed_chunk(&mut self, chunk: &[u8]) -> Result<(), JsValue> {
+        let Some(decoder) = self.pending_load.as_mut() else {
+            let error_msg = "feed_chunk called without begin_load".to_string();
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        };
+        decoder.feed(chunk).map_err(|error_msg| {
+            self.pending_load = None;
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })
+    }
+
+    /// Replaces the document with the one streamed in since `begin_load`.
+    pub fn finish_load(&mut self) -> Result<(), JsValue> {
+        let Some(decoder) = self.pending_load.take() else {
+            let error_msg = "finish_load called without begin_load".to_string();
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        };
+        let mut animation = decoder.finish().map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+        format::upgrade(&mut animation).map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+        self.replace_document(animation);
+        console_log!(
+            "Streamed load finished. Name: {}. Active polygon: {:?}",
+            self.animation_state.name,
+            self.active_polygon_id
+        );
+        Ok(())
+    }
+
+    /// Exports the features at `frame` as a GeoJSON FeatureCollection. Shapes crossing
+    /// the antimeridian are split and polar polygons are closed through the pole, so
+    /// the output draws correctly in flat-map tools. Features without enough points
+    /// at that frame are left out.
+    pub fn export_geojson(&self, frame: i32) -> Result<String, JsValue> {
+        let collection = geojson::frame_collection(&self.animation_state.polygons, frame);
+        serde_json::to_string(&collection)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize GeoJSON: {}", e)))
+    }
+
+    /// Returns the whole document as pretty-printed JSON mirroring the protobuf schema.
+    pub fn export_animation_json(&self) -> Result<String, JsValue> {
+        profiling::timed("encode_json", || {
+            document_json::to_json(&self.animation_state)
+        })
+        .map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })
+    }
+
+    /// Returns, as JSON, the patch that turns `base` (the protobuf document last loaded
+    /// or saved) into the current one. Autosaves send it to the backend instead of the
+    /// whole document.
+    pub fn export_patch_json(&self, base: &[u8]) -> Result<String, JsValue> {
+        let base = MapAnimation::decode(base)
+            .map_err(|e| JsValue::from_str(&format!("Failed to decode base document: {}", e)))?;
+        let patch = profiling::timed("make_patch", || {
+            patch::make_patch(&base, &self.animation_state)
+        });
+        serde_json::to_string(&patch)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize patch: {}", e)))
+    }
+
+    /// Replaces the document with one exported by `export_animation_json`
+    /// (or written by hand). Missing fields take their defaults.
+    pub fn import_animation_json(&mut self, json: &str) -> Result<(), JsValue> {
+        let animation = profiling::timed("decode_json", || document_json::from_json(json))
+            .map_err(|error_msg| {
+                console_log!("Error: {}", error_msg);
+                JsValue::from_str(&error_msg)
+            })?;
+        self.replace_document(animation);
+        console_log!(
+            "Animation JSON imported. Name: {}",
+            self.animation_state.name
+        );
+        Ok(())
+    }
+
+    /// Adds a marker feature for each series in a CSV file with id, frame or time, lat
+    /// and lon columns (see geco-core's point_series.rs), at `radius`: rows sharing an id
+    /// become one marker moving through them. Times are spread over the animation's
+    /// frames (240 if it has none); frame numbers are kept, lengthening the animation if
+    /// they run past its end. Returns the new feature IDs.
+    pub fn import_point_series_csv(
+        &mut self,
+        csv: &str,
+        radius: f32,
+    ) -> Result<Vec<String>, JsValue> {
+        let total_frames = match self.animation_state.total_frames {
+            frames if frames > 0 => frames,
+            _ => tracks::DEFAULT_TOTAL_FRAMES,
+        };
+        let (markers, frames) = point_series::import_point_series(csv, total_frames, radius)
+            .map_err(|error_msg| {
+                console_log!("Error: {}", error_msg);
+                JsValue::from_str(&error_msg)
+            })?;
+
+        let ids = self.add_markers(markers, frames);
+        console_log!("Imported {} point series", ids.len());
+        Ok(ids)
+    }
+
+    /// Adds a marker feature for each track of a GPX file, at `radius`, moving over
+    /// the animation's frames (240 if it has none). Every track point needs a time.
+    /// Returns the new feature IDs.
+    pub fn import_gpx(&mut self, gpx: &str, radius: f32) -> Result<Vec<String>, JsValue> {
+        let total_frames = match self.animation_state.total_frames {
+            frames if frames > 0 => frames,
+            _ => tracks::DEFAULT_TOTAL_FRAMES,
+        };
+        let markers = gpx::import_tracks(gpx, total_frames, radius).map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+
+        let ids = self.add_markers(markers, total_frames);
+        console_log!("Imported {} GPX tracks", ids.len());
+        Ok(ids)
+    }
+
+    // --- Profiling (only with the `profiling` feature) ---
+    /// Returns timings recorded so far as JSON: per call name, the number of calls
+    /// and total and maximum duration in milliseconds.
+    #[cfg(feature = "profiling")]
+    pub fn get_profile_json(&self) -> Result<String, JsValue> {
+        profiling::profile_json().map_err(|error_msg| JsValue::from_str(&error_msg))
+    }
+
+    #[cfg(feature = "profiling")]
+    pub fn reset_profile(&self) {
+        profiling::reset_profile();
+    }
+}
+
+impl Default for Geco {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A document opened for browsing: features are decoded without their points, which
+/// are only decoded when a feature is first looked at, so even huge documents open
+/// almost instantly. Call `into_geco` to open it for editing.
+#[wasm_bindgen]
+pub struct LazyAnimation {
+    document: lazy_load::LazyDocument,
+}
+
+#[wasm_bindgen]
+impl LazyAnimation {
+    /// Decodes the output of `Geco::get_animation_protobuf`, except for the points.
+    pub fn from_protobuf(data: &[u8]) -> Result<LazyAnimation, JsValue> {
+        let document = profiling::timed("decode_protobuf_headers", || {
+            lazy_load::LazyDocument::decode(data)
+        })
+        .map_err(|error_msg| {
+            console_log!("Error: {}", error_msg);
+            JsValue::from_str(&error_msg)
+        })?;
+        Ok(LazyAnimation { document })
+    }
+
+    pub fn get_animation_name(&self) -> String {
+        self.document.header().name.clone()
+    }
+
+    pub fn get_total_frames(&self) -> i32 {
+        self.document.header().total_frames
+    }
+
+    /// Returns every feature's metadata as JSON (ID, point count, properties, draw order,
+    /// type and parent), in stored order, without decoding any points.
+    pub fn get_feature_headers_json(&self) -> Result<String, JsValue> {
+        let headers: Vec<SimpleFeatureHeader> = self
+            .document
+            .header()
+            .polygons
+            .iter()
+            .enumerate()
+            .map(|(index, polygon)| SimpleFeatureHeader {
+                polygon_id: polygon.polygon_id.clone(),
+                point_count: self.document.point_count(index),
+                properties: polygon.properties.clone(),
+                draw_order: polygon.draw_order,
+                feature_type: editing::feature_type_name(polygon.feature_type()),
+                parent_id: polygon.parent_id.clone(),
+            })
+            .collect();
+        serde_json::to_string(&headers)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize headers: {}", e)))
+    }
+
+    /// Returns one feature with its points as JSON (the `get_polygons_json` format),
+    /// decoding them on first access.
+    pub fn get_feature_json(&mut self, feature_id: String) -> Result<String, JsValue> {
+        let index = self
+            .document
+            .header()
+            .polygons
+            .iter()
+            .position(|p| p.polygon_id == feature_id)
+            .ok_or_else(|| feature_not_found(&feature_id))?;
+        let polygon = self
+            .document
+            .materialize(index)
+            .map_err(|e| JsValue::from_str(&e))?;
+        serde_json::to_string(&SimplePolygon::from(polygon))
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize feature: {}", e)))
+    }
+
+    /// Decodes the remaining points and opens the document for editing.
+    pub fn into_geco(self) -> Result<Geco, JsValue> {
+        let animation = self
+            .document
+            .into_animation()
+            .map_err(|e| JsValue::from_str(&e))?;
+        let mut geco = Geco::new();
+        geco.replace_document(animation);
+        Ok(geco)
+    }
+}
+
+// --- Internal helpers (not exported to JS) ---
+fn feature_not_found(feature_id: &str) -> JsValue {
+    let error_msg = format!("Feature '{}' not found", feature_id);
+    console_log!("Error: {}", error_msg);
+    JsValue::from_str(&error_msg)
+}
+
+fn point_not_found(feature_id: &str, point_id: &str) -> JsValue {
+    let error_msg = format!("Point '{}' not found in feature '{}'", point_id, feature_id);
+    console_log!("Error: {}", error_msg);
+    JsValue::from_str(&error_msg)
+}
+
+/// Splits flat x, y, z triples into points.
+fn flat_points(values: &[f32]) -> Result<Vec<geometry::Vec3>, JsValue> {
+    if !values.len().is_multiple_of(3) {
+        let error_msg = format!("Expected flat x, y, z triples, got {} values", values.len());
+        console_log!("Error: {}", error_msg);
+        return Err(JsValue::from_str(&error_msg));
+    }
+    Ok(values.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect())
+}
+
+/// `render::build_vertex_buffers`, with the features interpolated on the rayon
+/// thread pool under the `parallel` feature.
+fn vertex_buffers_at(
+    all: &[Polygon],
+    polygons: &[&Polygon],
+    frame: i32,
+    color_ramp: Option<&color_ramp::ColorRamp>,
+) -> render::VertexBuffers {
+    #[cfg(feature = "parallel")]
+    let polygons = polygons.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let polygons = polygons.iter();
+    let features: Vec<render::VertexBuffers> = polygons
+        .map(|polygon| render::build_feature_buffers(all, polygon, frame, color_ramp))
+        .collect();
+    render::concat_feature_buffers(features)
+}
+
+/// `render::build_frame_range_buffers`, each frame built by `vertex_buffers_at`.
+fn frame_range_buffers(
+    all: &[Polygon],
+    polygons: &[&Polygon],
+    frames: impl Iterator<Item = i32>,
+    color_ramp: Option<&color_ramp::ColorRamp>,
+) -> render::FrameRangeBuffers {
+    render::pack_frame_buffers(
+        frames.map(|frame| (frame, vertex_buffers_at(all, polygons, frame, color_ramp))),
+    )
+}
+
+fn too_few_points_to_triangulate() -> JsValue {
+    let error_msg = "Need at least four distinct points not on one great circle".to_string();
+    console_log!("Error: {}", error_msg);
+    JsValue::from_str(&error_msg)
+}
+
+/// Unit normal of a mirror plane, rejecting the zero vector.
+fn mirror_normal(x: f32, y: f32, z: f32) -> Result<geometry::Vec3, JsValue> {
+    geometry::normalize([x, y, z]).ok_or_else(|| {
+        let error_msg = "Mirror plane normal must be non-zero".to_string();
+        console_log!("Error: {}", error_msg);
+        JsValue::from_str(&error_msg)
+    })
+}
+
+fn grid_layer_not_found(grid_id: &str) -> JsValue {
+    let error_msg = format!("Grid layer '{}' not found", grid_id);
+    console_log!("Error: {}", error_msg);
+    JsValue::from_str(&error_msg)
+}
+
+fn scene_not_found(scene_id: &str) -> JsValue {
+    let error_msg = format!("Scene '{}' not found", scene_id);
+    console_log!("Error: {}", error_msg);
+    JsValue::from_str(&error_msg)
+}
+
+fn data_series_not_found(series_id: &str) -> JsValue {
+    let error_msg = format!("Data series '{}' not found", series_id);
+    console_log!("Error: {}", error_msg);
+    JsValue::from_str(&error_msg)
+}
+
+impl Geco {
+    fn find_polygon(&self, polygon_id: &str) -> Option<&Polygon> {
+        self.animation_state
+            .polygons
+            .iter()
+            .find(|p| p.polygon_id == polygon_id)
+    }
+
+    /// Like `find_polygon`, but fails for missing features and for locked ones,
+    /// which must not be edited.
+    fn find_editable_polygon(&self, polygon_id: &str) -> Result<&Polygon, JsValue> {
+        let polygon = self
+            .find_polygon(polygon_id)
+            .ok_or_else(|| feature_not_found(polygon_id))?;
+        if polygon.locked {
+            let error_msg = format!("Feature '{}' is locked", polygon_id);
+            console_log!("Error: {}", error_msg);
+            return Err(JsValue::from_str(&error_msg));
+        }
+        Ok(polygon)
+    }
+
+    fn find_grid_layer(&self, grid_id: &str) -> Option<&GridLayer> {
+        self.animation_state
+            .grid_layers
+            .iter()
+            .find(|g| g.grid_id == grid_id)
+    }
+
+    fn find_scene(&self, scene_id: &str) -> Option<&Scene> {
+        self.animation_state
+            .scenes
+            .iter()
+            .find(|s| s.scene_id == scene_id)
+    }
+
+    fn find_scene_mut(&mut self, scene_id: &str) -> Option<&mut Scene> {
+        self.animation_state
+            .scenes
+            .iter_mut()
+            .find(|s| s.scene_id == scene_id)
+    }
+
+    fn find_data_series(&self, series_id: &str) -> Option<&DataSeries> {
+        self.animation_state
+            .data_series
+            .iter()
+            .find(|s| s.series_id == series_id)
+    }
+
+    fn find_data_series_mut(&mut self, series_id: &str) -> Option<&mut DataSeries> {
+        self.animation_state
+            .data_series
+            .iter_mut()
+            .find(|s| s.series_id == series_id)
+    }
+
+    fn find_polygon_mut(&mut self, polygon_id: &str) -> Option<&mut Polygon> {
+        self.animation_state
+            .polygons
+            .iter_mut()
+            .find(|p| p.polygon_id == polygon_id)
+    }
+
+    /// Adds imported markers above every feature as one undo step, renaming any whose
+    /// ID is taken, and lengthens the animation to `frames` if it is shorter.
+    fn add_markers(&mut self, markers: Vec<Polygon>, frames: i32) -> Vec<String> {
+        self.record_undo();
+        let draw_order = self.top_draw_order() + 1;
+        let mut ids = Vec::with_capacity(markers.len());
+        for mut marker in markers {
+            // Markers are named after their series or tracks, which may already be taken here
+            let polygon_id =
+                editing::unique_feature_id(&self.animation_state.polygons, &marker.polygon_id);
+            for point in &mut marker.points {
+                point.point_id = point.point_id.replacen(&marker.polygon_id, &polygon_id, 1);
+            }
+            marker.polygon_id = polygon_id.clone();
+            marker.draw_order += draw_order;
+            self.animation_state.polygons.push(marker);
+            ids.push(polygon_id);
+        }
+        self.animation_state.total_frames = self.animation_state.total_frames.max(frames);
+        ids
+    }
+
+    /// Highest draw order in use, or 0 for an empty document.
+    fn top_draw_order(&self) -> i32 {
+        self.animation_state
+            .polygons
+            .iter()
+            .map(|p| p.draw_order)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Polygons sorted back to front. The sort is stable so ties keep creation order.
+    fn polygons_in_draw_order(&self) -> Vec<&Polygon> {
+        let mut polygons: Vec<&Polygon> = self.animation_state.polygons.iter().collect();
+        polygons.sort_by_key(|p| p.draw_order);
+        polygons
+    }
+
+    /// Swaps in a freshly loaded document. It starts with no selection and no undo
+    /// history, and the last feature becomes the active one.
+    fn replace_document(&mut self, animation: MapAnimation) {
+        self.animation_state = animation;
+        self.selection.clear();
+        self.undo_stack.clear();
+        self.bounds_cache.borrow_mut().clear();
+        self.active_polygon_id = self
+            .animation_state
+            .polygons
+            .last()
+            .map(|p| p.polygon_id.clone());
+        self.symmetry_partner = None;
+    }
+
+    /// Snapshots the document so the next edit can be reverted with `undo`.
+    fn record_undo(&mut self) {
+        // Every edit passes through here, so derived caches are dropped here too
+        self.bounds_cache.borrow_mut().clear();
+        if self.undo_stack.len() >= MAX_UNDO_DEPTH {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(self.animation_state.clone());
+    }
+
+    /// Applies `edit` to every selected polygon, recording a single undo entry.
+    /// A point drawn into the active polygon: smoothed against the previous one, then snapped.
+    fn drawn_point(&mut self, x: f32, y: f32, z: f32) -> Point {
+        let [x, y, z] = match self.input_smoother.as_mut() {
+            Some(smoother) => smoother.filter([x, y, z]),
+            None => [x, y, z],
+        };
+        self.snapped_point(x, y, z)
+    }
+
+    /// A new point at the given position, snapped to the grid if snapping is on.
+    fn snapped_point(&self, x: f32, y: f32, z: f32) -> Point {
+        let [x, y, z] = match self.snap_grid_degrees {
+            Some(degrees) => geometry::snap_to_grid([x, y, z], degrees),
+            None => [x, y, z],
+        };
+        Point { x, y, z: Some(z) }
+    }
+
+    /// Adds static polygon features on top of everything, one per shape, named
+    /// `<id_prefix>-<n>` (skipping taken IDs). Records a single undo step.
+    fn add_generated_features(
+        &mut self,
+        id_prefix: &str,
+        shapes: Vec<Vec<geometry::Vec3>>,
+    ) -> Vec<String> {
+        self.record_undo();
+        let draw_order = self.top_draw_order() + 1;
+        let mut ids = Vec::with_capacity(shapes.len());
+        for (n, shape) in shapes.into_iter().enumerate() {
+            let polygon_id = editing::unique_feature_id(
+                &self.animation_state.polygons,
+                &format!("{}-{}", id_prefix, n),
+            );
+            let points = shape
+                .into_iter()
+                .enumerate()
+                .map(|(i, [x, y, z])| AnimatedPoint {
+                    point_id: format!("{}-pt{}", polygon_id, i),
+                    initial_position: Some(Point { x, y, z: Some(z) }),
+                    ..Default::default()
+                })
+                .collect();
+            self.animation_state.polygons.push(Polygon {
+                polygon_id: polygon_id.clone(),
+                points,
+                draw_order,
+                feature_type: FeatureType::Polygon as i32,
+                ..Default::default()
+            });
+            ids.push(polygon_id);
+        }
+        console_log!("Added {} generated features '{}-*'", ids.len(), id_prefix);
+        ids
+    }
+
+    /// Appends the mirror image of a point just drawn to the live-symmetry partner.
+    fn mirror_into_partner(&mut self, mut point: Point) {
+        let (Some(normal), Some(partner_id)) = (self.symmetry_normal, &self.symmetry_partner)
+        else {
+            return;
+        };
+        let partner_id = partner_id.clone();
+        let Some(partner) = self.find_polygon_mut(&partner_id) else {
+            return;
+        };
+        mirror::reflect_point(&mut point, normal);
+        partner.points.push(AnimatedPoint {
+            point_id: editing::next_point_id(partner),
+            initial_position: Some(point),
+            ..Default::default()
+        });
+    }
+
+    fn edit_selection<F: FnMut(&mut Polygon)>(&mut self, mut edit: F) {
+        if self.selection.is_empty() {
+            console_log!("Warning: No features selected.");
+            return;
+        }
+        self.record_undo();
+        let selection = &self.selection;
+        self.animation_state
+            .polygons
+            .iter_mut()
+            .filter(|p| selection.contains(&p.polygon_id))
+            .for_each(&mut edit);
+    }
 }
 
 // --- Add Dependencies ---
@@ -243,4 +2349,5 @@ impl Geco {
 // Include the test module
 #[cfg(test)]
 #[path = "lib_test.rs"]
+#[allow(clippy::module_inception)] // lib_test.rs wraps its tests in its own `mod tests`
 mod tests;
@@ -1,8 +1,7 @@
 #[cfg(test)]
 mod tests {
-    use crate::protobuf_gen::{AnimatedPoint, MapAnimation, Point, Polygon};
+    use crate::protobuf_gen::{AnimatedPoint, FeatureType, Point, Polygon};
     use crate::{SimpleAnimatedPoint, SimplePoint, SimplePolygon};
-    use prost::Message;
 
     #[test]
     fn test_simple_point_from() {
@@ -18,7 +17,7 @@ mod tests {
         assert_eq!(simple_point.y, 2.0);
         assert_eq!(simple_point.z, Some(3.0));
     }
-    
+
     #[test]
     fn test_simple_animated_point_from() {
         let point = Point {
@@ -31,6 +30,10 @@ mod tests {
             point_id: "test-point".to_string(),
             initial_position: Some(point),
             movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
         };
         
         let simple_animated_point = SimpleAnimatedPoint::from(&animated_point);
@@ -43,7 +46,7 @@ mod tests {
         assert_eq!(simple_pos.y, 2.0);
         assert_eq!(simple_pos.z, Some(3.0));
     }
-    
+
     #[test]
     fn test_simple_polygon_from() {
         let point = Point {
@@ -56,6 +59,10 @@ mod tests {
             point_id: "test-point".to_string(),
             initial_position: Some(point),
             movements: vec![],
+            end_frame: None,
+            start_frame: None,
+            scalar_keyframes: vec![],
+            pinned_feature_id: None,
         };
         
         let mut properties = std::collections::HashMap::new();
@@ -65,6 +72,11 @@ mod tests {
             polygon_id: "test-polygon".to_string(),
             points: vec![animated_point],
             properties,
+            draw_order: 0,
+            feature_type: FeatureType::Polygon as i32,
+            locked: false,
+            transform_keyframes: vec![],
+            parent_id: None,
         };
         
         let simple_polygon = SimplePolygon::from(&polygon);
@@ -74,51 +86,84 @@ mod tests {
         assert_eq!(simple_polygon.points[0].point_id, "test-point");
         assert_eq!(simple_polygon.properties.get("color").unwrap(), "red");
     }
-    
+
     #[test]
-    fn test_map_animation_serialization() {
-        let point = Point {
-            x: 1.0,
-            y: 2.0,
-            z: Some(3.0),
-        };
-        
-        let animated_point = AnimatedPoint {
-            point_id: "test-point".to_string(),
-            initial_position: Some(point),
-            movements: vec![],
-        };
-        
-        let polygon = Polygon {
-            polygon_id: "test-polygon".to_string(),
-            points: vec![animated_point],
-            properties: Default::default(),
-        };
-        
-        let animation = MapAnimation {
-            animation_id: "test-animation".to_string(),
-            name: "Test Animation".to_string(),
-            total_frames: 10,
-            polygons: vec![polygon],
-        };
-        
-        // Serialize to protobuf
-        let bytes = animation.encode_to_vec();
-        
-        // Deserialize
-        let decoded = MapAnimation::decode(&bytes[..]).unwrap();
-        
-        // Verify the data
-        assert_eq!(decoded.animation_id, "test-animation");
-        assert_eq!(decoded.name, "Test Animation");
-        assert_eq!(decoded.total_frames, 10);
-        assert_eq!(decoded.polygons.len(), 1);
-        assert_eq!(decoded.polygons[0].polygon_id, "test-polygon");
-        assert_eq!(decoded.polygons[0].points.len(), 1);
-        assert_eq!(decoded.polygons[0].points[0].point_id, "test-point");
-        let pos = decoded.polygons[0].points[0].initial_position.as_ref().unwrap();
-        assert_eq!(pos.x, 1.0);
-        assert_eq!(pos.y, 2.0);
-        assert_eq!(pos.z, Some(3.0));
+    fn test_render_buffers_match_serial_build() {
+        use crate::{frame_range_buffers, vertex_buffers_at};
+        use geco_core::color_ramp::ColorRamp;
+        use geco_core::editing::upsert_scalar_keyframe;
+        use geco_core::render::{build_frame_range_buffers, build_vertex_buffers};
+
+        // Enough features of each kind that a thread pool splits them up
+        let polygons: Vec<Polygon> = (0..24)
+            .map(|i| {
+                let (feature_type, point_count) = match i % 3 {
+                    0 => (FeatureType::Polygon, 5),
+                    1 => (FeatureType::Polyline, 4),
+                    _ => (FeatureType::Point, 1),
+                };
+                let points = (0..point_count)
+                    .map(|j| {
+                        let mut point = AnimatedPoint {
+                            point_id: format!("p{}-{}", i, j),
+                            initial_position: Some(Point {
+                                x: (i + j) as f32,
+                                y: j as f32 - 2.0,
+                                z: Some(1.0),
+                            }),
+                            movements: vec![],
+                            end_frame: None,
+                            // Points join their shapes at different frames
+                            start_frame: Some(j),
+                            scalar_keyframes: vec![],
+                            pinned_feature_id: None,
+                        };
+                        upsert_scalar_keyframe(&mut point, 0, i as f32);
+                        point
+                    })
+                    .collect();
+                Polygon {
+                    polygon_id: format!("feature-{}", i),
+                    points,
+                    properties: Default::default(),
+                    draw_order: i,
+                    feature_type: feature_type as i32,
+                    locked: false,
+                    transform_keyframes: vec![],
+                    parent_id: None,
+                }
+            })
+            .collect();
+        let in_order: Vec<&Polygon> = polygons.iter().collect();
+        let ramp = ColorRamp::from_json(
+            r#"[{"value": 0, "color": [0, 0, 1]}, {"value": 24, "color": [1, 0, 0]}]"#,
+        )
+        .unwrap();
+
+        // With the `parallel` feature these are built on the thread pool
+        for frame in [0, 2, 6] {
+            assert_eq!(
+                vertex_buffers_at(&polygons, &in_order, frame, Some(&ramp)),
+                build_vertex_buffers(&polygons, &in_order, frame, Some(&ramp))
+            );
+        }
+        assert_eq!(
+            frame_range_buffers(&polygons, &in_order, 0..6, Some(&ramp)),
+            build_frame_range_buffers(&polygons, &in_order, 0..6, Some(&ramp))
+        );
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn test_profile_accumulates_calls() {
+        use crate::profiling::Profile;
+
+        let mut profile = Profile::default();
+        profile.record("encode_protobuf", 2.0);
+        profile.record("encode_protobuf", 4.0);
+        let stats = &profile.calls["encode_protobuf"];
+        assert_eq!(stats.calls, 2);
+        assert_eq!(stats.total_ms, 6.0);
+        assert_eq!(stats.max_ms, 4.0);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,74 @@
+// klyja/geco/src/profiling.rs
+// Optional per-call timings for the hot paths (interpolation, serialization).
+// Compiled in only with the `profiling` feature; otherwise `timed` just runs the closure.
+
+/// Runs `f`, recording its duration under `name` when profiling is enabled.
+#[cfg(not(feature = "profiling"))]
+#[inline(always)]
+pub(crate) fn timed<T>(_name: &'static str, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+#[cfg(feature = "profiling")]
+pub(crate) use enabled::*;
+
+#[cfg(feature = "profiling")]
+mod enabled {
+    use serde::Serialize;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        // Available in windows and workers alike, unlike std::time on wasm32
+        #[wasm_bindgen(js_namespace = performance, js_name = now)]
+        fn performance_now() -> f64;
+    }
+
+    #[derive(Serialize, Debug, Default, Clone, PartialEq)]
+    pub(crate) struct CallStats {
+        pub calls: u64,
+        pub total_ms: f64,
+        pub max_ms: f64,
+    }
+
+    /// Timings keyed by call name, sorted so the JSON output is stable.
+    #[derive(Debug, Default)]
+    pub(crate) struct Profile {
+        pub calls: BTreeMap<&'static str, CallStats>,
+    }
+
+    impl Profile {
+        pub fn record(&mut self, name: &'static str, duration_ms: f64) {
+            let stats = self.calls.entry(name).or_default();
+            stats.calls += 1;
+            stats.total_ms += duration_ms;
+            stats.max_ms = stats.max_ms.max(duration_ms);
+        }
+    }
+
+    thread_local! {
+        static PROFILE: RefCell<Profile> = RefCell::new(Profile::default());
+    }
+
+    pub(crate) fn timed<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = performance_now();
+        let result = f();
+        let duration_ms = performance_now() - start;
+        PROFILE.with(|profile| profile.borrow_mut().record(name, duration_ms));
+        result
+    }
+
+    /// `{"<name>": {"calls", "total_ms", "max_ms"}, ...}` for everything timed so far.
+    pub(crate) fn profile_json() -> Result<String, String> {
+        PROFILE.with(|profile| {
+            serde_json::to_string(&profile.borrow().calls)
+                .map_err(|e| format!("Failed to serialize profile: {}", e))
+        })
+    }
+
+    pub(crate) fn reset_profile() {
+        PROFILE.with(|profile| profile.borrow_mut().calls.clear());
+    }
+}
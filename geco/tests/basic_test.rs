@@ -1,6 +1,6 @@
 //! Basic tests for geco crate that don't require wasm
 
-use geco::protobuf_gen::{AnimatedPoint, MapAnimation, Point, Polygon};
+use geco::protobuf_gen::{AnimatedPoint, FeatureType, MapAnimation, Point, Polygon};
 use prost::Message;
 
 #[test]
@@ -11,6 +11,11 @@ fn test_map_animation_serialization() {
         name: "Test Animation".to_string(),
         total_frames: 30,
         polygons: vec![],
+        grid_layers: vec![],
+        camera_keyframes: vec![],
+        data_series: vec![],
+        scenes: vec![],
+        format_version: 0,
     };
 
     // Serialize to bytes
@@ -68,12 +73,20 @@ fn test_polygon_with_points() {
         point_id: "point-1".to_string(),
         initial_position: Some(point1),
         movements: vec![],
+        end_frame: None,
+        start_frame: None,
+        scalar_keyframes: vec![],
+        pinned_feature_id: None,
     };
 
     let animated_point2 = AnimatedPoint {
         point_id: "point-2".to_string(),
         initial_position: Some(point2),
         movements: vec![],
+        end_frame: None,
+        start_frame: None,
+        scalar_keyframes: vec![],
+        pinned_feature_id: None,
     };
 
     // Create a polygon
@@ -84,6 +97,11 @@ fn test_polygon_with_points() {
         polygon_id: "polygon-1".to_string(),
         points: vec![animated_point1, animated_point2],
         properties,
+        draw_order: 0,
+        feature_type: FeatureType::Polygon as i32,
+        locked: false,
+        transform_keyframes: vec![],
+        parent_id: None,
     };
 
     // Serialize
@@ -71,7 +71,7 @@ mod wasm_tests {
         geco.add_point_to_active_polygon(7.0, 8.0, 0.0);
 
         // Test serialization
-        let bytes = geco.get_animation_protobuf();
+        let bytes = geco.get_animation_protobuf().unwrap();
         assert!(!bytes.is_empty());
 
         // Create a new instance
@@ -114,5 +114,225 @@ mod wasm_tests {
         let polygons_json = geco.get_polygons_json();
         assert_eq!(polygons_json, "[]");
     }
-}
 
+    #[wasm_bindgen_test]
+    fn test_batch_delete_selected_is_single_undo_step() {
+        let mut geco = Geco::new();
+        geco.add_static_polygon("a".to_string(), 1.0, 1.0);
+        geco.add_static_polygon("b".to_string(), 2.0, 2.0);
+        geco.add_static_polygon("c".to_string(), 3.0, 3.0);
+
+        let selected = geco.select_features(vec![
+            "a".to_string(),
+            "c".to_string(),
+            "missing".to_string(),
+        ]);
+        assert_eq!(selected, 2);
+
+        assert_eq!(geco.delete_selected_features(), 2);
+        let polygons_json = geco.get_polygons_json();
+        assert!(!polygons_json.contains("\"a\""));
+        assert!(polygons_json.contains("\"b\""));
+        assert!(geco.get_selected_feature_ids().is_empty());
+
+        // One undo restores both deleted features
+        assert!(geco.undo());
+        let polygons_json = geco.get_polygons_json();
+        assert!(polygons_json.contains("\"a\""));
+        assert!(polygons_json.contains("\"c\""));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_set_selected_style() {
+        let mut geco = Geco::new();
+        geco.add_static_polygon("styled".to_string(), 1.0, 1.0);
+        geco.select_features(vec!["styled".to_string()]);
+        geco.set_selected_style("color".to_string(), "#ff0000".to_string());
+
+        assert!(geco.get_polygons_json().contains("#ff0000"));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_draw_order_controls_render_output() {
+        let mut geco = Geco::new();
+        geco.add_static_polygon("bottom".to_string(), 1.0, 1.0);
+        geco.add_static_polygon("top".to_string(), 2.0, 2.0);
+
+        let json = geco.get_polygons_json();
+        assert!(json.find("bottom").unwrap() < json.find("top").unwrap());
+
+        geco.bring_to_front("bottom".to_string()).unwrap();
+        let json = geco.get_polygons_json();
+        assert!(json.find("top").unwrap() < json.find("bottom").unwrap());
+
+        geco.send_to_back("bottom".to_string()).unwrap();
+        let json = geco.get_polygons_json();
+        assert!(json.find("bottom").unwrap() < json.find("top").unwrap());
+
+        assert!(geco.set_feature_order("missing".to_string(), 3).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_get_renderable_feature_json() {
+        let mut geco = Geco::new();
+        geco.add_static_polygon("solo".to_string(), 1.0, 2.0);
+        geco.add_point_to_active_polygon(3.0, 4.0, 0.0);
+
+        let json = geco
+            .get_renderable_feature_json("solo".to_string(), 0)
+            .unwrap();
+        assert!(json.contains("\"feature_id\":\"solo\""));
+        assert!(json.contains("solo-pt1"));
+
+        assert!(geco
+            .get_renderable_feature_json("missing".to_string(), 0)
+            .is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_remove_point_at_frame_keeps_earlier_frames() {
+        let mut geco = Geco::new();
+        geco.add_static_polygon("shape".to_string(), 1.0, 1.0);
+        geco.add_point_to_active_polygon(2.0, 2.0, 0.0);
+
+        geco.remove_point_at_frame("shape".to_string(), "shape-pt1".to_string(), 5)
+            .unwrap();
+
+        let early = geco
+            .get_renderable_feature_json("shape".to_string(), 4)
+            .unwrap();
+        assert!(early.contains("shape-pt1"));
+        let late = geco
+            .get_renderable_feature_json("shape".to_string(), 5)
+            .unwrap();
+        assert!(!late.contains("shape-pt1"));
+
+        assert!(geco
+            .remove_point_at_frame("shape".to_string(), "missing".to_string(), 1)
+            .is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_insert_point_at_index() {
+        let mut geco = Geco::new();
+        geco.add_static_polygon("edge".to_string(), 0.0, 0.0);
+        geco.add_point_to_active_polygon(2.0, 0.0, 0.0);
+
+        let point_id = geco
+            .insert_point_at_index("edge".to_string(), 3, 1, 1.0, 0.0, 0.0)
+            .unwrap();
+
+        let before = geco
+            .get_renderable_feature_json("edge".to_string(), 2)
+            .unwrap();
+        assert!(!before.contains(&point_id));
+        let after = geco
+            .get_renderable_feature_json("edge".to_string(), 3)
+            .unwrap();
+        let first = after.find("edge-pt0").unwrap();
+        let inserted = after.find(&point_id).unwrap();
+        let last = after.find("edge-pt1").unwrap();
+        assert!(first < inserted && inserted < last);
+
+        assert!(geco
+            .insert_point_at_index("edge".to_string(), 3, 9, 1.0, 0.0, 0.0)
+            .is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_grid_layer_keyframes() {
+        let mut geco = Geco::new();
+        geco.add_grid_layer("sea-level".to_string(), 2, 2).unwrap();
+        assert!(geco.add_grid_layer("sea-level".to_string(), 2, 2).is_err());
+
+        geco.set_grid_keyframe("sea-level".to_string(), 0, vec![0.0; 4])
+            .unwrap();
+        geco.set_grid_keyframe("sea-level".to_string(), 4, vec![4.0; 4])
+            .unwrap();
+        assert!(geco
+            .set_grid_keyframe("sea-level".to_string(), 2, vec![1.0; 3])
+            .is_err());
+
+        let values = geco.get_grid_values("sea-level".to_string(), 2).unwrap();
+        assert_eq!(values, vec![2.0; 4]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_from_shared_state_renders_the_same() {
+        let mut geco = Geco::new();
+        geco.add_static_polygon("shared".to_string(), 1.0, 2.0);
+        geco.add_point_to_active_polygon(2.0, 3.0, 0.0);
+        let ramp = r#"[{"value": 0, "color": [0, 0, 1]}]"#.to_string();
+        geco.set_color_ramp(&ramp).unwrap();
+
+        let worker =
+            Geco::from_shared_state(&geco.get_animation_protobuf().unwrap(), Some(ramp)).unwrap();
+        assert_eq!(worker.get_polygons_json(), geco.get_polygons_json());
+        assert_eq!(
+            worker.get_render_buffers(0).positions(),
+            geco.get_render_buffers(0).positions()
+        );
+
+        assert!(Geco::from_shared_state(&[0xFF], None).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_renderable_features_in_draw_order() {
+        let mut geco = Geco::new();
+        geco.add_static_polygon("lower".to_string(), 1.0, 2.0);
+        geco.add_static_polygon("upper".to_string(), 3.0, 4.0);
+        geco.send_to_back("upper".to_string()).unwrap();
+
+        let json = geco.get_renderable_features_json(0).unwrap();
+        let upper = json.find("\"upper\"").unwrap();
+        let lower = json.find("\"lower\"").unwrap();
+        assert!(upper < lower);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_import_gpx_adds_moving_markers() {
+        let mut geco = Geco::new();
+        geco.add_static_polygon("voyage".to_string(), 1.0, 2.0);
+        let gpx = r#"<gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+            <trk><name>voyage</name><trkseg>
+                <trkpt lat="0" lon="0"><time>2024-05-01T10:00:00Z</time></trkpt>
+                <trkpt lat="0" lon="10"><time>2024-05-01T11:00:00Z</time></trkpt>
+            </trkseg></trk>
+        </gpx>"#;
+
+        let ids = geco.import_gpx(gpx, 5.0).unwrap();
+        assert_eq!(ids, vec!["voyage-2".to_string()]);
+        // The track runs over the whole timeline
+        let keyframes = geco.get_keyframes_json("voyage-2".to_string()).unwrap();
+        assert!(keyframes.contains("\"frame\":0,"));
+        assert!(keyframes.contains("\"frame\":239,"));
+
+        geco.undo();
+        assert!(!geco.get_polygons_json().contains("\"voyage-2\""));
+        assert!(geco.import_gpx("<gpx></gpx>", 5.0).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_live_symmetry_draws_mirror_image() {
+        let mut geco = Geco::new();
+        assert!(geco.set_live_symmetry(0.0, 0.0, 0.0).is_err());
+        geco.set_live_symmetry(1.0, 0.0, 0.0).unwrap();
+        geco.add_static_polygon("wing".to_string(), 1.0, 2.0);
+        geco.add_point_to_active_polygon(2.0, 3.0, 1.0);
+        geco.clear_live_symmetry();
+        geco.add_point_to_active_polygon(3.0, 3.0, 1.0);
+
+        let json = geco.get_polygons_json();
+        assert!(json.contains("\"wing-mirror\""));
+        assert!(json.contains("\"x\":-2.0,\"y\":3.0,\"z\":1.0"));
+        assert!(!json.contains("\"x\":-3.0"));
+
+        let copy = geco
+            .mirror_feature("wing".to_string(), 1.0, 0.0, 0.0)
+            .unwrap();
+        assert_eq!(copy, "wing-mirror-2");
+        assert!(geco
+            .mirror_feature("missing".to_string(), 1.0, 0.0, 0.0)
+            .is_err());
+    }
+}
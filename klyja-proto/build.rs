@@ -0,0 +1,31 @@
+// klyja/klyja-proto/build.rs
+use std::env; // Needed for OUT_DIR
+use std::io::Result;
+use std::path::PathBuf; // Needed for path joining
+
+fn main() -> Result<()> {
+    println!("cargo:rerun-if-changed=../protobuf/AnimationData.proto");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // Get the Cargo OUT_DIR environment variable
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR not set"));
+
+    prost_build::Config::new()
+        // Specify the output directory for generated code
+        .out_dir(&out_dir) // Use OUT_DIR
+        // Serde support for the JSON document format; missing fields take their defaults
+        .message_attribute(
+            ".",
+            "#[derive(serde::Serialize, serde::Deserialize)] #[serde(default)]",
+        )
+        // Enums are plain i32 fields in prost; write them as readable names instead
+        .field_attribute(
+            ".klyja.map_animation.v1.Polygon.feature_type",
+            "#[serde(with = \"crate::serde_feature_type\")]",
+        )
+        // Compile the .proto file
+        .compile_protos(&["../protobuf/AnimationData.proto"], // Path relative to build.rs
+                        &["../protobuf/"]) // Include path
+        ?;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+// klyja/klyja-proto/src/lib.rs
+// Generated protobuf types for the map animation document (package
+// `klyja.map_animation.v1`), with serde support for its JSON form.
+include!(concat!(env!("OUT_DIR"), "/klyja.map_animation.v1.rs"));
+
+/// Name used for a feature type in the JS API and JSON output.
+pub fn feature_type_name(feature_type: FeatureType) -> &'static str {
+    match feature_type {
+        FeatureType::Polygon => "polygon",
+        FeatureType::Polyline => "polyline",
+        FeatureType::Point => "point",
+    }
+}
+
+pub fn parse_feature_type(name: &str) -> Option<FeatureType> {
+    match name {
+        "polygon" => Some(FeatureType::Polygon),
+        "polyline" => Some(FeatureType::Polyline),
+        "point" => Some(FeatureType::Point),
+        _ => None,
+    }
+}
+
+/// Serde adapter writing `Polygon.feature_type` as "polygon" / "polyline" / "point".
+pub mod serde_feature_type {
+    use super::{feature_type_name, parse_feature_type, FeatureType};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+        // Unknown values decode as the default type, same as `Polygon::feature_type()`
+        let feature_type = FeatureType::try_from(*value).unwrap_or_default();
+        serializer.serialize_str(feature_type_name(feature_type))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        parse_feature_type(&name)
+            .map(|feature_type| feature_type as i32)
+            .ok_or_else(|| D::Error::custom(format!("unknown feature type '{}'", name)))
+    }
+}
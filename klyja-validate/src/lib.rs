@@ -0,0 +1,355 @@
+// klyja/klyja-validate/src/lib.rs
+// Semantic validation of map animation documents. Protobuf decoding only checks the
+// wire format; these checks catch documents that decode fine but that the editor
+// can't work with: frames out of range, clashing IDs and dangling or inconsistent
+// references between features.
+use klyja_proto::{
+    AnimatedPoint, DataSeries, FeatureType, GridLayer, MapAnimation, Point, Polygon, Scene,
+};
+use std::collections::HashSet;
+use std::fmt;
+
+/// One problem found in a document, with where it was found
+/// (e.g. `polygons[2].points[0]`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Checks a document, returning every problem found rather than stopping at the first.
+pub fn validate(animation: &MapAnimation) -> Result<(), Vec<ValidationError>> {
+    let mut checker = Checker {
+        animation,
+        errors: vec![],
+    };
+    checker.check_document();
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.errors)
+    }
+}
+
+/// Joins errors into a single message, for APIs that report one string.
+pub fn summarize(errors: &[ValidationError]) -> String {
+    let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    format!("Invalid animation: {}", messages.join("; "))
+}
+
+struct Checker<'a> {
+    animation: &'a MapAnimation,
+    errors: Vec<ValidationError>,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, path: String, message: impl Into<String>) {
+        self.errors.push(ValidationError {
+            path,
+            message: message.into(),
+        });
+    }
+
+    fn check_document(&mut self) {
+        if self.animation.total_frames < 0 {
+            self.error("total_frames".to_string(), "must not be negative");
+        }
+        let mut feature_ids = HashSet::new();
+        for (index, polygon) in self.animation.polygons.iter().enumerate() {
+            let path = format!("polygons[{}]", index);
+            if polygon.polygon_id.is_empty() {
+                self.error(path.clone(), "feature ID is empty");
+            } else if !feature_ids.insert(polygon.polygon_id.as_str()) {
+                self.error(
+                    path.clone(),
+                    format!("duplicate feature ID '{}'", polygon.polygon_id),
+                );
+            }
+            self.check_polygon(&path, polygon);
+        }
+        let mut grid_ids = HashSet::new();
+        for (index, layer) in self.animation.grid_layers.iter().enumerate() {
+            let path = format!("grid_layers[{}]", index);
+            if !grid_ids.insert(layer.grid_id.as_str()) {
+                self.error(
+                    path.clone(),
+                    format!("duplicate grid layer ID '{}'", layer.grid_id),
+                );
+            }
+            self.check_grid_layer(&path, layer);
+        }
+        let camera_frames: Vec<i32> = self
+            .animation
+            .camera_keyframes
+            .iter()
+            .map(|k| k.frame)
+            .collect();
+        self.check_keyframe_frames("camera_keyframes", &camera_frames);
+        let mut series_ids = HashSet::new();
+        for (index, series) in self.animation.data_series.iter().enumerate() {
+            let path = format!("data_series[{}]", index);
+            if !series_ids.insert(series.series_id.as_str()) {
+                self.error(
+                    path.clone(),
+                    format!("duplicate data series ID '{}'", series.series_id),
+                );
+            }
+            self.check_data_series(&path, series);
+        }
+        let mut scene_ids = HashSet::new();
+        for (index, scene) in self.animation.scenes.iter().enumerate() {
+            let path = format!("scenes[{}]", index);
+            if scene.scene_id.is_empty() {
+                self.error(path.clone(), "scene ID is empty");
+            } else if !scene_ids.insert(scene.scene_id.as_str()) {
+                self.error(
+                    path.clone(),
+                    format!("duplicate scene ID '{}'", scene.scene_id),
+                );
+            }
+            self.check_scene(&path, scene);
+        }
+        // Playback finds scenes by binary search, so they must be sorted and apart
+        if let Some(index) = self
+            .animation
+            .scenes
+            .windows(2)
+            .position(|pair| pair[1].start_frame < pair[0].end_frame)
+        {
+            self.error(
+                format!("scenes[{}]", index + 1),
+                "scenes overlap or are not in start frame order",
+            );
+        }
+    }
+
+    fn check_polygon(&mut self, path: &str, polygon: &Polygon) {
+        if FeatureType::try_from(polygon.feature_type).is_err() {
+            self.error(
+                format!("{}.feature_type", path),
+                format!("unknown feature type {}", polygon.feature_type),
+            );
+        }
+        let mut point_ids = HashSet::new();
+        for (index, point) in polygon.points.iter().enumerate() {
+            let point_path = format!("{}.points[{}]", path, index);
+            if !point_ids.insert(point.point_id.as_str()) {
+                self.error(
+                    point_path.clone(),
+                    format!("duplicate point ID '{}'", point.point_id),
+                );
+            }
+            self.check_point(&point_path, polygon, point);
+        }
+
+        let frames: Vec<i32> = polygon
+            .transform_keyframes
+            .iter()
+            .map(|k| k.frame)
+            .collect();
+        self.check_keyframe_frames(&format!("{}.transform_keyframes", path), &frames);
+        for (index, keyframe) in polygon.transform_keyframes.iter().enumerate() {
+            let is_zero = keyframe
+                .axis
+                .as_ref()
+                .is_none_or(|a| a.x == 0.0 && a.y == 0.0 && a.z.unwrap_or(0.0) == 0.0);
+            if is_zero || !keyframe.angle_degrees.is_finite() {
+                self.error(
+                    format!("{}.transform_keyframes[{}]", path, index),
+                    "rotation needs a non-zero axis and a finite angle",
+                );
+            }
+        }
+
+        if let Some(parent_id) = polygon.parent_id.as_deref() {
+            if !self.has_feature(parent_id) {
+                self.error(
+                    format!("{}.parent_id", path),
+                    format!("parent feature '{}' doesn't exist", parent_id),
+                );
+            } else if self.parent_chain_loops(polygon) {
+                self.error(format!("{}.parent_id", path), "feature is its own ancestor");
+            }
+        }
+    }
+
+    fn check_point(&mut self, path: &str, polygon: &Polygon, point: &AnimatedPoint) {
+        match &point.initial_position {
+            None => self.error(path.to_string(), "point has no initial position"),
+            Some(position) if !is_finite(position) => {
+                self.error(path.to_string(), "initial position is not finite")
+            }
+            Some(_) => {}
+        }
+        if point
+            .movements
+            .iter()
+            .any(|m| !(m.dx.is_finite() && m.dy.is_finite() && m.dz.unwrap_or(0.0).is_finite()))
+        {
+            self.error(path.to_string(), "movement is not finite");
+        }
+
+        let start = point.start_frame.unwrap_or(0);
+        self.check_frame(&format!("{}.start_frame", path), start);
+        if let Some(end) = point.end_frame {
+            if end <= start {
+                self.error(
+                    format!("{}.end_frame", path),
+                    format!("ends at frame {} before it starts at frame {}", end, start),
+                );
+            }
+            // The end frame is exclusive, so it may equal the frame count
+            if self.animation.total_frames > 0 && end > self.animation.total_frames {
+                self.error(
+                    format!("{}.end_frame", path),
+                    format!("frame {} is past the last frame", end),
+                );
+            }
+        }
+        let last_moving_frame = start.saturating_add(point.movements.len() as i32);
+        if !point.movements.is_empty()
+            && self.animation.total_frames > 0
+            && last_moving_frame > self.animation.total_frames
+        {
+            self.error(
+                format!("{}.movements", path),
+                format!(
+                    "movements run to frame {}, past the last frame",
+                    last_moving_frame
+                ),
+            );
+        }
+
+        let frames: Vec<i32> = point.scalar_keyframes.iter().map(|k| k.frame).collect();
+        self.check_keyframe_frames(&format!("{}.scalar_keyframes", path), &frames);
+
+        if let Some(target_id) = point.pinned_feature_id.as_deref() {
+            if target_id == polygon.polygon_id {
+                self.error(
+                    format!("{}.pinned_feature_id", path),
+                    "point is pinned to its own feature",
+                );
+            } else if !self.has_feature(target_id) {
+                self.error(
+                    format!("{}.pinned_feature_id", path),
+                    format!("pinned to missing feature '{}'", target_id),
+                );
+            }
+        }
+    }
+
+    fn check_grid_layer(&mut self, path: &str, layer: &GridLayer) {
+        if layer.lat_count <= 0 || layer.lon_count <= 0 {
+            self.error(path.to_string(), "grid needs at least one row and column");
+        }
+        let cell_count = layer.lat_count.max(0) as usize * layer.lon_count.max(0) as usize;
+        for (index, keyframe) in layer.keyframes.iter().enumerate() {
+            if keyframe.values.len() != cell_count {
+                self.error(
+                    format!("{}.keyframes[{}]", path, index),
+                    format!(
+                        "has {} values, the grid has {} cells",
+                        keyframe.values.len(),
+                        cell_count
+                    ),
+                );
+            }
+        }
+        let frames: Vec<i32> = layer.keyframes.iter().map(|k| k.frame).collect();
+        self.check_keyframe_frames(&format!("{}.keyframes", path), &frames);
+    }
+
+    fn check_data_series(&mut self, path: &str, series: &DataSeries) {
+        for (index, keyframe) in series.keyframes.iter().enumerate() {
+            if !keyframe.value.is_finite() {
+                self.error(
+                    format!("{}.keyframes[{}]", path, index),
+                    "value is not a finite number",
+                );
+            }
+        }
+        let frames: Vec<i32> = series.keyframes.iter().map(|k| k.frame).collect();
+        self.check_keyframe_frames(&format!("{}.keyframes", path), &frames);
+    }
+
+    fn check_scene(&mut self, path: &str, scene: &Scene) {
+        self.check_frame(&format!("{}.start_frame", path), scene.start_frame);
+        if scene.end_frame <= scene.start_frame {
+            self.error(path.to_string(), "scene must end after it starts");
+        } else {
+            // The end is exclusive, so it may be the frame just past the last one
+            self.check_frame(&format!("{}.end_frame", path), scene.end_frame - 1);
+        }
+    }
+
+    /// Keyframes are looked up by binary search, so they must be in increasing
+    /// frame order without repeats, and each frame in range.
+    fn check_keyframe_frames(&mut self, path: &str, frames: &[i32]) {
+        for (index, &frame) in frames.iter().enumerate() {
+            self.check_frame(&format!("{}[{}]", path, index), frame);
+        }
+        if let Some(index) = frames.windows(2).position(|pair| pair[0] >= pair[1]) {
+            self.error(
+                format!("{}[{}]", path, index + 1),
+                "keyframes are not in increasing frame order",
+            );
+        }
+    }
+
+    fn check_frame(&mut self, path: &str, frame: i32) {
+        if frame < 0 {
+            self.error(path.to_string(), format!("frame {} is negative", frame));
+        } else if self.animation.total_frames > 0 && frame >= self.animation.total_frames {
+            self.error(
+                path.to_string(),
+                format!(
+                    "frame {} is past the last frame ({} frames)",
+                    frame, self.animation.total_frames
+                ),
+            );
+        }
+    }
+
+    fn has_feature(&self, feature_id: &str) -> bool {
+        self.animation
+            .polygons
+            .iter()
+            .any(|p| p.polygon_id == feature_id)
+    }
+
+    fn parent_chain_loops(&self, polygon: &Polygon) -> bool {
+        let mut current = polygon.parent_id.as_deref();
+        for _ in 0..self.animation.polygons.len() {
+            let Some(id) = current else {
+                return false;
+            };
+            if id == polygon.polygon_id {
+                return true;
+            }
+            current = self
+                .animation
+                .polygons
+                .iter()
+                .find(|p| p.polygon_id == id)
+                .and_then(|p| p.parent_id.as_deref());
+        }
+        // Longer than the feature list: the chain loops above this feature
+        current.is_some()
+    }
+}
+
+fn is_finite(point: &Point) -> bool {
+    point.x.is_finite() && point.y.is_finite() && point.z.unwrap_or(0.0).is_finite()
+}
+
+// Include the test module
+#[cfg(test)]
+#[path = "lib_test.rs"]
+#[allow(clippy::module_inception)] // lib_test.rs wraps its tests in its own `mod tests`
+mod tests;
@@ -0,0 +1,152 @@
+#[cfg(test)]
+mod tests {
+    use crate::validate;
+    use klyja_proto::{
+        AnimatedPoint, FeatureType, GridKeyframe, GridLayer, MapAnimation, Point, Polygon,
+        ScalarKeyframe,
+    };
+
+    fn point(id: &str) -> AnimatedPoint {
+        AnimatedPoint {
+            point_id: id.to_string(),
+            initial_position: Some(Point {
+                x: 0.0,
+                y: 0.0,
+                z: Some(5.0),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn polygon(id: &str, point_ids: &[&str]) -> Polygon {
+        Polygon {
+            polygon_id: id.to_string(),
+            points: point_ids.iter().map(|p| point(p)).collect(),
+            feature_type: FeatureType::Polygon as i32,
+            ..Default::default()
+        }
+    }
+
+    fn animation(polygons: Vec<Polygon>) -> MapAnimation {
+        MapAnimation {
+            animation_id: "anim".to_string(),
+            name: "Test".to_string(),
+            total_frames: 10,
+            polygons,
+            ..Default::default()
+        }
+    }
+
+    fn error_paths(animation: &MapAnimation) -> Vec<String> {
+        validate(animation)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.path)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_document_passes() {
+        let mut child = polygon("b", &["p1", "p2"]);
+        child.parent_id = Some("a".to_string());
+        child.points[0].pinned_feature_id = Some("a".to_string());
+        child.points[1].start_frame = Some(2);
+        child.points[1].end_frame = Some(10);
+        let doc = animation(vec![polygon("a", &["p1", "p2", "p3"]), child]);
+        assert_eq!(validate(&doc), Ok(()));
+
+        // With no frame count set, frames are unbounded
+        let mut unbounded = doc.clone();
+        unbounded.total_frames = 0;
+        unbounded.polygons[0].points[0].start_frame = Some(500);
+        assert_eq!(validate(&unbounded), Ok(()));
+    }
+
+    #[test]
+    fn test_duplicate_ids_are_rejected() {
+        let doc = animation(vec![polygon("a", &["p1", "p1"]), polygon("a", &["p1"])]);
+        assert_eq!(
+            error_paths(&doc),
+            vec![
+                "polygons[0].points[1]".to_string(),
+                "polygons[1]".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frames_out_of_range_are_rejected() {
+        let mut doc = animation(vec![polygon("a", &["p1"])]);
+        let p = &mut doc.polygons[0].points[0];
+        p.start_frame = Some(12);
+        p.end_frame = Some(4);
+        p.scalar_keyframes = vec![
+            ScalarKeyframe {
+                frame: 3,
+                ..Default::default()
+            },
+            ScalarKeyframe {
+                frame: 3,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            error_paths(&doc),
+            vec![
+                "polygons[0].points[0].start_frame".to_string(),
+                "polygons[0].points[0].end_frame".to_string(),
+                "polygons[0].points[0].scalar_keyframes[1]".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dangling_references_and_cycles_are_rejected() {
+        let mut a = polygon("a", &["p1"]);
+        a.parent_id = Some("b".to_string());
+        let mut b = polygon("b", &["p1"]);
+        b.parent_id = Some("a".to_string());
+        b.points[0].pinned_feature_id = Some("b".to_string());
+        let mut c = polygon("c", &["p1"]);
+        c.parent_id = Some("missing".to_string());
+        c.points[0].initial_position = None;
+
+        let errors = validate(&animation(vec![a, b, c])).unwrap_err();
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "polygons[0].parent_id: feature is its own ancestor",
+                "polygons[1].points[0].pinned_feature_id: point is pinned to its own feature",
+                "polygons[1].parent_id: feature is its own ancestor",
+                "polygons[2].points[0]: point has no initial position",
+                "polygons[2].parent_id: parent feature 'missing' doesn't exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grid_snapshot_size_must_match_grid() {
+        let mut doc = animation(vec![]);
+        doc.grid_layers = vec![GridLayer {
+            grid_id: "g".to_string(),
+            lat_count: 2,
+            lon_count: 3,
+            keyframes: vec![
+                GridKeyframe {
+                    frame: 0,
+                    values: vec![0.0; 6],
+                },
+                GridKeyframe {
+                    frame: 4,
+                    values: vec![0.0; 5],
+                },
+            ],
+            ..Default::default()
+        }];
+        assert_eq!(
+            error_paths(&doc),
+            vec!["grid_layers[0].keyframes[1]".to_string()]
+        );
+    }
+}
@@ -0,0 +1,5 @@
+-- klyja/migrations/2026-10-16-090000_create_users_and_collaborators/down.sql
+DROP TABLE animation_collaborators;
+ALTER TABLE animations DROP COLUMN owner_id;
+DROP TABLE sessions;
+DROP TABLE users;
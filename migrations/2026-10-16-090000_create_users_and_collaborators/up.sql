@@ -0,0 +1,28 @@
+--- klyja/migrations/2026-10-16-090000_create_users_and_collaborators/up.sql
+CREATE TABLE users (
+    id SERIAL PRIMARY KEY,
+    username VARCHAR(255) NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Browser sessions, identified by the token stored in the `klyja_session` cookie
+CREATE TABLE sessions (
+    token VARCHAR(64) PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL
+);
+
+-- Animations saved without a session stay unowned and readable by anyone, as before
+ALTER TABLE animations ADD COLUMN owner_id INTEGER REFERENCES users(id) ON DELETE SET NULL;
+CREATE INDEX animations_owner_id_idx ON animations (owner_id);
+
+-- Users other than the owner who may view or edit an animation
+CREATE TABLE animation_collaborators (
+    animation_id INTEGER NOT NULL REFERENCES animations(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role VARCHAR(16) NOT NULL CHECK (role IN ('viewer', 'editor')),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (animation_id, user_id)
+);
+CREATE INDEX animation_collaborators_user_id_idx ON animation_collaborators (user_id);
@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-16-100000_create_animation_thumbnails/down.sql
+DROP TABLE animation_thumbnails;
@@ -0,0 +1,7 @@
+--- klyja/migrations/2026-10-16-100000_create_animation_thumbnails/up.sql
+-- Kept out of `animations` so listings don't read preview images they don't return
+CREATE TABLE animation_thumbnails (
+    animation_id INTEGER PRIMARY KEY REFERENCES animations(id) ON DELETE CASCADE,
+    png_data BYTEA NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
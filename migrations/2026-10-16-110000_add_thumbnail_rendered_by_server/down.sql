@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-16-110000_add_thumbnail_rendered_by_server/down.sql
+ALTER TABLE animation_thumbnails DROP COLUMN rendered_by_server;
@@ -0,0 +1,3 @@
+--- klyja/migrations/2026-10-16-110000_add_thumbnail_rendered_by_server/up.sql
+-- Server-rendered thumbnails are replaced on every save; uploaded ones are kept
+ALTER TABLE animation_thumbnails ADD COLUMN rendered_by_server BOOLEAN NOT NULL DEFAULT FALSE;
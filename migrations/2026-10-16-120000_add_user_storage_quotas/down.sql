@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-16-120000_add_user_storage_quotas/down.sql
+ALTER TABLE users DROP COLUMN storage_quota_bytes;
@@ -0,0 +1,3 @@
+--- klyja/migrations/2026-10-16-120000_add_user_storage_quotas/up.sql
+-- Total bytes of documents a user may own; usage is summed from their animations
+ALTER TABLE users ADD COLUMN storage_quota_bytes BIGINT NOT NULL DEFAULT 104857600;
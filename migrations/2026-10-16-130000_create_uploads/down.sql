@@ -0,0 +1,3 @@
+-- klyja/migrations/2026-10-16-130000_create_uploads/down.sql
+DROP TABLE upload_parts;
+DROP TABLE uploads;
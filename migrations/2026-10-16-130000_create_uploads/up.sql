@@ -0,0 +1,17 @@
+--- klyja/migrations/2026-10-16-130000_create_uploads/up.sql
+-- Resumable uploads: a document sent in numbered parts, saved once all have arrived
+CREATE TABLE uploads (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- The animation the finished document replaces; NULL creates a new one
+    animation_id INTEGER REFERENCES animations(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX uploads_user_id_idx ON uploads (user_id);
+
+CREATE TABLE upload_parts (
+    upload_id INTEGER NOT NULL REFERENCES uploads(id) ON DELETE CASCADE,
+    part_number INTEGER NOT NULL CHECK (part_number >= 0),
+    data BYTEA NOT NULL,
+    PRIMARY KEY (upload_id, part_number)
+);
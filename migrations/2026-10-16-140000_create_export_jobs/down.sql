@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-16-140000_create_export_jobs/down.sql
+DROP TABLE export_jobs;
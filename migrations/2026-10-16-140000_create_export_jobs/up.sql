@@ -0,0 +1,16 @@
+--- klyja/migrations/2026-10-16-140000_create_export_jobs/up.sql
+-- Video exports rendered in the background; the finished file is kept until downloaded or purged
+CREATE TABLE export_jobs (
+    id SERIAL PRIMARY KEY,
+    animation_id INTEGER NOT NULL REFERENCES animations(id) ON DELETE CASCADE,
+    format VARCHAR(8) NOT NULL CHECK (format IN ('gif', 'mp4')),
+    size INTEGER NOT NULL,
+    fps INTEGER NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'done', 'failed')),
+    error TEXT,
+    output BYTEA,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP
+);
+CREATE INDEX export_jobs_animation_id_idx ON export_jobs (animation_id);
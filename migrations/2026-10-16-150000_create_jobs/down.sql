@@ -0,0 +1,3 @@
+-- klyja/migrations/2026-10-16-150000_create_jobs/down.sql
+ALTER TABLE export_jobs DROP COLUMN job_id;
+DROP TABLE jobs;
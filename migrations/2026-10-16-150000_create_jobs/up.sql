@@ -0,0 +1,20 @@
+--- klyja/migrations/2026-10-16-150000_create_jobs/up.sql
+-- Persistent background job queue shared by all server workers
+CREATE TABLE jobs (
+    id SERIAL PRIMARY KEY,
+    kind VARCHAR(32) NOT NULL,
+    payload JSONB NOT NULL,
+    animation_id INTEGER REFERENCES animations(id) ON DELETE CASCADE,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'done', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 3,
+    run_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMP,
+    finished_at TIMESTAMP,
+    last_error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX jobs_status_run_at_idx ON jobs (status, run_at);
+
+ALTER TABLE export_jobs ADD COLUMN job_id INTEGER REFERENCES jobs(id) ON DELETE SET NULL;
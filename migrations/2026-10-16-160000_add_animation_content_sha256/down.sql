@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-16-160000_add_animation_content_sha256/down.sql
+ALTER TABLE animations DROP COLUMN content_sha256;
@@ -0,0 +1,9 @@
+--- klyja/migrations/2026-10-16-160000_add_animation_content_sha256/up.sql
+-- Hex SHA-256 of the (uncompressed) protobuf document, checked whenever it is loaded
+ALTER TABLE animations ADD COLUMN content_sha256 VARCHAR(64);
+
+-- Documents still stored uncompressed can be hashed here; compressed ones get their
+-- hash the next time they are saved, and aren't checked until then
+UPDATE animations
+SET content_sha256 = encode(sha256(protobuf_data), 'hex')
+WHERE substring(protobuf_data FROM 1 FOR 1) <> '\x00'::bytea;
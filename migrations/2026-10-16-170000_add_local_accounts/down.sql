@@ -0,0 +1,4 @@
+-- klyja/migrations/2026-10-16-170000_add_local_accounts/down.sql
+DROP TABLE password_resets;
+ALTER TABLE users DROP COLUMN password_hash;
+ALTER TABLE users DROP COLUMN email;
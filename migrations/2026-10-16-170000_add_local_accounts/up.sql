@@ -0,0 +1,13 @@
+--- klyja/migrations/2026-10-16-170000_add_local_accounts/up.sql
+-- Local accounts: users who sign in with an email and password instead of an OAuth provider
+ALTER TABLE users ADD COLUMN email VARCHAR(255) UNIQUE;
+ALTER TABLE users ADD COLUMN password_hash VARCHAR(255); -- Argon2 PHC string
+
+-- One-time password reset tokens; only their SHA-256 is stored
+CREATE TABLE password_resets (
+    token_sha256 VARCHAR(64) PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL
+);
+CREATE INDEX password_resets_user_id_idx ON password_resets (user_id);
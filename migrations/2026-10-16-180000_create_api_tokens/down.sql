@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-16-180000_create_api_tokens/down.sql
+DROP TABLE api_tokens;
@@ -0,0 +1,13 @@
+--- klyja/migrations/2026-10-16-180000_create_api_tokens/up.sql
+-- Personal access tokens, sent as `Authorization: Bearer` by scripts and CI; only their SHA-256 is stored
+CREATE TABLE api_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    token_sha256 VARCHAR(64) NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL, -- 'read' for GET and HEAD requests, 'write' for the rest
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMP,
+    expires_at TIMESTAMP
+);
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-16-190000_add_animation_lock_version/down.sql
+ALTER TABLE animations DROP COLUMN lock_version;
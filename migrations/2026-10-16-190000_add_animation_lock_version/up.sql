@@ -0,0 +1,3 @@
+--- klyja/migrations/2026-10-16-190000_add_animation_lock_version/up.sql
+-- Bumped on every document update; clients send the version they edited in If-Match
+ALTER TABLE animations ADD COLUMN lock_version INTEGER NOT NULL DEFAULT 1;
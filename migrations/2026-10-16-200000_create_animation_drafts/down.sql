@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-16-200000_create_animation_drafts/down.sql
+DROP TABLE animation_drafts;
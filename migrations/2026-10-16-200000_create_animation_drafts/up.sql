@@ -0,0 +1,10 @@
+--- klyja/migrations/2026-10-16-200000_create_animation_drafts/up.sql
+-- Autosaves, one per user and animation, kept apart from the saved document and its version
+CREATE TABLE animation_drafts (
+    animation_id INTEGER NOT NULL REFERENCES animations(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    protobuf_data BYTEA NOT NULL,
+    based_on_version INTEGER NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (animation_id, user_id)
+);
@@ -0,0 +1,5 @@
+-- klyja/migrations/2026-10-16-210000_create_animation_versions/down.sql
+DROP TRIGGER record_version_on_update ON animations;
+DROP TRIGGER record_version_on_insert ON animations;
+DROP FUNCTION record_animation_version();
+DROP TABLE animation_versions;
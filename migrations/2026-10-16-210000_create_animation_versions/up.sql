@@ -0,0 +1,35 @@
+--- klyja/migrations/2026-10-16-210000_create_animation_versions/up.sql
+-- Every saved version of each document, so versions can be compared. Filled by a
+-- trigger whenever an animation is created or its lock_version goes up.
+CREATE TABLE animation_versions (
+    animation_id INTEGER NOT NULL REFERENCES animations(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    protobuf_data BYTEA NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (animation_id, version)
+);
+
+CREATE OR REPLACE FUNCTION record_animation_version()
+RETURNS TRIGGER AS $$
+BEGIN
+  INSERT INTO animation_versions (animation_id, version, protobuf_data)
+  VALUES (NEW.id, NEW.lock_version, NEW.protobuf_data)
+  ON CONFLICT (animation_id, version) DO UPDATE SET protobuf_data = EXCLUDED.protobuf_data;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER record_version_on_insert
+AFTER INSERT ON animations
+FOR EACH ROW
+EXECUTE PROCEDURE record_animation_version();
+
+CREATE TRIGGER record_version_on_update
+AFTER UPDATE ON animations
+FOR EACH ROW
+WHEN (NEW.lock_version IS DISTINCT FROM OLD.lock_version)
+EXECUTE PROCEDURE record_animation_version();
+
+-- Earlier versions are gone; start each history from the current document
+INSERT INTO animation_versions (animation_id, version, protobuf_data, created_at)
+SELECT id, lock_version, protobuf_data, updated_at FROM animations;
@@ -0,0 +1,3 @@
+-- klyja/migrations/2026-10-16-220000_add_animation_uuid/down.sql
+DROP INDEX animations_owner_uuid_idx;
+ALTER TABLE animations DROP COLUMN animation_uuid;
@@ -0,0 +1,8 @@
+-- klyja/migrations/2026-10-16-220000_add_animation_uuid/up.sql
+-- The animation_id inside the document, so a user's repeated saves of the same
+-- document update one row instead of adding another each time
+ALTER TABLE animations ADD COLUMN animation_uuid VARCHAR(255);
+
+-- NULLs never collide, so anonymous saves and rows not yet resaved are unaffected.
+-- Existing documents are compressed, so they get theirs the next time they are saved.
+CREATE UNIQUE INDEX animations_owner_uuid_idx ON animations (owner_id, animation_uuid);
@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-16-230000_create_stars/down.sql
+DROP TABLE stars;
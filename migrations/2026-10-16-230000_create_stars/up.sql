@@ -0,0 +1,10 @@
+-- klyja/migrations/2026-10-16-230000_create_stars/up.sql
+-- Users' stars on animations they can load, counted in listings
+CREATE TABLE stars (
+    animation_id INTEGER NOT NULL REFERENCES animations(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (animation_id, user_id)
+);
+
+CREATE INDEX stars_user_id_idx ON stars (user_id);
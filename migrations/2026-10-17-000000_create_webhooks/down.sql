@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-17-000000_create_webhooks/down.sql
+DROP TABLE webhooks;
@@ -0,0 +1,12 @@
+-- klyja/migrations/2026-10-17-000000_create_webhooks/up.sql
+-- URLs users want called when their animations change. The secret signs each delivery,
+-- so it is kept as given rather than hashed.
+CREATE TABLE webhooks (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    url VARCHAR(2048) NOT NULL,
+    secret VARCHAR(255) NOT NULL,
+    events TEXT[] NOT NULL, -- Which events to deliver, e.g. 'saved'
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX webhooks_user_id_idx ON webhooks (user_id);
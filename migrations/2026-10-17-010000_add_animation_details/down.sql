@@ -0,0 +1,5 @@
+-- klyja/migrations/2026-10-17-010000_add_animation_details/down.sql
+ALTER TABLE animations
+    DROP COLUMN source_attribution,
+    DROP COLUMN license,
+    DROP COLUMN description;
@@ -0,0 +1,6 @@
+-- klyja/migrations/2026-10-17-010000_add_animation_details/up.sql
+-- Context shown alongside an animation; none of it is part of the document
+ALTER TABLE animations
+    ADD COLUMN description TEXT,
+    ADD COLUMN license VARCHAR(100),
+    ADD COLUMN source_attribution VARCHAR(1000);
@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-17-020000_create_animation_daily_views/down.sql
+DROP TABLE animation_daily_views;
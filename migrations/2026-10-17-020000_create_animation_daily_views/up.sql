@@ -0,0 +1,9 @@
+-- klyja/migrations/2026-10-17-020000_create_animation_daily_views/up.sql
+-- How often each animation was loaded per day, by anyone but its owner. One counter
+-- per day rather than a row per view, so popular animations don't grow the table.
+CREATE TABLE animation_daily_views (
+    animation_id INTEGER NOT NULL REFERENCES animations(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    views BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (animation_id, day)
+);
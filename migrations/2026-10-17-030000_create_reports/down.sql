@@ -0,0 +1,4 @@
+-- klyja/migrations/2026-10-17-030000_create_reports/down.sql
+DROP TABLE reports;
+ALTER TABLE animations DROP COLUMN hidden_at;
+ALTER TABLE users DROP COLUMN is_admin;
@@ -0,0 +1,16 @@
+-- klyja/migrations/2026-10-17-030000_create_reports/up.sql
+-- Moderation: users report animations, and admins hide them from everyone but their owner
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE animations ADD COLUMN hidden_at TIMESTAMP;
+
+-- One report per user and animation; reporting again replaces the reason and reopens it
+CREATE TABLE reports (
+    id SERIAL PRIMARY KEY,
+    animation_id INTEGER NOT NULL REFERENCES animations(id) ON DELETE CASCADE,
+    reporter_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason VARCHAR(1000) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMP, -- Set when an admin hides or unhides the animation
+    UNIQUE (animation_id, reporter_id)
+);
+CREATE INDEX reports_open_idx ON reports (created_at) WHERE resolved_at IS NULL;
@@ -0,0 +1,3 @@
+-- klyja/migrations/2026-10-17-040000_add_user_count_limits/down.sql
+ALTER TABLE users DROP COLUMN max_versions_per_animation;
+ALTER TABLE users DROP COLUMN max_animations;
@@ -0,0 +1,4 @@
+-- klyja/migrations/2026-10-17-040000_add_user_count_limits/up.sql
+-- How many animations a user may own, and how many saved versions each may have
+ALTER TABLE users ADD COLUMN max_animations INTEGER NOT NULL DEFAULT 1000;
+ALTER TABLE users ADD COLUMN max_versions_per_animation INTEGER NOT NULL DEFAULT 1000;
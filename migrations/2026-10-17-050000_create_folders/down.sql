@@ -0,0 +1,3 @@
+-- klyja/migrations/2026-10-17-050000_create_folders/down.sql
+ALTER TABLE animations DROP COLUMN folder_id;
+DROP TABLE folders;
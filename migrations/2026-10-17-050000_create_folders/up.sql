@@ -0,0 +1,14 @@
+-- klyja/migrations/2026-10-17-050000_create_folders/up.sql
+-- Folders let users sort their animations into projects; they nest, and deleting
+-- one deletes its subfolders and moves the animations in them back to the top level
+CREATE TABLE folders (
+    id SERIAL PRIMARY KEY,
+    owner_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    parent_id INTEGER REFERENCES folders(id) ON DELETE CASCADE, -- NULL at the top level
+    name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX folders_owner_id_idx ON folders (owner_id);
+
+ALTER TABLE animations ADD COLUMN folder_id INTEGER REFERENCES folders(id) ON DELETE SET NULL;
+CREATE INDEX animations_folder_id_idx ON animations (folder_id);
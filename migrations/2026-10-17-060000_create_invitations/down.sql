@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-17-060000_create_invitations/down.sql
+DROP TABLE invitations;
@@ -0,0 +1,14 @@
+-- klyja/migrations/2026-10-17-060000_create_invitations/up.sql
+-- Invitations to collaborate on an animation, sent to an email or username that may
+-- not have an account yet; only the SHA-256 of each token is stored
+CREATE TABLE invitations (
+    id SERIAL PRIMARY KEY,
+    token_sha256 VARCHAR(64) NOT NULL UNIQUE,
+    animation_id INTEGER NOT NULL REFERENCES animations(id) ON DELETE CASCADE,
+    invited_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    invitee VARCHAR(255) NOT NULL, -- Lowercased email, or a username
+    role VARCHAR(16) NOT NULL CHECK (role IN ('viewer', 'editor')),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL
+);
+CREATE INDEX invitations_animation_id_idx ON invitations (animation_id);
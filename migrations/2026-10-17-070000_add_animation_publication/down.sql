@@ -0,0 +1,3 @@
+-- klyja/migrations/2026-10-17-070000_add_animation_publication/down.sql
+ALTER TABLE animations DROP COLUMN publish_at;
+ALTER TABLE animations DROP COLUMN published_at;
@@ -0,0 +1,5 @@
+-- klyja/migrations/2026-10-17-070000_add_animation_publication/up.sql
+-- Owned animations can be published, making them readable by anyone, straight away
+-- or at a scheduled time (picked up by a job queued for that time)
+ALTER TABLE animations ADD COLUMN published_at TIMESTAMP; -- NULL while private
+ALTER TABLE animations ADD COLUMN publish_at TIMESTAMP; -- NULL unless a publication is scheduled
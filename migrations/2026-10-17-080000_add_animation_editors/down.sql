@@ -0,0 +1,3 @@
+-- klyja/migrations/2026-10-17-080000_add_animation_editors/down.sql
+ALTER TABLE animations DROP COLUMN last_edited_by;
+ALTER TABLE animations DROP COLUMN created_by;
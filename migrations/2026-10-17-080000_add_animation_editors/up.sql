@@ -0,0 +1,8 @@
+-- klyja/migrations/2026-10-17-080000_add_animation_editors/up.sql
+-- Who created each animation and who last changed it, so shared animations can show
+-- which collaborator touched them last. NULL for anonymous saves and deleted users.
+ALTER TABLE animations ADD COLUMN created_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE animations ADD COLUMN last_edited_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+-- Until now only owners' saves were attributed to anyone
+UPDATE animations SET created_by = owner_id, last_edited_by = owner_id;
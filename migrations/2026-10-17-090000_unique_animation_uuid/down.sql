@@ -0,0 +1,4 @@
+-- klyja/migrations/2026-10-17-090000_unique_animation_uuid/down.sql
+-- Document IDs given up when the index was added are not restored
+DROP INDEX animations_uuid_idx;
+CREATE UNIQUE INDEX animations_owner_uuid_idx ON animations (owner_id, animation_uuid);
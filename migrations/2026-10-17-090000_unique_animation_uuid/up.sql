@@ -0,0 +1,16 @@
+-- klyja/migrations/2026-10-17-090000_unique_animation_uuid/up.sql
+-- Each document ID is held by at most one animation, so clients can look animations
+-- up by it. Where different owners saved the same document, the earliest save keeps
+-- the ID and the others give it up; they are still reachable by their database IDs.
+UPDATE animations SET animation_uuid = NULL
+WHERE id IN (
+    SELECT id FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY animation_uuid ORDER BY id) AS n
+        FROM animations
+        WHERE animation_uuid IS NOT NULL
+    ) AS saves
+    WHERE n > 1
+);
+
+DROP INDEX animations_owner_uuid_idx;
+CREATE UNIQUE INDEX animations_uuid_idx ON animations (animation_uuid);
@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-17-100000_create_backups/down.sql
+DROP TABLE backups;
@@ -0,0 +1,17 @@
+-- klyja/migrations/2026-10-17-100000_create_backups/up.sql
+-- Admin backups of every animation to a zip in BACKUP_DIR, and restores from one,
+-- each run in the job queue
+CREATE TABLE backups (
+    id SERIAL PRIMARY KEY,
+    kind VARCHAR(8) NOT NULL CHECK (kind IN ('backup', 'restore')),
+    file_name VARCHAR(255) NOT NULL, -- Within BACKUP_DIR
+    status VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'done', 'failed')),
+    animation_count INTEGER, -- Written or restored, once done
+    skipped_count INTEGER, -- Restores: animations already here by document ID, or unreadable
+    error TEXT,
+    requested_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    job_id INTEGER REFERENCES jobs(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP
+);
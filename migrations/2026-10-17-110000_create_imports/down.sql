@@ -0,0 +1,3 @@
+-- klyja/migrations/2026-10-17-110000_create_imports/down.sql
+DROP TABLE import_entries;
+DROP TABLE imports;
@@ -0,0 +1,23 @@
+-- klyja/migrations/2026-10-17-110000_create_imports/up.sql
+-- Archive imports: a zip of documents, each entry made into an animation by its own
+-- queue job and reported on separately
+CREATE TABLE imports (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX imports_user_id_idx ON imports (user_id);
+
+CREATE TABLE import_entries (
+    id SERIAL PRIMARY KEY,
+    import_id INTEGER NOT NULL REFERENCES imports(id) ON DELETE CASCADE,
+    file_name VARCHAR(255) NOT NULL, -- Path within the zip
+    status VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'done', 'failed')),
+    data BYTEA, -- The entry's file, dropped once it is imported or has failed
+    animation_id INTEGER REFERENCES animations(id) ON DELETE SET NULL,
+    error TEXT,
+    job_id INTEGER REFERENCES jobs(id) ON DELETE SET NULL,
+    finished_at TIMESTAMP
+);
+CREATE INDEX import_entries_import_id_idx ON import_entries (import_id);
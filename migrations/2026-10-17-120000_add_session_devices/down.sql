@@ -0,0 +1,7 @@
+-- klyja/migrations/2026-10-17-120000_add_session_devices/down.sql
+ALTER TABLE sessions
+    DROP COLUMN new_device,
+    DROP COLUMN last_seen_at,
+    DROP COLUMN ip,
+    DROP COLUMN user_agent,
+    DROP COLUMN id;
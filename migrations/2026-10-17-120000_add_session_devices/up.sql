@@ -0,0 +1,11 @@
+-- klyja/migrations/2026-10-17-120000_add_session_devices/up.sql
+-- What device each session is used from, so users can review and end their sessions
+-- and sign-ins from unfamiliar devices stand out
+ALTER TABLE sessions
+    ADD COLUMN id SERIAL UNIQUE, -- Names a session without revealing its token
+    ADD COLUMN user_agent VARCHAR(512),
+    ADD COLUMN ip VARCHAR(64), -- Hashed, truncated or left out per SESSION_IP_STORAGE
+    ADD COLUMN last_seen_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    -- Set when the user had other sessions and none shared this one's IP or user agent
+    ADD COLUMN new_device BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE sessions SET last_seen_at = created_at;
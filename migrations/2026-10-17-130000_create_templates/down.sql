@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-17-130000_create_templates/down.sql
+DROP TABLE templates;
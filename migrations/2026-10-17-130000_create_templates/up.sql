@@ -0,0 +1,11 @@
+-- klyja/migrations/2026-10-17-130000_create_templates/up.sql
+-- Templates: curated animations (a world basemap, a tectonic starter, blank timelines)
+-- that users can copy into their account as a starting point
+CREATE TABLE templates (
+    id SERIAL PRIMARY KEY,
+    animation_id INTEGER NOT NULL UNIQUE REFERENCES animations(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    sort_order INTEGER NOT NULL DEFAULT 0, -- Listed lowest first
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,6 @@
+-- klyja/migrations/2026-10-17-140000_add_animation_size_metrics/down.sql
+ALTER TABLE animations
+    DROP COLUMN keyframe_count,
+    DROP COLUMN point_count,
+    DROP COLUMN feature_count,
+    DROP COLUMN encoded_bytes;
@@ -0,0 +1,13 @@
+-- klyja/migrations/2026-10-17-140000_add_animation_size_metrics/up.sql
+-- Sizes of each animation's document, written whenever it is saved, so listings,
+-- quotas and dashboards can use them without decoding documents
+ALTER TABLE animations
+    ADD COLUMN encoded_bytes INTEGER NOT NULL DEFAULT 0, -- The document as stored
+    ADD COLUMN feature_count INTEGER, -- The counts are NULL until measured
+    ADD COLUMN point_count INTEGER,
+    ADD COLUMN keyframe_count INTEGER;
+
+-- Stored sizes are known already; counts need the document decoded, so they come with
+-- the next save, or from `klyja-admin measure`
+UPDATE animations SET encoded_bytes = octet_length(protobuf_data);
+ALTER TABLE animations ALTER COLUMN encoded_bytes DROP DEFAULT;
@@ -0,0 +1,5 @@
+-- klyja/migrations/2026-10-17-150000_add_geojson_frame_exports/down.sql
+DELETE FROM export_jobs WHERE format = 'geojson';
+ALTER TABLE export_jobs DROP COLUMN frame_step;
+ALTER TABLE export_jobs DROP CONSTRAINT export_jobs_format_check;
+ALTER TABLE export_jobs ADD CONSTRAINT export_jobs_format_check CHECK (format IN ('gif', 'mp4'));
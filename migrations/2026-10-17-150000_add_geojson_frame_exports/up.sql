@@ -0,0 +1,7 @@
+-- klyja/migrations/2026-10-17-150000_add_geojson_frame_exports/up.sql
+-- Exports can also be zips of one GeoJSON file per frame, or per every frame_step-th
+-- frame. Their size and fps are 0, since nothing is drawn.
+ALTER TABLE export_jobs DROP CONSTRAINT export_jobs_format_check;
+ALTER TABLE export_jobs ADD CONSTRAINT export_jobs_format_check
+    CHECK (format IN ('gif', 'mp4', 'geojson'));
+ALTER TABLE export_jobs ADD COLUMN frame_step INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-17-170000_create_restored_animations/down.sql
+DROP TABLE restored_animations;
@@ -0,0 +1,9 @@
+-- klyja/migrations/2026-10-17-170000_create_restored_animations/up.sql
+-- Which backup entries a restore has added, so a retried or repeated restore of the
+-- same zip skips them, with or without a document ID
+CREATE TABLE restored_animations (
+    file_name VARCHAR(255) NOT NULL, -- The backup, within BACKUP_DIR
+    source_id INTEGER NOT NULL, -- The animation's ID in the deployment backed up
+    animation_id INTEGER NOT NULL REFERENCES animations(id) ON DELETE CASCADE,
+    PRIMARY KEY (file_name, source_id)
+);
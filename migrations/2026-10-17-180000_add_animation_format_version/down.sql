@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-17-180000_add_animation_format_version/down.sql
+ALTER TABLE animations DROP COLUMN format_version;
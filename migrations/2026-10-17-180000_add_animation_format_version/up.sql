@@ -0,0 +1,7 @@
+-- klyja/migrations/2026-10-17-180000_add_animation_format_version/up.sql
+-- The geco_core::format version each stored document is written in, so documents
+-- saved before a schema change can be found and rewritten without decoding every one
+ALTER TABLE animations ADD COLUMN format_version INTEGER NOT NULL DEFAULT 0;
+
+-- Every document so far predates recorded versions, which is version 0; saves set it
+ALTER TABLE animations ALTER COLUMN format_version DROP DEFAULT;
@@ -0,0 +1,2 @@
+-- klyja/migrations/2026-10-17-190000_add_animation_embed_generation/down.sql
+ALTER TABLE animations DROP COLUMN embed_generation;
@@ -0,0 +1,3 @@
+-- klyja/migrations/2026-10-17-190000_add_animation_embed_generation/up.sql
+-- Signed into every embed link to the animation; bumping it revokes all of them
+ALTER TABLE animations ADD COLUMN embed_generation INTEGER NOT NULL DEFAULT 0;
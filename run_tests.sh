@@ -11,6 +11,14 @@ echo "Running WASM unit tests..."
 cd ../geco
 cargo test -- --lib
 
+echo "Running animation engine tests..."
+cd ../geco-core
+cargo test
+
+echo "Running document validation tests..."
+cd ../klyja-validate
+cargo test
+
 echo "Running frontend tests with coverage..."
 cd ../frontend
 npm test